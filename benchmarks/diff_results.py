@@ -0,0 +1,130 @@
+"""Per-cell comparison of two benchmark runs.
+
+Each run is a combined results file written by run_all.py, a raw Rust
+results file, or the NDJSON that wave-bench prints. Cells are matched by
+library, format, file and operation; for each, the change in mean time
+and peak memory is shown and flagged where it exceeds the thresholds, with
+a Mann-Whitney U test on the repetition times saying whether a time change
+is more than noise. The biggest movers are summarized at the end.
+
+`python run_all.py diff-results RUN_A RUN_B` runs the comparison.
+"""
+
+import json
+from pathlib import Path
+
+from report import format_time, mann_whitney_p, normalize_results, record_key, SIGNIFICANCE
+from schema import migrate
+
+# How many of the biggest movers the summary lists
+MOVERS = 5
+
+
+def load_run(path):
+    """Normalized records of the run in `path`, tagged with its scale
+    where the file records one."""
+    text = Path(path).read_text()
+    try:
+        doc = json.loads(text)
+    except json.JSONDecodeError:
+        # wave-bench output: one result per line
+        doc = [json.loads(line) for line in text.splitlines() if line.strip().startswith("{")]
+    if isinstance(doc, list):
+        doc = {"rust_results": doc}
+    records = normalize_results(migrate(doc))
+    for r in records:
+        r["scale"] = doc.get("scale", "")
+    return records
+
+
+def change(before, after):
+    """Relative change from `before` to `after`, or None if unknown."""
+    if before and after and before > 0:
+        return after / before - 1
+    return None
+
+
+def format_change(delta):
+    return "N/A" if delta is None else f"{delta * 100:+.1f}%"
+
+
+def diff_runs(a, b, threshold=0.05, memory_threshold=0.05):
+    """Rows comparing each cell that ran ok in both `a` and `b`, and the
+    keys of cells found in only one of them."""
+    before = {record_key(r): r for r in a if r["status"] == "ok"}
+    after = {record_key(r): r for r in b if r["status"] == "ok"}
+    rows = []
+    for key in sorted(before.keys() & after.keys()):
+        old, new = before[key], after[key]
+        time_delta = change(old["mean_s"], new["mean_s"])
+        memory_delta = change(old["memory_kb"], new["memory_kb"])
+        p = mann_whitney_p(old.get("times_s") or [], new.get("times_s") or [])
+        flags = []
+        if time_delta is not None and abs(time_delta) > threshold:
+            flags.append("slower" if time_delta > 0 else "faster")
+            if p is not None and p >= SIGNIFICANCE:
+                flags[-1] += " (n.s.)"
+        if memory_delta is not None and abs(memory_delta) > memory_threshold:
+            flags.append("more memory" if memory_delta > 0 else "less memory")
+        rows.append({
+            "key": key,
+            "before": old,
+            "after": new,
+            "time_delta": time_delta,
+            "memory_delta": memory_delta,
+            "p": p,
+            "flags": flags,
+        })
+    only_a = sorted(before.keys() - after.keys())
+    only_b = sorted(after.keys() - before.keys())
+    return rows, only_a, only_b
+
+
+def format_diff(rows, only_a, only_b, name_a, name_b):
+    """The comparison as Markdown."""
+    lines = [f"# Benchmark diff: `{name_a}` -> `{name_b}`\n"]
+    lines.append("| Library | Format | File | Operation | Time A | Time B | Time | Memory | Flags |")
+    lines.append("|---------|--------|------|-----------|--------|--------|------|--------|-------|")
+    for row in rows:
+        library, fmt, file, test = row["key"]
+        lines.append(
+            f"| {library} | {fmt} | `{file}` | {test} | "
+            f"{format_time(row['before']['mean_s'])} | {format_time(row['after']['mean_s'])} | "
+            f"{format_change(row['time_delta'])} | {format_change(row['memory_delta'])} | "
+            f"{', '.join(row['flags'])} |"
+        )
+    lines.append("")
+    lines.append(f"Changes marked (n.s.) are not significant at p < {SIGNIFICANCE} (Mann-Whitney U")
+    lines.append("on the repetition times), and may be noise.\n")
+
+    for label, keys in ((f"Only in `{name_a}`", only_a), (f"Only in `{name_b}`", only_b)):
+        if keys:
+            lines.append(f"{label}:\n")
+            lines.extend(f"- {lib} {test} `{file}` ({fmt})" for lib, fmt, file, test in keys)
+            lines.append("")
+
+    timed = [r for r in rows if r["time_delta"] is not None]
+    slower = [r for r in rows if any(f.startswith("slower") for f in r["flags"])]
+    faster = [r for r in rows if any(f.startswith("faster") for f in r["flags"])]
+    lines.append("## Summary\n")
+    lines.append(f"- **Cells compared**: {len(rows)}")
+    lines.append(f"- **Slower beyond threshold**: {len(slower)}")
+    lines.append(f"- **Faster beyond threshold**: {len(faster)}")
+    lines.append("")
+    if timed:
+        lines.append("Biggest movers by time:\n")
+        for row in sorted(timed, key=lambda r: abs(r["time_delta"]), reverse=True)[:MOVERS]:
+            library, fmt, file, test = row["key"]
+            p = f", p = {row['p']:.3f}" if row["p"] is not None else ""
+            lines.append(f"- {library} {test} `{file}`: {format_change(row['time_delta'])}{p}")
+        lines.append("")
+    return "\n".join(lines)
+
+
+def diff_files(path_a, path_b, threshold=0.05, memory_threshold=0.05):
+    """Compare the runs in two files, returning the Markdown and whether
+    any cell got slower beyond the threshold."""
+    rows, only_a, only_b = diff_runs(load_run(path_a), load_run(path_b), threshold, memory_threshold)
+    text = format_diff(rows, only_a, only_b, Path(path_a).name, Path(path_b).name)
+    regressed = any(f.startswith("slower") for r in rows for f in r["flags"])
+    return text, regressed
@@ -10,6 +10,7 @@ Usage:
     python generate_testdata.py --scale small
     python generate_testdata.py --scale large
     python generate_testdata.py --data-dir /tmp/bench
+    python generate_testdata.py --size 100M --data-dir data/sweep/100M
 """
 
 import argparse
@@ -39,6 +40,12 @@ SCALE_CONFIG = {
     },
 }
 
+# Signal mix of size-targeted files (--size); only the timestep count varies
+SWEEP_SIGNALS = 200
+SWEEP_CALIBRATION_STEPS = 1000
+
+SIZE_SUFFIXES = {"K": 1024, "M": 1024 ** 2, "G": 1024 ** 3, "T": 1024 ** 4}
+
 # Real-world files to symlink (largest available in wellen inputs)
 WELLEN_INPUTS = Path("/home/sdu/wave_parse/wellen/wellen/inputs")
 REAL_WORLD_FILES = {
@@ -58,6 +65,17 @@ def format_size(size_bytes: int) -> str:
     return f"{size_bytes:.2f} TB"
 
 
+def parse_size(text: str) -> int:
+    """Parse a size such as `100M` or `1G` (binary units) into bytes."""
+    text = text.strip().upper().removesuffix("B")
+    scale = SIZE_SUFFIXES.get(text[-1:], 1)
+    number = text[:-1] if text[-1:] in SIZE_SUFFIXES else text
+    try:
+        return int(float(number) * scale)
+    except ValueError:
+        raise argparse.ArgumentTypeError(f"invalid size: {text!r}")
+
+
 # ---------------------------------------------------------------------------
 # VCD generation (uses pyvcd / VCDWriter)
 # ---------------------------------------------------------------------------
@@ -164,6 +182,54 @@ def generate_fst(path: str, num_signals: int, num_timesteps: int) -> int:
     return Path(path).stat().st_size
 
 
+# ---------------------------------------------------------------------------
+# Size-targeted files
+# ---------------------------------------------------------------------------
+def timesteps_for_size(target_bytes: int, data_dir: Path) -> int:
+    """Timesteps that make a VCD of `SWEEP_SIGNALS` signals about `target_bytes`.
+
+    VCD size grows linearly with the timestep count, so a short calibration
+    file gives the bytes per timestep.
+    """
+    calibration = data_dir / ".calibration.vcd"
+    try:
+        size = generate_vcd(str(calibration), SWEEP_SIGNALS, SWEEP_CALIBRATION_STEPS)
+    finally:
+        calibration.unlink(missing_ok=True)
+    return max(1, round(target_bytes * SWEEP_CALIBRATION_STEPS / size))
+
+
+def generate_sized(label: str, data_dir: Path) -> None:
+    """Generate `sweep_<label>.vcd` and `.fst` with the VCD about `label` in size.
+
+    Both formats hold the same signals and changes, so the FST is smaller by
+    its compression ratio. Existing files are kept.
+    """
+    target = parse_size(label)
+    vcd_path = data_dir / f"sweep_{label}.vcd"
+    fst_path = data_dir / f"sweep_{label}.fst"
+    if vcd_path.exists() and fst_path.exists():
+        print(f"  sweep_{label}: exists, keeping")
+        return
+
+    nt = timesteps_for_size(target, data_dir)
+    print(f"\n--- sweep {label} ({SWEEP_SIGNALS} signals x {nt} steps) ---")
+
+    print(f"  Generating VCD ...", end=" ", flush=True)
+    t0 = time.perf_counter()
+    vcd_size = generate_vcd(str(vcd_path), SWEEP_SIGNALS, nt)
+    print(f"{format_size(vcd_size)} in {time.perf_counter() - t0:.2f}s")
+
+    print(f"  Generating FST ...", end=" ", flush=True)
+    t0 = time.perf_counter()
+    try:
+        fst_size = generate_fst(str(fst_path), SWEEP_SIGNALS, nt)
+        print(f"{format_size(fst_size)} in {time.perf_counter() - t0:.2f}s")
+    except Exception as e:
+        print(f"FAILED: {e}")
+        print("  (pylibfst may not be installed; FST generation skipped)")
+
+
 # ---------------------------------------------------------------------------
 # Symlink real-world files
 # ---------------------------------------------------------------------------
@@ -201,11 +267,26 @@ def main() -> None:
         default=os.path.join(os.path.dirname(os.path.abspath(__file__)), "data"),
         help="Output directory for generated files.",
     )
+    parser.add_argument(
+        "--size",
+        type=str,
+        default=None,
+        help="Generate sweep_<SIZE> files of about SIZE (e.g. 100M, 1G) instead of a scale.",
+    )
     args = parser.parse_args()
 
     data_dir = Path(args.data_dir)
     data_dir.mkdir(parents=True, exist_ok=True)
 
+    if args.size:
+        try:
+            parse_size(args.size)
+        except argparse.ArgumentTypeError as e:
+            parser.error(str(e))
+        generate_sized(args.size, data_dir)
+        print("\nDone. Files written to:", data_dir)
+        return
+
     scales = list(SCALE_CONFIG.keys()) if args.scale == "all" else [args.scale]
 
     print("=" * 60)
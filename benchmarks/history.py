@@ -0,0 +1,235 @@
+"""A history of benchmark runs in SQLite, and trends over it.
+
+run_all.py adds every run to results/history.sqlite, keyed by the git
+commit it benchmarked and the time it ran, with its `--label` pairs in
+`run_labels`. The trends report shows the mean time of each cell over the
+runs of a scale, and finds the most likely change point in each series:
+the split into an earlier and a later stretch of runs whose means differ
+most relative to the noise. A shift past the threshold is reported, so a
+slow drift over many commits shows up even when no two consecutive runs
+differ by much.
+
+`python run_all.py trends [--scale SCALE] [--label KEY=VALUE]` prints the
+report for the runs with those labels.
+
+The history also sets the timeouts of `run_all.py --auto-timeout`: each
+cell gets a multiple of the 99th percentile of its past repetition times.
+"""
+
+import json
+import math
+import sqlite3
+import subprocess
+from pathlib import Path
+
+from report import format_time, normalize_results
+
+SCHEMA = """
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    git_commit TEXT,
+    commit_date TEXT,
+    timestamp TEXT NOT NULL,
+    scale TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS cells (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    library TEXT NOT NULL,
+    format TEXT NOT NULL,
+    file TEXT NOT NULL,
+    operation TEXT NOT NULL,
+    status TEXT NOT NULL,
+    mean_s REAL,
+    stdev_s REAL,
+    memory_kb INTEGER,
+    times_s TEXT
+);
+CREATE INDEX IF NOT EXISTS cells_by_key ON cells (library, format, file, operation);
+CREATE TABLE IF NOT EXISTS run_labels (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    key TEXT NOT NULL,
+    value TEXT NOT NULL
+);
+"""
+
+# Fewest runs on either side of a change point
+MIN_SEGMENT = 2
+
+# Characters of a sparkline, lowest to highest
+SPARK = "_.-=+*#"
+
+# Shortest timeout of a cell, so that a fast cell is not cut off by a
+# moment of load
+MIN_TIMEOUT = 1.0
+
+
+def git_commit(repo):
+    """The commit checked out in `repo` and its date, or Nones outside git."""
+    try:
+        out = subprocess.run(
+            ["git", "log", "-1", "--format=%H %cI"],
+            cwd=repo, capture_output=True, text=True, check=True,
+        ).stdout.split()
+    except (OSError, subprocess.CalledProcessError):
+        return None, None
+    return (out[0], out[1]) if len(out) == 2 else (None, None)
+
+
+def connect(db_path):
+    db = sqlite3.connect(db_path)
+    db.executescript(SCHEMA)
+    return db
+
+
+def record_run(db_path, combined, repo):
+    """Add the run in `combined` to the history at `db_path`."""
+    commit, commit_date = git_commit(repo)
+    with connect(db_path) as db:
+        run_id = db.execute(
+            "INSERT INTO runs (git_commit, commit_date, timestamp, scale) VALUES (?, ?, ?, ?)",
+            (commit, commit_date, combined["timestamp"], combined["scale"]),
+        ).lastrowid
+        db.executemany(
+            "INSERT INTO cells VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            [
+                (run_id, r["library"], r["format"], r["file"], r["test"], r["status"],
+                 r["mean_s"], r["stdev_s"], r["memory_kb"], json.dumps(r.get("times_s") or []))
+                for r in normalize_results(combined)
+            ],
+        )
+        db.executemany(
+            "INSERT INTO run_labels VALUES (?, ?, ?)",
+            [(run_id, k, v) for k, v in (combined.get("labels") or {}).items()],
+        )
+    db.close()
+
+
+def change_point(values, threshold):
+    """The most likely change point of `values`, as (index, relative shift),
+    if the shift exceeds `threshold`; the run at `index` starts the later
+    stretch."""
+    n = len(values)
+    if n < 2 * MIN_SEGMENT:
+        return None
+    mean = sum(values) / n
+    sd = math.sqrt(sum((v - mean) ** 2 for v in values) / (n - 1))
+    best = None
+    for k in range(MIN_SEGMENT, n - MIN_SEGMENT + 1):
+        before = sum(values[:k]) / k
+        after = sum(values[k:]) / (n - k)
+        # Shift scaled by the noise and by how much data backs each side
+        score = abs(after - before) * math.sqrt(k * (n - k) / n) / sd if sd > 0 else 0
+        if before > 0 and (best is None or score > best[0]):
+            best = (score, k, after / before - 1)
+    if best is None or abs(best[2]) <= threshold:
+        return None
+    return best[1], best[2]
+
+
+def sparkline(values):
+    lo, hi = min(values), max(values)
+    if hi == lo:
+        return SPARK[len(SPARK) // 2] * len(values)
+    return "".join(SPARK[int((v - lo) / (hi - lo) * (len(SPARK) - 1))] for v in values)
+
+
+def percentile(values, q):
+    """The `q` quantile of `values` by nearest rank."""
+    values = sorted(values)
+    return values[max(0, math.ceil(q * len(values)) - 1)]
+
+
+def timeouts(db_path, scale, factor=3.0, last=20):
+    """{(library, operation, file): seconds} for the cells that ran ok in
+    the last `last` runs of `scale`: `factor` times the 99th percentile of
+    their repetition times, and at least MIN_TIMEOUT."""
+    if not Path(db_path).exists():
+        return {}
+    db = connect(db_path)
+    runs = [
+        run_id for (run_id,) in db.execute(
+            "SELECT id FROM runs WHERE scale = ? ORDER BY timestamp DESC, id DESC LIMIT ?",
+            (scale, last),
+        )
+    ]
+    times = {}
+    for library, file, operation, times_s in db.execute(
+        "SELECT library, file, operation, times_s FROM cells "
+        f"WHERE status = 'ok' AND run_id IN ({','.join('?' * len(runs))})",
+        runs,
+    ):
+        times.setdefault((library, operation, file), []).extend(json.loads(times_s or "[]"))
+    db.close()
+    return {
+        key: max(MIN_TIMEOUT, factor * percentile(t, 0.99))
+        for key, t in times.items()
+        if t
+    }
+
+
+def trends(db_path, scale, threshold=0.05, last=20, labels=None):
+    """Markdown report of the time series of every cell over the last
+    `last` runs of `scale` that have all of `labels` ({key: value})."""
+    if not Path(db_path).exists():
+        return f"No history at {db_path}; run benchmarks first.\n"
+    db = connect(db_path)
+    labelled = "".join(
+        " AND EXISTS (SELECT 1 FROM run_labels l WHERE l.run_id = runs.id AND l.key = ? AND l.value = ?)"
+        for _ in labels or {}
+    )
+    params = [scale] + [x for pair in (labels or {}).items() for x in pair] + [last]
+    runs = db.execute(
+        f"SELECT id, git_commit, timestamp FROM runs WHERE scale = ?{labelled} "
+        "ORDER BY timestamp DESC, id DESC LIMIT ?",
+        params,
+    ).fetchall()[::-1]
+    if not runs:
+        db.close()
+        return f"No runs of scale {scale} in {db_path}.\n"
+    runs_by_id = {run_id: i for i, (run_id, _, _) in enumerate(runs)}
+    series = {}
+    for run_id, library, fmt, file, operation, mean_s in db.execute(
+        "SELECT run_id, library, format, file, operation, mean_s FROM cells "
+        f"WHERE status = 'ok' AND mean_s > 0 AND run_id IN ({','.join('?' * len(runs))})",
+        list(runs_by_id),
+    ):
+        series.setdefault((library, fmt, file, operation), []).append((runs_by_id[run_id], mean_s))
+    db.close()
+
+    lines = [f"# Benchmark Trends ({scale})\n"]
+    if labels:
+        lines.append(f"- **Labels**: {', '.join(f'{k}={v}' for k, v in labels.items())}")
+    lines.append(f"- **Runs**: {len(runs)}")
+    first, latest = runs[0], runs[-1]
+    lines.append(f"- **From**: {first[2]} ({(first[1] or 'unknown')[:10]})")
+    lines.append(f"- **To**: {latest[2]} ({(latest[1] or 'unknown')[:10]})")
+    lines.append("")
+    lines.append("| Library | File | Operation | Trend | First | Latest | Change Point |")
+    lines.append("|---------|------|-----------|-------|-------|--------|--------------|")
+    alerts = []
+    for key, points in sorted(series.items()):
+        library, fmt, file, operation = key
+        points.sort()
+        values = [v for _, v in points]
+        cp = change_point(values, threshold)
+        cp_str = ""
+        if cp is not None:
+            index, shift = cp
+            commit = runs[points[index][0]][1]
+            cp_str = f"{shift * 100:+.1f}% at {(commit or 'unknown')[:10]}"
+            if shift > 0:
+                alerts.append(f"- {library} {operation} `{file}`: {cp_str}")
+        lines.append(
+            f"| {library} | `{file}` | {operation} | `{sparkline(values)}` | "
+            f"{format_time(values[0])} | {format_time(values[-1])} | {cp_str} |"
+        )
+    lines.append("")
+    lines.append("## Alerts\n")
+    if alerts:
+        lines.append(f"Cells whose time shifted up by more than {threshold * 100:.0f}%, and the")
+        lines.append("commit of the first run after the shift:\n")
+        lines.extend(alerts)
+    else:
+        lines.append("No slowdowns.")
+    lines.append("")
+    return "\n".join(lines)
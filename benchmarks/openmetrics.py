@@ -0,0 +1,81 @@
+"""Export of benchmark results as OpenMetrics, for Prometheus and Grafana.
+
+Every cell becomes a set of gauges labelled with library, format, file,
+operation and scale: mean time, its standard deviation, peak memory, and
+whether the cell ran ok. The text can be written for a scrape target to
+serve, or pushed to a Prometheus Pushgateway, which takes the classic
+Prometheus text format, so pushes leave out the OpenMetrics-only lines.
+
+`python run_all.py export-metrics RESULTS [--pushgateway URL]` exports a
+results file.
+"""
+
+import urllib.parse
+import urllib.request
+
+from diff_results import load_run
+
+PREFIX = "wave_bench"
+
+LABELS = ("library", "format", "file", "operation", "scale")
+
+# (name, unit, help, value of a record or None to skip it)
+METRICS = [
+    ("time_seconds", "seconds", "Mean wall time per repetition",
+     lambda r: r["mean_s"] if r["status"] == "ok" else None),
+    ("time_stdev_seconds", "seconds", "Standard deviation of the wall time per repetition",
+     lambda r: r["stdev_s"] if r["status"] == "ok" else None),
+    ("peak_memory_bytes", "bytes", "Peak resident set size",
+     lambda r: r["memory_kb"] * 1024 if r["status"] == "ok" and r["memory_kb"] > 0 else None),
+    ("ok", "", "1 if the cell ran without error or timeout, else 0; skipped cells have none",
+     lambda r: None if r["status"] == "skipped" else 1 if r["status"] == "ok" else 0),
+]
+
+
+def escape(value):
+    return str(value).replace("\\", "\\\\").replace('"', '\\"').replace("\n", "\\n")
+
+
+def exposition(records, scale, openmetrics=True):
+    """The records as exposition text: OpenMetrics, or with `openmetrics`
+    false, the Prometheus text format."""
+    lines = []
+    for name, unit, help_text, value in METRICS:
+        metric = f"{PREFIX}_{name}"
+        lines.append(f"# TYPE {metric} gauge")
+        if unit and openmetrics:
+            lines.append(f"# UNIT {metric} {unit}")
+        lines.append(f"# HELP {metric} {help_text}.")
+        for r in records:
+            v = value(r)
+            if v is None:
+                continue
+            labels = dict(zip(LABELS, (r["library"], r["format"], r["file"], r["test"], scale)))
+            label_text = ",".join(f'{k}="{escape(text)}"' for k, text in labels.items())
+            lines.append(f"{metric}{{{label_text}}} {v}")
+    if openmetrics:
+        lines.append("# EOF")
+    return "\n".join(lines) + "\n"
+
+
+def push(text, gateway, job=PREFIX):
+    """Replace the metrics of `job` on the Pushgateway at `gateway`."""
+    url = f"{gateway.rstrip('/')}/metrics/job/{urllib.parse.quote(job, safe='')}"
+    request = urllib.request.Request(
+        url,
+        data=text.encode(),
+        method="PUT",
+        headers={"Content-Type": "text/plain; version=0.0.4"},
+    )
+    with urllib.request.urlopen(request, timeout=30) as response:
+        response.read()
+
+
+def export_file(path, gateway=None):
+    """Exposition text of the results in `path`, pushed to `gateway` if
+    given."""
+    records = load_run(path)
+    scale = next((r["scale"] for r in records if r["scale"]), "unknown")
+    if gateway:
+        push(exposition(records, scale, openmetrics=False), gateway)
+    return exposition(records, scale)
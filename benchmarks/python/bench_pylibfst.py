@@ -14,6 +14,8 @@ import time
 
 SCALE_TIMEOUTS = {"small": 60, "medium": 120, "large": 300}
 REPETITIONS = 3
+# Version of the result schema; see benchmarks/schema.py
+SCHEMA_VERSION = 1
 
 
 class TimeoutError(Exception):
@@ -204,6 +206,7 @@ def run_benchmark(data_dir, scale, output_path):
 
     if not fst_files:
         print(json.dumps({
+            "schema_version": SCHEMA_VERSION,
             "library": "pylibfst",
             "format": "fst",
             "results": [{
@@ -272,6 +275,7 @@ def run_benchmark(data_dir, scale, output_path):
             results.append(result)
 
     output = {
+        "schema_version": SCHEMA_VERSION,
         "library": "pylibfst",
         "format": "fst",
         "results": results,
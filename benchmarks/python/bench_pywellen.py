@@ -14,6 +14,8 @@ import time
 
 SCALE_TIMEOUTS = {"small": 60, "medium": 120, "large": 300}
 REPETITIONS = 3
+# Version of the result schema; see benchmarks/schema.py
+SCHEMA_VERSION = 1
 
 
 class TimeoutError(Exception):
@@ -313,6 +315,7 @@ def run_benchmark(data_dir, scale, output_path):
         import pywellen  # noqa: F401
     except ImportError as e:
         output = {
+            "schema_version": SCHEMA_VERSION,
             "library": "pywellen",
             "format": "vcd+fst",
             "results": [{
@@ -384,6 +387,7 @@ def run_benchmark(data_dir, scale, output_path):
         )
 
     output = {
+        "schema_version": SCHEMA_VERSION,
         "library": "pywellen",
         "format": "vcd+fst",
         "results": all_results,
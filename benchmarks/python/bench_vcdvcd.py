@@ -14,6 +14,8 @@ import time
 
 SCALE_TIMEOUTS = {"small": 60, "medium": 120, "large": 300}
 REPETITIONS = 3
+# Version of the result schema; see benchmarks/schema.py
+SCHEMA_VERSION = 1
 
 
 class TimeoutError(Exception):
@@ -161,6 +163,7 @@ def run_benchmark(data_dir, scale, output_path):
 
     if not vcd_files:
         print(json.dumps({
+            "schema_version": SCHEMA_VERSION,
             "library": "vcdvcd",
             "format": "vcd",
             "results": [{
@@ -229,6 +232,7 @@ def run_benchmark(data_dir, scale, output_path):
             results.append(result)
 
     output = {
+        "schema_version": SCHEMA_VERSION,
         "library": "vcdvcd",
         "format": "vcd",
         "results": results,
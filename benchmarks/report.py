@@ -4,17 +4,32 @@
 Reads JSON results from the results/ directory and produces a comprehensive
 Markdown report with tables and ASCII bar charts.
 
+With --junit, also writes a JUnit XML file in which every (library, file,
+operation) is a test case, failing if it errored, timed out or, given
+--baseline, regressed.
+
+--library, --format, --operation and --file narrow the report to matching
+records, and --aggregate adds the geometric mean over files of each library
+and operation.
+
 Usage:
     python report.py --results-dir results/ --scale small
+    python report.py --scale small --junit results/junit.xml --baseline old/combined_small.json
+    python report.py --scale all --format fst --file '*deep*' --aggregate
 """
 
 import argparse
+import fnmatch
 import json
+import math
 import os
 import sys
 import time
+import xml.etree.ElementTree as ET
 from pathlib import Path
 
+from schema import migrate
+
 
 def log(msg):
     print(f"[report] {msg}", file=sys.stderr, flush=True)
@@ -27,7 +42,7 @@ def load_combined(results_dir, scale):
         log(f"Combined results not found: {path}")
         return None
     with open(path) as f:
-        return json.load(f)
+        return migrate(json.load(f))
 
 
 def normalize_results(combined):
@@ -55,6 +70,7 @@ def normalize_results(combined):
                 "error": r.get("error", ""),
                 "file_size_bytes": r.get("file_size_bytes", 0),
                 "times_s": r.get("times_s", []),
+                "labels": lib_data.get("labels") or {},
             })
 
     # Rust results: list of {library, format, file, operation, mean, ...}
@@ -63,15 +79,23 @@ def normalize_results(combined):
         if isinstance(r, dict) and "operation" in r:
             file_path = r.get("file", "")
             file_name = os.path.basename(file_path) if file_path else ""
-            # Estimate file size from path
-            file_size = 0
-            if file_path and os.path.exists(file_path):
+            # File size as the harness recorded it, or from the path
+            meta = r.get("input") or {}
+            file_size = meta.get("size_bytes") or r.get("file_size_bytes", 0)
+            if not file_size and file_path and os.path.exists(file_path):
                 try:
                     file_size = os.path.getsize(file_path)
                 except OSError:
                     pass
+            # Rows of a backend built with another allocator are labelled
+            # with it, so that tables keep them apart
+            backend = r.get("library", "unknown")
+            allocator = r.get("allocator", "system")
+            library = backend if allocator == "system" else f"{backend} [{allocator}]"
             records.append({
-                "library": r.get("library", "unknown"),
+                "library": library,
+                "backend": backend,
+                "allocator": allocator,
                 "language": "Rust",
                 "format": r.get("format", "unknown"),
                 "file": file_name,
@@ -83,6 +107,13 @@ def normalize_results(combined):
                 "error": r.get("error", ""),
                 "file_size_bytes": file_size,
                 "times_s": r.get("times", []),
+                "cpu_s": mean_cpu(r),
+                "first_event_s": (r.get("first_event") or {}).get("mean", 0),
+                "energy_j": r.get("energy_j") or 0,
+                "power_w": r.get("power_w") or 0,
+                "input_changes": meta.get("value_changes") or 0,
+                "tainted": bool(r.get("tainted")),
+                "labels": r.get("labels") or {},
             })
         elif isinstance(r, dict) and "results" in r:
             # Wrapped format (from error cases)
@@ -101,11 +132,87 @@ def normalize_results(combined):
                     "error": sub.get("error", ""),
                     "file_size_bytes": sub.get("file_size_bytes", 0),
                     "times_s": sub.get("times_s", []),
+                    "labels": r.get("labels") or {},
                 })
 
     return records
 
 
+def mean_cpu(r):
+    """Mean user+system CPU time per repetition of a Rust result, or 0."""
+    user = r.get("user_times") or []
+    system = r.get("sys_times") or []
+    if not user or len(user) != len(system):
+        return 0
+    return sum(u + s for u, s in zip(user, system)) / len(user)
+
+
+def find_input(name, data_dir):
+    """Path of the input file `name` under `data_dir`, or None."""
+    if not name or not data_dir or not Path(data_dir).is_dir():
+        return None
+    return next(Path(data_dir).rglob(name), None)
+
+
+def count_vcd_changes(path):
+    """Number of value changes in a VCD file, initial values included."""
+    count = 0
+    in_body = False
+    with open(path, "rb") as f:
+        for line in f:
+            line = line.strip()
+            if not in_body:
+                in_body = line.startswith(b"$enddefinitions")
+                continue
+            if line and line[:1] not in (b"#", b"$"):
+                count += 1
+    return count
+
+
+def value_changes(name, data_dir, cache):
+    """Number of value changes in input `name`, or None if unknown.
+
+    FST files are counted through the VCD of the same name next to them,
+    which the generator writes with the same changes. Counts are cached in
+    `cache`, keyed by path, size and modification time.
+    """
+    path = find_input(name, data_dir)
+    if path is None:
+        return None
+    if path.suffix == ".fst":
+        path = path.with_suffix(".vcd")
+        if not path.exists():
+            return None
+    stat = path.stat()
+    key = f"{path.resolve()}:{stat.st_size}:{int(stat.st_mtime)}"
+    if key not in cache:
+        log(f"Counting value changes in {path}")
+        cache[key] = count_vcd_changes(path)
+    return cache[key]
+
+
+def add_normalized_metrics(records, data_dir, cache_path):
+    """Add `memory_per_byte` and `ns_per_change` to each record, where known.
+
+    Peak memory over file size shows how much a library blows an input up;
+    time per value change makes files of different sizes comparable. Rust
+    rows carry the value changes of their input; others are counted here.
+    """
+    cache = {}
+    if cache_path.exists():
+        with open(cache_path) as f:
+            cache = json.load(f)
+    for r in records:
+        size = r.get("file_size_bytes", 0)
+        r["memory_per_byte"] = r["memory_kb"] * 1024 / size if size > 0 and r["memory_kb"] > 0 else 0
+        changes = r.get("input_changes") or value_changes(r["file"], data_dir, cache)
+        r["value_changes"] = changes or 0
+        r["ns_per_change"] = r["mean_s"] * 1e9 / changes if changes and r["mean_s"] > 0 else 0
+    cache_path.parent.mkdir(parents=True, exist_ok=True)
+    with open(cache_path, "w") as f:
+        json.dump(cache, f, indent=2)
+
+
 def format_time(seconds):
     """Format time to human-readable string."""
     if seconds <= 0:
@@ -144,17 +251,131 @@ def ascii_bar(value, max_value, width=30):
     return "#" * bar_len
 
 
-def generate_report(records, scale, output_path):
-    """Generate a Markdown report from normalized records."""
+def speedup_matrix(libs, speedup):
+    """Markdown table of `speedup(row, col)`, how many times faster library
+    `row` is than library `col`, formatted, or None if unknown."""
+    lines = ["| Faster than -> | " + " | ".join(libs) + " |"]
+    lines.append("|----------------|" + "|".join("-" * (len(lib) + 2) for lib in libs) + "|")
+    for row in libs:
+        cells = []
+        for col in libs:
+            cell = "-" if row == col else speedup(row, col)
+            cells.append(cell or "N/A")
+        lines.append(f"| {row} | " + " | ".join(cells) + " |")
+    return lines
+
+
+# p-value below which a difference in repetition times is called significant
+SIGNIFICANCE = 0.05
+
+
+def mann_whitney_p(a, b):
+    """Two-sided p-value of the Mann-Whitney U test that samples `a` and
+    `b` come from the same distribution, or None if either is empty.
+
+    Small samples without ties use the exact distribution of U; otherwise
+    the normal approximation with tie and continuity corrections. With
+    three repetitions each, p is never below 0.1.
+    """
+    n1, n2 = len(a), len(b)
+    if n1 == 0 or n2 == 0:
+        return None
+    pooled = sorted([(v, 0) for v in a] + [(v, 1) for v in b])
+    ranks = [0.0] * len(pooled)
+    ties = []
+    i = 0
+    while i < len(pooled):
+        j = i
+        while j + 1 < len(pooled) and pooled[j + 1][0] == pooled[i][0]:
+            j += 1
+        for k in range(i, j + 1):
+            ranks[k] = (i + j) / 2 + 1
+        ties.append(j - i + 1)
+        i = j + 1
+    r1 = sum(rank for rank, (_, group) in zip(ranks, pooled) if group == 0)
+    u = r1 - n1 * (n1 + 1) / 2
+    u = min(u, n1 * n2 - u)
+
+    if n1 + n2 <= 20 and all(t == 1 for t in ties):
+        # Memo of orderings(n, m, k), the orderings of n and m values with U = k
+        counts = {}
+
+        def orderings(n, m, k):
+            if k < 0:
+                return 0
+            if n == 0 or m == 0:
+                return 1 if k == 0 else 0
+            key = (n, m, k)
+            if key not in counts:
+                counts[key] = orderings(n - 1, m, k - m) + orderings(n, m - 1, k)
+            return counts[key]
+
+        total = math.comb(n1 + n2, n1)
+        tail = sum(orderings(n1, n2, k) for k in range(int(u) + 1)) / total
+        return min(1.0, 2 * tail)
+
+    n = n1 + n2
+    tie_term = sum(t ** 3 - t for t in ties) / (n * (n - 1))
+    sigma = math.sqrt(n1 * n2 / 12 * ((n + 1) - tie_term))
+    if sigma == 0:
+        return 1.0
+    z = (n1 * n2 / 2 - u - 0.5) / sigma
+    return min(1.0, math.erfc(max(z, 0) / math.sqrt(2)))
+
+
+def geomean(values):
+    """Geometric mean of positive `values`."""
+    return math.exp(sum(math.log(v) for v in values) / len(values))
+
+
+def filter_records(records, libraries=(), formats=(), operations=(), files=()):
+    """The records matching every non-empty criterion: any of its libraries
+    (with or without an allocator suffix), formats, operations, or file
+    name glob patterns."""
+    def keep(r):
+        if libraries and r["library"] not in libraries and r.get("backend") not in libraries:
+            return False
+        if formats and not set(formats) & set(r["format"].split("+")):
+            return False
+        if operations and r["test"] not in operations:
+            return False
+        if files and not any(fnmatch.fnmatch(r["file"], pattern) for pattern in files):
+            return False
+        return True
+
+    return [r for r in records if keep(r)]
+
+
+def aggregate(records):
+    """{(library, operation): (geometric mean time, files)} over the timed
+    ok records."""
+    times = {}
+    for r in records:
+        if r["status"] == "ok" and r["mean_s"] > 0:
+            times.setdefault((r["library"], r["test"]), []).append(r["mean_s"])
+    return {key: (geomean(t), len(t)) for key, t in times.items()}
+
+
+def generate_report(records, scale, output_path, filters=(), aggregated=False):
+    """Generate a Markdown report from normalized records.
+
+    `filters` describes how the records were selected, and `aggregated`
+    adds the geometric means over files."""
     lines = []
     lines.append("# VCD/FST Library Benchmark Report\n")
     lines.append(f"- **Scale**: {scale}")
     lines.append(f"- **Date**: {time.strftime('%Y-%m-%d %H:%M:%S')}")
+    if filters:
+        lines.append(f"- **Filters**: {', '.join(filters)}")
     lines.append(f"- **Total benchmarks**: {len(records)}")
 
     ok_records = [r for r in records if r["status"] == "ok"]
-    err_records = [r for r in records if r["status"] != "ok"]
-    lines.append(f"- **Passed**: {len(ok_records)}, **Failed/Skipped**: {len(err_records)}")
+    skipped_records = [r for r in records if r["status"] == "skipped"]
+    err_records = [r for r in records if r["status"] not in ("ok", "skipped")]
+    lines.append(
+        f"- **Passed**: {len(ok_records)}, **Failed**: {len(err_records)}, "
+        f"**Skipped**: {len(skipped_records)}"
+    )
     lines.append("")
 
     # ----- Section 1: Full Parse Comparison -----
@@ -170,8 +391,8 @@ def generate_report(records, scale, output_path):
                 continue
             file_size = max(r["file_size_bytes"] for r in file_records)
             lines.append(f"### File: `{f}` ({format_size(file_size)})\n")
-            lines.append("| Library | Language | Format | Time | Throughput | Memory |")
-            lines.append("|---------|----------|--------|------|------------|--------|")
+            lines.append("| Library | Language | Format | Time | First Event | CPU (user+sys) | Throughput | Memory |")
+            lines.append("|---------|----------|--------|------|-------------|----------------|------------|--------|")
 
             sorted_recs = sorted(file_records, key=lambda r: r["mean_s"] if r["mean_s"] > 0 else 9999)
             for r in sorted_recs:
@@ -180,7 +401,8 @@ def generate_report(records, scale, output_path):
                 mem_str = f"{r['memory_kb']}KB" if r["memory_kb"] > 0 else "N/A"
                 lines.append(
                     f"| {r['library']} | {r['language']} | {r['format']} | "
-                    f"{format_time(r['mean_s'])} | {tp_str} | {mem_str} |"
+                    f"{format_time(r['mean_s'])} | {format_time(r.get('first_event_s', 0))} | "
+                    f"{format_time(r.get('cpu_s', 0))} | {tp_str} | {mem_str} |"
                 )
             lines.append("")
 
@@ -323,9 +545,112 @@ def generate_report(records, scale, output_path):
                     lines.append(f"- {lib}: {ratio:.1f}x slower")
             lines.append("")
 
-    # ----- Section 6: Errors and Failures -----
+    # ----- Section 6: Normalized Metrics -----
+    lines.append("## 6. Normalized Full Parse Metrics\n")
+    norm_records = [
+        r for r in parse_records
+        if r.get("memory_per_byte", 0) > 0 or r.get("ns_per_change", 0) > 0
+    ]
+    if norm_records:
+        lines.append("Memory per input byte shows how far a library blows a file up; time per")
+        lines.append("value change compares files of different sizes.\n")
+        lines.append("| Library | Language | File | Memory / Byte | Time / Change | Value Changes |")
+        lines.append("|---------|----------|------|---------------|---------------|---------------|")
+        for r in sorted(norm_records, key=lambda r: (r["file"], r["ns_per_change"] or 9e99)):
+            mpb = f"{r['memory_per_byte']:.2f}x" if r["memory_per_byte"] > 0 else "N/A"
+            npc = f"{r['ns_per_change']:.1f}ns" if r["ns_per_change"] > 0 else "N/A"
+            changes = f"{r['value_changes']:,}" if r["value_changes"] > 0 else "N/A"
+            lines.append(
+                f"| {r['library']} | {r['language']} | `{r['file']}` | {mpb} | {npc} | {changes} |"
+            )
+        lines.append("")
+    else:
+        lines.append("No file sizes or value counts available.\n")
+
+    # ----- Section 7: Allocator Comparison -----
+    alloc_records = [r for r in parse_records if r["language"] == "Rust" and r["mean_s"] > 0]
+    allocators = sorted(set(r["allocator"] for r in alloc_records), key=lambda a: (a != "system", a))
+    if len(allocators) > 1:
+        lines.append("## 7. Allocator Comparison (Full Parse)\n")
+        lines.append("Full parse time of each Rust backend per global allocator, and the change")
+        lines.append("relative to the system allocator.\n")
+        lines.append("| Backend | File | " + " | ".join(allocators) + " |")
+        lines.append("|---------|------|" + "|".join("-" * (len(a) + 2) for a in allocators) + "|")
+        times = {(r["backend"], r["file"], r["allocator"]): r["mean_s"] for r in alloc_records}
+        for backend, f in sorted(set((r["backend"], r["file"]) for r in alloc_records)):
+            base = times.get((backend, f, "system"))
+            cells = []
+            for a in allocators:
+                t = times.get((backend, f, a))
+                if t is None:
+                    cells.append("N/A")
+                elif a != "system" and base:
+                    cells.append(f"{format_time(t)} ({(t / base - 1) * 100:+.1f}%)")
+                else:
+                    cells.append(format_time(t))
+            lines.append(f"| {backend} | `{f}` | " + " | ".join(cells) + " |")
+        lines.append("")
+
+    # ----- Section 8: Energy -----
+    energy_records = [r for r in parse_records if r.get("energy_j", 0) > 0]
+    if energy_records:
+        lines.append("## 8. Energy (Full Parse)\n")
+        lines.append("CPU package energy per parse from RAPL; the fastest library is not always")
+        lines.append("the one using the least energy.\n")
+        lines.append("| Library | File | Time | Energy | Power |")
+        lines.append("|---------|------|------|--------|-------|")
+        for r in sorted(energy_records, key=lambda r: (r["file"], r["energy_j"])):
+            lines.append(
+                f"| {r['library']} | `{r['file']}` | {format_time(r['mean_s'])} | "
+                f"{r['energy_j']:.3f}J | {r['power_w']:.1f}W |"
+            )
+        lines.append("")
+
+    # ----- Section 9: Speedup Matrix -----
+    timed_records = [r for r in ok_records if r["mean_s"] > 0]
+    operations = sorted(set(r["test"] for r in timed_records))
+    if timed_records:
+        lines.append("## 9. Speedup Matrix\n")
+        lines.append("How many times faster the library of each row is than the library of each")
+        lines.append("column, per file, and the geometric mean over the files both libraries ran.")
+        lines.append(f"Per file, `*` marks a difference significant at p < {SIGNIFICANCE} (Mann-Whitney U")
+        lines.append("on the repetition times); unmarked differences may be noise.\n")
+    for op in operations:
+        op_records = [r for r in timed_records if r["test"] == op]
+        by_file = {}
+        reps = {}
+        for r in op_records:
+            by_file.setdefault(r["file"], {})[r["library"]] = r["mean_s"]
+            reps[(r["file"], r["library"])] = r.get("times_s") or []
+        by_file = {f: t for f, t in by_file.items() if len(t) > 1}
+        if not by_file:
+            continue
+        lines.append(f"### {op}\n")
+        for f, times in sorted(by_file.items()):
+            def speedup(row, col):
+                p = mann_whitney_p(reps[(f, row)], reps[(f, col)])
+                mark = "*" if p is not None and p < SIGNIFICANCE else ""
+                return f"{times[col] / times[row]:.2f}x{mark}"
+
+            libs = sorted(times, key=times.get)
+            lines.append(f"`{f}`:\n")
+            lines.extend(speedup_matrix(libs, speedup))
+            lines.append("")
+        if len(by_file) > 1:
+            def overall(row, col):
+                ratios = [t[col] / t[row] for t in by_file.values() if row in t and col in t]
+                return f"{geomean(ratios):.2f}x ({len(ratios)})" if ratios else None
+
+            libs = sorted(set(lib for t in by_file.values() for lib in t))
+            lines.append("Geometric mean over files (files compared):\n")
+            lines.extend(speedup_matrix(libs, overall))
+            lines.append("")
+
+    # ----- Section 10: Errors and Failures -----
+    tainted_records = [r for r in ok_records if r.get("tainted")]
+    if err_records or skipped_records or tainted_records:
+        lines.append("## 10. Errors and Failures\n")
     if err_records:
-        lines.append("## 6. Errors and Failures\n")
         lines.append("| Library | Test | Status | Error |")
         lines.append("|---------|------|--------|-------|")
         for r in err_records:
@@ -334,9 +659,44 @@ def generate_report(records, scale, output_path):
                 f"| {r['library']} | {r['test']} | {r['status']} | {err_msg} |"
             )
         lines.append("")
+    if skipped_records:
+        reasons = {}
+        for r in skipped_records:
+            reasons.setdefault(r.get("error") or "no reason given", set()).add(r["library"])
+        lines.append("Skipped cells, which could not run:\n")
+        for reason, libs in sorted(reasons.items()):
+            lines.append(f"- {reason}: {', '.join(sorted(libs))}")
+        lines.append("")
+    if tainted_records:
+        lines.append("Cells measured after a timeout, while its stuck repetition may still")
+        lines.append("have been running in the same process:\n")
+        for r in tainted_records:
+            lines.append(f"- {r['library']} {r['test']} `{r['file']}`")
+        lines.append("")
+
+    # ----- Section 11: Aggregate over Files -----
+    means = aggregate(records) if aggregated else {}
+    if means:
+        lines.append("## 11. Aggregate over Files\n")
+        lines.append("Geometric mean time of each library and operation over the files it ran")
+        lines.append("(files), comparable between libraries that ran the same files.\n")
+        agg_ops = sorted(set(op for _, op in means))
+        agg_libs = sorted(set(lib for lib, _ in means))
+        lines.append("| Library | " + " | ".join(agg_ops) + " |")
+        lines.append("|---------|" + "|".join("-" * (len(op) + 2) for op in agg_ops) + "|")
+        for lib in agg_libs:
+            cells = []
+            for op in agg_ops:
+                if (lib, op) in means:
+                    mean, n = means[(lib, op)]
+                    cells.append(f"{format_time(mean)} ({n})")
+                else:
+                    cells.append("")
+            lines.append(f"| {lib} | " + " | ".join(cells) + " |")
+        lines.append("")
 
-    # ----- Section 7: Summary -----
-    lines.append("## 7. Summary\n")
+    # ----- Section 12: Summary -----
+    lines.append("## 12. Summary\n")
 
     vcd_libs = set()
     fst_libs = set()
@@ -369,6 +729,76 @@ def generate_report(records, scale, output_path):
     return str(output)
 
 
+def record_key(r):
+    """The identity of a benchmark cell, across runs."""
+    return (r["library"], r["format"], r["file"], r["test"])
+
+
+def write_junit(records, output_path, baseline=None, threshold=0.10):
+    """Write `records` as JUnit XML, one test case per record.
+
+    A case fails if it errored or timed out, or if it is more than
+    `threshold` (relative) slower than the record with the same key in
+    `baseline`; a skipped record is a skipped case.
+    Cases are grouped into one test suite per scale.
+    """
+    baseline_records = {
+        record_key(r): r
+        for r in baseline or []
+        if r["status"] == "ok" and r["mean_s"] > 0
+    }
+    suites = {}
+    for r in records:
+        suites.setdefault(r.get("scale", ""), []).append(r)
+
+    root = ET.Element("testsuites", name="benchmarks")
+    for scale, cases in suites.items():
+        suite = ET.SubElement(root, "testsuite", name=f"benchmarks.{scale}" if scale else "benchmarks")
+        failures = 0
+        skipped = 0
+        for r in cases:
+            case = ET.SubElement(
+                suite,
+                "testcase",
+                classname=r["library"],
+                name=f"{r['test']} [{r['file']}]" if r["file"] else r["test"],
+                time=f"{sum(r.get('times_s') or []):.6f}",
+            )
+            message = None
+            if r["status"] == "skipped":
+                skipped += 1
+                ET.SubElement(case, "skipped", message=r.get("error") or "skipped")
+            elif r["status"] != "ok":
+                kind = "timeout" if "timeout" in (r["status"], r.get("error")) else "error"
+                message = r.get("error") or r["status"]
+            else:
+                old = baseline_records.get(record_key(r))
+                if old and r["mean_s"] > old["mean_s"] * (1 + threshold):
+                    kind = "regression"
+                    message = (
+                        f"mean {format_time(r['mean_s'])} is "
+                        f"{(r['mean_s'] / old['mean_s'] - 1) * 100:.1f}% slower than "
+                        f"baseline {format_time(old['mean_s'])}"
+                    )
+                    p = mann_whitney_p(r.get("times_s") or [], old.get("times_s") or [])
+                    if p is not None:
+                        verdict = "significant" if p < SIGNIFICANCE else "not significant"
+                        message += f" (Mann-Whitney p = {p:.3f}, {verdict})"
+            if message is not None:
+                failures += 1
+                ET.SubElement(case, "failure", type=kind, message=message).text = message
+        suite.set("tests", str(len(cases)))
+        suite.set("failures", str(failures))
+        suite.set("errors", "0")
+        suite.set("skipped", str(skipped))
+
+    output = Path(output_path)
+    output.parent.mkdir(parents=True, exist_ok=True)
+    ET.indent(root)
+    ET.ElementTree(root).write(output, encoding="utf-8", xml_declaration=True)
+    log(f"JUnit XML written to {output}")
+
+
 def main():
     parser = argparse.ArgumentParser(description="Generate benchmark comparison report")
     parser.add_argument("--results-dir", default="results", help="Directory with JSON results")
@@ -378,6 +808,64 @@ def main():
         default="",
         help="Output file path (default: results/benchmark_report.md)",
     )
+    parser.add_argument(
+        "--data-dir",
+        default=str(Path(__file__).parent / "data"),
+        help="Directory with the input files, for counting value changes",
+    )
+    parser.add_argument(
+        "--library",
+        action="append",
+        default=[],
+        help="Only report this library; repeatable",
+    )
+    parser.add_argument(
+        "--format",
+        action="append",
+        default=[],
+        help="Only report this format (vcd/fst); repeatable",
+    )
+    parser.add_argument(
+        "--operation",
+        action="append",
+        default=[],
+        help="Only report this operation, e.g. full_parse; repeatable",
+    )
+    parser.add_argument(
+        "--file",
+        action="append",
+        default=[],
+        metavar="PATTERN",
+        help="Only report files whose name matches this glob; repeatable",
+    )
+    parser.add_argument(
+        "--aggregate",
+        action="store_true",
+        help="Add the geometric mean over files of each library and operation",
+    )
+    parser.add_argument(
+        "--label",
+        action="append",
+        default=[],
+        metavar="KEY=VALUE",
+        help="Only report results labelled KEY=VALUE; repeatable",
+    )
+    parser.add_argument(
+        "--junit",
+        default="",
+        help="Also write a JUnit XML file of benchmark statuses to this path",
+    )
+    parser.add_argument(
+        "--baseline",
+        default="",
+        help="Combined results of an earlier run; slower cells fail in the JUnit XML",
+    )
+    parser.add_argument(
+        "--regression-threshold",
+        type=float,
+        default=0.10,
+        help="Relative slowdown against --baseline that fails a cell (default: 0.10)",
+    )
     args = parser.parse_args()
 
     results_dir = Path(args.results_dir)
@@ -408,11 +896,41 @@ def main():
         log("No benchmark records found in results. Run run_all.py first.")
         sys.exit(1)
 
+    for label in args.label:
+        key, sep, value = label.partition("=")
+        if not sep:
+            parser.error(f"--label needs KEY=VALUE, not {label!r}")
+        all_records = [r for r in all_records if r["labels"].get(key) == value]
+    all_records = filter_records(all_records, args.library, args.format, args.operation, args.file)
+    filters = [
+        f"{name} {' or '.join(values)}"
+        for name, values in (
+            ("library", args.library),
+            ("format", args.format),
+            ("operation", args.operation),
+            ("file", args.file),
+            ("label", args.label),
+        )
+        if values
+    ]
+    if not all_records:
+        log(f"No benchmark records match the filters: {', '.join(filters)}")
+        sys.exit(1)
+
     scale_label = "+".join(scales_found) if len(scales_found) > 1 else (scales_found[0] if scales_found else "unknown")
     log(f"Loaded {len(all_records)} benchmark records from scales: {', '.join(scales_found)}")
 
+    add_normalized_metrics(all_records, args.data_dir, results_dir / "value_changes.json")
+
     output_path = args.output or str(results_dir / "benchmark_report.md")
-    generate_report(all_records, scale_label, output_path)
+    generate_report(all_records, scale_label, output_path, filters, args.aggregate)
+
+    if args.junit:
+        baseline = None
+        if args.baseline:
+            with open(args.baseline) as f:
+                baseline = normalize_results(migrate(json.load(f)))
+        write_junit(all_records, args.junit, baseline, args.regression_threshold)
 
 
 if __name__ == "__main__":
@@ -9,21 +9,52 @@ Usage:
     python run_all.py --scale medium             # Standard run
     python run_all.py --scale large              # Extreme test (100MB+ files)
     python run_all.py --scale small --skip-rust  # Python only
+    python run_all.py --scale small --profile flamegraph  # Rust flamegraphs
+    python run_all.py --scale small --profile heap        # Rust heap profiles
+    python run_all.py --scale medium --numa-node 0        # Bind to NUMA node 0
+    python run_all.py --scale medium --noise-budget 120   # Rerun noisy Rust cells
+    python run_all.py sweep --sizes 10M,100M,1G           # Rust scaling sweep
+    python run_all.py --allocators system,jemalloc,mimalloc  # Compare allocators
+    python run_all.py --scale large --io-throttle 50M     # Simulate 50 MB/s storage
+    python run_all.py validate results/*.json             # Check result files
+    python run_all.py diff-results old.json new.json      # Compare two runs
+    python run_all.py trends --scale small                # Times over past runs
+    python run_all.py export-metrics results/combined_small.json  # OpenMetrics
+    python run_all.py --anonymize --alias core.vcd=big.vcd  # Shareable results
 """
 
 import argparse
+import hashlib
 import json
 import os
+import shutil
 import subprocess
 import sys
 import time
 from pathlib import Path
 
+from diff_results import diff_files
+from generate_testdata import parse_size
+from history import record_run, timeouts as history_timeouts, trends
+from openmetrics import export_file
+from schema import SCHEMA_VERSION, validate_files
+
 BENCH_DIR = Path(__file__).parent.resolve()
 DATA_DIR = BENCH_DIR / "data"
 PYTHON_DIR = BENCH_DIR / "python"
 RUST_DIR = BENCH_DIR / "rust"
 RESULTS_DIR = BENCH_DIR / "results"
+HISTORY_DB = RESULTS_DIR / "history.sqlite"
+
+# Cargo feature each wave-bench --profile mode needs
+PROFILE_FEATURES = {"flamegraph": "flamegraph", "heap": "dhat-heap"}
+
+# Cargo feature selecting each wave-bench global allocator
+ALLOCATOR_FEATURES = {"system": None, "jemalloc": "jemalloc", "mimalloc": "mimalloc"}
+
+# Cargo features of the wave-bench backends, all on by default; fstapi
+# builds a C library
+RUST_BACKENDS = ["wellen", "rust-vcd", "vcd-ng", "fst-reader", "fstapi"]
 
 # Subprocess timeout per scale (seconds)
 SCALE_TIMEOUTS = {"small": 300, "medium": 600, "large": 1200}
@@ -109,9 +140,42 @@ def find_datagen_python():
     return sys.executable
 
 
-def run_python_benchmarks(scale, timeout):
-    """Run all Python benchmark scripts, return list of result dicts."""
+def numa_prefix(numa_node):
+    """Command prefix binding a child's CPUs and memory to `numa_node`."""
+    if numa_node is None:
+        return []
+    if shutil.which("numactl") is None:
+        log(f"  WARNING: numactl not found, Python benchmarks not bound to node {numa_node}")
+        return []
+    return ["numactl", f"--cpunodebind={numa_node}", f"--membind={numa_node}"]
+
+
+def io_throttle_prefix(io_throttle):
+    """Command prefix running a child in a cgroup limited to `io_throttle`.
+
+    The limit applies to reads and writes of the device holding the test
+    data. It caps storage traffic only, so page cache hits stay fast: the
+    cold first repetition of a cell shows the throttle, warm ones do not.
+    """
+    if not io_throttle:
+        return []
+    rate = parse_size(io_throttle)
+    return [
+        "systemd-run", "--scope", "--quiet",
+        "-p", f"IOReadBandwidthMax={DATA_DIR} {rate}",
+        "-p", f"IOWriteBandwidthMax={DATA_DIR} {rate}",
+        "--",
+    ]
+
+
+def run_python_benchmarks(scale, timeout, numa_node=None, io_throttle=None):
+    """Run all Python benchmark scripts, return list of result dicts.
+
+    With `numa_node`, each script runs bound to that node via numactl. With
+    `io_throttle`, each script runs with storage limited to that rate.
+    """
     all_results = []
+    prefix = io_throttle_prefix(io_throttle) + numa_prefix(numa_node)
 
     for script_name, venv_name, description in PYTHON_BENCHMARKS:
         log(f"\n--- Python: {description} ---")
@@ -120,6 +184,7 @@ def run_python_benchmarks(scale, timeout):
         if not script_path.exists():
             log(f"  SKIP: {script_path} not found")
             all_results.append({
+                "schema_version": SCHEMA_VERSION,
                 "library": description,
                 "format": "unknown",
                 "results": [{
@@ -142,6 +207,7 @@ def run_python_benchmarks(scale, timeout):
             log(f"  SKIP: venv not found at {venv_python}")
             log(f"  Run 'bash benchmarks/python/setup_envs.sh' first")
             all_results.append({
+                "schema_version": SCHEMA_VERSION,
                 "library": description,
                 "format": "unknown",
                 "results": [{
@@ -166,7 +232,7 @@ def run_python_benchmarks(scale, timeout):
         effective_data_dir = str(scale_data_dir) if scale_data_dir.is_dir() else str(DATA_DIR)
 
         stdout, stderr, rc = run_subprocess(
-            [
+            prefix + [
                 str(venv_python),
                 str(script_path),
                 "--data-dir", effective_data_dir,
@@ -185,6 +251,7 @@ def run_python_benchmarks(scale, timeout):
         if rc == -1:
             # Timeout
             all_results.append({
+                "schema_version": SCHEMA_VERSION,
                 "library": description,
                 "format": "unknown",
                 "results": [{
@@ -211,6 +278,7 @@ def run_python_benchmarks(scale, timeout):
             except json.JSONDecodeError as e:
                 log(f"  ERROR parsing output: {e}")
                 all_results.append({
+                    "schema_version": SCHEMA_VERSION,
                     "library": description,
                     "format": "unknown",
                     "results": [{
@@ -239,8 +307,50 @@ def run_python_benchmarks(scale, timeout):
     return all_results
 
 
-def run_rust_benchmarks(scale, timeout):
-    """Build and run Rust benchmark, return list of result dicts."""
+def write_timeouts(timeouts, allocator, path):
+    """Write the timeouts of the cells of wave-bench built with `allocator`
+    to `path`, in the form `--timeouts` reads, and return `path`."""
+    suffix = "" if allocator == "system" else f" [{allocator}]"
+    lines = ["# library\toperation\tfile\tseconds"]
+    for (library, operation, file), seconds in sorted(timeouts.items()):
+        if suffix and not library.endswith(suffix):
+            continue
+        if not suffix and library.endswith("]"):
+            continue
+        backend = library[:len(library) - len(suffix)]
+        lines.append(f"{backend}\t{operation}\t{file}\t{seconds:.3f}")
+    path.parent.mkdir(parents=True, exist_ok=True)
+    path.write_text("\n".join(lines) + "\n")
+    return path
+
+
+def has_c_compiler():
+    """Whether the cc crate will find a C compiler: $CC, or cc, gcc or clang
+    on the PATH."""
+    cc = os.environ.get("CC", "").split()
+    return any(shutil.which(c) for c in cc[:1] + ["cc", "gcc", "clang"])
+
+
+def run_rust_benchmarks(
+    scale, timeout, profile=None, numa_node=None, noise_budget=None, data_dir=None,
+    allocator="system", io_throttle=None, trace=None, prime_cache=False, labels=None,
+    timeouts=None,
+):
+    """Build and run Rust benchmark, return list of result dicts.
+
+    With `profile`, cells are profiled in that mode and the profiles are
+    written to results/profiles/. With `numa_node`, wave-bench binds itself
+    to that node. With `noise_budget`, wave-bench spends up to that many
+    seconds on extra repetitions of noisy cells. `data_dir` overrides the
+    inputs of `scale`. `allocator` picks the global allocator wave-bench is
+    built with. With `io_throttle`, wave-bench runs with storage limited to
+    that rate. With `trace`, wave-bench writes a Chrome trace of the run to
+    that path. With `prime_cache`, each input is read into the page cache
+    before its first cell instead of being evicted before every cell.
+    `labels` ({key: value}) are attached to every result. `timeouts`
+    ({(library, operation, file): seconds}, libraries as named in reports)
+    replace the timeout of a repetition for the cells they name.
+    """
     log("\n--- Rust benchmarks ---")
 
     cargo_toml = RUST_DIR / "Cargo.toml"
@@ -248,13 +358,22 @@ def run_rust_benchmarks(scale, timeout):
         log(f"  SKIP: {cargo_toml} not found")
         return []
 
-    # Build
-    log("  Building (cargo build --release)...")
+    # Build, without fstapi if there is no C compiler to build it with
+    build_cmd = ["cargo", "build", "--release"]
+    features = [PROFILE_FEATURES.get(profile), ALLOCATOR_FEATURES[allocator]]
+    features = [f for f in features if f]
+    if not has_c_compiler():
+        log("  No C compiler found; building without fstapi")
+        build_cmd.append("--no-default-features")
+        features += [b for b in RUST_BACKENDS if b != "fstapi"]
+    if features:
+        build_cmd += ["--features", ",".join(features)]
+    log(f"  Building ({' '.join(build_cmd)})...")
     stdout, stderr, rc = run_subprocess(
-        ["cargo", "build", "--release"],
+        build_cmd,
         cwd=str(RUST_DIR),
         timeout=300,
-        description="cargo build --release",
+        description=" ".join(build_cmd),
     )
     if rc != 0:
         log(f"  BUILD FAILED (rc={rc})")
@@ -262,6 +381,7 @@ def run_rust_benchmarks(scale, timeout):
             for line in stderr.strip().split("\n")[-20:]:
                 log(f"    {line}")
         return [{
+            "schema_version": SCHEMA_VERSION,
             "library": "rust-all",
             "format": "mixed",
             "results": [{
@@ -293,21 +413,40 @@ def run_rust_benchmarks(scale, timeout):
     # Use scale-specific subdirectory if it exists
     scale_data_dir = DATA_DIR / scale
     effective_data_dir = str(scale_data_dir) if scale_data_dir.is_dir() else str(DATA_DIR)
+    if data_dir is not None:
+        effective_data_dir = str(data_dir)
 
     env = os.environ.copy()
     env["DATA_DIR"] = effective_data_dir
     env["TIMEOUT"] = str(rust_timeout)
     env["REPS"] = "3"
 
-    log(f"  Running wave-bench (DATA_DIR={DATA_DIR}, TIMEOUT={rust_timeout}s)...")
+    log(f"  Running wave-bench (DATA_DIR={effective_data_dir}, TIMEOUT={rust_timeout}s)...")
+
+    cmd = io_throttle_prefix(io_throttle) + [str(binary)]
+    if profile:
+        cmd += ["--profile", profile, "--profile-dir", str(RESULTS_DIR / "profiles")]
+    if numa_node is not None:
+        cmd += ["--numa-node", str(numa_node)]
+    if noise_budget:
+        cmd += ["--noise-budget", str(noise_budget)]
+    if trace:
+        cmd += ["--trace", str(trace)]
+    if prime_cache:
+        cmd.append("--prime-cache")
+    for key, value in (labels or {}).items():
+        cmd += ["--label", f"{key}={value}"]
+    if timeouts:
+        timeouts_path = write_timeouts(timeouts, allocator, RESULTS_DIR / f"timeouts_{scale}_{allocator}.tsv")
+        cmd += ["--timeouts", str(timeouts_path)]
 
     try:
         result = subprocess.run(
-            [str(binary)],
+            cmd,
             env=env,
             capture_output=True,
             text=True,
-            timeout=timeout,
+            timeout=timeout + (noise_budget or 0),
         )
         stdout = result.stdout
         stderr = result.stderr
@@ -315,6 +454,7 @@ def run_rust_benchmarks(scale, timeout):
     except subprocess.TimeoutExpired:
         log(f"  TIMEOUT after {timeout}s")
         return [{
+            "schema_version": SCHEMA_VERSION,
             "library": "rust-all",
             "format": "mixed",
             "results": [{
@@ -358,8 +498,132 @@ def run_rust_benchmarks(scale, timeout):
     return rust_results
 
 
+def run_sweep(
+    sizes, timeout, datagen_python, profile=None, numa_node=None, noise_budget=None, labels=None,
+):
+    """Benchmark the Rust libraries on generated inputs of each size.
+
+    Inputs of the same signal mix are generated into data/sweep/<size>/,
+    and each result is tagged with its size so that time and memory can be
+    plotted against input size per library. Returns the tagged results.
+    """
+    gen_script = BENCH_DIR / "generate_testdata.py"
+    results = []
+    for label in sizes:
+        log(f"\n[Sweep] {label}")
+        size_dir = DATA_DIR / "sweep" / label
+        stdout, stderr, rc = run_subprocess(
+            [datagen_python, str(gen_script), "--size", label, "--data-dir", str(size_dir)],
+            timeout=None,  # generating gigabytes of VCD takes a while
+            description=f"generate_testdata.py --size {label}",
+        )
+        for line in (stdout + stderr).strip().split("\n"):
+            if line.strip():
+                log(f"    {line}")
+        if rc != 0:
+            log(f"  WARNING: Data generation returned code {rc}, skipping {label}")
+            continue
+
+        # The largest scale's per-repetition timeouts suit every sweep size
+        size_results = run_rust_benchmarks(
+            "large", timeout, profile, numa_node, noise_budget, data_dir=size_dir, labels=labels,
+        )
+        for r in size_results:
+            r["size_label"] = label
+            r["target_size_bytes"] = parse_size(label)
+            path = r.get("file", "")
+            r["file_size_bytes"] = os.path.getsize(path) if path and os.path.exists(path) else 0
+        results.extend(size_results)
+    return results
+
+
+def file_alias(path, aliases):
+    """The name `path` is published under: its alias in `aliases`, given by
+    full path or file name, or a hash of its file name, which is stable
+    across runs and machines and keeps the extension."""
+    name = os.path.basename(path)
+    if path in aliases:
+        return aliases[path]
+    if name in aliases:
+        return aliases[name]
+    ext = os.path.splitext(name)[1]
+    return f"file-{hashlib.sha256(name.encode()).hexdigest()[:10]}{ext}"
+
+
+def anonymize_results(python_results, rust_results, aliases):
+    """Replace input file paths in results with aliases, in place.
+
+    Sizes are recorded before paths are replaced, since readers can no
+    longer find the files. Paths, file names and file stems are also
+    replaced inside error messages and profile paths, and the directories
+    holding inputs become "<data>". Profiles on disk keep their names.
+    """
+    replacements = {}
+
+    def alias(path):
+        if not path:
+            return path
+        new = file_alias(path, aliases)
+        replacements[path] = new
+        replacements[os.path.basename(path)] = new
+        replacements[os.path.splitext(os.path.basename(path))[0]] = os.path.splitext(new)[0]
+        if os.path.dirname(path):
+            replacements[os.path.dirname(path)] = "<data>"
+        return new
+
+    records = [r for payload in python_results for r in payload.get("results", [])]
+    for r in rust_results:
+        if "results" in r:
+            records += r["results"]
+        else:
+            path = r.get("file", "")
+            if path and "file_size_bytes" not in r:
+                r["file_size_bytes"] = os.path.getsize(path) if os.path.exists(path) else 0
+            records.append(r)
+    for r in records:
+        r["file"] = alias(r.get("file", ""))
+
+    # Longest first, so that a path is replaced before the name inside it
+    order = sorted((k for k in replacements if k), key=len, reverse=True)
+    for r in records:
+        for field in ("error", "profile"):
+            text = r.get(field)
+            if isinstance(text, str):
+                for old in order:
+                    text = text.replace(old, replacements[old])
+                r[field] = text
+
+
+def parse_pairs(parser, option, form, values):
+    """{key: value} of repeated `option` values of the form KEY=VALUE."""
+    pairs = {}
+    for value in values:
+        key, sep, val = value.partition("=")
+        if not sep or not key:
+            parser.error(f"{option} needs {form}, not {value!r}")
+        pairs[key] = val
+    return pairs
+
+
 def main():
     parser = argparse.ArgumentParser(description="Run VCD/FST library benchmarks")
+    parser.add_argument(
+        "mode",
+        nargs="?",
+        choices=["run", "sweep", "validate", "diff-results", "trends", "export-metrics"],
+        default="run",
+        help="run: benchmark one scale (default); sweep: scale Rust inputs by --sizes; "
+             "validate: check result files against the schema; "
+             "diff-results: compare two runs cell by cell; "
+             "trends: show cell times over the run history of --scale; "
+             "export-metrics: print a results file as OpenMetrics",
+    )
+    parser.add_argument(
+        "files",
+        nargs="*",
+        help="Result files for validate mode (default: the combined and sweep files in "
+             "results/), the two runs to compare for diff-results, or the file to export",
+    )
     parser.add_argument(
         "--scale",
         choices=["small", "medium", "large"],
@@ -374,8 +638,201 @@ def main():
         default=0,
         help="Override subprocess timeout in seconds (0=auto based on scale)",
     )
+    parser.add_argument(
+        "--auto-timeout",
+        action="store_true",
+        help="Time out each Rust cell after --timeout-factor times the 99th percentile "
+             "of its repetition times in the run history, instead of one timeout for all",
+    )
+    parser.add_argument(
+        "--timeout-factor",
+        type=float,
+        default=3.0,
+        help="Multiple of past times that --auto-timeout allows a repetition (default: 3)",
+    )
+    parser.add_argument(
+        "--profile",
+        choices=sorted(PROFILE_FEATURES),
+        default=None,
+        help="Profile each Rust benchmark cell, writing profiles to results/profiles/",
+    )
+    parser.add_argument(
+        "--numa-node",
+        type=int,
+        default=None,
+        help="Bind benchmark CPUs and memory to this NUMA node",
+    )
+    parser.add_argument(
+        "--noise-budget",
+        type=float,
+        default=0,
+        help="Seconds to spend on extra repetitions of noisy Rust cells (0=none)",
+    )
+    parser.add_argument(
+        "--allocators",
+        default="system",
+        help="Comma-separated allocators to run the Rust benchmarks with, "
+             f"from {', '.join(ALLOCATOR_FEATURES)} (default: system)",
+    )
+    parser.add_argument(
+        "--io-throttle",
+        default=None,
+        help="Limit benchmark storage I/O to this rate per second (e.g. 50M) "
+             "in a cgroup, to simulate network storage",
+    )
+    parser.add_argument(
+        "--trace",
+        action="store_true",
+        help="Write a Chrome trace (chrome://tracing, Perfetto) of each Rust run "
+             "to results/",
+    )
+    parser.add_argument(
+        "--prime-cache",
+        action="store_true",
+        help="Read each input into the page cache before its first Rust cell, "
+             "so every repetition is warm",
+    )
+    parser.add_argument(
+        "--junit",
+        action="store_true",
+        help="Also write the benchmark statuses as JUnit XML to results/junit_<scale>.xml",
+    )
+    parser.add_argument(
+        "--anonymize",
+        action="store_true",
+        help="Replace input file paths in the combined results and report with stable "
+             "hashes of the file names, or --alias names",
+    )
+    parser.add_argument(
+        "--alias",
+        action="append",
+        default=[],
+        metavar="FILE=NAME",
+        help="With --anonymize, publish FILE (a path or file name) as NAME; repeatable",
+    )
+    parser.add_argument(
+        "--diff-threshold",
+        type=float,
+        default=0.05,
+        help="Relative time change that diff-results flags (default: 0.05)",
+    )
+    parser.add_argument(
+        "--diff-memory-threshold",
+        type=float,
+        default=0.05,
+        help="Relative peak memory change that diff-results flags (default: 0.05)",
+    )
+    parser.add_argument(
+        "--trend-threshold",
+        type=float,
+        default=0.05,
+        help="Relative shift in a cell's times that trends reports (default: 0.05)",
+    )
+    parser.add_argument(
+        "--no-history",
+        action="store_true",
+        help=f"Do not add this run to the history in {HISTORY_DB.name}",
+    )
+    parser.add_argument(
+        "--pushgateway",
+        default=None,
+        metavar="URL",
+        help="With export-metrics, also push the metrics to this Prometheus Pushgateway",
+    )
+    parser.add_argument(
+        "--label",
+        action="append",
+        default=[],
+        metavar="KEY=VALUE",
+        help="Attach KEY=VALUE to every result of the run, e.g. branch=refactor or "
+             "storage=nfs; repeatable. In trends mode, only runs with these labels",
+    )
+    parser.add_argument(
+        "--sizes",
+        default="10M,100M,1G",
+        help="Comma-separated input sizes for sweep mode (default: 10M,100M,1G)",
+    )
     args = parser.parse_args()
 
+    allocators = [a.strip() for a in args.allocators.split(",") if a.strip()]
+    for allocator in allocators:
+        if allocator not in ALLOCATOR_FEATURES:
+            parser.error(f"unknown allocator: {allocator!r}")
+    if args.profile == "heap" and allocators != ["system"]:
+        parser.error("--profile heap replaces the allocator; use --allocators system")
+    if args.io_throttle:
+        try:
+            parse_size(args.io_throttle)
+        except argparse.ArgumentTypeError as e:
+            parser.error(str(e))
+        # Running unthrottled would silently mislabel the results
+        if shutil.which("systemd-run") is None:
+            parser.error("--io-throttle needs systemd-run")
+
+    aliases = parse_pairs(parser, "--alias", "FILE=NAME", args.alias)
+    if not all(aliases.values()):
+        parser.error("--alias needs a NAME")
+    labels = parse_pairs(parser, "--label", "KEY=VALUE", args.label)
+    if args.timeout_factor <= 0:
+        parser.error("--timeout-factor must be positive")
+    if aliases and not args.anonymize:
+        parser.error("--alias needs --anonymize")
+
+    if args.mode == "validate":
+        paths = args.files or sorted(RESULTS_DIR.glob("combined_*.json")) + sorted(RESULTS_DIR.glob("sweep.json"))
+        if not paths:
+            parser.error(f"no result files in {RESULTS_DIR}")
+        sys.exit(0 if validate_files(paths) else 1)
+    if args.mode == "diff-results":
+        if len(args.files) != 2:
+            parser.error("diff-results needs two result files")
+        text, regressed = diff_files(
+            args.files[0], args.files[1], args.diff_threshold, args.diff_memory_threshold
+        )
+        print(text)
+        # Like diff(1), exit 1 when there is something to look at
+        sys.exit(1 if regressed else 0)
+    if args.mode == "trends":
+        print(trends(HISTORY_DB, args.scale, args.trend_threshold, labels=labels))
+        return
+    if args.mode == "export-metrics":
+        if len(args.files) != 1:
+            parser.error("export-metrics needs one result file")
+        print(export_file(args.files[0], gateway=args.pushgateway), end="")
+        return
+    if args.files:
+        parser.error("result files are only taken in validate, diff-results and export-metrics modes")
+
+    if args.mode == "sweep":
+        sizes = [s.strip() for s in args.sizes.split(",") if s.strip()]
+        for size in sizes:
+            try:
+                parse_size(size)
+            except argparse.ArgumentTypeError as e:
+                parser.error(str(e))
+        timeout = args.timeout or SCALE_TIMEOUTS["large"]
+        RESULTS_DIR.mkdir(parents=True, exist_ok=True)
+        log(f"Scaling sweep over {', '.join(sizes)}")
+        results = run_sweep(
+            sizes, timeout, find_datagen_python(), args.profile, args.numa_node,
+            args.noise_budget, labels,
+        )
+        if args.anonymize:
+            anonymize_results([], results, aliases)
+        sweep_path = RESULTS_DIR / "sweep.json"
+        with open(sweep_path, "w") as f:
+            json.dump({
+                "schema_version": SCHEMA_VERSION,
+                "sizes": sizes,
+                "timestamp": time.strftime("%Y-%m-%d %H:%M:%S"),
+                "numa_node": args.numa_node,
+                "anonymized": args.anonymize,
+                "labels": labels,
+                "rust_results": results,
+            }, f, indent=2)
+        log(f"Sweep results saved to {sweep_path}")
+        return
+
     scale = args.scale
     timeout = args.timeout or SCALE_TIMEOUTS.get(scale, 600)
 
@@ -385,6 +842,22 @@ def main():
     log("VCD/FST Library Benchmark Suite")
     log(f"  Scale: {scale}")
     log(f"  Timeout: {timeout}s")
+    cell_timeouts = None
+    if args.auto_timeout:
+        cell_timeouts = history_timeouts(HISTORY_DB, scale, args.timeout_factor)
+        if cell_timeouts:
+            log(f"  Cell timeouts: {len(cell_timeouts)} from history, "
+                f"{min(cell_timeouts.values()):.1f}s to {max(cell_timeouts.values()):.1f}s")
+        else:
+            log(f"  Cell timeouts: no history of {scale} runs, using the default")
+    if args.profile:
+        log(f"  Profile: {args.profile}")
+    if args.numa_node is not None:
+        log(f"  NUMA node: {args.numa_node}")
+    if args.io_throttle:
+        log(f"  I/O throttle: {args.io_throttle}/s")
+    if labels:
+        log(f"  Labels: {', '.join(f'{k}={v}' for k, v in labels.items())}")
     log(f"  Data dir: {DATA_DIR}")
     log(f"  Results dir: {RESULTS_DIR}")
     log("=" * 60)
@@ -402,7 +875,9 @@ def main():
     python_results = []
     if not args.skip_python:
         log("\n[Step 2] Running Python benchmarks...")
-        python_results = run_python_benchmarks(scale, timeout)
+        python_results = run_python_benchmarks(
+            scale, timeout, args.numa_node, args.io_throttle
+        )
     else:
         log("\n[Step 2] Skipping Python benchmarks (--skip-python)")
 
@@ -410,15 +885,40 @@ def main():
     rust_results = []
     if not args.skip_rust:
         log("\n[Step 3] Running Rust benchmarks...")
-        rust_results = run_rust_benchmarks(scale, timeout)
+        for allocator in allocators:
+            trace = None
+            if args.trace:
+                suffix = f"_{allocator}" if len(allocators) > 1 else ""
+                trace = RESULTS_DIR / f"trace_{scale}{suffix}.json"
+            if len(allocators) > 1:
+                log(f"\n  Allocator: {allocator}")
+            rust_results += run_rust_benchmarks(
+                scale, timeout, args.profile, args.numa_node, args.noise_budget,
+                allocator=allocator, io_throttle=args.io_throttle, trace=trace,
+                prime_cache=args.prime_cache, labels=labels, timeouts=cell_timeouts,
+            )
     else:
         log("\n[Step 3] Skipping Rust benchmarks (--skip-rust)")
 
     # Step 4: Save combined results
     log("\n[Step 4] Saving results...")
+    # Python scripts know nothing of labels, so their results are labelled
+    # here, as are the error records standing in for failed runs
+    for payload in python_results + [r for r in rust_results if "results" in r]:
+        payload["labels"] = labels
+    if args.anonymize:
+        anonymize_results(python_results, rust_results, aliases)
+        log("  Input file paths anonymized")
     combined = {
+        "schema_version": SCHEMA_VERSION,
         "scale": scale,
         "timestamp": time.strftime("%Y-%m-%d %H:%M:%S"),
+        "numa_node": args.numa_node,
+        "allocators": allocators,
+        "io_throttle": args.io_throttle,
+        "prime_cache": args.prime_cache,
+        "anonymized": args.anonymize,
+        "labels": labels,
         "python_results": python_results,
         "rust_results": rust_results,
     }
@@ -426,13 +926,19 @@ def main():
     with open(combined_path, "w") as f:
         json.dump(combined, f, indent=2)
     log(f"  Combined results saved to {combined_path}")
+    if not args.no_history:
+        record_run(HISTORY_DB, combined, BENCH_DIR.parent)
+        log(f"  Run added to {HISTORY_DB}")
 
     # Step 5: Generate report
     log("\n[Step 5] Generating report...")
     report_script = BENCH_DIR / "report.py"
     if report_script.exists():
+        report_cmd = [sys.executable, str(report_script), "--results-dir", str(RESULTS_DIR), "--scale", scale]
+        if args.junit:
+            report_cmd += ["--junit", str(RESULTS_DIR / f"junit_{scale}.xml")]
         stdout, stderr, rc = run_subprocess(
-            [sys.executable, str(report_script), "--results-dir", str(RESULTS_DIR), "--scale", scale],
+            report_cmd,
             timeout=60,
             description="report.py",
         )
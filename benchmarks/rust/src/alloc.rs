@@ -0,0 +1,126 @@
+//! Allocator selection and allocation counting.
+//!
+//! The harness allocates through the system allocator, or through jemalloc
+//! or mimalloc with the cargo feature of that name; [`NAME`] is recorded
+//! with every result, so the same backend can be compared across
+//! allocators.
+//!
+//! With the `count-allocs` cargo feature, the allocator is wrapped in
+//! [`Counting`], which tallies every allocation and its size, and each
+//! cell records how many allocations and bytes a repetition made. Unlike
+//! times, these counts barely vary between runs or machines, so they
+//! catch regressions that timing noise hides. A reallocation counts as an
+//! allocation of its new size.
+
+#![cfg_attr(not(feature = "count-allocs"), allow(dead_code))]
+
+use std::alloc::{GlobalAlloc, Layout};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(all(feature = "jemalloc", feature = "mimalloc"))]
+compile_error!("features `jemalloc` and `mimalloc` both select the allocator");
+
+#[cfg(all(
+    feature = "dhat-heap",
+    any(feature = "count-allocs", feature = "jemalloc", feature = "mimalloc")
+))]
+compile_error!("feature `dhat-heap` replaces the global allocator");
+
+#[cfg(feature = "jemalloc")]
+type Base = tikv_jemallocator::Jemalloc;
+#[cfg(feature = "jemalloc")]
+const BASE: Base = tikv_jemallocator::Jemalloc;
+
+#[cfg(feature = "mimalloc")]
+type Base = mimalloc::MiMalloc;
+#[cfg(feature = "mimalloc")]
+const BASE: Base = mimalloc::MiMalloc;
+
+#[cfg(not(any(feature = "jemalloc", feature = "mimalloc")))]
+type Base = std::alloc::System;
+#[cfg(not(any(feature = "jemalloc", feature = "mimalloc")))]
+const BASE: Base = std::alloc::System;
+
+/// The allocator the harness allocates through.
+pub const NAME: &str = if cfg!(feature = "jemalloc") {
+    "jemalloc"
+} else if cfg!(feature = "mimalloc") {
+    "mimalloc"
+} else if cfg!(feature = "dhat-heap") {
+    "dhat"
+} else {
+    "system"
+};
+
+#[cfg(feature = "count-allocs")]
+#[global_allocator]
+static ALLOC: Counting<Base> = Counting(BASE);
+
+#[cfg(all(
+    not(feature = "count-allocs"),
+    any(feature = "jemalloc", feature = "mimalloc")
+))]
+#[global_allocator]
+static ALLOC: Base = BASE;
+
+static COUNT: AtomicU64 = AtomicU64::new(0);
+static BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// A global allocator counting the allocations it forwards to `A`.
+pub struct Counting<A>(pub A);
+
+#[inline]
+fn record(size: usize) {
+    COUNT.fetch_add(1, Ordering::Relaxed);
+    BYTES.fetch_add(size as u64, Ordering::Relaxed);
+}
+
+// SAFETY: every call is forwarded unchanged to the wrapped allocator.
+unsafe impl<A: GlobalAlloc> GlobalAlloc for Counting<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record(layout.size());
+        self.0.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        record(layout.size());
+        self.0.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record(new_size);
+        self.0.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.dealloc(ptr, layout)
+    }
+}
+
+/// Allocations made since the process started.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AllocCounts {
+    pub count: u64,
+    pub bytes: u64,
+}
+
+impl AllocCounts {
+    /// The counts so far, or `None` if this build does not count.
+    pub fn now() -> Option<AllocCounts> {
+        if !cfg!(feature = "count-allocs") {
+            return None;
+        }
+        Some(AllocCounts {
+            count: COUNT.load(Ordering::Relaxed),
+            bytes: BYTES.load(Ordering::Relaxed),
+        })
+    }
+
+    /// The allocations made between `earlier` and `self`.
+    pub fn since(self, earlier: AllocCounts) -> AllocCounts {
+        AllocCounts {
+            count: self.count - earlier.count,
+            bytes: self.bytes - earlier.bytes,
+        }
+    }
+}
@@ -0,0 +1,978 @@
+//! The benchmarked libraries.
+//!
+//! Each `bench_*` function runs every operation on one input, as cells of
+//! [`run_cell`]: full_parse, signal_list, value_query and pipeline.
+//!
+//! Every backend but wave_parse is behind the cargo feature of its name,
+//! all on by default, so the harness builds without the libraries it does
+//! not compare; `--no-default-features --features wellen,rust-vcd,vcd-ng,
+//! fst-reader` leaves out fstapi, the one needing a C compiler.
+//! [`compiled`] names the backends a build has.
+//!
+//! The cells a backend cannot run, on a format it does not read or in a
+//! build without it, are skipped: each gets a result row with status
+//! `skipped` and the reason, so the results hold the whole matrix.
+
+use std::fs;
+use std::io;
+#[cfg(feature = "rust-vcd")]
+use std::io::BufReader;
+use std::path::Path;
+
+#[cfg(feature = "fst-reader")]
+use crate::input::Input;
+use crate::input::Io;
+use crate::{latency, run_cell, skip_cell, BenchError, Config};
+
+/// A benchmarked library.
+struct Backend {
+    /// Its cargo feature, and name in `--list-backends`.
+    name: &'static str,
+    /// The library name of each of its I/O variants.
+    libraries: &'static [&'static str],
+    formats: &'static [&'static str],
+    compiled: bool,
+}
+
+const OPERATIONS: &[&str] = &["full_parse", "signal_list", "value_query", "pipeline"];
+
+const BACKENDS: &[Backend] = &[
+    Backend {
+        name: "wellen",
+        libraries: &["wellen"],
+        formats: &["vcd", "fst"],
+        compiled: cfg!(feature = "wellen"),
+    },
+    Backend {
+        name: "rust-vcd",
+        libraries: &["rust-vcd"],
+        formats: &["vcd"],
+        compiled: cfg!(feature = "rust-vcd"),
+    },
+    Backend {
+        name: "vcd-ng",
+        libraries: &["vcd-ng"],
+        formats: &["vcd"],
+        compiled: cfg!(feature = "vcd-ng"),
+    },
+    Backend {
+        name: "wave_parse",
+        libraries: &["wave_parse-read", "wave_parse-mmap"],
+        formats: &["vcd", "fst"],
+        compiled: true,
+    },
+    Backend {
+        name: "fst-reader",
+        libraries: &["fst-reader", "fst-reader-mmap"],
+        formats: &["fst"],
+        compiled: cfg!(feature = "fst-reader"),
+    },
+    Backend {
+        name: "fstapi",
+        libraries: &["fstapi"],
+        formats: &["fst"],
+        compiled: cfg!(feature = "fstapi"),
+    },
+];
+
+/// The backends compiled in.
+pub fn compiled() -> Vec<&'static str> {
+    BACKENDS
+        .iter()
+        .filter(|b| b.compiled)
+        .map(|b| b.name)
+        .collect()
+}
+
+/// Skip every cell that cannot run on `file`: those of backends not
+/// reading `format` or not compiled in.
+fn skip_others(file: &Path, format: &str, cfg: &Config) {
+    let file = file.to_string_lossy();
+    for (library, reason) in skipped(BACKENDS, format) {
+        for operation in OPERATIONS {
+            skip_cell(cfg, library, format, &file, operation, &reason);
+        }
+    }
+}
+
+/// The libraries of `backends` that cannot run on a file of `format`,
+/// with the reason.
+fn skipped(backends: &[Backend], format: &str) -> Vec<(&'static str, String)> {
+    let mut out = Vec::new();
+    for backend in backends {
+        let reason = if !backend.formats.contains(&format) {
+            format!("{} does not read {}", backend.name, format.to_uppercase())
+        } else if !backend.compiled {
+            format!("built without the {} feature", backend.name)
+        } else {
+            continue;
+        };
+        out.extend(backend.libraries.iter().map(|&l| (l, reason.clone())));
+    }
+    out
+}
+
+/// Run every compiled VCD backend on `file`.
+pub fn bench_vcd(file: &Path, cfg: &Config) {
+    #[cfg(feature = "wellen")]
+    {
+        eprintln!("    wellen...");
+        bench_wellen(file, "vcd", cfg);
+    }
+
+    #[cfg(feature = "rust-vcd")]
+    {
+        eprintln!("    rust-vcd...");
+        bench_rust_vcd(file, cfg);
+    }
+
+    #[cfg(feature = "vcd-ng")]
+    {
+        eprintln!("    vcd-ng...");
+        bench_vcdng_parser(file, cfg);
+    }
+
+    eprintln!("    wave_parse...");
+    bench_wave_parse(file, "vcd", Io::Read, cfg);
+    bench_wave_parse(file, "vcd", Io::Mmap, cfg);
+
+    skip_others(file, "vcd", cfg);
+}
+
+/// Run every compiled FST backend on `file`.
+pub fn bench_fst(file: &Path, cfg: &Config) {
+    #[cfg(feature = "wellen")]
+    {
+        eprintln!("    wellen...");
+        bench_wellen(file, "fst", cfg);
+    }
+
+    #[cfg(feature = "fst-reader")]
+    {
+        eprintln!("    fst-reader...");
+        bench_fst_reader(file, Io::Buffered, cfg);
+        bench_fst_reader(file, Io::Mmap, cfg);
+    }
+
+    #[cfg(feature = "fstapi")]
+    {
+        eprintln!("    fstapi...");
+        bench_fstapi(file, cfg);
+    }
+
+    eprintln!("    wave_parse...");
+    bench_wave_parse(file, "fst", Io::Read, cfg);
+    bench_wave_parse(file, "fst", Io::Mmap, cfg);
+
+    skip_others(file, "fst", cfg);
+}
+
+// ---------------------------------------------------------------------------
+// Helpers for counting vars from rust-vcd / vcd-ng Header
+// ---------------------------------------------------------------------------
+
+#[cfg(feature = "rust-vcd")]
+fn count_vcd_vars(items: &[vcd::ScopeItem]) -> usize {
+    let mut count = 0;
+    for item in items {
+        match item {
+            vcd::ScopeItem::Var(_) => count += 1,
+            vcd::ScopeItem::Scope(scope) => count += count_vcd_vars(&scope.items),
+            _ => {}
+        }
+    }
+    count
+}
+
+#[cfg(feature = "rust-vcd")]
+fn collect_vcd_codes(items: &[vcd::ScopeItem], codes: &mut Vec<vcd::IdCode>) {
+    for item in items {
+        match item {
+            vcd::ScopeItem::Var(v) => codes.push(v.code),
+            vcd::ScopeItem::Scope(scope) => collect_vcd_codes(&scope.items, codes),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(feature = "vcd-ng")]
+fn count_vcdng_vars(items: &[vcd_ng::ScopeItem]) -> usize {
+    let mut count = 0;
+    for item in items {
+        match item {
+            vcd_ng::ScopeItem::Var(_) => count += 1,
+            vcd_ng::ScopeItem::Scope(scope) => count += count_vcdng_vars(&scope.children),
+            _ => {}
+        }
+    }
+    count
+}
+
+#[cfg(feature = "vcd-ng")]
+fn collect_vcdng_codes(items: &[vcd_ng::ScopeItem], codes: &mut Vec<vcd_ng::IdCode>) {
+    for item in items {
+        match item {
+            vcd_ng::ScopeItem::Var(v) => codes.push(v.code),
+            vcd_ng::ScopeItem::Scope(scope) => collect_vcdng_codes(&scope.children, codes),
+            _ => {}
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Benchmark: wellen (VCD + FST)
+// ---------------------------------------------------------------------------
+
+#[cfg(feature = "wellen")]
+pub fn bench_wellen(file: &Path, format: &str, cfg: &Config) {
+    let file_str = file.to_string_lossy().to_string();
+    let lib = "wellen";
+
+    // full_parse
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "full_parse", move || {
+            let _wave = wellen::simple::read(&p).map_err(|e| BenchError::parse("wellen", e))?;
+            Ok(())
+        });
+    }
+
+    // signal_list
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "signal_list", move || {
+            let wave = wellen::simple::read(&p).map_err(|e| BenchError::parse("wellen", e))?;
+            let count = wave.hierarchy().iter_vars().count();
+            if count == 0 {
+                return Err(BenchError::parse("wellen", "no variables found"));
+            }
+            Ok(())
+        });
+    }
+
+    // value_query
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "value_query", move || {
+            let mut wave = wellen::simple::read(&p).map_err(|e| BenchError::parse("wellen", e))?;
+            // pick up to 10 signals
+            let sig_refs: Vec<wellen::SignalRef> = wave
+                .hierarchy()
+                .iter_vars()
+                .take(10)
+                .map(|v| v.signal_ref())
+                .collect();
+            if sig_refs.is_empty() {
+                return Err(BenchError::parse("wellen", "no signals to query"));
+            }
+            wave.load_signals(&sig_refs);
+            for sr in &sig_refs {
+                let _ = wave.get_signal(*sr);
+            }
+            Ok(())
+        });
+    }
+
+    // pipeline: load -> signal_list -> time_range -> value_query in one flow
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "pipeline", move || {
+            // 1. Full parse
+            let mut wave = wellen::simple::read(&p).map_err(|e| BenchError::parse("wellen", e))?;
+            // 2. Signal list
+            let var_count = wave.hierarchy().iter_vars().count();
+            if var_count == 0 {
+                return Err(BenchError::parse("wellen", "no variables found"));
+            }
+            // 3. Time range
+            let _time_table = wave.time_table();
+            // 4. Value query (up to 10 signals)
+            let sig_refs: Vec<wellen::SignalRef> = wave
+                .hierarchy()
+                .iter_vars()
+                .take(10)
+                .map(|v| v.signal_ref())
+                .collect();
+            if !sig_refs.is_empty() {
+                wave.load_signals(&sig_refs);
+                for sr in &sig_refs {
+                    let _ = wave.get_signal(*sr);
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Benchmark: rust-vcd (VCD only, streaming parser)
+// ---------------------------------------------------------------------------
+
+#[cfg(feature = "rust-vcd")]
+pub fn bench_rust_vcd(file: &Path, cfg: &Config) {
+    let file_str = file.to_string_lossy().to_string();
+    let lib = "rust-vcd";
+    let format = "vcd";
+
+    // full_parse: parse header + iterate all commands
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "full_parse", move || {
+            let f = fs::File::open(&p)?;
+            let mut parser = vcd::Parser::new(BufReader::new(f));
+            let _header = parser
+                .parse_header()
+                .map_err(|e| BenchError::parse("rust-vcd", e))?;
+            let mut first = latency::FirstEvent::default();
+            for cmd in parser {
+                let cmd = cmd.map_err(|e| BenchError::parse("rust-vcd", e))?;
+                if matches!(
+                    cmd,
+                    vcd::Command::ChangeScalar(..)
+                        | vcd::Command::ChangeVector(..)
+                        | vcd::Command::ChangeReal(..)
+                        | vcd::Command::ChangeString(..)
+                ) {
+                    first.hit();
+                }
+            }
+            Ok(())
+        });
+    }
+
+    // signal_list: parse header and count variables
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "signal_list", move || {
+            let f = fs::File::open(&p)?;
+            let mut parser = vcd::Parser::new(BufReader::new(f));
+            let header = parser
+                .parse_header()
+                .map_err(|e| BenchError::parse("rust-vcd", e))?;
+            let count = count_vcd_vars(&header.items);
+            if count == 0 {
+                return Err(BenchError::parse("rust-vcd", "no variables found"));
+            }
+            Ok(())
+        });
+    }
+
+    // value_query: parse header, then stream and filter first 10 signal codes
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "value_query", move || {
+            let f = fs::File::open(&p)?;
+            let mut parser = vcd::Parser::new(BufReader::new(f));
+            let header = parser
+                .parse_header()
+                .map_err(|e| BenchError::parse("rust-vcd", e))?;
+            let mut codes = Vec::new();
+            collect_vcd_codes(&header.items, &mut codes);
+            codes.truncate(10);
+            if codes.is_empty() {
+                return Err(BenchError::parse("rust-vcd", "no signals to query"));
+            }
+            let mut first = latency::FirstEvent::default();
+            let mut _match_count = 0u64;
+            for cmd in parser {
+                let cmd = cmd.map_err(|e| BenchError::parse("rust-vcd", e))?;
+                match &cmd {
+                    vcd::Command::ChangeScalar(id, _)
+                    | vcd::Command::ChangeVector(id, _)
+                    | vcd::Command::ChangeReal(id, _)
+                    | vcd::Command::ChangeString(id, _) => {
+                        first.hit();
+                        if codes.contains(id) {
+                            _match_count += 1;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Ok(())
+        });
+    }
+
+    // pipeline: continuous operation
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "pipeline", move || {
+            let f = fs::File::open(&p)?;
+            let mut parser = vcd::Parser::new(BufReader::new(f));
+            // 1+2. Parse header + signal list
+            let header = parser
+                .parse_header()
+                .map_err(|e| BenchError::parse("rust-vcd", e))?;
+            let mut codes = Vec::new();
+            collect_vcd_codes(&header.items, &mut codes);
+            codes.truncate(10);
+            // 3+4. Stream and filter values
+            let mut first = latency::FirstEvent::default();
+            let mut _match_count = 0u64;
+            for cmd in parser {
+                let cmd = cmd.map_err(|e| BenchError::parse("rust-vcd", e))?;
+                match &cmd {
+                    vcd::Command::ChangeScalar(id, _)
+                    | vcd::Command::ChangeVector(id, _)
+                    | vcd::Command::ChangeReal(id, _)
+                    | vcd::Command::ChangeString(id, _) => {
+                        first.hit();
+                        if codes.contains(id) {
+                            _match_count += 1;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Benchmark: vcd-ng Parser mode (VCD only)
+// ---------------------------------------------------------------------------
+
+#[cfg(feature = "vcd-ng")]
+pub fn bench_vcdng_parser(file: &Path, cfg: &Config) {
+    let file_str = file.to_string_lossy().to_string();
+    let lib = "vcd-ng";
+    let format = "vcd";
+
+    // full_parse
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "full_parse", move || {
+            let f = fs::File::open(&p)?;
+            let mut parser = vcd_ng::Parser::new(f);
+            let _header = parser
+                .parse_header()
+                .map_err(|e| BenchError::parse("vcd-ng", e))?;
+            for cmd in parser {
+                let _ = cmd.map_err(|e| BenchError::parse("vcd-ng", e))?;
+            }
+            Ok(())
+        });
+    }
+
+    // signal_list
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "signal_list", move || {
+            let f = fs::File::open(&p)?;
+            let mut parser = vcd_ng::Parser::new(f);
+            let header = parser
+                .parse_header()
+                .map_err(|e| BenchError::parse("vcd-ng", e))?;
+            let count = count_vcdng_vars(&header.items);
+            if count == 0 {
+                return Err(BenchError::parse("vcd-ng", "no variables found"));
+            }
+            Ok(())
+        });
+    }
+
+    // value_query using FastFlow
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "value_query", move || {
+            // First pass: parse header to get signal codes
+            let f = fs::File::open(&p)?;
+            let mut parser = vcd_ng::Parser::new(f);
+            let header = parser
+                .parse_header()
+                .map_err(|e| BenchError::parse("vcd-ng", e))?;
+            let mut codes = Vec::new();
+            collect_vcdng_codes(&header.items, &mut codes);
+            codes.truncate(10);
+            if codes.is_empty() {
+                return Err(BenchError::parse("vcd-ng", "no signals to query"));
+            }
+
+            // Second pass: use FastFlow for fast value streaming
+            let f2 = fs::File::open(&p)?;
+            let mut ff = vcd_ng::FastFlow::new(f2, 1 << 20); // 1MB buffer
+            let _ = ff
+                .first_timestamp()
+                .map_err(|e| BenchError::parse("vcd-ng", e))?;
+            let mut first = latency::FirstEvent::default();
+            let mut _match_count = 0u64;
+            loop {
+                match ff.next_token() {
+                    Ok(Some(vcd_ng::FastFlowToken::Value(vc))) => {
+                        first.hit();
+                        if codes.contains(&vc.id) {
+                            _match_count += 1;
+                        }
+                    }
+                    Ok(Some(_)) => {}
+                    Ok(None) => break,
+                    Err(e) => return Err(BenchError::parse("vcd-ng", e)),
+                }
+            }
+            Ok(())
+        });
+    }
+
+    // pipeline: header parse + FastFlow value query
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "pipeline", move || {
+            // 1+2. Parse header + signal list
+            let f = fs::File::open(&p)?;
+            let mut parser = vcd_ng::Parser::new(f);
+            let header = parser
+                .parse_header()
+                .map_err(|e| BenchError::parse("vcd-ng", e))?;
+            let mut codes = Vec::new();
+            collect_vcdng_codes(&header.items, &mut codes);
+            codes.truncate(10);
+            // 3+4. FastFlow streaming query
+            let f2 = fs::File::open(&p)?;
+            let mut ff = vcd_ng::FastFlow::new(f2, 1 << 20);
+            let _ = ff
+                .first_timestamp()
+                .map_err(|e| BenchError::parse("vcd-ng", e))?;
+            let mut first = latency::FirstEvent::default();
+            let mut _match_count = 0u64;
+            loop {
+                match ff.next_token() {
+                    Ok(Some(vcd_ng::FastFlowToken::Value(vc))) => {
+                        first.hit();
+                        if codes.contains(&vc.id) {
+                            _match_count += 1;
+                        }
+                    }
+                    Ok(Some(_)) => {}
+                    Ok(None) => break,
+                    Err(e) => return Err(BenchError::parse("vcd-ng", e)),
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Benchmark: wave_parse (VCD and FST)
+// ---------------------------------------------------------------------------
+
+/// Map an error of wave_parse opening a file.
+fn wave_parse_error(e: io::Error) -> BenchError {
+    match e.kind() {
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => {
+            BenchError::parse("wave_parse", e)
+        }
+        _ => BenchError::Io(e),
+    }
+}
+
+fn no_streaming_reader() -> BenchError {
+    BenchError::Unsupported("wave_parse has no streaming reader".to_string())
+}
+
+/// Open the VCD file at `path` with wave_parse, mapped or read into
+/// memory.
+fn open_wave_parse(path: &str, io: Io) -> Result<wave_parse::VcdFile, BenchError> {
+    match io {
+        Io::Read => fs::read(path).and_then(wave_parse::VcdFile::from_bytes),
+        Io::Mmap => wave_parse::VcdFile::open(path),
+        Io::Buffered => return Err(no_streaming_reader()),
+    }
+    .map_err(wave_parse_error)
+}
+
+/// Open the file at `path` with wave_parse as a waveform of `format`.
+fn open_wave_parse_as(
+    path: &str,
+    format: &str,
+    io: Io,
+) -> Result<Box<dyn wave_parse::Waveform>, BenchError> {
+    match format {
+        "fst" => match io {
+            Io::Read => fs::read(path).and_then(wave_parse::FstFile::from_bytes),
+            Io::Mmap => wave_parse::FstFile::open(path),
+            Io::Buffered => return Err(no_streaming_reader()),
+        }
+        .map(|fst| Box::new(fst) as Box<dyn wave_parse::Waveform>)
+        .map_err(wave_parse_error),
+        _ => Ok(Box::new(open_wave_parse(path, io)?)),
+    }
+}
+
+/// Load the signals of up to the first 10 variables of `wave`. They load
+/// whole, so no latency is recorded.
+fn query_wave_parse(wave: &mut dyn wave_parse::Waveform) -> Result<(), BenchError> {
+    let ids: Vec<wave_parse::SignalId> =
+        wave.hierarchy().signal_ids().into_iter().take(10).collect();
+    if ids.is_empty() {
+        return Err(BenchError::parse("wave_parse", "no signals to query"));
+    }
+    wave.load_signals(&ids)
+        .map_err(|e| BenchError::parse("wave_parse", e))?;
+    Ok(())
+}
+
+pub fn bench_wave_parse(file: &Path, format: &'static str, io: Io, cfg: &Config) {
+    let file_str = file.to_string_lossy().to_string();
+    let lib = &io.variant("wave_parse");
+
+    // full_parse: header + tokenize the whole body of a VCD file, or
+    // decode every signal of an FST file
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "full_parse", move || {
+            if format == "fst" {
+                // FST signals load whole, so there is no latency to record.
+                let mut wave = open_wave_parse_as(&p, format, io)?;
+                let ids = wave.hierarchy().signal_ids();
+                wave.load_signals(&ids)
+                    .map_err(|e| BenchError::parse("wave_parse", e))?;
+                return Ok(());
+            }
+            let vcd = open_wave_parse(&p, io)?;
+            let mut first = latency::FirstEvent::default();
+            for token in vcd.tokens() {
+                let token = token.map_err(|e| BenchError::parse("wave_parse", e))?;
+                if let wave_parse::vcd::Token::Change(_) = token {
+                    first.hit();
+                }
+            }
+            Ok(())
+        });
+    }
+
+    // signal_list
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "signal_list", move || {
+            let wave = open_wave_parse_as(&p, format, io)?;
+            if wave.hierarchy().var_count() == 0 {
+                return Err(BenchError::parse("wave_parse", "no variables found"));
+            }
+            Ok(())
+        });
+    }
+
+    // value_query: load the first 10 signals
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "value_query", move || {
+            let mut wave = open_wave_parse_as(&p, format, io)?;
+            query_wave_parse(&mut *wave)
+        });
+    }
+
+    // pipeline: open -> signal_list -> value_query in one flow
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "pipeline", move || {
+            let mut wave = open_wave_parse_as(&p, format, io)?;
+            if wave.hierarchy().var_count() == 0 {
+                return Err(BenchError::parse("wave_parse", "no variables found"));
+            }
+            query_wave_parse(&mut *wave)
+        });
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Benchmark: fst-reader (FST only, pure Rust)
+// ---------------------------------------------------------------------------
+
+#[cfg(feature = "fst-reader")]
+pub fn bench_fst_reader(file: &Path, io: Io, cfg: &Config) {
+    let file_str = file.to_string_lossy().to_string();
+    let lib = &io.variant("fst-reader");
+    let format = "fst";
+
+    // full_parse: open + read hierarchy + read all signals
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "full_parse", move || {
+            let f = Input::open(&p, io)?;
+            let mut reader =
+                fst_reader::FstReader::open(f).map_err(|e| BenchError::parse("fst-reader", e))?;
+            let mut _var_count = 0u64;
+            reader
+                .read_hierarchy(|entry| {
+                    if let fst_reader::FstHierarchyEntry::Var { .. } = entry {
+                        _var_count += 1;
+                    }
+                })
+                .map_err(|e| BenchError::parse("fst-reader", e))?;
+            let filter = fst_reader::FstFilter::all();
+            let mut first = latency::FirstEvent::default();
+            let mut _change_count = 0u64;
+            reader
+                .read_signals(&filter, |_time, _handle, _value| {
+                    first.hit();
+                    _change_count += 1;
+                })
+                .map_err(|e| BenchError::parse("fst-reader", e))?;
+            Ok(())
+        });
+    }
+
+    // signal_list
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "signal_list", move || {
+            let f = Input::open(&p, io)?;
+            let mut reader =
+                fst_reader::FstReader::open(f).map_err(|e| BenchError::parse("fst-reader", e))?;
+            let mut var_count = 0u64;
+            reader
+                .read_hierarchy(|entry| {
+                    if let fst_reader::FstHierarchyEntry::Var { .. } = entry {
+                        var_count += 1;
+                    }
+                })
+                .map_err(|e| BenchError::parse("fst-reader", e))?;
+            if var_count == 0 {
+                return Err(BenchError::parse("fst-reader", "no variables found"));
+            }
+            Ok(())
+        });
+    }
+
+    // value_query: read first 10 signal handles
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "value_query", move || {
+            let f = Input::open(&p, io)?;
+            let mut reader =
+                fst_reader::FstReader::open(f).map_err(|e| BenchError::parse("fst-reader", e))?;
+            let mut handles = Vec::new();
+            reader
+                .read_hierarchy(|entry| {
+                    if let fst_reader::FstHierarchyEntry::Var { handle, .. } = entry {
+                        if handles.len() < 10 {
+                            handles.push(handle);
+                        }
+                    }
+                })
+                .map_err(|e| BenchError::parse("fst-reader", e))?;
+            if handles.is_empty() {
+                return Err(BenchError::parse("fst-reader", "no signals to query"));
+            }
+            let filter = fst_reader::FstFilter::filter_signals(handles);
+            let mut first = latency::FirstEvent::default();
+            let mut _change_count = 0u64;
+            reader
+                .read_signals(&filter, |_time, _handle, _value| {
+                    first.hit();
+                    _change_count += 1;
+                })
+                .map_err(|e| BenchError::parse("fst-reader", e))?;
+            Ok(())
+        });
+    }
+
+    // pipeline
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "pipeline", move || {
+            let f = Input::open(&p, io)?;
+            let mut reader =
+                fst_reader::FstReader::open(f).map_err(|e| BenchError::parse("fst-reader", e))?;
+            // 1+2. Hierarchy + signal list
+            let mut handles = Vec::new();
+            reader
+                .read_hierarchy(|entry| {
+                    if let fst_reader::FstHierarchyEntry::Var { handle, .. } = entry {
+                        if handles.len() < 10 {
+                            handles.push(handle);
+                        }
+                    }
+                })
+                .map_err(|e| BenchError::parse("fst-reader", e))?;
+            // 3+4. Read values for selected signals
+            if !handles.is_empty() {
+                let filter = fst_reader::FstFilter::filter_signals(handles);
+                let mut first = latency::FirstEvent::default();
+                let mut _change_count = 0u64;
+                reader
+                    .read_signals(&filter, |_time, _handle, _value| {
+                        first.hit();
+                        _change_count += 1;
+                    })
+                    .map_err(|e| BenchError::parse("fst-reader", e))?;
+            }
+            Ok(())
+        });
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Benchmark: fstapi (FST only, C bindings)
+// ---------------------------------------------------------------------------
+
+#[cfg(feature = "fstapi")]
+pub fn bench_fstapi(file: &Path, cfg: &Config) {
+    let file_str = file.to_string_lossy().to_string();
+    let lib = "fstapi";
+    let format = "fst";
+
+    // full_parse: open + iterate vars + iterate all blocks
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "full_parse", move || {
+            let mut reader =
+                fstapi::Reader::open(&p).map_err(|e| BenchError::parse("fstapi", e))?;
+            for var_result in reader.vars() {
+                let _ = var_result.map_err(|e| BenchError::parse("fstapi", e))?;
+            }
+            reader.set_mask_all();
+            let mut first = latency::FirstEvent::default();
+            let mut _change_count = 0u64;
+            reader
+                .for_each_block(|_time, _handle, _value, _var_len| {
+                    first.hit();
+                    _change_count += 1;
+                })
+                .map_err(|e| BenchError::parse("fstapi", e))?;
+            Ok(())
+        });
+    }
+
+    // signal_list
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "signal_list", move || {
+            let mut reader =
+                fstapi::Reader::open(&p).map_err(|e| BenchError::parse("fstapi", e))?;
+            let mut var_count = 0u64;
+            for var_result in reader.vars() {
+                let _ = var_result.map_err(|e| BenchError::parse("fstapi", e))?;
+                var_count += 1;
+            }
+            if var_count == 0 {
+                return Err(BenchError::parse("fstapi", "no variables found"));
+            }
+            Ok(())
+        });
+    }
+
+    // value_query: collect first 10 handles, mask them, iterate
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "value_query", move || {
+            let mut reader =
+                fstapi::Reader::open(&p).map_err(|e| BenchError::parse("fstapi", e))?;
+            let mut handles = Vec::new();
+            for var_result in reader.vars() {
+                let (_, var) = var_result.map_err(|e| BenchError::parse("fstapi", e))?;
+                if handles.len() < 10 {
+                    handles.push(var.handle());
+                }
+            }
+            if handles.is_empty() {
+                return Err(BenchError::parse("fstapi", "no signals to query"));
+            }
+            reader.clear_mask_all();
+            for h in &handles {
+                reader.set_mask(*h);
+            }
+            let mut first = latency::FirstEvent::default();
+            let mut _change_count = 0u64;
+            reader
+                .for_each_block(|_time, _handle, _value, _var_len| {
+                    first.hit();
+                    _change_count += 1;
+                })
+                .map_err(|e| BenchError::parse("fstapi", e))?;
+            Ok(())
+        });
+    }
+
+    // pipeline
+    {
+        let p = file_str.clone();
+        run_cell(cfg, lib, format, &file_str, "pipeline", move || {
+            let mut reader =
+                fstapi::Reader::open(&p).map_err(|e| BenchError::parse("fstapi", e))?;
+            // 1+2. Signal list
+            let mut handles = Vec::new();
+            for var_result in reader.vars() {
+                let (_, var) = var_result.map_err(|e| BenchError::parse("fstapi", e))?;
+                if handles.len() < 10 {
+                    handles.push(var.handle());
+                }
+            }
+            // 3+4. Value query
+            if !handles.is_empty() {
+                reader.clear_mask_all();
+                for h in &handles {
+                    reader.set_mask(*h);
+                }
+                let mut first = latency::FirstEvent::default();
+                let mut _change_count = 0u64;
+                reader
+                    .for_each_block(|_time, _handle, _value, _var_len| {
+                        first.hit();
+                        _change_count += 1;
+                    })
+                    .map_err(|e| BenchError::parse("fstapi", e))?;
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn skips_backends_that_cannot_run() {
+        let backends = [
+            Backend {
+                name: "vcd-only",
+                libraries: &["vcd-only"],
+                formats: &["vcd"],
+                compiled: true,
+            },
+            Backend {
+                name: "absent",
+                libraries: &["absent-read", "absent-mmap"],
+                formats: &["vcd", "fst"],
+                compiled: false,
+            },
+            Backend {
+                name: "both",
+                libraries: &["both"],
+                formats: &["vcd", "fst"],
+                compiled: true,
+            },
+        ];
+        let absent = "built without the absent feature".to_string();
+        assert_eq!(
+            skipped(&backends, "vcd"),
+            [
+                ("absent-read", absent.clone()),
+                ("absent-mmap", absent.clone())
+            ]
+        );
+        assert_eq!(
+            skipped(&backends, "fst"),
+            [
+                ("vcd-only", "vcd-only does not read FST".to_string()),
+                ("absent-read", absent.clone()),
+                ("absent-mmap", absent),
+            ]
+        );
+        // A format no backend reads skips them all, before their builds.
+        assert_eq!(skipped(&backends, "ghw").len(), 4);
+        assert!(skipped(&backends, "ghw")[1]
+            .1
+            .ends_with("does not read GHW"));
+
+        // wave_parse is always built.
+        let vcd = skipped(BACKENDS, "vcd");
+        assert!(vcd
+            .iter()
+            .all(|(library, _)| !library.starts_with("wave_parse")));
+        assert!(vcd.contains(&("fstapi", "fstapi does not read VCD".to_string())));
+    }
+}
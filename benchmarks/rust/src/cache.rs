@@ -0,0 +1,58 @@
+//! Page cache control for input files.
+//!
+//! The first repetition of a cell runs with its input evicted from the
+//! page cache, so it measures a cold read, and later repetitions find the
+//! file cached. Eviction uses `posix_fadvise(POSIX_FADV_DONTNEED)`, which
+//! needs no privileges but only drops clean pages, and may be ignored.
+//!
+//! With `--prime-cache`, nothing is evicted: each input is instead read
+//! once before its first cell, so every repetition of every cell, the
+//! first library's included, finds it cached.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// Ask the kernel to drop `path` from the page cache, returning whether
+/// the request was accepted.
+pub fn evict(path: &Path) -> bool {
+    sys::evict(path)
+}
+
+/// Read all of `path` so that it is in the page cache, returning whether
+/// it could be read.
+pub fn prime(path: &Path) -> bool {
+    File::open(path)
+        .and_then(|mut file| io::copy(&mut file, &mut io::sink()))
+        .is_ok()
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::fs::File;
+    use std::os::raw::c_int;
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    const POSIX_FADV_DONTNEED: c_int = 4;
+
+    extern "C" {
+        fn posix_fadvise(fd: c_int, offset: i64, len: i64, advice: c_int) -> c_int;
+    }
+
+    pub fn evict(path: &Path) -> bool {
+        let Ok(file) = File::open(path) else {
+            return false;
+        };
+        // SAFETY: `file` is open for the call; offset and length 0 cover
+        // the whole file.
+        unsafe { posix_fadvise(file.as_raw_fd(), 0, 0, POSIX_FADV_DONTNEED) == 0 }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    pub fn evict(_path: &std::path::Path) -> bool {
+        false
+    }
+}
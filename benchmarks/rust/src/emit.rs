@@ -0,0 +1,95 @@
+//! Output formats of results, chosen with `--emit-format`.
+//!
+//! By default every result is printed as a JSON line when its cell is
+//! done. With `--emit-format github`, results are instead collected and
+//! printed at the end as one JSON array in the `customSmallerIsBetter`
+//! shape of github-action-benchmark, with a time and a peak memory entry
+//! per successful cell, for continuous-benchmarking dashboards.
+
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::BenchResult;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmitFormat {
+    /// One `BenchResult` per line, as cells finish.
+    Ndjson,
+    /// github-action-benchmark's `customSmallerIsBetter`, at the end.
+    Github,
+}
+
+impl FromStr for EmitFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<EmitFormat, String> {
+        match s {
+            "ndjson" | "json" => Ok(EmitFormat::Ndjson),
+            "github" | "customSmallerIsBetter" => Ok(EmitFormat::Github),
+            _ => Err(format!(
+                "unknown emit format {:?} (expected ndjson or github)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for EmitFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EmitFormat::Ndjson => "ndjson",
+            EmitFormat::Github => "github",
+        })
+    }
+}
+
+/// One benchmark of github-action-benchmark; smaller values are better.
+#[derive(Serialize)]
+pub struct Entry {
+    name: String,
+    unit: &'static str,
+    value: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    range: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extra: Option<String>,
+}
+
+/// The entries of a result: none for a failed cell.
+pub fn github(r: &BenchResult) -> Vec<Entry> {
+    if r.status != "ok" {
+        return Vec::new();
+    }
+    let file = Path::new(&r.file)
+        .file_name()
+        .map_or_else(|| r.file.clone(), |n| n.to_string_lossy().into_owned());
+    let mut name = format!("{} {} ({})", r.library, r.operation, file);
+    if r.allocator != "system" {
+        name = format!("{} [{}]", name, r.allocator);
+    }
+    let extra = format!(
+        "{} {}, {} repetitions",
+        r.format,
+        r.operation,
+        r.times.len()
+    );
+    let mut entries = vec![Entry {
+        name: name.clone(),
+        unit: "s",
+        value: r.mean,
+        range: Some(format!("± {:.6}", r.stdev)),
+        extra: Some(extra.clone()),
+    }];
+    if r.peak_memory_kb > 0 {
+        entries.push(Entry {
+            name: format!("{} - peak memory", name),
+            unit: "KB",
+            value: r.peak_memory_kb as f64,
+            range: None,
+            extra: Some(extra),
+        });
+    }
+    entries
+}
@@ -0,0 +1,115 @@
+//! Why a repetition failed.
+//!
+//! Backends, the worker and [`crate::benchmark`] report failures as a
+//! [`BenchError`], so the class of a failure can be told apart without
+//! matching on text. A result records the error's [`BenchError::status`]
+//! and its `Display` text.
+
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum BenchError {
+    /// The input could not be opened or read.
+    Io(io::Error),
+    /// A backend rejected the input, or found nothing in it to query.
+    Parse {
+        backend: &'static str,
+        message: String,
+    },
+    /// A repetition ran longer than its timeout.
+    Timeout(Duration),
+    /// A repetition panicked, or its worker died.
+    Panic(String),
+    /// The cell cannot run, such as a VCD-only backend on an FST file.
+    Unsupported(String),
+}
+
+impl BenchError {
+    /// A parse error of `backend`.
+    pub fn parse(backend: &'static str, message: impl fmt::Display) -> BenchError {
+        BenchError::Parse {
+            backend,
+            message: message.to_string(),
+        }
+    }
+
+    /// The status of a cell ending in this error.
+    pub fn status(&self) -> &'static str {
+        match self {
+            BenchError::Timeout(_) => "timeout",
+            BenchError::Unsupported(_) => "skipped",
+            _ => "error",
+        }
+    }
+}
+
+impl fmt::Display for BenchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BenchError::Io(e) => write!(f, "I/O error: {}", e),
+            BenchError::Parse { backend, message } => write!(f, "{}: {}", backend, message),
+            BenchError::Timeout(t) => write!(f, "timeout after {:.1}s", t.as_secs_f64()),
+            BenchError::Panic(msg) => write!(f, "panic: {}", msg),
+            BenchError::Unsupported(reason) => f.write_str(reason),
+        }
+    }
+}
+
+impl std::error::Error for BenchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BenchError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for BenchError {
+    fn from(e: io::Error) -> BenchError {
+        BenchError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn status_and_message() {
+        let cases = [
+            (
+                BenchError::from(io::Error::new(io::ErrorKind::NotFound, "no such file")),
+                "error",
+                "I/O error: no such file",
+            ),
+            (
+                BenchError::parse("wellen", "bad header"),
+                "error",
+                "wellen: bad header",
+            ),
+            (
+                BenchError::Timeout(Duration::from_millis(2500)),
+                "timeout",
+                "timeout after 2.5s",
+            ),
+            (
+                BenchError::Panic("index out of bounds".to_string()),
+                "error",
+                "panic: index out of bounds",
+            ),
+            (
+                BenchError::Unsupported("rust-vcd does not read FST".to_string()),
+                "skipped",
+                "rust-vcd does not read FST",
+            ),
+        ];
+        for (error, status, message) in cases {
+            assert_eq!(
+                (error.status(), error.to_string().as_str()),
+                (status, message)
+            );
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! I/O strategies for backends that accept more than one kind of input.
+//!
+//! fst-reader takes any seekable buffered reader, so it is benchmarked
+//! reading through a buffer and from a memory map, as separate library
+//! variants. wave_parse parses bytes in memory and has no streaming
+//! reader, so its variants read the whole file into memory first
+//! (`wave_parse-read`) or map it. The difference between the variants is
+//! the cost of the I/O strategy alone.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read, Seek, SeekFrom};
+
+use wave_parse::mmap::Mmap;
+
+/// How a backend reads its input file.
+#[derive(Clone, Copy, Debug)]
+pub enum Io {
+    /// Through a `BufReader`.
+    Buffered,
+    /// Read into memory whole before parsing.
+    Read,
+    /// From a read-only memory map.
+    Mmap,
+}
+
+impl Io {
+    /// The library name of the variant of `lib` reading this way.
+    pub fn variant(self, lib: &str) -> String {
+        match self {
+            Io::Buffered => lib.to_string(),
+            Io::Read => format!("{}-read", lib),
+            Io::Mmap => format!("{}-mmap", lib),
+        }
+    }
+}
+
+/// A seekable input read either way.
+pub enum Input {
+    Buffered(BufReader<File>),
+    Read(Cursor<Vec<u8>>),
+    Mapped(Cursor<Mmap>),
+}
+
+impl Input {
+    pub fn open(path: &str, io: Io) -> io::Result<Input> {
+        let mut file = File::open(path)?;
+        Ok(match io {
+            Io::Buffered => Input::Buffered(BufReader::new(file)),
+            Io::Read => {
+                let mut data = Vec::new();
+                file.read_to_end(&mut data)?;
+                Input::Read(Cursor::new(data))
+            }
+            Io::Mmap => Input::Mapped(Cursor::new(Mmap::open(&file)?)),
+        })
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Input::Buffered(r) => r.read(buf),
+            Input::Read(r) => r.read(buf),
+            Input::Mapped(r) => r.read(buf),
+        }
+    }
+}
+
+impl BufRead for Input {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self {
+            Input::Buffered(r) => r.fill_buf(),
+            Input::Read(r) => r.fill_buf(),
+            Input::Mapped(r) => r.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        match self {
+            Input::Buffered(r) => r.consume(amt),
+            Input::Read(r) => r.consume(amt),
+            Input::Mapped(r) => r.consume(amt),
+        }
+    }
+}
+
+impl Seek for Input {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Input::Buffered(r) => r.seek(pos),
+            Input::Read(r) => r.seek(pos),
+            Input::Mapped(r) => r.seek(pos),
+        }
+    }
+}
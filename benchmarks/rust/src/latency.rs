@@ -0,0 +1,55 @@
+//! Time to first event of streaming backends.
+//!
+//! A repetition starts the clock with [`start`], and a streaming backend
+//! calls [`FirstEvent::hit`] on every value change it delivers; the first
+//! hit records the latency since the start, which interactive viewers see
+//! as the time to first paint. Backends that load the whole file before
+//! returning anything record no latency. The clock is per thread, so hits
+//! from threads a backend spawns are ignored.
+
+use std::cell::Cell;
+use std::time::Instant;
+
+thread_local! {
+    static START: Cell<Option<Instant>> = const { Cell::new(None) };
+    static FIRST: Cell<Option<f64>> = const { Cell::new(None) };
+}
+
+/// Start timing a repetition on the calling thread.
+pub fn start() {
+    START.with(|s| s.set(Some(Instant::now())));
+    FIRST.with(|f| f.set(None));
+}
+
+/// The latency to the first event since [`start`], in seconds, if there
+/// was one.
+pub fn take() -> Option<f64> {
+    START.with(|s| s.set(None));
+    FIRST.with(|f| f.take())
+}
+
+fn mark() {
+    let Some(start) = START.with(|s| s.get()) else {
+        return;
+    };
+    FIRST.with(|f| {
+        if f.get().is_none() {
+            f.set(Some(start.elapsed().as_secs_f64()));
+        }
+    });
+}
+
+/// Marks the first of the events a backend delivers; later hits cost a
+/// branch.
+#[derive(Default)]
+pub struct FirstEvent(bool);
+
+impl FirstEvent {
+    #[inline]
+    pub fn hit(&mut self) {
+        if !self.0 {
+            self.0 = true;
+            mark();
+        }
+    }
+}
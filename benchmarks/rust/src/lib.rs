@@ -0,0 +1,805 @@
+//! The measurement machinery of wave-bench, for embedding.
+//!
+//! [`benchmark`] runs a function the given number of times on a worker
+//! thread under a timeout ([`run_with_timeout`]), evicting or priming its
+//! input first, and [`Reps::summarize`] turns the repetitions into a
+//! [`BenchResult`]. [`run_cell`] does both for one cell of the comparison
+//! and emits the result as [`Config`] says; the functions of [`backends`]
+//! run every cell of one library on one input. Failures are
+//! [`BenchError`]s. The `wave-bench` binary is a command line over this
+//! crate.
+
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub mod alloc;
+pub mod backends;
+pub mod cache;
+pub mod emit;
+pub mod error;
+pub mod input;
+pub mod latency;
+pub mod meta;
+pub mod numa;
+pub mod perf;
+pub mod procio;
+pub mod profile;
+pub mod rapl;
+pub mod rusage;
+pub mod summary;
+pub mod timeouts;
+pub mod trace;
+pub mod worker;
+
+use alloc::AllocCounts;
+use emit::EmitFormat;
+pub use error::BenchError;
+use meta::FileMeta;
+use perf::{Counters, PerfCounts};
+use procio::IoCounts;
+use profile::{HeapSummary, Profile};
+use rapl::Energy;
+use rusage::Usage;
+use timeouts::Timeouts;
+
+pub use worker::run as run_with_timeout;
+
+// ---------------------------------------------------------------------------
+// JSON output schema
+// ---------------------------------------------------------------------------
+
+/// Version of the result schema, kept in step with `benchmarks/schema.py`.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize, Default)]
+pub struct BenchResult {
+    pub schema_version: u32,
+    pub library: String,
+    pub format: String,
+    pub file: String,
+    pub operation: String,
+    /// What is in `file`.
+    pub input: FileMeta,
+    /// The global allocator the harness was built with.
+    pub allocator: &'static str,
+    /// The `--label` pairs of the run.
+    pub labels: BTreeMap<String, String>,
+    /// Wall time of each repetition, in seconds; the first ran with its
+    /// input evicted from the page cache, unless it was primed.
+    pub times: Vec<f64>,
+    /// CPU time in user and kernel mode of each repetition, parallel to
+    /// `times`, in seconds; empty where unavailable.
+    pub user_times: Vec<f64>,
+    pub sys_times: Vec<f64>,
+    /// Mean minor and major page faults per repetition.
+    pub minor_faults: Option<u64>,
+    pub major_faults: Option<u64>,
+    /// Statistics of the warm repetitions, or of the cold one if it was
+    /// the only one to succeed.
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    pub stdev: f64,
+    /// The first repetition, kept apart since a cold read of a large file
+    /// can take several times as long as a warm one.
+    pub cold: Option<Stats>,
+    /// The repetitions after the first.
+    pub warm: Option<Stats>,
+    /// Latency from the start of a repetition to the first value change
+    /// delivered, over the same repetitions as `mean`, for streaming
+    /// backends.
+    pub first_event: Option<Stats>,
+    /// Whether the input was evicted from the page cache before the first
+    /// repetition; if not, `cold` may have found it cached.
+    pub cold_evicted: bool,
+    /// Whether the input was read into the page cache before the first
+    /// cell using it, with `--prime-cache`; if so, no repetition is cold.
+    pub primed: bool,
+    /// Repetitions added because the cell was noisy, included in `times`.
+    pub extra_reps: usize,
+    /// Whether a repetition ran after one in an earlier cell timed out;
+    /// its stuck worker may still have been running, so the times and the
+    /// process-wide counters can include its work.
+    pub tainted: bool,
+    pub peak_memory_kb: u64,
+    /// Peak resident set size of each repetition, parallel to `times`;
+    /// empty where the peak cannot be reset between repetitions.
+    pub rep_memory_kb: Vec<u64>,
+    /// Mean hardware counts per repetition, if the kernel permits them.
+    pub perf: Option<PerfCounts>,
+    /// Mean allocations and bytes allocated per repetition, with the
+    /// `count-allocs` feature.
+    pub alloc_count: Option<u64>,
+    pub alloc_bytes: Option<u64>,
+    /// Mean I/O per repetition, from `/proc/self/io`.
+    pub io: Option<IoCounts>,
+    /// Mean CPU package energy per repetition in joules, and the power it
+    /// averages to in watts, where RAPL can be read.
+    pub energy_j: Option<f64>,
+    pub power_w: Option<f64>,
+    /// `ok`, `error`, `timeout`, or `skipped` for a cell that cannot run.
+    pub status: String,
+    /// What went wrong, or why the cell was skipped.
+    pub error: Option<String>,
+    /// Where the profile of the cell was written, if profiling.
+    pub profile: Option<String>,
+    /// Heap use over all repetitions, with `--profile heap`.
+    pub heap: Option<HeapSummary>,
+}
+
+/// Summary statistics of a set of repetition times, in seconds.
+#[derive(Serialize, Default)]
+pub struct Stats {
+    pub n: usize,
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    pub stdev: f64,
+}
+
+impl Stats {
+    pub fn of(times: &[f64]) -> Option<Stats> {
+        if times.is_empty() {
+            return None;
+        }
+        let (mean, min, max, stdev) = stats(times);
+        Some(Stats {
+            n: times.len(),
+            mean,
+            min,
+            max,
+            stdev,
+        })
+    }
+}
+
+/// Settings shared by every benchmark cell, and the state of the run.
+pub struct Config {
+    pub reps: usize,
+    /// Timeout of one repetition, per cell.
+    pub timeouts: Timeouts,
+    pub profile: Profile,
+    pub profile_dir: PathBuf,
+    pub noise: Option<Noise>,
+    /// Cells waiting for the noise pass.
+    pending: RefCell<Vec<Pending>>,
+    /// Read each input into the page cache before its first cell instead
+    /// of evicting it before every cell.
+    pub prime_cache: bool,
+    /// Whether each input primed so far could be read.
+    primed: RefCell<BTreeMap<PathBuf, bool>>,
+    /// The statistics of each input seen so far.
+    inputs: RefCell<BTreeMap<PathBuf, FileMeta>>,
+    pub emit_format: EmitFormat,
+    /// Entries kept for the end, with `--emit-format github`.
+    entries: RefCell<Vec<emit::Entry>>,
+    /// Every cell so far, for the summary at the end.
+    summary: RefCell<Vec<summary::Row>>,
+    /// Pairs attached to every result, from `--label KEY=VALUE`.
+    pub labels: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// `reps` repetitions of each cell under `timeouts`, printed as NDJSON,
+    /// with no profiling, noise pass, priming or labels.
+    pub fn new(reps: usize, timeouts: Timeouts) -> Config {
+        Config {
+            reps,
+            timeouts,
+            profile: Profile::Off,
+            profile_dir: PathBuf::from("profiles"),
+            noise: None,
+            pending: RefCell::new(Vec::new()),
+            prime_cache: false,
+            primed: RefCell::new(BTreeMap::new()),
+            inputs: RefCell::new(BTreeMap::new()),
+            emit_format: EmitFormat::Ndjson,
+            entries: RefCell::new(Vec::new()),
+            summary: RefCell::new(Vec::new()),
+            labels: BTreeMap::new(),
+        }
+    }
+
+    /// The entries kept for `--emit-format github`, as the JSON array
+    /// github-action-benchmark reads.
+    pub fn github_entries(&self) -> String {
+        serde_json::to_string_pretty(&*self.entries.borrow()).unwrap()
+    }
+
+    /// Print `result`, or keep it for the end in formats printed then,
+    /// stamped with the settings of the run.
+    fn emit(&self, mut result: BenchResult) {
+        result.schema_version = SCHEMA_VERSION;
+        result.allocator = alloc::NAME;
+        result.labels = self.labels.clone();
+        if result.status != "skipped" {
+            self.summary.borrow_mut().push(summary::Row {
+                file: result.file.clone(),
+                operation: result.operation.clone(),
+                library: result.library.clone(),
+                mean: (result.status == "ok").then_some(result.mean),
+            });
+        }
+        match self.emit_format {
+            EmitFormat::Ndjson => println!("{}", serde_json::to_string(&result).unwrap()),
+            EmitFormat::Github => self.entries.borrow_mut().extend(emit::github(&result)),
+        }
+    }
+
+    /// The lines of the summary of every cell so far.
+    pub fn summary(&self) -> Vec<String> {
+        summary::table(&self.summary.borrow())
+    }
+
+    /// The statistics of `input`, computed on its first cell.
+    fn input_meta(&self, input: &Path) -> FileMeta {
+        self.inputs
+            .borrow_mut()
+            .entry(input.to_path_buf())
+            .or_insert_with(|| {
+                let _span = trace::span("phase", "metadata", worker::current());
+                meta::of(input)
+            })
+            .clone()
+    }
+
+    /// Whether `input` is primed, priming it if this is its first cell.
+    fn prime(&self, input: &Path) -> bool {
+        if !self.prime_cache {
+            return false;
+        }
+        *self
+            .primed
+            .borrow_mut()
+            .entry(input.to_path_buf())
+            .or_insert_with(|| {
+                let _span = trace::span("phase", "prime", worker::current());
+                cache::prime(input)
+            })
+    }
+}
+
+/// Extra repetitions for noisy cells, given with `--noise-budget`.
+pub struct Noise {
+    /// Relative standard deviation above which a cell gets more
+    /// repetitions.
+    pub threshold: f64,
+    /// Time to spend on extra repetitions, over all cells.
+    pub budget: Duration,
+    /// Most repetitions of one cell.
+    pub max_reps: usize,
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+fn get_peak_memory_kb() -> u64 {
+    if let Ok(content) = fs::read_to_string("/proc/self/status") {
+        for line in content.lines() {
+            if line.starts_with("VmPeak:") {
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                if parts.len() >= 2 {
+                    return parts[1].parse().unwrap_or(0);
+                }
+            }
+        }
+    }
+    0
+}
+
+/// Reset the peak resident set size of the process to its current size,
+/// so `VmHWM` measures from now on. Needs Linux 4.0 or later.
+fn reset_peak_rss() -> bool {
+    fs::write("/proc/self/clear_refs", "5").is_ok()
+}
+
+/// The peak resident set size since the last reset, from `VmHWM`.
+fn get_peak_rss_kb() -> Option<u64> {
+    let content = fs::read_to_string("/proc/self/status").ok()?;
+    let line = content.lines().find(|l| l.starts_with("VmHWM:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+pub fn stats(times: &[f64]) -> (f64, f64, f64, f64) {
+    if times.is_empty() {
+        return (0.0, 0.0, 0.0, 0.0);
+    }
+    let n = times.len() as f64;
+    let mean = times.iter().sum::<f64>() / n;
+    let min = times.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = times.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let variance = if times.len() > 1 {
+        times.iter().map(|t| (t - mean).powi(2)).sum::<f64>() / (n - 1.0)
+    } else {
+        0.0
+    };
+    let stdev = variance.sqrt();
+    (mean, min, max, stdev)
+}
+
+/// What one repetition measured besides its wall time.
+#[derive(Default)]
+struct Sample {
+    /// Wall time of `f`, in seconds.
+    seconds: f64,
+    /// Seconds until the backend delivered its first value change.
+    first_event: Option<f64>,
+    perf: PerfCounts,
+    allocs: Option<AllocCounts>,
+    usage: Option<Usage>,
+    io: Option<IoCounts>,
+    memory_kb: Option<u64>,
+    /// CPU package energy, in joules.
+    energy_j: Option<f64>,
+}
+
+/// Run `f` once on the calling thread, measuring it.
+fn measure<F>(f: F) -> (Result<(), BenchError>, Sample)
+where
+    F: FnOnce() -> Result<(), BenchError>,
+{
+    let reset = reset_peak_rss();
+    let io = IoCounts::now();
+    let usage = Usage::now();
+    let allocs = AllocCounts::now();
+    let energy = Energy::now();
+    let counters = Counters::start();
+    let start = Instant::now();
+    latency::start();
+    let result = f();
+    let seconds = start.elapsed().as_secs_f64();
+    let first_event = latency::take();
+    let perf = counters.stop();
+    let energy_j = energy.and_then(|before| Some(Energy::now()?.joules_since(&before)));
+    let allocs = allocs.and_then(|before| Some(AllocCounts::now()?.since(before)));
+    let usage = usage.and_then(|before| Some(Usage::now()?.since(before)));
+    let io = io.and_then(|before| Some(IoCounts::now()?.since(before)));
+    let memory_kb = if reset { get_peak_rss_kb() } else { None };
+    (
+        result,
+        Sample {
+            seconds,
+            first_event,
+            perf,
+            allocs,
+            usage,
+            io,
+            memory_kb,
+            energy_j,
+        },
+    )
+}
+
+/// Mean of the values of the repetitions that have one.
+fn mean_u64(values: impl Iterator<Item = Option<u64>>) -> Option<u64> {
+    let values: Vec<u64> = values.flatten().collect();
+    if values.is_empty() {
+        return None;
+    }
+    Some(values.iter().sum::<u64>() / values.len() as u64)
+}
+
+/// A benchmark function, shared with the worker running its repetitions.
+pub type BenchFn = dyn Fn() -> Result<(), BenchError> + Send + Sync;
+
+/// The repetitions of a cell so far.
+pub struct Reps {
+    samples: Vec<Sample>,
+    /// Whether the first sample is the cold first repetition.
+    cold: bool,
+    cold_evicted: bool,
+    /// The input was primed, so the first sample is warm too.
+    primed: bool,
+    /// Repetitions added by the noise pass.
+    extra: usize,
+    peak_memory_kb: u64,
+    last_error: Option<BenchError>,
+    /// A repetition timed out, so no more are run.
+    timed_out: bool,
+    /// A repetition ran while an abandoned worker may have been busy.
+    tainted: bool,
+}
+
+impl Reps {
+    /// Evict `input` from the page cache, ready for a cold first repetition,
+    /// unless it is `primed`.
+    pub fn new(input: &Path, primed: bool) -> Reps {
+        let cold_evicted = !primed && {
+            let _span = trace::span("phase", "evict", worker::current());
+            cache::evict(input)
+        };
+        Reps {
+            samples: Vec::new(),
+            cold: false,
+            cold_evicted,
+            primed,
+            extra: 0,
+            peak_memory_kb: 0,
+            last_error: None,
+            timed_out: false,
+            tainted: false,
+        }
+    }
+
+    /// Run `f` once more on the worker. A timeout ends the cell, since its
+    /// worker is still busy with the repetition that timed out.
+    pub fn run(&mut self, f: &Arc<BenchFn>, timeout: Duration) {
+        let first = self.samples.is_empty() && self.last_error.is_none() && !self.primed;
+        let name = if first { "cold rep" } else { "rep" };
+        let _span = trace::span("rep", name, worker::current());
+        let f = Arc::clone(f);
+        self.tainted |= worker::tainted();
+        match worker::run(timeout, move || measure(&*f)) {
+            Ok((Ok(()), sample)) => {
+                self.cold |= first;
+                self.samples.push(sample);
+            }
+            Ok((Err(e), _)) => {
+                self.last_error = Some(e);
+            }
+            Err(e) => {
+                self.timed_out = matches!(e, BenchError::Timeout(_));
+                self.last_error = Some(e);
+            }
+        }
+        self.peak_memory_kb = get_peak_memory_kb();
+    }
+
+    pub fn times(&self) -> Vec<f64> {
+        self.samples.iter().map(|s| s.seconds).collect()
+    }
+
+    /// The repetitions after the cold one.
+    fn warm(&self) -> &[Sample] {
+        &self.samples[self.cold as usize..]
+    }
+
+    fn warm_times(&self) -> Vec<f64> {
+        self.warm().iter().map(|s| s.seconds).collect()
+    }
+
+    /// Standard deviation of the warm times relative to their mean.
+    fn relative_stdev(&self) -> f64 {
+        match Stats::of(&self.warm_times()) {
+            Some(w) if w.mean > 0.0 => w.stdev / w.mean,
+            _ => 0.0,
+        }
+    }
+
+    /// The result of the repetitions, without the identity of the cell.
+    pub fn summarize(self) -> BenchResult {
+        if self.samples.is_empty() {
+            return BenchResult {
+                peak_memory_kb: self.peak_memory_kb,
+                cold_evicted: self.cold_evicted,
+                primed: self.primed,
+                tainted: self.tainted,
+                status: self
+                    .last_error
+                    .as_ref()
+                    .map_or("error", BenchError::status)
+                    .into(),
+                error: self.last_error.map(|e| e.to_string()),
+                ..BenchResult::default()
+            };
+        }
+        let times = self.times();
+        let samples = &self.samples;
+        let cold = if self.cold {
+            Stats::of(&times[..1])
+        } else {
+            None
+        };
+        let warm = Stats::of(&self.warm_times());
+        let like_mean = if self.warm().is_empty() {
+            &self.samples[..]
+        } else {
+            self.warm()
+        };
+        let first_event: Vec<f64> = like_mean.iter().filter_map(|s| s.first_event).collect();
+        let (mean, min, max, stdev) = match &warm {
+            Some(w) => (w.mean, w.min, w.max, w.stdev),
+            None => stats(&times),
+        };
+        let counts: Vec<PerfCounts> = samples.iter().map(|s| s.perf).collect();
+        let perf = PerfCounts::mean(&counts);
+        let usage: Vec<Usage> = samples.iter().filter_map(|s| s.usage).collect();
+        let (user_times, sys_times) = if usage.len() == samples.len() {
+            usage.iter().map(|u| (u.user, u.sys)).unzip()
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        let energy: Vec<(f64, f64)> = samples
+            .iter()
+            .filter_map(|s| Some((s.energy_j?, s.seconds)))
+            .collect();
+        let joules: f64 = energy.iter().map(|e| e.0).sum();
+        let seconds: f64 = energy.iter().map(|e| e.1).sum();
+        let rep_memory_kb: Vec<u64> = samples.iter().filter_map(|s| s.memory_kb).collect();
+        let rep_memory_kb = if rep_memory_kb.len() == samples.len() {
+            rep_memory_kb
+        } else {
+            Vec::new()
+        };
+        BenchResult {
+            times,
+            user_times,
+            sys_times,
+            minor_faults: mean_u64(samples.iter().map(|s| s.usage.map(|u| u.minor_faults))),
+            major_faults: mean_u64(samples.iter().map(|s| s.usage.map(|u| u.major_faults))),
+            mean,
+            min,
+            max,
+            stdev,
+            cold,
+            warm,
+            first_event: Stats::of(&first_event),
+            cold_evicted: self.cold_evicted,
+            primed: self.primed,
+            extra_reps: self.extra,
+            tainted: self.tainted,
+            peak_memory_kb: self.peak_memory_kb,
+            rep_memory_kb,
+            perf: (!perf.is_empty()).then_some(perf),
+            alloc_count: mean_u64(samples.iter().map(|s| s.allocs.map(|a| a.count))),
+            alloc_bytes: mean_u64(samples.iter().map(|s| s.allocs.map(|a| a.bytes))),
+            io: IoCounts::mean(&samples.iter().filter_map(|s| s.io).collect::<Vec<_>>()),
+            energy_j: (!energy.is_empty()).then(|| joules / energy.len() as f64),
+            power_w: (seconds > 0.0).then(|| joules / seconds),
+            status: "ok".into(),
+            ..BenchResult::default()
+        }
+    }
+}
+
+/// Run `f` `reps` times, the first with `input` evicted from the page
+/// cache unless it is `primed`.
+pub fn benchmark(
+    input: &Path,
+    primed: bool,
+    reps: usize,
+    timeout: Duration,
+    f: &Arc<BenchFn>,
+) -> Reps {
+    let mut runs = Reps::new(input, primed);
+    for _ in 0..reps {
+        runs.run(f, timeout);
+        if runs.timed_out {
+            break;
+        }
+    }
+    runs
+}
+
+/// A cell whose result waits for the noise pass.
+struct Pending {
+    library: String,
+    format: String,
+    file: String,
+    operation: String,
+    input: FileMeta,
+    f: Arc<BenchFn>,
+    reps: Reps,
+    profile: Option<profile::Output>,
+}
+
+impl Pending {
+    /// Print the result, or keep it for the end in formats printed then.
+    fn emit(self, cfg: &Config) {
+        let mut result = self.reps.summarize();
+        if let Some(output) = self.profile {
+            result.profile = output.path.map(|p| p.to_string_lossy().into_owned());
+            result.heap = output.heap;
+        }
+        result.library = self.library;
+        result.format = self.format;
+        result.file = self.file;
+        result.operation = self.operation;
+        result.input = self.input;
+        cfg.emit(result);
+    }
+}
+
+/// Benchmark one cell, profiling it if asked to, and print its result, or
+/// keep it for the noise pass if there is one.
+pub fn run_cell<F>(cfg: &Config, library: &str, format: &str, file: &str, operation: &str, f: F)
+where
+    F: Fn() -> Result<(), BenchError> + Send + Sync + 'static,
+{
+    let f: Arc<BenchFn> = Arc::new(f);
+    let _span = trace::span(
+        "cell",
+        &format!("{} {}", library, operation),
+        worker::current(),
+    )
+    .arg("format", format)
+    .arg("file", file);
+    let stem = profile::cell_stem(library, format, file, operation);
+    let session = cfg.profile.start(&cfg.profile_dir, &stem);
+    let input = cfg.input_meta(Path::new(file));
+    let primed = cfg.prime(Path::new(file));
+    let timeout = cfg.timeouts.get(library, operation, file);
+    let reps = benchmark(Path::new(file), primed, cfg.reps, timeout, &f);
+    let cell = Pending {
+        library: library.to_string(),
+        format: format.to_string(),
+        file: file.to_string(),
+        operation: operation.to_string(),
+        input,
+        f,
+        reps,
+        profile: session.map(|s| {
+            let _span = trace::span("phase", "profile", worker::current());
+            s.finish()
+        }),
+    };
+    if cfg.noise.is_some() {
+        cfg.pending.borrow_mut().push(cell);
+    } else {
+        cell.emit(cfg);
+    }
+}
+
+/// Print a result with status `skipped` for a cell that cannot run, with
+/// `reason` as its [`BenchError::Unsupported`] error.
+pub fn skip_cell(
+    cfg: &Config,
+    library: &str,
+    format: &str,
+    file: &str,
+    operation: &str,
+    reason: &str,
+) {
+    let error = BenchError::Unsupported(reason.to_string());
+    cfg.emit(BenchResult {
+        library: library.to_string(),
+        format: format.to_string(),
+        file: file.to_string(),
+        operation: operation.to_string(),
+        input: cfg.input_meta(Path::new(file)),
+        status: error.status().into(),
+        error: Some(error.to_string()),
+        ..BenchResult::default()
+    });
+}
+
+/// The cell of `reps` the noise pass repeats next with `left` of its
+/// budget: the noisiest whose relative standard deviation is above the
+/// threshold, that has not timed out or reached `max_reps`. `None` ends the
+/// pass, also when that cell's repetition is expected to overrun the
+/// budget, as a quieter cell would only get the budget the noisiest could
+/// not use.
+fn next_noisy<'a, I>(reps: I, noise: &Noise, left: Duration) -> Option<usize>
+where
+    I: IntoIterator<Item = &'a Reps>,
+{
+    let (i, reps) = reps
+        .into_iter()
+        .enumerate()
+        .filter(|(_, r)| !r.timed_out && r.samples.len() < noise.max_reps)
+        .map(|(i, r)| (r.relative_stdev(), i, r))
+        .filter(|(rsd, _, _)| *rsd > noise.threshold)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, i, r)| (i, r))?;
+    let expected = Stats::of(&reps.warm_times()).map_or(0.0, |w| w.mean);
+    (left.as_secs_f64() > expected).then_some(i)
+}
+
+/// Spend the noise budget on repetitions of the cells whose relative
+/// standard deviation is above the threshold, noisiest first, then print
+/// every pending result.
+pub fn noise_pass(cfg: &Config) {
+    let mut cells = cfg.pending.take();
+    if let Some(noise) = &cfg.noise {
+        let deadline = Instant::now() + noise.budget;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            let Some(i) = next_noisy(cells.iter().map(|c| &c.reps), noise, left) else {
+                break;
+            };
+            let cell = &mut cells[i];
+            let name = format!("{} {}", cell.library, cell.operation);
+            let _span = trace::span("noise", &name, worker::current())
+                .arg("format", &cell.format)
+                .arg("file", &cell.file);
+            let timeout = cfg.timeouts.get(&cell.library, &cell.operation, &cell.file);
+            cell.reps.run(&cell.f, timeout);
+            cell.reps.extra += 1;
+        }
+    }
+    for cell in cells {
+        cell.emit(cfg);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Discover test files
+// ---------------------------------------------------------------------------
+
+/// The VCD and FST files in `data_dir`, sorted.
+pub fn discover_files(data_dir: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let mut vcd_files = Vec::new();
+    let mut fst_files = Vec::new();
+    if let Ok(entries) = fs::read_dir(data_dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if let Some(ext) = path.extension() {
+                match ext.to_string_lossy().as_ref() {
+                    "vcd" => vcd_files.push(path),
+                    "fst" => fst_files.push(path),
+                    _ => {}
+                }
+            }
+        }
+    }
+    vcd_files.sort();
+    fst_files.sort();
+    (vcd_files, fst_files)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Warm repetitions taking `times` seconds.
+    fn reps(times: &[f64]) -> Reps {
+        let mut reps = Reps::new(Path::new("/nonexistent"), true);
+        for &seconds in times {
+            reps.samples.push(Sample {
+                seconds,
+                ..Sample::default()
+            });
+        }
+        reps
+    }
+
+    fn noise(threshold: f64, max_reps: usize) -> Noise {
+        Noise {
+            threshold,
+            budget: Duration::from_secs(60),
+            max_reps,
+        }
+    }
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn repeats_the_noisiest_cell() {
+        // Relative standard deviations of 0, about 0.47 and about 0.09.
+        let cells = [reps(&[1.0, 1.0]), reps(&[1.0, 2.0]), reps(&[1.0, 1.1, 1.2])];
+        assert_eq!(next_noisy(&cells, &noise(0.05, 10), MINUTE), Some(1));
+        assert_eq!(next_noisy(&cells[2..], &noise(0.05, 10), MINUTE), Some(0));
+        // Nothing above the threshold.
+        assert_eq!(next_noisy(&cells, &noise(0.5, 10), MINUTE), None);
+        assert_eq!(next_noisy(&[], &noise(0.05, 10), MINUTE), None);
+    }
+
+    #[test]
+    fn skips_finished_cells() {
+        let mut timed_out = reps(&[1.0, 2.0]);
+        timed_out.timed_out = true;
+        let cells = [timed_out, reps(&[1.0, 1.5, 2.0, 2.5]), reps(&[1.0, 1.1])];
+        // The noisiest cell timed out, and the next has max_reps samples.
+        assert_eq!(next_noisy(&cells, &noise(0.05, 4), MINUTE), Some(2));
+        assert_eq!(next_noisy(&cells, &noise(0.05, 5), MINUTE), Some(1));
+        assert_eq!(next_noisy(&cells[..2], &noise(0.05, 4), MINUTE), None);
+    }
+
+    #[test]
+    fn stops_before_overrunning_the_budget() {
+        // The cold repetition does not count towards the expected time.
+        let mut slow = reps(&[30.0, 2.0, 4.0]);
+        slow.cold = true;
+        let cells = [slow, reps(&[0.1, 0.11])];
+        let n = noise(0.05, 10);
+        assert_eq!(next_noisy(&cells, &n, Duration::from_secs(4)), Some(0));
+        assert_eq!(next_noisy(&cells, &n, Duration::from_secs(3)), None);
+        assert_eq!(next_noisy(&cells, &n, Duration::ZERO), None);
+    }
+}
@@ -1,786 +1,136 @@
-use serde::Serialize;
-use std::env;
-use std::fs;
-use std::io::BufReader;
-use std::panic;
-use std::path::{Path, PathBuf};
-use std::sync::mpsc;
-use std::thread;
-use std::time::{Duration, Instant};
-
-// ---------------------------------------------------------------------------
-// JSON output schema
-// ---------------------------------------------------------------------------
-
-#[derive(Serialize)]
-struct BenchResult {
-    library: String,
-    format: String,
-    file: String,
-    operation: String,
-    times: Vec<f64>,
-    mean: f64,
-    min: f64,
-    max: f64,
-    stdev: f64,
-    peak_memory_kb: u64,
-    status: String,
-    error: Option<String>,
-}
-
-// ---------------------------------------------------------------------------
-// Helpers
-// ---------------------------------------------------------------------------
-
-fn get_peak_memory_kb() -> u64 {
-    if let Ok(content) = fs::read_to_string("/proc/self/status") {
-        for line in content.lines() {
-            if line.starts_with("VmPeak:") {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 2 {
-                    return parts[1].parse().unwrap_or(0);
-                }
-            }
-        }
-    }
-    0
-}
+//! The wave-bench command line: benchmarks every backend on every input
+//! in the data directory and prints the results.
 
-fn stats(times: &[f64]) -> (f64, f64, f64, f64) {
-    if times.is_empty() {
-        return (0.0, 0.0, 0.0, 0.0);
-    }
-    let n = times.len() as f64;
-    let mean = times.iter().sum::<f64>() / n;
-    let min = times.iter().cloned().fold(f64::INFINITY, f64::min);
-    let max = times.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
-    let variance = if times.len() > 1 {
-        times.iter().map(|t| (t - mean).powi(2)).sum::<f64>() / (n - 1.0)
-    } else {
-        0.0
+use std::collections::BTreeMap;
+use std::env;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+
+use wave_bench::backends;
+use wave_bench::emit::EmitFormat;
+use wave_bench::profile::Profile;
+use wave_bench::timeouts::Timeouts;
+use wave_bench::{alloc, discover_files, noise_pass, numa, trace, Config, Noise};
+
+/// Command-line options; anything else is a positional argument.
+struct Args {
+    positional: Vec<String>,
+    profile: Profile,
+    profile_dir: Option<PathBuf>,
+    numa_node: Option<u32>,
+    noise_budget: Option<f64>,
+    noise_threshold: Option<f64>,
+    max_reps: Option<usize>,
+    trace: Option<PathBuf>,
+    prime_cache: bool,
+    emit_format: EmitFormat,
+    labels: BTreeMap<String, String>,
+    timeouts: Option<PathBuf>,
+}
+
+fn usage_error(msg: &str) -> ! {
+    eprintln!("wave-bench: {}", msg);
+    eprintln!(
+        "usage: wave-bench [DATA_DIR [SCALE]] [--profile flamegraph|heap] [--profile-dir DIR] \
+         [--numa-node N] [--noise-budget SECS [--noise-threshold R] [--max-reps N]] \
+         [--trace FILE] [--prime-cache] [--emit-format ndjson|github] \
+         [--label KEY=VALUE]... [--timeouts FILE] [--list-backends]"
+    );
+    process::exit(2);
+}
+
+/// Parse the value of option `name` as a number.
+fn number<T: std::str::FromStr>(name: &str, value: String) -> T {
+    value
+        .parse()
+        .unwrap_or_else(|_| usage_error(&format!("{} needs a number, not {:?}", name, value)))
+}
+
+fn parse_args() -> Args {
+    let mut args = Args {
+        positional: Vec::new(),
+        profile: Profile::Off,
+        profile_dir: None,
+        numa_node: None,
+        noise_budget: None,
+        noise_threshold: None,
+        max_reps: None,
+        trace: None,
+        prime_cache: false,
+        emit_format: EmitFormat::Ndjson,
+        labels: BTreeMap::new(),
+        timeouts: None,
     };
-    let stdev = variance.sqrt();
-    (mean, min, max, stdev)
-}
-
-/// Run a closure with panic catching and timeout.
-fn run_with_timeout<F, R>(timeout_secs: u64, f: F) -> Result<R, String>
-where
-    F: FnOnce() -> R + Send + 'static,
-    R: Send + 'static,
-{
-    let (tx, rx) = mpsc::channel();
-    thread::spawn(move || {
-        let result = panic::catch_unwind(panic::AssertUnwindSafe(f));
-        let _ = tx.send(result);
-    });
-    match rx.recv_timeout(Duration::from_secs(timeout_secs)) {
-        Ok(Ok(val)) => Ok(val),
-        Ok(Err(panic_err)) => {
-            let msg = if let Some(s) = panic_err.downcast_ref::<&str>() {
-                s.to_string()
-            } else if let Some(s) = panic_err.downcast_ref::<String>() {
-                s.clone()
-            } else {
-                "unknown panic".to_string()
-            };
-            Err(format!("panic: {}", msg))
-        }
-        Err(_) => Err("timeout".to_string()),
-    }
-}
-
-/// Run a benchmark function `reps` times, returning timing results.
-fn benchmark<F>(reps: usize, timeout_secs: u64, f: F) -> BenchResult
-where
-    F: Fn() -> Result<(), String> + Send + Clone + 'static,
-{
-    let mut times = Vec::new();
-    let mut last_error = None;
-    for _ in 0..reps {
-        let ff = f.clone();
-        let start = Instant::now();
-        let result = run_with_timeout(timeout_secs, move || ff());
-        let elapsed = start.elapsed().as_secs_f64();
-        match result {
-            Ok(Ok(())) => times.push(elapsed),
-            Ok(Err(e)) => {
-                last_error = Some(e);
-            }
-            Err(e) => {
-                last_error = Some(e);
-            }
-        }
-    }
-    let peak_mem = get_peak_memory_kb();
-    if times.is_empty() {
-        BenchResult {
-            library: String::new(),
-            format: String::new(),
-            file: String::new(),
-            operation: String::new(),
-            times: vec![],
-            mean: 0.0,
-            min: 0.0,
-            max: 0.0,
-            stdev: 0.0,
-            peak_memory_kb: peak_mem,
-            status: "error".into(),
-            error: last_error,
-        }
-    } else {
-        let (mean, min, max, stdev) = stats(&times);
-        BenchResult {
-            library: String::new(),
-            format: String::new(),
-            file: String::new(),
-            operation: String::new(),
-            times,
-            mean,
-            min,
-            max,
-            stdev,
-            peak_memory_kb: peak_mem,
-            status: "ok".into(),
-            error: None,
-        }
-    }
-}
-
-fn emit(mut result: BenchResult, library: &str, format: &str, file: &str, operation: &str) {
-    result.library = library.to_string();
-    result.format = format.to_string();
-    result.file = file.to_string();
-    result.operation = operation.to_string();
-    println!("{}", serde_json::to_string(&result).unwrap());
-}
-
-// ---------------------------------------------------------------------------
-// Helpers for counting vars from rust-vcd / vcd-ng Header
-// ---------------------------------------------------------------------------
-
-fn count_vcd_vars(items: &[vcd::ScopeItem]) -> usize {
-    let mut count = 0;
-    for item in items {
-        match item {
-            vcd::ScopeItem::Var(_) => count += 1,
-            vcd::ScopeItem::Scope(scope) => count += count_vcd_vars(&scope.items),
-            _ => {}
-        }
-    }
-    count
-}
-
-fn collect_vcd_codes(items: &[vcd::ScopeItem], codes: &mut Vec<vcd::IdCode>) {
-    for item in items {
-        match item {
-            vcd::ScopeItem::Var(v) => codes.push(v.code),
-            vcd::ScopeItem::Scope(scope) => collect_vcd_codes(&scope.items, codes),
-            _ => {}
-        }
-    }
-}
-
-fn count_vcdng_vars(items: &[vcd_ng::ScopeItem]) -> usize {
-    let mut count = 0;
-    for item in items {
-        match item {
-            vcd_ng::ScopeItem::Var(_) => count += 1,
-            vcd_ng::ScopeItem::Scope(scope) => count += count_vcdng_vars(&scope.children),
-            _ => {}
-        }
-    }
-    count
-}
-
-fn collect_vcdng_codes(items: &[vcd_ng::ScopeItem], codes: &mut Vec<vcd_ng::IdCode>) {
-    for item in items {
-        match item {
-            vcd_ng::ScopeItem::Var(v) => codes.push(v.code),
-            vcd_ng::ScopeItem::Scope(scope) => collect_vcdng_codes(&scope.children, codes),
-            _ => {}
-        }
-    }
-}
-
-// ---------------------------------------------------------------------------
-// Benchmark: wellen (VCD + FST)
-// ---------------------------------------------------------------------------
-
-fn bench_wellen(file: &Path, format: &str, reps: usize, timeout: u64) {
-    let file_str = file.to_string_lossy().to_string();
-    let lib = "wellen";
-
-    // full_parse
-    {
-        let p = file_str.clone();
-        let r = benchmark(reps, timeout, move || {
-            let _wave = wellen::simple::read(&p).map_err(|e| format!("{}", e))?;
-            Ok(())
-        });
-        emit(r, lib, format, &file_str, "full_parse");
-    }
-
-    // signal_list
-    {
-        let p = file_str.clone();
-        let r = benchmark(reps, timeout, move || {
-            let wave = wellen::simple::read(&p).map_err(|e| format!("{}", e))?;
-            let count = wave.hierarchy().iter_vars().count();
-            if count == 0 {
-                return Err("no variables found".into());
-            }
-            Ok(())
-        });
-        emit(r, lib, format, &file_str, "signal_list");
-    }
-
-    // value_query
-    {
-        let p = file_str.clone();
-        let r = benchmark(reps, timeout, move || {
-            let mut wave = wellen::simple::read(&p).map_err(|e| format!("{}", e))?;
-            // pick up to 10 signals
-            let sig_refs: Vec<wellen::SignalRef> = wave
-                .hierarchy()
-                .iter_vars()
-                .take(10)
-                .map(|v| v.signal_ref())
-                .collect();
-            if sig_refs.is_empty() {
-                return Err("no signals to query".into());
-            }
-            wave.load_signals(&sig_refs);
-            for sr in &sig_refs {
-                let _ = wave.get_signal(*sr);
-            }
-            Ok(())
-        });
-        emit(r, lib, format, &file_str, "value_query");
-    }
-
-    // pipeline: load -> signal_list -> time_range -> value_query in one flow
-    {
-        let p = file_str.clone();
-        let r = benchmark(reps, timeout, move || {
-            // 1. Full parse
-            let mut wave = wellen::simple::read(&p).map_err(|e| format!("{}", e))?;
-            // 2. Signal list
-            let var_count = wave.hierarchy().iter_vars().count();
-            if var_count == 0 {
-                return Err("no variables found".into());
-            }
-            // 3. Time range
-            let _time_table = wave.time_table();
-            // 4. Value query (up to 10 signals)
-            let sig_refs: Vec<wellen::SignalRef> = wave
-                .hierarchy()
-                .iter_vars()
-                .take(10)
-                .map(|v| v.signal_ref())
-                .collect();
-            if !sig_refs.is_empty() {
-                wave.load_signals(&sig_refs);
-                for sr in &sig_refs {
-                    let _ = wave.get_signal(*sr);
+    let mut it = env::args().skip(1);
+    while let Some(arg) = it.next() {
+        let mut value = |name: &str| {
+            it.next()
+                .unwrap_or_else(|| usage_error(&format!("{} needs a value", name)))
+        };
+        match arg.as_str() {
+            "--profile" => {
+                args.profile = value("--profile")
+                    .parse()
+                    .unwrap_or_else(|e: String| usage_error(&e))
+            }
+            "--profile-dir" => args.profile_dir = Some(PathBuf::from(value("--profile-dir"))),
+            "--numa-node" => args.numa_node = Some(number(&arg, value(&arg))),
+            "--noise-budget" => args.noise_budget = Some(number(&arg, value(&arg))),
+            "--noise-threshold" => args.noise_threshold = Some(number(&arg, value(&arg))),
+            "--max-reps" => args.max_reps = Some(number(&arg, value(&arg))),
+            "--trace" => args.trace = Some(PathBuf::from(value("--trace"))),
+            "--prime-cache" => args.prime_cache = true,
+            "--list-backends" => {
+                for name in backends::compiled() {
+                    println!("{}", name);
                 }
-            }
-            Ok(())
-        });
-        emit(r, lib, format, &file_str, "pipeline");
-    }
-}
-
-// ---------------------------------------------------------------------------
-// Benchmark: rust-vcd (VCD only, streaming parser)
-// ---------------------------------------------------------------------------
-
-fn bench_rust_vcd(file: &Path, reps: usize, timeout: u64) {
-    let file_str = file.to_string_lossy().to_string();
-    let lib = "rust-vcd";
-    let format = "vcd";
-
-    // full_parse: parse header + iterate all commands
-    {
-        let p = file_str.clone();
-        let r = benchmark(reps, timeout, move || {
-            let f = fs::File::open(&p).map_err(|e| format!("{}", e))?;
-            let mut parser = vcd::Parser::new(BufReader::new(f));
-            let _header = parser.parse_header().map_err(|e| format!("{}", e))?;
-            for cmd in parser {
-                let _ = cmd.map_err(|e| format!("{}", e))?;
-            }
-            Ok(())
-        });
-        emit(r, lib, format, &file_str, "full_parse");
-    }
-
-    // signal_list: parse header and count variables
-    {
-        let p = file_str.clone();
-        let r = benchmark(reps, timeout, move || {
-            let f = fs::File::open(&p).map_err(|e| format!("{}", e))?;
-            let mut parser = vcd::Parser::new(BufReader::new(f));
-            let header = parser.parse_header().map_err(|e| format!("{}", e))?;
-            let count = count_vcd_vars(&header.items);
-            if count == 0 {
-                return Err("no variables found".into());
-            }
-            Ok(())
-        });
-        emit(r, lib, format, &file_str, "signal_list");
-    }
-
-    // value_query: parse header, then stream and filter first 10 signal codes
-    {
-        let p = file_str.clone();
-        let r = benchmark(reps, timeout, move || {
-            let f = fs::File::open(&p).map_err(|e| format!("{}", e))?;
-            let mut parser = vcd::Parser::new(BufReader::new(f));
-            let header = parser.parse_header().map_err(|e| format!("{}", e))?;
-            let mut codes = Vec::new();
-            collect_vcd_codes(&header.items, &mut codes);
-            codes.truncate(10);
-            if codes.is_empty() {
-                return Err("no signals to query".into());
-            }
-            let mut _match_count = 0u64;
-            for cmd in parser {
-                let cmd = cmd.map_err(|e| format!("{}", e))?;
-                match &cmd {
-                    vcd::Command::ChangeScalar(id, _)
-                    | vcd::Command::ChangeVector(id, _)
-                    | vcd::Command::ChangeReal(id, _)
-                    | vcd::Command::ChangeString(id, _) => {
-                        if codes.contains(id) {
-                            _match_count += 1;
-                        }
-                    }
-                    _ => {}
-                }
-            }
-            Ok(())
-        });
-        emit(r, lib, format, &file_str, "value_query");
-    }
-
-    // pipeline: continuous operation
-    {
-        let p = file_str.clone();
-        let r = benchmark(reps, timeout, move || {
-            let f = fs::File::open(&p).map_err(|e| format!("{}", e))?;
-            let mut parser = vcd::Parser::new(BufReader::new(f));
-            // 1+2. Parse header + signal list
-            let header = parser.parse_header().map_err(|e| format!("{}", e))?;
-            let mut codes = Vec::new();
-            collect_vcd_codes(&header.items, &mut codes);
-            codes.truncate(10);
-            // 3+4. Stream and filter values
-            let mut _match_count = 0u64;
-            for cmd in parser {
-                let cmd = cmd.map_err(|e| format!("{}", e))?;
-                match &cmd {
-                    vcd::Command::ChangeScalar(id, _)
-                    | vcd::Command::ChangeVector(id, _)
-                    | vcd::Command::ChangeReal(id, _)
-                    | vcd::Command::ChangeString(id, _) => {
-                        if codes.contains(id) {
-                            _match_count += 1;
-                        }
-                    }
-                    _ => {}
-                }
-            }
-            Ok(())
-        });
-        emit(r, lib, format, &file_str, "pipeline");
-    }
-}
-
-// ---------------------------------------------------------------------------
-// Benchmark: vcd-ng Parser mode (VCD only)
-// ---------------------------------------------------------------------------
-
-fn bench_vcdng_parser(file: &Path, reps: usize, timeout: u64) {
-    let file_str = file.to_string_lossy().to_string();
-    let lib = "vcd-ng";
-    let format = "vcd";
-
-    // full_parse
-    {
-        let p = file_str.clone();
-        let r = benchmark(reps, timeout, move || {
-            let f = fs::File::open(&p).map_err(|e| format!("{}", e))?;
-            let mut parser = vcd_ng::Parser::new(f);
-            let _header = parser.parse_header().map_err(|e| format!("{}", e))?;
-            for cmd in parser {
-                let _ = cmd.map_err(|e| format!("{}", e))?;
-            }
-            Ok(())
-        });
-        emit(r, lib, format, &file_str, "full_parse");
-    }
-
-    // signal_list
-    {
-        let p = file_str.clone();
-        let r = benchmark(reps, timeout, move || {
-            let f = fs::File::open(&p).map_err(|e| format!("{}", e))?;
-            let mut parser = vcd_ng::Parser::new(f);
-            let header = parser.parse_header().map_err(|e| format!("{}", e))?;
-            let count = count_vcdng_vars(&header.items);
-            if count == 0 {
-                return Err("no variables found".into());
-            }
-            Ok(())
-        });
-        emit(r, lib, format, &file_str, "signal_list");
-    }
-
-    // value_query using FastFlow
-    {
-        let p = file_str.clone();
-        let r = benchmark(reps, timeout, move || {
-            // First pass: parse header to get signal codes
-            let f = fs::File::open(&p).map_err(|e| format!("{}", e))?;
-            let mut parser = vcd_ng::Parser::new(f);
-            let header = parser.parse_header().map_err(|e| format!("{}", e))?;
-            let mut codes = Vec::new();
-            collect_vcdng_codes(&header.items, &mut codes);
-            codes.truncate(10);
-            if codes.is_empty() {
-                return Err("no signals to query".into());
-            }
-
-            // Second pass: use FastFlow for fast value streaming
-            let f2 = fs::File::open(&p).map_err(|e| format!("{}", e))?;
-            let mut ff = vcd_ng::FastFlow::new(f2, 1 << 20); // 1MB buffer
-            let _ = ff.first_timestamp().map_err(|e| format!("{}", e))?;
-            let mut _match_count = 0u64;
-            loop {
-                match ff.next_token() {
-                    Ok(Some(vcd_ng::FastFlowToken::Value(vc))) => {
-                        if codes.contains(&vc.id) {
-                            _match_count += 1;
-                        }
-                    }
-                    Ok(Some(_)) => {}
-                    Ok(None) => break,
-                    Err(e) => return Err(format!("{}", e)),
-                }
-            }
-            Ok(())
-        });
-        emit(r, lib, format, &file_str, "value_query");
-    }
-
-    // pipeline: header parse + FastFlow value query
-    {
-        let p = file_str.clone();
-        let r = benchmark(reps, timeout, move || {
-            // 1+2. Parse header + signal list
-            let f = fs::File::open(&p).map_err(|e| format!("{}", e))?;
-            let mut parser = vcd_ng::Parser::new(f);
-            let header = parser.parse_header().map_err(|e| format!("{}", e))?;
-            let mut codes = Vec::new();
-            collect_vcdng_codes(&header.items, &mut codes);
-            codes.truncate(10);
-            // 3+4. FastFlow streaming query
-            let f2 = fs::File::open(&p).map_err(|e| format!("{}", e))?;
-            let mut ff = vcd_ng::FastFlow::new(f2, 1 << 20);
-            let _ = ff.first_timestamp().map_err(|e| format!("{}", e))?;
-            let mut _match_count = 0u64;
-            loop {
-                match ff.next_token() {
-                    Ok(Some(vcd_ng::FastFlowToken::Value(vc))) => {
-                        if codes.contains(&vc.id) {
-                            _match_count += 1;
-                        }
-                    }
-                    Ok(Some(_)) => {}
-                    Ok(None) => break,
-                    Err(e) => return Err(format!("{}", e)),
-                }
-            }
-            Ok(())
-        });
-        emit(r, lib, format, &file_str, "pipeline");
-    }
-}
-
-// ---------------------------------------------------------------------------
-// Benchmark: fst-reader (FST only, pure Rust)
-// ---------------------------------------------------------------------------
-
-fn bench_fst_reader(file: &Path, reps: usize, timeout: u64) {
-    let file_str = file.to_string_lossy().to_string();
-    let lib = "fst-reader";
-    let format = "fst";
-
-    // full_parse: open + read hierarchy + read all signals
-    {
-        let p = file_str.clone();
-        let r = benchmark(reps, timeout, move || {
-            let f = fs::File::open(&p).map_err(|e| format!("{}", e))?;
-            let mut reader =
-                fst_reader::FstReader::open(BufReader::new(f)).map_err(|e| format!("{}", e))?;
-            let mut _var_count = 0u64;
-            reader
-                .read_hierarchy(|entry| {
-                    if let fst_reader::FstHierarchyEntry::Var { .. } = entry {
-                        _var_count += 1;
-                    }
-                })
-                .map_err(|e| format!("{}", e))?;
-            let filter = fst_reader::FstFilter::all();
-            let mut _change_count = 0u64;
-            reader
-                .read_signals(&filter, |_time, _handle, _value| {
-                    _change_count += 1;
-                })
-                .map_err(|e| format!("{}", e))?;
-            Ok(())
-        });
-        emit(r, lib, format, &file_str, "full_parse");
-    }
-
-    // signal_list
-    {
-        let p = file_str.clone();
-        let r = benchmark(reps, timeout, move || {
-            let f = fs::File::open(&p).map_err(|e| format!("{}", e))?;
-            let mut reader =
-                fst_reader::FstReader::open(BufReader::new(f)).map_err(|e| format!("{}", e))?;
-            let mut var_count = 0u64;
-            reader
-                .read_hierarchy(|entry| {
-                    if let fst_reader::FstHierarchyEntry::Var { .. } = entry {
-                        var_count += 1;
-                    }
-                })
-                .map_err(|e| format!("{}", e))?;
-            if var_count == 0 {
-                return Err("no variables found".into());
-            }
-            Ok(())
-        });
-        emit(r, lib, format, &file_str, "signal_list");
+                process::exit(0);
+            }
+            "--timeouts" => args.timeouts = Some(PathBuf::from(value("--timeouts"))),
+            "--label" => {
+                let label = value("--label");
+                let Some((key, val)) = label.split_once('=').filter(|(k, _)| !k.is_empty()) else {
+                    usage_error(&format!("--label needs KEY=VALUE, not {:?}", label));
+                };
+                args.labels.insert(key.to_string(), val.to_string());
+            }
+            "--emit-format" => {
+                args.emit_format = value("--emit-format")
+                    .parse()
+                    .unwrap_or_else(|e: String| usage_error(&e))
+            }
+            _ if arg.starts_with("--") => usage_error(&format!("unknown option {}", arg)),
+            _ => args.positional.push(arg),
+        }
     }
-
-    // value_query: read first 10 signal handles
-    {
-        let p = file_str.clone();
-        let r = benchmark(reps, timeout, move || {
-            let f = fs::File::open(&p).map_err(|e| format!("{}", e))?;
-            let mut reader =
-                fst_reader::FstReader::open(BufReader::new(f)).map_err(|e| format!("{}", e))?;
-            let mut handles = Vec::new();
-            reader
-                .read_hierarchy(|entry| {
-                    if let fst_reader::FstHierarchyEntry::Var { handle, .. } = entry {
-                        if handles.len() < 10 {
-                            handles.push(handle);
-                        }
-                    }
-                })
-                .map_err(|e| format!("{}", e))?;
-            if handles.is_empty() {
-                return Err("no signals to query".into());
-            }
-            let filter = fst_reader::FstFilter::filter_signals(handles);
-            let mut _change_count = 0u64;
-            reader
-                .read_signals(&filter, |_time, _handle, _value| {
-                    _change_count += 1;
-                })
-                .map_err(|e| format!("{}", e))?;
-            Ok(())
-        });
-        emit(r, lib, format, &file_str, "value_query");
-    }
-
-    // pipeline
-    {
-        let p = file_str.clone();
-        let r = benchmark(reps, timeout, move || {
-            let f = fs::File::open(&p).map_err(|e| format!("{}", e))?;
-            let mut reader =
-                fst_reader::FstReader::open(BufReader::new(f)).map_err(|e| format!("{}", e))?;
-            // 1+2. Hierarchy + signal list
-            let mut handles = Vec::new();
-            reader
-                .read_hierarchy(|entry| {
-                    if let fst_reader::FstHierarchyEntry::Var { handle, .. } = entry {
-                        if handles.len() < 10 {
-                            handles.push(handle);
-                        }
-                    }
-                })
-                .map_err(|e| format!("{}", e))?;
-            // 3+4. Read values for selected signals
-            if !handles.is_empty() {
-                let filter = fst_reader::FstFilter::filter_signals(handles);
-                let mut _change_count = 0u64;
-                reader
-                    .read_signals(&filter, |_time, _handle, _value| {
-                        _change_count += 1;
-                    })
-                    .map_err(|e| format!("{}", e))?;
-            }
-            Ok(())
-        });
-        emit(r, lib, format, &file_str, "pipeline");
+    if let Err(e) = args.profile.check() {
+        usage_error(&e);
     }
+    args
 }
 
-// ---------------------------------------------------------------------------
-// Benchmark: fstapi (FST only, C bindings)
-// ---------------------------------------------------------------------------
-
-fn bench_fstapi(file: &Path, reps: usize, timeout: u64) {
-    let file_str = file.to_string_lossy().to_string();
-    let lib = "fstapi";
-    let format = "fst";
-
-    // full_parse: open + iterate vars + iterate all blocks
-    {
-        let p = file_str.clone();
-        let r = benchmark(reps, timeout, move || {
-            let mut reader = fstapi::Reader::open(&p).map_err(|e| format!("{}", e))?;
-            for var_result in reader.vars() {
-                let _ = var_result.map_err(|e| format!("{}", e))?;
-            }
-            reader.set_mask_all();
-            let mut _change_count = 0u64;
-            reader
-                .for_each_block(|_time, _handle, _value, _var_len| {
-                    _change_count += 1;
-                })
-                .map_err(|e| format!("{}", e))?;
-            Ok(())
-        });
-        emit(r, lib, format, &file_str, "full_parse");
-    }
-
-    // signal_list
-    {
-        let p = file_str.clone();
-        let r = benchmark(reps, timeout, move || {
-            let mut reader = fstapi::Reader::open(&p).map_err(|e| format!("{}", e))?;
-            let mut var_count = 0u64;
-            for var_result in reader.vars() {
-                let _ = var_result.map_err(|e| format!("{}", e))?;
-                var_count += 1;
-            }
-            if var_count == 0 {
-                return Err("no variables found".into());
-            }
-            Ok(())
-        });
-        emit(r, lib, format, &file_str, "signal_list");
-    }
-
-    // value_query: collect first 10 handles, mask them, iterate
-    {
-        let p = file_str.clone();
-        let r = benchmark(reps, timeout, move || {
-            let mut reader = fstapi::Reader::open(&p).map_err(|e| format!("{}", e))?;
-            let mut handles = Vec::new();
-            for var_result in reader.vars() {
-                let (_, var) = var_result.map_err(|e| format!("{}", e))?;
-                if handles.len() < 10 {
-                    handles.push(var.handle());
-                }
-            }
-            if handles.is_empty() {
-                return Err("no signals to query".into());
-            }
-            reader.clear_mask_all();
-            for h in &handles {
-                reader.set_mask(*h);
-            }
-            let mut _change_count = 0u64;
-            reader
-                .for_each_block(|_time, _handle, _value, _var_len| {
-                    _change_count += 1;
-                })
-                .map_err(|e| format!("{}", e))?;
-            Ok(())
-        });
-        emit(r, lib, format, &file_str, "value_query");
-    }
-
-    // pipeline
-    {
-        let p = file_str.clone();
-        let r = benchmark(reps, timeout, move || {
-            let mut reader = fstapi::Reader::open(&p).map_err(|e| format!("{}", e))?;
-            // 1+2. Signal list
-            let mut handles = Vec::new();
-            for var_result in reader.vars() {
-                let (_, var) = var_result.map_err(|e| format!("{}", e))?;
-                if handles.len() < 10 {
-                    handles.push(var.handle());
-                }
-            }
-            // 3+4. Value query
-            if !handles.is_empty() {
-                reader.clear_mask_all();
-                for h in &handles {
-                    reader.set_mask(*h);
-                }
-                let mut _change_count = 0u64;
-                reader
-                    .for_each_block(|_time, _handle, _value, _var_len| {
-                        _change_count += 1;
-                    })
-                    .map_err(|e| format!("{}", e))?;
-            }
-            Ok(())
-        });
-        emit(r, lib, format, &file_str, "pipeline");
+fn main() {
+    let args = parse_args();
+    if args.trace.is_some() {
+        trace::enable();
     }
-}
-
-// ---------------------------------------------------------------------------
-// Discover test files
-// ---------------------------------------------------------------------------
 
-fn discover_files(data_dir: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
-    let mut vcd_files = Vec::new();
-    let mut fst_files = Vec::new();
-    if let Ok(entries) = fs::read_dir(data_dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if let Some(ext) = path.extension() {
-                match ext.to_string_lossy().as_ref() {
-                    "vcd" => vcd_files.push(path),
-                    "fst" => fst_files.push(path),
-                    _ => {}
-                }
-            }
+    if let Some(node) = args.numa_node {
+        if let Err(e) = numa::bind(node) {
+            eprintln!("wave-bench: cannot bind to NUMA node {}: {}", node, e);
+            process::exit(1);
         }
     }
-    vcd_files.sort();
-    fst_files.sort();
-    (vcd_files, fst_files)
-}
-
-// ---------------------------------------------------------------------------
-// Main
-// ---------------------------------------------------------------------------
-
-fn main() {
-    let args: Vec<String> = env::args().collect();
 
     let data_dir = env::var("DATA_DIR")
-        .or_else(|_| args.get(1).cloned().ok_or(()))
+        .or_else(|_| args.positional.first().cloned().ok_or(()))
         .unwrap_or_else(|_| "data".to_string());
 
     let _scale: usize = env::var("SCALE")
         .ok()
         .and_then(|s| s.parse().ok())
-        .or_else(|| args.get(2).and_then(|s| s.parse().ok()))
+        .or_else(|| args.positional.get(1).and_then(|s| s.parse().ok()))
         .unwrap_or(1);
 
     let reps: usize = env::var("REPS")
@@ -792,14 +142,45 @@ fn main() {
         .ok()
         .and_then(|s| s.parse().ok())
         .unwrap_or(300);
+    let timeouts = match &args.timeouts {
+        Some(path) => Timeouts::load(path, Duration::from_secs(timeout)).unwrap_or_else(|e| {
+            eprintln!("wave-bench: cannot read timeouts: {}", e);
+            process::exit(1);
+        }),
+        None => Timeouts::uniform(Duration::from_secs(timeout)),
+    };
+
+    let mut cfg = Config::new(reps, timeouts);
+    cfg.profile = args.profile;
+    if let Some(dir) = args.profile_dir {
+        cfg.profile_dir = dir;
+    }
+    cfg.noise = args.noise_budget.map(|budget| Noise {
+        threshold: args.noise_threshold.unwrap_or(0.05),
+        budget: Duration::from_secs_f64(budget),
+        max_reps: args.max_reps.unwrap_or(reps * 10),
+    });
+    cfg.prime_cache = args.prime_cache;
+    cfg.emit_format = args.emit_format;
+    cfg.labels = args.labels;
 
     let data_path = PathBuf::from(&data_dir);
     let (vcd_files, fst_files) = discover_files(&data_path);
 
     eprintln!(
-        "wave-bench: data_dir={}, reps={}, timeout={}s",
-        data_dir, reps, timeout
+        "wave-bench: data_dir={}, reps={}, timeout={}s, cell_timeouts={}, profile={}, \
+         numa_node={}, allocator={}, cache={}",
+        data_dir,
+        reps,
+        timeout,
+        cfg.timeouts.len(),
+        cfg.profile,
+        args.numa_node
+            .map_or_else(|| "none".to_string(), |n| n.to_string()),
+        alloc::NAME,
+        if cfg.prime_cache { "primed" } else { "evicted" }
     );
+    eprintln!("  Backends: {}", backends::compiled().join(", "));
     eprintln!(
         "  Found {} VCD files, {} FST files",
         vcd_files.len(),
@@ -809,29 +190,40 @@ fn main() {
     // --- VCD benchmarks ---
     for vcd_file in &vcd_files {
         eprintln!("  Benchmarking VCD: {}", vcd_file.display());
-
-        eprintln!("    wellen...");
-        bench_wellen(vcd_file, "vcd", reps, timeout);
-
-        eprintln!("    rust-vcd...");
-        bench_rust_vcd(vcd_file, reps, timeout);
-
-        eprintln!("    vcd-ng...");
-        bench_vcdng_parser(vcd_file, reps, timeout);
+        backends::bench_vcd(vcd_file, &cfg);
     }
 
     // --- FST benchmarks ---
     for fst_file in &fst_files {
         eprintln!("  Benchmarking FST: {}", fst_file.display());
+        backends::bench_fst(fst_file, &cfg);
+    }
 
-        eprintln!("    wellen...");
-        bench_wellen(fst_file, "fst", reps, timeout);
+    if let Some(noise) = &cfg.noise {
+        eprintln!(
+            "  Extra repetitions for cells noisier than {:.0}% (budget {:.0}s)...",
+            noise.threshold * 100.0,
+            noise.budget.as_secs_f64()
+        );
+    }
+    noise_pass(&cfg);
+    if cfg.emit_format == EmitFormat::Github {
+        println!("{}", cfg.github_entries());
+    }
 
-        eprintln!("    fst-reader...");
-        bench_fst_reader(fst_file, reps, timeout);
+    if let Some(path) = &args.trace {
+        match trace::write(path) {
+            Ok(()) => eprintln!("  Trace written to {}", path.display()),
+            Err(e) => eprintln!("wave-bench: cannot write trace {}: {}", path.display(), e),
+        }
+    }
 
-        eprintln!("    fstapi...");
-        bench_fstapi(fst_file, reps, timeout);
+    let table = cfg.summary();
+    if !table.is_empty() {
+        eprintln!("  Summary:");
+        for line in table {
+            eprintln!("{}", line);
+        }
     }
 
     eprintln!("wave-bench: done.");
@@ -0,0 +1,109 @@
+//! Structural statistics of input files.
+//!
+//! Every result row carries the statistics of its input, so times can be
+//! normalized by the amount of work in a file and a row says which file it
+//! was without the file at hand. They are computed once per file, on the
+//! first cell using it: VCD files are tokenized with wave_parse and FST
+//! files read with fst-reader, so a file either library cannot read, or an
+//! FST file in a build without fst-reader, gets its size alone.
+
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+#[cfg(feature = "fst-reader")]
+use std::{fs::File, io::BufReader};
+
+/// What is in an input file.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct FileMeta {
+    pub size_bytes: u64,
+    pub var_count: Option<u64>,
+    /// Value changes in the body, initial values included.
+    pub value_changes: Option<u64>,
+    /// The last timestamp, in ticks, the length of one tick in seconds,
+    /// and the simulated time they make.
+    pub end_time: Option<u64>,
+    pub tick_s: Option<f64>,
+    pub duration_s: Option<f64>,
+}
+
+struct Counts {
+    var_count: u64,
+    value_changes: u64,
+    end_time: u64,
+    tick_s: Option<f64>,
+}
+
+/// The statistics of `path`, which is read whole.
+pub fn of(path: &Path) -> FileMeta {
+    let size_bytes = fs::metadata(path).map_or(0, |m| m.len());
+    let counts = match path.extension().and_then(|e| e.to_str()) {
+        Some("vcd") => vcd(path),
+        Some("fst") => fst(path),
+        _ => None,
+    };
+    let Some(c) = counts else {
+        return FileMeta {
+            size_bytes,
+            ..FileMeta::default()
+        };
+    };
+    FileMeta {
+        size_bytes,
+        var_count: Some(c.var_count),
+        value_changes: Some(c.value_changes),
+        end_time: Some(c.end_time),
+        tick_s: c.tick_s,
+        duration_s: c.tick_s.map(|t| t * c.end_time as f64),
+    }
+}
+
+fn vcd(path: &Path) -> Option<Counts> {
+    let vcd = wave_parse::VcdFile::open(path).ok()?;
+    let header = vcd.header();
+    let mut counts = Counts {
+        var_count: header.flat().var_count() as u64,
+        value_changes: 0,
+        end_time: 0,
+        tick_s: header.timescale.map(|t| t.seconds()),
+    };
+    for token in vcd.tokens() {
+        match token.ok()? {
+            wave_parse::vcd::Token::Change(_) => counts.value_changes += 1,
+            wave_parse::vcd::Token::Timestamp(t) => counts.end_time = counts.end_time.max(t),
+            _ => {}
+        }
+    }
+    Some(counts)
+}
+
+#[cfg(not(feature = "fst-reader"))]
+fn fst(_path: &Path) -> Option<Counts> {
+    None
+}
+
+#[cfg(feature = "fst-reader")]
+fn fst(path: &Path) -> Option<Counts> {
+    let file = BufReader::new(File::open(path).ok()?);
+    let mut reader = fst_reader::FstReader::open(file).ok()?;
+    let header = reader.get_header();
+    let mut counts = Counts {
+        var_count: 0,
+        value_changes: 0,
+        end_time: header.end_time,
+        tick_s: Some(10f64.powi(header.timescale_exponent as i32)),
+    };
+    reader
+        .read_hierarchy(|entry| {
+            if let fst_reader::FstHierarchyEntry::Var { .. } = entry {
+                counts.var_count += 1;
+            }
+        })
+        .ok()?;
+    reader
+        .read_signals(&fst_reader::FstFilter::all(), |_time, _handle, _value| {
+            counts.value_changes += 1;
+        })
+        .ok()?;
+    Some(counts)
+}
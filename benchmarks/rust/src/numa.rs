@@ -0,0 +1,124 @@
+//! Binding the benchmark to one NUMA node.
+//!
+//! With `--numa-node N`, the process runs on the CPUs of node `N` and
+//! allocates only from its memory, so backends bound by memory bandwidth
+//! do not pay for traffic between sockets. Both settings are inherited by
+//! threads created afterwards, so binding happens before the worker
+//! starts.
+
+use std::fs;
+use std::io;
+
+/// The CPUs of `node`, from its `cpulist` in sysfs.
+fn node_cpus(node: u32) -> io::Result<Vec<usize>> {
+    let path = format!("/sys/devices/system/node/node{}/cpulist", node);
+    let list = fs::read_to_string(&path)
+        .map_err(|e| io::Error::new(e.kind(), format!("no NUMA node {}: {}", node, e)))?;
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("bad cpulist in {}", path),
+        )
+    };
+    let mut cpus = Vec::new();
+    for range in list.trim().split(',').filter(|r| !r.is_empty()) {
+        let (lo, hi) = range.split_once('-').unwrap_or((range, range));
+        let lo: usize = lo.parse().map_err(|_| invalid())?;
+        let hi: usize = hi.parse().map_err(|_| invalid())?;
+        cpus.extend(lo..=hi);
+    }
+    if cpus.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("NUMA node {} has no CPUs", node),
+        ));
+    }
+    Ok(cpus)
+}
+
+/// Bind the calling thread's CPUs and memory to `node`.
+pub fn bind(node: u32) -> io::Result<()> {
+    let cpus = node_cpus(node)?;
+    sys::bind(node, &cpus)
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::io;
+    use std::os::raw::{c_int, c_long, c_ulong};
+
+    #[cfg(target_arch = "x86_64")]
+    const SYS_SET_MEMPOLICY: c_long = 238;
+    #[cfg(target_arch = "aarch64")]
+    const SYS_SET_MEMPOLICY: c_long = 237;
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    const SYS_SET_MEMPOLICY: c_long = -1;
+
+    const MPOL_BIND: c_int = 2;
+
+    /// Bits in a `cpu_set_t` and in our node mask.
+    const MAX_CPUS: usize = 1024;
+    const WORD_BITS: usize = c_ulong::BITS as usize;
+
+    extern "C" {
+        fn syscall(num: c_long, ...) -> c_long;
+        fn sched_setaffinity(pid: c_int, size: usize, mask: *const c_ulong) -> c_int;
+    }
+
+    fn mask(bits: impl IntoIterator<Item = usize>) -> io::Result<[c_ulong; MAX_CPUS / WORD_BITS]> {
+        let mut mask = [0 as c_ulong; MAX_CPUS / WORD_BITS];
+        for bit in bits {
+            if bit >= MAX_CPUS {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("CPU or node {} out of range", bit),
+                ));
+            }
+            mask[bit / WORD_BITS] |= 1 << (bit % WORD_BITS);
+        }
+        Ok(mask)
+    }
+
+    pub fn bind(node: u32, cpus: &[usize]) -> io::Result<()> {
+        let cpu_mask = mask(cpus.iter().copied())?;
+        // SAFETY: `cpu_mask` is a `cpu_set_t` of the size passed; pid 0 is
+        // the calling thread.
+        let rc =
+            unsafe { sched_setaffinity(0, std::mem::size_of_val(&cpu_mask), cpu_mask.as_ptr()) };
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if SYS_SET_MEMPOLICY < 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "memory binding is not supported on this architecture",
+            ));
+        }
+        let node_mask = mask([node as usize])?;
+        // SAFETY: `node_mask` holds `MAX_CPUS` bits and outlives the call.
+        let rc = unsafe {
+            syscall(
+                SYS_SET_MEMPOLICY,
+                MPOL_BIND,
+                node_mask.as_ptr(),
+                MAX_CPUS as c_ulong,
+            )
+        };
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use std::io;
+
+    pub fn bind(_node: u32, _cpus: &[usize]) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "NUMA binding is only supported on Linux",
+        ))
+    }
+}
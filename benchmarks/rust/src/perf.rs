@@ -0,0 +1,244 @@
+//! Hardware performance counters via `perf_event_open(2)`.
+//!
+//! Counters are opened for the calling thread and inherited by threads it
+//! spawns afterwards, counting user space only so they work under the
+//! default `perf_event_paranoid` setting. Where the kernel refuses them
+//! (containers, other platforms), every count is `None`.
+
+use serde::Serialize;
+
+/// Counts of one benchmark repetition, `None` where unavailable.
+#[derive(Serialize, Clone, Copy, Default, Debug)]
+pub struct PerfCounts {
+    pub instructions: Option<u64>,
+    pub cycles: Option<u64>,
+    pub branch_misses: Option<u64>,
+    pub llc_misses: Option<u64>,
+}
+
+impl PerfCounts {
+    /// Per-field mean of `counts`, over the repetitions that have a value.
+    pub fn mean(counts: &[PerfCounts]) -> PerfCounts {
+        PerfCounts {
+            instructions: crate::mean_u64(counts.iter().map(|c| c.instructions)),
+            cycles: crate::mean_u64(counts.iter().map(|c| c.cycles)),
+            branch_misses: crate::mean_u64(counts.iter().map(|c| c.branch_misses)),
+            llc_misses: crate::mean_u64(counts.iter().map(|c| c.llc_misses)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_none()
+            && self.cycles.is_none()
+            && self.branch_misses.is_none()
+            && self.llc_misses.is_none()
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::fs::File;
+    use std::io::Read;
+    use std::os::raw::{c_int, c_long, c_ulong};
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+
+    #[cfg(target_arch = "x86_64")]
+    const SYS_PERF_EVENT_OPEN: c_long = 298;
+    #[cfg(target_arch = "aarch64")]
+    const SYS_PERF_EVENT_OPEN: c_long = 241;
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    const SYS_PERF_EVENT_OPEN: c_long = -1;
+
+    const PERF_TYPE_HARDWARE: u32 = 0;
+    const PERF_TYPE_HW_CACHE: u32 = 3;
+    const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+    const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+    const PERF_COUNT_HW_BRANCH_MISSES: u64 = 5;
+    /// Last-level cache, read accesses, misses.
+    const PERF_COUNT_HW_CACHE_LL_READ_MISS: u64 = 2 | (1 << 16);
+
+    const PERF_FORMAT_TOTAL_TIME_ENABLED: u64 = 1;
+    const PERF_FORMAT_TOTAL_TIME_RUNNING: u64 = 2;
+
+    const FLAG_DISABLED: u64 = 1 << 0;
+    const FLAG_INHERIT: u64 = 1 << 1;
+    const FLAG_EXCLUDE_KERNEL: u64 = 1 << 5;
+    const FLAG_EXCLUDE_HV: u64 = 1 << 6;
+
+    const PERF_EVENT_IOC_ENABLE: c_ulong = 0x2400;
+    const PERF_EVENT_IOC_DISABLE: c_ulong = 0x2401;
+    const PERF_EVENT_IOC_RESET: c_ulong = 0x2403;
+
+    /// `struct perf_event_attr`, up to `PERF_ATTR_SIZE_VER5`.
+    #[repr(C)]
+    #[derive(Default)]
+    struct PerfEventAttr {
+        type_: u32,
+        size: u32,
+        config: u64,
+        sample_period: u64,
+        sample_type: u64,
+        read_format: u64,
+        flags: u64,
+        wakeup_events: u32,
+        bp_type: u32,
+        config1: u64,
+        config2: u64,
+        branch_sample_type: u64,
+        sample_regs_user: u64,
+        sample_stack_user: u32,
+        clockid: i32,
+        sample_regs_intr: u64,
+        aux_watermark: u32,
+        sample_max_stack: u16,
+        reserved: u16,
+    }
+
+    extern "C" {
+        fn syscall(num: c_long, ...) -> c_long;
+        fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    }
+
+    pub struct Counter(File);
+
+    impl Counter {
+        fn open(type_: u32, config: u64) -> Option<Counter> {
+            if SYS_PERF_EVENT_OPEN < 0 {
+                return None;
+            }
+            let attr = PerfEventAttr {
+                type_,
+                size: std::mem::size_of::<PerfEventAttr>() as u32,
+                config,
+                read_format: PERF_FORMAT_TOTAL_TIME_ENABLED | PERF_FORMAT_TOTAL_TIME_RUNNING,
+                flags: FLAG_DISABLED | FLAG_INHERIT | FLAG_EXCLUDE_KERNEL | FLAG_EXCLUDE_HV,
+                ..PerfEventAttr::default()
+            };
+            // SAFETY: `attr` outlives the call; pid 0 and cpu -1 count the
+            // calling thread on any CPU.
+            let fd = unsafe {
+                syscall(
+                    SYS_PERF_EVENT_OPEN,
+                    &attr as *const PerfEventAttr,
+                    0 as c_int,
+                    -1 as c_int,
+                    -1 as c_int,
+                    0 as c_ulong,
+                )
+            };
+            if fd < 0 {
+                return None;
+            }
+            // SAFETY: the kernel handed us this descriptor and nothing else
+            // owns it.
+            Some(Counter(unsafe { File::from_raw_fd(fd as c_int) }))
+        }
+
+        pub fn instructions() -> Option<Counter> {
+            Counter::open(PERF_TYPE_HARDWARE, PERF_COUNT_HW_INSTRUCTIONS)
+        }
+
+        pub fn cycles() -> Option<Counter> {
+            Counter::open(PERF_TYPE_HARDWARE, PERF_COUNT_HW_CPU_CYCLES)
+        }
+
+        pub fn branch_misses() -> Option<Counter> {
+            Counter::open(PERF_TYPE_HARDWARE, PERF_COUNT_HW_BRANCH_MISSES)
+        }
+
+        pub fn llc_misses() -> Option<Counter> {
+            Counter::open(PERF_TYPE_HW_CACHE, PERF_COUNT_HW_CACHE_LL_READ_MISS)
+        }
+
+        fn ioctl(&self, request: c_ulong) {
+            // SAFETY: a perf event descriptor and an argument-less request.
+            unsafe {
+                ioctl(self.0.as_raw_fd(), request, 0 as c_ulong);
+            }
+        }
+
+        pub fn start(&self) {
+            self.ioctl(PERF_EVENT_IOC_RESET);
+            self.ioctl(PERF_EVENT_IOC_ENABLE);
+        }
+
+        /// Stop counting and read the count, scaled up if the counter was
+        /// multiplexed with others.
+        pub fn stop(&mut self) -> Option<u64> {
+            self.ioctl(PERF_EVENT_IOC_DISABLE);
+            let mut buf = [0u8; 24];
+            self.0.read_exact(&mut buf).ok()?;
+            let word = |i: usize| u64::from_ne_bytes(buf[i * 8..i * 8 + 8].try_into().unwrap());
+            let (value, enabled, running) = (word(0), word(1), word(2));
+            if running == 0 {
+                return None;
+            }
+            Some((value as u128 * enabled as u128 / running as u128) as u64)
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    pub struct Counter;
+
+    impl Counter {
+        pub fn instructions() -> Option<Counter> {
+            None
+        }
+        pub fn cycles() -> Option<Counter> {
+            None
+        }
+        pub fn branch_misses() -> Option<Counter> {
+            None
+        }
+        pub fn llc_misses() -> Option<Counter> {
+            None
+        }
+        pub fn start(&self) {}
+        pub fn stop(&mut self) -> Option<u64> {
+            None
+        }
+    }
+}
+
+/// The counters of one repetition, opened on the thread running it.
+pub struct Counters {
+    instructions: Option<sys::Counter>,
+    cycles: Option<sys::Counter>,
+    branch_misses: Option<sys::Counter>,
+    llc_misses: Option<sys::Counter>,
+}
+
+impl Counters {
+    /// Open and start the counters for the calling thread.
+    pub fn start() -> Counters {
+        let counters = Counters {
+            instructions: sys::Counter::instructions(),
+            cycles: sys::Counter::cycles(),
+            branch_misses: sys::Counter::branch_misses(),
+            llc_misses: sys::Counter::llc_misses(),
+        };
+        for c in [
+            &counters.instructions,
+            &counters.cycles,
+            &counters.branch_misses,
+            &counters.llc_misses,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            c.start();
+        }
+        counters
+    }
+
+    pub fn stop(mut self) -> PerfCounts {
+        PerfCounts {
+            instructions: self.instructions.as_mut().and_then(|c| c.stop()),
+            cycles: self.cycles.as_mut().and_then(|c| c.stop()),
+            branch_misses: self.branch_misses.as_mut().and_then(|c| c.stop()),
+            llc_misses: self.llc_misses.as_mut().and_then(|c| c.stop()),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! I/O statistics of the process from `/proc/self/io`.
+//!
+//! `rchar` counts bytes returned by read calls, page cache hits included,
+//! while `read_bytes` counts bytes the storage layer actually fetched. A
+//! backend reading a file twice doubles `rchar`; a cold page cache shows
+//! up in `read_bytes`. Memory-mapped reads show up in neither.
+
+use serde::Serialize;
+use std::fs;
+
+/// I/O of one benchmark repetition.
+#[derive(Serialize, Clone, Copy, Debug, Default)]
+pub struct IoCounts {
+    /// Bytes read by read-like system calls.
+    pub rchar: u64,
+    /// Bytes fetched from storage.
+    pub read_bytes: u64,
+    /// Number of read-like and write-like system calls.
+    pub syscr: u64,
+    pub syscw: u64,
+}
+
+impl IoCounts {
+    /// The counts of the process so far, or `None` where unavailable.
+    pub fn now() -> Option<IoCounts> {
+        let content = fs::read_to_string("/proc/self/io").ok()?;
+        let mut counts = IoCounts::default();
+        for line in content.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let Ok(value) = value.trim().parse() else {
+                continue;
+            };
+            match key {
+                "rchar" => counts.rchar = value,
+                "read_bytes" => counts.read_bytes = value,
+                "syscr" => counts.syscr = value,
+                "syscw" => counts.syscw = value,
+                _ => {}
+            }
+        }
+        Some(counts)
+    }
+
+    /// The I/O between `earlier` and `self`.
+    pub fn since(self, earlier: IoCounts) -> IoCounts {
+        IoCounts {
+            rchar: self.rchar.saturating_sub(earlier.rchar),
+            read_bytes: self.read_bytes.saturating_sub(earlier.read_bytes),
+            syscr: self.syscr.saturating_sub(earlier.syscr),
+            syscw: self.syscw.saturating_sub(earlier.syscw),
+        }
+    }
+
+    /// Per-field mean of `counts`, or `None` if there are none.
+    pub fn mean(counts: &[IoCounts]) -> Option<IoCounts> {
+        let mean = |f: fn(&IoCounts) -> u64| crate::mean_u64(counts.iter().map(|c| Some(f(c))));
+        Some(IoCounts {
+            rchar: mean(|c| c.rchar)?,
+            read_bytes: mean(|c| c.read_bytes)?,
+            syscr: mean(|c| c.syscr)?,
+            syscw: mean(|c| c.syscw)?,
+        })
+    }
+}
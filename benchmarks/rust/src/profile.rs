@@ -0,0 +1,237 @@
+//! Profiling of benchmark cells.
+//!
+//! With `--profile flamegraph`, every repetition of a cell runs under a
+//! sampling profiler and the cell's samples are written as a flamegraph
+//! SVG to the profile directory, one file per cell. With `--profile heap`,
+//! the cell runs under dhat instead: its totals and peak are added to the
+//! cell's result, and the allocation sites are written as a dhat JSON
+//! report, viewable with `dh_view.html`.
+//!
+//! Sampling needs the `flamegraph` cargo feature, which pulls in pprof-rs,
+//! and heap profiling the `dhat-heap` feature, which replaces the global
+//! allocator with dhat's and so slows every cell down.
+
+use serde::Serialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Sampling frequency of the flamegraph profiler, in Hz. Prime, so it
+/// does not beat with periodic work in the backends.
+#[cfg(feature = "flamegraph")]
+const FREQUENCY: i32 = 997;
+
+#[cfg(feature = "dhat-heap")]
+#[global_allocator]
+static ALLOC: dhat::Alloc = dhat::Alloc;
+
+/// What to profile cells with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Profile {
+    #[default]
+    Off,
+    Flamegraph,
+    Heap,
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Profile, String> {
+        match s {
+            "off" | "none" => Ok(Profile::Off),
+            "flamegraph" => Ok(Profile::Flamegraph),
+            "heap" => Ok(Profile::Heap),
+            _ => Err(format!(
+                "unknown profile mode {:?} (expected flamegraph or heap)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Profile::Off => "off",
+            Profile::Flamegraph => "flamegraph",
+            Profile::Heap => "heap",
+        })
+    }
+}
+
+impl Profile {
+    /// Whether this build can profile in this mode.
+    pub fn check(self) -> Result<(), String> {
+        match self {
+            Profile::Off => Ok(()),
+            Profile::Flamegraph if cfg!(feature = "flamegraph") => Ok(()),
+            Profile::Flamegraph => {
+                Err("--profile flamegraph needs a build with --features flamegraph".into())
+            }
+            Profile::Heap if cfg!(feature = "dhat-heap") => Ok(()),
+            Profile::Heap => Err("--profile heap needs a build with --features dhat-heap".into()),
+        }
+    }
+
+    /// Extension of the profile files written in this mode.
+    fn extension(self) -> &'static str {
+        match self {
+            Profile::Off => "",
+            Profile::Flamegraph => "svg",
+            Profile::Heap => "dhat.json",
+        }
+    }
+
+    /// Start profiling a cell, to be written to `dir` under a name made
+    /// from `stem`. Failures are reported and leave the cell unprofiled.
+    pub fn start(self, dir: &Path, stem: &str) -> Option<Session> {
+        let path = dir.join(format!("{}.{}", stem, self.extension()));
+        if self != Profile::Off {
+            if let Err(e) = std::fs::create_dir_all(dir) {
+                eprintln!("      creating {} failed: {}", dir.display(), e);
+                return None;
+            }
+        }
+        let kind = match self {
+            Profile::Off => return None,
+            Profile::Flamegraph => Kind::flamegraph()?,
+            Profile::Heap => Kind::heap(&path)?,
+        };
+        Some(Session { kind, path })
+    }
+}
+
+/// The name of the profiles of a cell, without extension: its library,
+/// the file stem, format and operation, with anything unusual in a file
+/// name replaced.
+pub fn cell_stem(library: &str, format: &str, file: &str, operation: &str) -> String {
+    let stem = Path::new(file)
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = format!("{}_{}_{}_{}", library, stem, format, operation);
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Heap use of a cell over all its repetitions, from dhat.
+#[derive(Serialize, Clone, Copy, Debug, Default)]
+pub struct HeapSummary {
+    /// Number of allocations.
+    pub total_blocks: u64,
+    pub total_bytes: u64,
+    /// Most memory allocated at once, and in how many blocks.
+    pub peak_blocks: u64,
+    pub peak_bytes: u64,
+}
+
+/// What profiling a cell produced.
+#[derive(Default)]
+pub struct Output {
+    pub path: Option<PathBuf>,
+    pub heap: Option<HeapSummary>,
+}
+
+enum Kind {
+    #[cfg(feature = "flamegraph")]
+    Flamegraph(pprof::ProfilerGuard<'static>),
+    #[cfg(feature = "dhat-heap")]
+    Heap(dhat::Profiler),
+}
+
+impl Kind {
+    #[cfg(feature = "flamegraph")]
+    fn flamegraph() -> Option<Kind> {
+        let guard = pprof::ProfilerGuardBuilder::default()
+            .frequency(FREQUENCY)
+            .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+            .build();
+        match guard {
+            Ok(guard) => Some(Kind::Flamegraph(guard)),
+            Err(e) => {
+                eprintln!("      profiler failed to start: {}", e);
+                None
+            }
+        }
+    }
+
+    #[cfg(not(feature = "flamegraph"))]
+    fn flamegraph() -> Option<Kind> {
+        None
+    }
+
+    #[cfg(feature = "dhat-heap")]
+    fn heap(path: &Path) -> Option<Kind> {
+        Some(Kind::Heap(
+            dhat::Profiler::builder().file_name(path).build(),
+        ))
+    }
+
+    #[cfg(not(feature = "dhat-heap"))]
+    fn heap(_path: &Path) -> Option<Kind> {
+        None
+    }
+}
+
+/// A running profile of one cell.
+pub struct Session {
+    kind: Kind,
+    path: PathBuf,
+}
+
+impl Session {
+    /// Stop profiling and write the profile.
+    #[cfg_attr(
+        not(any(feature = "flamegraph", feature = "dhat-heap")),
+        allow(unused_variables)
+    )]
+    pub fn finish(self) -> Output {
+        let path = self.path;
+        match self.kind {
+            #[cfg(feature = "flamegraph")]
+            Kind::Flamegraph(guard) => {
+                let written = guard
+                    .report()
+                    .build()
+                    .map_err(|e| format!("{}", e))
+                    .and_then(|report| {
+                        let file = std::fs::File::create(&path).map_err(|e| format!("{}", e))?;
+                        report.flamegraph(file).map_err(|e| format!("{}", e))
+                    });
+                match written {
+                    Ok(()) => Output {
+                        path: Some(path),
+                        heap: None,
+                    },
+                    Err(e) => {
+                        eprintln!("      writing {} failed: {}", path.display(), e);
+                        Output::default()
+                    }
+                }
+            }
+            #[cfg(feature = "dhat-heap")]
+            Kind::Heap(profiler) => {
+                let stats = dhat::HeapStats::get();
+                // Dropping the profiler writes its report.
+                drop(profiler);
+                Output {
+                    path: Some(path),
+                    heap: Some(HeapSummary {
+                        total_blocks: stats.total_blocks,
+                        total_bytes: stats.total_bytes,
+                        peak_blocks: stats.max_blocks as u64,
+                        peak_bytes: stats.max_bytes as u64,
+                    }),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! CPU package energy from RAPL, via the Linux powercap interface.
+//!
+//! The counters cover whole packages, so they include whatever else the
+//! machine runs; on a quiet machine the energy of a repetition is close to
+//! the energy of the backend. Reading `energy_uj` needs root on most
+//! kernels, and where it cannot be read every energy is `None`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const POWERCAP: &str = "/sys/class/powercap";
+
+/// A package counter and the value at which it wraps, in microjoules.
+struct Zone {
+    path: PathBuf,
+    range: u64,
+}
+
+fn read_u64(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// The readable package zones, found once.
+fn zones() -> &'static [Zone] {
+    static ZONES: OnceLock<Vec<Zone>> = OnceLock::new();
+    ZONES.get_or_init(|| {
+        let Ok(entries) = fs::read_dir(POWERCAP) else {
+            return Vec::new();
+        };
+        let mut zones: Vec<Zone> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|dir| {
+                // Packages only: subzones (`intel-rapl:0:0`) are part of
+                // their package, and `psys` overlaps them.
+                let name = fs::read_to_string(dir.join("name")).unwrap_or_default();
+                name.starts_with("package")
+            })
+            .filter_map(|dir| {
+                let path = dir.join("energy_uj");
+                read_u64(&path)?;
+                Some(Zone {
+                    range: read_u64(&dir.join("max_energy_range_uj")).unwrap_or(u64::MAX),
+                    path,
+                })
+            })
+            .collect();
+        zones.sort_by(|a, b| a.path.cmp(&b.path));
+        zones
+    })
+}
+
+/// Energy counter readings of every package.
+pub struct Energy(Vec<u64>);
+
+impl Energy {
+    /// The counters now, or `None` where RAPL cannot be read.
+    pub fn now() -> Option<Energy> {
+        let zones = zones();
+        if zones.is_empty() {
+            return None;
+        }
+        zones
+            .iter()
+            .map(|z| read_u64(&z.path))
+            .collect::<Option<Vec<_>>>()
+            .map(Energy)
+    }
+
+    /// Joules used by all packages between `earlier` and `self`,
+    /// allowing each counter to wrap once.
+    pub fn joules_since(&self, earlier: &Energy) -> f64 {
+        let uj: u64 = zones()
+            .iter()
+            .zip(self.0.iter().zip(&earlier.0))
+            .map(|(zone, (&now, &then))| {
+                if now >= then {
+                    now - then
+                } else {
+                    zone.range - then + now
+                }
+            })
+            .sum();
+        uj as f64 * 1e-6
+    }
+}
@@ -0,0 +1,104 @@
+//! Resource usage of the process via `getrusage(2)`.
+//!
+//! Usage is process-wide, so it covers threads a backend spawns; cells run
+//! one at a time, so the difference across a repetition is the usage of
+//! that repetition. Page faults show where a memory-mapped backend spends
+//! its time: major faults on first touch read the file from storage.
+
+/// Resource usage so far.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Usage {
+    /// CPU time in user and kernel mode, in seconds.
+    pub user: f64,
+    pub sys: f64,
+    /// Page faults served without and with I/O.
+    pub minor_faults: u64,
+    pub major_faults: u64,
+}
+
+impl Usage {
+    /// The usage of the process so far, or `None` where unavailable.
+    pub fn now() -> Option<Usage> {
+        sys::now()
+    }
+
+    /// The usage between `earlier` and `self`.
+    pub fn since(self, earlier: Usage) -> Usage {
+        Usage {
+            user: self.user - earlier.user,
+            sys: self.sys - earlier.sys,
+            minor_faults: self.minor_faults - earlier.minor_faults,
+            major_faults: self.major_faults - earlier.major_faults,
+        }
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::os::raw::{c_int, c_long};
+
+    use super::Usage;
+
+    const RUSAGE_SELF: c_int = 0;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct Timeval {
+        tv_sec: c_long,
+        tv_usec: c_long,
+    }
+
+    impl Timeval {
+        fn seconds(&self) -> f64 {
+            self.tv_sec as f64 + self.tv_usec as f64 * 1e-6
+        }
+    }
+
+    /// `struct rusage`.
+    #[repr(C)]
+    #[derive(Default)]
+    #[allow(dead_code)]
+    struct Rusage {
+        ru_utime: Timeval,
+        ru_stime: Timeval,
+        ru_maxrss: c_long,
+        ru_ixrss: c_long,
+        ru_idrss: c_long,
+        ru_isrss: c_long,
+        ru_minflt: c_long,
+        ru_majflt: c_long,
+        ru_nswap: c_long,
+        ru_inblock: c_long,
+        ru_oublock: c_long,
+        ru_msgsnd: c_long,
+        ru_msgrcv: c_long,
+        ru_nsignals: c_long,
+        ru_nvcsw: c_long,
+        ru_nivcsw: c_long,
+    }
+
+    extern "C" {
+        fn getrusage(who: c_int, usage: *mut Rusage) -> c_int;
+    }
+
+    pub fn now() -> Option<Usage> {
+        let mut usage = Rusage::default();
+        // SAFETY: `usage` is a valid `struct rusage` to fill in.
+        if unsafe { getrusage(RUSAGE_SELF, &mut usage) } != 0 {
+            return None;
+        }
+        Some(Usage {
+            user: usage.ru_utime.seconds(),
+            sys: usage.ru_stime.seconds(),
+            minor_faults: usage.ru_minflt as u64,
+            major_faults: usage.ru_majflt as u64,
+        })
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    pub fn now() -> Option<super::Usage> {
+        None
+    }
+}
@@ -0,0 +1,96 @@
+//! The table of winners printed to stderr at the end of a run.
+//!
+//! For each file and operation it names the fastest library and its mean
+//! time, and how many times slower each other library was; failed cells
+//! are listed as failed. The JSON results hold the same numbers.
+
+use std::collections::BTreeMap;
+
+/// The outcome of one cell.
+pub struct Row {
+    pub file: String,
+    pub operation: String,
+    pub library: String,
+    /// Mean time in seconds, or `None` if the cell failed.
+    pub mean: Option<f64>,
+}
+
+fn format_time(seconds: f64) -> String {
+    if seconds < 1e-3 {
+        format!("{:.1}us", seconds * 1e6)
+    } else if seconds < 1.0 {
+        format!("{:.2}ms", seconds * 1e3)
+    } else {
+        format!("{:.3}s", seconds)
+    }
+}
+
+/// The lines of the table; empty without rows.
+pub fn table(rows: &[Row]) -> Vec<String> {
+    let mut cells: BTreeMap<(&str, &str), Vec<&Row>> = BTreeMap::new();
+    for row in rows {
+        cells
+            .entry((&row.file, &row.operation))
+            .or_default()
+            .push(row);
+    }
+    let mut table = Vec::new();
+    for ((file, operation), mut libs) in cells {
+        libs.sort_by(|a, b| match (a.mean, b.mean) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        });
+        let (fastest, mean) = match libs[0].mean {
+            Some(mean) => (libs[0].library.as_str(), format_time(mean)),
+            None => ("-", "-".to_string()),
+        };
+        let others: Vec<String> = libs[1..]
+            .iter()
+            .map(|r| match (r.mean, libs[0].mean) {
+                (Some(m), Some(best)) if best > 0.0 => format!("{} {:.2}x", r.library, m / best),
+                (Some(_), _) => r.library.clone(),
+                (None, _) => format!("{} failed", r.library),
+            })
+            .collect();
+        let name = std::path::Path::new(file)
+            .file_name()
+            .map_or_else(|| file.to_string(), |n| n.to_string_lossy().into_owned());
+        table.push([
+            name,
+            operation.to_string(),
+            fastest.to_string(),
+            mean,
+            others.join(", "),
+        ]);
+    }
+    if table.is_empty() {
+        return Vec::new();
+    }
+    let header = ["file", "operation", "fastest", "mean", "slower by"].map(String::from);
+    table.insert(0, header);
+    let mut widths = [0; 4];
+    for row in &table {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.len());
+        }
+    }
+    table
+        .iter()
+        .map(|row| {
+            format!(
+                "  {:<w0$}  {:<w1$}  {:<w2$}  {:>w3$}  {}",
+                row[0],
+                row[1],
+                row[2],
+                row[3],
+                row[4],
+                w0 = widths[0],
+                w1 = widths[1],
+                w2 = widths[2],
+                w3 = widths[3],
+            )
+            .trim_end()
+            .to_string()
+        })
+        .collect()
+}
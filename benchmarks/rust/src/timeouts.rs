@@ -0,0 +1,78 @@
+//! Timeouts per cell, from `--timeouts FILE`.
+//!
+//! Without the file every repetition gets the one `TIMEOUT`. The file,
+//! which run_all.py derives from the times of earlier runs, gives cells
+//! their own: one per line, as tab-separated library, operation, input
+//! file name and seconds. Cells missing from it keep the default, so a new
+//! file or library still runs.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+pub struct Timeouts {
+    default: Duration,
+    cells: BTreeMap<(String, String, String), Duration>,
+}
+
+impl Timeouts {
+    /// `default` for every cell.
+    pub fn uniform(default: Duration) -> Timeouts {
+        Timeouts {
+            default,
+            cells: BTreeMap::new(),
+        }
+    }
+
+    /// The timeouts in `path`, with `default` for cells it does not list.
+    pub fn load(path: &Path, default: Duration) -> Result<Timeouts, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut timeouts = Timeouts::uniform(default);
+        for (n, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            let seconds = match fields[..] {
+                [_, _, _, secs] => secs.parse::<f64>().ok().filter(|s| *s > 0.0),
+                _ => None,
+            };
+            let Some(seconds) = seconds else {
+                return Err(format!(
+                    "{}:{}: expected library, operation, file and seconds",
+                    path.display(),
+                    n + 1
+                ));
+            };
+            let key = (
+                fields[0].to_string(),
+                fields[1].to_string(),
+                fields[2].to_string(),
+            );
+            timeouts.cells.insert(key, Duration::from_secs_f64(seconds));
+        }
+        Ok(timeouts)
+    }
+
+    /// How many cells have their own timeout.
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Whether every cell has the default.
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// The timeout of one repetition of a cell.
+    pub fn get(&self, library: &str, operation: &str, file: &str) -> Duration {
+        let name = Path::new(file)
+            .file_name()
+            .map_or_else(|| file.to_string(), |n| n.to_string_lossy().into_owned());
+        self.cells
+            .get(&(library.to_string(), operation.to_string(), name))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
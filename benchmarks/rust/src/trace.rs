@@ -0,0 +1,133 @@
+//! A timeline of the whole run in the Chrome trace event format.
+//!
+//! With `--trace FILE`, every cell becomes a slice on the track of the
+//! worker that ran it, with its phases (cache eviction, each repetition,
+//! writing the profile) nested inside, and the file opens in
+//! chrome://tracing or Perfetto. A worker replaced after a timeout gets a
+//! track of its own. Without `--trace`, spans cost a branch.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+/// One event; `ph` is `X` for a complete slice and `M` for metadata.
+#[derive(Serialize)]
+struct Event {
+    name: String,
+    cat: &'static str,
+    ph: &'static str,
+    /// Start and duration, in microseconds since the run started.
+    ts: f64,
+    dur: f64,
+    pid: u64,
+    tid: u64,
+    args: BTreeMap<&'static str, String>,
+}
+
+#[derive(Serialize)]
+#[allow(non_snake_case)]
+struct TraceFile {
+    traceEvents: Vec<Event>,
+    displayTimeUnit: &'static str,
+}
+
+struct Trace {
+    start: Instant,
+    events: Mutex<Vec<Event>>,
+}
+
+static TRACE: OnceLock<Trace> = OnceLock::new();
+
+/// Start recording; the run starts now.
+pub fn enable() {
+    let _ = TRACE.set(Trace {
+        start: Instant::now(),
+        events: Mutex::new(Vec::new()),
+    });
+}
+
+fn record(event: Event) {
+    if let Some(trace) = TRACE.get() {
+        trace.events.lock().unwrap().push(event);
+    }
+}
+
+fn micros(trace: &Trace, t: Instant) -> f64 {
+    t.duration_since(trace.start).as_secs_f64() * 1e6
+}
+
+/// Name the track of worker `tid`.
+pub fn name_track(tid: u64, name: &str) {
+    record(Event {
+        name: "thread_name".into(),
+        cat: "",
+        ph: "M",
+        ts: 0.0,
+        dur: 0.0,
+        pid: 1,
+        tid,
+        args: BTreeMap::from([("name", name.to_string())]),
+    });
+}
+
+/// A slice on track `tid`, recorded when dropped.
+pub struct Span {
+    inner: Option<(Event, Instant)>,
+}
+
+/// Start a slice named `name` in category `cat` on track `tid`.
+pub fn span(cat: &'static str, name: &str, tid: u64) -> Span {
+    let inner = TRACE.get().map(|_| {
+        let event = Event {
+            name: name.to_string(),
+            cat,
+            ph: "X",
+            ts: 0.0,
+            dur: 0.0,
+            pid: 1,
+            tid,
+            args: BTreeMap::new(),
+        };
+        (event, Instant::now())
+    });
+    Span { inner }
+}
+
+impl Span {
+    /// Attach `key: value` to the slice.
+    pub fn arg(mut self, key: &'static str, value: &str) -> Span {
+        if let Some((event, _)) = &mut self.inner {
+            event.args.insert(key, value.to_string());
+        }
+        self
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let (Some((mut event, start)), Some(trace)) = (self.inner.take(), TRACE.get()) else {
+            return;
+        };
+        event.ts = micros(trace, start);
+        event.dur = start.elapsed().as_secs_f64() * 1e6;
+        record(event);
+    }
+}
+
+/// Write the events recorded so far to `path`, if recording, and forget
+/// them.
+pub fn write(path: &Path) -> io::Result<()> {
+    let Some(trace) = TRACE.get() else {
+        return Ok(());
+    };
+    let file = TraceFile {
+        traceEvents: std::mem::take(&mut *trace.events.lock().unwrap()),
+        displayTimeUnit: "ms",
+    };
+    let json = serde_json::to_string(&file).map_err(io::Error::other)?;
+    fs::write(path, json)
+}
@@ -0,0 +1,132 @@
+//! The thread benchmark repetitions run on.
+//!
+//! Repetitions run one at a time on a long-lived worker thread, so a
+//! repetition does not pay for spawning a thread and the main thread can
+//! enforce a timeout. A thread cannot be killed, so when a repetition
+//! times out its worker is abandoned and the next job starts a new one;
+//! the abandoned thread exits once the stuck job returns. Until then it
+//! keeps using the CPU and memory that later repetitions measure through
+//! process-wide counters (rusage, perf, peak RSS), so after the first
+//! timeout every repetition in the process is [`tainted`]. Each worker has
+//! a number, which is its track in the `--trace` timeline.
+
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use crate::error::BenchError;
+use crate::trace;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct Worker {
+    id: u64,
+    jobs: mpsc::Sender<Job>,
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A repetition timed out and its worker was abandoned.
+static ABANDONED: AtomicBool = AtomicBool::new(false);
+
+impl Worker {
+    fn spawn() -> Worker {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        trace::name_track(id, &format!("bench-worker {}", id));
+        let (jobs, rx) = mpsc::channel::<Job>();
+        thread::Builder::new()
+            .name("bench-worker".into())
+            .spawn(move || {
+                for job in rx {
+                    job();
+                }
+            })
+            .expect("failed to spawn benchmark worker");
+        Worker { id, jobs }
+    }
+}
+
+thread_local! {
+    static WORKER: RefCell<Option<Worker>> = const { RefCell::new(None) };
+}
+
+/// Whether a worker was abandoned to a repetition that timed out, so that
+/// repetitions from now on may share the process with it.
+pub fn tainted() -> bool {
+    ABANDONED.load(Ordering::Relaxed)
+}
+
+/// The number of the worker the next job runs on, starting it if there is
+/// none.
+pub fn current() -> u64 {
+    WORKER.with(|worker| worker.borrow_mut().get_or_insert_with(Worker::spawn).id)
+}
+
+/// Run `f` on the worker, waiting at most `timeout` for it. A panic in `f`
+/// is caught and returned as an error.
+pub fn run<F, R>(timeout: Duration, f: F) -> Result<R, BenchError>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    let job: Job = Box::new(move || {
+        let _ = tx.send(panic::catch_unwind(AssertUnwindSafe(f)));
+    });
+    WORKER.with(|worker| {
+        let mut worker = worker.borrow_mut();
+        if let Err(mpsc::SendError(job)) = worker.get_or_insert_with(Worker::spawn).jobs.send(job) {
+            // The worker died; start another.
+            let fresh = Worker::spawn();
+            let _ = fresh.jobs.send(job);
+            *worker = Some(fresh);
+        }
+    });
+    match rx.recv_timeout(timeout) {
+        Ok(Ok(val)) => Ok(val),
+        Ok(Err(panic_err)) => {
+            let msg = if let Some(s) = panic_err.downcast_ref::<&str>() {
+                s.to_string()
+            } else if let Some(s) = panic_err.downcast_ref::<String>() {
+                s.clone()
+            } else {
+                "unknown panic".to_string()
+            };
+            Err(BenchError::Panic(msg))
+        }
+        Err(RecvTimeoutError::Timeout) => {
+            ABANDONED.store(true, Ordering::Relaxed);
+            WORKER.with(|worker| worker.borrow_mut().take());
+            Err(BenchError::Timeout(timeout))
+        }
+        Err(RecvTimeoutError::Disconnected) => {
+            WORKER.with(|worker| worker.borrow_mut().take());
+            Err(BenchError::Panic("benchmark worker exited".to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn replaces_a_timed_out_worker() {
+        assert_eq!(run(Duration::from_secs(60), || 1).unwrap(), 1);
+        let first = current();
+
+        // A job that does not return until released.
+        let (release, stuck) = mpsc::channel::<()>();
+        let result = run(Duration::from_millis(10), move || stuck.recv());
+        assert!(matches!(result, Err(BenchError::Timeout(_))));
+        assert!(tainted());
+
+        // The next job runs on a new worker.
+        assert_ne!(current(), first);
+        assert_eq!(run(Duration::from_secs(60), || 2).unwrap(), 2);
+        release.send(()).unwrap();
+    }
+}
@@ -0,0 +1,162 @@
+"""The schema of benchmark result files, and migration of older files.
+
+Every record the benchmarks emit carries `schema_version`: each Python
+script's output, each Rust result line, and the combined and sweep files
+written by run_all.py. Files written before the field existed are version
+0. `migrate` brings a file of any known version up to SCHEMA_VERSION, so
+readers only handle the current schema, and `validate` lists what is wrong
+with a file, so that tooling breaks loudly instead of silently.
+
+Bump SCHEMA_VERSION, here and in the scripts under python/ and the Rust
+harness, when a field is renamed, removed or changes meaning, and add a
+step to MIGRATIONS. Adding a field that readers may ignore needs no bump.
+
+`python run_all.py validate [FILE ...]` validates result files.
+"""
+
+import json
+
+SCHEMA_VERSION = 1
+
+# "skipped" marks a cell that cannot run, such as a VCD-only library on an
+# FST file, with the reason as its error
+STATUSES = {"ok", "error", "timeout", "skipped"}
+
+NUMBER = (int, float)
+
+
+def _migrate_0(doc):
+    """Version 0 had no `schema_version`, and Rust results from before the
+    allocator comparison have no `allocator`."""
+    for r in doc.get("rust_results", []):
+        if isinstance(r, dict) and "operation" in r:
+            r.setdefault("allocator", "system")
+    return doc
+
+
+# MIGRATIONS[v] turns a version v file into a version v + 1 file
+MIGRATIONS = {0: _migrate_0}
+
+
+def _stamp(doc, version):
+    doc["schema_version"] = version
+    for record in doc.get("python_results", []) + doc.get("rust_results", []):
+        if isinstance(record, dict):
+            record["schema_version"] = version
+
+
+def migrate(doc):
+    """Bring a combined or sweep results document up to SCHEMA_VERSION.
+
+    Raises ValueError for a version newer than this code knows.
+    """
+    version = doc.get("schema_version", 0)
+    if version > SCHEMA_VERSION:
+        raise ValueError(
+            f"schema version {version} is newer than {SCHEMA_VERSION}; update the benchmark scripts"
+        )
+    while version < SCHEMA_VERSION:
+        doc = MIGRATIONS[version](doc)
+        version += 1
+        _stamp(doc, version)
+    return doc
+
+
+def _check(errors, where, record, fields):
+    """Append to `errors` each of `fields` ({name: type}) that `record`
+    lacks or holds with the wrong type."""
+    if not isinstance(record, dict):
+        errors.append(f"{where}: expected an object, got {type(record).__name__}")
+        return False
+    for name, kind in fields.items():
+        if name not in record:
+            errors.append(f"{where}: missing {name!r}")
+        elif not isinstance(record[name], kind) or (isinstance(record[name], bool) and kind is NUMBER):
+            errors.append(f"{where}: {name!r} has type {type(record[name]).__name__}")
+    return True
+
+
+def _check_times(errors, where, times):
+    if isinstance(times, list) and not all(isinstance(t, NUMBER) for t in times):
+        errors.append(f"{where}: repetition times must be numbers")
+
+
+def _check_status(errors, where, record):
+    status = record.get("status")
+    if isinstance(status, str) and status not in STATUSES:
+        errors.append(f"{where}: unknown status {status!r}")
+
+
+def _check_python_payload(errors, where, payload):
+    """A Python script's output, or a wrapped error record of run_all.py."""
+    if not _check(errors, where, payload, {
+        "schema_version": int, "library": str, "format": str, "results": list,
+    }):
+        return
+    for i, r in enumerate(payload.get("results") or []):
+        at = f"{where}.results[{i}]"
+        if _check(errors, at, r, {
+            "test": str, "file": str, "times_s": list, "mean_s": NUMBER,
+            "stdev_s": NUMBER, "memory_kb": NUMBER, "status": str, "error": str,
+        }):
+            _check_times(errors, at, r.get("times_s"))
+            _check_status(errors, at, r)
+
+
+def _check_rust_result(errors, where, r):
+    """One result line of the Rust harness."""
+    if not _check(errors, where, r, {
+        "schema_version": int, "library": str, "format": str, "file": str,
+        "operation": str, "allocator": str, "times": list, "mean": NUMBER,
+        "min": NUMBER, "max": NUMBER, "stdev": NUMBER, "peak_memory_kb": NUMBER,
+        "status": str,
+    }):
+        return
+    _check_times(errors, where, r.get("times"))
+    _check_status(errors, where, r)
+    if r.get("error") is not None and not isinstance(r["error"], str):
+        errors.append(f"{where}: 'error' must be a string or null")
+
+
+def validate(doc):
+    """List what is wrong with a combined or sweep results document, after
+    migrating it; an empty list means it is valid."""
+    errors = []
+    if not isinstance(doc, dict):
+        return ["expected a JSON object"]
+    try:
+        doc = migrate(doc)
+    except ValueError as e:
+        return [str(e)]
+    sweep = "sizes" in doc
+    fields = {"schema_version": int, "timestamp": str, "rust_results": list}
+    fields.update({"sizes": list} if sweep else {"scale": str, "python_results": list})
+    _check(errors, "file", doc, fields)
+    for i, payload in enumerate(doc.get("python_results") or []):
+        _check_python_payload(errors, f"python_results[{i}]", payload)
+    for i, r in enumerate(doc.get("rust_results") or []):
+        where = f"rust_results[{i}]"
+        if isinstance(r, dict) and "results" in r:
+            _check_python_payload(errors, where, r)
+        else:
+            _check_rust_result(errors, where, r)
+    return errors
+
+
+def validate_files(paths):
+    """Validate each file, printing its problems; returns whether all are valid."""
+    ok = True
+    for path in paths:
+        try:
+            with open(path) as f:
+                errors = validate(json.load(f))
+        except (OSError, ValueError) as e:
+            errors = [str(e)]
+        if errors:
+            ok = False
+            print(f"{path}: {len(errors)} problem(s)")
+            for e in errors:
+                print(f"  {e}")
+        else:
+            print(f"{path}: valid (schema version {SCHEMA_VERSION})")
+    return ok
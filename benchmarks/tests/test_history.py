@@ -0,0 +1,97 @@
+"""Tests of the run history in history.py."""
+
+import json
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+sys.path.insert(0, str(Path(__file__).resolve().parent.parent))
+
+from history import MIN_TIMEOUT, change_point, connect, percentile, timeouts  # noqa: E402
+
+
+class ChangePointTest(unittest.TestCase):
+    def test_step(self):
+        index, shift = change_point([1.0, 1.1, 0.9, 1.0, 2.0, 2.1, 1.9, 2.0], 0.05)
+        self.assertEqual(index, 4)
+        self.assertAlmostEqual(shift, 1.0)
+        # A speedup is a negative shift.
+        index, shift = change_point([2.0, 2.0, 2.0, 1.0, 1.0], 0.05)
+        self.assertEqual(index, 3)
+        self.assertAlmostEqual(shift, -0.5)
+
+    def test_flat(self):
+        # No spread at all: every split scores 0, and none shifts the mean.
+        self.assertIsNone(change_point([3.0] * 6, 0.05))
+
+    def test_below_threshold(self):
+        values = [1.0, 1.01, 0.99, 1.02, 1.03, 1.02]
+        self.assertIsNone(change_point(values, 0.05))
+        index, shift = change_point(values, 0.01)
+        self.assertEqual(index, 3)
+        self.assertAlmostEqual(shift, 0.07 / 3)
+
+    def test_too_short(self):
+        # Each side needs MIN_SEGMENT runs.
+        self.assertIsNone(change_point([1.0, 1.0, 5.0], 0.05))
+
+
+class TimeoutsTest(unittest.TestCase):
+    def setUp(self):
+        self.dir = tempfile.TemporaryDirectory()
+        self.db = Path(self.dir.name) / "history.sqlite"
+
+    def tearDown(self):
+        self.dir.cleanup()
+
+    def add_run(self, timestamp, cells, scale="small"):
+        """Add a run with `cells`, (library, operation, status, times)."""
+        with connect(self.db) as db:
+            run_id = db.execute(
+                "INSERT INTO runs (timestamp, scale) VALUES (?, ?)", (timestamp, scale)
+            ).lastrowid
+            db.executemany(
+                "INSERT INTO cells VALUES (?, ?, 'vcd', 'small.vcd', ?, ?, 0, 0, 0, ?)",
+                [(run_id, lib, op, status, json.dumps(times)) for lib, op, status, times in cells],
+            )
+        db.close()
+
+    def test_percentile(self):
+        self.assertEqual(percentile(range(1, 101), 0.99), 99)
+        self.assertEqual(percentile([4, 1, 3, 2], 0.5), 2)
+        self.assertEqual(percentile([7], 0.99), 7)
+
+    def test_derived(self):
+        self.add_run("2026-01-01", [("wellen", "full_parse", "ok", [2.0, 4.0])])
+        self.add_run("2026-01-02", [
+            ("wellen", "full_parse", "ok", [3.0]),
+            ("wellen", "signal_list", "ok", [0.01]),
+            # Failed and timed out cells say nothing about how long a cell takes.
+            ("vcd-ng", "full_parse", "timeout", [60.0]),
+        ])
+        self.add_run("2026-01-03", [("wellen", "full_parse", "ok", [9.0])], scale="large")
+        derived = timeouts(self.db, "small", factor=2.0)
+        self.assertEqual(derived, {
+            ("wellen", "full_parse", "small.vcd"): 8.0,
+            # 2 x 0.01s is floored.
+            ("wellen", "signal_list", "small.vcd"): MIN_TIMEOUT,
+        })
+        # Only the latest run counts with last=1.
+        self.assertEqual(timeouts(self.db, "small", factor=2.0, last=1)[
+            ("wellen", "full_parse", "small.vcd")], 6.0)
+        self.add_run("2026-01-04", [("wellen", "full_parse", "ok", [1.0])])
+        self.assertEqual(timeouts(self.db, "small", factor=2.0, last=1), {
+            ("wellen", "full_parse", "small.vcd"): 2.0,
+        })
+
+    def test_empty_history(self):
+        self.assertEqual(timeouts(self.db, "small"), {})
+        self.assertFalse(self.db.exists())
+        self.add_run("2026-01-01", [])
+        self.assertEqual(timeouts(self.db, "small"), {})
+        self.assertEqual(timeouts(self.db, "large"), {})
+
+
+if __name__ == "__main__":
+    unittest.main()
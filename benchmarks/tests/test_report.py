@@ -0,0 +1,44 @@
+"""Tests of the statistics in report.py."""
+
+import sys
+import unittest
+from pathlib import Path
+
+sys.path.insert(0, str(Path(__file__).resolve().parent.parent))
+
+from report import mann_whitney_p  # noqa: E402
+
+
+class MannWhitneyTest(unittest.TestCase):
+    def test_exact(self):
+        # 48 of the 70 orderings of two groups of 4 have U <= 2.
+        self.assertAlmostEqual(mann_whitney_p([1, 3, 5, 7], [2, 4, 6, 8]), 48 / 70)
+        self.assertAlmostEqual(mann_whitney_p([2, 4, 6, 8], [1, 3, 5, 7]), 48 / 70)
+        # Fully separated groups of 3 are 2 of 20 orderings.
+        self.assertAlmostEqual(mann_whitney_p([1, 2, 3], [4, 5, 6]), 0.1)
+        # Interleaved, U = 3, which 7 of the 20 reach or fall below.
+        self.assertAlmostEqual(mann_whitney_p([1, 2, 3], [1.5, 2.5, 3.5]), 0.7)
+
+    def test_ties(self):
+        # Ranks 1.5, 1.5, 3.5 against 3.5, 5.5, 5.5: U = 0.5, and the three
+        # pairs of ties shrink the variance of U from 5.25 to 4.8.
+        self.assertAlmostEqual(mann_whitney_p([1, 1, 2], [2, 3, 3]), 0.110149, places=6)
+        # Nothing but ties leaves no variance to test against.
+        self.assertEqual(mann_whitney_p([5, 5, 5], [5, 5, 5]), 1.0)
+
+    def test_large_samples(self):
+        # 30 values take the normal approximation: U = 105 of 225.
+        evens = [2 * i for i in range(15)]
+        odds = [2 * i + 1 for i in range(15)]
+        self.assertAlmostEqual(mann_whitney_p(evens, odds), 0.771551, places=6)
+        low = list(range(15))
+        high = list(range(100, 115))
+        self.assertLess(mann_whitney_p(low, high), 1e-5)
+
+    def test_empty(self):
+        self.assertIsNone(mann_whitney_p([], [1.0]))
+        self.assertIsNone(mann_whitney_p([1.0], []))
+
+
+if __name__ == "__main__":
+    unittest.main()
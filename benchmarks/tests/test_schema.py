@@ -0,0 +1,110 @@
+"""Tests of the result file schema in schema.py."""
+
+import copy
+import sys
+import unittest
+from pathlib import Path
+
+sys.path.insert(0, str(Path(__file__).resolve().parent.parent))
+
+from schema import SCHEMA_VERSION, migrate, validate  # noqa: E402
+
+
+def rust_result(**fields):
+    r = {
+        "schema_version": SCHEMA_VERSION, "library": "wellen", "format": "vcd",
+        "file": "small.vcd", "operation": "full_parse", "allocator": "system",
+        "times": [0.1, 0.2], "mean": 0.15, "min": 0.1, "max": 0.2,
+        "stdev": 0.05, "peak_memory_kb": 1024, "status": "ok", "error": None,
+    }
+    r.update(fields)
+    return r
+
+
+def python_payload(**fields):
+    result = {
+        "test": "full_parse", "file": "small.vcd", "times_s": [0.3],
+        "mean_s": 0.3, "stdev_s": 0.0, "memory_kb": 2048, "status": "ok",
+        "error": "",
+    }
+    result.update(fields)
+    return {
+        "schema_version": SCHEMA_VERSION, "library": "vcdvcd", "format": "vcd",
+        "results": [result],
+    }
+
+
+def combined(**fields):
+    doc = {
+        "schema_version": SCHEMA_VERSION, "timestamp": "2026-01-01T00:00:00",
+        "scale": "small", "python_results": [python_payload()],
+        "rust_results": [rust_result()],
+    }
+    doc.update(fields)
+    return doc
+
+
+class MigrateTest(unittest.TestCase):
+    def test_version_0(self):
+        old = rust_result()
+        del old["schema_version"], old["allocator"]
+        doc = {"timestamp": "2025-01-01T00:00:00", "scale": "small",
+               "python_results": [], "rust_results": [old]}
+        doc = migrate(doc)
+        self.assertEqual(doc["schema_version"], SCHEMA_VERSION)
+        self.assertEqual(doc["rust_results"][0]["allocator"], "system")
+        self.assertEqual(doc["rust_results"][0]["schema_version"], SCHEMA_VERSION)
+        self.assertEqual(validate(doc), [])
+
+    def test_current_is_unchanged(self):
+        doc = combined()
+        self.assertEqual(migrate(copy.deepcopy(doc)), doc)
+
+    def test_newer_version(self):
+        with self.assertRaises(ValueError):
+            migrate(combined(schema_version=SCHEMA_VERSION + 1))
+        self.assertIn("newer", validate(combined(schema_version=SCHEMA_VERSION + 1))[0])
+
+
+class ValidateTest(unittest.TestCase):
+    def test_valid(self):
+        self.assertEqual(validate(combined()), [])
+        skipped = rust_result(status="skipped", error="vcd-ng does not read FST")
+        self.assertEqual(validate(combined(rust_results=[skipped])), [])
+        sweep = {"schema_version": SCHEMA_VERSION, "timestamp": "t",
+                 "sizes": [], "rust_results": []}
+        self.assertEqual(validate(sweep), [])
+
+    def test_missing_fields(self):
+        doc = combined()
+        del doc["scale"]
+        del doc["rust_results"][0]["mean"]
+        del doc["python_results"][0]["results"][0]["times_s"]
+        self.assertEqual(sorted(validate(doc)), [
+            "file: missing 'scale'",
+            "python_results[0].results[0]: missing 'times_s'",
+            "rust_results[0]: missing 'mean'",
+        ])
+
+    def test_bad_status(self):
+        doc = combined(rust_results=[rust_result(status="crashed")])
+        doc["python_results"][0]["results"][0]["status"] = "done"
+        self.assertEqual(validate(doc), [
+            "python_results[0].results[0]: unknown status 'done'",
+            "rust_results[0]: unknown status 'crashed'",
+        ])
+
+    def test_bad_types(self):
+        doc = combined(rust_results=[rust_result(mean=True, times=[0.1, "x"], error=3)])
+        self.assertEqual(validate(doc), [
+            "rust_results[0]: 'mean' has type bool",
+            "rust_results[0]: repetition times must be numbers",
+            "rust_results[0]: 'error' must be a string or null",
+        ])
+        self.assertEqual(validate([]), ["expected a JSON object"])
+        self.assertEqual(validate(combined(rust_results=[7])),
+                         ["rust_results[0]: expected an object, got int"])
+
+
+if __name__ == "__main__":
+    unittest.main()
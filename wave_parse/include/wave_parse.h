@@ -0,0 +1,178 @@
+#ifndef WAVE_PARSE_H
+#define WAVE_PARSE_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#include <stddef.h>
+#include <stdbool.h>
+#include <stdint.h>
+
+// A loaded signal.
+typedef struct WpSignal WpSignal;
+
+// An open waveform.
+typedef struct WpWaveform WpWaveform;
+
+// `ArrowArray` of the C data interface.
+typedef struct ArrowArray {
+  int64_t length;
+  int64_t null_count;
+  int64_t offset;
+  int64_t n_buffers;
+  int64_t n_children;
+  const void **buffers;
+  struct ArrowArray **children;
+  struct ArrowArray *dictionary;
+  void (*release)(struct ArrowArray*);
+  void *private_data;
+} ArrowArray;
+
+// `ArrowSchema` of the C data interface.
+typedef struct ArrowSchema {
+  const char *format;
+  const char *name;
+  const char *metadata;
+  int64_t flags;
+  int64_t n_children;
+  struct ArrowSchema **children;
+  struct ArrowSchema *dictionary;
+  void (*release)(struct ArrowSchema*);
+  void *private_data;
+} ArrowSchema;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The message of the last failure on this thread, or null if nothing
+// failed yet.
+const char *wp_last_error(void);
+
+// Open a waveform file, picking the reader by extension.
+//
+// # Safety
+//
+// `path` must be a NUL-terminated string.
+struct WpWaveform *wp_open(const char *path);
+
+// Close a waveform. Signals loaded from it stay valid.
+//
+// # Safety
+//
+// `w` must come from [`wp_open`] and not be used afterwards, or be null.
+void wp_close(struct WpWaveform *w);
+
+// Number of variables, which are numbered depth-first from 0.
+//
+// # Safety
+//
+// `w` must be an open waveform.
+size_t wp_var_count(const struct WpWaveform *w);
+
+// The dot-separated path of variable `i`, or null if there is none.
+//
+// # Safety
+//
+// `w` must be an open waveform.
+const char *wp_var_path(const struct WpWaveform *w, size_t i);
+
+// The width in bits of variable `i`, 0 if there is none.
+//
+// # Safety
+//
+// `w` must be an open waveform.
+uint32_t wp_var_width(const struct WpWaveform *w, size_t i);
+
+// The signal carrying the values of variable `i`. Aliased variables
+// share a signal.
+//
+// # Safety
+//
+// `w` must be an open waveform and `i` less than [`wp_var_count`].
+uint64_t wp_var_signal(const struct WpWaveform *w, size_t i);
+
+// Find the variable at a dot-separated path and store its index in
+// `index`. Returns whether it exists.
+//
+// # Safety
+//
+// `w` must be an open waveform, `path` a NUL-terminated string and
+// `index` valid for writes.
+bool wp_find_var(const struct WpWaveform *w, const char *path, size_t *index);
+
+// The duration of one time tick in seconds, 0 if unknown.
+//
+// # Safety
+//
+// `w` must be an open waveform.
+double wp_timescale_seconds(const struct WpWaveform *w);
+
+// Load the changes of a signal, to be freed with [`wp_signal_free`].
+//
+// # Safety
+//
+// `w` must be an open waveform.
+struct WpSignal *wp_load_signal(struct WpWaveform *w, uint64_t signal);
+
+// Free a signal.
+//
+// # Safety
+//
+// `s` must come from [`wp_load_signal`] and not be used afterwards, or
+// be null.
+void wp_signal_free(struct WpSignal *s);
+
+// Number of value changes.
+//
+// # Safety
+//
+// `s` must be a loaded signal.
+size_t wp_signal_len(const struct WpSignal *s);
+
+// Time of change `i`.
+//
+// # Safety
+//
+// `s` must be a loaded signal and `i` less than [`wp_signal_len`].
+uint64_t wp_signal_time(const struct WpSignal *s, size_t i);
+
+// The value of change `i` as text (e.g. `1`, `x01z` or a real number),
+// not NUL-terminated; its length is stored in `len`.
+//
+// # Safety
+//
+// `s` must be a loaded signal, `i` less than [`wp_signal_len`] and `len`
+// valid for writes.
+const uint8_t *wp_signal_value(const struct WpSignal *s, size_t i, size_t *len);
+
+// Store the index of the change in effect at `time` in `index`. Returns
+// false if the signal has no value yet at that time.
+//
+// # Safety
+//
+// `s` must be a loaded signal and `index` valid for writes.
+bool wp_signal_index_at(const struct WpSignal *s, uint64_t time, size_t *index);
+
+// Sample the variables at `paths` at their change times in
+// `[start, end)` and export them through the Arrow C data interface as a
+// struct array with a `time` column first (see
+// [`to_arrow`](arrow::to_arrow)). Returns false on failure.
+//
+// # Safety
+//
+// `w` must be an open waveform, `paths` point to `n` NUL-terminated
+// strings, and `array` and `schema` be valid for writes. On success the
+// caller owns both structures and must release them.
+bool wp_to_arrow(struct WpWaveform *w,
+                 const char *const *paths,
+                 size_t n,
+                 uint64_t start,
+                 uint64_t end,
+                 struct ArrowArray *array,
+                 struct ArrowSchema *schema);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* WAVE_PARSE_H */
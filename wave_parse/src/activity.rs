@@ -0,0 +1,182 @@
+//! Activity profiles: value changes counted in equal time buckets.
+//!
+//! An [`Activity`] shows where in a long simulation things happen, for a
+//! viewer's overview strip or to find the interesting part of a run.
+//! [`signal_activity`] profiles one signal, [`wave_activity`] all
+//! signals of any waveform, and [`vcd_activity`] a whole VCD file in one
+//! pass over its body, without loading any signal.
+
+use std::io;
+use std::ops::Range;
+
+use crate::parallel;
+use crate::vcd::Token;
+use crate::{Signal, VcdFile, Waveform};
+
+/// Signals loaded at once by [`wave_activity`].
+const BATCH: usize = 256;
+
+/// Change counts over a time range split into equal buckets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Activity {
+    pub range: Range<u64>,
+    /// Changes per bucket, in time order.
+    pub counts: Vec<u64>,
+}
+
+impl Activity {
+    /// An empty profile of `range` in `buckets` buckets, at least one.
+    pub fn new(range: Range<u64>, buckets: usize) -> Activity {
+        Activity {
+            range,
+            counts: vec![0; buckets.max(1)],
+        }
+    }
+
+    /// The bucket `time` falls in, if it is in the range.
+    pub fn bucket_of(&self, time: u64) -> Option<usize> {
+        if !self.range.contains(&time) {
+            return None;
+        }
+        let len = (self.range.end - self.range.start) as u128;
+        let offset = (time - self.range.start) as u128;
+        Some((offset * self.counts.len() as u128 / len) as usize)
+    }
+
+    /// The time range of bucket `i`.
+    pub fn bucket_range(&self, i: usize) -> Range<u64> {
+        let len = self.range.end.saturating_sub(self.range.start) as u128;
+        let n = self.counts.len() as u128;
+        // The first time mapped to bucket `i` by `bucket_of`.
+        let start = |i: u128| self.range.start + (i * len).div_ceil(n) as u64;
+        start(i as u128)..start(i as u128 + 1)
+    }
+
+    /// Count a change at `time`, if it is in the range.
+    pub fn add(&mut self, time: u64) {
+        if let Some(i) = self.bucket_of(time) {
+            self.counts[i] += 1;
+        }
+    }
+
+    /// Count the changes of `signal` in the range.
+    pub fn add_signal(&mut self, signal: &Signal) {
+        let times = signal.times();
+        let from = times.partition_point(|&t| t < self.range.start);
+        let to = times.partition_point(|&t| t < self.range.end).max(from);
+        for &t in &times[from..to] {
+            self.add(t);
+        }
+    }
+
+    /// Changes in all buckets.
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// The bucket with the most changes, the first of several, if any
+    /// change was counted.
+    pub fn busiest(&self) -> Option<usize> {
+        let max = *self.counts.iter().max()?;
+        (max > 0).then(|| self.counts.iter().position(|&c| c == max).expect("max"))
+    }
+}
+
+/// The activity of one signal.
+pub fn signal_activity(signal: &Signal, range: Range<u64>, buckets: usize) -> Activity {
+    let mut activity = Activity::new(range, buckets);
+    activity.add_signal(signal);
+    activity
+}
+
+/// The activity of all signals of `wave`, loaded a few at a time.
+/// Variables sharing a signal count its changes once.
+pub fn wave_activity<F>(wave: &mut F, range: Range<u64>, buckets: usize) -> io::Result<Activity>
+where
+    F: Waveform + ?Sized,
+{
+    let mut activity = Activity::new(range, buckets);
+    let ids = wave.hierarchy().signal_ids();
+    for batch in ids.chunks(BATCH) {
+        for signal in wave.load_signals(batch)? {
+            activity.add_signal(&signal);
+        }
+    }
+    Ok(activity)
+}
+
+/// The activity of a whole VCD file, from one pass over its body on
+/// [`threads`](VcdFile::threads) threads. Changes of `$dumpvars`-like
+/// blocks count like others.
+pub fn vcd_activity(vcd: &VcdFile, range: Range<u64>, buckets: usize) -> io::Result<Activity> {
+    let data = vcd.bytes();
+    let chunks = parallel::split_at_timestamps(data, vcd.body_start()..data.len(), vcd.threads());
+    let empty = Activity::new(range, buckets);
+    let parts = parallel::map_chunks(data, &chunks, vcd.threads(), |tokens| {
+        let mut part = empty.clone();
+        let mut time = 0;
+        for token in tokens {
+            match token? {
+                Token::Timestamp(t) => time = t,
+                Token::Change(_) => part.add(time),
+                _ => {}
+            }
+        }
+        Ok(part)
+    })?;
+    let mut activity = empty.clone();
+    for part in parts {
+        for (total, count) in activity.counts.iter_mut().zip(part.counts) {
+            *total += count;
+        }
+    }
+    Ok(activity)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{SignalId, SignalLoader};
+
+    #[test]
+    fn buckets() {
+        let a = Activity::new(10..20, 3);
+        assert_eq!(a.bucket_of(9), None);
+        assert_eq!(a.bucket_of(13), Some(0));
+        assert_eq!(a.bucket_of(14), Some(1));
+        assert_eq!(a.bucket_of(19), Some(2));
+        assert_eq!(a.bucket_of(20), None);
+        let ranges: Vec<Range<u64>> = (0..3).map(|i| a.bucket_range(i)).collect();
+        assert_eq!(ranges, [10..14, 14..17, 17..20]);
+        assert_eq!(
+            Activity::new(0..u64::MAX, 2).bucket_of(u64::MAX - 1),
+            Some(1)
+        );
+        assert_eq!(a.busiest(), None);
+
+        // A reversed range is empty.
+        let (start, end) = (20, 10);
+        let a = Activity::new(start..end, 2);
+        assert_eq!(a.bucket_of(15), None);
+        assert_eq!(a.bucket_range(1), 20..20);
+    }
+
+    #[test]
+    fn profiles() {
+        let mut vcd = VcdFile::from_bytes(
+            b"$var wire 1 ! a $end $var wire 1 \" b $end $var wire 1 ! a2 $end
+$enddefinitions $end $dumpvars 0! 0\" $end #1 1! #2 0! #3 1! #7 1\" #9 0! #12 0\""
+                .to_vec(),
+        )
+        .unwrap();
+        let whole = vcd_activity(&vcd, 0..10, 2).unwrap();
+        assert_eq!(whole.counts, [5, 2]);
+        assert_eq!(whole.busiest(), Some(0));
+        assert_eq!(wave_activity(&mut vcd, 0..10, 2).unwrap(), whole);
+
+        let id = SignalId::from_code(b"!").unwrap();
+        let a = signal_activity(&vcd.load_signals(&[id]).unwrap()[0], 0..10, 5);
+        assert_eq!(a.counts, [2, 2, 0, 0, 1]);
+        assert_eq!(a.total(), 5);
+    }
+}
@@ -0,0 +1,264 @@
+//! Time alignment of two waveforms.
+//!
+//! Traces of the same design from different sources (RTL and gate-level
+//! simulation, a silicon capture) rarely share time zero. [`Shifted`]
+//! moves a whole waveform in time, and [`estimate_offset`] finds the shift
+//! that best lines up a reference signal of both, by cross-correlating
+//! their changes.
+//!
+//! Pick a reference signal whose changes are irregular, such as a reset
+//! or a valid strobe: a free-running clock matches itself at every period.
+
+use std::io;
+
+use crate::diff::same_value;
+use crate::{Blackouts, Hierarchy, Signal, SignalId, SignalLoader, Timescale, Waveform};
+
+/// Changes of the other signal used to propose offsets.
+const SAMPLED: usize = 64;
+/// Offsets proposed by one sampled change at most.
+const PROPOSED: usize = 4096;
+/// Changes of the other signal checked when scoring an offset.
+const SCORED: usize = 4096;
+
+/// Options for [`estimate_offset`].
+#[derive(Debug, Clone)]
+pub struct AlignOptions {
+    /// Largest offset considered, in either direction.
+    pub max_offset: u64,
+    /// How far apart two changes may be and still match, e.g. for gate
+    /// delays.
+    pub tolerance: u64,
+}
+
+impl Default for AlignOptions {
+    fn default() -> AlignOptions {
+        AlignOptions {
+            max_offset: u64::MAX,
+            tolerance: 0,
+        }
+    }
+}
+
+/// Whether `signal` changes to `value` within `tolerance` of `time`.
+fn matches(signal: &Signal, time: i128, value: &[u8], tolerance: u64) -> bool {
+    let times = signal.times();
+    let lo = (time - tolerance as i128).max(0);
+    let start = times.partition_point(|&t| (t as i128) < lo);
+    (start..signal.len())
+        .take_while(|&i| times[i] as i128 <= time + tolerance as i128)
+        .any(|i| same_value(signal.value(i), value, false))
+}
+
+/// The offset to add to the times of `other` so its changes line up best
+/// with those of `reference`, or none if no change matches any other.
+///
+/// Each offset proposed by matching the first changes of both signals is
+/// scored by how many changes of `other` then meet an equal change of
+/// `reference`. Ties go to the smallest offset.
+pub fn estimate_offset(reference: &Signal, other: &Signal, options: &AlignOptions) -> Option<i64> {
+    let mut offsets = Vec::new();
+    for j in 0..other.len().min(SAMPLED) {
+        let t = other.time(j);
+        let lo = t.saturating_sub(options.max_offset);
+        let hi = t.saturating_add(options.max_offset);
+        let start = reference.times().partition_point(|&r| r < lo);
+        offsets.extend(
+            (start..reference.len())
+                .take_while(|&i| reference.time(i) <= hi)
+                .filter(|&i| same_value(reference.value(i), other.value(j), false))
+                .take(PROPOSED)
+                .map(|i| reference.time(i) as i128 - t as i128),
+        );
+    }
+    offsets.sort_unstable_by_key(|&o| (o.abs(), o));
+    offsets.dedup();
+
+    let mut best = None;
+    for offset in offsets {
+        let score = (0..other.len().min(SCORED))
+            .filter(|&j| {
+                let t = other.time(j) as i128 + offset;
+                matches(reference, t, other.value(j), options.tolerance)
+            })
+            .count();
+        if best.is_none_or(|(s, _)| score > s) {
+            best = Some((score, offset));
+        }
+    }
+    best.and_then(|(_, offset)| i64::try_from(offset).ok())
+}
+
+/// `signal` with `offset` added to its times. Changes moved before time
+/// zero collapse into the value in effect at zero.
+pub fn shift(signal: &Signal, offset: i64) -> Signal {
+    let mut out = Signal::new();
+    let shifted: Vec<u64> = signal
+        .times()
+        .iter()
+        .map(|t| t.saturating_add_signed(offset))
+        .collect();
+    for (i, (_, v)) in signal.iter().enumerate() {
+        if shifted[i] == 0 && shifted.get(i + 1) == Some(&0) {
+            continue;
+        }
+        out.push(shifted[i], v);
+    }
+    out
+}
+
+/// A waveform moved in time by a constant offset, in its own ticks.
+#[derive(Debug)]
+pub struct Shifted<W> {
+    inner: W,
+    offset: i64,
+}
+
+impl<W: Waveform> Shifted<W> {
+    pub fn new(inner: W, offset: i64) -> Shifted<W> {
+        Shifted { inner, offset }
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.offset
+    }
+
+    pub fn inner(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Waveform> SignalLoader for Shifted<W> {
+    fn load_signals(&mut self, ids: &[SignalId]) -> io::Result<Vec<Signal>> {
+        let signals = self.inner.load_signals(ids)?;
+        if self.offset == 0 {
+            return Ok(signals);
+        }
+        Ok(signals.iter().map(|s| shift(s, self.offset)).collect())
+    }
+}
+
+impl<W: Waveform> Waveform for Shifted<W> {
+    fn hierarchy(&self) -> &Hierarchy {
+        self.inner.hierarchy()
+    }
+
+    fn timescale(&self) -> Option<Timescale> {
+        self.inner.timescale()
+    }
+
+    fn date(&self) -> Option<&str> {
+        self.inner.date()
+    }
+
+    fn blackouts(&self) -> io::Result<Blackouts> {
+        let blackouts = self.inner.blackouts()?;
+        Ok(blackouts.map_times(|t| t.saturating_add_signed(self.offset)))
+    }
+}
+
+/// [`estimate_offset`] for the variable at `path` in `reference` and the
+/// one at `other_path` in `other`.
+pub fn align<A, B>(
+    reference: &mut A,
+    path: &str,
+    other: &mut B,
+    other_path: &str,
+    options: &AlignOptions,
+) -> io::Result<Option<i64>>
+where
+    A: Waveform + ?Sized,
+    B: Waveform + ?Sized,
+{
+    fn load<W: Waveform + ?Sized>(wave: &mut W, path: &str) -> io::Result<Signal> {
+        let id = wave
+            .hierarchy()
+            .lookup(path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no signal {}", path)))?
+            .signal;
+        Ok(wave.load_signals(&[id])?.remove(0))
+    }
+    let a = load(reference, path)?;
+    let b = load(other, other_path)?;
+    Ok(estimate_offset(&a, &b, options))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::VcdFile;
+
+    /// An irregular strobe: high for a while at pseudo-random times.
+    fn strobe(offset: u64, jitter: bool) -> Signal {
+        let mut s = Signal::new();
+        let mut t = 0;
+        let mut x = 7u64;
+        for i in 0..200u64 {
+            x = x
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            t += 10 + (x >> 58);
+            let j = if jitter { i % 3 } else { 0 };
+            s.push(t + offset + j, if i % 2 == 0 { b"1" } else { b"0" });
+        }
+        s
+    }
+
+    #[test]
+    fn finds_offsets() {
+        let reference = strobe(1000, false);
+        let options = AlignOptions::default();
+        assert_eq!(
+            estimate_offset(&reference, &strobe(0, false), &options),
+            Some(1000)
+        );
+        assert_eq!(
+            estimate_offset(&strobe(0, false), &reference, &options),
+            Some(-1000)
+        );
+
+        let loose = AlignOptions {
+            max_offset: 5000,
+            tolerance: 2,
+        };
+        let found = estimate_offset(&reference, &strobe(300, true), &loose).unwrap();
+        assert!((698..=700).contains(&found), "{}", found);
+        assert_eq!(estimate_offset(&reference, &Signal::new(), &options), None);
+    }
+
+    #[test]
+    fn shifted_waveform() {
+        let vcd = VcdFile::from_bytes(
+            b"$var wire 1 ! a $end $enddefinitions $end #0 0! #3 1! #5 0! #9 1!".to_vec(),
+        )
+        .unwrap();
+        let id = vcd.hierarchy().signal_ids()[0];
+        let mut later = Shifted::new(vcd, 10);
+        assert_eq!(
+            later.load_signals(&[id]).unwrap()[0].times(),
+            [10, 13, 15, 19]
+        );
+        let mut earlier = Shifted::new(later.into_inner(), -4);
+        let s = &earlier.load_signals(&[id]).unwrap()[0];
+        assert_eq!(s.times(), [0, 1, 5]);
+        assert_eq!(s.value(0), b"1");
+
+        let mut a = Shifted::new(earlier.into_inner(), 0);
+        let mut b = Shifted::new(
+            VcdFile::from_bytes(
+                b"$var wire 1 ! b $end $enddefinitions $end #7 1! #9 0! #13 1!".to_vec(),
+            )
+            .unwrap(),
+            0,
+        );
+        assert_eq!(
+            align(&mut a, "a", &mut b, "b", &Default::default()).unwrap(),
+            Some(-4)
+        );
+        assert!(align(&mut a, "c", &mut b, "b", &Default::default()).is_err());
+    }
+}
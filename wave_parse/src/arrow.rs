@@ -0,0 +1,446 @@
+//! Export of signal data as Arrow record batches.
+//!
+//! [`to_arrow`] samples a set of variables into a [`RecordBatch`]: a
+//! `time` column with every change time in a window, plus one column per
+//! variable holding its value at that time. Columns are typed by the
+//! variable: single bits become booleans, vectors up to 64 bits unsigned
+//! integers, reals doubles, and strings and wider vectors their text.
+//! Unknown values (`x`, `z`, unassigned) are nulls.
+//!
+//! [`RecordBatch::export`] hands the batch over through the [Arrow C data
+//! interface], which pyarrow, polars, DataFusion and arrow-rs import
+//! without copying, e.g. with `pyarrow.RecordBatch._import_from_c`. No
+//! Arrow library is needed on this side.
+//!
+//! [Arrow C data interface]: https://arrow.apache.org/docs/format/CDataInterface.html
+
+use std::ffi::{c_char, c_void, CString};
+use std::io;
+use std::ops::Range;
+use std::ptr;
+
+use crate::numeric::raw_bits;
+use crate::{Var, VarKind, Waveform};
+
+/// The values of one column.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnData {
+    Boolean(Vec<Option<bool>>),
+    UInt64(Vec<Option<u64>>),
+    Float64(Vec<Option<f64>>),
+    Utf8(Vec<Option<String>>),
+}
+
+impl ColumnData {
+    fn for_var(var: &Var) -> ColumnData {
+        if var.kind.is_real() {
+            ColumnData::Float64(Vec::new())
+        } else if var.kind == VarKind::String || var.width > 64 {
+            ColumnData::Utf8(Vec::new())
+        } else if var.width == 1 {
+            ColumnData::Boolean(Vec::new())
+        } else {
+            ColumnData::UInt64(Vec::new())
+        }
+    }
+
+    fn push(&mut self, value: Option<&[u8]>, width: u32) {
+        match self {
+            ColumnData::Boolean(v) => v.push(match value {
+                Some(b"1") => Some(true),
+                Some(b"0") => Some(false),
+                _ => None,
+            }),
+            ColumnData::UInt64(v) => v.push(value.and_then(|b| raw_bits(b, width))),
+            ColumnData::Float64(v) => v.push(
+                value
+                    .and_then(|b| std::str::from_utf8(b).ok())
+                    .and_then(|s| s.parse().ok()),
+            ),
+            ColumnData::Utf8(v) => {
+                v.push(value.map(|b| String::from_utf8_lossy(b).into_owned()));
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            ColumnData::Boolean(v) => v.len(),
+            ColumnData::UInt64(v) => v.len(),
+            ColumnData::Float64(v) => v.len(),
+            ColumnData::Utf8(v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A named column of a [`RecordBatch`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub data: ColumnData,
+}
+
+/// Variables sampled at the change times of a window.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecordBatch {
+    pub time: Vec<u64>,
+    pub columns: Vec<Column>,
+}
+
+/// Sample the variables at `paths` at every time in `range` at which one
+/// of them changes. The first row is at the start of the range if any of
+/// them has a value by then.
+pub fn to_arrow<F>(wave: &mut F, paths: &[&str], range: Range<u64>) -> io::Result<RecordBatch>
+where
+    F: Waveform + ?Sized,
+{
+    let mut vars = Vec::with_capacity(paths.len());
+    for path in paths {
+        let var = wave.hierarchy().lookup(path).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("no signal {}", path))
+        })?;
+        vars.push(var.clone());
+    }
+    let ids: Vec<_> = vars.iter().map(|v| v.signal).collect();
+    let signals = wave.load_signals(&ids)?;
+
+    let mut time = Vec::new();
+    if signals.iter().any(|s| s.value_at(range.start).is_some()) {
+        time.push(range.start);
+    }
+    for s in &signals {
+        let t = s.times();
+        let from = t.partition_point(|&t| t <= range.start);
+        let to = t.partition_point(|&t| t < range.end);
+        time.extend_from_slice(&t[from..to.max(from)]);
+    }
+    time.sort_unstable();
+    time.dedup();
+
+    let columns = paths
+        .iter()
+        .zip(&vars)
+        .zip(&signals)
+        .map(|((path, var), signal)| {
+            let mut data = ColumnData::for_var(var);
+            for &t in &time {
+                data.push(signal.value_at(t), var.width);
+            }
+            Column {
+                name: path.to_string(),
+                data,
+            }
+        })
+        .collect();
+    Ok(RecordBatch { time, columns })
+}
+
+/// `ArrowSchema` of the C data interface.
+#[repr(C)]
+#[derive(Debug)]
+pub struct ArrowSchema {
+    format: *const c_char,
+    name: *const c_char,
+    metadata: *const c_char,
+    flags: i64,
+    n_children: i64,
+    children: *mut *mut ArrowSchema,
+    dictionary: *mut ArrowSchema,
+    release: Option<unsafe extern "C" fn(*mut ArrowSchema)>,
+    private_data: *mut c_void,
+}
+
+/// `ArrowArray` of the C data interface.
+#[repr(C)]
+#[derive(Debug)]
+pub struct ArrowArray {
+    length: i64,
+    null_count: i64,
+    offset: i64,
+    n_buffers: i64,
+    n_children: i64,
+    buffers: *mut *const c_void,
+    children: *mut *mut ArrowArray,
+    dictionary: *mut ArrowArray,
+    release: Option<unsafe extern "C" fn(*mut ArrowArray)>,
+    private_data: *mut c_void,
+}
+
+/// Both structures release what they own when dropped, unless a consumer
+/// moved it out and marked them released.
+impl Drop for ArrowSchema {
+    fn drop(&mut self) {
+        if let Some(release) = self.release {
+            unsafe { release(self) }
+        }
+    }
+}
+
+impl Drop for ArrowArray {
+    fn drop(&mut self) {
+        if let Some(release) = self.release {
+            unsafe { release(self) }
+        }
+    }
+}
+
+const NULLABLE: i64 = 2;
+
+struct SchemaData {
+    format: CString,
+    name: CString,
+    children: Vec<*mut ArrowSchema>,
+}
+
+unsafe extern "C" fn release_schema(schema: *mut ArrowSchema) {
+    let schema = &mut *schema;
+    let data = Box::from_raw(schema.private_data as *mut SchemaData);
+    for &child in &data.children {
+        drop(Box::from_raw(child));
+    }
+    schema.release = None;
+}
+
+fn schema(format: &str, name: &str, flags: i64, children: Vec<ArrowSchema>) -> ArrowSchema {
+    let mut data = Box::new(SchemaData {
+        format: CString::new(format).expect("format has no NUL"),
+        name: CString::new(name.replace('\0', "")).expect("NULs removed"),
+        children: children
+            .into_iter()
+            .map(|c| Box::into_raw(Box::new(c)))
+            .collect(),
+    });
+    ArrowSchema {
+        format: data.format.as_ptr(),
+        name: data.name.as_ptr(),
+        metadata: ptr::null(),
+        flags,
+        n_children: data.children.len() as i64,
+        children: data.children.as_mut_ptr(),
+        dictionary: ptr::null_mut(),
+        release: Some(release_schema),
+        private_data: Box::into_raw(data) as *mut c_void,
+    }
+}
+
+/// A buffer with the alignment its contents need.
+enum Buffer {
+    Bytes(Vec<u8>),
+    Offsets(Vec<i32>),
+    Words(Vec<u64>),
+}
+
+impl Buffer {
+    fn as_ptr(&self) -> *const c_void {
+        match self {
+            Buffer::Bytes(b) => b.as_ptr() as *const c_void,
+            Buffer::Offsets(b) => b.as_ptr() as *const c_void,
+            Buffer::Words(b) => b.as_ptr() as *const c_void,
+        }
+    }
+}
+
+struct ArrayData {
+    _buffers: Vec<Option<Buffer>>,
+    pointers: Vec<*const c_void>,
+    children: Vec<*mut ArrowArray>,
+}
+
+unsafe extern "C" fn release_array(array: *mut ArrowArray) {
+    let array = &mut *array;
+    let data = Box::from_raw(array.private_data as *mut ArrayData);
+    for &child in &data.children {
+        drop(Box::from_raw(child));
+    }
+    array.release = None;
+}
+
+fn array(
+    length: usize,
+    null_count: usize,
+    buffers: Vec<Option<Buffer>>,
+    children: Vec<ArrowArray>,
+) -> ArrowArray {
+    let pointers = buffers
+        .iter()
+        .map(|b| b.as_ref().map_or(ptr::null(), Buffer::as_ptr))
+        .collect();
+    let mut data = Box::new(ArrayData {
+        _buffers: buffers,
+        pointers,
+        children: children
+            .into_iter()
+            .map(|c| Box::into_raw(Box::new(c)))
+            .collect(),
+    });
+    ArrowArray {
+        length: length as i64,
+        null_count: null_count as i64,
+        offset: 0,
+        n_buffers: data.pointers.len() as i64,
+        n_children: data.children.len() as i64,
+        buffers: data.pointers.as_mut_ptr(),
+        children: data.children.as_mut_ptr(),
+        dictionary: ptr::null_mut(),
+        release: Some(release_array),
+        private_data: Box::into_raw(data) as *mut c_void,
+    }
+}
+
+/// A bitmap of `bits`, least significant bit first.
+fn bitmap(bits: impl Iterator<Item = bool>) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (i, b) in bits.enumerate() {
+        if i % 8 == 0 {
+            out.push(0);
+        }
+        if b {
+            out[i / 8] |= 1 << (i % 8);
+        }
+    }
+    out
+}
+
+/// The validity bitmap of `values` and their null count; no bitmap if
+/// none is null.
+fn validity<T>(values: &[Option<T>]) -> (Option<Buffer>, usize) {
+    let nulls = values.iter().filter(|v| v.is_none()).count();
+    if nulls == 0 {
+        return (None, 0);
+    }
+    let bits = bitmap(values.iter().map(Option::is_some));
+    (Some(Buffer::Bytes(bits)), nulls)
+}
+
+fn export_column(data: ColumnData) -> (ArrowArray, &'static str) {
+    let len = data.len();
+    match data {
+        ColumnData::Boolean(v) => {
+            let (valid, nulls) = validity(&v);
+            let values = Buffer::Bytes(bitmap(v.iter().map(|b| *b == Some(true))));
+            (array(len, nulls, vec![valid, Some(values)], vec![]), "b")
+        }
+        ColumnData::UInt64(v) => {
+            let (valid, nulls) = validity(&v);
+            let values = Buffer::Words(v.iter().map(|n| n.unwrap_or(0)).collect());
+            (array(len, nulls, vec![valid, Some(values)], vec![]), "L")
+        }
+        ColumnData::Float64(v) => {
+            let (valid, nulls) = validity(&v);
+            let values = Buffer::Words(v.iter().map(|f| f.unwrap_or(0.0).to_bits()).collect());
+            (array(len, nulls, vec![valid, Some(values)], vec![]), "g")
+        }
+        ColumnData::Utf8(v) => {
+            let (valid, nulls) = validity(&v);
+            let mut offsets = vec![0i32];
+            let mut bytes = Vec::new();
+            for s in &v {
+                bytes.extend_from_slice(s.as_deref().unwrap_or("").as_bytes());
+                offsets.push(bytes.len() as i32);
+            }
+            let buffers = vec![
+                valid,
+                Some(Buffer::Offsets(offsets)),
+                Some(Buffer::Bytes(bytes)),
+            ];
+            (array(len, nulls, buffers, vec![]), "u")
+        }
+    }
+}
+
+impl RecordBatch {
+    pub fn num_rows(&self) -> usize {
+        self.time.len()
+    }
+
+    /// Move the batch into the structures of the Arrow C data interface:
+    /// a struct array with one child per column, `time` first.
+    ///
+    /// # Panics
+    ///
+    /// If a text column holds more than 2 GiB, which Arrow's `utf8` type
+    /// cannot address.
+    pub fn export(self) -> (ArrowArray, ArrowSchema) {
+        let rows = self.num_rows();
+        let mut arrays = vec![array(
+            rows,
+            0,
+            vec![None, Some(Buffer::Words(self.time))],
+            vec![],
+        )];
+        let mut fields = vec![schema("L", "time", 0, vec![])];
+        for column in self.columns {
+            if let ColumnData::Utf8(v) = &column.data {
+                let total: usize = v.iter().flatten().map(String::len).sum();
+                assert!(total <= i32::MAX as usize, "text column too large");
+            }
+            let (a, format) = export_column(column.data);
+            arrays.push(a);
+            fields.push(schema(format, &column.name, NULLABLE, vec![]));
+        }
+        (
+            array(rows, 0, vec![None], arrays),
+            schema("+s", "", 0, fields),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::VcdFile;
+    use std::ffi::CStr;
+
+    #[test]
+    fn export_batch() {
+        let mut vcd = VcdFile::from_bytes(
+            b"$scope module top $end $var wire 1 ! clk $end $var wire 8 \" d $end
+$var real 1 # r $end $var wire 70 $ w $end $upscope $end $enddefinitions $end
+#0 0! bx \" r1.5 # b1 $
+#5 1! b101 \"
+#10 0! r2 #
+#20 1!"
+                .to_vec(),
+        )
+        .unwrap();
+        let paths = ["top.clk", "top.d", "top.r", "top.w"];
+        let batch = to_arrow(&mut vcd, &paths, 3..20).unwrap();
+        assert_eq!(batch.time, [3, 5, 10]);
+        let data: Vec<_> = batch.columns.iter().map(|c| &c.data).collect();
+        assert_eq!(
+            *data[0],
+            ColumnData::Boolean(vec![Some(false), Some(true), Some(false)])
+        );
+        assert_eq!(*data[1], ColumnData::UInt64(vec![None, Some(5), Some(5)]));
+        assert_eq!(
+            *data[2],
+            ColumnData::Float64(vec![Some(1.5), Some(1.5), Some(2.0)])
+        );
+        assert!(matches!(data[3], ColumnData::Utf8(_)));
+        assert!(to_arrow(&mut vcd, &["top.nope"], 0..1).is_err());
+
+        let (array, schema) = batch.export();
+        unsafe {
+            assert_eq!(CStr::from_ptr(schema.format).to_str(), Ok("+s"));
+            assert_eq!(schema.n_children, 5);
+            let d = &**schema.children.add(2);
+            assert_eq!(CStr::from_ptr(d.name).to_str(), Ok("top.d"));
+            assert_eq!(CStr::from_ptr(d.format).to_str(), Ok("L"));
+
+            assert_eq!((array.length, array.n_children), (3, 5));
+            let d = &**array.children.add(2);
+            assert_eq!(d.null_count, 1);
+            let valid = *(*d.buffers as *const u8);
+            assert_eq!(valid, 0b110);
+            let values = *d.buffers.add(1) as *const u64;
+            assert_eq!(*values.add(1), 5);
+            let clk = &**array.children.add(1);
+            assert!((*clk.buffers).is_null());
+            assert_eq!(*(*clk.buffers.add(1) as *const u8), 0b010);
+        }
+    }
+}
@@ -0,0 +1,437 @@
+//! AXI4 transaction extraction.
+//!
+//! The channel signals of an AXI4 interface are sampled on the rising
+//! edges of its clock, taking the value each signal had just before the
+//! edge, and every valid/ready handshake becomes an address, data or
+//! response event. These are put together into [`WriteTransaction`]s and
+//! [`ReadTransaction`]s:
+//!
+//! * write data beats go to the oldest write address without all its
+//!   beats (AXI4 write data has no ID), possibly before its address;
+//! * write responses and read data go to the oldest outstanding
+//!   transaction with their ID.
+//!
+//! Signals are found by path through an [`AxiNames`] mapping. Only the
+//! clock, valid, ready, address and data signals are required; missing
+//! ID, length, size, burst, last and response signals read as zero
+//! (`WLAST`/`RLAST` then follow from the burst length). Payload bits that
+//! are not `0`/`1` also read as zero.
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+
+use crate::numeric::raw_bits;
+use crate::{InvalidData, Signal, Waveform};
+
+/// The AXI4 channel signals, in lower case.
+pub const SIGNALS: [&str; 30] = [
+    "aclk", "awid", "awaddr", "awlen", "awsize", "awburst", "awvalid", "awready", "wdata", "wstrb",
+    "wlast", "wvalid", "wready", "bid", "bresp", "bvalid", "bready", "arid", "araddr", "arlen",
+    "arsize", "arburst", "arvalid", "arready", "rid", "rdata", "rresp", "rlast", "rvalid",
+    "rready",
+];
+
+const COUNT: usize = SIGNALS.len();
+
+fn signal_index(name: &str) -> Option<usize> {
+    SIGNALS.iter().position(|s| s.eq_ignore_ascii_case(name))
+}
+
+/// Paths of the signals of one AXI4 interface.
+#[derive(Debug, Clone, Default)]
+pub struct AxiNames {
+    paths: HashMap<usize, String>,
+}
+
+impl AxiNames {
+    pub fn new() -> AxiNames {
+        AxiNames::default()
+    }
+
+    /// The usual naming: every signal is `prefix` followed by its lower
+    /// case name, e.g. `top.m_axi_awvalid` for the prefix `top.m_axi_`.
+    pub fn with_prefix(prefix: &str) -> AxiNames {
+        AxiNames {
+            paths: (0..COUNT)
+                .map(|i| (i, format!("{}{}", prefix, SIGNALS[i])))
+                .collect(),
+        }
+    }
+
+    /// Set the path of a channel signal, named like `AWVALID` in either
+    /// case.
+    pub fn set(&mut self, signal: &str, path: &str) -> Result<(), InvalidData> {
+        let i = signal_index(signal).ok_or(InvalidData("unknown AXI signal"))?;
+        self.paths.insert(i, path.to_string());
+        Ok(())
+    }
+
+    /// The path of a channel signal.
+    pub fn get(&self, signal: &str) -> Option<&str> {
+        self.paths.get(&signal_index(signal)?).map(String::as_str)
+    }
+}
+
+/// One data beat of a burst.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Beat {
+    /// Time of the clock edge of the handshake.
+    pub time: u64,
+    /// The raw data value.
+    pub data: Vec<u8>,
+    /// `WSTRB` for writes, `RRESP` for reads.
+    pub info: u64,
+}
+
+/// Fields of an address handshake.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Address {
+    pub time: u64,
+    pub id: u64,
+    pub addr: u64,
+    /// Number of beats minus one.
+    pub len: u64,
+    /// Log2 of the bytes per beat.
+    pub size: u64,
+    /// 0 for FIXED, 1 for INCR, 2 for WRAP.
+    pub burst: u64,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct WriteTransaction {
+    pub address: Address,
+    pub beats: Vec<Beat>,
+    /// Time of the response handshake and `BRESP`.
+    pub response: Option<(u64, u64)>,
+}
+
+impl WriteTransaction {
+    /// Time from the address handshake to the response.
+    pub fn latency(&self) -> Option<u64> {
+        let (t, _) = self.response?;
+        Some(t.saturating_sub(self.address.time))
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ReadTransaction {
+    pub address: Address,
+    pub beats: Vec<Beat>,
+    /// Whether the last beat arrived.
+    pub complete: bool,
+}
+
+impl ReadTransaction {
+    /// Time from the address handshake to the last data beat.
+    pub fn latency(&self) -> Option<u64> {
+        if !self.complete {
+            return None;
+        }
+        Some(self.beats.last()?.time.saturating_sub(self.address.time))
+    }
+
+    /// The worst `RRESP` of the beats.
+    pub fn response(&self) -> u64 {
+        self.beats.iter().map(|b| b.info).max().unwrap_or(0)
+    }
+}
+
+/// The transactions of an interface, in order of their address handshake.
+/// Transactions still in flight when the signals end are included.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct AxiTransactions {
+    pub writes: Vec<WriteTransaction>,
+    pub reads: Vec<ReadTransaction>,
+}
+
+/// Reads signal values just before increasing times.
+struct Sampler<'s> {
+    signal: Option<&'s Signal>,
+    next: usize,
+}
+
+impl<'s> Sampler<'s> {
+    fn before(&mut self, time: u64) -> Option<&'s [u8]> {
+        let s = self.signal?;
+        while self.next < s.len() && s.time(self.next) < time {
+            self.next += 1;
+        }
+        self.next.checked_sub(1).map(|i| s.value(i))
+    }
+
+    fn number(&mut self, time: u64) -> u64 {
+        self.before(time)
+            .and_then(|v| raw_bits(v, v.len() as u32))
+            .unwrap_or(0)
+    }
+
+    fn high(&mut self, time: u64) -> bool {
+        self.number(time) == 1
+    }
+}
+
+/// Decode the transactions of an interface from its signals, indexed like
+/// [`SIGNALS`].
+fn decode(signals: &[Option<&Signal>; COUNT], out: &mut AxiTransactions) {
+    fn i(name: &str) -> usize {
+        signal_index(name).expect("known signal")
+    }
+    let mut s: Vec<Sampler<'_>> = signals
+        .iter()
+        .map(|&signal| Sampler { signal, next: 0 })
+        .collect();
+    let has = |name| signals[i(name)].is_some();
+    let writes = ["awvalid", "awready", "wvalid", "wready", "bvalid", "bready"]
+        .into_iter()
+        .all(has);
+    let reads = ["arvalid", "arready", "rvalid", "rready"]
+        .into_iter()
+        .all(has);
+
+    // Indices into `out.writes` / `out.reads`.
+    let mut unfilled: VecDeque<usize> = VecDeque::new();
+    let mut pending_beats: VecDeque<(Beat, bool)> = VecDeque::new();
+    let mut awaiting_response: HashMap<u64, VecDeque<usize>> = HashMap::new();
+    let mut reading: HashMap<u64, VecDeque<usize>> = HashMap::new();
+
+    let Some(clk) = signals[i("aclk")] else {
+        return;
+    };
+    let mut prev = None;
+    for (time, value) in clk {
+        let rising = prev == Some(&b"0"[..]) && value == b"1";
+        prev = Some(value);
+        if !rising {
+            continue;
+        }
+        let t = time;
+        // The ID, address, length, size and burst signals of a channel.
+        let address = |s: &mut [Sampler<'_>], [id, addr, len, size, burst]: [&str; 5]| Address {
+            time: t,
+            id: s[i(id)].number(t),
+            addr: s[i(addr)].number(t),
+            len: s[i(len)].number(t),
+            size: s[i(size)].number(t),
+            burst: s[i(burst)].number(t),
+        };
+        let beat = |s: &mut [Sampler<'_>], data: &str, info: &str| Beat {
+            time: t,
+            data: s[i(data)].before(t).unwrap_or_default().to_vec(),
+            info: s[i(info)].number(t),
+        };
+
+        if writes {
+            if s[i("awvalid")].high(t) && s[i("awready")].high(t) {
+                let a = address(&mut s, ["awid", "awaddr", "awlen", "awsize", "awburst"]);
+                out.writes.push(WriteTransaction {
+                    address: a,
+                    beats: Vec::new(),
+                    response: None,
+                });
+                unfilled.push_back(out.writes.len() - 1);
+            }
+            if s[i("wvalid")].high(t) && s[i("wready")].high(t) {
+                let last = s[i("wlast")].high(t);
+                pending_beats.push_back((beat(&mut s, "wdata", "wstrb"), last));
+            }
+            // Hand buffered beats to transactions with a known address.
+            while let (Some(&w), Some(_)) = (unfilled.front(), pending_beats.front()) {
+                let (b, last) = pending_beats.pop_front().expect("checked");
+                let txn = &mut out.writes[w];
+                txn.beats.push(b);
+                let done = match signals[i("wlast")] {
+                    Some(_) => last,
+                    None => txn.beats.len() as u64 > txn.address.len,
+                };
+                if done {
+                    unfilled.pop_front();
+                    awaiting_response
+                        .entry(txn.address.id)
+                        .or_default()
+                        .push_back(w);
+                }
+            }
+            if s[i("bvalid")].high(t) && s[i("bready")].high(t) {
+                let id = s[i("bid")].number(t);
+                let resp = s[i("bresp")].number(t);
+                if let Some(w) = awaiting_response.get_mut(&id).and_then(VecDeque::pop_front) {
+                    out.writes[w].response = Some((t, resp));
+                }
+            }
+        }
+
+        if reads {
+            if s[i("arvalid")].high(t) && s[i("arready")].high(t) {
+                let a = address(&mut s, ["arid", "araddr", "arlen", "arsize", "arburst"]);
+                out.reads.push(ReadTransaction {
+                    address: a,
+                    beats: Vec::new(),
+                    complete: false,
+                });
+                reading
+                    .entry(a.id)
+                    .or_default()
+                    .push_back(out.reads.len() - 1);
+            }
+            if s[i("rvalid")].high(t) && s[i("rready")].high(t) {
+                let id = s[i("rid")].number(t);
+                let last = s[i("rlast")].high(t);
+                let b = beat(&mut s, "rdata", "rresp");
+                if let Some(queue) = reading.get_mut(&id) {
+                    if let Some(&r) = queue.front() {
+                        let txn = &mut out.reads[r];
+                        txn.beats.push(b);
+                        let done = match signals[i("rlast")] {
+                            Some(_) => last,
+                            None => txn.beats.len() as u64 > txn.address.len,
+                        };
+                        if done {
+                            txn.complete = true;
+                            queue.pop_front();
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Extract the transactions of the interface named by `names`.
+pub fn extract<F>(wave: &mut F, names: &AxiNames) -> io::Result<AxiTransactions>
+where
+    F: Waveform + ?Sized,
+{
+    let mut ids = Vec::new();
+    let mut slots = [None; COUNT];
+    for (i, slot) in slots.iter_mut().enumerate() {
+        let Some(path) = names.paths.get(&i) else {
+            continue;
+        };
+        if let Some(var) = wave.hierarchy().lookup(path) {
+            *slot = Some(ids.len());
+            ids.push(var.signal);
+        }
+    }
+    // Signals without which an interface, or its write or read side if
+    // present, cannot be decoded.
+    let present = |name| slots[signal_index(name).expect("known signal")].is_some();
+    for (side, required) in [
+        ("aclk", "aclk"),
+        ("awvalid", "awaddr"),
+        ("awvalid", "wdata"),
+        ("arvalid", "araddr"),
+        ("arvalid", "rdata"),
+    ] {
+        if (side == "aclk" || present(side)) && !present(required) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("AXI signal {} not found", required.to_ascii_uppercase()),
+            ));
+        }
+    }
+    let loaded = wave.load_signals(&ids)?;
+    let mut signals = [None; COUNT];
+    for (signal, slot) in signals.iter_mut().zip(slots) {
+        *signal = slot.map(|n| &loaded[n]);
+    }
+    let mut out = AxiTransactions::default();
+    decode(&signals, &mut out);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Hierarchy, MemoryWaveform, Scope, ScopeKind, SignalId, Var, VarKind};
+
+    /// A waveform with the given `(name, per-cycle values)`, one cycle per
+    /// 10 ticks with the rising clock edge at the start of each cycle.
+    fn wave(channels: &[(&str, &[&str])]) -> MemoryWaveform {
+        let mut signals = Vec::new();
+        let mut clk = Signal::new();
+        let cycles = channels.iter().map(|c| c.1.len()).max().unwrap_or(0);
+        for n in 0..=cycles as u64 {
+            clk.push(n * 10, b"1");
+            clk.push(n * 10 + 5, b"0");
+        }
+        signals.push(("aclk", clk));
+        for &(name, values) in channels {
+            let mut s = Signal::new();
+            // Values change half a cycle before the edge sampling them.
+            for (n, v) in values.iter().enumerate() {
+                s.push(n as u64 * 10 + 5, v.as_bytes());
+            }
+            signals.push((name, s));
+        }
+        let mut out = MemoryWaveform::new(Hierarchy::default(), None);
+        let mut scope = Scope::new(ScopeKind::Module, out.hierarchy_mut().intern("top"));
+        for (n, (name, signal)) in signals.into_iter().enumerate() {
+            scope.vars.push(Var {
+                kind: VarKind::Wire,
+                width: 1,
+                signal: SignalId(n as u64),
+                name: out.hierarchy_mut().intern(&format!("axi_{}", name)),
+                index: None,
+            });
+            out.insert_signal(SignalId(n as u64), signal);
+        }
+        out.hierarchy_mut().scopes.push(scope);
+        out
+    }
+
+    #[test]
+    fn burst_write_and_reads() {
+        let mut w = wave(&[
+            ("awvalid", &["0", "1", "0", "0", "0", "0"]),
+            ("awready", &["1", "1", "1", "1", "1", "1"]),
+            ("awaddr", &["0", "10000000", "0", "0", "0", "0"]),
+            ("awlen", &["0", "1", "0", "0", "0", "0"]),
+            // Data before its address is legal and must not get lost.
+            ("wvalid", &["1", "1", "0", "0", "0", "0"]),
+            ("wready", &["1", "1", "1", "1", "1", "1"]),
+            ("wdata", &["1010", "0101", "0", "0", "0", "0"]),
+            ("wlast", &["0", "1", "0", "0", "0", "0"]),
+            ("bvalid", &["0", "0", "0", "1", "0", "0"]),
+            ("bready", &["1", "1", "1", "1", "1", "1"]),
+            ("arvalid", &["1", "1", "0", "0", "0", "0"]),
+            ("arready", &["1", "1", "1", "1", "1", "1"]),
+            ("arid", &["0", "1", "0", "0", "0", "0"]),
+            ("araddr", &["100", "1000", "0", "0", "0", "0"]),
+            ("rvalid", &["0", "0", "1", "1", "0", "0"]),
+            ("rready", &["1", "1", "1", "1", "1", "1"]),
+            // Out-of-order completion across IDs.
+            ("rid", &["0", "0", "1", "0", "0", "0"]),
+            ("rdata", &["0", "0", "1111", "1", "0", "0"]),
+            ("rlast", &["0", "0", "1", "1", "0", "0"]),
+            ("rresp", &["0", "0", "0", "10", "0", "0"]),
+        ]);
+        let txns = extract(&mut w, &AxiNames::with_prefix("top.axi_")).unwrap();
+        assert_eq!(txns.writes.len(), 1);
+        let wr = &txns.writes[0];
+        assert_eq!((wr.address.addr, wr.address.len), (0x80, 1));
+        assert_eq!(wr.beats.len(), 2);
+        assert_eq!(wr.beats[1].data, b"0101");
+        assert_eq!(wr.response, Some((40, 0)));
+        assert_eq!(wr.beats[0].time, 10);
+        assert_eq!(wr.latency(), Some(20));
+
+        assert_eq!(txns.reads.len(), 2);
+        assert_eq!(txns.reads[0].address.addr, 4);
+        assert_eq!(txns.reads[0].beats[0].time, 40);
+        assert_eq!(txns.reads[0].response(), 2);
+        assert_eq!(txns.reads[1].beats[0].data, b"1111");
+        assert_eq!(txns.reads[1].latency(), Some(10));
+    }
+
+    #[test]
+    fn names() {
+        let mut names = AxiNames::new();
+        names.set("ACLK", "top.clk").unwrap();
+        assert_eq!(names.get("aclk"), Some("top.clk"));
+        assert!(names.set("AXVALID", "x").is_err());
+        assert_eq!(AxiNames::with_prefix("s_").get("RREADY"), Some("s_rready"));
+        let mut w = wave(&[("awvalid", &["0"])]);
+        let err = extract(&mut w, &AxiNames::with_prefix("top.axi_")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
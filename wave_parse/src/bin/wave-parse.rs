@@ -0,0 +1,175 @@
+use std::env;
+use std::path::Path;
+use std::process::ExitCode;
+
+use wave_parse::align::{self, AlignOptions};
+use wave_parse::convert::{self, ConvertOptions};
+use wave_parse::diff::{self, DiffOptions};
+use wave_parse::merge;
+
+const USAGE: &str = "usage: wave-parse convert <input> <output> [options]
+       wave-parse extract <input> <output> <path>... [options]
+       wave-parse diff <first> <second> [diff options]
+       wave-parse merge <output> [<prefix>=]<input>...
+
+Converts between waveform formats, picked by file extension.
+Inputs: .vcd .fst .ghw .lxt2 .sr .csv .tsv    Outputs: .vcd .fst .csv .parquet
+
+options:
+    --scope <path>    only keep the scope at this dot-separated path
+    --from <time>     drop changes before this time (in input ticks)
+    --to <time>       drop changes at or after this time
+    --compact-ids     renumber VCD identifier codes densely
+    --radix <radix>   write CSV values as bin, oct, hex, dec, signed or ascii
+
+Extract writes only the scopes and variables at the given paths.
+
+Compares two waveforms signal by signal and exits with failure if they differ.
+
+diff options:
+    --offset <ticks>         shift the second waveform in time (may be negative)
+    --rename <from>=<to>     match paths under <from> with paths under <to>
+    --align <path>[=<other>] find the offset that best lines up this signal
+                             (and <other> in the second waveform)
+    --tolerance <ticks>      how far apart changes may be and match for --align
+
+Merges waveforms onto one timeline, each under a top-level scope named by
+its prefix (by default the input file name without extension).";
+
+fn parse_time(v: Option<String>, flag: &str) -> Result<u64, String> {
+    v.ok_or_else(|| format!("{} needs a value", flag))?
+        .parse()
+        .map_err(|_| format!("invalid time for {}", flag))
+}
+
+/// Runs `convert`, or `extract` with the paths to keep after the output.
+fn convert_command(args: impl Iterator<Item = String>, extract: bool) -> Result<(), String> {
+    let mut args = args.peekable();
+    let mut paths = Vec::new();
+    let mut options = ConvertOptions::default();
+    let (mut from, mut to) = (None, None);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--scope" => options.scope = Some(args.next().ok_or("--scope needs a value")?),
+            "--from" => from = Some(parse_time(args.next(), "--from")?),
+            "--to" => to = Some(parse_time(args.next(), "--to")?),
+            "--compact-ids" => options.vcd.compact_ids = true,
+            "--radix" => {
+                let v = args.next().ok_or("--radix needs a value")?;
+                options.radix = Some(v.parse().map_err(|_| format!("unknown radix {}", v))?);
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ => paths.push(arg),
+        }
+    }
+    let (input, output) = match &paths[..] {
+        [input, output] if !extract => (input, output),
+        [input, output, selected @ ..] if extract && !selected.is_empty() => {
+            options.paths = selected.to_vec();
+            (input, output)
+        }
+        _ => return Err(USAGE.to_string()),
+    };
+    if from.is_some() || to.is_some() {
+        options.window = Some(from.unwrap_or(0)..to.unwrap_or(u64::MAX));
+    }
+    convert::convert(Path::new(input), Path::new(output), &options)
+        .map_err(|e| format!("{}: {}", input, e))
+}
+
+fn diff_command(args: impl Iterator<Item = String>) -> Result<bool, String> {
+    let mut args = args.peekable();
+    let mut paths = Vec::new();
+    let mut options = DiffOptions::default();
+    let mut align_path = None;
+    let mut align_options = AlignOptions::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--offset" => {
+                options.offset = args
+                    .next()
+                    .ok_or("--offset needs a value")?
+                    .parse()
+                    .map_err(|_| "invalid time for --offset")?
+            }
+            "--rename" => {
+                let v = args.next().ok_or("--rename needs a value")?;
+                let (from, to) = v.split_once('=').ok_or("--rename needs <from>=<to>")?;
+                options.rename.push((from.to_string(), to.to_string()));
+            }
+            "--align" => align_path = Some(args.next().ok_or("--align needs a value")?),
+            "--tolerance" => align_options.tolerance = parse_time(args.next(), "--tolerance")?,
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ => paths.push(arg),
+        }
+    }
+    let [first, second] = &paths[..] else {
+        return Err(USAGE.to_string());
+    };
+    let mut a = convert::open(Path::new(first)).map_err(|e| format!("{}: {}", first, e))?;
+    let mut b = convert::open(Path::new(second)).map_err(|e| format!("{}: {}", second, e))?;
+    if let Some(path) = &align_path {
+        let (path, other) = path.split_once('=').unwrap_or((path, path));
+        let offset = align::align(&mut *a, path, &mut *b, other, &align_options)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("no offset lines up {}", path))?;
+        println!("aligned with offset {}", offset);
+        options.offset = offset;
+    }
+    let report = diff::diff(&mut *a, &mut *b, &options).map_err(|e| e.to_string())?;
+    println!("{}", report);
+    Ok(report.is_empty())
+}
+
+fn merge_command(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let args: Vec<String> = args.collect();
+    let [output, inputs @ ..] = &args[..] else {
+        return Err(USAGE.to_string());
+    };
+    if inputs.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    let mut merged = merge::MergedWaveform::default();
+    for input in inputs {
+        let (prefix, path) = match input.split_once('=') {
+            Some((prefix, path)) => (prefix.to_string(), Path::new(path)),
+            None => {
+                let path = Path::new(input);
+                let stem = path.file_stem().unwrap_or_default();
+                (stem.to_string_lossy().into_owned(), path)
+            }
+        };
+        let wave = convert::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        merged
+            .add(&prefix, wave)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    convert::save(&mut merged, Path::new(output), &Default::default())
+        .map_err(|e| format!("{}: {}", output, e))
+}
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let result = match args.next().as_deref() {
+        Some("convert") => convert_command(args, false),
+        Some("extract") => convert_command(args, true),
+        Some("merge") => merge_command(args),
+        Some("diff") => match diff_command(args) {
+            Ok(true) => Ok(()),
+            Ok(false) => return ExitCode::FAILURE,
+            Err(e) => Err(e),
+        },
+        Some("-h" | "--help") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Time ranges in which a waveform recorded no values.
+//!
+//! Between `$dumpoff` and `$dumpon` a VCD writer records no changes. It
+//! should set every variable to `x` at the `$dumpoff`, but not all
+//! writers do, and a query inside the gap then returns the last value from
+//! before it as if it were still current. [`Waveform::blackouts`] lists
+//! the gaps, and [`Blackouts::value_at`] answers [`Sample::NoData`] inside
+//! them.
+
+use std::ops::Range;
+
+use crate::Signal;
+
+/// The value of a signal at some time.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Sample<'a> {
+    Value(&'a [u8]),
+    /// Nothing was assigned yet.
+    Unassigned,
+    /// The time is inside a blackout.
+    NoData,
+}
+
+impl<'a> Sample<'a> {
+    /// The value, if there is one.
+    pub fn value(self) -> Option<&'a [u8]> {
+        match self {
+            Sample::Value(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// The blackouts of a waveform: sorted, disjoint, non-empty time ranges.
+/// A blackout never ended ends at `u64::MAX`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Blackouts {
+    ranges: Vec<Range<u64>>,
+}
+
+impl Blackouts {
+    /// Blackouts covering `ranges`, which may overlap and come in any
+    /// order.
+    pub fn new<I: IntoIterator<Item = Range<u64>>>(ranges: I) -> Blackouts {
+        let mut sorted: Vec<Range<u64>> = ranges.into_iter().filter(|r| !r.is_empty()).collect();
+        sorted.sort_by_key(|r| r.start);
+        let mut merged: Vec<Range<u64>> = Vec::with_capacity(sorted.len());
+        for r in sorted {
+            match merged.last_mut() {
+                Some(last) if r.start <= last.end => last.end = last.end.max(r.end),
+                _ => merged.push(r),
+            }
+        }
+        Blackouts { ranges: merged }
+    }
+
+    /// Blackouts from `$dumpoff` (`false`) and `$dumpon` (`true`) edges in
+    /// time order. Repeated edges have no effect.
+    pub(crate) fn from_edges<I: IntoIterator<Item = (u64, bool)>>(edges: I) -> Blackouts {
+        let mut ranges = Vec::new();
+        let mut off = None;
+        for (time, on) in edges {
+            match (on, off) {
+                (false, None) => off = Some(time),
+                (true, Some(start)) => {
+                    ranges.push(start..time);
+                    off = None;
+                }
+                _ => {}
+            }
+        }
+        ranges.extend(off.map(|start| start..u64::MAX));
+        Blackouts::new(ranges)
+    }
+
+    pub fn ranges(&self) -> &[Range<u64>] {
+        &self.ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// The blackout containing `time`, if any.
+    pub fn at(&self, time: u64) -> Option<Range<u64>> {
+        let i = self.ranges.partition_point(|r| r.end <= time);
+        self.ranges.get(i).filter(|r| r.start <= time).cloned()
+    }
+
+    pub fn contains(&self, time: u64) -> bool {
+        self.at(time).is_some()
+    }
+
+    /// The value of `signal` at `time`, or [`Sample::NoData`] inside a
+    /// blackout.
+    pub fn value_at<'s>(&self, signal: &'s Signal, time: u64) -> Sample<'s> {
+        if self.contains(time) {
+            return Sample::NoData;
+        }
+        signal
+            .value_at(time)
+            .map_or(Sample::Unassigned, Sample::Value)
+    }
+
+    /// Index of the last change of `signal` strictly before `time`,
+    /// skipping changes inside blackouts, such as the `x` values written
+    /// at a `$dumpoff`.
+    pub fn prev_change(&self, signal: &Signal, time: u64) -> Option<usize> {
+        let mut i = signal.prev_change(time)?;
+        while let Some(blackout) = self.at(signal.time(i)) {
+            i = signal.prev_change(blackout.start)?;
+        }
+        Some(i)
+    }
+
+    /// Index of the first change of `signal` strictly after `time`,
+    /// skipping changes inside blackouts. The values dumped at a
+    /// `$dumpon` are the first after a blackout.
+    pub fn next_change(&self, signal: &Signal, time: u64) -> Option<usize> {
+        let mut i = signal.next_change(time)?;
+        while let Some(blackout) = self.at(signal.time(i)) {
+            i = signal.next_change(blackout.end.checked_sub(1)?)?;
+        }
+        Some(i)
+    }
+
+    /// The blackouts with each bound passed through `f`, e.g. to move
+    /// them in time. Open ends stay open.
+    pub fn map_times<F: Fn(u64) -> u64>(&self, f: F) -> Blackouts {
+        let map = |t: u64| if t == u64::MAX { t } else { f(t) };
+        Blackouts::new(self.ranges.iter().map(|r| map(r.start)..map(r.end)))
+    }
+}
+
+/// Serializes as the sequence of ranges.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Blackouts {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        self.ranges.serialize(s)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Blackouts {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Blackouts, D::Error> {
+        Vec::<Range<u64>>::deserialize(d).map(Blackouts::new)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ranges_and_queries() {
+        let b = Blackouts::from_edges([
+            (10, false),
+            (12, false),
+            (20, true),
+            (20, true),
+            (30, false),
+            (30, true),
+            (40, false),
+        ]);
+        assert_eq!(b.ranges(), [10..20, 40..u64::MAX]);
+        assert_eq!(b.at(19), Some(10..20));
+        assert!(!b.contains(20) && !b.contains(9) && b.contains(u64::MAX - 1));
+        assert_eq!(Blackouts::new([5..8, 0..6, 9..9]).at(7), Some(0..8));
+
+        let mut s = Signal::new();
+        s.push(5, b"1");
+        assert_eq!(b.value_at(&s, 4), Sample::Unassigned);
+        assert_eq!(b.value_at(&s, 15), Sample::NoData);
+        assert_eq!(b.value_at(&s, 25).value(), Some(&b"1"[..]));
+
+        s.push(10, b"x");
+        s.push(20, b"0");
+        s.push(40, b"x");
+        assert_eq!(b.prev_change(&s, 25), Some(2));
+        assert_eq!(b.prev_change(&s, 20), Some(0));
+        assert_eq!(b.prev_change(&s, 5), None);
+        assert_eq!(b.next_change(&s, 5), Some(2));
+        assert_eq!(b.next_change(&s, 20), None);
+
+        let shifted = b.map_times(|t| t.saturating_sub(15));
+        assert_eq!(shifted.ranges(), [0..5, 25..u64::MAX]);
+    }
+}
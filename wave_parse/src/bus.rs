@@ -0,0 +1,307 @@
+//! Vectors reassembled from bits dumped one by one.
+//!
+//! Some tools dump a bus as separate 1-bit variables, named `data_0`,
+//! `data_1`, … or `data[0]`, `data[1]`, …, which viewers then show as
+//! unrelated wires. [`find_buses`] groups such bits back into buses, and a
+//! [`BusWaveform`] adds each bus as a vector variable next to its bits,
+//! with the bits' changes merged into one stream by [`merge_bits`].
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+use crate::{
+    Blackouts, Hierarchy, Interner, ReferenceIndex, Signal, SignalId, SignalLoader, Timescale, Var,
+    VarKind, Waveform,
+};
+
+/// A group of 1-bit variables forming a vector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bus {
+    /// Names of the scopes declaring the bits, outermost first.
+    pub scope: Vec<String>,
+    /// The name without the bit index.
+    pub name: String,
+    pub msb: i32,
+    pub lsb: i32,
+    /// The signals of the bits, most significant first.
+    pub bits: Vec<SignalId>,
+}
+
+impl Bus {
+    /// The dot-separated path of the bus.
+    pub fn path(&self) -> String {
+        let mut parts = self.scope.clone();
+        parts.push(self.name.clone());
+        parts.join(".")
+    }
+}
+
+/// The name and index of a variable holding one bit of a bus.
+fn bit_of<'n>(names: &'n Interner, var: &Var) -> Option<(&'n str, i32)> {
+    if var.width != 1 || var.kind.is_real() || var.kind == VarKind::String {
+        return None;
+    }
+    let name = names.resolve(var.name);
+    match var.index {
+        Some(ReferenceIndex::BitSelect(i)) => return Some((name, i)),
+        Some(ReferenceIndex::Range(..)) => return None,
+        None => {}
+    }
+    let (base, index) = match name.strip_suffix(']') {
+        Some(rest) => rest.rsplit_once('[')?,
+        None => name.rsplit_once('_')?,
+    };
+    let digits = !index.is_empty() && index.bytes().all(|c| c.is_ascii_digit());
+    if base.is_empty() || !digits {
+        return None;
+    }
+    Some((base, index.parse().ok()?))
+}
+
+/// The buses among `vars`, declared in `scope`.
+fn scope_buses(names: &Interner, scope: &[String], vars: &[Var], out: &mut Vec<Bus>) {
+    let mut groups: Vec<(&str, Vec<(i32, SignalId)>)> = Vec::new();
+    for var in vars {
+        let Some((name, index)) = bit_of(names, var) else {
+            continue;
+        };
+        match groups.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, bits)) => bits.push((index, var.signal)),
+            None => groups.push((name, vec![(index, var.signal)])),
+        }
+    }
+    for (name, mut bits) in groups {
+        bits.sort_by_key(|&(index, _)| std::cmp::Reverse(index));
+        bits.dedup_by_key(|&mut (index, _)| index);
+        let (msb, lsb) = (bits[0].0, bits[bits.len() - 1].0);
+        let contiguous = (msb as i64 - lsb as i64 + 1) as usize == bits.len();
+        // A variable already named like the bus would hide it.
+        let taken = vars
+            .iter()
+            .any(|v| names.resolve(v.name) == name && v.index.is_none());
+        if bits.len() < 2 || !contiguous || taken {
+            continue;
+        }
+        out.push(Bus {
+            scope: scope.to_vec(),
+            name: name.to_string(),
+            msb,
+            lsb,
+            bits: bits.into_iter().map(|(_, id)| id).collect(),
+        });
+    }
+}
+
+/// The buses of `hierarchy`: in each scope, 1-bit variables sharing a name
+/// but for a bit index, whose indices are contiguous. Repeated indices
+/// count once.
+pub fn find_buses(hierarchy: &Hierarchy) -> Vec<Bus> {
+    fn walk(
+        names: &Interner,
+        scope: &mut Vec<String>,
+        scopes: &[crate::Scope],
+        out: &mut Vec<Bus>,
+    ) {
+        for s in scopes {
+            scope.push(names.resolve(s.name).to_string());
+            scope_buses(names, scope, &s.vars, out);
+            walk(names, scope, &s.scopes, out);
+            scope.pop();
+        }
+    }
+    let names = hierarchy.names();
+    let mut out = Vec::new();
+    scope_buses(names, &[], &hierarchy.vars, &mut out);
+    walk(names, &mut Vec::new(), &hierarchy.scopes, &mut out);
+    out
+}
+
+/// The vector whose bits, most significant first, are the 1-bit `bits`.
+/// It changes whenever a bit does; bits not assigned yet are `x`.
+pub fn merge_bits(bits: &[Signal]) -> Signal {
+    let mut out = Signal::new();
+    let mut value = vec![b'x'; bits.len()];
+    let mut next = vec![0; bits.len()];
+    while let Some(time) = bits
+        .iter()
+        .zip(&next)
+        .filter(|(s, &i)| i < s.len())
+        .map(|(s, &i)| s.time(i))
+        .min()
+    {
+        for ((s, i), bit) in bits.iter().zip(&mut next).zip(&mut value) {
+            while *i < s.len() && s.time(*i) == time {
+                *bit = s.value(*i).last().copied().unwrap_or(b'x');
+                *i += 1;
+            }
+        }
+        if out.is_empty() || out.value(out.len() - 1) != value {
+            out.push(time, &value);
+        }
+    }
+    out
+}
+
+/// A waveform extended with the buses of its per-bit variables.
+#[derive(Debug)]
+pub struct BusWaveform<W> {
+    inner: W,
+    hierarchy: Hierarchy,
+    buses: Vec<Bus>,
+    ids: HashMap<SignalId, usize>,
+}
+
+impl<W: Waveform> BusWaveform<W> {
+    /// Wrap `inner`, adding a variable for each bus [`find_buses`] finds
+    /// to the scope of its bits.
+    pub fn new(inner: W) -> BusWaveform<W> {
+        let mut hierarchy = inner.hierarchy().clone();
+        let buses = find_buses(&hierarchy);
+        let next_id = hierarchy
+            .signal_ids()
+            .iter()
+            .map(|id| id.0 + 1)
+            .max()
+            .unwrap_or(0);
+        let mut ids = HashMap::new();
+        for (n, bus) in buses.iter().enumerate() {
+            let id = SignalId(next_id + n as u64);
+            let name = hierarchy.intern(&bus.name);
+            let names = Arc::clone(hierarchy.names());
+            let mut vars = &mut hierarchy.vars;
+            let mut scopes = &mut hierarchy.scopes;
+            for scope_name in &bus.scope {
+                let scope = scopes
+                    .iter_mut()
+                    .find(|s| names.resolve(s.name) == scope_name)
+                    .expect("bus found in this scope");
+                vars = &mut scope.vars;
+                scopes = &mut scope.scopes;
+            }
+            // Before its bits, so the bus is found by a lookup of its name
+            // even when they are named like it with a bit select.
+            let at = vars
+                .iter()
+                .position(|v| bit_of(&names, v).is_some_and(|(base, _)| base == bus.name))
+                .unwrap_or(vars.len());
+            vars.insert(
+                at,
+                Var {
+                    kind: VarKind::Wire,
+                    width: bus.bits.len() as u32,
+                    signal: id,
+                    name,
+                    index: Some(ReferenceIndex::Range(bus.msb, bus.lsb)),
+                },
+            );
+            ids.insert(id, n);
+        }
+        BusWaveform {
+            inner,
+            hierarchy,
+            buses,
+            ids,
+        }
+    }
+
+    /// The buses added, in hierarchy order.
+    pub fn buses(&self) -> &[Bus] {
+        &self.buses
+    }
+
+    /// The bus behind a signal, if it was added rather than read from the
+    /// inner waveform.
+    pub fn bus(&self, id: SignalId) -> Option<&Bus> {
+        self.ids.get(&id).map(|&n| &self.buses[n])
+    }
+
+    pub fn inner(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Waveform> SignalLoader for BusWaveform<W> {
+    fn load_signals(&mut self, ids: &[SignalId]) -> io::Result<Vec<Signal>> {
+        let real: Vec<SignalId> = ids
+            .iter()
+            .copied()
+            .filter(|id| !self.ids.contains_key(id))
+            .collect();
+        let mut loaded = self.inner.load_signals(&real)?.into_iter();
+        let mut out = Vec::with_capacity(ids.len());
+        for id in ids {
+            match self.ids.get(id) {
+                Some(&n) => {
+                    let bits = self.inner.load_signals(&self.buses[n].bits)?;
+                    out.push(merge_bits(&bits));
+                }
+                None => out.push(loaded.next().expect("one signal per id")),
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl<W: Waveform> Waveform for BusWaveform<W> {
+    fn hierarchy(&self) -> &Hierarchy {
+        &self.hierarchy
+    }
+
+    fn timescale(&self) -> Option<Timescale> {
+        self.inner.timescale()
+    }
+
+    fn date(&self) -> Option<&str> {
+        self.inner.date()
+    }
+
+    fn blackouts(&self) -> io::Result<Blackouts> {
+        self.inner.blackouts()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::VcdFile;
+
+    #[test]
+    fn buses() {
+        let vcd = VcdFile::from_bytes(
+            b"$scope module top $end
+$var wire 1 ! data_0 $end $var wire 1 \" data_1 $end $var wire 1 # data_2 $end
+$var wire 1 $ q [1] $end $var wire 1 % q [0] $end
+$var wire 1 & gap_0 $end $var wire 1 ' gap_2 $end
+$var wire 1 ( v[4] $end $var wire 1 ) v[5] $end $var wire 1 * v $end
+$upscope $end $enddefinitions $end
+#0 1! 0\" 0$ 1%
+#5 1# 1\" 1$
+#8 1!
+#9 0!"
+                .to_vec(),
+        )
+        .unwrap();
+        let mut wave = BusWaveform::new(vcd);
+        let paths: Vec<String> = wave.buses().iter().map(Bus::path).collect();
+        assert_eq!(paths, ["top.data", "top.q"]);
+        assert_eq!(wave.buses()[0].msb, 2);
+
+        let data = wave.hierarchy().lookup("top.data").unwrap().clone();
+        assert_eq!(data.width, 3);
+        assert_eq!(data.index, Some(ReferenceIndex::Range(2, 0)));
+        assert!(wave.bus(data.signal).is_some());
+        let q = wave.hierarchy().lookup("top.q").unwrap().signal;
+        let a = SignalId::from_code(b"!").unwrap();
+
+        let s = wave.load_signals(&[data.signal, a, q]).unwrap();
+        let changes: Vec<(u64, &[u8])> = s[0].iter().collect();
+        assert_eq!(changes, [(0, &b"x01"[..]), (5, b"111"), (9, b"110")]);
+        assert_eq!(s[1].len(), 3);
+        assert_eq!(s[2].value(1), b"11");
+    }
+}
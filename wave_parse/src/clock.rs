@@ -0,0 +1,203 @@
+//! Detection of clock signals.
+//!
+//! A 1-bit signal is taken for a clock if, after its initial value, it
+//! alternates between `0` and `1` at regular intervals: at least
+//! [`MIN_EDGES`] rising edges whose spacing deviates from the mean by less
+//! than a relative tolerance. Detected clocks convert between time and
+//! cycle numbers using their actual edges, so they stay exact for clocks
+//! with jitter or a changing frequency within the tolerance.
+
+use std::io;
+
+use crate::{Signal, Waveform};
+
+/// Minimum number of rising edges of a clock.
+pub const MIN_EDGES: usize = 4;
+
+/// Default relative tolerance on the period.
+pub const DEFAULT_TOLERANCE: f64 = 0.1;
+
+/// A detected clock.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clock {
+    /// Mean time between rising edges, in ticks.
+    pub period: f64,
+    /// Mean fraction of the period the clock is high.
+    pub duty_cycle: f64,
+    /// Standard deviation of the time between rising edges, in ticks.
+    pub jitter: f64,
+    /// Times of the rising edges.
+    pub rising: Vec<u64>,
+}
+
+impl Clock {
+    /// Analyze a 1-bit signal, returning `None` if it is not a clock within
+    /// `tolerance`, the allowed deviation of any period from the mean
+    /// relative to the mean.
+    pub fn detect(signal: &Signal, tolerance: f64) -> Option<Clock> {
+        let mut rising = Vec::new();
+        let mut falling = Vec::new();
+        let mut prev = None;
+        for (time, value) in signal {
+            let v = match value {
+                b"0" => b'0',
+                b"1" => b'1',
+                _ if rising.is_empty() => {
+                    prev = None;
+                    continue;
+                }
+                _ => return None,
+            };
+            match (prev, v) {
+                (Some(b'0'), b'1') => rising.push(time),
+                (Some(b'1'), b'0') if !rising.is_empty() => falling.push(time),
+                _ => {}
+            }
+            prev = Some(v);
+        }
+        if rising.len() < MIN_EDGES {
+            return None;
+        }
+        let periods: Vec<f64> = rising.windows(2).map(|w| (w[1] - w[0]) as f64).collect();
+        let period = periods.iter().sum::<f64>() / periods.len() as f64;
+        if period == 0.0
+            || periods
+                .iter()
+                .any(|p| (p - period).abs() > tolerance * period)
+        {
+            return None;
+        }
+        let jitter = (periods.iter().map(|p| (p - period).powi(2)).sum::<f64>()
+            / periods.len() as f64)
+            .sqrt();
+        // High time of each full cycle: rising edge to the next falling edge.
+        let high: Vec<f64> = rising
+            .iter()
+            .zip(&falling)
+            .map(|(&r, &f)| f.saturating_sub(r) as f64)
+            .take(periods.len())
+            .collect();
+        let duty_cycle = match high.len() {
+            0 => 0.5,
+            n => high.iter().sum::<f64>() / n as f64 / period,
+        };
+        Some(Clock {
+            period,
+            duty_cycle,
+            jitter,
+            rising,
+        })
+    }
+
+    /// Frequency in cycles per tick.
+    pub fn frequency(&self) -> f64 {
+        1.0 / self.period
+    }
+
+    /// The cycle containing `time`: the number of rising edges before or at
+    /// it, minus one. `None` before the first rising edge.
+    pub fn cycle_at(&self, time: u64) -> Option<u64> {
+        let n = self.rising.partition_point(|&t| t <= time);
+        n.checked_sub(1).map(|c| c as u64)
+    }
+
+    /// The time of the rising edge starting cycle `cycle`.
+    pub fn cycle_start(&self, cycle: u64) -> Option<u64> {
+        self.rising.get(usize::try_from(cycle).ok()?).copied()
+    }
+}
+
+/// All 1-bit logic variables of `wave` that are clocks, with their paths.
+/// Variables sharing a signal are reported once, under their first path.
+pub fn find_clocks<F>(wave: &mut F, tolerance: f64) -> io::Result<Vec<(String, Clock)>>
+where
+    F: Waveform + ?Sized,
+{
+    let mut seen = std::collections::HashSet::new();
+    let candidates: Vec<_> = wave
+        .hierarchy()
+        .var_paths()
+        .into_iter()
+        .filter(|(_, v)| v.width == 1 && !v.kind.is_real() && seen.insert(v.signal))
+        .map(|(p, v)| (p, v.signal))
+        .collect();
+    let ids: Vec<_> = candidates.iter().map(|c| c.1).collect();
+    let signals = wave.load_signals(&ids)?;
+    Ok(candidates
+        .into_iter()
+        .zip(&signals)
+        .filter_map(|((path, _), s)| Some((path, Clock::detect(s, tolerance)?)))
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::VcdFile;
+
+    fn clock(edges: &[(u64, &str)]) -> Signal {
+        let mut s = Signal::new();
+        for &(t, v) in edges {
+            s.push(t, v.as_bytes());
+        }
+        s
+    }
+
+    #[test]
+    fn detection() {
+        let mut edges = vec![(0, "x")];
+        for i in 0..8 {
+            edges.push((10 * i + 1, "0"));
+            edges.push((10 * i + 4, "1"));
+        }
+        let clk = Clock::detect(&clock(&edges), DEFAULT_TOLERANCE).unwrap();
+        assert_eq!(clk.period, 10.0);
+        assert_eq!(clk.jitter, 0.0);
+        assert_eq!(clk.duty_cycle, 0.7);
+        assert_eq!(clk.cycle_at(3), None);
+        assert_eq!(clk.cycle_at(4), Some(0));
+        assert_eq!(clk.cycle_at(25), Some(2));
+        assert_eq!(clk.cycle_start(2), Some(24));
+
+        let jittery = clock(&[
+            (0, "0"),
+            (5, "1"),
+            (10, "0"),
+            (16, "1"),
+            (20, "0"),
+            (25, "1"),
+            (30, "0"),
+            (36, "1"),
+        ]);
+        let clk = Clock::detect(&jittery, 0.25).unwrap();
+        assert!((clk.period - 31.0 / 3.0).abs() < 1e-9);
+        assert!(clk.jitter > 0.0);
+        assert!(Clock::detect(&jittery, 0.05).is_none());
+
+        // Too few edges, glitches and unknowns after the start.
+        assert!(Clock::detect(&clock(&[(0, "0"), (5, "1"), (10, "0")]), 0.1).is_none());
+        let mut edges: Vec<_> = (0..10)
+            .map(|i| (i * 5, ["0", "1"][i as usize % 2]))
+            .collect();
+        edges.push((50, "x"));
+        assert!(Clock::detect(&clock(&edges), 0.1).is_none());
+    }
+
+    #[test]
+    fn waveform() {
+        let mut body = String::new();
+        for t in 0..10 {
+            body.push_str(&format!("#{} {}! b{:b} \"\n", t * 5, t % 2, t));
+        }
+        let input = format!(
+            "$scope module top $end $var wire 1 ! clk $end $var wire 4 \" n $end
+$upscope $end $enddefinitions $end\n{}",
+            body
+        );
+        let mut vcd = VcdFile::from_bytes(input.into_bytes()).unwrap();
+        let clocks = find_clocks(&mut vcd, DEFAULT_TOLERANCE).unwrap();
+        assert_eq!(clocks.len(), 1);
+        assert_eq!(clocks[0].0, "top.clk");
+        assert_eq!(clocks[0].1.period, 10.0);
+    }
+}
@@ -0,0 +1,399 @@
+//! Conversion between waveform formats.
+//!
+//! Inputs are opened by file extension: `.vcd`, `.fst` (see
+//! [`fst`](crate::fst)), `.ghw` (see [`ghw`](crate::ghw)), `.lxt2` (see
+//! [`lxt2`](crate::lxt2)), `.sr` (sigrok sessions) and `.csv`/`.tsv` (see
+//! [`csv`]). Outputs can be `.vcd`, `.fst`, `.csv` or `.parquet` (see
+//! [`parquet`](crate::parquet)). A conversion can be restricted to
+//! selected scopes and variables and to a time window with
+//! [`ConvertOptions`], e.g. to extract the relevant slice of a huge dump;
+//! only the selected signals are loaded.
+//!
+//! Other formats fail with [`io::ErrorKind::Unsupported`].
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::arrow::to_arrow;
+use crate::csv::{self, CsvOptions, Resample};
+use crate::format::Radix;
+use crate::fst::{write_fst, FstFile};
+use crate::parquet::{write_parquet, ParquetOptions};
+use crate::write::{write_waveform, WriteOptions};
+use crate::{
+    sigrok, GhwFile, Hierarchy, Lxt2File, MemoryWaveform, Scope, Signal, VcdFile, Waveform,
+};
+
+/// Options for [`convert`].
+#[derive(Debug, Clone, Default)]
+pub struct ConvertOptions {
+    /// Only keep the scope at this dot-separated path and everything below
+    /// it. Its parent scopes are kept, without their other contents.
+    pub scope: Option<String>,
+    /// Only keep the scopes and variables at these dot-separated paths,
+    /// with their parent scopes, together with [`scope`](Self::scope).
+    pub paths: Vec<String>,
+    /// Only keep changes in this time range. The value of every signal at
+    /// the start of the window is kept as a change at the start.
+    pub window: Option<Range<u64>>,
+    /// Output options for VCD, also used for FST.
+    pub vcd: WriteOptions,
+    /// Radix of logic values in CSV outputs, which hold the raw values if
+    /// `None`.
+    pub radix: Option<Radix>,
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+fn unsupported(what: &str, path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("unsupported {} format: {}", what, path.display()),
+    )
+}
+
+/// Open a waveform file, picking the reader by extension.
+pub fn open(path: &Path) -> io::Result<Box<dyn Waveform + Send>> {
+    match extension(path).as_str() {
+        "vcd" => Ok(Box::new(VcdFile::open(path)?)),
+        "fst" => Ok(Box::new(FstFile::open(path)?)),
+        "ghw" => Ok(Box::new(GhwFile::open(path)?)),
+        "lxt2" => Ok(Box::new(Lxt2File::open(path)?)),
+        "sr" => Ok(Box::new(sigrok::import_file(path)?)),
+        "csv" => {
+            let r = BufReader::new(File::open(path)?);
+            Ok(Box::new(csv::import_csv(r, &CsvOptions::default())?))
+        }
+        "tsv" => {
+            let options = CsvOptions {
+                delimiter: b'\t',
+                ..Default::default()
+            };
+            let r = BufReader::new(File::open(path)?);
+            Ok(Box::new(csv::import_csv(r, &options)?))
+        }
+        _ => Err(unsupported("input", path)),
+    }
+}
+
+/// Copy the scope or variable at `path` of `from` into `to`, creating its
+/// parent scopes. Returns whether `path` exists. `to` shares the names of
+/// `from`, so the copies keep their ids.
+fn graft(to: &mut Hierarchy, from: &Hierarchy, path: &str) -> bool {
+    let parts: Vec<&str> = path.split('.').collect();
+    let scope = from.find_scope(&parts);
+    let var = from.find_var(&parts);
+    if scope.is_none() && var.is_none() {
+        return false;
+    }
+    let parents = &parts[..parts.len() - 1];
+    let (mut scopes, mut vars) = (&mut to.scopes, &mut to.vars);
+    for depth in 1..=parents.len() {
+        let parent = from.find_scope(&parts[..depth]).expect("parents exist");
+        let i = match scopes.iter().position(|s| s.name == parent.name) {
+            Some(i) => i,
+            None => {
+                scopes.push(Scope::new(parent.kind, parent.name));
+                scopes.len() - 1
+            }
+        };
+        let scope = &mut scopes[i];
+        (scopes, vars) = (&mut scope.scopes, &mut scope.vars);
+    }
+    match scope {
+        Some(scope) => match scopes.iter_mut().find(|s| s.name == scope.name) {
+            Some(existing) => *existing = scope.clone(),
+            None => scopes.push(scope.clone()),
+        },
+        None => {
+            let var = var.expect("checked above");
+            if !vars.iter().any(|v| v.name == var.name) {
+                vars.push(var.clone());
+            }
+        }
+    }
+    true
+}
+
+/// The changes of `signal` within `window`, starting with its value at the
+/// start of the window.
+fn clip(signal: &Signal, window: &Range<u64>) -> Signal {
+    let mut out = Signal::new();
+    if let Some(v) = signal.value_at(window.start) {
+        out.push(window.start, v);
+    }
+    let t = signal.times();
+    let from = t.partition_point(|&t| t <= window.start);
+    let to = t.partition_point(|&t| t < window.end);
+    for (time, value) in signal.iter().take(to).skip(from) {
+        out.push(time, value);
+    }
+    out
+}
+
+/// Apply the scope and window filters of `options`, loading the signals
+/// that remain into memory.
+pub fn filter<F>(wave: &mut F, options: &ConvertOptions) -> io::Result<MemoryWaveform>
+where
+    F: Waveform + ?Sized,
+{
+    let hierarchy = if options.scope.is_none() && options.paths.is_empty() {
+        wave.hierarchy().clone()
+    } else {
+        let mut selected = Hierarchy::with_names(Arc::clone(wave.hierarchy().names()));
+        for path in options.scope.iter().chain(&options.paths) {
+            if !graft(&mut selected, wave.hierarchy(), path) {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no scope or variable {}", path),
+                ));
+            }
+        }
+        selected
+    };
+    let ids = hierarchy.signal_ids();
+    let signals = wave.load_signals(&ids)?;
+    let mut out = MemoryWaveform::new(hierarchy, wave.timescale());
+    out.set_date(wave.date().map(str::to_string));
+    for (id, signal) in ids.into_iter().zip(signals) {
+        let signal = match &options.window {
+            Some(window) => clip(&signal, window),
+            None => signal,
+        };
+        out.insert_signal(id, signal);
+    }
+    Ok(out)
+}
+
+/// Write a waveform to `path`, picking the format by extension.
+pub fn save<F>(wave: &mut F, path: &Path, options: &WriteOptions) -> io::Result<()>
+where
+    F: Waveform + ?Sized,
+{
+    match extension(path).as_str() {
+        "vcd" => write_waveform(File::create(path)?, wave, options),
+        "fst" => write_fst(BufWriter::new(File::create(path)?), wave, options).map(drop),
+        ext @ ("csv" | "parquet") => {
+            let paths = var_paths(wave.hierarchy());
+            let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+            if ext == "parquet" {
+                let batch = to_arrow(wave, &paths, 0..u64::MAX)?;
+                let mut out = BufWriter::new(File::create(path)?);
+                write_parquet(&mut out, &batch, &ParquetOptions::default())?;
+                return out.flush();
+            }
+            csv::export_csv(
+                File::create(path)?,
+                wave,
+                &paths,
+                0..u64::MAX,
+                Resample::Changes,
+            )
+        }
+        _ => Err(unsupported("output", path)),
+    }
+}
+
+fn var_paths(hierarchy: &Hierarchy) -> Vec<String> {
+    hierarchy.var_paths().into_iter().map(|(p, _)| p).collect()
+}
+
+/// [`save`] with the output options of a conversion.
+fn save_converted<F>(wave: &mut F, path: &Path, options: &ConvertOptions) -> io::Result<()>
+where
+    F: Waveform + ?Sized,
+{
+    match options.radix {
+        Some(radix) if extension(path) == "csv" => {
+            let paths = var_paths(wave.hierarchy());
+            let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+            csv::export_csv_as(
+                File::create(path)?,
+                wave,
+                &paths,
+                0..u64::MAX,
+                Resample::Changes,
+                radix,
+            )
+        }
+        _ => save(wave, path, &options.vcd),
+    }
+}
+
+/// Convert the waveform at `input` into the format of `output`.
+pub fn convert(input: &Path, output: &Path, options: &ConvertOptions) -> io::Result<()> {
+    // Fail on the output format before reading a possibly huge input.
+    if !matches!(
+        extension(output).as_str(),
+        "vcd" | "fst" | "csv" | "parquet"
+    ) {
+        return Err(unsupported("output", output));
+    }
+    let mut wave = open(input)?;
+    if options.scope.is_none() && options.paths.is_empty() && options.window.is_none() {
+        return save_converted(&mut *wave, output, options);
+    }
+    let mut filtered = filter(&mut *wave, options)?;
+    save_converted(&mut filtered, output, options)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SignalLoader;
+
+    const INPUT: &[u8] = b"$timescale 1 ns $end
+$scope module top $end
+$var wire 1 ! clk $end
+$scope module cpu $end
+$var wire 8 \" pc $end
+$upscope $end
+$upscope $end
+$enddefinitions $end
+#0
+0!
+b0 \"
+#10
+1!
+b1 \"
+#20
+0!
+b10 \"
+#30
+b11 \"
+";
+
+    #[test]
+    fn scope_and_window() {
+        let mut vcd = VcdFile::from_bytes(INPUT.to_vec()).unwrap();
+        let options = ConvertOptions {
+            scope: Some("top.cpu".to_string()),
+            window: Some(15..30),
+            ..Default::default()
+        };
+        let mut out = filter(&mut vcd, &options).unwrap();
+        let h = out.hierarchy();
+        assert!(h.lookup("top.clk").is_none());
+        let pc = h.lookup("top.cpu.pc").unwrap().signal;
+        assert_eq!(h.var_count(), 1);
+        let pc = &out.load_signals(&[pc]).unwrap()[0];
+        assert_eq!(pc.times(), &[15, 20]);
+        assert_eq!(pc.value(0), b"1");
+
+        assert!(filter(
+            &mut vcd,
+            &ConvertOptions {
+                scope: Some("top.gpu".to_string()),
+                ..Default::default()
+            }
+        )
+        .is_err());
+
+        let options = ConvertOptions {
+            scope: Some("top.cpu".to_string()),
+            paths: vec!["top.clk".to_string(), "top.cpu.pc".to_string()],
+            ..Default::default()
+        };
+        let out = filter(&mut vcd, &options).unwrap();
+        assert_eq!(out.hierarchy(), vcd.hierarchy());
+        let options = ConvertOptions {
+            paths: vec!["top.clk".to_string(), "top.cpu.sp".to_string()],
+            ..Default::default()
+        };
+        assert!(filter(&mut vcd, &options).is_err());
+    }
+
+    #[test]
+    fn files() {
+        let dir = std::env::temp_dir().join(format!("wave_parse_convert_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("in.vcd");
+        std::fs::write(&input, INPUT).unwrap();
+
+        let csv = dir.join("out.csv");
+        convert(&input, &csv, &Default::default()).unwrap();
+        let text = std::fs::read_to_string(&csv).unwrap();
+        assert!(text.starts_with("time,top.clk,top.cpu.pc\n0,0,0\n10,1,1\n"));
+
+        let vcd = dir.join("out.vcd");
+        convert(&input, &vcd, &Default::default()).unwrap();
+        let mut copy = open(&vcd).unwrap();
+        assert_eq!(copy.hierarchy().var_count(), 2);
+        assert_eq!(
+            copy.load_signals(&[crate::SignalId(1)]).unwrap()[0].len(),
+            4
+        );
+
+        let parquet = dir.join("out.parquet");
+        convert(&input, &parquet, &Default::default()).unwrap();
+        assert!(std::fs::read(&parquet).unwrap().ends_with(b"PAR1"));
+
+        let fst = dir.join("out.fst");
+        let window = ConvertOptions {
+            window: Some(5..25),
+            ..Default::default()
+        };
+        convert(&input, &fst, &window).unwrap();
+        let back = dir.join("back.vcd");
+        convert(&fst, &back, &Default::default()).unwrap();
+        let mut copy = open(&back).unwrap();
+        let pc = copy.hierarchy().lookup("top.cpu.pc").unwrap().signal;
+        let pc = &copy.load_signals(&[pc]).unwrap()[0];
+        assert_eq!(pc.times(), [5, 10, 20]);
+        assert_eq!(pc.value(2), b"00000010");
+
+        let err = convert(&input, &dir.join("out.ghw"), &Default::default()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+
+        let ghw = dir.join("in.ghw");
+        std::fs::write(&ghw, crate::ghw::test::sample()).unwrap();
+        convert(&ghw, &fst, &Default::default()).unwrap();
+        let mut copy = open(&fst).unwrap();
+        let data = copy.hierarchy().lookup("top.data").unwrap().signal;
+        let data = &copy.load_signals(&[data]).unwrap()[0];
+        assert_eq!(data.times(), [0, 1_000_000]);
+        assert_eq!(data.value(1), b"0100");
+
+        let lxt2 = dir.join("in.lxt2");
+        std::fs::write(&lxt2, crate::lxt2::test::sample()).unwrap();
+        convert(&lxt2, &vcd, &Default::default()).unwrap();
+        let mut copy = open(&vcd).unwrap();
+        let count = copy.hierarchy().lookup("top.count").unwrap().signal;
+        let count = &copy.load_signals(&[count]).unwrap()[0];
+        assert_eq!(count.times(), [0, 10, 20, 40, 50]);
+        assert_eq!(count.value(4), b"0101");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fst_round_trip_keeps_the_header() {
+        let dir = std::env::temp_dir().join(format!("wave_parse_header_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("in.vcd");
+        std::fs::write(
+            &input,
+            b"$date Mon Oct 12 2026 $end $timescale 1 ns $end
+$scope module top $end $var wire 1 ! clk $end $var string 8 \" msg $end $upscope $end
+$enddefinitions $end #0 0! shello \" #5 1!",
+        )
+        .unwrap();
+        let (fst, back) = (dir.join("out.fst"), dir.join("back.vcd"));
+        convert(&input, &fst, &Default::default()).unwrap();
+        convert(&fst, &back, &Default::default()).unwrap();
+        let (input, back) = (
+            VcdFile::open(&input).unwrap(),
+            VcdFile::open(&back).unwrap(),
+        );
+        assert_eq!(back.header(), input.header());
+        assert_eq!(back.header().date.as_deref(), Some("Mon Oct 12 2026"));
+        assert_eq!(back.hierarchy().lookup("top.msg").unwrap().width, 8);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
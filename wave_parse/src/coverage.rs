@@ -0,0 +1,218 @@
+//! Toggle coverage.
+//!
+//! A bit is covered once it has been seen rising (`0` to `1`) and falling
+//! (`1` to `0`). Only direct transitions count: a bit going from `0`
+//! through `x` to `1` has not toggled. Real and string variables are
+//! ignored.
+
+use std::fmt::{self, Display};
+use std::io::{self, Write};
+
+use crate::{Logic, ReferenceIndex, Signal, Var, VarKind, Waveform};
+
+/// Toggle counts of the bits of one variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VarToggles {
+    pub path: String,
+    /// Declared index of each bit, least significant first.
+    pub indices: Vec<i32>,
+    /// Number of `0` to `1` transitions per bit, least significant first.
+    pub rises: Vec<u64>,
+    /// Number of `1` to `0` transitions per bit, least significant first.
+    pub falls: Vec<u64>,
+}
+
+impl VarToggles {
+    /// Whether bit `i`, counted from the least significant bit, toggled
+    /// both ways.
+    pub fn is_covered(&self, i: usize) -> bool {
+        self.rises[i] > 0 && self.falls[i] > 0
+    }
+
+    /// Number of covered bits.
+    pub fn covered(&self) -> usize {
+        (0..self.rises.len())
+            .filter(|&i| self.is_covered(i))
+            .count()
+    }
+
+    pub fn width(&self) -> usize {
+        self.rises.len()
+    }
+}
+
+/// Toggle coverage of a set of variables.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoverageReport {
+    pub vars: Vec<VarToggles>,
+}
+
+impl CoverageReport {
+    /// Number of covered bits and total number of bits.
+    pub fn totals(&self) -> (usize, usize) {
+        self.vars
+            .iter()
+            .fold((0, 0), |(c, n), v| (c + v.covered(), n + v.width()))
+    }
+
+    /// Write one CSV line per bit: path, declared bit index, rise count,
+    /// fall count and whether the bit is covered.
+    pub fn write_csv<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "path,bit,rises,falls,covered")?;
+        for v in &self.vars {
+            for i in (0..v.width()).rev() {
+                writeln!(
+                    out,
+                    "{},{},{},{},{}",
+                    v.path,
+                    v.indices[i],
+                    v.rises[i],
+                    v.falls[i],
+                    v.is_covered(i) as u8
+                )?;
+            }
+        }
+        out.flush()
+    }
+}
+
+impl Display for CoverageReport {
+    /// A summary line followed by the variables with uncovered bits.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (covered, total) = self.totals();
+        let percent = if total == 0 {
+            100.0
+        } else {
+            100.0 * covered as f64 / total as f64
+        };
+        write!(
+            f,
+            "toggle coverage: {} of {} bits ({:.1}%)",
+            covered, total, percent
+        )?;
+        for v in &self.vars {
+            let missing: Vec<String> = (0..v.width())
+                .rev()
+                .filter(|&i| !v.is_covered(i))
+                .map(|i| v.indices[i].to_string())
+                .collect();
+            if !missing.is_empty() {
+                write!(f, "\n{}: bits {} not covered", v.path, missing.join(" "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The declared index of the bit at position `pos` from the least
+/// significant bit.
+fn declared_index(var: &Var, pos: u32) -> i32 {
+    match var.index {
+        Some(ReferenceIndex::Range(msb, lsb)) if msb < lsb => lsb - pos as i32,
+        Some(ReferenceIndex::Range(_, lsb)) => lsb + pos as i32,
+        Some(ReferenceIndex::BitSelect(i)) => i + pos as i32,
+        None => pos as i32,
+    }
+}
+
+/// Count the toggles of each bit of a `width`-bit signal.
+pub fn count_toggles(signal: &Signal, width: u32) -> (Vec<u64>, Vec<u64>) {
+    let width = width as usize;
+    let mut rises = vec![0; width];
+    let mut falls = vec![0; width];
+    let mut prev: Vec<u8> = vec![b'x'; width];
+    for (_, value) in signal {
+        let fill = Logic::vcd_fill(value).to_char();
+        for (i, p) in prev.iter_mut().enumerate() {
+            let c = match value.len().checked_sub(i + 1) {
+                Some(pos) => value[pos],
+                None => fill,
+            };
+            match (*p, c) {
+                (b'0', b'1') => rises[i] += 1,
+                (b'1', b'0') => falls[i] += 1,
+                _ => {}
+            }
+            *p = c;
+        }
+    }
+    (rises, falls)
+}
+
+/// Toggle coverage of the variables below the scopes at the given
+/// dot-separated paths, or of all variables if `scopes` is empty.
+pub fn toggle_coverage<F>(wave: &mut F, scopes: &[&str]) -> io::Result<CoverageReport>
+where
+    F: Waveform + ?Sized,
+{
+    let selected = |path: &str| {
+        scopes.is_empty()
+            || scopes.iter().any(|s| {
+                path.strip_prefix(s)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+            })
+    };
+    let vars: Vec<(String, Var)> = wave
+        .hierarchy()
+        .var_paths()
+        .into_iter()
+        .filter(|(path, var)| selected(path) && !var.kind.is_real() && var.kind != VarKind::String)
+        .map(|(path, var)| (path, var.clone()))
+        .collect();
+    let ids: Vec<_> = vars.iter().map(|(_, v)| v.signal).collect();
+    let signals = wave.load_signals(&ids)?;
+    let vars = vars
+        .into_iter()
+        .zip(&signals)
+        .map(|((path, var), signal)| {
+            let (rises, falls) = count_toggles(signal, var.width);
+            VarToggles {
+                path,
+                indices: (0..var.width).map(|p| declared_index(&var, p)).collect(),
+                rises,
+                falls,
+            }
+        })
+        .collect();
+    Ok(CoverageReport { vars })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::VcdFile;
+
+    #[test]
+    fn toggles() {
+        let mut s = Signal::new();
+        for v in ["0", "1", "0", "x", "1", "10"] {
+            s.push(0, v.as_bytes());
+        }
+        // bit 0: 0 1 0 x 1 0, bit 1: 0 0 0 x 0 1
+        assert_eq!(count_toggles(&s, 2), (vec![1, 1], vec![2, 0]));
+
+        let mut vcd = VcdFile::from_bytes(
+            b"$scope module top $end $var wire 1 ! clk $end
+$scope module cpu $end $var wire 2 \" st [3:2] $end $var real 64 # r $end $upscope $end
+$upscope $end $enddefinitions $end
+#0 0! b0 \" r0 # #1 1! b11 \" #2 0! b10 \""
+                .to_vec(),
+        )
+        .unwrap();
+        let all = toggle_coverage(&mut vcd, &[]).unwrap();
+        assert_eq!(all.totals(), (2, 3));
+        let cpu = toggle_coverage(&mut vcd, &["top.cpu"]).unwrap();
+        assert_eq!(cpu.vars.len(), 1);
+        assert_eq!(cpu.vars[0].indices, [2, 3]);
+        assert_eq!(
+            cpu.to_string(),
+            "toggle coverage: 1 of 2 bits (50.0%)\ntop.cpu.st: bits 3 not covered"
+        );
+        let mut csv = Vec::new();
+        cpu.write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "path,bit,rises,falls,covered\ntop.cpu.st,3,1,0,0\ntop.cpu.st,2,1,1,1\n"
+        );
+    }
+}
@@ -0,0 +1,484 @@
+//! Time series in CSV and TSV files.
+//!
+//! [`import_csv`] reads a table into a waveform: one column holds the
+//! time, every other column becomes a variable. The
+//! header names each column and may give its type after a colon:
+//!
+//! * `name:real` (the default): floating point values,
+//! * `name:N`: an `N`-bit vector, written as binary (`b0101`), hex
+//!   (`0x5`) or decimal,
+//! * `name:bit`: shorthand for `name:1`,
+//! * `name:string`: text.
+//!
+//! Empty cells mean "unchanged", so sparse exports load as expected, and
+//! consecutive equal values are only stored once.
+//!
+//! [`export_csv`] goes the other way, writing selected signals of any
+//! [`Waveform`] as a time column followed by one column per signal, with
+//! their raw values or, with [`export_csv_as`], in a [`Radix`].
+
+use std::borrow::Cow;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::ops::Range;
+
+use crate::format::{format_value, Radix};
+use crate::{
+    Hierarchy, InvalidData, MemoryWaveform, Scope, ScopeKind, Signal, SignalId, TimeUnit,
+    Timescale, Var, VarKind, Waveform,
+};
+
+/// Options for [`import_csv`].
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    /// Field separator, `,` for CSV and `\t` for TSV.
+    pub delimiter: u8,
+    /// Index of the time column.
+    pub time_column: usize,
+    /// Unit of the numbers in the time column.
+    pub time_unit: Timescale,
+    /// Timescale of the resulting waveform; times are rounded to it.
+    pub timescale: Timescale,
+    /// Put all variables into a module scope of this name instead of the
+    /// top level.
+    pub scope: Option<String>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> CsvOptions {
+        CsvOptions {
+            delimiter: b',',
+            time_column: 0,
+            time_unit: Timescale::new(1, TimeUnit::S),
+            timescale: Timescale::new(1, TimeUnit::NS),
+            scope: None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum ColumnType {
+    Real,
+    Bits(u32),
+    String,
+}
+
+impl ColumnType {
+    fn parse(s: &str) -> Result<ColumnType, InvalidData> {
+        match s {
+            "real" => Ok(ColumnType::Real),
+            "string" => Ok(ColumnType::String),
+            "bit" => Ok(ColumnType::Bits(1)),
+            _ => match s.parse() {
+                Ok(width) if width > 0 => Ok(ColumnType::Bits(width)),
+                _ => Err(InvalidData("invalid CSV column type")),
+            },
+        }
+    }
+
+    fn kind(self) -> VarKind {
+        match self {
+            ColumnType::Real => VarKind::Real,
+            ColumnType::Bits(_) => VarKind::Wire,
+            ColumnType::String => VarKind::String,
+        }
+    }
+
+    fn width(self) -> u32 {
+        match self {
+            ColumnType::Real => 64,
+            ColumnType::Bits(w) => w,
+            ColumnType::String => 1,
+        }
+    }
+
+    /// Convert a cell into the stored value encoding.
+    fn value(self, cell: &str, out: &mut Vec<u8>) -> Result<(), InvalidData> {
+        out.clear();
+        match self {
+            ColumnType::Real => {
+                let v: f64 = cell
+                    .parse()
+                    .map_err(|_| InvalidData("invalid real value in CSV"))?;
+                out.extend_from_slice(v.to_string().as_bytes());
+            }
+            ColumnType::String => out.extend_from_slice(cell.as_bytes()),
+            ColumnType::Bits(width) => {
+                if let Some(bits) = cell.strip_prefix(['b', 'B']) {
+                    if !bits.bytes().all(|b| b"01xXzZ".contains(&b)) {
+                        return Err(InvalidData("invalid binary value in CSV"));
+                    }
+                    out.extend_from_slice(bits.as_bytes());
+                    return Ok(());
+                }
+                if width == 1 && matches!(cell, "x" | "X" | "z" | "Z") {
+                    out.extend_from_slice(cell.as_bytes());
+                    return Ok(());
+                }
+                let n = match cell.strip_prefix("0x").or_else(|| cell.strip_prefix("0X")) {
+                    Some(hex) => u128::from_str_radix(hex, 16),
+                    None => cell.parse(),
+                }
+                .map_err(|_| InvalidData("invalid integer value in CSV"))?;
+                if width < 128 && n >> width != 0 {
+                    return Err(InvalidData("CSV value does not fit column width"));
+                }
+                let digits = (128 - n.leading_zeros()).max(1) as usize;
+                out.resize(width as usize - digits.min(width as usize), b'0');
+                out.extend(format!("{:b}", n).bytes());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Split one record, honouring double quotes with `""` escapes.
+fn split_record(line: &str, delimiter: u8) -> Vec<String> {
+    let delimiter = delimiter as char;
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Import a CSV or TSV time series.
+///
+/// Rows must be in time order.
+pub fn import_csv<R: BufRead>(r: R, options: &CsvOptions) -> io::Result<MemoryWaveform> {
+    let mut lines = r.lines();
+    let header = lines
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "empty CSV file"))??;
+    let header = split_record(&header, options.delimiter);
+    if options.time_column >= header.len() {
+        return Err(InvalidData("CSV time column out of range").into());
+    }
+
+    let mut hierarchy = Hierarchy::default();
+    let mut columns = Vec::new();
+    let mut vars = Vec::new();
+    for (i, cell) in header.iter().enumerate() {
+        if i == options.time_column {
+            continue;
+        }
+        let (name, ty) = match cell.rsplit_once(':') {
+            Some((name, ty)) => (name.trim(), ColumnType::parse(ty.trim())?),
+            None => (cell.trim(), ColumnType::Real),
+        };
+        let signal = SignalId(vars.len() as u64);
+        vars.push(Var {
+            kind: ty.kind(),
+            width: ty.width(),
+            signal,
+            name: hierarchy.intern(name),
+            index: None,
+        });
+        columns.push((i, ty));
+    }
+
+    let unit = options.time_unit.seconds() / options.timescale.seconds();
+    let mut signals: Vec<Signal> = columns.iter().map(|_| Signal::new()).collect();
+    let mut value = Vec::new();
+    let mut last_time = 0;
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = split_record(&line, options.delimiter);
+        let time: f64 = record
+            .get(options.time_column)
+            .and_then(|t| t.trim().parse().ok())
+            .ok_or(InvalidData("invalid time in CSV"))?;
+        let time = (time * unit).round();
+        if time < 0.0 || (time as u64) < last_time {
+            return Err(InvalidData("CSV rows are not in time order").into());
+        }
+        last_time = time as u64;
+        for (signal, &(col, ty)) in signals.iter_mut().zip(&columns) {
+            let cell = record.get(col).map_or("", |c| c.trim());
+            if cell.is_empty() {
+                continue;
+            }
+            ty.value(cell, &mut value)?;
+            if signal.is_empty() || signal.value(signal.len() - 1) != value {
+                signal.push(last_time, &value);
+            }
+        }
+    }
+
+    match &options.scope {
+        Some(name) => {
+            let mut scope = Scope::new(ScopeKind::Module, hierarchy.intern(name));
+            scope.vars = vars;
+            hierarchy.scopes.push(scope);
+        }
+        None => hierarchy.vars = vars,
+    }
+    let mut wave = MemoryWaveform::new(hierarchy, Some(options.timescale));
+    for (i, signal) in signals.into_iter().enumerate() {
+        wave.insert_signal(SignalId(i as u64), signal);
+    }
+    Ok(wave)
+}
+
+/// Which times become rows of an export.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Resample {
+    /// Every time any exported signal changes, plus the start of the range
+    /// if some signal already has a value there.
+    Changes,
+    /// The start of the range and then every given number of ticks.
+    Every(u64),
+}
+
+fn write_cell<W: Write>(out: &mut W, cell: &[u8]) -> io::Result<()> {
+    if cell
+        .iter()
+        .any(|b| matches!(b, b',' | b'"' | b'\n' | b'\r'))
+    {
+        out.write_all(b"\"")?;
+        for (i, part) in cell.split(|&b| b == b'"').enumerate() {
+            if i > 0 {
+                out.write_all(b"\"\"")?;
+            }
+            out.write_all(part)?;
+        }
+        out.write_all(b"\"")
+    } else {
+        out.write_all(cell)
+    }
+}
+
+/// Write named signals as CSV, one row per time selected by `policy`
+/// within `range`.
+///
+/// Cells before a signal's first change are left empty.
+pub fn write_csv<W: Write>(
+    out: W,
+    columns: &[(&str, &Signal)],
+    range: Range<u64>,
+    policy: Resample,
+) -> io::Result<()> {
+    write_rows(out, columns, range, policy, |_, value| Cow::Borrowed(value))
+}
+
+/// [`write_csv`] with the cells of column `i` holding `cell(i, value)`.
+fn write_rows<W, F>(
+    out: W,
+    columns: &[(&str, &Signal)],
+    range: Range<u64>,
+    policy: Resample,
+    cell: F,
+) -> io::Result<()>
+where
+    W: Write,
+    F: for<'v> Fn(usize, &'v [u8]) -> Cow<'v, [u8]>,
+{
+    let mut out = BufWriter::new(out);
+    out.write_all(b"time")?;
+    for (name, _) in columns {
+        out.write_all(b",")?;
+        write_cell(&mut out, name.as_bytes())?;
+    }
+    out.write_all(b"\n")?;
+
+    let mut times = Vec::new();
+    match policy {
+        Resample::Changes => {
+            let started = columns
+                .iter()
+                .any(|(_, s)| s.value_at(range.start).is_some());
+            if range.start < range.end && started {
+                times.push(range.start);
+            }
+            for (_, signal) in columns {
+                let t = signal.times();
+                let from = t.partition_point(|&t| t < range.start);
+                let to = t.partition_point(|&t| t < range.end);
+                times.extend_from_slice(&t[from..to]);
+            }
+            times.sort_unstable();
+            times.dedup();
+        }
+        Resample::Every(step) => {
+            if step == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "resample step must not be zero",
+                ));
+            }
+            times.extend(range.clone().step_by(step.try_into().unwrap_or(usize::MAX)));
+        }
+    }
+
+    for time in times {
+        write!(out, "{}", time)?;
+        for (i, (_, signal)) in columns.iter().enumerate() {
+            out.write_all(b",")?;
+            if let Some(value) = signal.value_at(time) {
+                write_cell(&mut out, &cell(i, value))?;
+            }
+        }
+        out.write_all(b"\n")?;
+    }
+    out.flush()
+}
+
+/// Export the variables at the dot-separated `paths` of a waveform as CSV.
+pub fn export_csv<W, F>(
+    out: W,
+    wave: &mut F,
+    paths: &[&str],
+    range: Range<u64>,
+    policy: Resample,
+) -> io::Result<()>
+where
+    W: Write,
+    F: Waveform + ?Sized,
+{
+    let (_, signals) = load_columns(wave, paths)?;
+    let columns: Vec<(&str, &Signal)> = paths.iter().copied().zip(&signals).collect();
+    write_csv(out, &columns, range, policy)
+}
+
+/// [`export_csv`] with logic values written in `radix`, see
+/// [`format`](crate::format).
+pub fn export_csv_as<W, F>(
+    out: W,
+    wave: &mut F,
+    paths: &[&str],
+    range: Range<u64>,
+    policy: Resample,
+    radix: Radix,
+) -> io::Result<()>
+where
+    W: Write,
+    F: Waveform + ?Sized,
+{
+    let (vars, signals) = load_columns(wave, paths)?;
+    let columns: Vec<(&str, &Signal)> = paths.iter().copied().zip(&signals).collect();
+    write_rows(out, &columns, range, policy, |i, value| {
+        Cow::Owned(format_value(&vars[i], value, radix).into_bytes())
+    })
+}
+
+/// The variables at `paths` and their signals.
+fn load_columns<F>(wave: &mut F, paths: &[&str]) -> io::Result<(Vec<Var>, Vec<Signal>)>
+where
+    F: Waveform + ?Sized,
+{
+    let mut vars = Vec::with_capacity(paths.len());
+    for path in paths {
+        let var = wave.hierarchy().lookup(path).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("no variable {}", path))
+        })?;
+        vars.push(var.clone());
+    }
+    let ids: Vec<SignalId> = vars.iter().map(|v| v.signal).collect();
+    let signals = wave.load_signals(&ids)?;
+    Ok((vars, signals))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{SignalLoader, Waveform};
+
+    #[test]
+    fn typed_columns() {
+        let csv = "\"time, ns\",voltage,state:4,flag:bit,label:string\n\
+                   0,1.50,3,0,idle\n\
+                   10,1.5,0xa,,busy\n\
+                   20,2,b1x01,1,\"say \"\"hi\"\"\"\n";
+        let options = CsvOptions {
+            time_unit: Timescale::new(1, TimeUnit::NS),
+            timescale: Timescale::new(1, TimeUnit::PS),
+            scope: Some("bench".to_string()),
+            ..Default::default()
+        };
+        let mut wave = import_csv(csv.as_bytes(), &options).unwrap();
+        let h = wave.hierarchy();
+        assert_eq!(h.lookup("bench.state").unwrap().width, 4);
+        assert_eq!(h.lookup("bench.voltage").unwrap().kind, VarKind::Real);
+        let sigs = wave
+            .load_signals(&[SignalId(0), SignalId(1), SignalId(2), SignalId(3)])
+            .unwrap();
+        assert_eq!(sigs[0].times(), &[0, 20_000]);
+        assert_eq!(sigs[0].value(0), b"1.5");
+        assert_eq!(sigs[1].value(0), b"0011");
+        assert_eq!(sigs[1].value(1), b"1010");
+        assert_eq!(sigs[1].value(2), b"1x01");
+        assert_eq!(sigs[2].times(), &[0, 20_000]);
+        assert_eq!(sigs[3].value(2), b"say \"hi\"");
+    }
+
+    #[test]
+    fn tsv_and_errors() {
+        let options = CsvOptions {
+            delimiter: b'\t',
+            time_column: 1,
+            ..Default::default()
+        };
+        let mut wave = import_csv("a:8\tt\n255\t0.5\n".as_bytes(), &options).unwrap();
+        let sig = &wave.load_signals(&[SignalId(0)]).unwrap()[0];
+        assert_eq!(sig.times(), &[500_000_000]);
+        assert_eq!(sig.value(0), b"11111111");
+
+        assert!(import_csv("t,a:2\n0,4\n".as_bytes(), &Default::default()).is_err());
+        assert!(import_csv("t,a\n1,0\n0,1\n".as_bytes(), &Default::default()).is_err());
+        assert!(import_csv("t,a:wide\n".as_bytes(), &Default::default()).is_err());
+    }
+
+    #[test]
+    fn export_selected_signals() {
+        let csv = "t,a:bit,b:string\n0,0,\n5,1,\"x,y\"\n8,0,z\n";
+        let options = CsvOptions {
+            time_unit: Timescale::new(1, TimeUnit::NS),
+            ..Default::default()
+        };
+        let mut wave = import_csv(csv.as_bytes(), &options).unwrap();
+
+        let mut out = Vec::new();
+        export_csv(&mut out, &mut wave, &["b", "a"], 3..9, Resample::Changes).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "time,b,a\n3,,0\n5,\"x,y\",1\n8,z,0\n"
+        );
+
+        let mut out = Vec::new();
+        export_csv(&mut out, &mut wave, &["a"], 0..10, Resample::Every(4)).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "time,a\n0,0\n4,0\n8,0\n");
+
+        assert!(export_csv(Vec::new(), &mut wave, &["c"], 0..1, Resample::Changes).is_err());
+
+        let csv = "t,v:8,r\n0,0x41,1.5\n2,b1x,\n";
+        let mut wave = import_csv(csv.as_bytes(), &options).unwrap();
+        let mut out = Vec::new();
+        export_csv_as(
+            &mut out,
+            &mut wave,
+            &["v", "r"],
+            0..5,
+            Resample::Changes,
+            Radix::Hex,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "time,v,r\n0,41,1.5\n2,0X,1.5\n"
+        );
+    }
+}
@@ -0,0 +1,203 @@
+//! A small encoder for DEFLATE streams (RFC 1951), the counterpart of
+//! [`inflate`](crate::inflate) for formats that must be written
+//! compressed.
+//!
+//! Matches are found greedily through hash chains over a 32 KiB window
+//! and coded with the fixed Huffman codes, so no code tables are built or
+//! stored. Waveform sections are repetitive enough that this lands within
+//! a small factor of zlib's default level.
+
+use crate::inflate::{adler32, crc32, DIST_BASE, DIST_EXTRA, LENGTH_BASE, LENGTH_EXTRA};
+
+const WINDOW: usize = 1 << 15;
+const HASH_BITS: u32 = 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// Candidates tried per position before settling for the best so far.
+const MAX_CHAIN: usize = 64;
+const NONE: u32 = u32::MAX;
+
+struct BitWriter {
+    out: Vec<u8>,
+    buf: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn bits(&mut self, v: u32, n: u32) {
+        self.buf |= (v as u64) << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.out.push(self.buf as u8);
+            self.buf >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Write a Huffman code, which goes most significant bit first.
+    fn code(&mut self, code: u32, n: u32) {
+        self.bits(code.reverse_bits() >> (32 - n), n);
+    }
+
+    fn literal(&mut self, sym: u32) {
+        match sym {
+            0..=143 => self.code(0x30 + sym, 8),
+            144..=255 => self.code(0x190 + sym - 144, 9),
+            256..=279 => self.code(sym - 256, 7),
+            _ => self.code(0xc0 + sym - 280, 8),
+        }
+    }
+
+    fn copy(&mut self, len: usize, dist: usize) {
+        let i = LENGTH_BASE.partition_point(|&b| b as usize <= len) - 1;
+        self.literal(257 + i as u32);
+        self.bits(
+            (len - LENGTH_BASE[i] as usize) as u32,
+            LENGTH_EXTRA[i] as u32,
+        );
+        let d = DIST_BASE.partition_point(|&b| b as usize <= dist) - 1;
+        self.code(d as u32, 5);
+        self.bits((dist - DIST_BASE[d] as usize) as u32, DIST_EXTRA[d] as u32);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.buf as u8);
+        }
+        self.out
+    }
+}
+
+fn hash(data: &[u8], pos: usize) -> usize {
+    let v = u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], 0]);
+    (v.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Hash chains over the positions seen so far.
+struct Matcher {
+    head: Vec<u32>,
+    prev: Vec<u32>,
+}
+
+impl Matcher {
+    fn insert(&mut self, data: &[u8], pos: usize) {
+        if pos + MIN_MATCH <= data.len() {
+            let h = hash(data, pos);
+            self.prev[pos % WINDOW] = self.head[h];
+            self.head[h] = pos as u32;
+        }
+    }
+
+    /// The longest earlier match for `pos` as length and distance.
+    fn longest(&self, data: &[u8], pos: usize) -> (usize, usize) {
+        let (mut best_len, mut best_dist) = (0, 0);
+        if pos + MIN_MATCH > data.len() {
+            return (0, 0);
+        }
+        let max = (data.len() - pos).min(MAX_MATCH);
+        let mut candidate = self.head[hash(data, pos)];
+        for _ in 0..MAX_CHAIN {
+            if candidate == NONE || pos - candidate as usize >= WINDOW {
+                break;
+            }
+            let from = candidate as usize;
+            let len = data[from..from + max]
+                .iter()
+                .zip(&data[pos..pos + max])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if len > best_len {
+                (best_len, best_dist) = (len, pos - from);
+                if len == max {
+                    break;
+                }
+            }
+            let next = self.prev[from % WINDOW];
+            if next == NONE || next >= candidate {
+                break;
+            }
+            candidate = next;
+        }
+        (best_len, best_dist)
+    }
+}
+
+/// Encode `data` as a single fixed-code DEFLATE block.
+pub(crate) fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut w = BitWriter {
+        out: Vec::with_capacity(data.len() / 2 + 16),
+        buf: 0,
+        count: 0,
+    };
+    w.bits(1, 1);
+    w.bits(1, 2);
+    let mut matcher = Matcher {
+        head: vec![NONE; 1 << HASH_BITS],
+        prev: vec![NONE; WINDOW],
+    };
+    let mut pos = 0;
+    while pos < data.len() {
+        let (len, dist) = matcher.longest(data, pos);
+        if len >= MIN_MATCH {
+            w.copy(len, dist);
+            for p in pos..pos + len {
+                matcher.insert(data, p);
+            }
+            pos += len;
+        } else {
+            w.literal(data[pos] as u32);
+            matcher.insert(data, pos);
+            pos += 1;
+        }
+    }
+    w.literal(256);
+    w.finish()
+}
+
+/// Encode `data` as a zlib stream (RFC 1950).
+pub(crate) fn zlib_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    out.extend(deflate(data));
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+/// Encode `data` as a gzip stream (RFC 1952) without a file name or
+/// modification time.
+pub(crate) fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+    out.extend(deflate(data));
+    out.extend(crc32(data).to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::inflate::{gunzip, inflate, zlib_decode};
+
+    #[test]
+    fn round_trips() {
+        let vcd: Vec<u8> = (0..2000)
+            .flat_map(|i| format!("#{}\nb{:b} !\n", i * 10, i % 37).into_bytes())
+            .collect();
+        let noise: Vec<u8> = (0u32..5000)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+        for data in [
+            &b""[..],
+            b"a",
+            b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            &vcd,
+            &noise,
+        ] {
+            let packed = deflate(data);
+            assert_eq!(inflate(&packed, 0).unwrap(), data);
+            assert_eq!(zlib_decode(&zlib_encode(data), 0).unwrap(), data);
+            assert_eq!(gunzip(&gzip(data), 0).unwrap(), data);
+        }
+        // zlib -6 compresses this to 22% of its size.
+        assert!(deflate(&vcd).len() < vcd.len() / 3);
+    }
+}
@@ -0,0 +1,186 @@
+//! Virtual signals computed from expressions.
+//!
+//! A [`DerivedWaveform`] wraps another waveform and adds variables whose
+//! values are [`expr`](crate::expr) expressions over its signals, like
+//! GTKWave's virtual signals. They appear in the hierarchy like any other
+//! variable and are computed when loaded, so searches, exports and
+//! decoders use them without knowing. Wrap the waveform in a
+//! [`SignalStore`](crate::SignalStore) to keep computed signals around.
+
+use std::collections::HashMap;
+use std::io;
+
+use crate::expr::{Evaluator, Expression};
+use crate::{
+    Blackouts, Hierarchy, NameId, Scope, ScopeKind, Signal, SignalId, SignalLoader, Timescale, Var,
+    VarKind, Waveform,
+};
+
+/// A waveform extended with derived signals.
+#[derive(Debug)]
+pub struct DerivedWaveform<W> {
+    inner: W,
+    hierarchy: Hierarchy,
+    derived: HashMap<SignalId, String>,
+    next_id: u64,
+}
+
+impl<W: Waveform> DerivedWaveform<W> {
+    pub fn new(inner: W) -> DerivedWaveform<W> {
+        let hierarchy = inner.hierarchy().clone();
+        let next_id = hierarchy
+            .signal_ids()
+            .iter()
+            .map(|id| id.0 + 1)
+            .max()
+            .unwrap_or(0);
+        DerivedWaveform {
+            inner,
+            hierarchy,
+            derived: HashMap::new(),
+            next_id,
+        }
+    }
+
+    /// Declare the variable at the dot-separated `path`, creating missing
+    /// scopes as modules, with the value of `expr`. The expression may
+    /// refer to variables defined before.
+    pub fn define(&mut self, path: &str, expr: &str) -> io::Result<SignalId> {
+        if self.hierarchy.lookup(path).is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", path),
+            ));
+        }
+        let parsed = Expression::parse(expr)?;
+        let mut widths = Vec::new();
+        for name in parsed.names() {
+            let var = self.hierarchy.lookup(name).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, format!("no signal {}", name))
+            })?;
+            widths.push(var.width);
+        }
+        let input_widths: Vec<u32> = parsed.inputs().iter().map(|&(n, _)| widths[n]).collect();
+
+        let id = SignalId(self.next_id);
+        self.next_id += 1;
+        let mut parts: Vec<NameId> = path.split('.').map(|p| self.hierarchy.intern(p)).collect();
+        let name = parts.pop().expect("split yields at least one part");
+        let var = Var {
+            kind: VarKind::Wire,
+            width: parsed.width(&input_widths).max(1),
+            signal: id,
+            name,
+            index: None,
+        };
+        let mut scopes = &mut self.hierarchy.scopes;
+        let mut vars = &mut self.hierarchy.vars;
+        for part in parts {
+            let i = match scopes.iter().position(|s| s.name == part) {
+                Some(i) => i,
+                None => {
+                    scopes.push(Scope::new(ScopeKind::Module, part));
+                    scopes.len() - 1
+                }
+            };
+            let scope = &mut scopes[i];
+            scopes = &mut scope.scopes;
+            vars = &mut scope.vars;
+        }
+        vars.push(var);
+        self.derived.insert(id, expr.to_string());
+        Ok(id)
+    }
+
+    /// Whether a signal is derived rather than read from the inner
+    /// waveform.
+    pub fn is_derived(&self, id: SignalId) -> bool {
+        self.derived.contains_key(&id)
+    }
+
+    pub fn inner(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Waveform> SignalLoader for DerivedWaveform<W> {
+    fn load_signals(&mut self, ids: &[SignalId]) -> io::Result<Vec<Signal>> {
+        let real: Vec<SignalId> = ids
+            .iter()
+            .copied()
+            .filter(|id| !self.derived.contains_key(id))
+            .collect();
+        let mut loaded = self.inner.load_signals(&real)?.into_iter();
+        let mut out = Vec::with_capacity(ids.len());
+        for id in ids {
+            match self.derived.get(id).cloned() {
+                Some(expr) => out.push(Evaluator::load(self, &expr)?.to_signal()),
+                None => out.push(loaded.next().expect("one signal per id")),
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl<W: Waveform> Waveform for DerivedWaveform<W> {
+    fn hierarchy(&self) -> &Hierarchy {
+        &self.hierarchy
+    }
+
+    fn timescale(&self) -> Option<Timescale> {
+        self.inner.timescale()
+    }
+
+    fn date(&self) -> Option<&str> {
+        self.inner.date()
+    }
+
+    fn blackouts(&self) -> io::Result<Blackouts> {
+        self.inner.blackouts()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::search::find_first;
+    use crate::VcdFile;
+
+    #[test]
+    fn virtual_signals() {
+        let vcd = VcdFile::from_bytes(
+            b"$scope module top $end $var wire 1 ! valid $end $var wire 1 \" ready $end
+$var wire 4 # a $end $upscope $end $enddefinitions $end
+#0 0! 0\" b1 #
+#10 1! b10 #
+#20 1\" b11 #
+#30 0! b11 #"
+                .to_vec(),
+        )
+        .unwrap();
+        let mut wave = DerivedWaveform::new(vcd);
+        let fire = wave.define("top.fire", "top.valid && top.ready").unwrap();
+        let sum = wave.define("calc.sum", "top.a + delay(top.a, 10)").unwrap();
+        let twice = wave.define("calc.twice", "calc.sum << 1").unwrap();
+        assert!(wave.define("top.fire", "1").is_err());
+        assert!(wave.define("x", "top.nope").is_err());
+        assert!(wave.is_derived(fire) && !wave.is_derived(SignalId(0)));
+        assert_eq!(wave.hierarchy().lookup("calc.sum").unwrap().width, 4);
+
+        let s = wave.load_signals(&[fire, SignalId(2), sum, twice]).unwrap();
+        assert_eq!(s[0].times(), &[0, 20, 30]);
+        assert_eq!(s[0].value(1), b"1");
+        assert_eq!(s[1].len(), 4);
+        // a + a delayed: x until 10, then 2+1, 3+2, 3+3.
+        assert_eq!(s[2].times(), &[0, 10, 20, 30]);
+        assert_eq!(s[2].value(0), b"xxxx");
+        assert_eq!(s[2].value(2), b"0101");
+        assert_eq!(s[3].value(3), b"1100");
+
+        assert_eq!(find_first(&mut wave, "top.fire", 0).unwrap(), Some(20));
+    }
+}
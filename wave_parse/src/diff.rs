@@ -0,0 +1,313 @@
+//! Signal-by-signal comparison of two waveforms.
+//!
+//! Variables of the first waveform are matched to the second by path,
+//! optionally renamed with [`DiffOptions::rename`]. Two signals agree at
+//! a time if their values are equal there after VCD's left extension, so
+//! `b1` and `b0001` agree; real values are compared numerically.
+//!
+//! Both waveforms must use the same timescale; [`DiffOptions::offset`]
+//! shifts the second one in time, e.g. by an offset found with
+//! [`align`](crate::align).
+
+use std::fmt::{self, Display};
+use std::io;
+use std::ops::Range;
+
+use crate::{Logic, Signal, Waveform};
+
+/// Options for [`diff`].
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    /// Added to every time of the second waveform. Changes moved before
+    /// time zero happen at zero.
+    pub offset: i64,
+    /// Path renames from the first to the second waveform. A rename
+    /// `(from, to)` applies to the path `from` and to everything below it,
+    /// e.g. `("tb.dut", "top")` matches `tb.dut.pc` with `top.pc`. The
+    /// first matching rename is used.
+    pub rename: Vec<(String, String)>,
+}
+
+/// The differences of one pair of signals.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignalDiff {
+    /// Path in the first waveform.
+    pub path: String,
+    /// Path in the second waveform.
+    pub other: String,
+    /// Number of changes in each waveform.
+    pub changes: (usize, usize),
+    /// The time ranges in which the values differ. A difference lasting
+    /// to the end of both signals ends at `u64::MAX`.
+    pub mismatches: Vec<Range<u64>>,
+}
+
+impl SignalDiff {
+    /// The first time the signals differ.
+    pub fn first_divergence(&self) -> Option<u64> {
+        self.mismatches.first().map(|r| r.start)
+    }
+}
+
+/// The result of [`diff`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiffReport {
+    /// Signals that differ, in declaration order of the first waveform.
+    pub signals: Vec<SignalDiff>,
+    /// Number of signal pairs compared.
+    pub compared: usize,
+    /// Paths of the first waveform missing in the second.
+    pub only_in_first: Vec<String>,
+    /// Paths of the second waveform not matched by any of the first.
+    pub only_in_second: Vec<String>,
+}
+
+impl DiffReport {
+    /// Whether the waveforms agree everywhere.
+    pub fn is_empty(&self) -> bool {
+        self.signals.is_empty() && self.only_in_first.is_empty() && self.only_in_second.is_empty()
+    }
+
+    /// The earliest divergence of any signal.
+    pub fn first_divergence(&self) -> Option<u64> {
+        self.signals
+            .iter()
+            .filter_map(SignalDiff::first_divergence)
+            .min()
+    }
+}
+
+impl Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for s in &self.signals {
+            write!(f, "{}", s.path)?;
+            if s.other != s.path {
+                write!(f, " ({})", s.other)?;
+            }
+            write!(
+                f,
+                ": {} vs {} changes, first divergence at {}, {} mismatching regions:",
+                s.changes.0,
+                s.changes.1,
+                s.first_divergence().unwrap_or_default(),
+                s.mismatches.len()
+            )?;
+            for r in s.mismatches.iter().take(4) {
+                match r.end {
+                    u64::MAX => write!(f, " {}..", r.start)?,
+                    end => write!(f, " {}..{}", r.start, end)?,
+                }
+            }
+            if s.mismatches.len() > 4 {
+                f.write_str(" ...")?;
+            }
+            writeln!(f)?;
+        }
+        for path in &self.only_in_first {
+            writeln!(f, "only in first: {}", path)?;
+        }
+        for path in &self.only_in_second {
+            writeln!(f, "only in second: {}", path)?;
+        }
+        write!(
+            f,
+            "{} of {} signals differ",
+            self.signals.len(),
+            self.compared
+        )
+    }
+}
+
+pub(crate) fn same_value(a: &[u8], b: &[u8], real: bool) -> bool {
+    if a == b {
+        return true;
+    }
+    if real {
+        let parse = |v: &[u8]| std::str::from_utf8(v).ok()?.parse::<f64>().ok();
+        return matches!((parse(a), parse(b)), (Some(a), Some(b)) if a == b);
+    }
+    let width = a.len().max(b.len());
+    (0..width).all(|i| match (Logic::vcd_bit(a, i), Logic::vcd_bit(b, i)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    })
+}
+
+/// The time ranges in which `a` and `b`, shifted by `offset`, differ.
+/// A signal without a value yet only agrees with another one without.
+pub fn compare(a: &Signal, b: &Signal, offset: i64, real: bool) -> Vec<Range<u64>> {
+    let shift = |t: u64| t.saturating_add_signed(offset);
+    let (mut i, mut j) = (0, 0);
+    let (mut va, mut vb): (Option<&[u8]>, Option<&[u8]>) = (None, None);
+    let mut start = None;
+    let mut out = Vec::new();
+    loop {
+        let ta = (i < a.len()).then(|| a.time(i));
+        let tb = (j < b.len()).then(|| shift(b.time(j)));
+        let t = match (ta, tb) {
+            (Some(ta), Some(tb)) => ta.min(tb),
+            (Some(t), None) | (None, Some(t)) => t,
+            (None, None) => break,
+        };
+        while i < a.len() && a.time(i) == t {
+            va = Some(a.value(i));
+            i += 1;
+        }
+        while j < b.len() && shift(b.time(j)) == t {
+            vb = Some(b.value(j));
+            j += 1;
+        }
+        let same = match (va, vb) {
+            (Some(x), Some(y)) => same_value(x, y, real),
+            (x, y) => x.is_none() && y.is_none(),
+        };
+        match (same, start) {
+            (false, None) => start = Some(t),
+            (true, Some(s)) => {
+                out.push(s..t);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        out.push(s..u64::MAX);
+    }
+    out
+}
+
+fn rename(path: &str, renames: &[(String, String)]) -> String {
+    for (from, to) in renames {
+        if let Some(rest) = path.strip_prefix(from.as_str()) {
+            if rest.is_empty() || rest.starts_with('.') {
+                return format!("{}{}", to, rest);
+            }
+        }
+    }
+    path.to_string()
+}
+
+/// Compare every variable of `a` with its counterpart in `b`.
+pub fn diff<A, B>(a: &mut A, b: &mut B, options: &DiffOptions) -> io::Result<DiffReport>
+where
+    A: Waveform + ?Sized,
+    B: Waveform + ?Sized,
+{
+    if let (Some(x), Some(y)) = (a.timescale(), b.timescale()) {
+        if x != y {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("timescales differ: {} and {}", x, y),
+            ));
+        }
+    }
+    let mut report = DiffReport::default();
+    let mut pairs = Vec::new();
+    let mut matched = std::collections::HashSet::new();
+    for (path, var) in a.hierarchy().var_paths() {
+        let other = rename(&path, &options.rename);
+        match b.hierarchy().lookup(&other) {
+            Some(o) => {
+                matched.insert(other.clone());
+                pairs.push((path, other, var.signal, o.signal, var.kind.is_real()));
+            }
+            None => report.only_in_first.push(path),
+        }
+    }
+    report.only_in_second = b
+        .hierarchy()
+        .var_paths()
+        .into_iter()
+        .map(|(p, _)| p)
+        .filter(|p| !matched.contains(p))
+        .collect();
+
+    let ids: Vec<_> = pairs.iter().map(|p| p.2).collect();
+    let first = a.load_signals(&ids)?;
+    let ids: Vec<_> = pairs.iter().map(|p| p.3).collect();
+    let second = b.load_signals(&ids)?;
+    report.compared = pairs.len();
+    for ((path, other, _, _, real), (x, y)) in pairs.into_iter().zip(first.iter().zip(&second)) {
+        let mismatches = compare(x, y, options.offset, real);
+        if !mismatches.is_empty() {
+            report.signals.push(SignalDiff {
+                path,
+                other,
+                changes: (x.len(), y.len()),
+                mismatches,
+            });
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::VcdFile;
+
+    fn signal(changes: &[(u64, &str)]) -> Signal {
+        let mut s = Signal::new();
+        for &(t, v) in changes {
+            s.push(t, v.as_bytes());
+        }
+        s
+    }
+
+    #[test]
+    fn regions() {
+        let a = signal(&[(0, "0"), (10, "1"), (20, "0"), (30, "1")]);
+        let b = signal(&[(0, "0000"), (10, "1"), (25, "0"), (30, "1"), (40, "x")]);
+        assert_eq!(compare(&a, &b, 0, false), [20..25, 40..u64::MAX]);
+        assert_eq!(compare(&a, &a, 0, false), []);
+        let late = signal(&[(5, "0"), (15, "1"), (25, "0"), (35, "1")]);
+        assert_eq!(compare(&a, &late, -5, false), []);
+        assert_eq!(compare(&a, &late, 0, false), [0..5, 10..15, 20..25, 30..35]);
+        assert_eq!(
+            compare(&signal(&[(0, "1.0")]), &signal(&[(0, "1")]), 0, true),
+            []
+        );
+        assert_eq!(
+            compare(&signal(&[(0, "z")]), &signal(&[(0, "zz")]), 0, false),
+            []
+        );
+        // Bits compare as logic values, text that is none as it is.
+        assert!(same_value(b"X1", b"xx1", false));
+        assert!(same_value(b"H0", b"h0", false));
+        assert!(!same_value(b"x", b"0x", false));
+        assert!(!same_value(b"Idle", b"idle", false));
+    }
+
+    #[test]
+    fn waveforms() {
+        let mut a = VcdFile::from_bytes(
+            b"$scope module tb $end $scope module dut $end
+$var wire 1 ! clk $end $var wire 4 \" pc $end $var wire 1 # old $end
+$upscope $end $upscope $end $enddefinitions $end
+#0 0! b0 \" 0# #10 1! b1 \" #20 0! b10 \""
+                .to_vec(),
+        )
+        .unwrap();
+        let mut b = VcdFile::from_bytes(
+            b"$scope module top $end
+$var wire 1 ! clk $end $var wire 4 \" pc $end $var wire 1 # new $end
+$upscope $end $enddefinitions $end
+#0 0! b0 \" 0# #10 1! b1 \" #20 0! b11 \""
+                .to_vec(),
+        )
+        .unwrap();
+        let options = DiffOptions {
+            rename: vec![("tb.dut".to_string(), "top".to_string())],
+            ..Default::default()
+        };
+        let report = diff(&mut a, &mut b, &options).unwrap();
+        assert_eq!(report.compared, 2);
+        assert_eq!(report.first_divergence(), Some(20));
+        assert_eq!(report.signals.len(), 1);
+        assert_eq!(report.signals[0].other, "top.pc");
+        assert_eq!(report.signals[0].changes, (3, 3));
+        assert_eq!(report.only_in_first, ["tb.dut.old"]);
+        assert_eq!(report.only_in_second, ["top.new"]);
+        assert!(report.to_string().ends_with("1 of 2 signals differ"));
+    }
+}
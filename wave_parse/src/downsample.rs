@@ -0,0 +1,213 @@
+//! Min/max summaries of signals for drawing at any zoom level.
+//!
+//! A viewer drawing a signal into a fixed number of pixels needs, per
+//! pixel, the value on entry and exit, the range of values passed
+//! through, and whether anything changed. A [`MinMaxIndex`] is built once
+//! per signal in linear time; after that each pixel is answered in
+//! logarithmic time, independent of the number of changes it covers.
+
+use std::ops::Range;
+
+use crate::Signal;
+
+/// Summary of a set of values.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Node {
+    min: f64,
+    max: f64,
+    unknown: bool,
+}
+
+const EMPTY: Node = Node {
+    min: f64::INFINITY,
+    max: f64::NEG_INFINITY,
+    unknown: false,
+};
+
+impl Node {
+    fn merge(self, other: Node) -> Node {
+        Node {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+            unknown: self.unknown || other.unknown,
+        }
+    }
+}
+
+/// One time bucket of a signal, e.g. one pixel column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bucket<'s> {
+    pub range: Range<u64>,
+    /// The value at the start of the bucket.
+    pub first: Option<&'s [u8]>,
+    /// The value at the end of the bucket.
+    pub last: Option<&'s [u8]>,
+    /// The smallest and largest numeric value held during the bucket.
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Whether a value without numeric interpretation (`x`, `z`, ...) was
+    /// held during the bucket.
+    pub unknown: bool,
+    /// Number of changes in the bucket.
+    pub changes: usize,
+}
+
+/// Range minimum/maximum queries over the values of a signal.
+#[derive(Debug, Clone)]
+pub struct MinMaxIndex {
+    len: usize,
+    /// Segment tree: node `i` summarizes nodes `2i` and `2i + 1`, the
+    /// changes themselves are the leaves at `len..2 * len`.
+    nodes: Vec<Node>,
+}
+
+impl MinMaxIndex {
+    /// Index `signal`, interpreting each value with `value`, which returns
+    /// `None` for values without a numeric meaning. See
+    /// [`Encoding::decode`](crate::Encoding::decode).
+    pub fn new<F>(signal: &Signal, value: F) -> MinMaxIndex
+    where
+        F: Fn(&[u8]) -> Option<f64>,
+    {
+        let len = signal.len();
+        let mut nodes = vec![EMPTY; 2 * len];
+        for (i, (_, v)) in signal.iter().enumerate() {
+            nodes[len + i] = match value(v) {
+                Some(n) => Node {
+                    min: n,
+                    max: n,
+                    unknown: false,
+                },
+                None => Node {
+                    unknown: true,
+                    ..EMPTY
+                },
+            };
+        }
+        for i in (1..len).rev() {
+            nodes[i] = nodes[2 * i].merge(nodes[2 * i + 1]);
+        }
+        MinMaxIndex { len, nodes }
+    }
+
+    /// Summary of the changes with indices in `range`.
+    fn query(&self, range: Range<usize>) -> Node {
+        let (mut lo, mut hi) = (range.start + self.len, range.end + self.len);
+        let mut out = EMPTY;
+        while lo < hi {
+            if lo & 1 == 1 {
+                out = out.merge(self.nodes[lo]);
+                lo += 1;
+            }
+            if hi & 1 == 1 {
+                hi -= 1;
+                out = out.merge(self.nodes[hi]);
+            }
+            lo /= 2;
+            hi /= 2;
+        }
+        out
+    }
+
+    /// Split `range` into `buckets` equal parts and summarize `signal`,
+    /// which must be the signal this index was built from, in each.
+    pub fn downsample<'s>(
+        &self,
+        signal: &'s Signal,
+        range: Range<u64>,
+        buckets: usize,
+    ) -> Vec<Bucket<'s>> {
+        assert_eq!(signal.len(), self.len, "index built from another signal");
+        let span = range.end.saturating_sub(range.start) as u128;
+        let edge = |i: usize| range.start + (span * i as u128 / buckets.max(1) as u128) as u64;
+        let times = signal.times();
+        (0..buckets)
+            .map(|i| {
+                let (start, end) = (edge(i), edge(i + 1));
+                // The changes in the bucket, plus the one holding on entry.
+                // A bucket narrower than a time unit is empty and only shows
+                // the value held.
+                let from = times.partition_point(|&t| t < start);
+                let to = times.partition_point(|&t| t < end);
+                let held = signal.index_at(start);
+                let upto = held.map_or(to, |h| to.max(h + 1));
+                let node = self.query(held.unwrap_or(from)..upto);
+                Bucket {
+                    range: start..end,
+                    first: signal.value_at(start),
+                    last: upto.checked_sub(1).map(|i| signal.value(i)),
+                    min: (node.min <= node.max).then_some(node.min),
+                    max: (node.min <= node.max).then_some(node.max),
+                    unknown: node.unknown,
+                    changes: to - from,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Encoding;
+
+    #[test]
+    fn buckets() {
+        let mut s = Signal::new();
+        for t in 0..1000u64 {
+            s.push(t * 10, format!("{:b}", t % 7).as_bytes());
+        }
+        let index = MinMaxIndex::new(&s, |v| Encoding::Unsigned.decode(v, 3));
+        let b = index.downsample(&s, 0..10_000, 4);
+        assert_eq!(b.len(), 4);
+        assert_eq!(b[0].range, 0..2500);
+        assert_eq!(b[0].changes, 250);
+        assert_eq!((b[0].min, b[0].max), (Some(0.0), Some(6.0)));
+        assert_eq!(b[0].first, Some(&b"0"[..]));
+        // 249 % 7 == 4
+        assert_eq!(b[0].last, Some(&b"100"[..]));
+
+        // Zoomed in beyond single changes: a bucket between two changes still
+        // shows the value held.
+        let b = index.downsample(&s, 12..18, 3);
+        assert_eq!(b[0].changes, 0);
+        assert_eq!(b[0].first, Some(&b"1"[..]));
+        assert_eq!((b[0].min, b[0].max), (Some(1.0), Some(1.0)));
+        assert_eq!(b[1].first, Some(&b"1"[..]));
+
+        let mut s = Signal::new();
+        s.push(5, b"x");
+        s.push(10, b"1");
+        let index = MinMaxIndex::new(&s, |v| Encoding::Unsigned.decode(v, 1));
+        let b = index.downsample(&s, 0..20, 4);
+        assert_eq!(b[0].first, None);
+        assert_eq!(b[0].min, None);
+        assert!(!b[0].unknown);
+        assert!(b[1].unknown);
+        assert_eq!((b[2].min, b[2].unknown), (Some(1.0), false));
+    }
+
+    #[test]
+    fn more_buckets_than_time_units() {
+        let mut s = Signal::new();
+        for (t, v) in [(0, b"0"), (1, b"1"), (2, b"0")] {
+            s.push(t, v);
+        }
+        let index = MinMaxIndex::new(&s, |v| Encoding::Unsigned.decode(v, 1));
+        let b = index.downsample(&s, 0..3, 8);
+        assert_eq!(b.iter().map(|b| b.changes).sum::<usize>(), 3);
+        for b in &b {
+            assert_eq!(b.changes as u64, b.range.end - b.range.start);
+            assert_eq!(b.last, b.first);
+            assert_eq!(b.min, b.max);
+        }
+        assert_eq!(b[0].range, 0..0);
+        assert_eq!((b[0].first, b[0].min), (Some(&b"0"[..]), Some(0.0)));
+        assert_eq!((b[3].first, b[3].min), (Some(&b"1"[..]), Some(1.0)));
+
+        // Empty buckets at the very end of time.
+        let b = index.downsample(&s, u64::MAX - 1..u64::MAX, 4);
+        assert_eq!(b.iter().map(|b| b.changes).sum::<usize>(), 0);
+        assert_eq!(b[3].last, Some(&b"0"[..]));
+    }
+}
@@ -0,0 +1,171 @@
+//! Rising and falling edges of single bits.
+//!
+//! [`edges`] walks the transitions of one bit of a signal, a 1-bit signal
+//! or a selected bit of a vector, between `0` and `1`. By default only
+//! direct transitions are edges: a bit going from `0` through `x` to `1`
+//! has no edge, as the time it rose is unknown. With
+//! [`EdgeOptions::through_unknown`] it rises when it reaches `1`.
+//! [`latencies`] pairs the edges of two signals, e.g. requests and their
+//! acknowledges.
+
+use std::ops::Range;
+
+use crate::{Logic, Signal, SignalIter};
+
+/// Which transitions are edges.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum Polarity {
+    #[default]
+    Rising,
+    Falling,
+    Both,
+}
+
+/// Options for [`edges`].
+#[derive(Debug, Clone, Default)]
+pub struct EdgeOptions {
+    pub polarity: Polarity,
+    /// Position of the bit, counted from the least significant bit.
+    pub bit: usize,
+    /// Whether unknown values between two levels are skipped, so `0 x 1`
+    /// rises when it reaches `1`.
+    pub through_unknown: bool,
+}
+
+/// One transition of a bit.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Edge {
+    pub time: u64,
+    /// Whether the bit went from `0` to `1`.
+    pub rising: bool,
+}
+
+/// The level of bit `pos` of a raw value: `Some(true)` for `1` and `h`,
+/// `Some(false)` for `0` and `l`, `None` for anything else. Values shorter
+/// than the bit are extended like VCD vectors.
+pub fn bit_level(value: &[u8], pos: usize) -> Option<bool> {
+    match Logic::vcd_bit(value, pos)? {
+        Logic::Zero | Logic::L => Some(false),
+        Logic::One | Logic::H => Some(true),
+        _ => None,
+    }
+}
+
+/// The edges of a bit of a signal; see [`edges`].
+pub struct Edges<'s> {
+    changes: SignalIter<'s>,
+    options: EdgeOptions,
+    /// The last level seen, `None` after an unknown value unless skipping
+    /// them.
+    level: Option<bool>,
+}
+
+impl Iterator for Edges<'_> {
+    type Item = Edge;
+
+    fn next(&mut self) -> Option<Edge> {
+        for (time, value) in self.changes.by_ref() {
+            let level = bit_level(value, self.options.bit);
+            let prev = self.level;
+            if level.is_some() || !self.options.through_unknown {
+                self.level = level;
+            }
+            let (Some(prev), Some(level)) = (prev, level) else {
+                continue;
+            };
+            let wanted = match self.options.polarity {
+                Polarity::Rising => !prev && level,
+                Polarity::Falling => prev && !level,
+                Polarity::Both => prev != level,
+            };
+            if wanted {
+                return Some(Edge {
+                    time,
+                    rising: level,
+                });
+            }
+        }
+        None
+    }
+}
+
+/// The edges of a bit of `signal` at times in `range`. The value in effect
+/// before the range counts as the level an edge at its start leaves.
+pub fn edges<'s>(signal: &'s Signal, options: &EdgeOptions, range: Range<u64>) -> Edges<'s> {
+    let mut level = None;
+    let mut before = signal.prev_change(range.start);
+    while let Some(i) = before {
+        level = bit_level(signal.value(i), options.bit);
+        if level.is_some() || !options.through_unknown {
+            break;
+        }
+        before = i.checked_sub(1);
+    }
+    Edges {
+        changes: signal.changes_in(range),
+        options: options.clone(),
+        level,
+    }
+}
+
+/// For each time in `from`, the delay to the first time in `to` at or
+/// after it, or `None` if there is none. Both must be sorted.
+pub fn latencies(from: &[u64], to: &[u64]) -> Vec<Option<u64>> {
+    from.iter()
+        .map(|&t| {
+            let i = to.partition_point(|&u| u < t);
+            to.get(i).map(|&u| u - t)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn signal(changes: &[(u64, &str)]) -> Signal {
+        let mut s = Signal::new();
+        for &(t, v) in changes {
+            s.push(t, v.as_bytes());
+        }
+        s
+    }
+
+    #[test]
+    fn edges_and_latencies() {
+        let s = signal(&[
+            (0, "0"),
+            (5, "1"),
+            (10, "0"),
+            (15, "x"),
+            (20, "1"),
+            (25, "0"),
+        ]);
+        let times = |options: &EdgeOptions, range: Range<u64>| -> Vec<u64> {
+            edges(&s, options, range).map(|e| e.time).collect()
+        };
+        let mut options = EdgeOptions::default();
+        assert_eq!(times(&options, 0..100), [5]);
+        options.through_unknown = true;
+        assert_eq!(times(&options, 0..100), [5, 20]);
+        // The level before the range is known, even behind an `x`.
+        assert_eq!(times(&options, 18..100), [20]);
+        options.polarity = Polarity::Both;
+        assert_eq!(times(&options, 5..25), [5, 10, 20]);
+        let rising: Vec<bool> = edges(&s, &options, 0..100).map(|e| e.rising).collect();
+        assert_eq!(rising, [true, false, true, false]);
+
+        let bus = signal(&[(0, "0"), (3, "10"), (7, "1")]);
+        let bit1 = EdgeOptions {
+            polarity: Polarity::Falling,
+            bit: 1,
+            ..EdgeOptions::default()
+        };
+        let falls: Vec<u64> = edges(&bus, &bit1, 0..10).map(|e| e.time).collect();
+        assert_eq!(falls, [7]);
+        assert_eq!(bit_level(b"z", 3), None);
+        assert_eq!(bit_level(b"h0", 1), Some(true));
+
+        assert_eq!(latencies(&[5, 20, 30], &[8, 20]), [Some(3), Some(0), None]);
+    }
+}
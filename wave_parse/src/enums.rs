@@ -0,0 +1,147 @@
+//! Symbolic names for the values of enumerated signals.
+//!
+//! State machines are dumped as plain bit vectors; an [`EnumMap`] turns
+//! their values back into state names. Maps are supplied by the user, per
+//! signal, in an [`EnumMaps`] table, or come from the enum tables of an
+//! FST file through [`FstFile::enum_maps`](crate::FstFile::enum_maps) and
+//! the enumeration types of a GHW file through
+//! [`GhwFile::enum_maps`](crate::GhwFile::enum_maps).
+
+use std::collections::HashMap;
+
+use crate::numeric::raw_bits;
+use crate::{InvalidData, Signal, SignalId};
+
+/// Names for the values of one enumerated type.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnumMap {
+    names: HashMap<u64, String>,
+}
+
+impl EnumMap {
+    pub fn new() -> EnumMap {
+        EnumMap::default()
+    }
+
+    /// Name `value`, replacing any previous name.
+    pub fn insert(&mut self, value: u64, name: &str) {
+        self.names.insert(value, name.to_string());
+    }
+
+    /// Parse `NAME=VALUE` pairs separated by commas or newlines, e.g.
+    /// `IDLE=0, BUSY=1, DONE=0b10`. Values are decimal, or hexadecimal or
+    /// binary with a `0x` or `0b` prefix.
+    pub fn parse(text: &str) -> Result<EnumMap, InvalidData> {
+        let mut map = EnumMap::new();
+        for pair in text.split([',', '\n']).map(str::trim) {
+            if pair.is_empty() {
+                continue;
+            }
+            let (name, value) = pair
+                .split_once('=')
+                .ok_or(InvalidData("expected NAME=VALUE"))?;
+            let value = value.trim();
+            let value = if let Some(hex) = value.strip_prefix("0x") {
+                u64::from_str_radix(hex, 16)
+            } else if let Some(bin) = value.strip_prefix("0b") {
+                u64::from_str_radix(bin, 2)
+            } else {
+                value.parse()
+            }
+            .map_err(|_| InvalidData("invalid enum value"))?;
+            map.insert(value, name.trim());
+        }
+        Ok(map)
+    }
+
+    /// The name of a value.
+    pub fn get(&self, value: u64) -> Option<&str> {
+        self.names.get(&value).map(String::as_str)
+    }
+
+    /// The name of a raw value of a `width`-bit vector. Values with
+    /// non-binary bits have no name.
+    pub fn name(&self, raw: &[u8], width: u32) -> Option<&str> {
+        self.get(raw_bits(raw, width)?)
+    }
+
+    /// The values and their names, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &str)> {
+        self.names.iter().map(|(&v, n)| (v, n.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+/// Enum maps by signal.
+#[derive(Debug, Clone, Default)]
+pub struct EnumMaps {
+    maps: HashMap<SignalId, EnumMap>,
+}
+
+impl EnumMaps {
+    pub fn new() -> EnumMaps {
+        EnumMaps::default()
+    }
+
+    /// Attach `map` to a signal, replacing any previous map.
+    pub fn insert(&mut self, id: SignalId, map: EnumMap) {
+        self.maps.insert(id, map);
+    }
+
+    pub fn get(&self, id: SignalId) -> Option<&EnumMap> {
+        self.maps.get(&id)
+    }
+
+    /// The name of a raw value of signal `id`, if it has a map naming it.
+    pub fn name(&self, id: SignalId, raw: &[u8], width: u32) -> Option<&str> {
+        self.get(id)?.name(raw, width)
+    }
+}
+
+impl Signal {
+    /// The changes of a `width`-bit vector as names from `map`.
+    pub fn enum_names<'a>(
+        &'a self,
+        map: &'a EnumMap,
+        width: u32,
+    ) -> impl Iterator<Item = (u64, Option<&'a str>)> + 'a {
+        self.iter().map(move |(t, v)| (t, map.name(v, width)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn names() {
+        let map = EnumMap::parse("IDLE=0, BUSY = 1\nDONE=0b10,ERR=0x3").unwrap();
+        assert_eq!(map.len(), 4);
+        let mut s = Signal::new();
+        s.push(0, b"x");
+        s.push(1, b"0");
+        s.push(2, b"1");
+        s.push(3, b"10");
+        s.push(4, b"11");
+        let names: Vec<_> = s.enum_names(&map, 2).map(|(_, n)| n).collect();
+        assert_eq!(
+            names,
+            [None, Some("IDLE"), Some("BUSY"), Some("DONE"), Some("ERR")]
+        );
+
+        let mut maps = EnumMaps::new();
+        maps.insert(SignalId(3), map);
+        assert_eq!(maps.name(SignalId(3), b"01", 2), Some("BUSY"));
+        assert_eq!(maps.name(SignalId(4), b"01", 2), None);
+
+        assert!(EnumMap::parse("IDLE").is_err());
+        assert!(EnumMap::parse("IDLE=0xg").is_err());
+    }
+}
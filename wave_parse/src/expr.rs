@@ -0,0 +1,622 @@
+//! Expressions over signal values.
+//!
+//! The syntax is a subset of Verilog's: signal paths, integer literals
+//! (`42`, `0x2a`, `0b101010`), the arithmetic operators `+ - * / %`, the
+//! shifts `<< >>`, the bitwise operators `~ & | ^`, the comparisons
+//! `== != < <= > >=` and the logical operators `! && ||`, with Verilog's
+//! precedence and parentheses for grouping. For example
+//! `top.valid && top.ready && top.addr == 0x80`. On top of that,
+//! `delay(top.req, 10)` is the value `top.req` had 10 ticks earlier.
+//!
+//! Values are unsigned integers of up to 64 bits. A signal whose value has
+//! `x`/`z` bits, or is wider than 64 bits, is unknown, and so is every
+//! result depending on it, except that `0 && x` is `0` and `1 || x` is `1`.
+//! Division by zero is unknown too. Results have the width of their widest
+//! operand and wrap around like Verilog's; comparisons and logical
+//! operators are 1 bit wide.
+
+use std::io;
+
+use crate::numeric::raw_bits;
+use crate::{InvalidData, Signal, Waveform};
+
+/// A value and its width in bits.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Word {
+    pub bits: u64,
+    pub width: u32,
+}
+
+impl Word {
+    pub fn new(bits: u64, width: u32) -> Word {
+        Word {
+            bits: bits & mask(width),
+            width,
+        }
+    }
+
+    fn bool(b: bool) -> Word {
+        Word::new(b as u64, 1)
+    }
+
+    pub fn is_true(&self) -> bool {
+        self.bits != 0
+    }
+}
+
+fn mask(width: u32) -> u64 {
+    if width >= 64 {
+        u64::MAX
+    } else {
+        (1 << width) - 1
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Unary {
+    Not,
+    Invert,
+    Neg,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Binary {
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+/// Binary operators by precedence level, lowest first.
+const LEVELS: &[&[(&str, Binary)]] = &[
+    &[("||", Binary::Or)],
+    &[("&&", Binary::And)],
+    &[("|", Binary::BitOr)],
+    &[("^", Binary::BitXor)],
+    &[("&", Binary::BitAnd)],
+    &[("==", Binary::Eq), ("!=", Binary::Ne)],
+    &[
+        ("<=", Binary::Le),
+        ("<", Binary::Lt),
+        (">=", Binary::Ge),
+        (">", Binary::Gt),
+    ],
+    &[("<<", Binary::Shl), (">>", Binary::Shr)],
+    &[("+", Binary::Add), ("-", Binary::Sub)],
+    &[("*", Binary::Mul), ("/", Binary::Div), ("%", Binary::Rem)],
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Const(Word),
+    /// Index into [`Expression::inputs`].
+    Input(usize),
+    Unary(Unary, Box<Node>),
+    Binary(Binary, Box<Node>, Box<Node>),
+}
+
+/// A parsed expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    root: Node,
+    names: Vec<String>,
+    inputs: Vec<(usize, u64)>,
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    names: Vec<String>,
+    inputs: Vec<(usize, u64)>,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_space(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consume `op` if it comes next and is not the start of a doubled
+    /// operator such as `&&` or `<<`.
+    fn eat(&mut self, op: &str) -> bool {
+        self.skip_space();
+        let rest = &self.text[self.pos..];
+        if !rest.starts_with(op) || matches!(op, "&" | "|" | "<" | ">") && rest[1..].starts_with(op)
+        {
+            return false;
+        }
+        self.pos += op.len();
+        true
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Node, InvalidData> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        'outer: loop {
+            for &(text, op) in LEVELS[level] {
+                if self.eat(text) {
+                    let rhs = self.binary(level + 1)?;
+                    lhs = Node::Binary(op, Box::new(lhs), Box::new(rhs));
+                    continue 'outer;
+                }
+            }
+            return Ok(lhs);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, InvalidData> {
+        if self.eat("!") {
+            return Ok(Node::Unary(Unary::Not, Box::new(self.unary()?)));
+        }
+        if self.eat("~") {
+            return Ok(Node::Unary(Unary::Invert, Box::new(self.unary()?)));
+        }
+        if self.eat("-") {
+            return Ok(Node::Unary(Unary::Neg, Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Node, InvalidData> {
+        if self.eat("(") {
+            let inner = self.binary(0)?;
+            if !self.eat(")") {
+                return Err(InvalidData("expected )"));
+            }
+            return Ok(inner);
+        }
+        let token = self.word();
+        match token.chars().next() {
+            None => Err(InvalidData("expected a signal or number")),
+            Some('0'..='9') => parse_literal(token).map(Node::Const),
+            Some(_) if token == "delay" && self.eat("(") => {
+                let name = self.word();
+                if name.is_empty() || !self.eat(",") {
+                    return Err(InvalidData("expected delay(signal, ticks)"));
+                }
+                let ticks = parse_literal(self.word())?.bits;
+                if !self.eat(")") {
+                    return Err(InvalidData("expected )"));
+                }
+                Ok(self.input(name, ticks))
+            }
+            Some(_) => Ok(self.input(token, 0)),
+        }
+    }
+
+    /// The next signal path or number.
+    fn word(&mut self) -> &'a str {
+        self.skip_space();
+        let text = self.text;
+        let rest = &text[self.pos..];
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$')))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn input(&mut self, name: &str, delay: u64) -> Node {
+        let name = match self.names.iter().position(|n| n == name) {
+            Some(i) => i,
+            None => {
+                self.names.push(name.to_string());
+                self.names.len() - 1
+            }
+        };
+        let input = match self.inputs.iter().position(|&i| i == (name, delay)) {
+            Some(i) => i,
+            None => {
+                self.inputs.push((name, delay));
+                self.inputs.len() - 1
+            }
+        };
+        Node::Input(input)
+    }
+}
+
+fn parse_literal(token: &str) -> Result<Word, InvalidData> {
+    let token = token.replace('_', "");
+    let (digits, radix) = if let Some(hex) = token.strip_prefix("0x") {
+        (hex, 16)
+    } else if let Some(bin) = token.strip_prefix("0b") {
+        (bin, 2)
+    } else {
+        (token.as_str(), 10)
+    };
+    let bits = u64::from_str_radix(digits, radix).map_err(|_| InvalidData("invalid number"))?;
+    Ok(Word::new(bits, (64 - bits.leading_zeros()).max(1)))
+}
+
+impl Expression {
+    pub fn parse(text: &str) -> Result<Expression, InvalidData> {
+        let mut p = Parser {
+            text,
+            pos: 0,
+            names: Vec::new(),
+            inputs: Vec::new(),
+        };
+        let root = p.binary(0)?;
+        p.skip_space();
+        if p.pos != text.len() {
+            return Err(InvalidData("unexpected text in expression"));
+        }
+        Ok(Expression {
+            root,
+            names: p.names,
+            inputs: p.inputs,
+        })
+    }
+
+    /// The signal paths the expression refers to, in order of first use.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// The values the expression depends on: an index into
+    /// [`names`](Self::names) and how many ticks earlier the value of that
+    /// signal is taken.
+    pub fn inputs(&self) -> &[(usize, u64)] {
+        &self.inputs
+    }
+
+    /// Evaluate with the values of the [`inputs`](Self::inputs), `None`
+    /// for unknown values.
+    pub fn eval(&self, values: &[Option<Word>]) -> Option<Word> {
+        eval(&self.root, values)
+    }
+
+    /// The width of the result for inputs of the given widths.
+    pub fn width(&self, widths: &[u32]) -> u32 {
+        width(&self.root, widths)
+    }
+}
+
+fn width(node: &Node, widths: &[u32]) -> u32 {
+    match node {
+        Node::Const(w) => w.width,
+        Node::Input(i) => widths[*i],
+        Node::Unary(Unary::Not, _) => 1,
+        Node::Unary(_, a) => width(a, widths),
+        Node::Binary(op, a, b) => match op {
+            Binary::Shl | Binary::Shr => width(a, widths),
+            Binary::BitOr
+            | Binary::BitXor
+            | Binary::BitAnd
+            | Binary::Add
+            | Binary::Sub
+            | Binary::Mul
+            | Binary::Div
+            | Binary::Rem => width(a, widths).max(width(b, widths)),
+            _ => 1,
+        },
+    }
+}
+
+fn eval(node: &Node, values: &[Option<Word>]) -> Option<Word> {
+    match node {
+        Node::Const(w) => Some(*w),
+        Node::Input(i) => values[*i],
+        Node::Unary(op, a) => {
+            let a = eval(a, values)?;
+            Some(match op {
+                Unary::Not => Word::bool(!a.is_true()),
+                Unary::Invert => Word::new(!a.bits, a.width),
+                Unary::Neg => Word::new(a.bits.wrapping_neg(), a.width),
+            })
+        }
+        Node::Binary(Binary::And, a, b) => match eval(a, values) {
+            Some(a) if !a.is_true() => Some(Word::bool(false)),
+            a => match (a, eval(b, values)) {
+                (_, Some(b)) if !b.is_true() => Some(Word::bool(false)),
+                (Some(_), Some(_)) => Some(Word::bool(true)),
+                _ => None,
+            },
+        },
+        Node::Binary(Binary::Or, a, b) => match eval(a, values) {
+            Some(a) if a.is_true() => Some(Word::bool(true)),
+            a => match (a, eval(b, values)) {
+                (_, Some(b)) if b.is_true() => Some(Word::bool(true)),
+                (Some(_), Some(_)) => Some(Word::bool(false)),
+                _ => None,
+            },
+        },
+        Node::Binary(op, a, b) => {
+            let (a, b) = (eval(a, values)?, eval(b, values)?);
+            let width = a.width.max(b.width);
+            Some(match op {
+                Binary::BitOr => Word::new(a.bits | b.bits, width),
+                Binary::BitXor => Word::new(a.bits ^ b.bits, width),
+                Binary::BitAnd => Word::new(a.bits & b.bits, width),
+                Binary::Eq => Word::bool(a.bits == b.bits),
+                Binary::Ne => Word::bool(a.bits != b.bits),
+                Binary::Lt => Word::bool(a.bits < b.bits),
+                Binary::Le => Word::bool(a.bits <= b.bits),
+                Binary::Gt => Word::bool(a.bits > b.bits),
+                Binary::Ge => Word::bool(a.bits >= b.bits),
+                Binary::Shl => Word::new(shift(a.bits, b.bits, u64::checked_shl), a.width),
+                Binary::Shr => Word::new(shift(a.bits, b.bits, u64::checked_shr), a.width),
+                Binary::Add => Word::new(a.bits.wrapping_add(b.bits), width),
+                Binary::Sub => Word::new(a.bits.wrapping_sub(b.bits), width),
+                Binary::Mul => Word::new(a.bits.wrapping_mul(b.bits), width),
+                Binary::Div => Word::new(a.bits.checked_div(b.bits)?, width),
+                Binary::Rem => Word::new(a.bits.checked_rem(b.bits)?, width),
+                Binary::And | Binary::Or => unreachable!("handled above"),
+            })
+        }
+    }
+}
+
+/// `bits` shifted by `by`, 0 once every bit is shifted out.
+fn shift(bits: u64, by: u64, op: fn(u64, u32) -> Option<u64>) -> u64 {
+    u32::try_from(by)
+        .ok()
+        .and_then(|by| op(bits, by))
+        .unwrap_or(0)
+}
+
+/// An expression bound to the signals of its inputs.
+#[derive(Debug, Clone)]
+pub struct Evaluator {
+    expr: Expression,
+    /// Signal and width by name; width 0 for signals without numeric
+    /// values.
+    signals: Vec<(Signal, u32)>,
+}
+
+impl Evaluator {
+    /// Bind `expr` to signals and their widths, given in the order of
+    /// [`Expression::names`].
+    pub fn new(expr: Expression, signals: Vec<(Signal, u32)>) -> Evaluator {
+        assert_eq!(expr.names.len(), signals.len(), "one signal per name");
+        Evaluator { expr, signals }
+    }
+
+    /// Parse `text` and load the signals it refers to from `wave`.
+    pub fn load<F>(wave: &mut F, text: &str) -> io::Result<Evaluator>
+    where
+        F: Waveform + ?Sized,
+    {
+        let expr = Expression::parse(text)?;
+        let mut ids = Vec::new();
+        let mut widths = Vec::new();
+        for name in expr.names() {
+            let var = wave.hierarchy().lookup(name).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, format!("no signal {}", name))
+            })?;
+            ids.push(var.signal);
+            widths.push(if var.kind.is_real() { 0 } else { var.width });
+        }
+        let signals = wave.load_signals(&ids)?.into_iter().zip(widths).collect();
+        Ok(Evaluator::new(expr, signals))
+    }
+
+    pub fn expression(&self) -> &Expression {
+        &self.expr
+    }
+
+    /// The width of the result.
+    pub fn width(&self) -> u32 {
+        let widths: Vec<u32> = self
+            .expr
+            .inputs
+            .iter()
+            .map(|&(name, _)| self.signals[name].1)
+            .collect();
+        self.expr.width(&widths)
+    }
+
+    fn word(&self, name: usize, value: &[u8]) -> Option<Word> {
+        let width = self.signals[name].1;
+        if width == 0 || width > 64 {
+            return None;
+        }
+        Some(Word::new(raw_bits(value, width)?, width))
+    }
+
+    /// The value of input `i` at `time`.
+    fn input_at(&self, i: usize, time: u64) -> Option<Word> {
+        let (name, delay) = self.expr.inputs[i];
+        let v = self.signals[name].0.value_at(time.checked_sub(delay)?)?;
+        self.word(name, v)
+    }
+
+    /// Whether any input has been assigned a value by `time`.
+    fn assigned_at(&self, time: u64) -> bool {
+        self.expr.inputs.iter().any(|&(name, delay)| {
+            time.checked_sub(delay)
+                .is_some_and(|t| self.signals[name].0.value_at(t).is_some())
+        })
+    }
+
+    /// The value of the expression at `time`.
+    pub fn eval_at(&self, time: u64) -> Option<Word> {
+        let values: Vec<_> = (0..self.expr.inputs.len())
+            .map(|i| self.input_at(i, time))
+            .collect();
+        self.expr.eval(&values)
+    }
+
+    /// The value at `from`, followed by the value at every later time an
+    /// input changes. Between those times the value cannot change.
+    pub fn sweep(&self, from: u64) -> Sweep<'_> {
+        let inputs = &self.expr.inputs;
+        let values = (0..inputs.len()).map(|i| self.input_at(i, from)).collect();
+        let next = inputs
+            .iter()
+            .map(|&(name, delay)| {
+                let times = self.signals[name].0.times();
+                times.partition_point(|&t| t.saturating_add(delay) <= from)
+            })
+            .collect();
+        Sweep {
+            eval: self,
+            values,
+            next,
+            start: Some(from),
+        }
+    }
+
+    /// The result as a signal of its own, with a change wherever the value
+    /// changes. Unknown values are all `x`.
+    pub fn to_signal(&self) -> Signal {
+        let width = self.width().max(1) as usize;
+        let mut out = Signal::new();
+        let mut last: Option<Option<Word>> = None;
+        let mut buf = Vec::with_capacity(width);
+        for (time, value) in self.sweep(0) {
+            if last == Some(value) {
+                continue;
+            }
+            // Nothing to report before any input has a value.
+            if last.is_none() && value.is_none() && !self.assigned_at(time) {
+                continue;
+            }
+            buf.clear();
+            match value {
+                Some(w) => buf.extend((0..width).rev().map(|i| {
+                    let bit = i < 64 && w.bits >> i & 1 == 1;
+                    b'0' + bit as u8
+                })),
+                None => buf.resize(width, b'x'),
+            }
+            out.push(time, &buf);
+            last = Some(value);
+        }
+        out
+    }
+}
+
+/// The iterator returned by [`Evaluator::sweep`].
+pub struct Sweep<'e> {
+    eval: &'e Evaluator,
+    values: Vec<Option<Word>>,
+    next: Vec<usize>,
+    start: Option<u64>,
+}
+
+impl Iterator for Sweep<'_> {
+    type Item = (u64, Option<Word>);
+
+    fn next(&mut self) -> Option<(u64, Option<Word>)> {
+        if let Some(from) = self.start.take() {
+            return Some((from, self.eval.expr.eval(&self.values)));
+        }
+        let inputs = &self.eval.expr.inputs;
+        let t = inputs
+            .iter()
+            .zip(&self.next)
+            .filter_map(|(&(name, delay), &n)| {
+                let t = *self.eval.signals[name].0.times().get(n)?;
+                Some(t.saturating_add(delay))
+            })
+            .min()?;
+        for (i, &(name, delay)) in inputs.iter().enumerate() {
+            let s = &self.eval.signals[name].0;
+            while self.next[i] < s.len() && s.time(self.next[i]).saturating_add(delay) == t {
+                self.values[i] = self.eval.word(name, s.value(self.next[i]));
+                self.next[i] += 1;
+            }
+        }
+        Some((t, self.eval.expr.eval(&self.values)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn eval(text: &str, values: &[Option<u64>]) -> Option<u64> {
+        let e = Expression::parse(text).unwrap();
+        let values: Vec<_> = values.iter().map(|v| v.map(|b| Word::new(b, 8))).collect();
+        e.eval(&values).map(|w| w.bits)
+    }
+
+    #[test]
+    fn parse_and_eval() {
+        let e = Expression::parse("top.valid && top.ready && top.addr == 0x80").unwrap();
+        assert_eq!(e.names(), ["top.valid", "top.ready", "top.addr"]);
+        assert_eq!(
+            eval("a && b && c == 0x80", &[Some(1), Some(1), Some(128)]),
+            Some(1)
+        );
+        assert_eq!(
+            eval("a && b && c == 0x80", &[Some(1), Some(0), None]),
+            Some(0)
+        );
+        assert_eq!(eval("a || b", &[None, Some(1)]), Some(1));
+        assert_eq!(eval("a || b", &[None, Some(0)]), None);
+        assert_eq!(eval("a & 0b1100 | 1", &[Some(0xff)]), Some(13));
+        assert_eq!(eval("~a", &[Some(0x0f)]), Some(0xf0));
+        assert_eq!(eval("!(a >= 3) && a != 1", &[Some(2)]), Some(1));
+        assert_eq!(eval("a == a", &[None]), None);
+
+        assert!(Expression::parse("a &&").is_err());
+        assert!(Expression::parse("(a").is_err());
+        assert!(Expression::parse("a b").is_err());
+        assert!(Expression::parse("0xg").is_err());
+    }
+
+    #[test]
+    fn arithmetic_and_delays() {
+        assert_eq!(eval("a + b * 2 == 7", &[Some(3), Some(2)]), Some(1));
+        assert_eq!(eval("a - b", &[Some(1), Some(2)]), Some(0xff));
+        assert_eq!(eval("-a", &[Some(1)]), Some(0xff));
+        assert_eq!(eval("a << 1 >> 2", &[Some(0x81)]), Some(0x00));
+        assert_eq!(eval("a << 4294967296", &[Some(0x81)]), Some(0x00));
+        assert_eq!(eval("a >> 4294967297", &[Some(0x81)]), Some(0x00));
+        assert_eq!(eval("1 << 2 < 5", &[]), Some(1));
+        assert_eq!(eval("a / b", &[Some(7), Some(0)]), None);
+        assert_eq!(eval("a % 4", &[Some(7)]), Some(3));
+
+        let e = Expression::parse("a != delay(a, 10) && delay(b, 0x10) + a").unwrap();
+        assert_eq!(e.names(), ["a", "b"]);
+        assert_eq!(e.inputs(), [(0, 0), (0, 10), (1, 16)]);
+        assert_eq!(e.width(&[8, 8, 4]), 1);
+        let e = Expression::parse("a + b").unwrap();
+        assert_eq!(e.width(&[4, 12]), 12);
+        assert!(Expression::parse("delay(a)").is_err());
+        assert!(Expression::parse("delay(a, b)").is_err());
+    }
+
+    #[test]
+    fn wide_signals_are_unknown() {
+        let mut wide = Signal::new();
+        wide.push(0, &[b'0'; 128]);
+        let mut one = [b'0'; 128];
+        one[127] = b'1';
+        wide.push(10, &one);
+        let mut narrow = Signal::new();
+        narrow.push(0, b"1");
+
+        let load = |text| {
+            let expr = Expression::parse(text).unwrap();
+            Evaluator::new(expr, vec![(wide.clone(), 128), (narrow.clone(), 1)])
+        };
+        let e = load("a + b");
+        assert_eq!(e.width(), 128);
+        assert_eq!(e.eval_at(10), None);
+        let s = e.to_signal();
+        assert_eq!(s.times(), [0]);
+        assert_eq!(s.value(0), &[b'x'; 128]);
+        // Known results do not depend on the wide signal.
+        let s = load("b || a").to_signal();
+        assert_eq!(s.value(0), b"1");
+    }
+}
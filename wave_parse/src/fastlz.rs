@@ -0,0 +1,110 @@
+//! A decoder for FastLZ blocks, levels 1 and 2.
+//!
+//! fstapi compresses value chains with FastLZ instead of zlib when a
+//! writer asks for speed with `FST_WR_PT_FASTLZ`. The level is in the top
+//! three bits of the first byte; each instruction is a run of literals or
+//! a match of at least three bytes.
+
+use crate::inflate::capacity;
+use crate::InvalidData;
+
+const CORRUPT: InvalidData = InvalidData("corrupt FastLZ block");
+
+/// Matches at level 2 reach past this with a 16-bit distance.
+const MAX_DISTANCE: usize = 8191;
+
+fn byte(data: &[u8], pos: &mut usize) -> Result<usize, InvalidData> {
+    let b = *data.get(*pos).ok_or(CORRUPT)?;
+    *pos += 1;
+    Ok(b as usize)
+}
+
+/// Decode a complete FastLZ block.
+pub(crate) fn fastlz_decode(data: &[u8], size_hint: usize) -> Result<Vec<u8>, InvalidData> {
+    let Some(&first) = data.first() else {
+        return Ok(Vec::new());
+    };
+    let level2 = match first >> 5 {
+        0 => false,
+        1 => true,
+        _ => return Err(CORRUPT),
+    };
+    let mut out = Vec::with_capacity(capacity(size_hint, data.len()));
+    let mut pos = 1;
+    let mut ctrl = (first & 31) as usize;
+    loop {
+        if ctrl < 32 {
+            let literal = data.get(pos..pos + ctrl + 1).ok_or(CORRUPT)?;
+            out.extend_from_slice(literal);
+            pos += ctrl + 1;
+        } else {
+            let mut len = (ctrl >> 5) - 1;
+            let ofs = (ctrl & 31) << 8;
+            if len == 6 {
+                if level2 {
+                    loop {
+                        let b = byte(data, &mut pos)?;
+                        len += b;
+                        if b != 255 {
+                            break;
+                        }
+                    }
+                } else {
+                    len += byte(data, &mut pos)?;
+                }
+            }
+            let code = byte(data, &mut pos)?;
+            let mut distance = ofs + code + 1;
+            if level2 && code == 255 && ofs == 31 << 8 {
+                distance = (byte(data, &mut pos)? << 8 | byte(data, &mut pos)?) + MAX_DISTANCE + 1;
+            }
+            if distance > out.len() {
+                return Err(CORRUPT);
+            }
+            let start = out.len() - distance;
+            for k in 0..len + 3 {
+                out.push(out[start + k]);
+            }
+        }
+        match data.get(pos) {
+            Some(&b) => ctrl = b as usize,
+            None => break,
+        }
+        pos += 1;
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn instructions() {
+        // "abc", a copy of 6 bytes from 3 back, then "xyz".
+        let block = [2, b'a', b'b', b'c', 0x80, 2, 2, b'x', b'y', b'z'];
+        assert_eq!(fastlz_decode(&block, 0).unwrap(), b"abcabcabcxyz");
+        // A run of 1 + 3 + 6 + 11 bytes from a single literal, at both levels.
+        let run = [0, b'-', 0xe0, 11, 0];
+        assert_eq!(fastlz_decode(&run, 0).unwrap(), [b'-'; 21]);
+        let run = [0x20, b'-', 0xe0, 11, 0];
+        assert_eq!(fastlz_decode(&run, usize::MAX).unwrap(), [b'-'; 21]);
+        // Level 2 continues long lengths past 255.
+        let long = [0x20, b'-', 0xe0, 255, 1, 0];
+        assert_eq!(fastlz_decode(&long, 0).unwrap().len(), 1 + 3 + 6 + 256);
+        // A far match, 8195 bytes back.
+        let mut far = vec![0x3f];
+        far.extend([b'.'; 32]);
+        for _ in 0..8192 / 32 {
+            far.push(31);
+            far.extend([b'a'; 32]);
+        }
+        far.extend([0x3f, 255, 0, 3]);
+        let out = fastlz_decode(&far, 0).unwrap();
+        assert_eq!(out.len(), 32 + 8192 + 3);
+        assert_eq!(&out[8224..], b"...");
+        assert!(fastlz_decode(&[0, b'a', 0x20, 1], 0).is_err());
+        assert!(fastlz_decode(&[2, b'a'], 0).is_err());
+        assert!(fastlz_decode(&[0x40], 0).is_err());
+    }
+}
@@ -0,0 +1,464 @@
+//! C interface.
+//!
+//! The crate builds as a `cdylib` exporting the functions below, declared
+//! in `include/wave_parse.h`. The header is generated by cbindgen from
+//! `cbindgen.toml` and CI checks that it is up to date. A C program opens a
+//! waveform, walks its variables by index, loads the signals it needs and
+//! frees everything it got:
+//!
+//! ```c
+//! WpWaveform *w = wp_open("dump.vcd");
+//! if (!w) { fprintf(stderr, "%s\n", wp_last_error()); return 1; }
+//! size_t i;
+//! if (wp_find_var(w, "top.clk", &i)) {
+//!     WpSignal *s = wp_load_signal(w, wp_var_signal(w, i));
+//!     for (size_t n = 0; n < wp_signal_len(s); n++) {
+//!         size_t len;
+//!         const uint8_t *v = wp_signal_value(s, n, &len);
+//!         printf("%llu %.*s\n", wp_signal_time(s, n), (int)len, v);
+//!     }
+//!     wp_signal_free(s);
+//! }
+//! wp_close(w);
+//! ```
+//!
+//! Functions returning a pointer return null on failure, and
+//! [`wp_last_error`] describes the most recent failure on the calling
+//! thread. A panic inside the library is caught before it reaches C and
+//! reported as a failure; functions without a way to fail return null, 0
+//! or false instead. Strings and values returned by the library stay
+//! valid until the handle they came from is freed.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+
+use crate::arrow::{self, ArrowArray, ArrowSchema};
+use crate::{convert, Signal, SignalId, Waveform};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(e: io::Error) {
+    let message = CString::new(e.to_string().replace('\0', " ")).expect("no NUL bytes");
+    LAST_ERROR.with(|l| *l.borrow_mut() = Some(message));
+}
+
+/// Run the body of an entry point, returning `failed` if it panics, as
+/// unwinding into C is undefined behavior.
+fn guard<T>(failed: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(|s| s.as_str()))
+            .unwrap_or("unknown panic");
+        set_error(io::Error::other(format!("panic: {}", message)));
+        failed
+    })
+}
+
+/// An open waveform.
+pub struct WpWaveform {
+    wave: Box<dyn Waveform>,
+    vars: Vec<FfiVar>,
+}
+
+struct FfiVar {
+    path: CString,
+    signal: SignalId,
+    width: u32,
+}
+
+/// A loaded signal.
+pub struct WpSignal {
+    signal: Signal,
+}
+
+/// The message of the last failure on this thread, or null if nothing
+/// failed yet.
+#[no_mangle]
+pub extern "C" fn wp_last_error() -> *const c_char {
+    guard(ptr::null(), || {
+        LAST_ERROR.with(|l| l.borrow().as_ref().map_or(ptr::null(), |m| m.as_ptr()))
+    })
+}
+
+/// Open a waveform file, picking the reader by extension.
+///
+/// # Safety
+///
+/// `path` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn wp_open(path: *const c_char) -> *mut WpWaveform {
+    guard(ptr::null_mut(), || {
+        let path = match CStr::from_ptr(path).to_str() {
+            Ok(p) => p,
+            Err(_) => {
+                set_error(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "path is not UTF-8",
+                ));
+                return ptr::null_mut();
+            }
+        };
+        let wave = match convert::open(Path::new(path)) {
+            Ok(w) => w,
+            Err(e) => {
+                set_error(e);
+                return ptr::null_mut();
+            }
+        };
+        let vars = wave
+            .hierarchy()
+            .var_paths()
+            .into_iter()
+            .map(|(path, var)| FfiVar {
+                path: CString::new(path).unwrap_or_default(),
+                signal: var.signal,
+                width: var.width,
+            })
+            .collect();
+        Box::into_raw(Box::new(WpWaveform { wave, vars }))
+    })
+}
+
+/// Close a waveform. Signals loaded from it stay valid.
+///
+/// # Safety
+///
+/// `w` must come from [`wp_open`] and not be used afterwards, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn wp_close(w: *mut WpWaveform) {
+    guard((), || {
+        if !w.is_null() {
+            drop(Box::from_raw(w));
+        }
+    })
+}
+
+/// Number of variables, which are numbered depth-first from 0.
+///
+/// # Safety
+///
+/// `w` must be an open waveform.
+#[no_mangle]
+pub unsafe extern "C" fn wp_var_count(w: *const WpWaveform) -> usize {
+    guard(0, || (*w).vars.len())
+}
+
+/// The dot-separated path of variable `i`, or null if there is none.
+///
+/// # Safety
+///
+/// `w` must be an open waveform.
+#[no_mangle]
+pub unsafe extern "C" fn wp_var_path(w: *const WpWaveform, i: usize) -> *const c_char {
+    guard(ptr::null(), || {
+        let vars = &(*w).vars;
+        vars.get(i).map_or(ptr::null(), |v| v.path.as_ptr())
+    })
+}
+
+/// The width in bits of variable `i`, 0 if there is none.
+///
+/// # Safety
+///
+/// `w` must be an open waveform.
+#[no_mangle]
+pub unsafe extern "C" fn wp_var_width(w: *const WpWaveform, i: usize) -> u32 {
+    guard(0, || {
+        let vars = &(*w).vars;
+        vars.get(i).map_or(0, |v| v.width)
+    })
+}
+
+/// The signal carrying the values of variable `i`. Aliased variables
+/// share a signal.
+///
+/// # Safety
+///
+/// `w` must be an open waveform and `i` less than [`wp_var_count`].
+#[no_mangle]
+pub unsafe extern "C" fn wp_var_signal(w: *const WpWaveform, i: usize) -> u64 {
+    guard(0, || {
+        let vars = &(*w).vars;
+        vars[i].signal.0
+    })
+}
+
+/// Find the variable at a dot-separated path and store its index in
+/// `index`. Returns whether it exists.
+///
+/// # Safety
+///
+/// `w` must be an open waveform, `path` a NUL-terminated string and
+/// `index` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn wp_find_var(
+    w: *const WpWaveform,
+    path: *const c_char,
+    index: *mut usize,
+) -> bool {
+    guard(false, || {
+        let path = CStr::from_ptr(path);
+        match (*w).vars.iter().position(|v| v.path.as_c_str() == path) {
+            Some(i) => {
+                *index = i;
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+/// The duration of one time tick in seconds, 0 if unknown.
+///
+/// # Safety
+///
+/// `w` must be an open waveform.
+#[no_mangle]
+pub unsafe extern "C" fn wp_timescale_seconds(w: *const WpWaveform) -> f64 {
+    guard(0.0, || (*w).wave.timescale().map_or(0.0, |t| t.seconds()))
+}
+
+/// Load the changes of a signal, to be freed with [`wp_signal_free`].
+///
+/// # Safety
+///
+/// `w` must be an open waveform.
+#[no_mangle]
+pub unsafe extern "C" fn wp_load_signal(w: *mut WpWaveform, signal: u64) -> *mut WpSignal {
+    guard(ptr::null_mut(), || {
+        match (*w).wave.load_signals(&[SignalId(signal)]) {
+            Ok(mut s) => Box::into_raw(Box::new(WpSignal {
+                signal: s.pop().expect("one signal per id"),
+            })),
+            Err(e) => {
+                set_error(e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Free a signal.
+///
+/// # Safety
+///
+/// `s` must come from [`wp_load_signal`] and not be used afterwards, or
+/// be null.
+#[no_mangle]
+pub unsafe extern "C" fn wp_signal_free(s: *mut WpSignal) {
+    guard((), || {
+        if !s.is_null() {
+            drop(Box::from_raw(s));
+        }
+    })
+}
+
+/// Number of value changes.
+///
+/// # Safety
+///
+/// `s` must be a loaded signal.
+#[no_mangle]
+pub unsafe extern "C" fn wp_signal_len(s: *const WpSignal) -> usize {
+    guard(0, || (*s).signal.len())
+}
+
+/// Time of change `i`.
+///
+/// # Safety
+///
+/// `s` must be a loaded signal and `i` less than [`wp_signal_len`].
+#[no_mangle]
+pub unsafe extern "C" fn wp_signal_time(s: *const WpSignal, i: usize) -> u64 {
+    guard(0, || (*s).signal.time(i))
+}
+
+/// The value of change `i` as text (e.g. `1`, `x01z` or a real number),
+/// not NUL-terminated; its length is stored in `len`.
+///
+/// # Safety
+///
+/// `s` must be a loaded signal, `i` less than [`wp_signal_len`] and `len`
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn wp_signal_value(
+    s: *const WpSignal,
+    i: usize,
+    len: *mut usize,
+) -> *const u8 {
+    guard(ptr::null(), || {
+        let value = (*s).signal.value(i);
+        *len = value.len();
+        value.as_ptr()
+    })
+}
+
+/// Store the index of the change in effect at `time` in `index`. Returns
+/// false if the signal has no value yet at that time.
+///
+/// # Safety
+///
+/// `s` must be a loaded signal and `index` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn wp_signal_index_at(
+    s: *const WpSignal,
+    time: u64,
+    index: *mut usize,
+) -> bool {
+    guard(false, || match (*s).signal.index_at(time) {
+        Some(i) => {
+            *index = i;
+            true
+        }
+        None => false,
+    })
+}
+
+/// Sample the variables at `paths` at their change times in
+/// `[start, end)` and export them through the Arrow C data interface as a
+/// struct array with a `time` column first (see
+/// [`to_arrow`](arrow::to_arrow)). Returns false on failure.
+///
+/// # Safety
+///
+/// `w` must be an open waveform, `paths` point to `n` NUL-terminated
+/// strings, and `array` and `schema` be valid for writes. On success the
+/// caller owns both structures and must release them.
+#[no_mangle]
+pub unsafe extern "C" fn wp_to_arrow(
+    w: *mut WpWaveform,
+    paths: *const *const c_char,
+    n: usize,
+    start: u64,
+    end: u64,
+    array: *mut ArrowArray,
+    schema: *mut ArrowSchema,
+) -> bool {
+    guard(false, || {
+        let mut names = Vec::with_capacity(n);
+        for i in 0..n {
+            match CStr::from_ptr(*paths.add(i)).to_str() {
+                Ok(p) => names.push(p),
+                Err(_) => {
+                    set_error(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "path is not UTF-8",
+                    ));
+                    return false;
+                }
+            }
+        }
+        match arrow::to_arrow(&mut *(*w).wave, &names, start..end) {
+            Ok(batch) => {
+                let (a, s) = batch.export();
+                ptr::write(array, a);
+                ptr::write(schema, s);
+                true
+            }
+            Err(e) => {
+                set_error(e);
+                false
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn c_api() {
+        let path = std::env::temp_dir().join(format!("wave_parse_ffi_{}.vcd", std::process::id()));
+        std::fs::write(
+            &path,
+            b"$timescale 1ns $end $scope module top $end $var wire 2 ! bus $end
+$upscope $end $enddefinitions $end
+#0 b0 !
+#10 b1x !",
+        )
+        .unwrap();
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+        unsafe {
+            let w = wp_open(c_path.as_ptr());
+            assert!(!w.is_null());
+            assert_eq!(wp_var_count(w), 1);
+            assert_eq!(CStr::from_ptr(wp_var_path(w, 0)).to_str(), Ok("top.bus"));
+            assert!(wp_var_path(w, 1).is_null());
+            assert_eq!(wp_var_width(w, 0), 2);
+            assert_eq!(wp_timescale_seconds(w), 1e-9);
+
+            let mut i = 99;
+            assert!(!wp_find_var(w, c"top.nope".as_ptr(), &mut i));
+            assert!(wp_find_var(w, c"top.bus".as_ptr(), &mut i));
+            assert_eq!(i, 0);
+
+            let s = wp_load_signal(w, wp_var_signal(w, i));
+            wp_close(w);
+            assert_eq!(wp_signal_len(s), 2);
+            assert_eq!(wp_signal_time(s, 1), 10);
+            let mut len = 0;
+            let v = wp_signal_value(s, 1, &mut len);
+            assert_eq!(std::slice::from_raw_parts(v, len), b"1x");
+            assert!(wp_signal_index_at(s, 5, &mut i));
+            assert_eq!(i, 0);
+            wp_signal_free(s);
+
+            let paths = [c"top.bus".as_ptr()];
+            let mut array = std::mem::MaybeUninit::uninit();
+            let mut schema = std::mem::MaybeUninit::uninit();
+            let w = wp_open(c_path.as_ptr());
+            let ok = wp_to_arrow(
+                w,
+                paths.as_ptr(),
+                1,
+                0,
+                20,
+                array.as_mut_ptr(),
+                schema.as_mut_ptr(),
+            );
+            assert!(ok);
+            drop((array.assume_init(), schema.assume_init()));
+            wp_close(w);
+
+            assert!(wp_open(c"/nonexistent.vcd".as_ptr()).is_null());
+            assert!(!wp_last_error().is_null());
+
+            // Reading past the end panics inside, which must not reach C.
+            let w = wp_open(c_path.as_ptr());
+            let s = wp_load_signal(w, wp_var_signal(w, 0));
+            assert_eq!(wp_signal_time(s, 2), 0);
+            let error = CStr::from_ptr(wp_last_error()).to_str().unwrap();
+            assert!(error.starts_with("panic: "), "{}", error);
+            wp_signal_free(s);
+            wp_close(w);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn header_declares_every_function() {
+        let header = include_str!("../include/wave_parse.h");
+        let mut names = include_str!("ffi.rs")
+            .lines()
+            .filter_map(|l| l.split_once("extern \"C\" fn ")?.1.split_once('('))
+            .map(|(name, _)| name)
+            .peekable();
+        assert!(names.peek().is_some());
+        for name in names {
+            assert!(
+                header.contains(&format!("{}(", name)),
+                "{} is not declared",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,523 @@
+//! A hierarchy in flat arrays.
+//!
+//! A [`Hierarchy`] owns a tree of separately allocated scopes and
+//! variables, which walks of millions of variables spend most of their
+//! time chasing. A [`FlatHierarchy`] holds every scope in one array in
+//! depth-first order and every variable in another, referring to them by
+//! [`ScopeId`] and [`VarId`], with names interned in the same [`Interner`]
+//! as the tree, so converting between the two copies no names. A scope's
+//! subtree is a range of the scope array, and the variables it declares,
+//! directly or not, a range of the variable array, in the order of
+//! [`Hierarchy::for_each_var`].
+//!
+//! The VCD header parser declares scopes and variables straight into a
+//! [`FlatBuilder`], so a [`VcdFile`](crate::VcdFile) holds the flat form
+//! and only builds the tree when asked. [`FlatHierarchy::new`] flattens a
+//! tree built some other way.
+
+use std::mem;
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::{
+    Hierarchy, Interner, NameId, ReferenceIndex, Scope, ScopeKind, SignalId, Var, VarKind,
+};
+
+/// A scope of a [`FlatHierarchy`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScopeId(pub u32);
+
+/// A variable of a [`FlatHierarchy`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VarId(pub u32);
+
+/// A scope stored in a [`FlatHierarchy`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlatScope {
+    pub kind: ScopeKind,
+    pub name: NameId,
+    pub parent: Option<ScopeId>,
+    /// One past the last scope of the subtree.
+    end: u32,
+    /// The variables declared directly in the scope.
+    vars: Range<u32>,
+}
+
+/// A variable stored in a [`FlatHierarchy`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlatVar {
+    pub kind: VarKind,
+    pub width: u32,
+    pub signal: SignalId,
+    pub name: NameId,
+    pub index: Option<ReferenceIndex>,
+    /// The scope declaring the variable, `None` at the top level.
+    pub scope: Option<ScopeId>,
+}
+
+/// The scopes and variables of a waveform, in flat arrays.
+#[derive(Debug, Clone, Default)]
+pub struct FlatHierarchy {
+    names: Arc<Interner>,
+    scopes: Vec<FlatScope>,
+    vars: Vec<FlatVar>,
+    /// Variables declared outside of any scope.
+    top_vars: Range<u32>,
+}
+
+/// An iterator over the direct children of a scope; see
+/// [`FlatHierarchy::child_scopes`].
+pub struct ChildScopes<'h> {
+    scopes: &'h [FlatScope],
+    next: u32,
+    end: u32,
+}
+
+impl Iterator for ChildScopes<'_> {
+    type Item = ScopeId;
+
+    fn next(&mut self) -> Option<ScopeId> {
+        if self.next >= self.end {
+            return None;
+        }
+        let id = ScopeId(self.next);
+        self.next = self.scopes[self.next as usize].end;
+        Some(id)
+    }
+}
+
+fn var_ids(range: Range<u32>) -> impl Iterator<Item = VarId> {
+    range.map(VarId)
+}
+
+impl FlatHierarchy {
+    /// Flatten `hierarchy`.
+    pub fn new(hierarchy: &Hierarchy) -> FlatHierarchy {
+        let mut flat = FlatHierarchy {
+            names: Arc::clone(hierarchy.names()),
+            ..FlatHierarchy::default()
+        };
+        flat.top_vars = flat.push_vars(&hierarchy.vars, None);
+        for scope in &hierarchy.scopes {
+            flat.push_scope(scope, None);
+        }
+        flat.scopes.shrink_to_fit();
+        flat.vars.shrink_to_fit();
+        flat
+    }
+
+    fn push_vars(&mut self, vars: &[Var], scope: Option<ScopeId>) -> Range<u32> {
+        let start = self.vars.len() as u32;
+        for var in vars {
+            self.vars.push(FlatVar {
+                kind: var.kind,
+                width: var.width,
+                signal: var.signal,
+                name: var.name,
+                index: var.index,
+                scope,
+            });
+        }
+        start..self.vars.len() as u32
+    }
+
+    fn push_scope(&mut self, scope: &Scope, parent: Option<ScopeId>) {
+        let id = ScopeId(u32::try_from(self.scopes.len()).expect("too many scopes"));
+        self.scopes.push(FlatScope {
+            kind: scope.kind,
+            name: scope.name,
+            parent,
+            end: 0,
+            vars: 0..0,
+        });
+        let vars = self.push_vars(&scope.vars, Some(id));
+        for child in &scope.scopes {
+            self.push_scope(child, Some(id));
+        }
+        let end = self.scopes.len() as u32;
+        let flat = &mut self.scopes[id.0 as usize];
+        flat.vars = vars;
+        flat.end = end;
+    }
+
+    /// The names of the scopes and variables.
+    pub fn names(&self) -> &Interner {
+        &self.names
+    }
+
+    /// The name with id `id`.
+    pub fn name(&self, id: NameId) -> &str {
+        self.names.resolve(id)
+    }
+
+    pub fn scope(&self, id: ScopeId) -> &FlatScope {
+        &self.scopes[id.0 as usize]
+    }
+
+    pub fn var(&self, id: VarId) -> &FlatVar {
+        &self.vars[id.0 as usize]
+    }
+
+    /// Number of scopes, nested ones included.
+    pub fn scope_count(&self) -> usize {
+        self.scopes.len()
+    }
+
+    /// Number of variables in the hierarchy.
+    pub fn var_count(&self) -> usize {
+        self.vars.len()
+    }
+
+    /// The scopes outside of any scope.
+    pub fn top_scopes(&self) -> ChildScopes<'_> {
+        ChildScopes {
+            scopes: &self.scopes,
+            next: 0,
+            end: self.scopes.len() as u32,
+        }
+    }
+
+    /// The variables outside of any scope.
+    pub fn top_vars(&self) -> impl Iterator<Item = VarId> {
+        var_ids(self.top_vars.clone())
+    }
+
+    /// The scopes declared directly in scope `id`.
+    pub fn child_scopes(&self, id: ScopeId) -> ChildScopes<'_> {
+        ChildScopes {
+            scopes: &self.scopes,
+            next: id.0 + 1,
+            end: self.scope(id).end,
+        }
+    }
+
+    /// The variables declared directly in scope `id`.
+    pub fn scope_vars(&self, id: ScopeId) -> impl Iterator<Item = VarId> {
+        var_ids(self.scope(id).vars.clone())
+    }
+
+    /// The variables declared in scope `id` or any scope inside it,
+    /// depth-first.
+    pub fn subtree_vars(&self, id: ScopeId) -> impl Iterator<Item = VarId> {
+        let scope = self.scope(id);
+        let end = self.scopes[scope.end as usize - 1].vars.end;
+        var_ids(scope.vars.start..end)
+    }
+
+    /// All variables, depth-first.
+    pub fn vars(&self) -> impl Iterator<Item = (VarId, &FlatVar)> {
+        self.vars
+            .iter()
+            .enumerate()
+            .map(|(i, var)| (VarId(i as u32), var))
+    }
+
+    /// Find the scope object at a specified path.
+    pub fn find_scope<S>(&self, path: &[S]) -> Option<ScopeId>
+    where
+        S: AsRef<str>,
+    {
+        let mut scopes = self.top_scopes();
+        let mut found = None;
+        for name in path {
+            let name = self.names.get(name.as_ref())?;
+            let id = scopes.find(|&s| self.scope(s).name == name)?;
+            scopes = self.child_scopes(id);
+            found = Some(id);
+        }
+        found
+    }
+
+    /// Find the variable object at a specified path.
+    pub fn find_var<S>(&self, path: &[S]) -> Option<VarId>
+    where
+        S: AsRef<str>,
+    {
+        let (name, scope_path) = path.split_last()?;
+        let name = self.names.get(name.as_ref())?;
+        let mut vars = if scope_path.is_empty() {
+            self.top_vars.clone()
+        } else {
+            self.scope(self.find_scope(scope_path)?).vars.clone()
+        };
+        vars.find(|&v| self.vars[v as usize].name == name)
+            .map(VarId)
+    }
+
+    /// Find a variable by its dot-separated path, e.g. `top.cpu.pc`.
+    pub fn lookup(&self, path: &str) -> Option<VarId> {
+        let parts: Vec<&str> = path.split('.').collect();
+        self.find_var(&parts)
+    }
+
+    /// The dot-separated path of scope `id`.
+    pub fn scope_path(&self, id: ScopeId) -> String {
+        let mut names = Vec::new();
+        let mut scope = Some(id);
+        while let Some(id) = scope {
+            names.push(self.scope(id).name);
+            scope = self.scope(id).parent;
+        }
+        names.reverse();
+        self.names.path(&names)
+    }
+
+    /// The dot-separated path of variable `id`.
+    pub fn var_path(&self, id: VarId) -> String {
+        let var = self.var(id);
+        match var.scope {
+            Some(scope) => {
+                let mut path = self.scope_path(scope);
+                path.push('.');
+                path.push_str(self.name(var.name));
+                path
+            }
+            None => self.name(var.name).to_string(),
+        }
+    }
+
+    /// All variables with their full dot-separated paths, depth-first,
+    /// like [`Hierarchy::var_paths`].
+    pub fn var_paths(&self) -> Vec<(String, VarId)> {
+        let mut out = Vec::with_capacity(self.vars.len());
+        let mut prefix = String::new();
+        // Ends of the scopes the prefix holds, with its length before each.
+        let mut open: Vec<(u32, usize)> = Vec::new();
+        for var in self.top_vars() {
+            out.push((self.name(self.var(var).name).to_string(), var));
+        }
+        for (i, scope) in self.scopes.iter().enumerate() {
+            while open.last().is_some_and(|&(end, _)| end <= i as u32) {
+                let (_, len) = open.pop().expect("scope is open");
+                prefix.truncate(len);
+            }
+            open.push((scope.end, prefix.len()));
+            prefix.push_str(self.name(scope.name));
+            prefix.push('.');
+            for var in var_ids(scope.vars.clone()) {
+                out.push((format!("{}{}", prefix, self.name(self.var(var).name)), var));
+            }
+        }
+        out
+    }
+
+    /// Distinct signal ids in declaration order, like
+    /// [`Hierarchy::signal_ids`].
+    pub fn signal_ids(&self) -> Vec<SignalId> {
+        let mut seen = std::collections::HashSet::new();
+        self.vars
+            .iter()
+            .map(|var| var.signal)
+            .filter(|&id| seen.insert(id))
+            .collect()
+    }
+
+    fn to_var(&self, id: VarId) -> Var {
+        let var = self.var(id);
+        Var {
+            kind: var.kind,
+            width: var.width,
+            signal: var.signal,
+            name: var.name,
+            index: var.index,
+        }
+    }
+
+    fn to_scope(&self, id: ScopeId) -> Scope {
+        let flat = self.scope(id);
+        let mut scope = Scope::new(flat.kind, flat.name);
+        scope.vars = self.scope_vars(id).map(|v| self.to_var(v)).collect();
+        scope.scopes = self.child_scopes(id).map(|s| self.to_scope(s)).collect();
+        scope
+    }
+
+    /// The hierarchy as a tree again.
+    pub fn to_hierarchy(&self) -> Hierarchy {
+        let mut hierarchy = Hierarchy::with_names(Arc::clone(&self.names));
+        hierarchy.scopes = self.top_scopes().map(|s| self.to_scope(s)).collect();
+        hierarchy.vars = self.top_vars().map(|v| self.to_var(v)).collect();
+        hierarchy
+    }
+
+    /// Heap memory held, in bytes, approximately.
+    pub fn size_bytes(&self) -> usize {
+        self.names.size_bytes()
+            + self.scopes.capacity() * mem::size_of::<FlatScope>()
+            + self.vars.capacity() * mem::size_of::<FlatVar>()
+    }
+}
+
+/// Builds a [`FlatHierarchy`] in the order of a VCD header: scopes are
+/// opened and closed, and variables declared into the innermost open
+/// scope, before or after its child scopes.
+#[derive(Debug, Default)]
+pub struct FlatBuilder {
+    flat: FlatHierarchy,
+    /// The open scopes, outermost first.
+    open: Vec<ScopeId>,
+}
+
+impl FlatBuilder {
+    pub fn new() -> FlatBuilder {
+        FlatBuilder::default()
+    }
+
+    /// Open a scope inside the innermost open one, or at the top level.
+    pub fn open_scope(&mut self, kind: ScopeKind, name: &str) -> ScopeId {
+        let flat = &mut self.flat;
+        let id = ScopeId(u32::try_from(flat.scopes.len()).expect("too many scopes"));
+        let name = Arc::make_mut(&mut flat.names).intern(name);
+        flat.scopes.push(FlatScope {
+            kind,
+            name,
+            parent: self.open.last().copied(),
+            end: 0,
+            vars: 0..0,
+        });
+        self.open.push(id);
+        id
+    }
+
+    /// Close the innermost open scope, if there is one.
+    pub fn close_scope(&mut self) -> Option<ScopeId> {
+        let id = self.open.pop()?;
+        self.flat.scopes[id.0 as usize].end = self.flat.scopes.len() as u32;
+        Some(id)
+    }
+
+    /// Declare a variable in the innermost open scope, or at the top
+    /// level.
+    pub fn add_var(
+        &mut self,
+        kind: VarKind,
+        width: u32,
+        signal: SignalId,
+        name: &str,
+        index: Option<ReferenceIndex>,
+    ) {
+        let flat = &mut self.flat;
+        u32::try_from(flat.vars.len()).expect("too many variables");
+        let name = Arc::make_mut(&mut flat.names).intern(name);
+        flat.vars.push(FlatVar {
+            kind,
+            width,
+            signal,
+            name,
+            index,
+            scope: self.open.last().copied(),
+        });
+    }
+
+    /// Number of scopes open.
+    pub fn depth(&self) -> usize {
+        self.open.len()
+    }
+
+    /// The hierarchy, with the scopes still open closed.
+    pub fn finish(mut self) -> FlatHierarchy {
+        while self.close_scope().is_some() {}
+        let mut flat = self.flat;
+        // Scope ids are in depth-first order, so grouping the variables by
+        // scope, top level first, gives each scope and each subtree a
+        // contiguous range. The sort is stable, keeping declaration order.
+        flat.vars
+            .sort_by_key(|var| var.scope.map_or(0, |s| s.0 + 1));
+        let mut at = flat.vars.partition_point(|var| var.scope.is_none());
+        flat.top_vars = 0..at as u32;
+        for (i, scope) in flat.scopes.iter_mut().enumerate() {
+            let start = at;
+            at += flat.vars[at..].partition_point(|var| var.scope == Some(ScopeId(i as u32)));
+            scope.vars = start as u32..at as u32;
+        }
+        flat.scopes.shrink_to_fit();
+        flat.vars.shrink_to_fit();
+        flat
+    }
+}
+
+/// Serializes as the [`Hierarchy`] it flattens.
+#[cfg(feature = "serde")]
+impl serde::Serialize for FlatHierarchy {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        self.to_hierarchy().serialize(s)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for FlatHierarchy {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<FlatHierarchy, D::Error> {
+        Hierarchy::deserialize(d).map(|h| FlatHierarchy::new(&h))
+    }
+}
+
+impl From<&Hierarchy> for FlatHierarchy {
+    fn from(hierarchy: &Hierarchy) -> FlatHierarchy {
+        FlatHierarchy::new(hierarchy)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{VcdFile, Waveform};
+
+    #[test]
+    fn flattens() {
+        let vcd = VcdFile::from_bytes(
+            b"$var wire 1 ! rst $end
+$scope module top $end $var wire 1 \" clk $end
+$scope module genblk1 $end $var wire 4 # clk $end $upscope $end
+$scope module u $end $scope module genblk1 $end $var wire 1 \" clk $end $upscope $end
+$var wire 1 $ q $end $upscope $end
+$var wire 1 % d $end
+$upscope $end $scope module empty $end $upscope $end $enddefinitions $end"
+                .to_vec(),
+        )
+        .unwrap();
+        let hierarchy = vcd.hierarchy();
+        let flat = FlatHierarchy::new(hierarchy);
+        assert_eq!(flat.to_hierarchy(), *hierarchy);
+        assert_eq!((flat.scope_count(), flat.var_count()), (5, 6));
+        assert_eq!(flat.names().len(), 8);
+        assert!(std::ptr::eq(flat.names(), &**hierarchy.names()));
+
+        let paths: Vec<String> = flat.var_paths().into_iter().map(|(p, _)| p).collect();
+        let expected: Vec<String> = hierarchy.var_paths().into_iter().map(|(p, _)| p).collect();
+        assert_eq!(paths, expected);
+        // Variables declared after a child scope still come before it.
+        let parsed = vcd.header().flat();
+        let parsed_paths: Vec<String> = parsed.var_paths().into_iter().map(|(p, _)| p).collect();
+        assert_eq!(
+            parsed_paths,
+            [
+                "rst",
+                "top.clk",
+                "top.d",
+                "top.genblk1.clk",
+                "top.u.q",
+                "top.u.genblk1.clk"
+            ]
+        );
+        assert_eq!(parsed_paths, paths);
+        let top = parsed.find_scope(&["top"]).unwrap();
+        assert_eq!(parsed.subtree_vars(top).count(), 5);
+        for (i, path) in paths.iter().enumerate() {
+            assert_eq!(flat.var_path(VarId(i as u32)), *path);
+            assert_eq!(flat.lookup(path), Some(VarId(i as u32)), "{}", path);
+        }
+        assert_eq!(flat.lookup("top.nope"), None);
+        assert_eq!(flat.lookup("top.u.clk"), None);
+        assert_eq!(flat.signal_ids(), hierarchy.signal_ids());
+
+        let top = flat.find_scope(&["top"]).unwrap();
+        let children: Vec<String> = flat.child_scopes(top).map(|s| flat.scope_path(s)).collect();
+        assert_eq!(children, ["top.genblk1", "top.u"]);
+        assert_eq!(flat.subtree_vars(top).count(), 5);
+        let u = flat.find_scope(&["top", "u"]).unwrap();
+        assert_eq!(flat.scope(u).parent, Some(top));
+        assert_eq!(flat.scope_vars(u).count(), 1);
+        assert_eq!(flat.top_scopes().count(), 2);
+    }
+}
@@ -0,0 +1,217 @@
+//! Reading VCD files that are still being written.
+//!
+//! [`FollowReader`] remembers how far it got and picks up whatever the
+//! simulator appended since the last call. Only complete lines are
+//! parsed, and a record cut off by the end of the available data (a
+//! `$comment` spanning lines, a vector whose identifier is not written
+//! yet) is retried once more data arrives, so a partially written final
+//! record is never reported.
+//!
+//! To keep a whole [`VcdFile`](crate::VcdFile) and its loaded signals
+//! current instead, see [`VcdFile::refresh`](crate::VcdFile::refresh).
+//!
+//! FST files cannot be followed. The FST writers append value change
+//! blocks as they go, but write the geometry and the hierarchy, which
+//! those blocks cannot be decoded without, only when the file is closed.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::vcd::{parse_header, Header, Token, Tokens};
+
+/// Default time between polls while waiting for data.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Incremental reader for a growing VCD file. Other formats cannot be
+/// followed, see the [module documentation](self).
+pub struct FollowReader {
+    file: File,
+    /// Bytes read but not yet consumed.
+    buf: Vec<u8>,
+    /// Total bytes read from the file.
+    read: u64,
+    header: Option<Header>,
+    /// The last timestamp reported.
+    time: Option<u64>,
+    poll_interval: Duration,
+}
+
+impl FollowReader {
+    /// Start following the file at `path` from its beginning.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<FollowReader> {
+        Ok(FollowReader::new(File::open(path)?))
+    }
+
+    pub fn new(file: File) -> FollowReader {
+        FollowReader {
+            file,
+            buf: Vec::new(),
+            read: 0,
+            header: None,
+            time: None,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+
+    /// Time to sleep between polls in [`wait`](FollowReader::wait).
+    pub fn set_poll_interval(&mut self, interval: Duration) {
+        self.poll_interval = interval;
+    }
+
+    /// The header, once it has been written completely.
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+
+    /// Read whatever was appended to the file since the last call.
+    fn fill(&mut self) -> io::Result<usize> {
+        let len = self.file.metadata()?.len();
+        if len < self.read {
+            return Err(io::Error::other("followed file was truncated"));
+        }
+        let n = (&mut self.file)
+            .take(len - self.read)
+            .read_to_end(&mut self.buf)?;
+        self.read += n as u64;
+        Ok(n)
+    }
+
+    /// Length of the prefix of the buffer made of complete lines.
+    fn complete(&self) -> usize {
+        self.buf
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |p| p + 1)
+    }
+
+    /// Parse the header if it is complete.
+    fn try_header(&mut self) -> io::Result<bool> {
+        if self.header.is_some() {
+            return Ok(true);
+        }
+        let end = self.complete();
+        match parse_header(&self.buf[..end]) {
+            Ok((header, body)) => {
+                self.header = Some(header);
+                self.buf.drain(..body);
+                Ok(true)
+            }
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Process the complete records available now, calling `f` for each
+    /// body token. Returns the number of tokens.
+    ///
+    /// Nothing is reported until the header is complete.
+    pub fn poll<F>(&mut self, mut f: F) -> io::Result<usize>
+    where
+        F: FnMut(Token<'_>),
+    {
+        self.fill()?;
+        if !self.try_header()? {
+            return Ok(0);
+        }
+        let end = self.complete();
+        let mut tokens = Tokens::new(&self.buf[..end], 0).after(self.time);
+        let mut consumed = 0;
+        let mut count = 0;
+        while let Some(token) = tokens.next() {
+            match token {
+                Ok(token) => {
+                    f(token);
+                    consumed = tokens.position();
+                    self.time = tokens.time();
+                    count += 1;
+                }
+                // Cut off by the end of the data; retry with more.
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+        }
+        self.buf.drain(..consumed);
+        Ok(count)
+    }
+
+    /// Like [`poll`](FollowReader::poll), but blocks until at least one
+    /// token was processed or `timeout` elapsed.
+    ///
+    /// Not available on `wasm32-unknown-unknown`, which cannot block; call
+    /// `poll` from the event loop instead.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn wait<F>(&mut self, timeout: Option<Duration>, mut f: F) -> io::Result<usize>
+    where
+        F: FnMut(Token<'_>),
+    {
+        let start = std::time::Instant::now();
+        loop {
+            let n = self.poll(&mut f)?;
+            if n > 0 {
+                return Ok(n);
+            }
+            if timeout.is_some_and(|t| start.elapsed() >= t) {
+                return Ok(0);
+            }
+            std::thread::sleep(self.poll_interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vcd::Change;
+    use std::io::Write;
+
+    #[test]
+    fn follows_appended_data() {
+        let path =
+            std::env::temp_dir().join(format!("wave_parse_follow_{}.vcd", std::process::id()));
+        let mut out = File::create(&path).unwrap();
+        let mut reader = FollowReader::open(&path).unwrap();
+        let mut seen = Vec::new();
+        let mut collect = |t: Token<'_>| {
+            seen.push(match t {
+                Token::Timestamp(t) => format!("#{}", t),
+                Token::Change(Change { value, .. }) => String::from_utf8_lossy(value).into(),
+                _ => "?".to_string(),
+            })
+        };
+
+        out.write_all(b"$scope module t $end\n$var wire 4 ! a $end\n")
+            .unwrap();
+        assert_eq!(reader.poll(&mut collect).unwrap(), 0);
+        assert!(reader.header().is_none());
+
+        out.write_all(b"$upscope $end\n$enddefinitions $end\n#0\nb0101 !\n#1")
+            .unwrap();
+        assert_eq!(reader.poll(&mut collect).unwrap(), 2);
+        assert!(reader.header().is_some());
+
+        // A vector whose identifier is on a line not written yet.
+        out.write_all(b"0\nb1111\n").unwrap();
+        assert_eq!(reader.poll(&mut collect).unwrap(), 1);
+        out.write_all(b"!\n").unwrap();
+        reader.set_poll_interval(Duration::from_millis(1));
+        assert_eq!(
+            reader
+                .wait(Some(Duration::from_secs(5)), &mut collect)
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            reader
+                .wait(Some(Duration::from_millis(5)), &mut collect)
+                .unwrap(),
+            0
+        );
+        assert_eq!(seen, ["#0", "0101", "#10", "1111"]);
+
+        out.set_len(0).unwrap();
+        assert!(reader.poll(|_| {}).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
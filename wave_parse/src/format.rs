@@ -0,0 +1,235 @@
+//! Formatting values as text in a chosen radix.
+//!
+//! [`format_bits`] writes a raw value of a vector as binary, octal, hex,
+//! unsigned or signed decimal, or ASCII, and [`format_value`] does the
+//! same for any variable, passing real and string values through. Values
+//! are first extended to the declared width like VCD vectors, so a radix
+//! always produces the same number of digits for a variable. Unknown bits
+//! propagate as follows:
+//!
+//! * Binary keeps every bit, with the 9-state values of VHDL as well.
+//! * Octal and hex digits whose bits are all `x` or all `z` are `x` or
+//!   `z`; digits with only some unknown bits are `X` or `Z`, `X` winning.
+//! * Decimal values are `z` if every bit is `z` and `x` if any bit is not
+//!   `0` or `1`.
+//! * ASCII characters with unknown bits are `?`, and characters outside
+//!   printable ASCII are `.`. Leading NUL characters, the padding of short
+//!   strings in wide registers, are left out.
+//!
+//! Weak values count as the levels they are weak versions of, as with
+//! [`Logic::to_four_state`].
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::{InvalidData, Logic, LogicVec, Var, VarKind};
+
+/// How a value is written.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub enum Radix {
+    #[default]
+    Binary,
+    Octal,
+    Hex,
+    /// Unsigned decimal.
+    Decimal,
+    /// Two's complement decimal.
+    Signed,
+    /// 8-bit characters, most significant first.
+    Ascii,
+}
+
+impl Radix {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Radix::Binary => "bin",
+            Radix::Octal => "oct",
+            Radix::Hex => "hex",
+            Radix::Decimal => "dec",
+            Radix::Signed => "signed",
+            Radix::Ascii => "ascii",
+        }
+    }
+}
+
+impl Display for Radix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Radix {
+    type Err = InvalidData;
+
+    fn from_str(s: &str) -> Result<Radix, InvalidData> {
+        Ok(match s {
+            "bin" | "binary" => Radix::Binary,
+            "oct" | "octal" => Radix::Octal,
+            "hex" => Radix::Hex,
+            "dec" | "decimal" | "unsigned" => Radix::Decimal,
+            "signed" => Radix::Signed,
+            "ascii" => Radix::Ascii,
+            _ => return Err(InvalidData("unknown radix")),
+        })
+    }
+}
+
+/// A raw value of a `width`-bit vector written in `radix`. Values that
+/// are not logic values at all are written as they are.
+pub fn format_bits(value: &[u8], width: u32, radix: Radix) -> String {
+    let Ok(raw) = LogicVec::from_vcd(value, width as usize) else {
+        return String::from_utf8_lossy(value).into_owned();
+    };
+    let bits = raw.to_four_state();
+    match radix {
+        Radix::Binary => String::from_utf8(raw.to_bytes()).expect("value characters are ASCII"),
+        Radix::Octal => digits(&bits, 3),
+        Radix::Hex => digits(&bits, 4),
+        Radix::Decimal | Radix::Signed => decimal(&bits, radix == Radix::Signed),
+        Radix::Ascii => ascii(&bits),
+    }
+}
+
+/// A raw value of `var` written in `radix`. Real and string values are
+/// written as they are.
+pub fn format_value(var: &Var, value: &[u8], radix: Radix) -> String {
+    if var.kind.is_real() || var.kind == VarKind::String {
+        return String::from_utf8_lossy(value).into_owned();
+    }
+    format_bits(value, var.width, radix)
+}
+
+/// Digits of `bits` bits each, most significant first.
+fn digits(v: &LogicVec, bits: usize) -> String {
+    let n = v.len().div_ceil(bits);
+    (0..n)
+        .rev()
+        .map(|d| {
+            let group: Vec<Logic> = (d * bits..v.len().min((d + 1) * bits))
+                .map(|i| v.get(i))
+                .collect();
+            let count = |b: Logic| group.iter().filter(|&&g| g == b).count();
+            match (count(Logic::X), count(Logic::Z)) {
+                (x, _) if x == group.len() => 'x',
+                (x, _) if x > 0 => 'X',
+                (_, z) if z == group.len() => 'z',
+                (_, z) if z > 0 => 'Z',
+                _ => {
+                    let n = group
+                        .iter()
+                        .rev()
+                        .fold(0, |n, &b| n << 1 | (b == Logic::One) as u32);
+                    char::from_digit(n, 16).expect("digit below 16")
+                }
+            }
+        })
+        .collect()
+}
+
+/// The value as a decimal, two's complement if `signed`.
+fn decimal(v: &LogicVec, signed: bool) -> String {
+    if v.is_empty() {
+        return "0".to_string();
+    }
+    if v.iter().all(|b| b == Logic::Z) {
+        return "z".to_string();
+    }
+    if v.iter().any(|b| !matches!(b, Logic::Zero | Logic::One)) {
+        return "x".to_string();
+    }
+    let mut bits: Vec<bool> = v.iter().map(|b| b == Logic::One).collect();
+    let negative = signed && bits[bits.len() - 1];
+    if negative {
+        // Negate: invert and add one.
+        let mut carry = true;
+        for b in &mut bits {
+            let sum = !*b as u8 + carry as u8;
+            *b = sum & 1 != 0;
+            carry = sum > 1;
+        }
+    }
+    // Base 10^9 limbs, least significant first.
+    const BASE: u64 = 1_000_000_000;
+    let mut limbs: Vec<u64> = vec![0];
+    for &b in bits.iter().rev() {
+        let mut carry = b as u64;
+        for limb in &mut limbs {
+            let n = *limb * 2 + carry;
+            *limb = n % BASE;
+            carry = n / BASE;
+        }
+        if carry > 0 {
+            limbs.push(carry);
+        }
+    }
+    let mut out = String::from(if negative { "-" } else { "" });
+    let mut limbs = limbs.iter().rev();
+    out.push_str(&limbs.next().expect("one limb").to_string());
+    for limb in limbs {
+        out.push_str(&format!("{:09}", limb));
+    }
+    out
+}
+
+/// The value as characters of 8 bits, most significant first.
+fn ascii(v: &LogicVec) -> String {
+    let n = v.len().div_ceil(8);
+    let mut out = String::new();
+    for c in (0..n).rev() {
+        let mut byte = 0u8;
+        let mut known = true;
+        for i in (c * 8..v.len().min((c + 1) * 8)).rev() {
+            match v.get(i) {
+                Logic::Zero => byte <<= 1,
+                Logic::One => byte = byte << 1 | 1,
+                _ => known = false,
+            }
+        }
+        out.push(match (known, byte) {
+            (false, _) => '?',
+            (true, 0) if out.is_empty() => continue,
+            (true, 0x20..=0x7e) => byte as char,
+            (true, _) => '.',
+        });
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn radices() {
+        let f = |value: &str, width: u32, radix: &str| {
+            format_bits(value.as_bytes(), width, radix.parse().unwrap())
+        };
+        assert_eq!(f("101", 8, "bin"), "00000101");
+        assert_eq!(f("z1", 4, "bin"), "zzz1");
+        assert_eq!(f("h0", 2, "bin"), "h0");
+        assert_eq!(f("101101", 8, "hex"), "2d");
+        assert_eq!(f("1101", 10, "hex"), "00d");
+        assert_eq!(f("x", 8, "hex"), "xx");
+        assert_eq!(f("1x0000", 6, "hex"), "X0");
+        assert_eq!(f("z0001", 5, "oct"), "Z1");
+        assert_eq!(f("zzzzzz", 6, "oct"), "zz");
+        assert_eq!(f("h1", 2, "oct"), "3");
+
+        assert_eq!(f("1111", 4, "dec"), "15");
+        assert_eq!(f("1111", 4, "signed"), "-1");
+        assert_eq!(f("0111", 4, "signed"), "7");
+        assert_eq!(f("1000", 4, "signed"), "-8");
+        assert_eq!(f("1x", 4, "dec"), "x");
+        assert_eq!(f("z", 4, "dec"), "z");
+        let wide = format!("1{}", "0".repeat(100));
+        assert_eq!(f(&wide, 101, "dec"), "1267650600228229401496703205376");
+        assert_eq!(f(&wide, 101, "signed"), "-1267650600228229401496703205376");
+
+        // "OK" in a 32-bit register, and a character with an `x`.
+        assert_eq!(f("0100111101001011", 32, "ascii"), "OK");
+        assert_eq!(f("1x0000000001", 16, "ascii"), "?.");
+
+        assert!("base64".parse::<Radix>().is_err());
+        assert_eq!(Radix::Signed.to_string().parse().ok(), Some(Radix::Signed));
+    }
+}
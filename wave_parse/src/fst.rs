@@ -0,0 +1,2342 @@
+//! FST, the compressed waveform format of GTKWave.
+//!
+//! An FST file is a sequence of blocks, each a type byte followed by its
+//! length as a big-endian `u64` that counts the length itself:
+//!
+//! * a header with the time range, counts and timescale,
+//! * value change blocks, each holding the values of all signals at its
+//!   start (the *frame*), a compressed chain of changes per signal and a
+//!   table of the times the changes refer to by index,
+//! * the geometry, the width of every signal,
+//! * the gzip-compressed hierarchy, and
+//! * the blackouts, the times at which dumping was switched off and on.
+//!
+//! Signals are numbered by *handles* starting at 1. Variables declared
+//! with the handle of an earlier variable alias its signal.
+//!
+//! The hierarchy may also hold [attributes](Attribute) of the scope or
+//! variable declared next, such as the source file and line declaring it,
+//! which [`FstFile::scope_attributes`] and [`FstFile::var_attributes`]
+//! return. [`FstFile::members`] turns struct, union and interface scopes
+//! into [member trees](crate::members) with the bits of each member.
+//!
+//! [`FstFile`] reads FST files and [`FstWriter`] writes them, in pure
+//! Rust: compression uses the crate's own DEFLATE, LZ4 and FastLZ code, so
+//! neither needs GTKWave's C library. [`write_fst`] serializes a complete
+//! [`Waveform`].
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::mem;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::deflate::{gzip, zlib_encode};
+use crate::fastlz::fastlz_decode;
+use crate::inflate::{gunzip, zlib_decode};
+use crate::lz4::lz4_decode;
+use crate::members::{self, Child, Member};
+use crate::mmap::{Data, Mmap};
+use crate::vcd::SimulationCommand;
+use crate::write::{replay, rescaler, Step, WriteOptions};
+use crate::{
+    Blackouts, EnumMap, EnumMaps, Hierarchy, InvalidData, Logic, ReferenceIndex, Scope, ScopeKind,
+    Signal, SignalId, SignalLoader, TimeUnit, Timescale, Var, VarKind, Waveform,
+};
+
+const HEADER: u8 = 0;
+const VALUE_CHANGES: u8 = 1;
+const BLACKOUT: u8 = 2;
+const GEOMETRY: u8 = 3;
+const HIERARCHY: u8 = 4;
+
+/// Length of the header block after its type byte.
+const HEADER_LENGTH: u64 = 329;
+/// Lets readers tell the byte order of reals, which is little-endian
+/// here.
+const ENDIAN_TEST: f64 = std::f64::consts::E;
+const VERSION_LENGTH: usize = 128;
+const DATE_LENGTH: usize = 119;
+
+const SCOPE: u8 = 254;
+const UPSCOPE: u8 = 255;
+
+/// Geometry of real signals, which are stored as 8-byte doubles.
+const GEOMETRY_REAL: u64 = 0;
+/// Geometry of variable-length (string) signals.
+const GEOMETRY_VARIABLE: u64 = 0xffff_ffff;
+
+/// One-bit values other than `0` and `1`, by their code in value chains.
+const SCALAR_STATES: &[u8; 8] = b"xzhuwl-?";
+
+/// Value change blocks are cut once their chains exceed this many bytes.
+const DEFAULT_BLOCK_SIZE: usize = 32 << 20;
+
+/// Metadata the hierarchy declares about the scope or variable that
+/// follows it.
+///
+/// Enum tables are not attributes here: they name the values of signals
+/// through [`FstFile::enum_maps`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Attribute {
+    Comment(String),
+    /// An environment variable of the simulation, usually `NAME=value`.
+    EnvVar(String),
+    /// The source type of a variable. The codes are the `FST_SVT_*`
+    /// variable types (VHDL signal, variable, constant...) and the
+    /// `FST_SDT_*` data types of GTKWave's `fstapi.h`; the data type
+    /// takes 10 bits.
+    SupplementalVar {
+        type_name: String,
+        var_type: u32,
+        data_type: u32,
+    },
+    /// Where the scope or variable is declared.
+    Source {
+        path: String,
+        line: u64,
+    },
+    /// Where the scope is instantiated.
+    Instance {
+        path: String,
+        line: u64,
+    },
+    /// Any other attribute, by its kind and subtype codes: arrays, enums,
+    /// packing, value lists.
+    Other {
+        kind: u8,
+        subtype: u8,
+        name: String,
+        arg: u64,
+    },
+}
+
+fn scope_code(kind: ScopeKind) -> u8 {
+    use ScopeKind::*;
+    match kind {
+        Module => 0,
+        Task => 1,
+        Function => 2,
+        Begin => 3,
+        Fork => 4,
+        Generate => 5,
+        Struct => 6,
+        Union => 7,
+        Class => 8,
+        Interface => 9,
+        Package => 10,
+        Program => 11,
+    }
+}
+
+fn var_code(kind: VarKind) -> u8 {
+    use VarKind::*;
+    match kind {
+        Event => 0,
+        Integer => 1,
+        Parameter => 2,
+        Real => 3,
+        Reg => 5,
+        Supply0 => 6,
+        Supply1 => 7,
+        Time => 8,
+        Tri => 9,
+        TriAnd => 10,
+        TriOr => 11,
+        TriReg => 12,
+        Tri0 => 13,
+        Tri1 => 14,
+        WAnd => 15,
+        Wire => 16,
+        WOr => 17,
+        Port => 18,
+        RealTime => 20,
+        String => 21,
+        Bit => 22,
+        Logic => 23,
+        Int => 24,
+        ShortInt => 25,
+        LongInt => 26,
+        Byte => 27,
+        Enum => 28,
+    }
+}
+
+/// The FST timescale exponent of `ts`, which must be a power of ten.
+fn exponent(ts: Timescale) -> Result<i8, InvalidData> {
+    let digits = match ts.factor {
+        1 => 0,
+        10 => 1,
+        100 => 2,
+        _ => return Err(InvalidData("FST timescales are powers of ten")),
+    };
+    let unit = match ts.unit {
+        TimeUnit::S => 0,
+        TimeUnit::MS => -3,
+        TimeUnit::US => -6,
+        TimeUnit::NS => -9,
+        TimeUnit::PS => -12,
+        TimeUnit::FS => -15,
+    };
+    Ok(unit + digits)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Geometry {
+    Bits(u32),
+    Real,
+    Variable,
+}
+
+impl Geometry {
+    fn new(kind: VarKind, width: u32) -> Geometry {
+        if kind.is_real() {
+            Geometry::Real
+        } else if kind == VarKind::String {
+            Geometry::Variable
+        } else {
+            Geometry::Bits(width.max(1))
+        }
+    }
+
+    /// Size of a value in the frame.
+    fn frame_len(self) -> usize {
+        match self {
+            Geometry::Bits(len) => len as usize,
+            Geometry::Real => 8,
+            Geometry::Variable => 0,
+        }
+    }
+}
+
+/// Write the changes of a `len`-bit value as exactly `len` characters:
+/// shorter values are extended like in VCD, with `x` or `z` if that is
+/// their leftmost bit and `0` otherwise.
+fn extend(value: &[u8], len: usize, out: &mut Vec<u8>) {
+    if value.len() >= len {
+        out.extend_from_slice(&value[value.len() - len..]);
+        return;
+    }
+    let fill = Logic::vcd_fill(value).to_char();
+    out.resize(out.len() + len - value.len(), fill);
+    out.extend_from_slice(value);
+}
+
+fn parse_real(value: &[u8]) -> Result<f64, InvalidData> {
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .ok_or(InvalidData("invalid real value"))
+}
+
+/// The pending changes of one signal in the current block.
+#[derive(Default)]
+struct Chain {
+    data: Vec<u8>,
+    /// Time index of the last change.
+    last: u64,
+}
+
+struct Handle {
+    geometry: Geometry,
+    /// The current value in frame encoding.
+    value: Vec<u8>,
+}
+
+/// Writes FST files.
+///
+/// The hierarchy is declared first, with [`scope_def`](FstWriter::scope_def),
+/// [`var_def`](FstWriter::var_def) and [`upscope`](FstWriter::upscope) or
+/// from a [`Hierarchy`] with [`hierarchy`](FstWriter::hierarchy), then
+/// changes follow at non-decreasing [timestamps](FstWriter::timestamp).
+/// Changes are buffered per signal and written as a value change block
+/// whenever [`set_block_size`](FstWriter::set_block_size) bytes are
+/// pending; the header, which counts everything, is written last by
+/// seeking back to the start, so [`finish`](FstWriter::finish) must be
+/// called to get a valid file.
+pub struct FstWriter<W: Write + Seek> {
+    out: W,
+    /// Position of the header in `out`, once space was reserved for it.
+    start: Option<u64>,
+    timescale: i8,
+    version: String,
+    date: String,
+    hierarchy: Vec<u8>,
+    scope_depth: usize,
+    scope_count: u64,
+    var_count: u64,
+    handles: Vec<Handle>,
+    ids: HashMap<SignalId, u32>,
+    first_time: Option<u64>,
+    /// The current time, and whether a change happened at it.
+    time: Option<(u64, bool)>,
+    /// Times of the current block.
+    times: Vec<u64>,
+    chains: Vec<Chain>,
+    /// The values at the start of the current block, in frame encoding.
+    frame: Vec<u8>,
+    pending: usize,
+    block_size: usize,
+    blocks: u64,
+    blackouts: Vec<(bool, u64)>,
+    enum_tables: u64,
+    /// The numbers of the paths named for source attributes.
+    paths: HashMap<String, u64>,
+}
+
+impl<W: Write + Seek> FstWriter<W> {
+    /// Creates a writer with a timescale of 1 ns.
+    pub fn new(out: W) -> FstWriter<W> {
+        FstWriter {
+            out,
+            start: None,
+            timescale: -9,
+            version: format!("wave_parse {}", env!("CARGO_PKG_VERSION")),
+            date: String::new(),
+            hierarchy: Vec::new(),
+            scope_depth: 0,
+            scope_count: 0,
+            var_count: 0,
+            handles: Vec::new(),
+            ids: HashMap::new(),
+            first_time: None,
+            time: None,
+            times: Vec::new(),
+            chains: Vec::new(),
+            frame: Vec::new(),
+            pending: 0,
+            block_size: DEFAULT_BLOCK_SIZE,
+            blocks: 0,
+            blackouts: Vec::new(),
+            enum_tables: 0,
+            paths: HashMap::new(),
+        }
+    }
+
+    /// Sets the timescale, which FST restricts to powers of ten.
+    pub fn set_timescale(&mut self, ts: Timescale) -> io::Result<()> {
+        self.timescale = exponent(ts)?;
+        Ok(())
+    }
+
+    /// Sets the writer string of the header, at most 127 bytes.
+    pub fn set_version(&mut self, version: &str) {
+        self.version = version.to_string();
+    }
+
+    /// Sets the date string of the header, at most 118 bytes.
+    pub fn set_date(&mut self, date: &str) {
+        self.date = date.to_string();
+    }
+
+    /// Start a new value change block once this many bytes of changes
+    /// are pending. Smaller blocks make loading a time window cheaper,
+    /// larger ones compress better.
+    pub fn set_block_size(&mut self, bytes: usize) {
+        self.block_size = bytes.max(1);
+    }
+
+    fn declarations_open(&self) -> io::Result<()> {
+        if self.time.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "FST hierarchy declared after the first change",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Opens a scope.
+    pub fn scope_def(&mut self, kind: ScopeKind, name: &str) -> io::Result<()> {
+        self.declarations_open()?;
+        self.scope_depth += 1;
+        self.scope_count += 1;
+        self.hierarchy.extend([SCOPE, scope_code(kind)]);
+        self.name(name);
+        // The component (module type) name, which is not known.
+        self.hierarchy.push(0);
+        Ok(())
+    }
+
+    /// Closes the innermost open scope.
+    pub fn upscope(&mut self) -> io::Result<()> {
+        if self.scope_depth == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "upscope without a matching scope",
+            ));
+        }
+        self.scope_depth -= 1;
+        self.hierarchy.push(UPSCOPE);
+        Ok(())
+    }
+
+    fn name(&mut self, name: &str) {
+        self.hierarchy
+            .extend(name.bytes().map(|b| if b == 0 { b'_' } else { b }));
+        self.hierarchy.push(0);
+    }
+
+    /// Declares a variable. Variables sharing a signal share its handle,
+    /// which takes the geometry of the first of them.
+    pub fn var_def(
+        &mut self,
+        kind: VarKind,
+        width: u32,
+        signal: SignalId,
+        name: &str,
+        index: Option<ReferenceIndex>,
+    ) -> io::Result<()> {
+        self.declarations_open()?;
+        let geometry = Geometry::new(kind, width);
+        let alias = match self.ids.get(&signal) {
+            Some(&handle) => handle,
+            None => {
+                self.handles.push(Handle {
+                    geometry,
+                    value: match geometry {
+                        Geometry::Real => f64::NAN.to_le_bytes().to_vec(),
+                        _ => vec![b'x'; geometry.frame_len()],
+                    },
+                });
+                self.ids.insert(signal, self.handles.len() as u32);
+                0
+            }
+        };
+        self.var_count += 1;
+        self.hierarchy.extend([var_code(kind), 0]);
+        match index {
+            Some(index) => self.name(&format!("{} {}", name, index)),
+            None => self.name(name),
+        }
+        let length = match (kind, geometry) {
+            (_, Geometry::Real) => 8,
+            // Ports store three digits for each bit plus two.
+            (VarKind::Port, _) => width as u64 * 3 + 2,
+            _ => width as u64,
+        };
+        crate::varint::write(&mut self.hierarchy, length);
+        crate::varint::write(&mut self.hierarchy, alias as u64);
+        Ok(())
+    }
+
+    fn attribute(&mut self, kind: u8, subtype: u8, name: &str, arg: u64) -> io::Result<()> {
+        self.declarations_open()?;
+        self.hierarchy.extend([ATTRIBUTE, kind, subtype]);
+        self.name(name);
+        crate::varint::write(&mut self.hierarchy, arg);
+        Ok(())
+    }
+
+    /// Declares an enum table, the names of the values of `width`-bit
+    /// variables of the enum type `name`, and returns its handle for
+    /// [`enum_ref`](FstWriter::enum_ref).
+    pub fn enum_table(&mut self, name: &str, width: u32, map: &EnumMap) -> io::Result<u64> {
+        let mut entries: Vec<(u64, &str)> = map.iter().collect();
+        entries.sort_unstable();
+        let mut text = String::new();
+        escape(name, &mut text);
+        text.push_str(&format!(" {}", entries.len()));
+        for (_, literal) in &entries {
+            text.push(' ');
+            escape(literal, &mut text);
+        }
+        for (value, _) in &entries {
+            text.push_str(&format!(" {:0width$b}", value, width = width as usize));
+        }
+        self.enum_tables += 1;
+        let handle = self.enum_tables;
+        self.attribute(ATTRIBUTE_MISC, MISC_ENUM_TABLE, &text, handle)?;
+        Ok(handle)
+    }
+
+    /// Names the values of the next declared variable with enum table
+    /// `table`.
+    pub fn enum_ref(&mut self, table: u64) -> io::Result<()> {
+        self.attribute(ATTRIBUTE_MISC, MISC_ENUM_TABLE, "", table)
+    }
+
+    /// Declares an attribute of the next declared scope or variable.
+    pub fn attribute_def(&mut self, attribute: &Attribute) -> io::Result<()> {
+        match attribute {
+            Attribute::Comment(text) => self.attribute(ATTRIBUTE_MISC, MISC_COMMENT, text, 0),
+            Attribute::EnvVar(text) => self.attribute(ATTRIBUTE_MISC, MISC_ENV_VAR, text, 0),
+            Attribute::SupplementalVar {
+                type_name,
+                var_type,
+                data_type,
+            } => {
+                let arg = (*var_type as u64) << 10 | (*data_type as u64 & 0x3ff);
+                self.attribute(ATTRIBUTE_MISC, MISC_SUPPLEMENTAL_VAR, type_name, arg)
+            }
+            Attribute::Source { path, line } => self.source(MISC_SOURCE_STEM, path, *line),
+            Attribute::Instance { path, line } => self.source(MISC_SOURCE_INSTANCE, path, *line),
+            Attribute::Other {
+                kind: ATTRIBUTE_MISC,
+                subtype: MISC_SOURCE_STEM | MISC_SOURCE_INSTANCE,
+                ..
+            } => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "FST source attributes need a path and line",
+            )),
+            Attribute::Other {
+                kind,
+                subtype,
+                name,
+                arg,
+            } => self.attribute(*kind, *subtype, name, *arg),
+        }
+    }
+
+    /// A source attribute, which names its path the first time it is
+    /// used and refers to it by number.
+    fn source(&mut self, subtype: u8, path: &str, line: u64) -> io::Result<()> {
+        self.declarations_open()?;
+        let id = match self.paths.get(path) {
+            Some(&id) => id,
+            None => {
+                let id = self.paths.len() as u64 + 1;
+                self.paths.insert(path.to_string(), id);
+                self.attribute(ATTRIBUTE_MISC, MISC_PATH_NAME, path, id)?;
+                id
+            }
+        };
+        self.hierarchy.extend([ATTRIBUTE, ATTRIBUTE_MISC, subtype]);
+        crate::varint::write(&mut self.hierarchy, id);
+        crate::varint::write(&mut self.hierarchy, line);
+        Ok(())
+    }
+
+    /// Declares a variable of `h`.
+    pub fn var(&mut self, h: &Hierarchy, v: &Var) -> io::Result<()> {
+        self.var_def(v.kind, v.width, v.signal, h.name(v.name), v.index)
+    }
+
+    /// Declares a scope of `h` with everything declared inside it.
+    pub fn scope(&mut self, h: &Hierarchy, s: &Scope) -> io::Result<()> {
+        self.scope_def(s.kind, h.name(s.name))?;
+        for v in &s.vars {
+            self.var(h, v)?;
+        }
+        for child in &s.scopes {
+            self.scope(h, child)?;
+        }
+        self.upscope()
+    }
+
+    /// Declares all scopes and variables of a hierarchy.
+    pub fn hierarchy(&mut self, h: &Hierarchy) -> io::Result<()> {
+        for v in &h.vars {
+            self.var(h, v)?;
+        }
+        for s in &h.scopes {
+            self.scope(h, s)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the time of the following changes.
+    pub fn timestamp(&mut self, time: u64) -> io::Result<()> {
+        match self.time {
+            Some((current, _)) if time < current => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "FST timestamps must not decrease",
+                ))
+            }
+            Some((current, _)) if time == current => return Ok(()),
+            None => {
+                if self.scope_depth > 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "FST scopes must be closed before the first change",
+                    ));
+                }
+                self.chains = (0..self.handles.len()).map(|_| Chain::default()).collect();
+                self.frame = self.frame_values();
+            }
+            _ => {}
+        }
+        if self.pending >= self.block_size {
+            self.flush_block()?;
+        }
+        self.time = Some((time, false));
+        Ok(())
+    }
+
+    /// Writes a change of a declared signal, in the raw value encoding
+    /// used by [`Signal`].
+    pub fn change(&mut self, signal: SignalId, value: &[u8]) -> io::Result<()> {
+        let &handle = self.ids.get(&signal).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "change of undeclared signal")
+        })?;
+        let (time, changed) = self.time.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "change before the first timestamp",
+            )
+        })?;
+        if !changed {
+            self.times.push(time);
+            self.first_time.get_or_insert(time);
+            self.time = Some((time, true));
+        }
+        let index = self.times.len() as u64 - 1;
+        let h = &mut self.handles[handle as usize - 1];
+        let chain = &mut self.chains[handle as usize - 1];
+        let delta = index - chain.last;
+        chain.last = index;
+        let before = chain.data.len();
+        let out = &mut chain.data;
+        match h.geometry {
+            Geometry::Bits(1) => {
+                let bit = value.last().copied().unwrap_or(b'x');
+                match bit {
+                    b'0' | b'1' => {
+                        crate::varint::write(out, delta << 2 | ((bit - b'0') as u64) << 1)
+                    }
+                    _ => {
+                        let state = SCALAR_STATES
+                            .iter()
+                            .position(|&s| s == bit.to_ascii_lowercase())
+                            .unwrap_or(0);
+                        crate::varint::write(out, delta << 4 | (state as u64) << 1 | 1);
+                    }
+                }
+                h.value.clear();
+                h.value.push(bit.to_ascii_lowercase());
+            }
+            Geometry::Bits(len) => {
+                h.value.clear();
+                extend(value, len as usize, &mut h.value);
+                if h.value.iter().all(|&b| b == b'0' || b == b'1') {
+                    crate::varint::write(out, delta << 1);
+                    for byte in h.value.chunks(8) {
+                        let bits = byte
+                            .iter()
+                            .enumerate()
+                            .fold(0u8, |acc, (i, &b)| acc | (b - b'0') << (7 - i));
+                        out.push(bits);
+                    }
+                } else {
+                    crate::varint::write(out, delta << 1 | 1);
+                    out.extend_from_slice(&h.value);
+                }
+            }
+            Geometry::Real => {
+                // Stored raw, as fstapi does.
+                let v = parse_real(value)?.to_le_bytes();
+                crate::varint::write(out, delta << 1 | 1);
+                out.extend_from_slice(&v);
+                h.value.clear();
+                h.value.extend_from_slice(&v);
+            }
+            Geometry::Variable => {
+                crate::varint::write(out, delta);
+                crate::varint::write(out, value.len() as u64);
+                out.extend_from_slice(value);
+            }
+        }
+        self.pending += chain.data.len() - before;
+        Ok(())
+    }
+
+    /// Stops dumping at the current time, starting a blackout.
+    pub fn dumpoff(&mut self) -> io::Result<()> {
+        self.dump(false)
+    }
+
+    /// Resumes dumping at the current time, ending a blackout.
+    pub fn dumpon(&mut self) -> io::Result<()> {
+        self.dump(true)
+    }
+
+    fn dump(&mut self, active: bool) -> io::Result<()> {
+        let (time, _) = self.time.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "dump command before the first timestamp",
+            )
+        })?;
+        self.blackouts.push((active, time));
+        Ok(())
+    }
+
+    fn frame_values(&self) -> Vec<u8> {
+        self.handles
+            .iter()
+            .flat_map(|h| h.value.iter().copied())
+            .collect()
+    }
+
+    fn reserve_header(&mut self) -> io::Result<()> {
+        if self.start.is_none() {
+            self.start = Some(self.out.stream_position()?);
+            self.out.write_all(&[0; 1 + HEADER_LENGTH as usize])?;
+        }
+        Ok(())
+    }
+
+    fn flush_block(&mut self) -> io::Result<()> {
+        if self.times.is_empty() {
+            return Ok(());
+        }
+        self.reserve_header()?;
+        let mut block = Vec::new();
+        block.extend(self.times[0].to_be_bytes());
+        block.extend(self.times[self.times.len() - 1].to_be_bytes());
+        // Memory a reader needs to expand the block.
+        let memory = self.frame.len() + self.pending + self.times.len() * 8;
+        block.extend((memory as u64).to_be_bytes());
+
+        let frame = pack(&self.frame, zlib_encode);
+        crate::varint::write(&mut block, self.frame.len() as u64);
+        crate::varint::write(&mut block, frame.len() as u64);
+        crate::varint::write(&mut block, self.handles.len() as u64);
+        block.extend_from_slice(&frame);
+
+        crate::varint::write(&mut block, self.handles.len() as u64);
+        let origin = block.len();
+        block.push(b'Z');
+        let mut offsets = Vec::with_capacity(self.chains.len());
+        for chain in &mut self.chains {
+            if chain.data.is_empty() {
+                offsets.push(None);
+                continue;
+            }
+            offsets.push(Some((block.len() - origin) as u64));
+            let packed = zlib_encode(&chain.data);
+            if packed.len() < chain.data.len() {
+                crate::varint::write(&mut block, chain.data.len() as u64);
+                block.extend_from_slice(&packed);
+            } else {
+                block.push(0);
+                block.extend_from_slice(&chain.data);
+            }
+            *chain = Chain::default();
+        }
+
+        let table_start = block.len();
+        let (mut previous, mut zeros) = (0, 0u64);
+        for offset in offsets {
+            match offset {
+                Some(offset) => {
+                    if zeros > 0 {
+                        crate::varint::write(&mut block, zeros << 1);
+                        zeros = 0;
+                    }
+                    crate::varint::write(&mut block, (offset - previous) << 1 | 1);
+                    previous = offset;
+                }
+                None => zeros += 1,
+            }
+        }
+        if zeros > 0 {
+            crate::varint::write(&mut block, zeros << 1);
+        }
+        block.extend(((block.len() - table_start) as u64).to_be_bytes());
+
+        let mut times = Vec::new();
+        let mut previous = 0;
+        for &t in &self.times {
+            crate::varint::write(&mut times, t - previous);
+            previous = t;
+        }
+        let packed = pack(&times, zlib_encode);
+        block.extend_from_slice(&packed);
+        block.extend((times.len() as u64).to_be_bytes());
+        block.extend((packed.len() as u64).to_be_bytes());
+        block.extend((self.times.len() as u64).to_be_bytes());
+
+        write_block(&mut self.out, VALUE_CHANGES, &block)?;
+        self.blocks += 1;
+        self.times.clear();
+        self.pending = 0;
+        self.frame = self.frame_values();
+        Ok(())
+    }
+
+    /// Writes the remaining blocks and the header, and returns the
+    /// underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.scope_depth > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "FST scopes must be closed before finishing",
+            ));
+        }
+        self.flush_block()?;
+        self.reserve_header()?;
+
+        let mut geometry = Vec::new();
+        for h in &self.handles {
+            let g = match h.geometry {
+                Geometry::Bits(len) => len as u64,
+                Geometry::Real => GEOMETRY_REAL,
+                Geometry::Variable => GEOMETRY_VARIABLE,
+            };
+            crate::varint::write(&mut geometry, g);
+        }
+        let mut block = Vec::new();
+        block.extend((geometry.len() as u64).to_be_bytes());
+        block.extend((self.handles.len() as u64).to_be_bytes());
+        block.extend(pack(&geometry, zlib_encode));
+        write_block(&mut self.out, GEOMETRY, &block)?;
+
+        let mut block = Vec::new();
+        block.extend((self.hierarchy.len() as u64).to_be_bytes());
+        block.extend(gzip(&self.hierarchy));
+        write_block(&mut self.out, HIERARCHY, &block)?;
+
+        if !self.blackouts.is_empty() {
+            let mut block = Vec::new();
+            crate::varint::write(&mut block, self.blackouts.len() as u64);
+            let mut previous = 0;
+            for &(active, time) in &self.blackouts {
+                block.push(active as u8);
+                crate::varint::write(&mut block, time - previous);
+                previous = time;
+            }
+            write_block(&mut self.out, BLACKOUT, &block)?;
+        }
+
+        let end = self.out.stream_position()?;
+        let last = self.time.map_or(0, |(t, _)| t);
+        let mut header = Vec::with_capacity(HEADER_LENGTH as usize);
+        header.extend(self.first_time.unwrap_or(0).to_be_bytes());
+        header.extend(last.to_be_bytes());
+        header.extend(ENDIAN_TEST.to_le_bytes());
+        header.extend(0u64.to_be_bytes());
+        header.extend(self.scope_count.to_be_bytes());
+        header.extend(self.var_count.to_be_bytes());
+        header.extend((self.handles.len() as u64).to_be_bytes());
+        header.extend(self.blocks.to_be_bytes());
+        header.push(self.timescale as u8);
+        fixed_string(&mut header, &self.version, VERSION_LENGTH);
+        fixed_string(&mut header, &self.date, DATE_LENGTH);
+        // File type: Verilog.
+        header.push(0);
+        // Time zero.
+        header.extend(0u64.to_be_bytes());
+        self.out
+            .seek(SeekFrom::Start(self.start.expect("reserved")))?;
+        write_block(&mut self.out, HEADER, &header)?;
+        self.out.seek(SeekFrom::Start(end))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// `data` compressed with `compress`, or as is if that is not smaller.
+fn pack(data: &[u8], compress: fn(&[u8]) -> Vec<u8>) -> Vec<u8> {
+    let packed = compress(data);
+    if packed.len() < data.len() {
+        packed
+    } else {
+        data.to_vec()
+    }
+}
+
+fn fixed_string(out: &mut Vec<u8>, s: &str, len: usize) {
+    let bytes = &s.as_bytes()[..s.len().min(len - 1)];
+    out.extend_from_slice(bytes);
+    out.resize(out.len() + len - bytes.len(), 0);
+}
+
+fn write_block<W: Write>(out: &mut W, kind: u8, payload: &[u8]) -> io::Result<()> {
+    out.write_all(&[kind])?;
+    out.write_all(&(payload.len() as u64 + 8).to_be_bytes())?;
+    out.write_all(payload)
+}
+
+/// Serialize a complete waveform as FST.
+///
+/// The timescale, date and version of `options` are honored like by
+/// [`write_waveform`](crate::write_waveform); FST has no identifier
+/// codes, so `compact_ids` does not apply. Timescales that are not a
+/// power of ten are rejected.
+pub fn write_fst<W, F>(out: W, wave: &mut F, options: &WriteOptions) -> io::Result<W>
+where
+    W: Write + Seek,
+    F: Waveform + ?Sized,
+{
+    let source = wave.timescale();
+    let mut w = FstWriter::new(out);
+    if let Some(ts) = options.timescale.or(source) {
+        w.set_timescale(ts)?;
+    }
+    if let Some(date) = options.date.as_deref().or(wave.date()) {
+        w.set_date(date);
+    }
+    if let Some(version) = &options.version {
+        w.set_version(version);
+    }
+    w.hierarchy(wave.hierarchy())?;
+    replay(
+        wave,
+        rescaler(source, options.timescale),
+        |step| match step {
+            Step::Time(t) => w.timestamp(t),
+            Step::Change(id, value) => w.change(id, value),
+            Step::Dump(SimulationCommand::Dumpoff) => w.dumpoff(),
+            Step::Dump(_) => w.dumpon(),
+        },
+    )?;
+    w.finish()
+}
+
+const VALUE_CHANGES_ALIAS: u8 = 5;
+const HIERARCHY_LZ4: u8 = 6;
+const HIERARCHY_LZ4_TWICE: u8 = 7;
+const VALUE_CHANGES_ALIAS2: u8 = 8;
+/// A whole file compressed with gzip.
+const WRAPPED: u8 = 254;
+const SKIP: u8 = 255;
+
+const ATTRIBUTE: u8 = 252;
+const ATTRIBUTE_END: u8 = 253;
+const ATTRIBUTE_MISC: u8 = 0;
+const ATTRIBUTE_PACK: u8 = 3;
+const PACK_UNPACKED: u8 = 2;
+const MISC_COMMENT: u8 = 0;
+const MISC_ENV_VAR: u8 = 1;
+const MISC_SUPPLEMENTAL_VAR: u8 = 2;
+/// Names a path for source attributes, which refer to it by number.
+const MISC_PATH_NAME: u8 = 3;
+const MISC_SOURCE_STEM: u8 = 4;
+const MISC_SOURCE_INSTANCE: u8 = 5;
+const MISC_ENUM_TABLE: u8 = 7;
+
+const TRUNCATED: InvalidData = InvalidData("truncated FST file");
+
+fn scope_kind(code: u8) -> ScopeKind {
+    use ScopeKind::*;
+    match code {
+        1 | 13 => Task,
+        2 | 14 => Function,
+        3 | 16 | 17 => Begin,
+        4 => Fork,
+        5 | 18..=20 => Generate,
+        6 | 15 => Struct,
+        7 => Union,
+        8 => Class,
+        9 => Interface,
+        10 | 21 => Package,
+        11 => Program,
+        // Modules and VHDL architectures.
+        _ => Module,
+    }
+}
+
+fn var_kind(code: u8) -> Option<VarKind> {
+    use VarKind::*;
+    Some(match code {
+        0 => Event,
+        1 => Integer,
+        2 => Parameter,
+        3 | 4 | 29 => Real,
+        5 => Reg,
+        6 => Supply0,
+        7 => Supply1,
+        8 => Time,
+        9 => Tri,
+        10 => TriAnd,
+        11 => TriOr,
+        12 => TriReg,
+        13 => Tri0,
+        14 => Tri1,
+        15 => WAnd,
+        16 | 19 => Wire,
+        17 => WOr,
+        18 => Port,
+        20 => RealTime,
+        21 => String,
+        22 => Bit,
+        23 => Logic,
+        24 => Int,
+        25 => ShortInt,
+        26 => LongInt,
+        27 => Byte,
+        28 => Enum,
+        _ => return None,
+    })
+}
+
+/// The timescale of an FST exponent, e.g. `10 ns` for -8.
+pub(crate) fn timescale(exponent: i8) -> Result<Timescale, InvalidData> {
+    let e = exponent as i32;
+    if !(-15..=9).contains(&e) {
+        return Err(InvalidData("unsupported FST timescale"));
+    }
+    let unit = (e.div_euclid(3) * 3).min(0);
+    let factor = 10u32.pow((e - unit) as u32);
+    let unit = match unit {
+        0 => TimeUnit::S,
+        -3 => TimeUnit::MS,
+        -6 => TimeUnit::US,
+        -9 => TimeUnit::NS,
+        -12 => TimeUnit::PS,
+        _ => TimeUnit::FS,
+    };
+    Ok(Timescale::new(factor, unit))
+}
+
+/// A cursor over the bytes of a block.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data, pos: 0 }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], InvalidData> {
+        let end = self.pos.checked_add(n).ok_or(TRUNCATED)?;
+        let bytes = self.data.get(self.pos..end).ok_or(TRUNCATED)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, InvalidData> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64, InvalidData> {
+        Ok(u64::from_be_bytes(
+            self.bytes(8)?.try_into().expect("8 bytes"),
+        ))
+    }
+
+    fn varint(&mut self) -> Result<u64, InvalidData> {
+        crate::varint::read(self.data, &mut self.pos).ok_or(TRUNCATED)
+    }
+
+    /// A signed LEB128 varint.
+    fn svarint(&mut self) -> Result<i64, InvalidData> {
+        let (mut v, mut shift) = (0i64, 0);
+        loop {
+            let b = self.u8()?;
+            v |= ((b & 0x7f) as i64) << shift;
+            shift += 7;
+            if b < 0x80 {
+                if shift < 64 && b & 0x40 != 0 {
+                    v |= -1 << shift;
+                }
+                return Ok(v);
+            }
+            if shift >= 64 {
+                return Err(InvalidData("FST varint too long"));
+            }
+        }
+    }
+
+    /// A string ending in a NUL byte.
+    fn string(&mut self) -> Result<String, InvalidData> {
+        let rest = &self.data[self.pos.min(self.data.len())..];
+        let len = rest.iter().position(|&b| b == 0).ok_or(TRUNCATED)?;
+        self.pos += len + 1;
+        Ok(String::from_utf8_lossy(&rest[..len]).into_owned())
+    }
+
+    /// A NUL-padded string of `len` bytes.
+    fn fixed_string(&mut self, len: usize) -> Result<String, InvalidData> {
+        let bytes = self.bytes(len)?;
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(len);
+        Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    }
+}
+
+/// `data` holding `len` bytes, zlib-compressed unless it has that many.
+fn unpack_zlib(data: &[u8], len: usize) -> Result<Cow<'_, [u8]>, InvalidData> {
+    if data.len() == len {
+        Ok(Cow::Borrowed(data))
+    } else {
+        Ok(Cow::Owned(sized(zlib_decode(data, len)?, len)?))
+    }
+}
+
+/// `out` if it holds the `size` bytes its block declares.
+fn sized(out: Vec<u8>, size: usize) -> Result<Vec<u8>, InvalidData> {
+    match out.len() == size {
+        true => Ok(out),
+        false => Err(InvalidData("FST block does not decode to its size")),
+    }
+}
+
+/// Split a bit range off a variable name, as in `data [7:0]`.
+fn split_index(name: &str) -> (&str, Option<ReferenceIndex>) {
+    if let Some((base, index)) = name.rsplit_once(" [") {
+        if let Ok(index) = format!("[{}", index).parse() {
+            return (base, Some(index));
+        }
+    }
+    (name, None)
+}
+
+/// Undo the escaping of enum literals: C escapes and octal codes for
+/// bytes that are not printable, such as the spaces separating literals.
+fn unescape(s: &str) -> String {
+    let s = s.as_bytes();
+    let mut out = Vec::with_capacity(s.len());
+    let mut i = 0;
+    while i < s.len() {
+        let b = s[i];
+        i += 1;
+        if b != b'\\' || i == s.len() {
+            out.push(b);
+            continue;
+        }
+        // The digits at `at`, as a number.
+        let number = |at: usize, radix: u32, max: usize| {
+            let n = s[at..]
+                .iter()
+                .take(max)
+                .take_while(|c| (**c as char).is_digit(radix))
+                .count();
+            let text = std::str::from_utf8(&s[at..at + n]).expect("digits");
+            (u8::from_str_radix(text, radix).ok(), n)
+        };
+        let (byte, len) = match s[i] {
+            b'a' => (7, 1),
+            b'b' => (8, 1),
+            b'f' => (12, 1),
+            b'n' => (b'\n', 1),
+            b'r' => (b'\r', 1),
+            b't' => (b'\t', 1),
+            b'v' => (11, 1),
+            b'0'..=b'7' => {
+                let (v, n) = number(i, 8, 3);
+                (v.unwrap_or(b'?'), n)
+            }
+            b'x' => match number(i + 1, 16, 2) {
+                (Some(v), n) => (v, n + 1),
+                _ => (b'x', 1),
+            },
+            c => (c, 1),
+        };
+        out.push(byte);
+        i += len;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn escape(s: &str, out: &mut String) {
+    for b in s.bytes() {
+        match b {
+            b'\\' => out.push_str("\\\\"),
+            b'!'..=b'~' => out.push(b as char),
+            _ => out.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+}
+
+/// An enum table attribute: the type name, the number of literals, the
+/// literals and then their values as binary numbers, all separated by
+/// spaces.
+fn parse_enum_table(text: &str) -> Option<EnumMap> {
+    let mut words = text.split(' ');
+    let _type_name = words.next()?;
+    let count: usize = words.next()?.parse().ok()?;
+    let words: Vec<&str> = words.collect();
+    if words.len() < 2 * count {
+        return None;
+    }
+    let mut map = EnumMap::new();
+    for (name, value) in words[..count].iter().zip(&words[count..2 * count]) {
+        if let Ok(value) = u64::from_str_radix(value, 2) {
+            map.insert(value, &unescape(name));
+        }
+    }
+    Some(map)
+}
+
+/// Everything the hierarchy block declares.
+#[derive(Default)]
+struct Declarations {
+    hierarchy: Hierarchy,
+    enums: EnumMaps,
+    scope_attributes: HashMap<String, Vec<Attribute>>,
+    var_attributes: HashMap<String, Vec<Attribute>>,
+    /// The declarations inside structs, unions and interfaces, in order.
+    aggregates: HashMap<String, Vec<Child>>,
+}
+
+fn read_hierarchy(data: &[u8]) -> Result<Declarations, InvalidData> {
+    let mut hierarchy = Hierarchy::default();
+    let mut open: Vec<Scope> = Vec::new();
+    let mut r = Reader::new(data);
+    let mut handles = 0;
+    let mut tables = HashMap::new();
+    // The enum table of the next variable, and those of earlier ones.
+    let mut next_enum = None;
+    let mut enum_refs = Vec::new();
+    // The attributes of the next declaration, and the paths source
+    // attributes refer to.
+    let mut pending = Vec::new();
+    let mut paths = HashMap::new();
+    let mut scope_attributes = HashMap::new();
+    let mut var_attributes: HashMap<String, Vec<Attribute>> = HashMap::new();
+    let mut aggregates: HashMap<String, Vec<Child>> = HashMap::new();
+    while !r.at_end() {
+        match r.u8()? {
+            SCOPE => {
+                let kind = scope_kind(r.u8()?);
+                let name = r.string()?;
+                let _component = r.string()?;
+                if let Some(parent) = aggregates.get_mut(&path(&hierarchy, &open, None)) {
+                    parent.push(Child::Scope);
+                }
+                open.push(Scope::new(kind, hierarchy.intern(&name)));
+                if matches!(
+                    kind,
+                    ScopeKind::Struct | ScopeKind::Union | ScopeKind::Interface
+                ) {
+                    aggregates.insert(path(&hierarchy, &open, None), Vec::new());
+                }
+                if !pending.is_empty() {
+                    scope_attributes.insert(path(&hierarchy, &open, None), mem::take(&mut pending));
+                }
+            }
+            UPSCOPE => close_scope(&mut hierarchy, &mut open),
+            ATTRIBUTE => {
+                let (kind, subtype) = (r.u8()?, r.u8()?);
+                // Source locations refer to a path by number instead of
+                // naming it.
+                if kind == ATTRIBUTE_MISC
+                    && matches!(subtype, MISC_SOURCE_STEM | MISC_SOURCE_INSTANCE)
+                {
+                    let path = r.varint()?;
+                    let line = r.varint()?;
+                    let path = paths.get(&path).cloned().unwrap_or_default();
+                    pending.push(match subtype {
+                        MISC_SOURCE_STEM => Attribute::Source { path, line },
+                        _ => Attribute::Instance { path, line },
+                    });
+                    continue;
+                }
+                let name = r.string()?;
+                let arg = r.varint()?;
+                match (kind, subtype) {
+                    // A table has a definition as its name, a reference
+                    // to it before a variable none.
+                    (ATTRIBUTE_MISC, MISC_ENUM_TABLE) => {
+                        if name.is_empty() {
+                            next_enum = Some(arg);
+                        } else if let Some(map) = parse_enum_table(&name) {
+                            tables.insert(arg, map);
+                        }
+                    }
+                    (ATTRIBUTE_MISC, MISC_PATH_NAME) => {
+                        paths.insert(arg, name);
+                    }
+                    (ATTRIBUTE_MISC, MISC_COMMENT) => pending.push(Attribute::Comment(name)),
+                    (ATTRIBUTE_MISC, MISC_ENV_VAR) => pending.push(Attribute::EnvVar(name)),
+                    (ATTRIBUTE_MISC, MISC_SUPPLEMENTAL_VAR) => {
+                        pending.push(Attribute::SupplementalVar {
+                            type_name: name,
+                            var_type: (arg >> 10) as u32,
+                            data_type: (arg & 0x3ff) as u32,
+                        })
+                    }
+                    _ => pending.push(Attribute::Other {
+                        kind,
+                        subtype,
+                        name,
+                        arg,
+                    }),
+                }
+            }
+            ATTRIBUTE_END => {}
+            code => {
+                let kind = var_kind(code).ok_or(InvalidData("invalid FST hierarchy entry"))?;
+                let _direction = r.u8()?;
+                let name = r.string()?;
+                let length = r.varint()?;
+                let handle = match r.varint()? {
+                    0 => {
+                        handles += 1;
+                        handles
+                    }
+                    alias => alias,
+                };
+                let width = match kind {
+                    _ if kind.is_real() => 64,
+                    VarKind::Port => length.saturating_sub(2) / 3,
+                    _ => length,
+                };
+                let width =
+                    u32::try_from(width).map_err(|_| InvalidData("FST variable too wide"))?;
+                let (name, index) = split_index(&name);
+                if let Some(parent) = aggregates.get_mut(&path(&hierarchy, &open, None)) {
+                    parent.push(Child::Var);
+                }
+                if !pending.is_empty() {
+                    var_attributes
+                        .entry(path(&hierarchy, &open, Some(name)))
+                        .or_default()
+                        .append(&mut pending);
+                }
+                let var = Var {
+                    kind,
+                    width,
+                    signal: SignalId(handle - 1),
+                    name: hierarchy.intern(name),
+                    index,
+                };
+                match open.last_mut() {
+                    Some(scope) => scope.vars.push(var),
+                    None => hierarchy.vars.push(var),
+                }
+                if let Some(table) = next_enum.take() {
+                    enum_refs.push((SignalId(handle - 1), table));
+                }
+            }
+        }
+    }
+    while !open.is_empty() {
+        close_scope(&mut hierarchy, &mut open);
+    }
+    let mut enums = EnumMaps::new();
+    for (signal, table) in enum_refs {
+        if let Some(map) = tables.get(&table) {
+            enums.insert(signal, map.clone());
+        }
+    }
+    Ok(Declarations {
+        hierarchy,
+        enums,
+        scope_attributes,
+        var_attributes,
+        aggregates,
+    })
+}
+
+/// The dotted path of the open scopes, followed by `name` if given.
+fn path(hierarchy: &Hierarchy, open: &[Scope], name: Option<&str>) -> String {
+    let names: Vec<&str> = (open.iter().map(|s| hierarchy.name(s.name)))
+        .chain(name)
+        .collect();
+    names.join(".")
+}
+
+/// Close the innermost open scope into its parent.
+fn close_scope(hierarchy: &mut Hierarchy, open: &mut Vec<Scope>) {
+    if let Some(scope) = open.pop() {
+        match open.last_mut() {
+            Some(parent) => parent.scopes.push(scope),
+            None => hierarchy.scopes.push(scope),
+        }
+    }
+}
+
+/// Where the chain of a signal is in a value change block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Link {
+    None,
+    Offset(usize),
+    /// The chain of another handle, by index.
+    Alias(usize),
+}
+
+/// The chains of a value change block, as ranges relative to the byte
+/// before the first chain, from the chain table at `table` there.
+fn chain_table(
+    kind: u8,
+    table: &[u8],
+    table_offset: usize,
+) -> Result<Vec<Option<Range<usize>>>, InvalidData> {
+    let mut links = Vec::new();
+    let mut r = Reader::new(table);
+    let (mut previous, mut alias) = (0usize, 0usize);
+    while !r.at_end() {
+        if kind == VALUE_CHANGES_ALIAS2 && table[r.pos] & 1 == 1 {
+            let shifted = r.svarint()? >> 1;
+            match shifted {
+                0 => links.push(Link::Alias(alias)),
+                delta if delta > 0 => {
+                    previous += delta as usize;
+                    links.push(Link::Offset(previous));
+                }
+                negative => {
+                    alias = (-negative) as usize - 1;
+                    links.push(Link::Alias(alias));
+                }
+            }
+            continue;
+        }
+        let v = r.varint()?;
+        if v == 0 {
+            let handle = r.varint()? as usize;
+            links.push(Link::Alias(handle.checked_sub(1).ok_or(CORRUPT_CHAINS)?));
+        } else if v & 1 == 1 {
+            previous += (v >> 1) as usize;
+            links.push(Link::Offset(previous));
+        } else {
+            links.extend(std::iter::repeat_n(Link::None, (v >> 1) as usize));
+        }
+    }
+
+    let mut ranges = vec![None; links.len()];
+    let mut last: Option<usize> = None;
+    for (i, link) in links.iter().enumerate() {
+        if let Link::Offset(offset) = *link {
+            if let Some(prev) = last {
+                let start = ranges[prev].as_ref().map_or(0, |r: &Range<usize>| r.start);
+                ranges[prev] = Some(start..offset);
+            }
+            ranges[i] = Some(offset..offset);
+            last = Some(i);
+        }
+    }
+    if let Some(prev) = last {
+        let start = ranges[prev].as_ref().map_or(0, |r| r.start);
+        if start > table_offset {
+            return Err(CORRUPT_CHAINS);
+        }
+        ranges[prev] = Some(start..table_offset);
+    }
+    for (i, link) in links.iter().enumerate() {
+        if let Link::Alias(to) = *link {
+            ranges[i] = ranges.get(to).cloned().flatten();
+        }
+    }
+    Ok(ranges)
+}
+
+const CORRUPT_CHAINS: InvalidData = InvalidData("corrupt FST chain table");
+
+/// An FST file held in memory, usually through a memory map.
+///
+/// Opening reads the header, hierarchy, geometry and blackouts; loading
+/// signals decodes the value change blocks, decompressing only the
+/// chains of the requested signals, whether compressed with zlib, LZ4
+/// or FastLZ. The signal of the variable with handle `n` is
+/// `SignalId(n - 1)`.
+pub struct FstFile {
+    data: Data,
+    hierarchy: Hierarchy,
+    enums: EnumMaps,
+    scope_attributes: HashMap<String, Vec<Attribute>>,
+    var_attributes: HashMap<String, Vec<Attribute>>,
+    aggregates: HashMap<String, Vec<Child>>,
+    timescale: Timescale,
+    version: String,
+    date: String,
+    time_range: Range<u64>,
+    time_zero: i64,
+    big_endian: bool,
+    geometry: Vec<Geometry>,
+    blocks: Vec<(u8, Range<usize>)>,
+    blackouts: Blackouts,
+}
+
+impl FstFile {
+    /// Map a file and read everything but its value changes.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<FstFile> {
+        let file = File::open(path)?;
+        FstFile::new(Data::Mapped(Mmap::open(&file)?))
+    }
+
+    /// Read an FST file already in memory.
+    pub fn from_bytes(data: Vec<u8>) -> io::Result<FstFile> {
+        FstFile::new(Data::Owned(data))
+    }
+
+    fn new(data: Data) -> io::Result<FstFile> {
+        let bytes = data.as_slice();
+        if bytes.first() == Some(&WRAPPED) {
+            let mut r = Reader::new(&bytes[1..]);
+            let len = r.u64()?.saturating_sub(16) as usize;
+            let size = r.u64()? as usize;
+            let inner = sized(gunzip(r.bytes(len)?, size)?, size)?;
+            return FstFile::new(Data::Owned(inner));
+        }
+        let mut r = Reader::new(bytes);
+        if r.u8()? != HEADER || r.u64()? < HEADER_LENGTH {
+            return Err(InvalidData("not an FST file").into());
+        }
+        let time_range = r.u64()?..r.u64()?;
+        let endian = r.bytes(8)?;
+        let big_endian = if f64::from_le_bytes(endian.try_into().expect("8 bytes")) == ENDIAN_TEST {
+            false
+        } else if f64::from_be_bytes(endian.try_into().expect("8 bytes")) == ENDIAN_TEST {
+            true
+        } else {
+            return Err(InvalidData("invalid FST byte order").into());
+        };
+        let _memory = r.u64()?;
+        let _scopes = r.u64()?;
+        let _vars = r.u64()?;
+        let _handles = r.u64()?;
+        let _blocks = r.u64()?;
+        let timescale = timescale(r.u8()? as i8)?;
+        let version = r.fixed_string(VERSION_LENGTH)?;
+        let date = r.fixed_string(DATE_LENGTH)?;
+        let _file_type = r.u8()?;
+        let time_zero = r.u64()? as i64;
+
+        let mut fst = FstFile {
+            data: Data::Owned(Vec::new()),
+            hierarchy: Hierarchy::default(),
+            enums: EnumMaps::new(),
+            scope_attributes: HashMap::new(),
+            var_attributes: HashMap::new(),
+            aggregates: HashMap::new(),
+            timescale,
+            version,
+            date,
+            time_range,
+            time_zero,
+            big_endian,
+            geometry: Vec::new(),
+            blocks: Vec::new(),
+            blackouts: Blackouts::default(),
+        };
+        let mut pos = 1 + HEADER_LENGTH as usize;
+        while pos < bytes.len() {
+            let mut r = Reader::new(&bytes[pos..]);
+            let kind = r.u8()?;
+            let len = r.u64()? as usize;
+            let payload = pos + 9..pos + 1 + len;
+            if len < 8 || payload.end > bytes.len() {
+                return Err(TRUNCATED.into());
+            }
+            let block = &bytes[payload.clone()];
+            match kind {
+                VALUE_CHANGES | VALUE_CHANGES_ALIAS | VALUE_CHANGES_ALIAS2 => {
+                    fst.blocks.push((kind, payload.clone()))
+                }
+                GEOMETRY => fst.geometry = read_geometry(block)?,
+                HIERARCHY | HIERARCHY_LZ4 | HIERARCHY_LZ4_TWICE => {
+                    let mut r = Reader::new(block);
+                    let size = r.u64()? as usize;
+                    let hierarchy = match kind {
+                        HIERARCHY => gunzip(&block[8..], size)?,
+                        HIERARCHY_LZ4 => lz4_decode(&block[8..], size)?,
+                        _ => {
+                            let once = r.varint()? as usize;
+                            let once = sized(lz4_decode(&block[r.pos..], once)?, once)?;
+                            lz4_decode(&once, size)?
+                        }
+                    };
+                    let hierarchy = sized(hierarchy, size)?;
+                    let declared = read_hierarchy(&hierarchy)?;
+                    fst.hierarchy = declared.hierarchy;
+                    fst.enums = declared.enums;
+                    fst.scope_attributes = declared.scope_attributes;
+                    fst.var_attributes = declared.var_attributes;
+                    fst.aggregates = declared.aggregates;
+                }
+                BLACKOUT => fst.blackouts = read_blackouts(block)?,
+                // The header of a file cut short while it was written.
+                HEADER | SKIP => {}
+                _ => return Err(InvalidData("unknown FST block type").into()),
+            }
+            pos = payload.end;
+        }
+        fst.data = data;
+        Ok(fst)
+    }
+
+    /// The value names of the signals of enum variables, from the enum
+    /// tables of the file.
+    pub fn enum_maps(&self) -> &EnumMaps {
+        &self.enums
+    }
+
+    /// The attributes declared before the scope at a dotted `path`, such
+    /// as where it is instantiated.
+    pub fn scope_attributes(&self, path: &str) -> &[Attribute] {
+        self.scope_attributes.get(path).map_or(&[], Vec::as_slice)
+    }
+
+    /// The attributes declared before the variable at a dotted `path`,
+    /// such as where it is declared.
+    pub fn var_attributes(&self, path: &str) -> &[Attribute] {
+        self.var_attributes.get(path).map_or(&[], Vec::as_slice)
+    }
+
+    /// The member tree of the struct, union or interface scope at a dotted
+    /// `path`, `None` for other scopes. Structs and unions are packed
+    /// unless a pack attribute declares them unpacked.
+    pub fn members(&self, path: &str) -> Option<Member> {
+        let parts: Vec<&str> = path.split('.').collect();
+        let scope = self.hierarchy.find_scope(&parts)?;
+        let packed = |path: &str| {
+            !self.scope_attributes(path).iter().any(|a| {
+                matches!(
+                    a,
+                    Attribute::Other {
+                        kind: ATTRIBUTE_PACK,
+                        subtype: PACK_UNPACKED,
+                        ..
+                    }
+                )
+            })
+        };
+        members::build(&self.hierarchy, scope, path, &self.aggregates, &packed)
+    }
+
+    /// The member at a dot-separated path such as `top.bus.req.addr`,
+    /// from the tree of the outermost aggregate along the path, so its
+    /// bits are those in the outermost packed aggregate.
+    pub fn member(&self, path: &str) -> Option<Member> {
+        let parts: Vec<&str> = path.split('.').collect();
+        for i in 1..parts.len() + 1 {
+            self.hierarchy.find_scope(&parts[..i])?;
+            if let Some(root) = self.members(&parts[..i].join(".")) {
+                return match i == parts.len() {
+                    true => Some(root),
+                    false => root.get(&parts[i..].join(".")).cloned(),
+                };
+            }
+        }
+        None
+    }
+
+    /// The writer named in the header.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// The first and last time of the dump.
+    pub fn time_range(&self) -> Range<u64> {
+        self.time_range.clone()
+    }
+
+    /// The time, in ticks, that times in the file are relative to.
+    /// Viewers show `time_zero + t` for a time `t`.
+    pub fn time_zero(&self) -> i64 {
+        self.time_zero
+    }
+
+    /// Decode the changes of the requested handles in one value change
+    /// block into `out`. `slots` holds the output slot of every handle.
+    fn load_block(
+        &self,
+        kind: u8,
+        block: &[u8],
+        first: bool,
+        slots: &[Option<usize>],
+        out: &mut [Signal],
+    ) -> Result<(), InvalidData> {
+        let mut r = Reader::new(block);
+        let start = r.u64()?;
+        let _end = r.u64()?;
+        let _memory = r.u64()?;
+        let frame_len = r.varint()? as usize;
+        let frame_packed = r.varint()? as usize;
+        let frame_handles = r.varint()? as usize;
+        let frame = unpack_zlib(r.bytes(frame_packed)?, frame_len)?;
+        let _handles = r.varint()?;
+        let origin = r.pos;
+        let pack = r.u8()?;
+
+        let end = block.len();
+        let mut tail = Reader::new(
+            block
+                .get(end.checked_sub(24).ok_or(TRUNCATED)?..)
+                .ok_or(TRUNCATED)?,
+        );
+        let (times_len, times_packed, count) = (tail.u64()?, tail.u64()?, tail.u64()?);
+        let times_start = (end - 24)
+            .checked_sub(times_packed as usize)
+            .ok_or(TRUNCATED)?;
+        let times = unpack_zlib(&block[times_start..end - 24], times_len as usize)?;
+        let mut r = Reader::new(&times);
+        let mut time = 0;
+        let mut table = Vec::with_capacity((count as usize).min(times.len()));
+        for _ in 0..count {
+            time += r.varint()?;
+            table.push(time);
+        }
+
+        let table_len_at = times_start.checked_sub(8).ok_or(TRUNCATED)?;
+        let table_len = Reader::new(&block[table_len_at..]).u64()? as usize;
+        let table_start = table_len_at
+            .checked_sub(table_len)
+            .filter(|&s| s > origin)
+            .ok_or(CORRUPT_CHAINS)?;
+        let chains = chain_table(
+            kind,
+            &block[table_start..table_len_at],
+            table_start - origin,
+        )?;
+
+        let mut frame_at = 0;
+        for (handle, &geometry) in self.geometry.iter().enumerate() {
+            let frame_value = frame.get(frame_at..frame_at + geometry.frame_len());
+            frame_at += geometry.frame_len();
+            let Some(slot) = slots.get(handle).copied().flatten() else {
+                continue;
+            };
+            let signal = &mut out[slot];
+            // The frame of the first block holds the initial values, which
+            // are `x` unless set before the first change. Later frames
+            // repeat the last change.
+            let mut initial = match (first && handle < frame_handles, geometry, frame_value) {
+                (true, Geometry::Bits(_), Some(v)) if v.iter().any(|b| !b"xX".contains(b)) => {
+                    Some(v)
+                }
+                _ => None,
+            };
+            let mut push = |signal: &mut Signal, index: usize, value: &[u8]| {
+                let time = *table
+                    .get(index)
+                    .ok_or(InvalidData("FST time index out of range"))?;
+                if let Some(v) = initial.take() {
+                    if time > start {
+                        signal.push(start, v);
+                    }
+                }
+                signal.push(time, value);
+                Ok::<_, InvalidData>(())
+            };
+            let Some(range) = chains.get(handle).cloned().flatten() else {
+                if let Some(v) = initial {
+                    signal.push(start, v);
+                }
+                continue;
+            };
+            let chain = block
+                .get(origin + range.start..origin + range.end)
+                .ok_or(CORRUPT_CHAINS)?;
+            let mut r = Reader::new(chain);
+            let size = r.varint()? as usize;
+            let chain = match (size, pack) {
+                (0, _) => Cow::Borrowed(&chain[r.pos..]),
+                (_, b'Z') => Cow::Owned(sized(zlib_decode(&chain[r.pos..], size)?, size)?),
+                (_, b'4') => Cow::Owned(sized(lz4_decode(&chain[r.pos..], size)?, size)?),
+                (_, b'F') => Cow::Owned(sized(fastlz_decode(&chain[r.pos..], size)?, size)?),
+                _ => return Err(InvalidData("unsupported FST chain compression")),
+            };
+            let mut r = Reader::new(&chain);
+            let mut index = 0usize;
+            let mut value = Vec::new();
+            while !r.at_end() {
+                let v = r.varint()?;
+                value.clear();
+                match geometry {
+                    Geometry::Bits(1) => {
+                        if v & 1 == 0 {
+                            index += (v >> 2) as usize;
+                            value.push(b'0' + (v >> 1 & 1) as u8);
+                        } else {
+                            index += (v >> 4) as usize;
+                            value.push(SCALAR_STATES[(v >> 1 & 7) as usize]);
+                        }
+                    }
+                    Geometry::Bits(len) => {
+                        index += (v >> 1) as usize;
+                        let len = len as usize;
+                        if v & 1 == 0 {
+                            let bytes = r.bytes(len.div_ceil(8))?;
+                            value
+                                .extend((0..len).map(|i| b'0' + (bytes[i / 8] >> (7 - i % 8) & 1)));
+                        } else {
+                            value.extend_from_slice(r.bytes(len)?);
+                        }
+                    }
+                    Geometry::Real => {
+                        index += (v >> 1) as usize;
+                        // fstapi packs the rare doubles whose bytes are all
+                        // ASCII `0` or `1` into one byte.
+                        let bytes: [u8; 8] = if v & 1 == 0 {
+                            let bits = r.bytes(1)?[0];
+                            std::array::from_fn(|i| b'0' + (bits >> (7 - i) & 1))
+                        } else {
+                            r.bytes(8)?.try_into().expect("8 bytes")
+                        };
+                        let real = if self.big_endian {
+                            f64::from_be_bytes(bytes)
+                        } else {
+                            f64::from_le_bytes(bytes)
+                        };
+                        value.extend(real.to_string().bytes());
+                    }
+                    Geometry::Variable => {
+                        index += v as usize;
+                        let len = r.varint()? as usize;
+                        value.extend_from_slice(r.bytes(len)?);
+                    }
+                }
+                push(signal, index, &value)?;
+            }
+            if let Some(v) = initial {
+                signal.push(start, v);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_geometry(block: &[u8]) -> Result<Vec<Geometry>, InvalidData> {
+    let mut r = Reader::new(block);
+    let size = r.u64()? as usize;
+    let handles = r.u64()?;
+    let data = unpack_zlib(&block[16..], size)?;
+    let mut r = Reader::new(&data);
+    (0..handles)
+        .map(|_| {
+            Ok(match r.varint()? {
+                GEOMETRY_REAL => Geometry::Real,
+                GEOMETRY_VARIABLE => Geometry::Variable,
+                len => Geometry::Bits(
+                    u32::try_from(len).map_err(|_| InvalidData("FST signal too wide"))?,
+                ),
+            })
+        })
+        .collect()
+}
+
+fn read_blackouts(block: &[u8]) -> Result<Blackouts, InvalidData> {
+    let mut r = Reader::new(block);
+    let mut time = 0;
+    let edges = (0..r.varint()?)
+        .map(|_| {
+            let active = r.u8()? != 0;
+            time += r.varint()?;
+            Ok((time, active))
+        })
+        .collect::<Result<Vec<_>, InvalidData>>()?;
+    Ok(Blackouts::from_edges(edges))
+}
+
+impl SignalLoader for FstFile {
+    fn load_signals(&mut self, ids: &[SignalId]) -> io::Result<Vec<Signal>> {
+        let mut slots = vec![None; self.geometry.len()];
+        for (i, id) in ids.iter().enumerate() {
+            if let Some(slot) = slots.get_mut(id.0 as usize) {
+                slot.get_or_insert(i);
+            }
+        }
+        let mut out = vec![Signal::new(); ids.len()];
+        let data = self.data.as_slice();
+        for (i, (kind, range)) in self.blocks.iter().enumerate() {
+            self.load_block(*kind, &data[range.clone()], i == 0, &slots, &mut out)?;
+        }
+        // Requested twice: copy the first.
+        for (i, id) in ids.iter().enumerate() {
+            if let Some(&Some(first)) = slots.get(id.0 as usize) {
+                if first != i {
+                    out[i] = out[first].clone();
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl Waveform for FstFile {
+    fn hierarchy(&self) -> &Hierarchy {
+        &self.hierarchy
+    }
+
+    fn timescale(&self) -> Option<Timescale> {
+        Some(self.timescale)
+    }
+
+    fn date(&self) -> Option<&str> {
+        Some(self.date.as_str()).filter(|d| !d.is_empty())
+    }
+
+    fn blackouts(&self) -> io::Result<Blackouts> {
+        Ok(self.blackouts.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::inflate::inflate;
+    use crate::VcdFile;
+    use std::io::Cursor;
+
+    const INPUT: &[u8] = b"$timescale 10 ps $end
+$scope module top $end
+$var wire 1 ! clk $end
+$var wire 4 \" data [3:0] $end
+$var real 64 # volts $end
+$scope module sub $end
+$var wire 1 ! clk_alias $end
+$upscope $end
+$upscope $end
+$enddefinitions $end
+#0 0! b0 \" r0.5 #
+#5 1! bx1 \"
+#10 0! b11 \" r1.25 # $dumpoff $end
+#20 $dumpon $end
+";
+
+    fn blocks(data: &[u8]) -> Vec<(u8, &[u8])> {
+        let mut out = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let len = u64::from_be_bytes(data[pos + 1..pos + 9].try_into().unwrap()) as usize;
+            out.push((data[pos], &data[pos + 9..pos + 1 + len]));
+            pos += 1 + len;
+        }
+        out
+    }
+
+    fn u64_at(data: &[u8], pos: usize) -> u64 {
+        u64::from_be_bytes(data[pos..pos + 8].try_into().unwrap())
+    }
+
+    fn unpack(data: &[u8], len: usize) -> Vec<u8> {
+        if data.len() == len {
+            data.to_vec()
+        } else {
+            inflate(&data[2..data.len() - 4], len).unwrap()
+        }
+    }
+
+    #[test]
+    fn blocks_of_a_waveform() {
+        let mut vcd = VcdFile::from_bytes(INPUT.to_vec()).unwrap();
+        let out = write_fst(Cursor::new(Vec::new()), &mut vcd, &WriteOptions::default()).unwrap();
+        let data = out.into_inner();
+        let blocks = blocks(&data);
+        let kinds: Vec<u8> = blocks.iter().map(|b| b.0).collect();
+        assert_eq!(
+            kinds,
+            [HEADER, VALUE_CHANGES, GEOMETRY, HIERARCHY, BLACKOUT]
+        );
+
+        let header = blocks[0].1;
+        assert_eq!(header.len() as u64, HEADER_LENGTH - 8);
+        assert_eq!((u64_at(header, 0), u64_at(header, 8)), (0, 20));
+        assert_eq!(
+            f64::from_le_bytes(header[16..24].try_into().unwrap()),
+            ENDIAN_TEST
+        );
+        // Scopes, variables, handles and value change blocks.
+        let counts: Vec<u64> = (0..4).map(|i| u64_at(header, 32 + 8 * i)).collect();
+        assert_eq!(counts, [2, 4, 3, 1]);
+        assert_eq!(header[64] as i8, -11);
+        assert!(header[65..].starts_with(b"wave_parse "));
+
+        let geometry = blocks[2].1;
+        let mut pos = 0;
+        let geometry = unpack(&geometry[16..], u64_at(geometry, 0) as usize);
+        let widths: Vec<u64> =
+            std::iter::from_fn(|| crate::varint::read(&geometry, &mut pos)).collect();
+        assert_eq!(widths, [1, 4, GEOMETRY_REAL]);
+
+        let hierarchy = blocks[3].1;
+        let gz = &hierarchy[8..];
+        let hierarchy = inflate(&gz[10..gz.len() - 8], 0).unwrap();
+        let mut expected = vec![SCOPE, 0];
+        expected.extend(b"top\0\0");
+        expected.extend([16, 0]);
+        expected.extend(b"clk\0");
+        expected.extend([1, 0, 16, 0]);
+        expected.extend(b"data [3:0]\0");
+        expected.extend([4, 0, 3, 0]);
+        expected.extend(b"volts\0");
+        expected.extend([8, 0, SCOPE, 0]);
+        expected.extend(b"sub\0\0");
+        expected.extend([16, 0]);
+        expected.extend(b"clk_alias\0");
+        expected.extend([1, 1, UPSCOPE, UPSCOPE]);
+        assert_eq!(hierarchy, expected);
+
+        // Dumping stops at 10 and resumes at 20.
+        assert_eq!(blocks[4].1, [2, 0, 10, 1, 10]);
+
+        let vc = blocks[1].1;
+        assert_eq!((u64_at(vc, 0), u64_at(vc, 8)), (0, 10));
+        let mut pos = 24;
+        let frame_len = crate::varint::read(vc, &mut pos).unwrap() as usize;
+        let frame_packed = crate::varint::read(vc, &mut pos).unwrap() as usize;
+        assert_eq!(crate::varint::read(vc, &mut pos), Some(3));
+        let frame = unpack(&vc[pos..pos + frame_packed], frame_len);
+        assert_eq!(frame[..5], *b"xxxxx");
+        assert!(f64::from_le_bytes(frame[5..].try_into().unwrap()).is_nan());
+
+        let end = vc.len();
+        let count = u64_at(vc, end - 8) as usize;
+        let packed = u64_at(vc, end - 16) as usize;
+        let len = u64_at(vc, end - 24) as usize;
+        let times = unpack(&vc[end - 24 - packed..end - 24], len);
+        let mut pos = 0;
+        let deltas: Vec<u64> =
+            std::iter::from_fn(|| crate::varint::read(&times, &mut pos)).collect();
+        assert_eq!((count, deltas), (3, vec![0, 5, 5]));
+
+        // The chain of the clock, the first in the block: 0, 1 after one
+        // time step and 0 after another, without compression.
+        let table_len = u64_at(vc, end - 24 - packed - 8) as usize;
+        let table_end = end - 24 - packed - 8;
+        let table = &vc[table_end - table_len..table_end];
+        assert_eq!(table[0], 1 << 1 | 1);
+        let origin = vc[..table_end - table_len]
+            .iter()
+            .position(|&b| b == b'Z')
+            .unwrap();
+        assert_eq!(vc[origin + 1..origin + 5], [0, 0, 1 << 2 | 1 << 1, 1 << 2]);
+    }
+
+    #[test]
+    fn rejects_corrupt_sizes() {
+        let mut vcd = VcdFile::from_bytes(INPUT.to_vec()).unwrap();
+        let out = write_fst(Cursor::new(Vec::new()), &mut vcd, &WriteOptions::default()).unwrap();
+        let data = out.into_inner();
+        // The uncompressed size that starts the geometry and hierarchy
+        // blocks, too large to allocate or just off by one.
+        for kind in [GEOMETRY, HIERARCHY] {
+            let mut pos = 0;
+            while data[pos] != kind {
+                pos += 1 + u64_at(&data, pos + 1) as usize;
+            }
+            for size in [u64::MAX >> 1, u64_at(&data, pos + 9) + 1] {
+                let mut bad = data.clone();
+                bad[pos + 9..pos + 17].copy_from_slice(&size.to_be_bytes());
+                let err = FstFile::from_bytes(bad).err().expect("not rejected");
+                assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            }
+        }
+    }
+
+    fn signals(wave: &mut dyn Waveform, paths: &[&str]) -> Vec<Signal> {
+        let ids: Vec<SignalId> = paths
+            .iter()
+            .map(|p| wave.hierarchy().lookup(p).unwrap().signal)
+            .collect();
+        wave.load_signals(&ids).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut vcd = VcdFile::from_bytes(INPUT.to_vec()).unwrap();
+        let mut w = FstWriter::new(Cursor::new(Vec::new()));
+        w.set_timescale(vcd.timescale().unwrap()).unwrap();
+        w.set_block_size(4);
+        w.hierarchy(vcd.hierarchy()).unwrap();
+        let ids = vcd.hierarchy().signal_ids();
+        let loaded = vcd.load_signals(&ids).unwrap();
+        for t in [0, 5, 10] {
+            w.timestamp(t).unwrap();
+            for (id, s) in ids.iter().zip(&loaded) {
+                if let Some(i) = s.index_at(t).filter(|&i| s.time(i) == t) {
+                    w.change(*id, s.value(i)).unwrap();
+                }
+            }
+        }
+        w.timestamp(20).unwrap();
+        w.dumpoff().unwrap();
+        let mut fst = FstFile::from_bytes(w.finish().unwrap().into_inner()).unwrap();
+        assert_eq!(fst.blocks.len(), 3);
+        assert_eq!(fst.timescale(), vcd.timescale());
+        assert_eq!(fst.time_range(), 0..20);
+        assert!(fst.version().starts_with("wave_parse"));
+        assert!(fst.blackouts().unwrap().contains(u64::MAX - 1));
+        assert!(!fst.blackouts().unwrap().contains(19));
+
+        let h = fst.hierarchy();
+        let paths: Vec<String> = h.var_paths().into_iter().map(|(p, _)| p).collect();
+        assert_eq!(
+            paths,
+            ["top.clk", "top.data", "top.volts", "top.sub.clk_alias"]
+        );
+        let data = h.lookup("top.data").unwrap();
+        assert_eq!(
+            (data.width, data.index),
+            (4, Some(ReferenceIndex::Range(3, 0)))
+        );
+        assert_eq!(h.lookup("top.volts").unwrap().kind, VarKind::Real);
+        assert_eq!(
+            h.lookup("top.sub.clk_alias").unwrap().signal,
+            h.lookup("top.clk").unwrap().signal
+        );
+
+        let all = ["top.clk", "top.data", "top.volts", "top.sub.clk_alias"];
+        let s = signals(&mut fst, &all);
+        assert_eq!(s[0].times(), [0, 5, 10]);
+        assert_eq!(s[0].value(1), b"1");
+        assert_eq!(s[0], s[3]);
+        let data: Vec<&[u8]> = s[1].iter().map(|(_, v)| v).collect();
+        assert_eq!(data, [&b"0000"[..], b"xxx1", b"0011"]);
+        assert_eq!(s[2].times(), [0, 10]);
+        assert_eq!(s[2].value(1), b"1.25");
+    }
+
+    #[test]
+    fn write_fst_honors_blackouts_and_initial_values() {
+        let input = b"$timescale 1 us $end $var wire 2 ! a $end $var string 1 \" s $end
+$enddefinitions $end #3 b1 ! shello \" #7 $dumpoff $end #9 $dumpon b10 ! sbye \"";
+        let mut vcd = VcdFile::from_bytes(input.to_vec()).unwrap();
+        let out = write_fst(Cursor::new(Vec::new()), &mut vcd, &WriteOptions::default()).unwrap();
+        let mut fst = FstFile::from_bytes(out.into_inner()).unwrap();
+        assert_eq!(fst.timescale(), Some(Timescale::new(1, TimeUnit::US)));
+        assert_eq!(fst.blackouts().unwrap(), vcd.blackouts().unwrap());
+        let s = signals(&mut fst, &["a", "s"]);
+        assert_eq!(s[0].times(), [3, 9]);
+        assert_eq!(s[0].value(1), b"10");
+        assert_eq!(s[1].value(0), b"hello");
+        assert_eq!(s[1].value(1), b"bye");
+    }
+
+    #[test]
+    fn chain_tables() {
+        // Chains at 1 and 6, two handles without changes, two aliases of
+        // the first handle, and another chain at 11 up to the table at 20.
+        let table = [3, 4, 0x7f, 1, 11];
+        let ranges = chain_table(VALUE_CHANGES_ALIAS2, &table, 20).unwrap();
+        assert_eq!(
+            ranges,
+            [Some(1..6), None, None, Some(1..6), Some(1..6), Some(6..20)]
+        );
+        let table = [3, 4, 0, 1, 11];
+        let ranges = chain_table(VALUE_CHANGES_ALIAS, &table, 20).unwrap();
+        assert_eq!(ranges, [Some(1..6), None, None, Some(1..6), Some(6..20)]);
+        assert!(chain_table(VALUE_CHANGES, &[3, 41], 20).is_err());
+        assert_eq!(timescale(-8).unwrap(), Timescale::new(10, TimeUnit::NS));
+        assert_eq!(timescale(2).unwrap(), Timescale::new(100, TimeUnit::S));
+        assert!(timescale(-18).is_err());
+    }
+
+    #[test]
+    fn enum_tables() {
+        let mut w = FstWriter::new(Cursor::new(Vec::new()));
+        let map = EnumMap::parse("IDLE=0, RUN FAST=1, DONE\\=2").unwrap();
+        let table = w.enum_table("state_t", 2, &map).unwrap();
+        w.scope_def(ScopeKind::Module, "top").unwrap();
+        w.enum_ref(table).unwrap();
+        w.var_def(VarKind::Enum, 2, SignalId(0), "state", None)
+            .unwrap();
+        w.var_def(VarKind::Wire, 2, SignalId(1), "plain", None)
+            .unwrap();
+        w.upscope().unwrap();
+        let fst = FstFile::from_bytes(w.finish().unwrap().into_inner()).unwrap();
+        let names = fst.enum_maps().get(SignalId(0)).unwrap();
+        assert_eq!(names, &map);
+        assert_eq!(
+            fst.enum_maps().name(SignalId(0), b"01", 2),
+            Some("RUN FAST")
+        );
+        assert!(fst.enum_maps().get(SignalId(1)).is_none());
+
+        let table = parse_enum_table(r"t 3 a\040b \x41 c\\ 00 01 1x").unwrap();
+        let mut entries: Vec<(u64, &str)> = table.iter().collect();
+        entries.sort_unstable();
+        assert_eq!(entries, [(0, "a b"), (1, "A")]);
+        assert!(parse_enum_table("t 2 a 0").is_none());
+    }
+
+    #[test]
+    fn attributes() {
+        let instance = Attribute::Instance {
+            path: "top.sv".into(),
+            line: 3,
+        };
+        let source = Attribute::Source {
+            path: "sub.sv".into(),
+            line: 10,
+        };
+        let supplemental = Attribute::SupplementalVar {
+            type_name: "std_logic".into(),
+            var_type: 1,
+            data_type: 6,
+        };
+        let top = Attribute::Source {
+            path: "top.sv".into(),
+            line: 1,
+        };
+        let pack = Attribute::Other {
+            kind: 3,
+            subtype: 1,
+            name: String::new(),
+            arg: 0,
+        };
+        let mut w = FstWriter::new(Cursor::new(Vec::new()));
+        w.attribute_def(&Attribute::Comment("built by hand".into()))
+            .unwrap();
+        w.attribute_def(&instance).unwrap();
+        w.scope_def(ScopeKind::Module, "top").unwrap();
+        w.var_def(VarKind::Wire, 1, SignalId(0), "clk", None)
+            .unwrap();
+        w.attribute_def(&source).unwrap();
+        w.attribute_def(&supplemental).unwrap();
+        w.var_def(VarKind::Wire, 1, SignalId(1), "q", None).unwrap();
+        w.upscope().unwrap();
+        w.attribute_def(&top).unwrap();
+        w.attribute_def(&pack).unwrap();
+        w.var_def(VarKind::Wire, 1, SignalId(2), "rst", None)
+            .unwrap();
+        assert!(w
+            .attribute_def(&Attribute::Other {
+                kind: ATTRIBUTE_MISC,
+                subtype: MISC_SOURCE_STEM,
+                name: "x.sv".into(),
+                arg: 0,
+            })
+            .is_err());
+        let fst = FstFile::from_bytes(w.finish().unwrap().into_inner()).unwrap();
+        assert_eq!(
+            fst.scope_attributes("top"),
+            [Attribute::Comment("built by hand".into()), instance]
+        );
+        assert!(fst.var_attributes("top.clk").is_empty());
+        assert_eq!(fst.var_attributes("top.q"), [source, supplemental]);
+        assert_eq!(fst.var_attributes("rst"), [top, pack]);
+        assert!(fst.scope_attributes("top.q").is_empty());
+    }
+
+    #[test]
+    fn misuse() {
+        let mut w = FstWriter::new(Cursor::new(Vec::new()));
+        assert!(w.set_timescale(Timescale::new(5, TimeUnit::NS)).is_err());
+        assert!(w.upscope().is_err());
+        w.scope_def(ScopeKind::Module, "top").unwrap();
+        w.var_def(VarKind::Wire, 1, SignalId(7), "a", None).unwrap();
+        assert!(w.timestamp(0).is_err());
+        w.upscope().unwrap();
+        w.timestamp(10).unwrap();
+        assert!(w.change(SignalId(8), b"1").is_err());
+        assert!(w.var_def(VarKind::Wire, 1, SignalId(8), "b", None).is_err());
+        w.change(SignalId(7), b"1").unwrap();
+        assert!(w.timestamp(5).is_err());
+        let data = w.finish().unwrap().into_inner();
+        assert_eq!(data[0], HEADER);
+    }
+
+    #[test]
+    fn dump_commands() {
+        let mut w = FstWriter::new(Cursor::new(Vec::new()));
+        w.var_def(VarKind::Wire, 1, SignalId(0), "a", None).unwrap();
+        assert!(w.dumpoff().is_err());
+        w.timestamp(10).unwrap();
+        w.dumpoff().unwrap();
+        w.timestamp(20).unwrap();
+        w.dumpon().unwrap();
+        let data = w.finish().unwrap().into_inner();
+        // Dumping stops at 10 and resumes at 20.
+        let blocks = blocks(&data);
+        assert_eq!(blocks.last(), Some(&(BLACKOUT, &[2, 0, 10, 1, 10][..])));
+    }
+
+    #[test]
+    fn reads_fstapi_output() {
+        // Written by testdata/fstapi.c with GTKWave's fstapi.
+        let data = include_bytes!("../testdata/fstapi.fst").to_vec();
+        let mut fst = FstFile::from_bytes(data).unwrap();
+        assert_eq!(fst.timescale(), Some(Timescale::new(1, TimeUnit::NS)));
+        assert_eq!(fst.version(), "fstapi");
+        assert_eq!(fst.date(), Some("Thu Oct 15 12:00:00 2026"));
+        assert_eq!(fst.time_range(), 0..100);
+        assert_eq!(fst.blackouts().unwrap().at(45), Some(40..60));
+
+        let h = fst.hierarchy();
+        let paths: Vec<String> = h.var_paths().into_iter().map(|(p, _)| p).collect();
+        assert_eq!(paths, ["top.clk", "top.cnt", "top.v", "top.sub.count"]);
+        let cnt = h.lookup("top.cnt").unwrap();
+        assert_eq!(
+            (cnt.width, cnt.index),
+            (8, Some(ReferenceIndex::Range(7, 0)))
+        );
+        assert_eq!(h.lookup("top.v").unwrap().kind, VarKind::Real);
+        assert_eq!(h.lookup("top.sub.count").unwrap().signal, cnt.signal);
+
+        let s = signals(&mut fst, &["top.clk", "top.cnt", "top.v"]);
+        assert_eq!(s[0].len(), 21);
+        assert_eq!(s[0].value_at(45), Some(&b"1"[..]));
+        assert_eq!(s[1].times(), (0..=100).step_by(10).collect::<Vec<_>>());
+        assert_eq!(s[1].value(3), b"0000xxzz");
+        assert_eq!(s[1].value(10), b"00001010");
+        assert_eq!(s[2].value(1), b"2.5");
+    }
+
+    #[test]
+    fn reads_fastlz_chains() {
+        let paths = ["top.clk", "top.cnt", "top.v"];
+        let data = include_bytes!("../testdata/fstapi.fst").to_vec();
+        let zlib = signals(&mut FstFile::from_bytes(data).unwrap(), &paths);
+        let data = include_bytes!("../testdata/fastlz.fst").to_vec();
+        let fastlz = signals(&mut FstFile::from_bytes(data.clone()).unwrap(), &paths);
+        assert_eq!(fastlz, zlib);
+
+        // Corrupting any byte fails cleanly, whichever part it lands in.
+        for i in 0..data.len() {
+            let mut bad = data.clone();
+            bad[i] ^= 0x5a;
+            if let Ok(mut fst) = FstFile::from_bytes(bad) {
+                let ids: Vec<_> = (0..3).map(SignalId).collect();
+                let _ = fst.load_signals(&ids);
+            }
+        }
+    }
+
+    #[test]
+    fn fst_reader_reads_written_files() {
+        use fst_reader::{FstFilter, FstHierarchyEntry, FstReader, FstSignalValue};
+
+        let mut vcd = VcdFile::from_bytes(INPUT.to_vec()).unwrap();
+        let options = WriteOptions {
+            date: Some("today".to_string()),
+            ..WriteOptions::default()
+        };
+        let out = write_fst(Cursor::new(Vec::new()), &mut vcd, &options).unwrap();
+        let data = out.into_inner();
+        let mut reader = FstReader::open(Cursor::new(&data)).unwrap();
+        let header = reader.get_header();
+        assert_eq!(header.timescale_exponent, -11);
+        assert_eq!((header.start_time, header.end_time), (0, 20));
+        assert_eq!(header.date, "today");
+
+        let mut scopes = Vec::new();
+        let mut vars = Vec::new();
+        reader
+            .read_hierarchy(|entry| match entry {
+                FstHierarchyEntry::Scope { name, .. } => scopes.push(name),
+                FstHierarchyEntry::UpScope => drop(scopes.pop()),
+                FstHierarchyEntry::Var { name, handle, .. } => {
+                    vars.push((format!("{}.{}", scopes.join("."), name), handle))
+                }
+                _ => {}
+            })
+            .unwrap();
+        let names: Vec<&str> = vars.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "top.clk",
+                "top.data [3:0]",
+                "top.volts",
+                "top.sub.clk_alias"
+            ]
+        );
+        assert_eq!(vars[0].1, vars[3].1);
+
+        let mut changes: HashMap<_, Vec<(u64, String)>> = HashMap::new();
+        reader
+            .read_signals(&FstFilter::all(), |time, handle, value| {
+                let value = match value {
+                    FstSignalValue::String(v) => String::from_utf8_lossy(v).into_owned(),
+                    FstSignalValue::Real(v) => v.to_string(),
+                };
+                changes
+                    .entry(handle.get_index())
+                    .or_default()
+                    .push((time, value));
+            })
+            .unwrap();
+        // The same changes as read here, with vectors padded to their width.
+        let mut fst = FstFile::from_bytes(data).unwrap();
+        let paths = ["top.clk", "top.data", "top.volts"];
+        for ((_, handle), expected) in vars.iter().zip(signals(&mut fst, &paths)) {
+            let expected: Vec<(u64, String)> = (expected.iter())
+                .map(|(t, v)| (t, String::from_utf8_lossy(v).into_owned()))
+                .collect();
+            assert_eq!(changes[&handle.get_index()], expected);
+        }
+    }
+}
@@ -0,0 +1,1517 @@
+//! GHW, the waveform format of the GHDL VHDL simulator.
+//!
+//! A GHW file starts with a 16-byte header, `GHDLwave\n` followed by the
+//! format version and the byte order of words, and continues with
+//! sections, each introduced by a four-byte tag:
+//!
+//! * `STR`, the strings used by the other sections. Each string shares a
+//!   prefix with the one before it, whose length ends the string.
+//! * `TYP`, the VHDL types, and `WKT`, marking the well-known ones:
+//!   `boolean`, `bit` and `std_ulogic`.
+//! * `HIE`, the hierarchy of instances, blocks, generates and signals. A
+//!   signal of a composite type owns one *basic* signal per scalar element.
+//! * `EOH`, the end of the header.
+//! * `SNP`, a snapshot of the values of all basic signals, and `CYC`, the
+//!   changes of the following simulation cycles.
+//! * `DIR` and `TAI`, a directory of the sections.
+//!
+//! Integers are LEB128 varints, signed ones sign-extended, and times count
+//! femtoseconds.
+//!
+//! [`GhwFile`] maps VHDL onto the crate's model. Instances, blocks,
+//! generates, packages and generics become scopes; records become struct
+//! scopes; and signals and ports become variables. Processes are left
+//! out, as they declare no signals. Arrays of `std_ulogic`, `bit` or
+//! `boolean` are vectors, while other arrays get a variable per element.
+//! Values are rendered as follows:
+//!
+//! * other enumerations as the binary position of their literal,
+//! * integers as 32- or 64-bit two's complement,
+//! * floating-point values as reals.
+//!
+//! The VHDL types stay available to render values symbolically:
+//! [`GhwFile::signal_type`] and [`GhwFile::scope_type`] give the names,
+//! enumeration literals, record fields and array ranges of signals and
+//! records, and [`GhwFile::enum_maps`] names the values of enumerations.
+//!
+//! The reader follows the layout of GHDL's `ghwlib`. Its sample file is
+//! built after that layout rather than written by GHDL, and is checked
+//! against wellen's GHW reader, which reads GHDL's output.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::mem;
+use std::path::Path;
+
+use crate::inflate::gunzip;
+use crate::mmap::{Data, Mmap};
+use crate::{
+    EnumMap, EnumMaps, Hierarchy, InvalidData, ReferenceIndex, Scope, ScopeKind, Signal, SignalId,
+    SignalLoader, TimeUnit, Timescale, Var, VarKind, Waveform,
+};
+
+const MAGIC: &[u8] = b"GHDLwave\n";
+
+// Kinds of types (GHDL's `ghdl_rtik`).
+const TYPE_B2: u8 = 22;
+const TYPE_E8: u8 = 23;
+const TYPE_I32: u8 = 25;
+const TYPE_I64: u8 = 26;
+const TYPE_F64: u8 = 27;
+const TYPE_P32: u8 = 28;
+const TYPE_P64: u8 = 29;
+const TYPE_ARRAY: u8 = 31;
+const TYPE_RECORD: u8 = 32;
+const SUBTYPE_SCALAR: u8 = 34;
+const SUBTYPE_ARRAY: u8 = 35;
+const SUBTYPE_UNBOUNDED_ARRAY: u8 = 37;
+const SUBTYPE_RECORD: u8 = 38;
+const SUBTYPE_UNBOUNDED_RECORD: u8 = 39;
+
+// Hierarchy entries.
+const HIE_END: u8 = 0;
+const HIE_BLOCK: u8 = 3;
+const HIE_GENERATE_IF: u8 = 4;
+const HIE_GENERATE_FOR: u8 = 5;
+const HIE_INSTANCE: u8 = 6;
+const HIE_PACKAGE: u8 = 7;
+const HIE_PROCESS: u8 = 13;
+const HIE_GENERIC: u8 = 14;
+const HIE_END_SCOPE: u8 = 15;
+const HIE_SIGNAL: u8 = 16;
+const HIE_PORT_LINKAGE: u8 = 21;
+
+// Well-known types.
+const WKT_BOOLEAN: u8 = 1;
+const WKT_BIT: u8 = 2;
+const WKT_STD_ULOGIC: u8 = 3;
+
+/// The `std_ulogic` literals in order, as value characters.
+const STD_ULOGIC: &[u8; 9] = b"ux01zwlh-";
+
+const TRUNCATED: InvalidData = InvalidData("truncated GHW file");
+const CORRUPT: InvalidData = InvalidData("corrupt GHW file");
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn at_end(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], InvalidData> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos.saturating_add(n))
+            .ok_or(TRUNCATED)?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, InvalidData> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn tag(&mut self) -> Result<[u8; 4], InvalidData> {
+        Ok(self.bytes(4)?.try_into().expect("4 bytes"))
+    }
+
+    /// The four zero bytes opening most sections.
+    fn zeros(&mut self) -> Result<(), InvalidData> {
+        match self.tag()? {
+            [0, 0, 0, 0] => Ok(()),
+            _ => Err(CORRUPT),
+        }
+    }
+
+    fn i32(&mut self) -> Result<i32, InvalidData> {
+        let bytes = self.tag()?;
+        Ok(match self.big_endian {
+            true => i32::from_be_bytes(bytes),
+            false => i32::from_le_bytes(bytes),
+        })
+    }
+
+    fn i64(&mut self) -> Result<i64, InvalidData> {
+        let bytes = self.bytes(8)?.try_into().expect("8 bytes");
+        Ok(match self.big_endian {
+            true => i64::from_be_bytes(bytes),
+            false => i64::from_le_bytes(bytes),
+        })
+    }
+
+    fn f64(&mut self) -> Result<f64, InvalidData> {
+        Ok(f64::from_bits(self.i64()? as u64))
+    }
+
+    fn uleb(&mut self) -> Result<u64, InvalidData> {
+        crate::varint::read(self.data, &mut self.pos).ok_or(TRUNCATED)
+    }
+
+    fn sleb(&mut self) -> Result<i64, InvalidData> {
+        let mut v = 0i64;
+        let mut shift = 0;
+        loop {
+            let b = self.u8()?;
+            v |= ((b & 0x7f) as i64) << shift;
+            shift += 7;
+            if b & 0x80 == 0 {
+                if shift < 64 && b & 0x40 != 0 {
+                    v |= -1 << shift;
+                }
+                return Ok(v);
+            }
+            if shift >= 64 {
+                return Err(CORRUPT);
+            }
+        }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    /// A count of things that take at least a byte each of the rest of
+    /// the file.
+    fn count(&mut self) -> Result<usize, InvalidData> {
+        let count = usize::try_from(self.i32()?).map_err(|_| CORRUPT)?;
+        self.fits(count)
+    }
+
+    /// `count` if that many things of at least a byte fit in the rest of
+    /// the file.
+    fn fits(&self, count: usize) -> Result<usize, InvalidData> {
+        match count <= self.remaining() {
+            true => Ok(count),
+            false => Err(CORRUPT),
+        }
+    }
+}
+
+/// The `STR` section. String 0 is the name of anonymous things.
+fn read_strings(r: &mut Reader) -> Result<Vec<String>, InvalidData> {
+    r.zeros()?;
+    let count = r.count()?;
+    let _size = r.i32()?;
+    let mut strings = vec![String::new()];
+    let mut prev = Vec::new();
+    let mut shared = 0;
+    for _ in 0..count {
+        let mut s = prev[..shared.min(prev.len())].to_vec();
+        let mut c = loop {
+            match r.u8()? {
+                c @ (0..=31 | 128..=159) => break c,
+                c => s.push(c),
+            }
+        };
+        // The length shared with the next string, 5 bits per byte.
+        shared = (c & 0x1f) as usize;
+        let mut shift = 5;
+        while c >= 128 {
+            c = r.u8()?;
+            if shift > 30 {
+                return Err(CORRUPT);
+            }
+            shared |= ((c & 0x1f) as usize) << shift;
+            shift += 5;
+        }
+        strings.push(String::from_utf8_lossy(&s).into_owned());
+        prev = s;
+    }
+    if &r.tag()? != b"EOS\0" {
+        return Err(CORRUPT);
+    }
+    Ok(strings)
+}
+
+/// A discrete range, `left to right` or `left downto right`. The bounds
+/// of enumeration ranges are literal positions.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Bounds {
+    pub left: i64,
+    pub right: i64,
+    pub downto: bool,
+}
+
+impl Bounds {
+    /// The number of values, `usize::MAX` if there are more.
+    fn len(&self) -> usize {
+        let (low, high) = match self.downto {
+            true => (self.right, self.left),
+            false => (self.left, self.right),
+        };
+        let n = high as i128 - low as i128 + 1;
+        usize::try_from(n.max(0)).unwrap_or(usize::MAX)
+    }
+
+    /// The values of the range from left to right.
+    fn values(&self) -> impl Iterator<Item = i64> {
+        let (left, step) = (self.left, if self.downto { -1 } else { 1 });
+        (0..self.len() as i64).map(move |i| left + i * step)
+    }
+}
+
+impl fmt::Display for Bounds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let dir = if self.downto { "downto" } else { "to" };
+        write!(f, "{} {} {}", self.left, dir, self.right)
+    }
+}
+
+/// The VHDL type of a signal or record, as the file declares it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VhdlType {
+    /// The name of the type, or for an anonymous subtype that of the
+    /// type it constrains.
+    pub name: String,
+    pub kind: VhdlKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VhdlKind {
+    /// An enumeration, with its literals by position.
+    Enum(Vec<String>),
+    /// An integer type, with the range of a constrained subtype.
+    Integer(Option<Bounds>),
+    /// A physical type such as `time`, with the range of a constrained
+    /// subtype.
+    Physical(Option<Bounds>),
+    Real,
+    /// An array, with the range of each dimension; none if unconstrained.
+    Array {
+        ranges: Vec<Bounds>,
+        element: Box<VhdlType>,
+    },
+    /// A record, with its fields in order.
+    Record(Vec<(String, VhdlType)>),
+}
+
+#[derive(Debug, Clone)]
+enum Type {
+    Enum {
+        literals: Vec<String>,
+        wkt: u8,
+    },
+    Integer {
+        bits: u32,
+        physical: bool,
+    },
+    Real,
+    /// A constrained scalar subtype, without bounds for reals.
+    Scalar {
+        base: usize,
+        bounds: Option<Bounds>,
+    },
+    /// An unconstrained array type.
+    Array {
+        element: usize,
+        dims: usize,
+    },
+    /// An array subtype, with the bounds of each dimension and its
+    /// constrained element type.
+    ArraySub {
+        base: usize,
+        ranges: Vec<Bounds>,
+        element: usize,
+        scalars: usize,
+    },
+    /// A record type, unconstrained when a field is.
+    Record {
+        fields: Vec<(String, usize)>,
+        scalars: Option<usize>,
+    },
+    RecordSub {
+        base: usize,
+        fields: Vec<(String, usize)>,
+        scalars: usize,
+    },
+}
+
+/// How the value of a basic signal is read and rendered.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Basic {
+    Logic,
+    Bit,
+    /// The position of an enumeration literal, in this many bits.
+    Enum(u32),
+    Int32,
+    Int64,
+    Real,
+}
+
+impl Basic {
+    fn read(self, r: &mut Reader) -> Result<u64, InvalidData> {
+        Ok(match self {
+            Basic::Logic | Basic::Bit | Basic::Enum(_) => r.u8()? as u64,
+            Basic::Int32 | Basic::Int64 => r.sleb()? as u64,
+            Basic::Real => r.f64()?.to_bits(),
+        })
+    }
+
+    fn render(self, raw: u64, out: &mut Vec<u8>) {
+        let binary = |out: &mut Vec<u8>, bits: u32| {
+            out.extend((0..bits).rev().map(|i| b'0' + (raw >> i & 1) as u8));
+        };
+        match self {
+            Basic::Logic => out.push(*STD_ULOGIC.get(raw as usize).unwrap_or(&b'x')),
+            Basic::Bit => out.push(b'0' + (raw != 0) as u8),
+            Basic::Enum(bits) => binary(out, bits),
+            Basic::Int32 => binary(out, 32),
+            Basic::Int64 => binary(out, 64),
+            Basic::Real => out.extend(f64::from_bits(raw).to_string().bytes()),
+        }
+    }
+}
+
+/// The `TYP` section: types in a table that the file numbers from 1, and
+/// the anonymous element subtypes that array and record subtypes
+/// declare inline.
+#[derive(Default)]
+struct Types {
+    types: Vec<Type>,
+    /// The name of each type, empty for anonymous subtypes.
+    names: Vec<String>,
+    table: Vec<usize>,
+}
+
+impl Types {
+    fn read(r: &mut Reader, strings: &[String], version: u8) -> Result<Types, InvalidData> {
+        let string = |r: &mut Reader| -> Result<String, InvalidData> {
+            let id = r.uleb()?;
+            strings.get(id as usize).cloned().ok_or(CORRUPT)
+        };
+        r.zeros()?;
+        let count = r.count()?;
+        let mut types = Types::default();
+        for _ in 0..count {
+            let name;
+            let ty = match r.u8()? {
+                TYPE_B2 | TYPE_E8 => {
+                    name = string(r)?;
+                    let n = r.uleb()?;
+                    let literals = (0..n).map(|_| string(r)).collect::<Result<_, _>>()?;
+                    Type::Enum { literals, wkt: 0 }
+                }
+                kind @ (TYPE_I32 | TYPE_I64 | TYPE_P32 | TYPE_P64) => {
+                    name = string(r)?;
+                    let physical = matches!(kind, TYPE_P32 | TYPE_P64);
+                    if physical && version > 0 {
+                        for _ in 0..r.uleb()? {
+                            string(r)?;
+                            r.sleb()?;
+                        }
+                    }
+                    Type::Integer {
+                        bits: if matches!(kind, TYPE_I32 | TYPE_P32) {
+                            32
+                        } else {
+                            64
+                        },
+                        physical,
+                    }
+                }
+                TYPE_F64 => {
+                    name = string(r)?;
+                    Type::Real
+                }
+                SUBTYPE_SCALAR => {
+                    name = string(r)?;
+                    let base = types.id(r)?;
+                    let bounds = read_range(r)?;
+                    Type::Scalar { base, bounds }
+                }
+                TYPE_ARRAY => {
+                    name = string(r)?;
+                    let element = types.id(r)?;
+                    let dims = r.uleb()? as usize;
+                    for _ in 0..dims {
+                        types.id(r)?;
+                    }
+                    Type::Array { element, dims }
+                }
+                SUBTYPE_ARRAY => {
+                    name = string(r)?;
+                    let base = types.id(r)?;
+                    types.array_subtype(r, base)?
+                }
+                TYPE_RECORD => {
+                    name = string(r)?;
+                    let mut fields = Vec::new();
+                    let mut scalars = Some(0usize);
+                    for _ in 0..r.uleb()? {
+                        let field = (string(r)?, types.id(r)?);
+                        scalars = match (scalars, types.scalars(field.1)) {
+                            (Some(a), Some(b)) => Some(a.checked_add(b).ok_or(CORRUPT)?),
+                            _ => None,
+                        };
+                        fields.push(field);
+                    }
+                    Type::Record { fields, scalars }
+                }
+                SUBTYPE_RECORD => {
+                    name = string(r)?;
+                    let base = types.id(r)?;
+                    types.record_subtype(r, base)?
+                }
+                SUBTYPE_UNBOUNDED_ARRAY | SUBTYPE_UNBOUNDED_RECORD => {
+                    // Leaves the type it names unconstrained.
+                    name = string(r)?;
+                    let base = types.id(r)?;
+                    match &types.types[types.root(base)] {
+                        ty @ (Type::Array { .. } | Type::Record { .. }) => ty.clone(),
+                        _ => return Err(CORRUPT),
+                    }
+                }
+                _ => return Err(InvalidData("unknown GHW type")),
+            };
+            types.table.push(types.types.len());
+            types.types.push(ty);
+            types.names.push(name);
+        }
+        if r.u8()? != 0 {
+            return Err(CORRUPT);
+        }
+        Ok(types)
+    }
+
+    fn id(&self, r: &mut Reader) -> Result<usize, InvalidData> {
+        let id = r.uleb()? as usize;
+        self.table.get(id.wrapping_sub(1)).copied().ok_or(CORRUPT)
+    }
+
+    /// The type a subtype constrains, recursively.
+    fn root(&self, mut ty: usize) -> usize {
+        while let Type::Scalar { base, .. }
+        | Type::ArraySub { base, .. }
+        | Type::RecordSub { base, .. } = self.types[ty]
+        {
+            ty = base;
+        }
+        ty
+    }
+
+    /// The number of basic signals of a value, `None` if unconstrained.
+    fn scalars(&self, ty: usize) -> Option<usize> {
+        match &self.types[ty] {
+            Type::Array { .. } => None,
+            Type::ArraySub { scalars, .. } | Type::RecordSub { scalars, .. } => Some(*scalars),
+            Type::Record { scalars, .. } => *scalars,
+            _ => Some(1),
+        }
+    }
+
+    /// A constrained subtype of `ty` whose bounds follow in the file.
+    fn bounds(&mut self, r: &mut Reader, ty: usize) -> Result<usize, InvalidData> {
+        let sub = match self.types[ty] {
+            Type::Array { .. } | Type::ArraySub { .. } => self.array_subtype(r, ty)?,
+            Type::Record { .. } | Type::RecordSub { .. } => self.record_subtype(r, ty)?,
+            _ => return Err(CORRUPT),
+        };
+        self.types.push(sub);
+        self.names.push(String::new());
+        Ok(self.types.len() - 1)
+    }
+
+    fn array_subtype(&mut self, r: &mut Reader, base: usize) -> Result<Type, InvalidData> {
+        let Type::Array { element, dims, .. } = self.types[self.root(base)] else {
+            return Err(CORRUPT);
+        };
+        let ranges = (0..dims)
+            .map(|_| read_range(r)?.ok_or(CORRUPT))
+            .collect::<Result<Vec<_>, _>>()?;
+        let element = match self.scalars(element) {
+            Some(_) => element,
+            None => self.bounds(r, element)?,
+        };
+        let count = ranges
+            .iter()
+            .try_fold(1usize, |n, range| n.checked_mul(range.len()))
+            .ok_or(CORRUPT)?;
+        let scalars = count
+            .checked_mul(self.scalars(element).ok_or(CORRUPT)?)
+            .ok_or(CORRUPT)?;
+        Ok(Type::ArraySub {
+            base,
+            ranges,
+            element,
+            scalars,
+        })
+    }
+
+    fn record_subtype(&mut self, r: &mut Reader, base: usize) -> Result<Type, InvalidData> {
+        let Type::Record {
+            fields, scalars, ..
+        } = self.types[self.root(base)].clone()
+        else {
+            return Err(CORRUPT);
+        };
+        let fields = match scalars {
+            Some(_) => fields,
+            None => fields
+                .into_iter()
+                .map(|(field, ty)| match self.scalars(ty) {
+                    Some(_) => Ok((field, ty)),
+                    None => Ok((field, self.bounds(r, ty)?)),
+                })
+                .collect::<Result<Vec<_>, InvalidData>>()?,
+        };
+        let scalars = fields
+            .iter()
+            .try_fold(0usize, |n, (_, ty)| n.checked_add(self.scalars(*ty)?))
+            .ok_or(CORRUPT)?;
+        Ok(Type::RecordSub {
+            base,
+            fields,
+            scalars,
+        })
+    }
+
+    /// How basic signals of a scalar type are stored.
+    fn basic(&self, ty: usize) -> Option<Basic> {
+        Some(match &self.types[self.root(ty)] {
+            Type::Enum { wkt, literals, .. } => match *wkt {
+                WKT_STD_ULOGIC => Basic::Logic,
+                WKT_BIT | WKT_BOOLEAN => Basic::Bit,
+                _ => {
+                    let max = literals.len().saturating_sub(1) as u64;
+                    Basic::Enum((u64::BITS - max.leading_zeros()).max(1))
+                }
+            },
+            Type::Integer { bits: 32, .. } => Basic::Int32,
+            Type::Integer { .. } => Basic::Int64,
+            Type::Real => Basic::Real,
+            _ => return None,
+        })
+    }
+
+    /// The variable kind and width of a type that is declared as one
+    /// variable: a scalar, or a vector of one-bit elements.
+    fn leaf(&self, ty: usize) -> Option<(VarKind, u32)> {
+        if let Type::ArraySub {
+            ranges,
+            element,
+            scalars,
+            ..
+        } = &self.types[ty]
+        {
+            let kind = match self.basic(*element)? {
+                Basic::Logic => VarKind::Logic,
+                Basic::Bit => VarKind::Bit,
+                _ => return None,
+            };
+            return (ranges.len() == 1).then_some((kind, *scalars as u32));
+        }
+        let basic = self.basic(ty)?;
+        Some(match basic {
+            Basic::Logic => (VarKind::Logic, 1),
+            Basic::Bit => (VarKind::Bit, 1),
+            Basic::Enum(bits) => (VarKind::Enum, bits),
+            Basic::Int32 | Basic::Int64 => {
+                let bits = if basic == Basic::Int32 { 32 } else { 64 };
+                match self.types[self.root(ty)] {
+                    Type::Integer { physical: true, .. } if bits == 64 => (VarKind::Time, 64),
+                    _ => (VarKind::Integer, bits),
+                }
+            }
+            Basic::Real => (VarKind::Real, 64),
+        })
+    }
+
+    /// The name of a type, looking through anonymous subtypes.
+    fn name(&self, mut ty: usize) -> &str {
+        while self.names[ty].is_empty() {
+            match self.types[ty] {
+                Type::Scalar { base, .. }
+                | Type::ArraySub { base, .. }
+                | Type::RecordSub { base, .. } => ty = base,
+                _ => break,
+            }
+        }
+        &self.names[ty]
+    }
+
+    fn vhdl(&self, ty: usize) -> VhdlType {
+        let kind = match &self.types[ty] {
+            Type::Enum { literals, .. } => VhdlKind::Enum(literals.clone()),
+            Type::Integer {
+                physical: false, ..
+            } => VhdlKind::Integer(None),
+            Type::Integer { .. } => VhdlKind::Physical(None),
+            Type::Real => VhdlKind::Real,
+            Type::Scalar { base, bounds } => match self.vhdl(*base).kind {
+                VhdlKind::Integer(outer) => VhdlKind::Integer(bounds.or(outer)),
+                VhdlKind::Physical(outer) => VhdlKind::Physical(bounds.or(outer)),
+                kind => kind,
+            },
+            Type::Array { element, .. } => VhdlKind::Array {
+                ranges: Vec::new(),
+                element: Box::new(self.vhdl(*element)),
+            },
+            Type::ArraySub {
+                ranges, element, ..
+            } => VhdlKind::Array {
+                ranges: ranges.clone(),
+                element: Box::new(self.vhdl(*element)),
+            },
+            Type::Record { fields, .. } | Type::RecordSub { fields, .. } => VhdlKind::Record(
+                fields
+                    .iter()
+                    .map(|(field, ty)| (field.clone(), self.vhdl(*ty)))
+                    .collect(),
+            ),
+        };
+        VhdlType {
+            name: self.name(ty).to_string(),
+            kind,
+        }
+    }
+
+    /// The text of a value of a scalar type, for the names of generate
+    /// iterations.
+    fn literal(&self, ty: usize, raw: u64) -> String {
+        match (&self.types[self.root(ty)], self.basic(ty)) {
+            (Type::Enum { literals, .. }, _) => {
+                literals.get(raw as usize).cloned().unwrap_or_default()
+            }
+            (Type::Real, _) => f64::from_bits(raw).to_string(),
+            _ => (raw as i64).to_string(),
+        }
+    }
+}
+
+fn read_range(r: &mut Reader) -> Result<Option<Bounds>, InvalidData> {
+    let t = r.u8()?;
+    let downto = t & 0x80 != 0;
+    let (left, right) = match t & 0x7f {
+        TYPE_B2 | TYPE_E8 => (r.u8()? as i64, r.u8()? as i64),
+        TYPE_I32 | TYPE_P32 | TYPE_I64 | TYPE_P64 => (r.sleb()?, r.sleb()?),
+        TYPE_F64 => {
+            r.f64()?;
+            r.f64()?;
+            return Ok(None);
+        }
+        _ => return Err(InvalidData("unknown GHW range")),
+    };
+    Ok(Some(Bounds {
+        left,
+        right,
+        downto,
+    }))
+}
+
+/// Declares the signals of the `HIE` section.
+struct Declarer<'a> {
+    types: &'a Types,
+    hierarchy: Hierarchy,
+    /// The scopes being declared, innermost last.
+    open: Vec<Scope>,
+    /// The basic signals of each signal, and the signal of each list.
+    signals: Vec<Vec<u32>>,
+    ids: HashMap<Vec<u32>, u64>,
+    /// How each basic signal is stored, by its number.
+    basics: Vec<Option<Basic>>,
+    signal_types: HashMap<SignalId, VhdlType>,
+    /// The record types of struct scopes, by dotted path.
+    scope_types: HashMap<String, VhdlType>,
+    enums: EnumMaps,
+}
+
+impl Declarer<'_> {
+    fn open_scope(&mut self, kind: ScopeKind, name: &str) {
+        let name = self.hierarchy.intern(name);
+        self.open.push(Scope::new(kind, name));
+    }
+
+    fn close_scope(&mut self) {
+        if let Some(scope) = self.open.pop() {
+            match self.open.last_mut() {
+                Some(parent) => parent.scopes.push(scope),
+                None => self.hierarchy.scopes.push(scope),
+            }
+        }
+    }
+
+    /// The hierarchy, closing the scopes left open.
+    fn finish(&mut self) -> Hierarchy {
+        while !self.open.is_empty() {
+            self.close_scope();
+        }
+        mem::take(&mut self.hierarchy)
+    }
+
+    /// Reads the basic signals of a value of type `ty`.
+    fn read_signals(
+        &mut self,
+        r: &mut Reader,
+        ty: usize,
+        out: &mut Vec<u32>,
+    ) -> Result<(), InvalidData> {
+        match &self.types.types[ty] {
+            Type::ArraySub {
+                element, scalars, ..
+            } => {
+                let each = self.types.scalars(*element).ok_or(CORRUPT)?;
+                for _ in 0..scalars.checked_div(each).unwrap_or(0) {
+                    self.read_signals(r, *element, out)?;
+                }
+            }
+            Type::Record { fields, .. } | Type::RecordSub { fields, .. } => {
+                for (_, field) in fields {
+                    self.read_signals(r, *field, out)?;
+                }
+            }
+            Type::Array { .. } => return Err(CORRUPT),
+            _ => {
+                let sig = r.uleb()?;
+                let slot = usize::try_from(sig)
+                    .ok()
+                    .filter(|&s| s > 0)
+                    .and_then(|s| self.basics.get_mut(s))
+                    .ok_or(InvalidData("invalid GHW signal number"))?;
+                slot.get_or_insert(self.types.basic(ty).ok_or(CORRUPT)?);
+                out.push(sig as u32);
+            }
+        }
+        Ok(())
+    }
+
+    fn var(
+        &mut self,
+        name: &str,
+        index: Option<ReferenceIndex>,
+        (kind, width): (VarKind, u32),
+        sigs: &[u32],
+        ty: impl FnOnce(&Types) -> VhdlType,
+    ) {
+        let signals = &mut self.signals;
+        let id = *self.ids.entry(sigs.to_vec()).or_insert_with(|| {
+            signals.push(sigs.to_vec());
+            signals.len() as u64 - 1
+        });
+        let id = SignalId(id);
+        if !self.signal_types.contains_key(&id) {
+            let ty = ty(self.types);
+            if let (VhdlKind::Enum(literals), VarKind::Enum | VarKind::Bit) = (&ty.kind, kind) {
+                let mut map = EnumMap::new();
+                for (position, literal) in literals.iter().enumerate() {
+                    map.insert(position as u64, literal);
+                }
+                self.enums.insert(id, map);
+            }
+            self.signal_types.insert(id, ty);
+        }
+        let var = Var {
+            kind,
+            width,
+            signal: id,
+            name: self.hierarchy.intern(name),
+            index,
+        };
+        match self.open.last_mut() {
+            Some(scope) => scope.vars.push(var),
+            None => self.hierarchy.vars.push(var),
+        }
+    }
+
+    fn declare(&mut self, name: &str, ty: usize, sigs: &[u32]) {
+        let types = self.types;
+        match &types.types[ty] {
+            Type::ArraySub {
+                ranges, element, ..
+            } => match types.leaf(ty) {
+                Some(leaf) => {
+                    let bounds = ranges[0];
+                    let index = i32::try_from(bounds.left)
+                        .ok()
+                        .zip(i32::try_from(bounds.right).ok())
+                        .map(|(l, r)| ReferenceIndex::Range(l, r));
+                    self.var(name, index, leaf, sigs, |types| types.vhdl(ty));
+                }
+                None => self.array(name, ty, ranges, *element, sigs),
+            },
+            Type::Record { fields, .. } | Type::RecordSub { fields, .. } => {
+                self.open_scope(ScopeKind::Struct, name);
+                let path: Vec<&str> = (self.open.iter())
+                    .map(|s| self.hierarchy.name(s.name))
+                    .collect();
+                self.scope_types.insert(path.join("."), types.vhdl(ty));
+                let mut at = 0;
+                for (field, ty) in fields {
+                    let n = types.scalars(*ty).unwrap_or(0).min(sigs.len() - at);
+                    self.declare(field, *ty, &sigs[at..at + n]);
+                    at += n;
+                }
+                self.close_scope();
+            }
+            _ => {
+                if let Some(leaf) = types.leaf(ty) {
+                    self.var(name, None, leaf, sigs, |types| types.vhdl(ty));
+                }
+            }
+        }
+    }
+
+    /// The elements of an array of type `ty` that is not a vector, along
+    /// its first dimension.
+    fn array(&mut self, name: &str, ty: usize, ranges: &[Bounds], element: usize, sigs: &[u32]) {
+        let Some((first, rest)) = ranges.split_first() else {
+            return;
+        };
+        let each = sigs.len().checked_div(first.len()).unwrap_or(0);
+        if each == 0 {
+            return;
+        }
+        // A vector element of a multi-dimensional array.
+        let vector = match (rest, self.types.leaf(element)) {
+            ([last], Some(leaf @ (VarKind::Logic | VarKind::Bit, 1))) => {
+                Some((leaf.0, last.len() as u32))
+            }
+            _ => None,
+        };
+        for (k, value) in first.values().enumerate() {
+            let part = &sigs[k * each..(k + 1) * each];
+            let index = i32::try_from(value).ok().map(ReferenceIndex::BitSelect);
+            if let (true, Some(leaf)) = (rest.is_empty(), self.types.leaf(element)) {
+                self.var(name, index, leaf, part, |types| types.vhdl(element));
+            } else if let (Some(vector), Some(index)) = (vector, index) {
+                // A row has no type of its own; it is named after the array.
+                self.var(name, Some(index), vector, part, |types| VhdlType {
+                    name: types.name(ty).to_string(),
+                    kind: VhdlKind::Array {
+                        ranges: rest.to_vec(),
+                        element: Box::new(types.vhdl(element)),
+                    },
+                });
+            } else if rest.is_empty() {
+                self.declare(&format!("{}[{}]", name, value), element, part);
+            } else {
+                self.array(&format!("{}[{}]", name, value), ty, rest, element, part);
+            }
+        }
+    }
+}
+
+/// A GHW file held in memory, usually through a memory map.
+///
+/// Opening reads the header and hierarchy; loading signals reads the
+/// snapshots and cycles once for all requested signals, as GHW has no
+/// index of them. Files compressed with gzip, which GHDL's tools also
+/// read, are decompressed into memory.
+pub struct GhwFile {
+    data: Data,
+    big_endian: bool,
+    hierarchy: Hierarchy,
+    /// The basic signals of each signal.
+    signals: Vec<Vec<u32>>,
+    basics: Vec<Option<Basic>>,
+    /// The numbers of the basic signals in use, which cycles count through.
+    used: Vec<u32>,
+    signal_types: HashMap<SignalId, VhdlType>,
+    scope_types: HashMap<String, VhdlType>,
+    enums: EnumMaps,
+    /// Where the snapshots and cycles start.
+    body: usize,
+}
+
+impl GhwFile {
+    /// Map a file and read its header and hierarchy.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<GhwFile> {
+        let file = File::open(path)?;
+        GhwFile::new(Data::Mapped(Mmap::open(&file)?))
+    }
+
+    /// Read a GHW file already in memory.
+    pub fn from_bytes(data: Vec<u8>) -> io::Result<GhwFile> {
+        GhwFile::new(Data::Owned(data))
+    }
+
+    fn new(data: Data) -> io::Result<GhwFile> {
+        let bytes = data.as_slice();
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            return GhwFile::new(Data::Owned(gunzip(bytes, 0)?));
+        }
+        let header = bytes.get(..16).ok_or(TRUNCATED)?;
+        if !header.starts_with(MAGIC) || header[9] != 16 || header[10] != 0 {
+            return Err(InvalidData("not a GHW file").into());
+        }
+        let version = header[11];
+        if version > 1 {
+            return Err(InvalidData("unsupported GHW version").into());
+        }
+        let big_endian = match header[12] {
+            1 => false,
+            2 => true,
+            _ => return Err(InvalidData("invalid GHW byte order").into()),
+        };
+        let mut r = Reader {
+            data: bytes,
+            pos: 16,
+            big_endian,
+        };
+        // The hierarchy refers to strings and types, so it comes after
+        // them.
+        let mut strings = Vec::new();
+        let mut types = Types::default();
+        loop {
+            match &r.tag()? {
+                b"STR\0" => strings = read_strings(&mut r)?,
+                b"TYP\0" => types = Types::read(&mut r, &strings, version)?,
+                b"WKT\0" => {
+                    r.zeros()?;
+                    loop {
+                        let wkt = r.u8()?;
+                        if wkt == 0 {
+                            break;
+                        }
+                        let id = types.id(&mut r)?;
+                        if let Type::Enum { wkt: known, .. } = &mut types.types[id] {
+                            *known = wkt;
+                        }
+                    }
+                }
+                b"HIE\0" => break,
+                _ => return Err(CORRUPT.into()),
+            }
+        }
+        let mut declarer = read_hierarchy(&mut r, &types, &strings)?;
+        if &r.tag()? != b"EOH\0" {
+            return Err(CORRUPT.into());
+        }
+        let used = (0..declarer.basics.len() as u32)
+            .filter(|&s| declarer.basics[s as usize].is_some())
+            .collect();
+        let body = r.pos;
+        Ok(GhwFile {
+            big_endian,
+            hierarchy: declarer.finish(),
+            signals: declarer.signals,
+            basics: declarer.basics,
+            used,
+            signal_types: declarer.signal_types,
+            scope_types: declarer.scope_types,
+            enums: declarer.enums,
+            body,
+            data,
+        })
+    }
+
+    /// The VHDL type of a signal. Signals of an element of an array or
+    /// record have the type of the element.
+    pub fn signal_type(&self, id: SignalId) -> Option<&VhdlType> {
+        self.signal_types.get(&id)
+    }
+
+    /// The record type of the struct scope at a dotted `path`.
+    pub fn scope_type(&self, path: &str) -> Option<&VhdlType> {
+        self.scope_types.get(path)
+    }
+
+    /// The literals of enumerated signals other than `std_ulogic`, by
+    /// the position each value is rendered as.
+    pub fn enum_maps(&self) -> &EnumMaps {
+        &self.enums
+    }
+}
+
+fn read_hierarchy<'a>(
+    r: &mut Reader,
+    types: &'a Types,
+    strings: &[String],
+) -> Result<Declarer<'a>, InvalidData> {
+    let string = |r: &mut Reader| -> Result<&str, InvalidData> {
+        let id = r.uleb()?;
+        strings.get(id as usize).map(String::as_str).ok_or(CORRUPT)
+    };
+    r.zeros()?;
+    let _scopes = r.i32()?;
+    let _signals = r.i32()?;
+    let basics = r.count()?;
+    let mut declarer = Declarer {
+        types,
+        hierarchy: Hierarchy::default(),
+        open: Vec::new(),
+        signals: Vec::new(),
+        ids: HashMap::new(),
+        basics: vec![None; basics + 1],
+        signal_types: HashMap::new(),
+        scope_types: HashMap::new(),
+        enums: EnumMaps::new(),
+    };
+    loop {
+        match r.u8()? {
+            HIE_END => break,
+            HIE_END_SCOPE => {
+                declarer.close_scope();
+            }
+            HIE_PROCESS => {
+                string(r)?;
+            }
+            kind @ (HIE_BLOCK | HIE_GENERATE_IF | HIE_GENERATE_FOR | HIE_INSTANCE | HIE_PACKAGE
+            | HIE_GENERIC) => {
+                let mut name = string(r)?.to_string();
+                if kind == HIE_GENERATE_FOR {
+                    let ty = types.id(r)?;
+                    let raw = types.basic(ty).ok_or(CORRUPT)?.read(r)?;
+                    name = format!("{}({})", name, types.literal(ty, raw));
+                }
+                let kind = match kind {
+                    HIE_BLOCK | HIE_GENERIC => ScopeKind::Begin,
+                    HIE_INSTANCE => ScopeKind::Module,
+                    HIE_PACKAGE => ScopeKind::Package,
+                    _ => ScopeKind::Generate,
+                };
+                declarer.open_scope(kind, &name);
+            }
+            HIE_SIGNAL..=HIE_PORT_LINKAGE => {
+                let name = string(r)?;
+                let ty = types.id(r)?;
+                // Each basic signal takes a byte at least.
+                let mut sigs = Vec::with_capacity(r.fits(types.scalars(ty).ok_or(CORRUPT)?)?);
+                declarer.read_signals(r, ty, &mut sigs)?;
+                declarer.declare(name, ty, &sigs);
+            }
+            _ => return Err(InvalidData("unknown GHW hierarchy entry")),
+        }
+    }
+    Ok(declarer)
+}
+
+/// The state of loading signals: the current values of the basic signals
+/// and the requested signals changed at the current time.
+struct Loading<'a> {
+    file: &'a GhwFile,
+    ids: &'a [SignalId],
+    /// The requested signals each basic signal is part of.
+    users: Vec<Vec<usize>>,
+    values: Vec<u64>,
+    dirty: Vec<bool>,
+    changed: Vec<usize>,
+    out: Vec<Signal>,
+    buf: Vec<u8>,
+    /// The time of the last snapshot or cycle.
+    time: u64,
+}
+
+impl Loading<'_> {
+    fn set(&mut self, r: &mut Reader, sig: u32) -> Result<(), InvalidData> {
+        let basic = self.file.basics[sig as usize].expect("used signal");
+        self.values[sig as usize] = basic.read(r)?;
+        for &i in &self.users[sig as usize] {
+            if !mem::replace(&mut self.dirty[i], true) {
+                self.changed.push(i);
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self, time: i64) -> Result<(), InvalidData> {
+        let time = u64::try_from(time).map_err(|_| CORRUPT)?;
+        if time < self.time {
+            return Err(InvalidData("GHW time goes backwards"));
+        }
+        self.time = time;
+        for i in self.changed.drain(..) {
+            self.dirty[i] = false;
+            self.buf.clear();
+            for &sig in &self.file.signals[self.ids[i].0 as usize] {
+                let basic = self.file.basics[sig as usize].expect("declared signal");
+                basic.render(self.values[sig as usize], &mut self.buf);
+            }
+            let signal = &mut self.out[i];
+            if signal.is_empty() || signal.value(signal.len() - 1) != self.buf {
+                signal.push(time, &self.buf);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SignalLoader for GhwFile {
+    fn load_signals(&mut self, ids: &[SignalId]) -> io::Result<Vec<Signal>> {
+        let mut users = vec![Vec::new(); self.basics.len()];
+        for (i, id) in ids.iter().enumerate() {
+            for &sig in self.signals.get(id.0 as usize).into_iter().flatten() {
+                users[sig as usize].push(i);
+            }
+        }
+        let mut state = Loading {
+            file: self,
+            ids,
+            users,
+            values: vec![0; self.basics.len()],
+            dirty: vec![false; ids.len()],
+            changed: Vec::new(),
+            out: vec![Signal::new(); ids.len()],
+            buf: Vec::new(),
+            time: 0,
+        };
+        let mut r = Reader {
+            data: self.data.as_slice(),
+            pos: self.body,
+            big_endian: self.big_endian,
+        };
+        while !r.at_end() {
+            match &r.tag()? {
+                b"SNP\0" => {
+                    r.zeros()?;
+                    let time = r.i64()?;
+                    for &sig in &self.used {
+                        state.set(&mut r, sig)?;
+                    }
+                    if &r.tag()? != b"ESN\0" {
+                        return Err(CORRUPT.into());
+                    }
+                    state.flush(time)?;
+                }
+                b"CYC\0" => {
+                    let mut time = r.i64()?;
+                    loop {
+                        // Each changed signal is counted from the one
+                        // before it, among the signals in use.
+                        let mut at = 0usize;
+                        loop {
+                            let delta = r.uleb()? as usize;
+                            if delta == 0 {
+                                break;
+                            }
+                            at = at.saturating_add(delta);
+                            let sig = *self.used.get(at - 1).ok_or(CORRUPT)?;
+                            state.set(&mut r, sig)?;
+                        }
+                        state.flush(time)?;
+                        match r.sleb()? {
+                            -1 => break,
+                            delta => time = time.checked_add(delta).ok_or(CORRUPT)?,
+                        }
+                    }
+                    if &r.tag()? != b"ECY\0" {
+                        return Err(CORRUPT.into());
+                    }
+                }
+                b"DIR\0" | b"TAI\0" => break,
+                _ => return Err(CORRUPT.into()),
+            }
+        }
+        Ok(state.out)
+    }
+}
+
+impl Waveform for GhwFile {
+    fn hierarchy(&self) -> &Hierarchy {
+        &self.hierarchy
+    }
+
+    fn timescale(&self) -> Option<Timescale> {
+        Some(Timescale::new(1, TimeUnit::FS))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::deflate::gzip;
+
+    /// GHW bytes, written after the layout `GhwFile` reads.
+    struct Ghw(Vec<u8>);
+
+    impl Ghw {
+        fn sleb(&mut self, mut v: i64) -> &mut Ghw {
+            loop {
+                let b = (v & 0x7f) as u8;
+                v >>= 7;
+                if (v == 0 && b & 0x40 == 0) || (v == -1 && b & 0x40 != 0) {
+                    self.0.push(b);
+                    return self;
+                }
+                self.0.push(b | 0x80);
+            }
+        }
+
+        fn bytes(&mut self, bytes: &[u8]) -> &mut Ghw {
+            self.0.extend_from_slice(bytes);
+            self
+        }
+
+        /// Strings sharing their prefix with the one before.
+        fn strings(&mut self, strings: &[&str]) -> &mut Ghw {
+            self.bytes(b"STR\0\0\0\0\0");
+            self.bytes(&(strings.len() as i32).to_le_bytes());
+            self.bytes(&0i32.to_le_bytes());
+            let mut shared = 0;
+            for (i, s) in strings.iter().enumerate() {
+                self.bytes(&s.as_bytes()[shared..]);
+                let next = strings.get(i + 1).map_or(&b""[..], |n| n.as_bytes());
+                shared = s.bytes().zip(next).take_while(|(a, b)| a == *b).count();
+                self.0.push(shared as u8);
+            }
+            self.bytes(b"EOS\0")
+        }
+    }
+
+    /// A design with scalar, vector, enumerated, record and real signals,
+    /// as checked in. The file is also read by wellen's GHW reader, which
+    /// agrees on its hierarchy and values.
+    pub(crate) fn sample() -> Vec<u8> {
+        include_bytes!("../testdata/design.ghw").to_vec()
+    }
+
+    /// Build the checked-in sample.
+    fn build() -> Vec<u8> {
+        let mut g = Ghw(Vec::new());
+        g.bytes(MAGIC).bytes(&[16, 0, 1, 1, 4, 8, 0]);
+        let mut sections = Vec::new();
+        sections.push((b"STR\0", g.0.len() as i32));
+        g.strings(&[
+            "top",
+            "clk",
+            "clk_in",
+            "std_ulogic",
+            "'U'",
+            "'X'",
+            "'0'",
+            "'1'",
+            "'Z'",
+            "'W'",
+            "'L'",
+            "'H'",
+            "'-'",
+            "integer",
+            "natural",
+            "std_ulogic_vector",
+            "data",
+            "boolean",
+            "false",
+            "true",
+            "ok",
+            "state_t",
+            "idle",
+            "run",
+            "done",
+            "state",
+            "pair",
+            "a",
+            "n",
+            "blk",
+            "p",
+            "real",
+            "v",
+            "proc",
+            "gen",
+        ]);
+        sections.push((b"TYP\0", g.0.len() as i32));
+        g.bytes(b"TYP\0\0\0\0\0").bytes(&9i32.to_le_bytes());
+        // 1: std_ulogic, 2: integer, 3: natural.
+        g.bytes(&[TYPE_E8, 4, 9, 5, 6, 7, 8, 9, 10, 11, 12, 13]);
+        g.bytes(&[TYPE_I32, 14]);
+        g.bytes(&[SUBTYPE_SCALAR, 15, 2, TYPE_I32, 0])
+            .sleb(i32::MAX as i64);
+        // 4: std_ulogic_vector, 5: its subtype (3 downto 0).
+        g.bytes(&[TYPE_ARRAY, 16, 1, 1, 3]);
+        g.bytes(&[SUBTYPE_ARRAY, 0, 4, TYPE_I32 | 0x80, 3, 0]);
+        // 6: boolean, 7: state_t, 8: pair of std_ulogic and natural,
+        // 9: real.
+        g.bytes(&[TYPE_B2, 18, 2, 19, 20]);
+        g.bytes(&[TYPE_E8, 22, 3, 23, 24, 25]);
+        g.bytes(&[TYPE_RECORD, 27, 2, 28, 1, 29, 3]);
+        g.bytes(&[TYPE_F64, 32, 0]);
+        sections.push((b"WKT\0", g.0.len() as i32));
+        g.bytes(b"WKT\0\0\0\0\0")
+            .bytes(&[WKT_STD_ULOGIC, 1, WKT_BOOLEAN, 6, 0]);
+        sections.push((b"HIE\0", g.0.len() as i32));
+        g.bytes(b"HIE\0\0\0\0\0");
+        for count in [4i32, 7, 11] {
+            g.bytes(&count.to_le_bytes());
+        }
+        g.bytes(&[HIE_INSTANCE, 1]);
+        g.bytes(&[HIE_SIGNAL, 2, 1, 1]);
+        g.bytes(&[HIE_SIGNAL, 17, 5, 2, 3, 4, 5]);
+        g.bytes(&[HIE_SIGNAL, 21, 6, 6]);
+        g.bytes(&[HIE_SIGNAL, 26, 7, 7]);
+        g.bytes(&[HIE_BLOCK, 30, HIE_SIGNAL, 31, 8, 8, 9, HIE_END_SCOPE]);
+        g.bytes(&[HIE_PROCESS, 34]);
+        g.bytes(&[HIE_GENERATE_FOR, 35, 3, 1, HIE_END_SCOPE]);
+        g.bytes(&[HIE_SIGNAL, 33, 9, 10]);
+        g.bytes(&[HIE_SIGNAL + 1, 3, 1, 1]);
+        g.bytes(&[HIE_END_SCOPE, HIE_END]);
+        sections.push((b"EOH\0", g.0.len() as i32));
+        g.bytes(b"EOH\0");
+        // clk = 'U', data = "0000", ok, state = idle, p = ('1', 5),
+        // v = 0.5.
+        g.bytes(b"SNP\0\0\0\0\0").bytes(&0i64.to_le_bytes());
+        g.bytes(&[0, 2, 2, 2, 2, 1, 0, 3]).sleb(5);
+        g.bytes(&0.5f64.to_le_bytes()).bytes(b"ESN\0");
+        g.bytes(b"CYC\0").bytes(&1_000_000i64.to_le_bytes());
+        // At 1 ns clk = '1' and data(2) = '1'.
+        g.bytes(&[1, 3, 2, 3, 0]).sleb(1_000_000);
+        // At 2 ns clk = '0', state = done and p.n = 300.
+        g.bytes(&[1, 2, 6, 2, 2])
+            .sleb(300)
+            .bytes(&[0])
+            .sleb(500_000);
+        // At 2.5 ns data(3) is set to the value it has.
+        g.bytes(&[2, 2, 0]).sleb(-1).bytes(b"ECY\0");
+        // The directory of the header sections, and the tailer pointing
+        // to it.
+        let dir = g.0.len() as i32;
+        g.bytes(b"DIR\0\x04\0\0\0").bytes(&5i32.to_le_bytes());
+        for (tag, pos) in sections {
+            g.bytes(tag).bytes(&pos.to_le_bytes());
+        }
+        g.bytes(b"EOD\0TAI\0\0\0\0\0").bytes(&dir.to_le_bytes());
+        g.0
+    }
+
+    fn load(ghw: &mut GhwFile, path: &str) -> Signal {
+        let signal = ghw.hierarchy().lookup(path).unwrap().signal;
+        ghw.load_signals(&[signal]).unwrap().remove(0)
+    }
+
+    #[test]
+    fn reads_a_design() {
+        let mut ghw = GhwFile::from_bytes(sample()).unwrap();
+        assert_eq!(ghw.timescale(), Some(Timescale::new(1, TimeUnit::FS)));
+        let hierarchy = ghw.hierarchy();
+        let var = |path| hierarchy.lookup(path).unwrap();
+        assert_eq!(var("top.clk").kind, VarKind::Logic);
+        assert_eq!(var("top.clk_in").signal, var("top.clk").signal);
+        let data = var("top.data");
+        assert_eq!((data.kind, data.width), (VarKind::Logic, 4));
+        assert_eq!(data.index, Some(ReferenceIndex::Range(3, 0)));
+        assert_eq!(var("top.ok").kind, VarKind::Bit);
+        assert_eq!(
+            (var("top.state").kind, var("top.state").width),
+            (VarKind::Enum, 2)
+        );
+        assert_eq!(var("top.v").kind, VarKind::Real);
+        let p = hierarchy.find_scope(&["top", "blk", "p"]).unwrap();
+        assert_eq!(p.kind, ScopeKind::Struct);
+        assert_eq!(var("top.blk.p.n").width, 32);
+        assert!(hierarchy.find_scope(&["top", "gen(1)"]).is_some());
+        assert!(hierarchy.find_scope(&["top", "proc"]).is_none());
+        assert_eq!(hierarchy.var_count(), 8);
+
+        let clk = load(&mut ghw, "top.clk");
+        assert_eq!(clk.times(), [0, 1_000_000, 2_000_000]);
+        assert_eq!(clk.value(0), b"u");
+        assert_eq!(clk.value(1), b"1");
+        let data = load(&mut ghw, "top.data");
+        assert_eq!(data.times(), [0, 1_000_000]);
+        assert_eq!(data.value(1), b"0100");
+        assert_eq!(load(&mut ghw, "top.ok").value(0), b"1");
+        let state = load(&mut ghw, "top.state");
+        assert_eq!(state.times(), [0, 2_000_000]);
+        assert_eq!(state.value(1), b"10");
+        let n = load(&mut ghw, "top.blk.p.n");
+        assert_eq!(n.value(0), format!("{:032b}", 5).as_bytes());
+        assert_eq!(n.value(1), format!("{:032b}", 300).as_bytes());
+        assert_eq!(load(&mut ghw, "top.blk.p.a").value(0), b"1");
+        assert_eq!(load(&mut ghw, "top.v").value(0), b"0.5");
+
+        let mut zipped = GhwFile::from_bytes(gzip(&sample())).unwrap();
+        assert_eq!(load(&mut zipped, "top.data"), load(&mut ghw, "top.data"));
+
+        // Types, from the signals and record scopes.
+        let signal = |path| ghw.hierarchy().lookup(path).unwrap().signal;
+        let data = ghw.signal_type(signal("top.data")).unwrap();
+        assert_eq!(data.name, "std_ulogic_vector");
+        let VhdlKind::Array { ranges, element } = &data.kind else {
+            panic!("not an array")
+        };
+        assert_eq!(
+            ranges.iter().map(Bounds::to_string).collect::<Vec<_>>(),
+            ["3 downto 0"]
+        );
+        assert_eq!(element.name, "std_ulogic");
+        assert!(matches!(&element.kind, VhdlKind::Enum(l) if l.len() == 9 && l[2] == "'0'"));
+        let ok = ghw.signal_type(signal("top.ok")).unwrap();
+        assert_eq!(ok.kind, VhdlKind::Enum(vec!["false".into(), "true".into()]));
+        let p = ghw.scope_type("top.blk.p").unwrap();
+        assert_eq!(p.name, "pair");
+        let VhdlKind::Record(fields) = &p.kind else {
+            panic!("not a record")
+        };
+        assert_eq!(fields[0].0, "a");
+        assert_eq!(
+            (fields[1].0.as_str(), fields[1].1.name.as_str()),
+            ("n", "natural")
+        );
+        let natural = Bounds {
+            left: 0,
+            right: i32::MAX as i64,
+            downto: false,
+        };
+        assert_eq!(fields[1].1.kind, VhdlKind::Integer(Some(natural)));
+        assert_eq!(ghw.signal_type(signal("top.blk.p.n")), Some(&fields[1].1));
+        assert_eq!(
+            ghw.signal_type(signal("top.v")).unwrap().kind,
+            VhdlKind::Real
+        );
+        assert!(ghw.scope_type("top.blk").is_none());
+        let enums = ghw.enum_maps();
+        assert_eq!(enums.name(signal("top.state"), b"10", 2), Some("done"));
+        assert_eq!(enums.name(signal("top.ok"), b"1", 1), Some("true"));
+        assert!(enums.get(signal("top.clk")).is_none());
+
+        let mut bad = sample();
+        bad[9] = 17;
+        assert!(GhwFile::from_bytes(bad).is_err());
+        assert!(GhwFile::from_bytes(sample()[..100].to_vec()).is_err());
+    }
+
+    #[test]
+    fn sample_is_built() {
+        assert_eq!(build(), sample());
+    }
+
+    #[test]
+    fn rejects_truncated_and_corrupt_files() {
+        let sample = sample();
+        let at = |tag: &[u8]| sample.windows(4).position(|w| w == tag).unwrap();
+        let header = at(b"EOH\0") + 4;
+        let invalid = |bytes: Vec<u8>| {
+            let err = GhwFile::from_bytes(bytes).err().expect("not rejected");
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        };
+        let load_all = |bytes: Vec<u8>| -> io::Result<Vec<Signal>> {
+            let mut ghw = GhwFile::from_bytes(bytes)?;
+            let signals = ghw.hierarchy().signal_ids();
+            ghw.load_signals(&signals)
+        };
+
+        // A file cut in its header is rejected; one cut in its body has
+        // the values up to where it ends, or is rejected.
+        for len in 0..sample.len() {
+            let cut = sample[..len].to_vec();
+            if len < header {
+                invalid(cut);
+            } else {
+                let _ = load_all(cut);
+            }
+        }
+
+        // Counts larger than the file are rejected before anything is
+        // allocated for them.
+        let mut strings = sample.clone();
+        strings[24..28].copy_from_slice(&i32::MAX.to_le_bytes());
+        invalid(strings);
+        let hie = at(b"HIE\0");
+        let mut basics = sample.clone();
+        basics[hie + 16..hie + 20].copy_from_slice(&i32::MAX.to_le_bytes());
+        invalid(basics);
+
+        // No corrupt byte makes the reader panic.
+        for i in 0..sample.len() {
+            for byte in [0, 0x7f, 0x80, 0xff] {
+                let mut bad = sample.clone();
+                bad[i] = byte;
+                let _ = load_all(bad);
+            }
+        }
+    }
+}
@@ -0,0 +1,394 @@
+//! GTKWave save files (`.gtkw`).
+//!
+//! A save file lists the traces shown in a GTKWave session, one per line,
+//! preceded by `@<hex>` lines setting the display flags (format, groups)
+//! of the traces that follow and `[color]` lines setting the color of the
+//! next one. [`parse_gtkw`] reads the traces and their options, and
+//! [`SaveFile::resolve`] matches them against a waveform's hierarchy so a
+//! session can be restored on another viewer, or dumped with another
+//! tool.
+//!
+//! Session geometry (`[size]`, `[pos]`, zoom) and markers are skipped.
+
+use std::str::FromStr;
+
+use crate::{Hierarchy, InvalidData, ReferenceIndex, SignalId, Var};
+
+/// GTKWave's trace flag bits, from its `analyzer.h`.
+mod flag {
+    pub const HEX: u64 = 1 << 1;
+    pub const DEC: u64 = 1 << 2;
+    pub const BIN: u64 = 1 << 3;
+    pub const OCT: u64 = 1 << 4;
+    pub const INVERT: u64 = 1 << 6;
+    pub const REVERSE: u64 = 1 << 7;
+    pub const BLANK: u64 = 1 << 9;
+    pub const SIGNED: u64 = 1 << 10;
+    pub const ASCII: u64 = 1 << 11;
+    pub const REAL: u64 = 1 << 18;
+    pub const CLOSED: u64 = 1 << 22;
+    pub const GRP_BEGIN: u64 = 1 << 23;
+    pub const GRP_END: u64 = 1 << 24;
+}
+
+/// How a trace value is written.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Format {
+    Binary,
+    Octal,
+    Hex,
+    Decimal,
+    SignedDecimal,
+    Ascii,
+    Real,
+}
+
+/// What a trace line shows.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceKind {
+    /// One signal, by its path as written by GTKWave, e.g. `top.pc[31:0]`.
+    Signal(String),
+    /// A vector concatenated from several signals (`#{name} a b`).
+    Concat(Vec<String>),
+    /// A blank line or comment (`-text`).
+    Comment,
+}
+
+/// One line of the trace list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    pub kind: TraceKind,
+    /// The name shown instead of the path (`+{alias}` or the
+    /// concatenation's name), or the comment text.
+    pub alias: Option<String>,
+    /// Index of the GTKWave color, if set.
+    pub color: Option<u32>,
+    pub format: Format,
+    /// Raw GTKWave flags; see [`Trace::inverted`] and
+    /// [`Trace::reversed`].
+    pub flags: u64,
+    /// Names of the enclosing groups, outermost first.
+    pub groups: Vec<String>,
+}
+
+impl Trace {
+    pub fn inverted(&self) -> bool {
+        self.flags & flag::INVERT != 0
+    }
+
+    /// Whether bits are shown in reverse order.
+    pub fn reversed(&self) -> bool {
+        self.flags & flag::REVERSE != 0
+    }
+}
+
+/// A group of traces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    pub name: String,
+    /// Whether the group is shown closed.
+    pub closed: bool,
+}
+
+/// The contents of a save file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SaveFile {
+    /// The waveform the session was saved with.
+    pub dumpfile: Option<String>,
+    /// The first time shown.
+    pub timestart: Option<u64>,
+    pub traces: Vec<Trace>,
+    pub groups: Vec<Group>,
+}
+
+fn format(flags: u64) -> Format {
+    if flags & flag::REAL != 0 {
+        Format::Real
+    } else if flags & flag::ASCII != 0 {
+        Format::Ascii
+    } else if flags & flag::DEC != 0 {
+        if flags & flag::SIGNED != 0 {
+            Format::SignedDecimal
+        } else {
+            Format::Decimal
+        }
+    } else if flags & flag::BIN != 0 {
+        Format::Binary
+    } else if flags & flag::OCT != 0 {
+        Format::Octal
+    } else {
+        Format::Hex
+    }
+}
+
+/// The name between `{` and `}` at the start of `s`, and the rest.
+fn braced(s: &str) -> Result<(&str, &str), InvalidData> {
+    let s = s
+        .strip_prefix('{')
+        .ok_or(InvalidData("expected {name} in save file"))?;
+    let (name, rest) = s
+        .split_once('}')
+        .ok_or(InvalidData("unterminated {name} in save file"))?;
+    Ok((name, rest.trim()))
+}
+
+/// Parse the text of a save file.
+pub fn parse_gtkw(text: &str) -> Result<SaveFile, InvalidData> {
+    let mut save = SaveFile::default();
+    let mut flags = flag::HEX;
+    let mut color = None;
+    let mut groups: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("[*]") || line.starts_with('*') {
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix('[') {
+            let (key, value) = rest
+                .split_once(']')
+                .ok_or(InvalidData("unterminated [key] in save file"))?;
+            let value = value.trim();
+            match key {
+                "dumpfile" => save.dumpfile = Some(value.trim_matches('"').to_string()),
+                "timestart" => save.timestart = value.parse().ok(),
+                "color" => {
+                    color = Some(
+                        value
+                            .parse()
+                            .map_err(|_| InvalidData("invalid color in save file"))?,
+                    )
+                }
+                _ => {}
+            }
+            continue;
+        }
+        if let Some(hex) = line.strip_prefix('@') {
+            flags = u64::from_str_radix(hex, 16)
+                .map_err(|_| InvalidData("invalid trace flags in save file"))?;
+            continue;
+        }
+        let mut alias = None;
+        let mut line = line;
+        if let Some(rest) = line.strip_prefix('+') {
+            let (name, rest) = braced(rest)?;
+            alias = Some(name.to_string());
+            line = rest;
+        }
+        let (kind, text) = if let Some(comment) = line.strip_prefix('-') {
+            if flags & flag::GRP_END != 0 {
+                groups.pop();
+                continue;
+            }
+            if flags & flag::GRP_BEGIN != 0 {
+                save.groups.push(Group {
+                    name: comment.to_string(),
+                    closed: flags & flag::CLOSED != 0,
+                });
+                groups.push(comment.to_string());
+                continue;
+            }
+            (TraceKind::Comment, Some(comment.to_string()))
+        } else if let Some(rest) = line.strip_prefix('#') {
+            let (name, parts) = braced(rest)?;
+            let parts = parts.split_whitespace().map(str::to_string).collect();
+            (TraceKind::Concat(parts), Some(name.to_string()))
+        } else if flags & flag::BLANK != 0 {
+            (TraceKind::Comment, Some(line.to_string()))
+        } else {
+            (TraceKind::Signal(line.to_string()), alias)
+        };
+        save.traces.push(Trace {
+            kind,
+            alias: text,
+            color: color.take(),
+            format: format(flags),
+            flags,
+            groups: groups.clone(),
+        });
+    }
+    Ok(save)
+}
+
+impl FromStr for SaveFile {
+    type Err = InvalidData;
+    fn from_str(s: &str) -> Result<SaveFile, InvalidData> {
+        parse_gtkw(s)
+    }
+}
+
+/// A trace matched against a hierarchy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedTrace {
+    /// Index of the trace in the saved list, e.g. [`SaveFile::traces`].
+    pub trace: usize,
+    /// The dot-separated paths of the variables shown, without GTKWave's
+    /// index suffixes, with their signals.
+    pub vars: Vec<(String, SignalId)>,
+}
+
+/// The result of [`SaveFile::resolve`] and
+/// [`SurferState::resolve`](crate::surfer::SurferState::resolve).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Resolution {
+    /// Signal and concatenation traces found, in order.
+    pub resolved: Vec<ResolvedTrace>,
+    /// Indices of the traces naming a variable missing from the hierarchy.
+    pub unresolved: Vec<usize>,
+}
+
+/// Whether the trace name `last` (the part after the last dot) names `var`,
+/// called `name`.
+/// GTKWave appends the index, or `[width-1:0]` for vectors without one.
+fn names(last: &str, name: &str, var: &Var) -> bool {
+    if last == name {
+        return true;
+    }
+    let Some(index) = last.strip_prefix(name) else {
+        return false;
+    };
+    let Ok(index) = index.parse::<ReferenceIndex>() else {
+        return false;
+    };
+    match var.index {
+        Some(own) => own == index,
+        None => index == ReferenceIndex::Range(var.width as i32 - 1, 0),
+    }
+}
+
+/// Find the variable GTKWave calls `name`.
+fn find(h: &Hierarchy, name: &str) -> Option<(String, SignalId)> {
+    // The index suffix is not part of the scope path.
+    let bracket = name.find('[').unwrap_or(name.len());
+    let (scope_path, last) = match name[..bracket].rfind('.') {
+        Some(dot) => (&name[..dot], &name[dot + 1..]),
+        None => ("", name),
+    };
+    let vars = if scope_path.is_empty() {
+        &h.vars
+    } else {
+        let parts: Vec<&str> = scope_path.split('.').collect();
+        &h.find_scope(&parts)?.vars
+    };
+    let var = vars.iter().find(|v| names(last, h.name(v.name), v))?;
+    let path = match scope_path {
+        "" => h.name(var.name).to_string(),
+        scope => format!("{}.{}", scope, h.name(var.name)),
+    };
+    Some((path, var.signal))
+}
+
+impl SaveFile {
+    /// Match the traces against `h`. Comments are neither resolved nor
+    /// unresolved.
+    pub fn resolve(&self, h: &Hierarchy) -> Resolution {
+        resolve(
+            h,
+            self.traces.iter().map(|trace| match &trace.kind {
+                TraceKind::Signal(name) => std::slice::from_ref(name),
+                TraceKind::Concat(parts) => parts.as_slice(),
+                TraceKind::Comment => &[],
+            }),
+        )
+    }
+}
+
+/// Match traces, each given by the names of the variables it shows,
+/// against `h`. Traces showing no variable are skipped.
+pub(crate) fn resolve<'a, I>(h: &Hierarchy, traces: I) -> Resolution
+where
+    I: IntoIterator<Item = &'a [String]>,
+{
+    let mut out = Resolution::default();
+    for (i, names) in traces.into_iter().enumerate() {
+        if names.is_empty() {
+            continue;
+        }
+        match names.iter().map(|n| find(h, n)).collect::<Option<Vec<_>>>() {
+            Some(vars) => out.resolved.push(ResolvedTrace { trace: i, vars }),
+            None => out.unresolved.push(i),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{VcdFile, Waveform};
+
+    const SAVE: &str = r#"[*]
+[*] GTKWave Analyzer v3.3.104 (w)1999-2020 BSI
+[*] Mon Oct 12 10:00:00 2026
+[*]
+[dumpfile] "/work/sim/dump.vcd"
+[timestart] 100
+[size] 1000 600
+[pos] -1 -1
+*-14.000000 1234 -1 -1 -1 -1 -1 -1 -1 -1 -1 -1 -1 -1 -1 -1 -1 -1 -1 -1 -1 -1
+[treeopen] top.
+[sst_width] 200
+@28
+top.clk
+[color] 3
+@22
+top.cpu.pc[31:0]
+@800200
+-Bus
+@424
++{offset} top.cpu.off[7:0]
+@24
+#{top.mixed} top.clk top.cpu.gone
+@200
+-
+-Comment text
+@1000200
+-Bus
+@28
+top.bits[3]
+top.missing
+"#;
+
+    #[test]
+    fn parse_and_resolve() {
+        let save = parse_gtkw(SAVE).unwrap();
+        assert_eq!(save.dumpfile.as_deref(), Some("/work/sim/dump.vcd"));
+        assert_eq!(save.timestart, Some(100));
+        assert_eq!(
+            save.groups,
+            [Group {
+                name: "Bus".to_string(),
+                closed: false
+            }]
+        );
+        let t = &save.traces;
+        assert_eq!(t.len(), 8);
+        assert_eq!(t[0].kind, TraceKind::Signal("top.clk".to_string()));
+        assert_eq!(t[0].format, Format::Binary);
+        assert_eq!((t[1].format, t[1].color), (Format::Hex, Some(3)));
+        assert_eq!(t[2].alias.as_deref(), Some("offset"));
+        assert_eq!(t[2].format, Format::SignedDecimal);
+        assert_eq!(t[2].groups, ["Bus"]);
+        assert_eq!(
+            t[3].kind,
+            TraceKind::Concat(vec!["top.clk".into(), "top.cpu.gone".into()])
+        );
+        assert_eq!(t[4].kind, TraceKind::Comment);
+        assert_eq!(t[5].alias.as_deref(), Some("Comment text"));
+        assert!(t[6].groups.is_empty());
+
+        let vcd = VcdFile::from_bytes(
+            b"$scope module top $end $var wire 1 ! clk $end $var wire 1 $ bits [3] $end
+$scope module cpu $end $var wire 32 \" pc $end $var integer 8 # off [7:0] $end
+$upscope $end $upscope $end $enddefinitions $end"
+                .to_vec(),
+        )
+        .unwrap();
+        let r = save.resolve(vcd.hierarchy());
+        let paths: Vec<&str> = r.resolved.iter().map(|t| t.vars[0].0.as_str()).collect();
+        assert_eq!(paths, ["top.clk", "top.cpu.pc", "top.cpu.off", "top.bits"]);
+        assert_eq!(r.resolved[1].vars[0].1, SignalId::from_code(b"\"").unwrap());
+        assert_eq!(r.unresolved, [3, 7]);
+
+        assert!(parse_gtkw("@zz\ntop.a").is_err());
+    }
+}
@@ -0,0 +1,552 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::{Interner, InvalidData, NameId, SignalId, Value};
+
+/// A type of scope, as used in the `$scope` command.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ScopeKind {
+    Module,
+    Task,
+    Function,
+    Begin,
+    Fork,
+    Generate,
+    Struct,
+    Union,
+    Class,
+    Interface,
+    Package,
+    Program,
+}
+
+impl FromStr for ScopeKind {
+    type Err = InvalidData;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use ScopeKind::*;
+        match s {
+            "module" => Ok(Module),
+            "task" => Ok(Task),
+            "function" => Ok(Function),
+            "begin" => Ok(Begin),
+            "fork" => Ok(Fork),
+            "generate" => Ok(Generate),
+            "struct" => Ok(Struct),
+            "union" => Ok(Union),
+            "class" => Ok(Class),
+            "interface" => Ok(Interface),
+            "package" => Ok(Package),
+            "program" => Ok(Program),
+            _ => Err(InvalidData("invalid scope type")),
+        }
+    }
+}
+
+impl Display for ScopeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ScopeKind::*;
+        f.write_str(match *self {
+            Module => "module",
+            Task => "task",
+            Function => "function",
+            Begin => "begin",
+            Fork => "fork",
+            Generate => "generate",
+            Struct => "struct",
+            Union => "union",
+            Class => "class",
+            Interface => "interface",
+            Package => "package",
+            Program => "program",
+        })
+    }
+}
+
+/// A type of variable, as used in the `$var` command.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum VarKind {
+    Event,
+    Integer,
+    Parameter,
+    Real,
+    RealTime,
+    Reg,
+    Supply0,
+    Supply1,
+    Time,
+    Tri,
+    TriAnd,
+    TriOr,
+    TriReg,
+    Tri0,
+    Tri1,
+    WAnd,
+    Wire,
+    WOr,
+    String,
+    Logic,
+    Bit,
+    Int,
+    ShortInt,
+    LongInt,
+    Byte,
+    Enum,
+    Port,
+}
+
+impl VarKind {
+    /// Whether changes of this variable are `r` (real) values.
+    pub fn is_real(&self) -> bool {
+        matches!(self, VarKind::Real | VarKind::RealTime)
+    }
+}
+
+impl FromStr for VarKind {
+    type Err = InvalidData;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use VarKind::*;
+        match s {
+            "event" => Ok(Event),
+            "integer" => Ok(Integer),
+            "parameter" => Ok(Parameter),
+            "real" => Ok(Real),
+            "realtime" => Ok(RealTime),
+            "reg" => Ok(Reg),
+            "supply0" => Ok(Supply0),
+            "supply1" => Ok(Supply1),
+            "time" => Ok(Time),
+            "tri" => Ok(Tri),
+            "triand" => Ok(TriAnd),
+            "trior" => Ok(TriOr),
+            "trireg" => Ok(TriReg),
+            "tri0" => Ok(Tri0),
+            "tri1" => Ok(Tri1),
+            "wand" => Ok(WAnd),
+            "wire" => Ok(Wire),
+            "wor" => Ok(WOr),
+            "string" => Ok(String),
+            "logic" => Ok(Logic),
+            "bit" => Ok(Bit),
+            "int" => Ok(Int),
+            "shortint" => Ok(ShortInt),
+            "longint" => Ok(LongInt),
+            "byte" => Ok(Byte),
+            "enum" => Ok(Enum),
+            "port" => Ok(Port),
+            _ => Err(InvalidData("invalid variable type")),
+        }
+    }
+}
+
+impl Display for VarKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use VarKind::*;
+        f.write_str(match *self {
+            Event => "event",
+            Integer => "integer",
+            Parameter => "parameter",
+            Real => "real",
+            RealTime => "realtime",
+            Reg => "reg",
+            Supply0 => "supply0",
+            Supply1 => "supply1",
+            Time => "time",
+            Tri => "tri",
+            TriAnd => "triand",
+            TriOr => "trior",
+            TriReg => "trireg",
+            Tri0 => "tri0",
+            Tri1 => "tri1",
+            WAnd => "wand",
+            Wire => "wire",
+            WOr => "wor",
+            String => "string",
+            Logic => "logic",
+            Bit => "bit",
+            Int => "int",
+            ShortInt => "shortint",
+            LongInt => "longint",
+            Byte => "byte",
+            Enum => "enum",
+            Port => "port",
+        })
+    }
+}
+
+/// Index of a variable reference, either a bit select index `[i]` or a range index `[msb:lsb]`
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReferenceIndex {
+    BitSelect(i32),
+    Range(i32, i32),
+}
+
+impl FromStr for ReferenceIndex {
+    type Err = InvalidData;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let inner = s
+            .trim()
+            .strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .ok_or(InvalidData("invalid reference index"))?;
+        let num = |s: &str| {
+            s.trim()
+                .parse::<i32>()
+                .map_err(|_| InvalidData("invalid reference index"))
+        };
+        match inner.split_once(':') {
+            Some((msb, lsb)) => Ok(ReferenceIndex::Range(num(msb)?, num(lsb)?)),
+            None => Ok(ReferenceIndex::BitSelect(num(inner)?)),
+        }
+    }
+}
+
+impl Display for ReferenceIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReferenceIndex::BitSelect(idx) => write!(f, "[{}]", idx),
+            ReferenceIndex::Range(msb, lsb) => write!(f, "[{}:{}]", msb, lsb),
+        }
+    }
+}
+
+/// A variable declared in the hierarchy.
+///
+/// Its name is interned in the [`Hierarchy`] declaring it, so variables
+/// only compare equal within one hierarchy.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Var {
+    pub kind: VarKind,
+    pub width: u32,
+    pub signal: SignalId,
+    pub name: NameId,
+    pub index: Option<ReferenceIndex>,
+}
+
+impl Var {
+    /// Decode a raw value of this variable's signal.
+    pub fn decode(&self, raw: &[u8]) -> Result<Value, InvalidData> {
+        Value::decode(self.kind, self.width, raw)
+    }
+}
+
+/// A scope and everything declared inside it.
+///
+/// Like a [`Var`], its name is interned in its [`Hierarchy`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Scope {
+    pub kind: ScopeKind,
+    pub name: NameId,
+    pub scopes: Vec<Scope>,
+    pub vars: Vec<Var>,
+}
+
+impl Scope {
+    pub fn new(kind: ScopeKind, name: NameId) -> Scope {
+        Scope {
+            kind,
+            name,
+            scopes: Vec::new(),
+            vars: Vec::new(),
+        }
+    }
+
+    /// Looks up a direct child scope by name.
+    pub fn find_scope(&self, name: NameId) -> Option<&Scope> {
+        self.scopes.iter().find(|s| s.name == name)
+    }
+
+    /// Looks up a variable declared directly in this scope.
+    pub fn find_var(&self, name: NameId) -> Option<&Var> {
+        self.vars.iter().find(|v| v.name == name)
+    }
+}
+
+/// The scopes and variables of a waveform.
+///
+/// VCD allows variables outside of any scope, so the top level holds both.
+/// The names of all of them are interned in the hierarchy, which shares
+/// its [`Interner`] with the [`FlatHierarchy`](crate::FlatHierarchy) it
+/// was built from or flattened into, and lookups by path resolve the path
+/// through it before comparing ids.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "HierarchyParts")
+)]
+pub struct Hierarchy {
+    names: Arc<Interner>,
+    pub scopes: Vec<Scope>,
+    pub vars: Vec<Var>,
+}
+
+/// The fields of a deserialized [`Hierarchy`], checked before use.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct HierarchyParts {
+    names: Interner,
+    scopes: Vec<Scope>,
+    vars: Vec<Var>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<HierarchyParts> for Hierarchy {
+    type Error = InvalidData;
+
+    fn try_from(p: HierarchyParts) -> Result<Hierarchy, InvalidData> {
+        fn named(n: usize, scopes: &[Scope], vars: &[Var]) -> bool {
+            vars.iter().all(|v| (v.name.0 as usize) < n)
+                && scopes
+                    .iter()
+                    .all(|s| (s.name.0 as usize) < n && named(n, &s.scopes, &s.vars))
+        }
+        if !named(p.names.len(), &p.scopes, &p.vars) {
+            return Err(InvalidData("name not in the hierarchy"));
+        }
+        Ok(Hierarchy {
+            names: Arc::new(p.names),
+            scopes: p.scopes,
+            vars: p.vars,
+        })
+    }
+}
+
+impl PartialEq for Hierarchy {
+    /// Hierarchies are equal if they declare the same scopes and
+    /// variables under the same names, whatever the ids of the names.
+    fn eq(&self, other: &Hierarchy) -> bool {
+        fn same_vars(a: (&Hierarchy, &[Var]), b: (&Hierarchy, &[Var])) -> bool {
+            a.1.len() == b.1.len()
+                && a.1.iter().zip(b.1).all(|(x, y)| {
+                    a.0.name(x.name) == b.0.name(y.name)
+                        && (x.kind, x.width, x.signal, x.index)
+                            == (y.kind, y.width, y.signal, y.index)
+                })
+        }
+        fn same_scopes(a: (&Hierarchy, &[Scope]), b: (&Hierarchy, &[Scope])) -> bool {
+            a.1.len() == b.1.len()
+                && a.1.iter().zip(b.1).all(|(x, y)| {
+                    x.kind == y.kind
+                        && a.0.name(x.name) == b.0.name(y.name)
+                        && same_vars((a.0, &x.vars), (b.0, &y.vars))
+                        && same_scopes((a.0, &x.scopes), (b.0, &y.scopes))
+                })
+        }
+        same_vars((self, &self.vars), (other, &other.vars))
+            && same_scopes((self, &self.scopes), (other, &other.scopes))
+    }
+}
+
+impl Hierarchy {
+    /// An empty hierarchy whose names are interned in `names`.
+    pub fn with_names(names: Arc<Interner>) -> Hierarchy {
+        Hierarchy {
+            names,
+            ..Hierarchy::default()
+        }
+    }
+
+    /// The id of `name`, interning it if it is new.
+    pub fn intern(&mut self, name: &str) -> NameId {
+        if let Some(id) = self.names.get(name) {
+            return id;
+        }
+        Arc::make_mut(&mut self.names).intern(name)
+    }
+
+    /// The name with id `id`.
+    pub fn name(&self, id: NameId) -> &str {
+        self.names.resolve(id)
+    }
+
+    /// The names of the scopes and variables.
+    pub fn names(&self) -> &Arc<Interner> {
+        &self.names
+    }
+
+    /// A copy of `var` of `from` with its name interned in `self`.
+    pub fn import_var(&mut self, from: &Hierarchy, var: &Var) -> Var {
+        Var {
+            name: self.intern(from.name(var.name)),
+            ..var.clone()
+        }
+    }
+
+    /// A copy of `scope` of `from` and everything inside it, with the
+    /// names interned in `self`.
+    pub fn import_scope(&mut self, from: &Hierarchy, scope: &Scope) -> Scope {
+        let mut copy = Scope::new(scope.kind, self.intern(from.name(scope.name)));
+        copy.vars = scope
+            .vars
+            .iter()
+            .map(|v| self.import_var(from, v))
+            .collect();
+        copy.scopes = scope
+            .scopes
+            .iter()
+            .map(|s| self.import_scope(from, s))
+            .collect();
+        copy
+    }
+
+    /// Find the scope object at a specified path.
+    pub fn find_scope<S>(&self, path: &[S]) -> Option<&Scope>
+    where
+        S: AsRef<str>,
+    {
+        let (first, rest) = path.split_first()?;
+        let first = self.names.get(first.as_ref())?;
+        let mut scope = self.scopes.iter().find(|s| s.name == first)?;
+        for name in rest {
+            scope = scope.find_scope(self.names.get(name.as_ref())?)?;
+        }
+        Some(scope)
+    }
+
+    /// Find the variable object at a specified path.
+    pub fn find_var<S>(&self, path: &[S]) -> Option<&Var>
+    where
+        S: AsRef<str>,
+    {
+        let (name, scope_path) = path.split_last()?;
+        let name = self.names.get(name.as_ref())?;
+        if scope_path.is_empty() {
+            return self.vars.iter().find(|v| v.name == name);
+        }
+        self.find_scope(scope_path)?.find_var(name)
+    }
+
+    /// Find a variable by its dot-separated path, e.g. `top.cpu.pc`.
+    pub fn lookup(&self, path: &str) -> Option<&Var> {
+        let parts: Vec<&str> = path.split('.').collect();
+        self.find_var(&parts)
+    }
+
+    /// Visit every variable depth-first together with its scope path.
+    pub fn for_each_var<'h, F>(&'h self, mut f: F)
+    where
+        F: FnMut(&[&'h str], &'h Var),
+    {
+        fn walk<'h, F>(names: &'h Interner, scope: &'h Scope, path: &mut Vec<&'h str>, f: &mut F)
+        where
+            F: FnMut(&[&'h str], &'h Var),
+        {
+            path.push(names.resolve(scope.name));
+            for var in &scope.vars {
+                f(path, var);
+            }
+            for child in &scope.scopes {
+                walk(names, child, path, f);
+            }
+            path.pop();
+        }
+
+        let mut path = Vec::new();
+        for var in &self.vars {
+            f(&path, var);
+        }
+        for scope in &self.scopes {
+            walk(&self.names, scope, &mut path, &mut f);
+        }
+    }
+
+    /// All variables with their full dot-separated paths, depth-first.
+    pub fn var_paths(&self) -> Vec<(String, &Var)> {
+        let mut out = Vec::new();
+        self.for_each_var(|scope, var| {
+            let mut path = scope.join(".");
+            if !path.is_empty() {
+                path.push('.');
+            }
+            path.push_str(self.name(var.name));
+            out.push((path, var));
+        });
+        out
+    }
+
+    /// Number of variables in the hierarchy.
+    pub fn var_count(&self) -> usize {
+        let mut n = 0;
+        self.for_each_var(|_, _| n += 1);
+        n
+    }
+
+    /// Distinct signal ids in declaration order.
+    pub fn signal_ids(&self) -> Vec<SignalId> {
+        let mut seen = HashSet::new();
+        let mut out = Vec::new();
+        self.for_each_var(|_, var| {
+            if seen.insert(var.signal) {
+                out.push(var.signal);
+            }
+        });
+        out
+    }
+
+    /// The variables sharing each signal.
+    pub fn aliases(&self) -> Aliases {
+        let mut aliases = Aliases::default();
+        for (path, var) in self.var_paths() {
+            let n = aliases.groups.len();
+            let group = *aliases.index.entry(var.signal).or_insert(n);
+            if group == n {
+                aliases.groups.push((var.signal, Vec::new()));
+            }
+            aliases.groups[group].1.push(path);
+        }
+        aliases
+    }
+}
+
+/// The paths of the variables carrying each signal.
+///
+/// Several variables may carry one signal: FST aliases, or VCD variables
+/// reusing an identifier code, typically a port seen from both sides of a
+/// module boundary. Their changes are stored once under the shared
+/// [`SignalId`], so loading or caching any alias serves all of them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Aliases {
+    /// Signals in declaration order with their paths, first declared first.
+    groups: Vec<(SignalId, Vec<String>)>,
+    index: HashMap<SignalId, usize>,
+}
+
+impl Aliases {
+    /// Paths of the variables carrying `id`, in declaration order.
+    pub fn paths(&self, id: SignalId) -> &[String] {
+        self.index.get(&id).map_or(&[], |&i| &self.groups[i].1)
+    }
+
+    /// The first declared path of `id`, naming it in reports.
+    pub fn canonical(&self, id: SignalId) -> Option<&str> {
+        self.paths(id).first().map(|p| p.as_str())
+    }
+
+    /// Whether more than one variable carries `id`.
+    pub fn is_aliased(&self, id: SignalId) -> bool {
+        self.paths(id).len() > 1
+    }
+
+    /// Signals carried by more than one variable, with their paths.
+    pub fn groups(&self) -> impl Iterator<Item = (SignalId, &[String])> {
+        self.groups
+            .iter()
+            .filter(|(_, paths)| paths.len() > 1)
+            .map(|(id, paths)| (*id, paths.as_slice()))
+    }
+
+    /// Number of variables sharing the signal of an earlier declared
+    /// variable.
+    pub fn alias_count(&self) -> usize {
+        self.groups.iter().map(|(_, paths)| paths.len() - 1).sum()
+    }
+}
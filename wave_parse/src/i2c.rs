@@ -0,0 +1,267 @@
+//! I2C bus decoding.
+//!
+//! [`decode`] turns the SCL and SDA signals of a bus into
+//! [`Transaction`]s. `1`, `h` and `z` count as high, since undriven lines
+//! are pulled up. Changes of both lines at the same time, which zero-delay
+//! simulations produce, are ordered the way a real bus would see them: a
+//! falling SCL edge before the SDA change, a rising one after it.
+//!
+//! Only 7-bit addresses are interpreted; for 10-bit addressing the first
+//! two bytes are reported as they appear on the bus.
+
+use std::ops::Range;
+
+use crate::{Logic, Signal};
+
+/// One byte on the bus with its acknowledge bit.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Byte {
+    /// Time of the rising SCL edge sampling the first bit.
+    pub time: u64,
+    pub value: u8,
+    /// Whether the receiver pulled SDA low in the ninth bit.
+    pub ack: bool,
+}
+
+/// Everything between a start condition and the next stop or repeated
+/// start.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Transaction {
+    /// Time of the start condition.
+    pub start: u64,
+    /// Time of the stop or repeated start condition ending it, `None` if
+    /// the signals end first.
+    pub end: Option<u64>,
+    /// Whether it began with a repeated start.
+    pub repeated_start: bool,
+    /// Complete bytes, starting with the address byte.
+    pub bytes: Vec<Byte>,
+    /// SCL low phases longer than twice the shortest one of the
+    /// transaction, usually a target stretching the clock.
+    pub stretched: Vec<Range<u64>>,
+}
+
+impl Transaction {
+    /// The 7-bit target address.
+    pub fn address(&self) -> Option<u8> {
+        self.bytes.first().map(|b| b.value >> 1)
+    }
+
+    /// Whether the controller reads from the target.
+    pub fn is_read(&self) -> bool {
+        self.bytes.first().is_some_and(|b| b.value & 1 == 1)
+    }
+
+    /// Whether the address byte was acknowledged.
+    pub fn address_ack(&self) -> bool {
+        self.bytes.first().is_some_and(|b| b.ack)
+    }
+
+    /// The bytes after the address.
+    pub fn data(&self) -> &[Byte] {
+        self.bytes.get(1..).unwrap_or_default()
+    }
+}
+
+/// The level of a bus line, `None` if unknown.
+fn level(value: &[u8]) -> Option<bool> {
+    let [c] = value else {
+        return None;
+    };
+    match Logic::from_char(*c)? {
+        Logic::One | Logic::H | Logic::Z => Some(true),
+        Logic::Zero | Logic::L => Some(false),
+        _ => None,
+    }
+}
+
+struct Decoder {
+    scl: bool,
+    sda: bool,
+    current: Option<Transaction>,
+    /// Bits of the byte being received and time of its first bit.
+    bits: Vec<bool>,
+    first_bit: u64,
+    scl_fell: Option<u64>,
+    low_phases: Vec<Range<u64>>,
+    out: Vec<Transaction>,
+}
+
+impl Decoder {
+    fn finish(&mut self, end: Option<u64>) {
+        if let Some(mut t) = self.current.take() {
+            t.end = end;
+            let shortest = self.low_phases.iter().map(|r| r.end - r.start).min();
+            if let Some(shortest) = shortest {
+                t.stretched = self
+                    .low_phases
+                    .drain(..)
+                    .filter(|r| r.end - r.start > 2 * shortest)
+                    .collect();
+            }
+            self.out.push(t);
+        }
+        self.low_phases.clear();
+        self.bits.clear();
+    }
+
+    fn sda(&mut self, time: u64, high: bool) {
+        if self.scl && self.sda && !high {
+            let repeated = self.current.is_some();
+            self.finish(Some(time));
+            self.current = Some(Transaction {
+                start: time,
+                repeated_start: repeated,
+                ..Default::default()
+            });
+            self.scl_fell = None;
+        } else if self.scl && !self.sda && high {
+            self.finish(Some(time));
+        }
+        self.sda = high;
+    }
+
+    fn scl(&mut self, time: u64, high: bool) {
+        if self.scl == high {
+            return;
+        }
+        self.scl = high;
+        let Some(t) = &mut self.current else {
+            return;
+        };
+        if !high {
+            self.scl_fell = Some(time);
+            return;
+        }
+        if let Some(fell) = self.scl_fell.take() {
+            self.low_phases.push(fell..time);
+        }
+        if self.bits.is_empty() {
+            self.first_bit = time;
+        }
+        self.bits.push(self.sda);
+        if self.bits.len() == 9 {
+            let value = self.bits[..8].iter().fold(0u8, |v, &b| (v << 1) | b as u8);
+            t.bytes.push(Byte {
+                time: self.first_bit,
+                value,
+                ack: !self.bits[8],
+            });
+            self.bits.clear();
+        }
+    }
+}
+
+/// Decode the transactions on an I2C bus.
+pub fn decode(scl: &Signal, sda: &Signal) -> Vec<Transaction> {
+    let mut d = Decoder {
+        scl: true,
+        sda: true,
+        current: None,
+        bits: Vec::new(),
+        first_bit: 0,
+        scl_fell: None,
+        low_phases: Vec::new(),
+        out: Vec::new(),
+    };
+    let (mut i, mut j) = (0, 0);
+    while i < scl.len() || j < sda.len() {
+        let t = match (i < scl.len(), j < sda.len()) {
+            (true, true) => scl.time(i).min(sda.time(j)),
+            (true, false) => scl.time(i),
+            _ => sda.time(j),
+        };
+        // The last value of each line at this time.
+        let mut clock = None;
+        while i < scl.len() && scl.time(i) == t {
+            clock = level(scl.value(i)).or(clock);
+            i += 1;
+        }
+        let mut data = None;
+        while j < sda.len() && sda.time(j) == t {
+            data = level(sda.value(j)).or(data);
+            j += 1;
+        }
+        if clock == Some(false) {
+            d.scl(t, false);
+        }
+        if let Some(high) = data {
+            d.sda(t, high);
+        }
+        if clock == Some(true) {
+            d.scl(t, true);
+        }
+    }
+    d.finish(None);
+    d.out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Drive a bus from `(scl, sda)` levels, one step per 10 ticks.
+    fn bus(steps: &[(u8, u8)]) -> (Signal, Signal) {
+        let (mut scl, mut sda) = (Signal::new(), Signal::new());
+        for (n, &(c, d)) in steps.iter().enumerate() {
+            scl.push(n as u64 * 10, &[b'0' + c]);
+            sda.push(n as u64 * 10, &[b'0' + d]);
+        }
+        (scl, sda)
+    }
+
+    fn byte(steps: &mut Vec<(u8, u8)>, value: u8, ack: bool) {
+        let bits = (0..8).rev().map(|i| (value >> i) & 1).chain([!ack as u8]);
+        for b in bits {
+            steps.push((0, b));
+            steps.push((1, b));
+        }
+        steps.push((0, 0));
+    }
+
+    #[test]
+    fn write_then_read() {
+        let mut steps = vec![(1, 1), (1, 0)];
+        byte(&mut steps, 0x50 << 1, true);
+        byte(&mut steps, 0xa5, true);
+        // Repeated start: release SDA with SCL low, raise SCL, pull SDA.
+        steps.extend([(0, 1), (1, 1), (1, 0)]);
+        byte(&mut steps, (0x50 << 1) | 1, true);
+        // Stretched low phase before the data byte.
+        steps.extend([(0, 0); 6]);
+        byte(&mut steps, 0x3c, false);
+        steps.extend([(1, 0), (1, 1)]);
+        let (scl, sda) = bus(&steps);
+
+        let t = decode(&scl, &sda);
+        assert_eq!(t.len(), 2);
+        assert_eq!(t[0].start, 10);
+        assert_eq!(t[0].address(), Some(0x50));
+        assert!(!t[0].is_read() && t[0].address_ack() && !t[0].repeated_start);
+        assert_eq!(t[0].data().len(), 1);
+        assert_eq!(t[0].data()[0].value, 0xa5);
+        assert_eq!(t[0].end, Some(t[1].start));
+        assert!(t[0].stretched.is_empty());
+
+        assert!(t[1].repeated_start && t[1].is_read());
+        assert_eq!(t[1].data()[0].value, 0x3c);
+        assert!(!t[1].data()[0].ack);
+        assert_eq!(t[1].stretched.len(), 1);
+        assert_eq!(t[1].end, Some((steps.len() as u64 - 1) * 10));
+    }
+
+    #[test]
+    fn simultaneous_edges_and_truncation() {
+        // SDA changes at the same time as SCL falls: not a stop condition.
+        let mut scl = Signal::new();
+        let mut sda = Signal::new();
+        for (t, c, d) in [(0, "1", "z"), (5, "1", "0"), (10, "0", "1"), (15, "1", "1")] {
+            scl.push(t, c.as_bytes());
+            sda.push(t, d.as_bytes());
+        }
+        let t = decode(&scl, &sda);
+        assert_eq!(t.len(), 1);
+        assert_eq!(t[0].end, None);
+        assert!(t[0].bytes.is_empty());
+    }
+}
@@ -0,0 +1,69 @@
+//! Conversion between VCD identifier codes and [`SignalId`]s.
+//!
+//! Codes are read in natural byte order, which gives consecutive ids for
+//! the codes generated by most simulators.
+
+use crate::{InvalidData, SignalId};
+
+const ID_CHAR_MIN: u8 = b'!';
+const ID_CHAR_MAX: u8 = b'~';
+const NUM_ID_CHARS: u64 = (ID_CHAR_MAX - ID_CHAR_MIN + 1) as u64;
+
+impl SignalId {
+    /// Parse a VCD identifier code.
+    #[inline]
+    pub fn from_code(v: &[u8]) -> Result<SignalId, InvalidData> {
+        if v.is_empty() {
+            return Err(InvalidData("ID cannot be empty"));
+        }
+        let mut result = 0u64;
+        for &i in v {
+            if !(ID_CHAR_MIN..=ID_CHAR_MAX).contains(&i) {
+                return Err(InvalidData("invalid characters in ID"));
+            }
+            let c = ((i - ID_CHAR_MIN) as u64) + 1;
+            result = result
+                .checked_mul(NUM_ID_CHARS)
+                .and_then(|x| x.checked_add(c))
+                .ok_or(InvalidData("ID too long"))?;
+        }
+        Ok(SignalId(result - 1))
+    }
+
+    /// The VCD identifier code of this id.
+    pub fn to_code(self) -> String {
+        let mut i = self.0;
+        let mut rev = Vec::new();
+        loop {
+            let r = i % NUM_ID_CHARS;
+            rev.push(r as u8 + ID_CHAR_MIN);
+            if i < NUM_ID_CHARS {
+                break;
+            }
+            i = i / NUM_ID_CHARS - 1;
+        }
+        rev.reverse();
+        String::from_utf8(rev).expect("id codes are ASCII")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::SignalId;
+
+    #[test]
+    fn round_trip() {
+        for i in 0..10000 {
+            let id = SignalId(i);
+            assert_eq!(SignalId::from_code(id.to_code().as_bytes()).unwrap(), id);
+        }
+        assert_eq!(SignalId::from_code(b"!").unwrap(), SignalId(0));
+        assert_eq!(SignalId::from_code(b"\"").unwrap(), SignalId(1));
+        assert_eq!(
+            SignalId::from_code(b"999999999n").unwrap().to_code(),
+            "999999999n"
+        );
+        assert!(SignalId::from_code(b"9999999999n").is_err());
+        assert!(SignalId::from_code(b"").is_err());
+    }
+}
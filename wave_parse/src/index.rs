@@ -0,0 +1,543 @@
+//! Sidecar indexes for reopening large VCD files quickly.
+//!
+//! A [`VcdIndex`] records what a full pass over a file found: the body
+//! split into blocks starting at timestamps with the time each block
+//! starts at, for every signal the blocks in which it changes, and the
+//! [blackouts](crate::Blackouts). Saved next to the dump as a `.vcdx`
+//! file together with the parsed header, it lets
+//! [`VcdFile::open_indexed`](crate::VcdFile::open_indexed) skip header
+//! parsing and lets signal loading parse only the blocks a signal appears
+//! in, instead of the whole body.
+//!
+//! An index is tied to the file it was built from by its length,
+//! modification time and a hash of its first and last bytes; a stale
+//! index is rebuilt.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::parallel;
+use crate::progress::Progress;
+use crate::snapshot::{
+    read_bytes, read_hierarchy, read_string, read_u64, read_u8, write_bytes, write_hierarchy,
+    write_str, write_u64,
+};
+use crate::vcd::{Header, SimulationCommand, Token};
+use crate::{Blackouts, InvalidData, Phase, SignalId, Timescale, VcdFile};
+
+const MAGIC: &[u8; 5] = b"VCDX\x02";
+
+/// Most blocks per file; larger bodies get larger blocks.
+pub const MAX_BLOCKS: usize = 4096;
+
+/// Bytes hashed at each end of the file to detect changes.
+const HASHED: usize = 1 << 16;
+
+/// What identifies the contents of a file without reading all of it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Stamp {
+    pub len: u64,
+    /// Modification time in nanoseconds since the epoch, 0 if unknown.
+    pub modified: u64,
+    pub hash: u64,
+}
+
+impl Stamp {
+    /// The stamp of `data`, read from the file with `metadata`.
+    pub fn new(metadata: &fs::Metadata, data: &[u8]) -> Stamp {
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_nanos() as u64);
+        // FNV-1a over both ends, without hashing short files twice.
+        let mut hash = 0xcbf2_9ce4_8422_2325u64;
+        let tail = data
+            .len()
+            .saturating_sub(HASHED)
+            .max(HASHED.min(data.len()));
+        for &b in data[..HASHED.min(data.len())].iter().chain(&data[tail..]) {
+            hash = (hash ^ b as u64).wrapping_mul(0x100_0000_01b3);
+        }
+        Stamp {
+            len: data.len() as u64,
+            modified,
+            hash,
+        }
+    }
+}
+
+/// Block layout and signal membership of a VCD body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VcdIndex {
+    stamp: Stamp,
+    body_start: usize,
+    /// Start offset and start time of every block; each block ends where
+    /// the next begins, the last at the end of the file.
+    blocks: Vec<(usize, u64)>,
+    end: usize,
+    /// Bitmap of the blocks each signal changes in.
+    signals: HashMap<SignalId, Vec<u64>>,
+    blackouts: Blackouts,
+}
+
+/// What one block contains.
+struct BlockScan {
+    time: Option<u64>,
+    signals: HashSet<SignalId>,
+    /// Times of the `$dumpoff` (`false`) and `$dumpon` (`true`) edges.
+    edges: Vec<(u64, bool)>,
+}
+
+impl VcdIndex {
+    /// Index a parsed file with a full pass over its body, on
+    /// [`threads`](VcdFile::threads) threads, reporting to the file's
+    /// [progress sink](VcdFile::set_progress) and stopping early if its
+    /// [token](VcdFile::set_cancel_token) is cancelled. `stamp` identifies
+    /// the file on disk, if any.
+    pub fn build(vcd: &VcdFile, stamp: Stamp) -> io::Result<VcdIndex> {
+        let data = vcd.bytes();
+        let ranges = parallel::split_at_timestamps(data, vcd.body_start()..data.len(), MAX_BLOCKS);
+        let mut index = VcdIndex {
+            stamp,
+            body_start: vcd.body_start(),
+            blocks: Vec::with_capacity(ranges.len()),
+            end: data.len(),
+            signals: HashMap::new(),
+            blackouts: Blackouts::default(),
+        };
+        let scans = scan_blocks(vcd, &ranges)?;
+        index.push_blocks(&ranges, scans, Vec::new());
+        Ok(index)
+    }
+
+    /// Extend the index of a file to `vcd`, the same file grown by
+    /// appending. Only the last block, which the new data may continue,
+    /// and the new data are scanned, in blocks about as large as the
+    /// existing ones. `stamp` identifies the grown file.
+    pub fn extend(&mut self, vcd: &VcdFile, stamp: Stamp) -> io::Result<()> {
+        let data = vcd.bytes();
+        if self.end > data.len() || self.body_start != vcd.body_start() {
+            return Err(InvalidData("index does not match file").into());
+        }
+        let block_size = (self.end - self.body_start) / self.blocks.len().max(1);
+        let (start, time) = self.blocks.pop().unwrap_or((self.body_start, 0));
+        let last = self.blocks.len();
+        for bits in self.signals.values_mut() {
+            if let Some(word) = bits.get_mut(last / 64) {
+                *word &= !(1 << (last % 64));
+            }
+        }
+        // The dump edges of the blocks kept, which are all before the last
+        // block starts.
+        let mut edges = Vec::new();
+        for range in self.blackouts.ranges() {
+            if range.start < time {
+                edges.push((range.start, false));
+            }
+            if range.end < time {
+                edges.push((range.end, true));
+            }
+        }
+
+        let blocks = (data.len() - start) / block_size.max(1);
+        let ranges = parallel::split_at_timestamps(data, start..data.len(), blocks);
+        let scans = scan_blocks(vcd, &ranges)?;
+        self.push_blocks(&ranges, scans, edges);
+        self.stamp = stamp;
+        self.end = data.len();
+        Ok(())
+    }
+
+    /// Append the blocks at `ranges` found by `scans`, and rebuild the
+    /// blackouts from the dump `edges` of the blocks before and theirs.
+    fn push_blocks(
+        &mut self,
+        ranges: &[Range<usize>],
+        scans: Vec<BlockScan>,
+        edges: Vec<(u64, bool)>,
+    ) {
+        let words = (self.blocks.len() + ranges.len()).div_ceil(64);
+        for bits in self.signals.values_mut() {
+            bits.resize(words, 0);
+        }
+        let mut time = self.blocks.last().map_or(0, |b| b.1);
+        for (range, scan) in ranges.iter().zip(&scans) {
+            let i = self.blocks.len();
+            // Block 0 may start with changes before the first timestamp,
+            // which count as time 0.
+            if i > 0 {
+                time = scan.time.unwrap_or(time);
+            }
+            self.blocks.push((range.start, time));
+            for &id in &scan.signals {
+                self.signals.entry(id).or_insert_with(|| vec![0; words])[i / 64] |= 1 << (i % 64);
+            }
+        }
+        let scanned = scans.into_iter().flat_map(|s| s.edges);
+        self.blackouts = Blackouts::from_edges(edges.into_iter().chain(scanned));
+    }
+
+    pub(crate) fn body_start(&self) -> usize {
+        self.body_start
+    }
+
+    /// Length of the indexed file.
+    pub(crate) fn file_len(&self) -> usize {
+        self.end
+    }
+
+    pub fn stamp(&self) -> Stamp {
+        self.stamp
+    }
+
+    pub fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Byte range of block `i`.
+    pub fn block(&self, i: usize) -> Range<usize> {
+        let end = self.blocks.get(i + 1).map_or(self.end, |b| b.0);
+        self.blocks[i].0..end
+    }
+
+    /// Time at which block `i` starts.
+    pub fn block_time(&self, i: usize) -> u64 {
+        self.blocks[i].1
+    }
+
+    /// Index of the block containing `time`, the last one starting at or
+    /// before it. Parsing from its start reaches `time` without reading
+    /// what comes before.
+    pub fn block_at(&self, time: u64) -> usize {
+        self.blocks
+            .partition_point(|&(_, t)| t <= time)
+            .saturating_sub(1)
+    }
+
+    /// The blocks in which any of `ids` changes, in file order.
+    pub fn blocks_of(&self, ids: &[SignalId]) -> Vec<usize> {
+        let mut union = vec![0u64; self.blocks.len().div_ceil(64)];
+        for bits in ids.iter().filter_map(|id| self.signals.get(id)) {
+            for (u, b) in union.iter_mut().zip(bits) {
+                *u |= b;
+            }
+        }
+        (0..self.blocks.len())
+            .filter(|&i| union[i / 64] & (1 << (i % 64)) != 0)
+            .collect()
+    }
+
+    /// The blackouts of the indexed file.
+    pub fn blackouts(&self) -> &Blackouts {
+        &self.blackouts
+    }
+
+    /// Whether the index describes `vcd`.
+    pub(crate) fn fits(&self, vcd: &VcdFile) -> bool {
+        self.file_len() == vcd.bytes().len() && self.body_start == vcd.body_start()
+    }
+
+    /// Write the index with the `header` of its file, so that reading it
+    /// back needs no header parsing.
+    pub fn write<W: Write>(&self, header: &Header, out: &mut W) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        write_u64(out, self.stamp.len)?;
+        write_u64(out, self.stamp.modified)?;
+        write_u64(out, self.stamp.hash)?;
+        let h = header;
+        for s in [&h.date, &h.version, &h.comment] {
+            match s {
+                Some(s) => {
+                    out.write_all(&[1])?;
+                    write_str(out, s)?;
+                }
+                None => out.write_all(&[0])?,
+            }
+        }
+        match h.timescale {
+            Some(ts) => {
+                out.write_all(&[1])?;
+                write_u64(out, ts.factor as u64)?;
+                write_str(out, ts.unit.as_str())?;
+            }
+            None => out.write_all(&[0])?,
+        }
+        write_hierarchy(out, h.hierarchy())?;
+        write_u64(out, self.body_start as u64)?;
+        write_u64(out, self.end as u64)?;
+        write_u64(out, self.blocks.len() as u64)?;
+        for &(offset, time) in &self.blocks {
+            write_u64(out, offset as u64)?;
+            write_u64(out, time)?;
+        }
+        write_u64(out, self.signals.len() as u64)?;
+        for (id, bits) in &self.signals {
+            write_u64(out, id.0)?;
+            let bytes: Vec<u8> = bits.iter().flat_map(|w| w.to_le_bytes()).collect();
+            write_bytes(out, &bytes)?;
+        }
+        write_u64(out, self.blackouts.ranges().len() as u64)?;
+        for range in self.blackouts.ranges() {
+            write_u64(out, range.start)?;
+            write_u64(out, range.end)?;
+        }
+        Ok(())
+    }
+
+    /// Read an index and file header written by [`write`](VcdIndex::write).
+    pub fn read<R: Read>(input: &mut R) -> io::Result<(Header, VcdIndex)> {
+        let mut magic = [0; 5];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(InvalidData("not a VCD index").into());
+        }
+        let stamp = Stamp {
+            len: read_u64(input)?,
+            modified: read_u64(input)?,
+            hash: read_u64(input)?,
+        };
+        let mut text = || -> io::Result<Option<String>> {
+            match read_u8(input)? {
+                0 => Ok(None),
+                _ => Ok(Some(read_string(input)?)),
+            }
+        };
+        let (date, version, comment) = (text()?, text()?, text()?);
+        let timescale = match read_u8(input)? {
+            0 => None,
+            _ => {
+                let factor = u32::try_from(read_u64(input)?)
+                    .map_err(|_| InvalidData("invalid timescale in index"))?;
+                Some(Timescale::new(factor, read_string(input)?.parse()?))
+            }
+        };
+        let mut header = Header::default();
+        header.date = date;
+        header.version = version;
+        header.comment = comment;
+        header.timescale = timescale;
+        header.set_hierarchy(read_hierarchy(input)?);
+
+        let offset = |v: u64| usize::try_from(v).map_err(|_| InvalidData("offset too large"));
+        let body_start = offset(read_u64(input)?)?;
+        let end = offset(read_u64(input)?)?;
+        let mut blocks = Vec::new();
+        let mut last = body_start;
+        for _ in 0..read_u64(input)? {
+            let start = offset(read_u64(input)?)?;
+            if start < last || start > end {
+                return Err(InvalidData("blocks out of order").into());
+            }
+            last = start;
+            blocks.push((start, read_u64(input)?));
+        }
+        let words = blocks.len().div_ceil(64);
+        let mut signals = HashMap::new();
+        for _ in 0..read_u64(input)? {
+            let id = SignalId(read_u64(input)?);
+            let bytes = read_bytes(input)?;
+            if bytes.len() != words * 8 {
+                return Err(InvalidData("invalid block bitmap").into());
+            }
+            let bits = bytes
+                .chunks_exact(8)
+                .map(|w| u64::from_le_bytes(w.try_into().expect("8 bytes")))
+                .collect();
+            signals.insert(id, bits);
+        }
+        let mut ranges = Vec::new();
+        for _ in 0..read_u64(input)? {
+            ranges.push(read_u64(input)?..read_u64(input)?);
+        }
+        let index = VcdIndex {
+            stamp,
+            body_start,
+            blocks,
+            end,
+            signals,
+            blackouts: Blackouts::new(ranges),
+        };
+        Ok((header, index))
+    }
+}
+
+/// Scan the blocks at `ranges` of `vcd`; see [`VcdIndex::build`].
+fn scan_blocks(vcd: &VcdFile, ranges: &[Range<usize>]) -> io::Result<Vec<BlockScan>> {
+    let total = ranges.iter().map(|r| r.len() as u64).sum();
+    let progress = Progress::new(vcd.progress_sink(), vcd.cancel_token(), Phase::Index, total);
+    vcd.check_order(ranges)?;
+    parallel::map_chunks(vcd.bytes(), ranges, vcd.threads(), |mut tokens| {
+        let mut scan = BlockScan {
+            time: None,
+            signals: HashSet::new(),
+            edges: Vec::new(),
+        };
+        let start = tokens.position();
+        let mut time = 0;
+        for token in tokens.by_ref() {
+            match token? {
+                Token::Timestamp(t) => {
+                    scan.time.get_or_insert(t);
+                    time = t;
+                }
+                Token::Change(c) => {
+                    scan.signals.insert(c.signal()?);
+                }
+                Token::Begin(SimulationCommand::Dumpoff) => scan.edges.push((time, false)),
+                Token::Begin(SimulationCommand::Dumpon) => scan.edges.push((time, true)),
+                _ => {}
+            }
+        }
+        progress.add(tokens.position() - start)?;
+        Ok(scan)
+    })
+}
+
+/// Where the index of the VCD file at `path` is kept: the same name with
+/// a `.vcdx` extension.
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    path.with_extension("vcdx")
+}
+
+/// Read the header and index at `path` if they exist and match `stamp`.
+pub(crate) fn load_sidecar(path: &Path, stamp: Stamp) -> Option<(Header, VcdIndex)> {
+    let file = File::open(path).ok()?;
+    let (header, index) = VcdIndex::read(&mut BufReader::new(file)).ok()?;
+    (index.stamp == stamp).then_some((header, index))
+}
+
+/// Write `index` to `path`, through a temporary file so readers never
+/// see a partial index.
+pub(crate) fn save_sidecar(path: &Path, header: &Header, index: &VcdIndex) -> io::Result<()> {
+    let tmp = path.with_extension("vcdx.tmp");
+    let mut out = BufWriter::new(File::create(&tmp)?);
+    index.write(header, &mut out)?;
+    out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SignalLoader;
+
+    fn dump() -> Vec<u8> {
+        let mut text = b"$scope module t $end $var wire 4 ! a $end $var wire 1 \" b $end \
+                         $var wire 1 # late $end $upscope $end $enddefinitions $end\n0#\n"
+            .to_vec();
+        for t in 0..300_000u64 {
+            text.extend_from_slice(format!("#{}\nb{:b} !\n{}\"\n", t, t % 16, t % 2).as_bytes());
+            if t == 290_000 {
+                text.extend_from_slice(b"1#\n");
+            }
+        }
+        text
+    }
+
+    #[test]
+    fn indexed_loading() {
+        let mut vcd = VcdFile::from_bytes(dump()).unwrap();
+        let stamp = Stamp {
+            len: 0,
+            modified: 0,
+            hash: 0,
+        };
+        let index = VcdIndex::build(&vcd, stamp).unwrap();
+        assert!(index.block_count() > 2);
+        let late = [SignalId(2)];
+        // Set before the first timestamp and once near the end.
+        assert_eq!(index.blocks_of(&late), [0, index.block_count() - 1]);
+        assert_eq!(index.block_at(0), 0);
+        let mid = index.block(1);
+        assert_eq!(vcd.bytes()[mid.start], b'#');
+        assert_eq!(index.block_at(u64::MAX), index.block_count() - 1);
+
+        let mut bytes = Vec::new();
+        index.write(vcd.header(), &mut bytes).unwrap();
+        let (header, copy) = VcdIndex::read(&mut &bytes[..]).unwrap();
+        assert_eq!(&header, vcd.header());
+        assert_eq!(copy, index);
+
+        let ids = [SignalId(0), SignalId(1), SignalId(2)];
+        let full = vcd.load_signals(&ids).unwrap();
+        vcd.set_index(copy).unwrap();
+        assert_eq!(vcd.load_signals(&ids).unwrap(), full);
+        assert_eq!(vcd.load_signals(&late).unwrap()[0].times(), &[0, 290_000]);
+    }
+
+    #[test]
+    fn range_queries() {
+        let mut vcd = VcdFile::from_bytes(dump()).unwrap();
+        let ids = [SignalId(0), SignalId(2)];
+        let full = vcd.load_signals(&ids).unwrap();
+        let collect = |vcd: &VcdFile, id, range: Range<u64>| -> Vec<(u64, Vec<u8>)> {
+            vcd.changes_in(id, range)
+                .map(|c| c.map(|(t, v)| (t, v.to_vec())).unwrap())
+                .collect()
+        };
+        let expect = |signal: &crate::Signal, range: Range<u64>| -> Vec<(u64, Vec<u8>)> {
+            signal
+                .changes_in(range)
+                .map(|(t, v)| (t, v.to_vec()))
+                .collect()
+        };
+        let windows = [0..3, 150_000..150_004, 289_999..300_000, 400_000..500_000];
+        for range in windows.clone() {
+            assert_eq!(
+                collect(&vcd, ids[0], range.clone()),
+                expect(&full[0], range)
+            );
+        }
+        let stamp = Stamp {
+            len: 0,
+            modified: 0,
+            hash: 0,
+        };
+        vcd.set_index(VcdIndex::build(&vcd, stamp).unwrap())
+            .unwrap();
+        for range in windows {
+            for (&id, signal) in ids.iter().zip(&full) {
+                assert_eq!(
+                    collect(&vcd, id, range.clone()),
+                    expect(signal, range.clone())
+                );
+            }
+        }
+        assert_eq!(
+            collect(&vcd, ids[1], 1..u64::MAX),
+            [(290_000, b"1".to_vec())]
+        );
+    }
+
+    #[test]
+    fn sidecar_files() {
+        let dir = std::env::temp_dir().join(format!("wave_parse_index_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("dump.vcd");
+        fs::write(&path, dump()).unwrap();
+
+        let mut first = VcdFile::open_indexed(&path).unwrap();
+        assert!(sidecar_path(&path).exists());
+        let mut second = VcdFile::open_indexed(&path).unwrap();
+        assert!(second.index().is_some());
+        assert_eq!(second.header(), first.header());
+        let ids = [SignalId(2)];
+        assert_eq!(
+            first.load_signals(&ids).unwrap(),
+            second.load_signals(&ids).unwrap()
+        );
+
+        // A changed file gets a fresh index.
+        let mut changed = dump();
+        changed.extend_from_slice(b"#300000\n0#\n");
+        fs::write(&path, &changed).unwrap();
+        let mut third = VcdFile::open_indexed(&path).unwrap();
+        assert_eq!(third.load_signals(&ids).unwrap()[0].len(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
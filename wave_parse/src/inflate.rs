@@ -0,0 +1,385 @@
+//! A small decoder for raw DEFLATE streams (RFC 1951).
+//!
+//! Only what archive-based capture formats, LXT2 and FST need:
+//! whole-buffer decoding without streaming, plus the zlib and gzip
+//! containers. The decoder follows zlib's `puff` reference
+//! implementation, trading speed for brevity. The tables and checksums
+//! are shared with the [encoder](crate::deflate).
+
+use crate::InvalidData;
+
+const MAX_BITS: usize = 15;
+
+pub(crate) const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+pub(crate) const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+pub(crate) const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+pub(crate) const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order in which code length code lengths are stored.
+const CLEN_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+const TRUNCATED: InvalidData = InvalidData("truncated deflate stream");
+
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buf: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn bits(&mut self, n: u32) -> Result<u32, InvalidData> {
+        while self.count < n {
+            let b = *self.data.get(self.pos).ok_or(TRUNCATED)?;
+            self.pos += 1;
+            self.buf |= (b as u32) << self.count;
+            self.count += 8;
+        }
+        let v = self.buf & ((1u64 << n) - 1) as u32;
+        self.buf = ((self.buf as u64) >> n) as u32;
+        self.count -= n;
+        Ok(v)
+    }
+
+    fn align(&mut self) {
+        self.buf = 0;
+        self.count = 0;
+    }
+}
+
+/// A canonical Huffman code.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Huffman, InvalidData> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &l in lengths {
+            counts[l as usize] += 1;
+        }
+        let mut left = 1i32;
+        for &c in &counts[1..] {
+            left = (left << 1) - c as i32;
+            if left < 0 {
+                return Err(InvalidData("over-subscribed deflate code"));
+            }
+        }
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (sym, &l) in lengths.iter().enumerate() {
+            if l != 0 {
+                symbols[offsets[l as usize] as usize] = sym as u16;
+                offsets[l as usize] += 1;
+            }
+        }
+        counts[0] = 0;
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits<'_>) -> Result<u16, InvalidData> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= bits.bits(1)? as i32;
+            let count = count as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(InvalidData("invalid deflate code"))
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    let lit = Huffman::new(&lengths).expect("fixed code is complete");
+    let dist = Huffman::new(&[5; 30]).expect("fixed code is complete");
+    (lit, dist)
+}
+
+fn dynamic_codes(bits: &mut Bits<'_>) -> Result<(Huffman, Huffman), InvalidData> {
+    let nlen = bits.bits(5)? as usize + 257;
+    let ndist = bits.bits(5)? as usize + 1;
+    let ncode = bits.bits(4)? as usize + 4;
+    if nlen > 286 || ndist > 30 {
+        return Err(InvalidData("invalid deflate code counts"));
+    }
+    let mut clens = [0u8; 19];
+    for &i in &CLEN_ORDER[..ncode] {
+        clens[i] = bits.bits(3)? as u8;
+    }
+    let clen = Huffman::new(&clens)?;
+
+    let mut lengths = vec![0u8; nlen + ndist];
+    let mut i = 0;
+    while i < lengths.len() {
+        let sym = clen.decode(bits)?;
+        let (value, repeat) = match sym {
+            0..=15 => (sym as u8, 1),
+            16 => {
+                let prev = *i
+                    .checked_sub(1)
+                    .and_then(|p| lengths.get(p))
+                    .ok_or(InvalidData("deflate repeat without previous length"))?;
+                (prev, 3 + bits.bits(2)? as usize)
+            }
+            17 => (0, 3 + bits.bits(3)? as usize),
+            _ => (0, 11 + bits.bits(7)? as usize),
+        };
+        if i + repeat > lengths.len() {
+            return Err(InvalidData("too many deflate code lengths"));
+        }
+        lengths[i..i + repeat].fill(value);
+        i += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err(InvalidData("deflate code without end of block"));
+    }
+    Ok((
+        Huffman::new(&lengths[..nlen])?,
+        Huffman::new(&lengths[nlen..])?,
+    ))
+}
+
+fn codes(
+    bits: &mut Bits<'_>,
+    out: &mut Vec<u8>,
+    lit: &Huffman,
+    dist: &Huffman,
+) -> Result<(), InvalidData> {
+    loop {
+        let sym = lit.decode(bits)? as usize;
+        match sym {
+            0..=255 => out.push(sym as u8),
+            256 => return Ok(()),
+            _ => {
+                let i = sym - 257;
+                if i >= LENGTH_BASE.len() {
+                    return Err(InvalidData("invalid deflate length code"));
+                }
+                let len = LENGTH_BASE[i] as usize + bits.bits(LENGTH_EXTRA[i] as u32)? as usize;
+                let d = dist.decode(bits)? as usize;
+                if d >= DIST_BASE.len() {
+                    return Err(InvalidData("invalid deflate distance code"));
+                }
+                let back = DIST_BASE[d] as usize + bits.bits(DIST_EXTRA[d] as u32)? as usize;
+                if back > out.len() {
+                    return Err(InvalidData("deflate distance too far back"));
+                }
+                let start = out.len() - back;
+                for k in 0..len {
+                    out.push(out[start + k]);
+                }
+            }
+        }
+    }
+}
+
+/// The capacity to reserve for decoding `len` compressed bytes that a
+/// container says decode to `size_hint` bytes. The size comes from the
+/// file and may be corrupt, so no more than a few times the input is
+/// reserved up front.
+pub(crate) fn capacity(size_hint: usize, len: usize) -> usize {
+    size_hint.min(len.saturating_mul(4))
+}
+
+/// Decode a complete raw DEFLATE stream.
+pub(crate) fn inflate(data: &[u8], size_hint: usize) -> Result<Vec<u8>, InvalidData> {
+    let mut out = Vec::with_capacity(capacity(size_hint, data.len()));
+    let mut bits = Bits {
+        data,
+        pos: 0,
+        buf: 0,
+        count: 0,
+    };
+    loop {
+        let last = bits.bits(1)? == 1;
+        match bits.bits(2)? {
+            0 => {
+                bits.align();
+                let header = data.get(bits.pos..bits.pos + 4).ok_or(TRUNCATED)?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                let nlen = u16::from_le_bytes([header[2], header[3]]);
+                if len != !nlen {
+                    return Err(InvalidData("corrupt stored deflate block"));
+                }
+                let start = bits.pos + 4;
+                let block = data.get(start..start + len as usize).ok_or(TRUNCATED)?;
+                out.extend_from_slice(block);
+                bits.pos = start + len as usize;
+            }
+            1 => {
+                let (lit, dist) = fixed_codes();
+                codes(&mut bits, &mut out, &lit, &dist)?;
+            }
+            2 => {
+                let (lit, dist) = dynamic_codes(&mut bits)?;
+                codes(&mut bits, &mut out, &lit, &dist)?;
+            }
+            _ => return Err(InvalidData("invalid deflate block type")),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+/// Decode a zlib stream (RFC 1950), checking its Adler-32 trailer.
+pub(crate) fn zlib_decode(data: &[u8], size_hint: usize) -> Result<Vec<u8>, InvalidData> {
+    const CORRUPT: InvalidData = InvalidData("corrupt zlib stream");
+    if data.len() < 6
+        || data[0] & 0x0f != 8
+        || !u16::from_be_bytes([data[0], data[1]]).is_multiple_of(31)
+    {
+        return Err(CORRUPT);
+    }
+    if data[1] & 0x20 != 0 {
+        return Err(InvalidData("zlib stream with a preset dictionary"));
+    }
+    let out = inflate(&data[2..], size_hint)?;
+    let trailer = &data[data.len() - 4..];
+    if adler32(&out).to_be_bytes() != trailer {
+        return Err(CORRUPT);
+    }
+    Ok(out)
+}
+
+/// Decode a single-member gzip stream (RFC 1952), checking its CRC.
+pub(crate) fn gunzip(data: &[u8], size_hint: usize) -> Result<Vec<u8>, InvalidData> {
+    const CORRUPT: InvalidData = InvalidData("corrupt gzip stream");
+    if data.len() < 18 || data[..3] != [0x1f, 0x8b, 8] {
+        return Err(CORRUPT);
+    }
+    let flags = data[3];
+    let mut pos = 10;
+    if flags & 4 != 0 {
+        let extra = data.get(pos..pos + 2).ok_or(CORRUPT)?;
+        pos += 2 + u16::from_le_bytes([extra[0], extra[1]]) as usize;
+    }
+    for flag in [8, 16] {
+        if flags & flag != 0 {
+            let end = data.get(pos..).and_then(|d| d.iter().position(|&b| b == 0));
+            pos += end.ok_or(CORRUPT)? + 1;
+        }
+    }
+    if flags & 2 != 0 {
+        pos += 2;
+    }
+    let body = data.get(pos..data.len() - 8).ok_or(CORRUPT)?;
+    let out = inflate(body, size_hint)?;
+    let trailer = &data[data.len() - 8..];
+    if crc32(&out).to_le_bytes() != trailer[..4] || (out.len() as u32).to_le_bytes() != trailer[4..]
+    {
+        return Err(CORRUPT);
+    }
+    Ok(out)
+}
+
+/// The Adler-32 checksum used by zlib.
+pub(crate) fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+/// The CRC-32 checksum used by zip and gzip.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn block_types() {
+        // zlib's raw deflate output for each block type.
+        let stored = [1, 6, 0, 249, 255, b's', b't', b'o', b'r', b'e', b'd'];
+        assert_eq!(inflate(&stored, 0).unwrap(), b"stored");
+        // A corrupt size is only a hint.
+        assert_eq!(inflate(&stored, usize::MAX).unwrap(), b"stored");
+
+        let fixed = [75, 76, 74, 78, 68, 66, 10, 25, 169, 57, 57, 249, 200, 36, 0];
+        assert_eq!(
+            inflate(&fixed, 0).unwrap(),
+            b"abcabcabcabcabc hello hello hello"
+        );
+
+        let dynamic = [
+            53, 142, 203, 21, 192, 32, 12, 195, 238, 76, 209, 62, 22, 32, 124, 219, 117, 186, 255,
+            16, 16, 43, 189, 57, 18, 193, 201, 37, 125, 229, 186, 83, 182, 19, 204, 67, 245, 32,
+            212, 60, 137, 117, 49, 193, 161, 40, 58, 229, 69, 151, 162, 232, 195, 91, 225, 151,
+            108, 20, 176, 73, 155, 49, 96, 42, 31, 97, 104, 141, 157, 206, 191, 152, 193, 128, 153,
+            81, 131, 90, 49, 225, 226, 132, 88, 251, 143, 112, 183, 1,
+        ];
+        let expected: Vec<u8> = (0..20)
+            .flat_map(|i| format!("#{}\nb{:b} !\n", i * 10, i).into_bytes())
+            .collect();
+        assert_eq!(inflate(&dynamic, 0).unwrap(), expected);
+
+        assert_eq!(inflate(&[3, 0], 0).unwrap(), b"");
+        assert!(inflate(&fixed[..5], 0).is_err());
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn containers() {
+        // Python's zlib.compress(b"hello") and gzip.compress(b"hello", mtime=0).
+        let zlib = [120, 156, 203, 72, 205, 201, 201, 7, 0, 6, 44, 2, 21];
+        assert_eq!(zlib_decode(&zlib, 0).unwrap(), b"hello");
+        let gzip = [
+            31, 139, 8, 0, 0, 0, 0, 0, 2, 3, 203, 72, 205, 201, 201, 7, 0, 134, 166, 16, 54, 5, 0,
+            0, 0,
+        ];
+        assert_eq!(gunzip(&gzip, 0).unwrap(), b"hello");
+        let mut bad = gzip;
+        bad[17] ^= 1;
+        assert!(gunzip(&bad, 0).is_err());
+        let mut bad = zlib;
+        bad[12] ^= 1;
+        assert!(zlib_decode(&bad, 0).is_err());
+    }
+}
@@ -0,0 +1,186 @@
+//! Interned names.
+//!
+//! Generated hierarchies repeat a few scope and variable names, such as
+//! `genblk1`, `clk` or `data`, across millions of declarations. An
+//! [`Interner`] keeps each distinct name once, back to back in one
+//! buffer, and hands out [`NameId`]s, 4 bytes each, for structures to
+//! refer to them by. A path stored as a slice of ids then costs little
+//! more than its depth.
+
+use std::collections::HashMap;
+use std::mem;
+
+/// A name in an [`Interner`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NameId(pub u32);
+
+/// Marks the end of a hash chain.
+const NONE: u32 = u32::MAX;
+
+/// A set of distinct names, each with a [`NameId`] in order of insertion.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    data: String,
+    /// Start of each name in `data`; it ends where the next starts.
+    starts: Vec<u32>,
+    /// The last name inserted with each hash.
+    heads: HashMap<u64, u32>,
+    /// The name inserted before each name with the same hash.
+    chain: Vec<u32>,
+}
+
+/// FNV-1a, which is fast on the short strings names are.
+fn hash(name: &str) -> u64 {
+    let mut h = 0xcbf2_9ce4_8422_2325u64;
+    for &b in name.as_bytes() {
+        h = (h ^ b as u64).wrapping_mul(0x100_0000_01b3);
+    }
+    h
+}
+
+impl Interner {
+    pub fn new() -> Interner {
+        Interner::default()
+    }
+
+    /// The id of `name`, adding it if it is new.
+    ///
+    /// # Panics
+    ///
+    /// If the names would exceed 4 GiB or 2³² - 1 names.
+    pub fn intern(&mut self, name: &str) -> NameId {
+        let h = hash(name);
+        if let Some(id) = self.find(h, name) {
+            return id;
+        }
+        let id = u32::try_from(self.starts.len())
+            .ok()
+            .filter(|&id| id != NONE)
+            .expect("too many interned names");
+        let start = u32::try_from(self.data.len()).expect("interned names too long");
+        self.data.push_str(name);
+        u32::try_from(self.data.len()).expect("interned names too long");
+        self.starts.push(start);
+        self.chain.push(self.heads.insert(h, id).unwrap_or(NONE));
+        NameId(id)
+    }
+
+    fn find(&self, h: u64, name: &str) -> Option<NameId> {
+        let mut id = *self.heads.get(&h)?;
+        while id != NONE {
+            if self.resolve(NameId(id)) == name {
+                return Some(NameId(id));
+            }
+            id = self.chain[id as usize];
+        }
+        None
+    }
+
+    /// The id of `name`, if it was interned.
+    pub fn get(&self, name: &str) -> Option<NameId> {
+        self.find(hash(name), name)
+    }
+
+    /// The name with id `id`.
+    ///
+    /// # Panics
+    ///
+    /// If `id` is not from this interner.
+    pub fn resolve(&self, id: NameId) -> &str {
+        let i = id.0 as usize;
+        let end = self
+            .starts
+            .get(i + 1)
+            .map_or(self.data.len(), |&e| e as usize);
+        &self.data[self.starts[i] as usize..end]
+    }
+
+    /// The dot-separated path of the names `ids`.
+    pub fn path(&self, ids: &[NameId]) -> String {
+        let mut out = String::new();
+        for (i, &id) in ids.iter().enumerate() {
+            if i > 0 {
+                out.push('.');
+            }
+            out.push_str(self.resolve(id));
+        }
+        out
+    }
+
+    /// The ids of the parts of the dot-separated `path`, if all were
+    /// interned.
+    pub fn lookup_path(&self, path: &str) -> Option<Vec<NameId>> {
+        path.split('.').map(|part| self.get(part)).collect()
+    }
+
+    /// Number of distinct names.
+    pub fn len(&self) -> usize {
+        self.starts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.starts.is_empty()
+    }
+
+    /// Iterate over the names in order of their ids.
+    pub fn iter(&self) -> impl Iterator<Item = (NameId, &str)> {
+        (0..self.len() as u32).map(|i| (NameId(i), self.resolve(NameId(i))))
+    }
+
+    /// Heap memory held, in bytes, approximately.
+    pub fn size_bytes(&self) -> usize {
+        self.data.capacity()
+            + (self.starts.capacity() + self.chain.capacity()) * mem::size_of::<u32>()
+            + self.heads.capacity() * (mem::size_of::<(u64, u32)>() + 1)
+    }
+}
+
+/// Serializes as the sequence of names in order of their ids.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Interner {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(self.iter().map(|(_, name)| name))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Interner {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Interner, D::Error> {
+        let mut names = Interner::new();
+        for name in Vec::<String>::deserialize(d)? {
+            let next = names.len();
+            if names.intern(&name).0 as usize != next {
+                return Err(serde::de::Error::custom("duplicate interned name"));
+            }
+        }
+        Ok(names)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn interns_once() {
+        let mut names = Interner::new();
+        let a = names.intern("genblk1");
+        let b = names.intern("clk");
+        assert_eq!(names.intern("genblk1"), a);
+        assert_eq!(names.intern(""), NameId(2));
+        assert_eq!((names.resolve(a), names.resolve(b)), ("genblk1", "clk"));
+        assert_eq!(names.resolve(NameId(2)), "");
+        assert_eq!(names.len(), 3);
+        assert_eq!(names.get("clk"), Some(b));
+        assert_eq!(names.get("data"), None);
+
+        let path = names.lookup_path("genblk1.genblk1.clk").unwrap();
+        assert_eq!(path, [a, a, b]);
+        assert_eq!(names.path(&path), "genblk1.genblk1.clk");
+        assert!(names.lookup_path("genblk1.data").is_none());
+
+        let all: Vec<&str> = names.iter().map(|(_, n)| n).collect();
+        assert_eq!(all, ["genblk1", "clk", ""]);
+    }
+}
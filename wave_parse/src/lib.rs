@@ -0,0 +1,209 @@
+//! Waveform access primitives for large simulation dumps.
+//!
+//! The crate is built around the observation that viewers and analysis
+//! scripts only ever touch a small fraction of the signals in a dump.
+//! Signal data is therefore loaded on demand through a [`SignalLoader`]
+//! and kept in a [`SignalStore`], which evicts the least recently used
+//! signals once a memory budget is exceeded, optionally spilling them to a
+//! temporary file.
+//!
+//! VCD files are read through [`VcdFile`], which memory-maps the file and
+//! tokenizes it without per-token allocations (see the [`vcd`] module).
+//! FST files, GHDL's GHW files and GTKWave's LXT2 files are read through
+//! [`FstFile`], [`GhwFile`] and [`Lxt2File`].
+//!
+//! A VCD file that a simulator is still writing can be read as it grows
+//! with [`FollowReader`], or kept open as a [`VcdFile`] and brought up to
+//! date with [`VcdFile::refresh`], extending its index and a
+//! [`SignalStore`] with the appended changes. Both are for VCD files
+//! only: an FST writer leaves the hierarchy out until the file is closed.
+//!
+//! The crate has no dependencies by default and builds for
+//! `wasm32-unknown-unknown`, for viewers running in a browser. There, files
+//! without a file system are passed in with [`VcdFile::from_bytes`] or
+//! [`VcdFile::from_source`], and signals are loaded on a single thread.
+//!
+//! ## Features
+//!
+//! - `datafusion`: SQL queries over signals through the `sql` module,
+//!   e.g. `SELECT time, value FROM sig('top.cpu.pc')`.
+//! - `https`: `https://` URLs in [`source::HttpSource`], over rustls.
+//! - `object_store`: objects in S3, GCS and Azure through
+//!   `source::ObjectSource`.
+//! - `serde`: serde's `Serialize` and `Deserialize` for the hierarchy,
+//!   signal, time and value types.
+//!
+//! ## Example
+//!
+//! ```
+//! use std::io;
+//! use wave_parse::{Signal, SignalId, SignalLoader, SignalStore};
+//!
+//! /// A loader producing a constant clock for every requested signal.
+//! struct Clocks;
+//!
+//! impl SignalLoader for Clocks {
+//!     fn load_signals(&mut self, ids: &[SignalId]) -> io::Result<Vec<Signal>> {
+//!         Ok(ids.iter().map(|_| {
+//!             let mut s = Signal::new();
+//!             for t in 0..8 {
+//!                 s.push(t * 5, if t % 2 == 0 { b"0" } else { b"1" });
+//!             }
+//!             s
+//!         }).collect())
+//!     }
+//! }
+//!
+//! let mut store = SignalStore::new(Clocks, 1 << 20);
+//! let clk = store.get(SignalId(0)).unwrap();
+//! assert_eq!(clk.len(), 8);
+//! assert_eq!(clk.value(1), b"1");
+//! ```
+
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io;
+
+mod signal;
+pub use signal::{Signal, SignalId, SignalIter};
+
+mod packed;
+pub use packed::{PackedIter, PackedSignal};
+
+mod idcode;
+mod scan;
+mod slice;
+mod spill;
+mod varint;
+
+mod store;
+pub use store::{SignalLoader, SignalStore};
+
+mod progress;
+pub use progress::{CancelToken, Cancelled, Phase, ProgressSink};
+
+mod time;
+pub use time::{Rescaled, Time, TimeUnit, Timescale};
+
+mod blackout;
+pub use blackout::{Blackouts, Sample};
+
+mod value;
+pub use value::{Logic, LogicVec, States, Value};
+
+mod numeric;
+pub use numeric::Encoding;
+
+mod enums;
+pub use enums::{EnumMap, EnumMaps};
+
+mod downsample;
+pub use downsample::{Bucket, MinMaxIndex};
+
+mod intern;
+pub use intern::{Interner, NameId};
+
+mod hierarchy;
+pub use hierarchy::{Aliases, Hierarchy, ReferenceIndex, Scope, ScopeKind, Var, VarKind};
+
+mod flat;
+pub use flat::{ChildScopes, FlatBuilder, FlatHierarchy, FlatScope, FlatVar, ScopeId, VarId};
+
+pub mod mmap;
+
+pub mod vcd;
+pub use vcd::{Changes, VcdFile};
+
+mod write;
+pub use write::{write_waveform, VcdWriter, WriteOptions};
+
+pub mod fst;
+pub use fst::{write_fst, FstFile, FstWriter};
+
+pub mod ghw;
+pub use ghw::GhwFile;
+
+pub mod lxt2;
+pub use lxt2::Lxt2File;
+
+pub mod parallel;
+
+pub mod follow;
+pub use follow::FollowReader;
+
+mod memory;
+pub use memory::MemoryWaveform;
+
+pub mod csv;
+pub mod saleae;
+pub mod sigrok;
+
+pub mod activity;
+pub mod align;
+pub mod arrow;
+pub mod axi;
+pub mod bus;
+pub mod clock;
+pub mod convert;
+pub mod coverage;
+pub mod derived;
+pub mod diff;
+pub mod edges;
+pub mod expr;
+pub mod ffi;
+pub mod format;
+pub mod gtkw;
+pub mod i2c;
+pub mod index;
+pub mod members;
+pub mod merge;
+pub mod parquet;
+pub mod search;
+pub mod snapshot;
+pub mod source;
+#[cfg(feature = "datafusion")]
+pub mod sql;
+pub mod stats;
+pub mod surfer;
+pub mod task;
+
+mod deflate;
+mod fastlz;
+mod inflate;
+mod lz4;
+mod zip;
+
+/// A waveform: a hierarchy of variables whose signals load on demand.
+pub trait Waveform: SignalLoader {
+    /// The scopes and variables of the waveform.
+    fn hierarchy(&self) -> &Hierarchy;
+
+    /// The duration of one time tick, if the source declares it.
+    fn timescale(&self) -> Option<Timescale>;
+
+    /// When the source was written, if it says.
+    fn date(&self) -> Option<&str> {
+        None
+    }
+
+    /// The time ranges in which the source recorded no values. Sources
+    /// without such ranges have none.
+    fn blackouts(&self) -> io::Result<Blackouts> {
+        Ok(Blackouts::default())
+    }
+}
+
+/// Error wrapping a static string message explaining why parsing failed.
+#[derive(Debug)]
+pub struct InvalidData(pub(crate) &'static str);
+impl Display for InvalidData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+impl Error for InvalidData {}
+impl From<InvalidData> for io::Error {
+    fn from(e: InvalidData) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, e.0)
+    }
+}
@@ -0,0 +1,948 @@
+//! LXT2, the compressed waveform format of GTKWave's tools, also written
+//! by Icarus Verilog.
+//!
+//! An LXT2 file starts with a header: the id `0x1380`, the version, the
+//! granule size (32 or 64), the number of facilities (variables), the
+//! sizes of the two gzip streams that follow and the timescale exponent.
+//! The streams hold the full names of the facilities, each sharing a
+//! prefix with the one before it, and their geometry: rows, most and
+//! least significant bit and flags marking integers, reals, strings and
+//! aliases. Integers are big-endian.
+//!
+//! Changes follow in blocks, each a gzip stream after a header with its
+//! sizes and time range. A block is a sequence of *granules* of up to a
+//! granule size of time steps, ended by a dictionary of the values spelled
+//! out in full and one of the masks of time steps in which facilities
+//! change. A granule lists its times, the mask of each facility, and for
+//! each change an index into the dictionary. Indices below 18 code
+//! changes relative to the previous value: all zeros or ones, inverted,
+//! shifted, incremented or decremented by up to 4, all `x` or `z`, or
+//! blacked out by `$dumpoff`. Vectors in the dictionary drop their leading
+//! zeros.
+//!
+//! [`Lxt2File`] splits the names at dots into scopes and a variable, with
+//! a trailing bit select or range as its index. Facilities become wires,
+//! integers, reals or strings; aliases share the signal of their target.
+//!
+//! The reader follows the layout of GTKWave's `lxt2_read.c`. Its sample
+//! file is built after that layout rather than written by a simulator.
+//! Granules written in GTKWave's partial mode, which compresses groups of
+//! facilities once more, are not read: loading signals or blackouts from a
+//! block holding one fails with [`io::ErrorKind::Unsupported`].
+
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::fst::timescale;
+use crate::inflate::gunzip;
+use crate::mmap::{Data, Mmap};
+use crate::{
+    Blackouts, Hierarchy, InvalidData, ReferenceIndex, Scope, ScopeKind, Signal, SignalId,
+    SignalLoader, Timescale, Var, VarKind, Waveform,
+};
+
+const HDRID: u64 = 0x1380;
+const VERSION: u64 = 1;
+
+// Sections of a block.
+const SECT_TIME: u8 = 0;
+const SECT_DICT: u8 = 1;
+const SECT_TIME_PARTIAL: u8 = 2;
+
+// Flags of a facility.
+const F_INTEGER: u32 = 1 << 0;
+const F_DOUBLE: u32 = 1 << 1;
+const F_STRING: u32 = 1 << 2;
+const F_ALIAS: u32 = 1 << 3;
+
+// Value codes below the dictionary.
+const ENC_0: u64 = 0;
+const ENC_1: u64 = 1;
+const ENC_INV: u64 = 2;
+const ENC_LSH0: u64 = 3;
+const ENC_LSH1: u64 = 4;
+const ENC_RSH0: u64 = 5;
+const ENC_RSH1: u64 = 6;
+const ENC_ADD1: u64 = 7;
+const ENC_ADD4: u64 = 10;
+const ENC_SUB1: u64 = 11;
+const ENC_SUB4: u64 = 14;
+const ENC_X: u64 = 15;
+const ENC_Z: u64 = 16;
+const ENC_BLACKOUT: u64 = 17;
+const DICT_START: u64 = 18;
+
+/// Widths beyond those of any simulator, which only a corrupt geometry
+/// declares.
+const MAX_WIDTH: u32 = 1 << 20;
+
+const TRUNCATED: InvalidData = InvalidData("truncated LXT2 file");
+const CORRUPT: InvalidData = InvalidData("corrupt LXT2 file");
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], InvalidData> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos.saturating_add(n))
+            .ok_or(TRUNCATED)?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, InvalidData> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    /// An `n`-byte big-endian integer.
+    fn be(&mut self, n: usize) -> Result<u64, InvalidData> {
+        Ok(be(self.bytes(n)?))
+    }
+
+    fn u32(&mut self) -> Result<u32, InvalidData> {
+        Ok(self.be(4)? as u32)
+    }
+
+    fn u64(&mut self) -> Result<u64, InvalidData> {
+        self.be(8)
+    }
+
+    /// A byte between 1 and 4, the width of the indices that follow.
+    fn width(&mut self) -> Result<usize, InvalidData> {
+        match self.u8()? {
+            w @ 1..=4 => Ok(w as usize),
+            _ => Err(CORRUPT),
+        }
+    }
+}
+
+fn be(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |v, &b| v << 8 | b as u64)
+}
+
+/// How the values of a facility are spelled.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Kind {
+    Bits,
+    Real,
+    Text,
+}
+
+/// A facility, or the one an alias stands for.
+#[derive(Debug, Clone)]
+struct Fac {
+    kind: Kind,
+    width: u32,
+}
+
+/// A block of changes: its gzip stream in the file, the size it decodes
+/// to and its time range.
+#[derive(Debug, Clone)]
+struct Block {
+    data: Range<usize>,
+    size: usize,
+    start: u64,
+    end: u64,
+}
+
+/// The names of `count` facilities, each sharing a prefix with the one
+/// before it.
+fn read_names(data: &[u8], count: usize) -> Result<Vec<String>, InvalidData> {
+    let mut r = Reader { data, pos: 0 };
+    let mut names = Vec::with_capacity(count);
+    let mut prev = Vec::new();
+    for _ in 0..count {
+        let shared = r.be(2)? as usize;
+        if shared > prev.len() {
+            return Err(CORRUPT);
+        }
+        let rest = &data[r.pos..];
+        let end = rest.iter().position(|&b| b == 0).ok_or(TRUNCATED)?;
+        prev.truncate(shared);
+        prev.extend_from_slice(&rest[..end]);
+        r.pos += end + 1;
+        names.push(String::from_utf8_lossy(&prev).into_owned());
+    }
+    match r.remaining() {
+        0 => Ok(names),
+        _ => Err(CORRUPT),
+    }
+}
+
+/// A bit select or range ending `name`, split from it.
+fn split_index(name: &str) -> (&str, Option<ReferenceIndex>) {
+    if let Some(at) = name.rfind('[').filter(|&at| at > 0) {
+        if let Ok(index) = name[at..].parse() {
+            return (&name[..at], Some(index));
+        }
+    }
+    (name, None)
+}
+
+/// Close the innermost open scope into its parent.
+fn close_scope(hierarchy: &mut Hierarchy, open: &mut Vec<Scope>) {
+    if let Some(scope) = open.pop() {
+        match open.last_mut() {
+            Some(parent) => parent.scopes.push(scope),
+            None => hierarchy.scopes.push(scope),
+        }
+    }
+}
+
+/// An LXT2 file held in memory, usually through a memory map.
+///
+/// Opening reads the header and the names and geometry of the
+/// facilities; loading signals decodes every block once for all requested
+/// signals, as LXT2 has no index of them.
+pub struct Lxt2File {
+    data: Data,
+    hierarchy: Hierarchy,
+    timescale: Timescale,
+    granule: u32,
+    facs: Vec<Fac>,
+    /// The facility of each signal.
+    signals: Vec<usize>,
+    blocks: Vec<Block>,
+}
+
+impl Lxt2File {
+    /// Map a file and read its header and facilities.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Lxt2File> {
+        let file = File::open(path)?;
+        Lxt2File::new(Data::Mapped(Mmap::open(&file)?))
+    }
+
+    /// Read an LXT2 file already in memory.
+    pub fn from_bytes(data: Vec<u8>) -> io::Result<Lxt2File> {
+        Lxt2File::new(Data::Owned(data))
+    }
+
+    fn new(data: Data) -> io::Result<Lxt2File> {
+        let mut r = Reader {
+            data: data.as_slice(),
+            pos: 0,
+        };
+        if r.be(2)? != HDRID {
+            return Err(InvalidData("not an LXT2 file").into());
+        }
+        if r.be(2)? > VERSION {
+            return Err(InvalidData("unsupported LXT2 version").into());
+        }
+        let granule = r.u8()? as u32;
+        if granule != 32 && granule != 64 {
+            return Err(InvalidData("invalid LXT2 granule size").into());
+        }
+        let count = r.u32()? as usize;
+        let _name_bytes = r.u32()?;
+        let _longest_name = r.u32()?;
+        let names_len = r.u32()? as usize;
+        let names_size = r.u32()? as usize;
+        let geometry_len = r.u32()? as usize;
+        let timescale =
+            timescale(r.u8()? as i8).map_err(|_| InvalidData("unsupported LXT2 timescale"))?;
+        let names = gunzip(r.bytes(names_len)?, names_size)?;
+        let geometry_size = count.checked_mul(16).ok_or(CORRUPT)?;
+        let geometry = gunzip(r.bytes(geometry_len)?, geometry_size)?;
+        // The geometry has a fixed size per facility, which bounds the
+        // count before anything is allocated for it.
+        if names.len() != names_size || geometry.len() != geometry_size {
+            return Err(CORRUPT.into());
+        }
+        let names = read_names(&names, count)?;
+
+        let mut g = Reader {
+            data: &geometry,
+            pos: 0,
+        };
+        let mut geometries = Vec::with_capacity(count);
+        for _ in 0..count {
+            let rows = g.u32()? as usize;
+            let msb = g.u32()? as i32;
+            let lsb = g.u32()? as i32;
+            let flags = g.u32()?;
+            geometries.push((rows, msb, lsb, flags));
+        }
+        // The rows of an alias are the facility it stands for.
+        let mut facs = Vec::with_capacity(count);
+        let mut targets = Vec::with_capacity(count);
+        let mut signals = Vec::new();
+        for (i, &(rows, _, _, flags)) in geometries.iter().enumerate() {
+            let target = match flags & F_ALIAS {
+                0 => i,
+                _ => match geometries.get(rows) {
+                    Some(&(_, _, _, flags)) if flags & F_ALIAS == 0 => rows,
+                    _ => return Err(CORRUPT.into()),
+                },
+            };
+            let (_, msb, lsb, flags) = geometries[target];
+            let (kind, width) = if flags & F_DOUBLE != 0 {
+                (Kind::Real, 64)
+            } else if flags & F_STRING != 0 {
+                (Kind::Text, 0)
+            } else {
+                (Kind::Bits, msb.abs_diff(lsb).saturating_add(1))
+            };
+            if width > MAX_WIDTH {
+                return Err(InvalidData("LXT2 vector too wide").into());
+            }
+            if target == i {
+                signals.push(i);
+            }
+            targets.push(target);
+            facs.push(Fac { kind, width });
+        }
+        let signal_of = |i: usize| {
+            let signal = signals.binary_search(&targets[i]).expect("a facility");
+            SignalId(signal as u64)
+        };
+
+        // Sorting by parts keeps the contents of each scope together.
+        let paths: Vec<Vec<&str>> = names.iter().map(|n| n.split('.').collect()).collect();
+        let mut order: Vec<usize> = (0..count).collect();
+        order.sort_by(|&a, &b| paths[a].cmp(&paths[b]));
+        let mut hierarchy = Hierarchy::default();
+        let mut open: Vec<Scope> = Vec::new();
+        for i in order {
+            let (name, scopes) = paths[i].split_last().ok_or(CORRUPT)?;
+            let keep = (open.iter().zip(scopes))
+                .take_while(|(a, b)| hierarchy.name(a.name) == **b)
+                .count();
+            while open.len() > keep {
+                close_scope(&mut hierarchy, &mut open);
+            }
+            for &scope in &scopes[keep..] {
+                open.push(Scope::new(ScopeKind::Module, hierarchy.intern(scope)));
+            }
+            let (_, msb, lsb, _) = geometries[i];
+            let flags = geometries[targets[i]].3;
+            let Fac { kind, width } = facs[i];
+            let (name, mut index) = split_index(name);
+            let kind = match kind {
+                Kind::Real => VarKind::Real,
+                Kind::Text => VarKind::String,
+                Kind::Bits if flags & F_INTEGER != 0 => VarKind::Integer,
+                Kind::Bits => VarKind::Wire,
+            };
+            if index.is_none() && width > 1 && kind != VarKind::Real {
+                index = Some(ReferenceIndex::Range(msb, lsb));
+            }
+            let var = Var {
+                kind,
+                width,
+                signal: signal_of(i),
+                name: hierarchy.intern(name),
+                index,
+            };
+            match open.last_mut() {
+                Some(scope) => scope.vars.push(var),
+                None => hierarchy.vars.push(var),
+            }
+        }
+        while !open.is_empty() {
+            close_scope(&mut hierarchy, &mut open);
+        }
+
+        // Blocks still being written end the file.
+        let mut blocks = Vec::new();
+        while r.remaining() >= 24 {
+            let size = r.u32()? as usize;
+            let len = r.u32()? as usize;
+            let start = r.u64()?;
+            let end = r.u64()?;
+            if size == 0 || len == 0 || len > r.remaining() {
+                break;
+            }
+            if start > end {
+                return Err(CORRUPT.into());
+            }
+            blocks.push(Block {
+                data: r.pos..r.pos + len,
+                size,
+                start,
+                end,
+            });
+            r.pos += len;
+        }
+
+        Ok(Lxt2File {
+            hierarchy,
+            timescale,
+            granule,
+            facs,
+            signals,
+            blocks,
+            data,
+        })
+    }
+
+    /// Decode every block, calling `change` with the facility, time and
+    /// new value of each change of the facilities marked in `wanted`, or
+    /// with no value where the facility is blacked out.
+    fn replay<F>(&self, wanted: &[bool], mut change: F) -> io::Result<()>
+    where
+        F: FnMut(usize, u64, Option<&[u8]>),
+    {
+        let mut values: Vec<Vec<u8>> = (self.facs.iter().zip(wanted))
+            .map(|(fac, &wanted)| match (wanted, fac.kind) {
+                (true, Kind::Bits) => vec![b'x'; fac.width as usize],
+                _ => Vec::new(),
+            })
+            .collect();
+        let mut last = 0;
+        for block in &self.blocks {
+            let data = gunzip(&self.data.as_slice()[block.data.clone()], block.size)?;
+            if data.len() != block.size {
+                return Err(CORRUPT.into());
+            }
+            let block = Granules::new(&data, block, self.granule)?;
+            block.replay(self, wanted, &mut values, &mut last, &mut change)?;
+        }
+        Ok(())
+    }
+}
+
+/// A decoded block: its granules, and the dictionaries of values and
+/// masks at its end.
+struct Granules<'a> {
+    data: &'a [u8],
+    block: &'a Block,
+    granule: u32,
+    /// Where the granules end.
+    end: usize,
+    dict: Vec<&'a [u8]>,
+    masks: Vec<u64>,
+}
+
+impl<'a> Granules<'a> {
+    fn new(data: &'a [u8], block: &'a Block, granule: u32) -> Result<Granules<'a>, InvalidData> {
+        let counts = data.len().checked_sub(12).ok_or(CORRUPT)?;
+        let mut r = Reader { data, pos: counts };
+        let dict_count = r.u32()? as usize;
+        let dict_size = r.u32()? as usize;
+        let mask_count = r.u32()? as usize;
+        let mask_bytes = granule as usize / 8;
+        let masks_start = mask_count
+            .checked_mul(mask_bytes)
+            .and_then(|len| counts.checked_sub(len))
+            .ok_or(CORRUPT)?;
+        let dict_start = masks_start.checked_sub(dict_size).ok_or(CORRUPT)?;
+        if dict_start == 0 || data[dict_start - 1] != SECT_DICT {
+            return Err(CORRUPT);
+        }
+        let masks = data[masks_start..counts]
+            .chunks_exact(mask_bytes)
+            .map(be)
+            .collect();
+        let dict: Vec<&[u8]> = match data[dict_start..masks_start].split_last() {
+            Some((0, strings)) => strings.split(|&b| b == 0).collect(),
+            Some(_) => return Err(CORRUPT),
+            None => Vec::new(),
+        };
+        if dict.len() != dict_count {
+            return Err(CORRUPT);
+        }
+        Ok(Granules {
+            data,
+            block,
+            granule,
+            end: dict_start - 1,
+            dict,
+            masks,
+        })
+    }
+
+    fn replay<F>(
+        &self,
+        file: &Lxt2File,
+        wanted: &[bool],
+        values: &mut [Vec<u8>],
+        last: &mut u64,
+        change: &mut F,
+    ) -> io::Result<()>
+    where
+        F: FnMut(usize, u64, Option<&[u8]>),
+    {
+        let mut r = Reader {
+            data: &self.data[..self.end],
+            pos: 0,
+        };
+        let mut times = Vec::with_capacity(self.granule as usize);
+        while r.remaining() > 0 {
+            match r.u8()? {
+                SECT_TIME => {}
+                SECT_TIME_PARTIAL => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        "partial LXT2 granules are not supported",
+                    ))
+                }
+                _ => return Err(CORRUPT.into()),
+            }
+            let count = r.u8()? as u32;
+            if count == 0 || count > self.granule {
+                return Err(CORRUPT.into());
+            }
+            times.clear();
+            for _ in 0..count {
+                let time = r.u64()?;
+                if time < *last {
+                    return Err(InvalidData("LXT2 time goes backwards").into());
+                }
+                if !(self.block.start..=self.block.end).contains(&time) {
+                    return Err(CORRUPT.into());
+                }
+                *last = time;
+                times.push(time);
+            }
+            let map_width = r.width()?;
+            let code_width = r.width()?;
+            let maps = r.bytes(file.facs.len().saturating_mul(map_width))?;
+            for (fac, map) in maps.chunks_exact(map_width).enumerate() {
+                let mask = *self.masks.get(be(map) as usize).ok_or(CORRUPT)?;
+                if count < 64 && mask >> count != 0 {
+                    return Err(CORRUPT.into());
+                }
+                let codes = r.bytes(mask.count_ones() as usize * code_width)?;
+                if !wanted[fac] {
+                    continue;
+                }
+                let steps = (0..count).filter(|&i| mask >> i & 1 != 0);
+                for (step, code) in steps.zip(codes.chunks_exact(code_width)) {
+                    let kind = file.facs[fac].kind;
+                    let time = times[step as usize];
+                    match self.apply(&mut values[fac], kind, be(code))? {
+                        true => change(fac, time, None),
+                        false => change(fac, time, Some(&values[fac])),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply value code `code` to `value`, the value of a facility of
+    /// `kind`. Returns whether the facility is blacked out instead.
+    fn apply(&self, value: &mut Vec<u8>, kind: Kind, code: u64) -> Result<bool, InvalidData> {
+        if code == ENC_BLACKOUT {
+            return Ok(true);
+        }
+        if code >= DICT_START {
+            let entry = *self.dict.get((code - DICT_START) as usize).ok_or(CORRUPT)?;
+            match kind {
+                Kind::Bits => {
+                    let pad = value.len().checked_sub(entry.len()).ok_or(CORRUPT)?;
+                    value[..pad].fill(b'0');
+                    value[pad..].copy_from_slice(entry);
+                }
+                Kind::Real => {
+                    let real: f64 = (std::str::from_utf8(entry).ok())
+                        .and_then(|s| s.trim().parse().ok())
+                        .ok_or(CORRUPT)?;
+                    value.clear();
+                    value.extend(real.to_string().bytes());
+                }
+                Kind::Text => {
+                    value.clear();
+                    value.extend_from_slice(entry);
+                }
+            }
+            return Ok(false);
+        }
+        if kind != Kind::Bits {
+            return Err(CORRUPT);
+        }
+        let last = value.len() - 1;
+        let bit = |base: u64| b'0' + (code - base) as u8;
+        match code {
+            ENC_0 | ENC_1 => value.fill(bit(ENC_0)),
+            ENC_INV => {
+                for b in value.iter_mut() {
+                    *b = match *b {
+                        b'0' => b'1',
+                        b'1' => b'0',
+                        other => other,
+                    };
+                }
+            }
+            ENC_LSH0 | ENC_LSH1 => {
+                value.copy_within(1.., 0);
+                value[last] = bit(ENC_LSH0);
+            }
+            ENC_RSH0 | ENC_RSH1 => {
+                value.copy_within(..last, 1);
+                value[0] = bit(ENC_RSH0);
+            }
+            ENC_ADD1..=ENC_ADD4 => add(value, (code - ENC_ADD1 + 1) as i64)?,
+            ENC_SUB1..=ENC_SUB4 => add(value, -((code - ENC_SUB1 + 1) as i64))?,
+            ENC_X => value.fill(b'x'),
+            ENC_Z => value.fill(b'z'),
+            _ => unreachable!("codes up to the dictionary are handled"),
+        }
+        Ok(false)
+    }
+}
+
+/// Add `delta` to the binary number `value`, wrapping around at its
+/// width.
+fn add(value: &mut [u8], delta: i64) -> Result<(), InvalidData> {
+    let mut carry = 0;
+    for (i, b) in value.iter_mut().rev().enumerate() {
+        let d = (delta >> i.min(63)) as u8 & 1;
+        let sum = match *b {
+            b'0' => d + carry,
+            b'1' => 1 + d + carry,
+            _ => return Err(CORRUPT),
+        };
+        *b = b'0' + (sum & 1);
+        carry = sum >> 1;
+    }
+    Ok(())
+}
+
+impl SignalLoader for Lxt2File {
+    fn load_signals(&mut self, ids: &[SignalId]) -> io::Result<Vec<Signal>> {
+        let mut users = vec![Vec::new(); self.facs.len()];
+        for (i, id) in ids.iter().enumerate() {
+            if let Some(&fac) = self.signals.get(id.0 as usize) {
+                users[fac].push(i);
+            }
+        }
+        let wanted: Vec<bool> = users.iter().map(|u| !u.is_empty()).collect();
+        let mut out = vec![Signal::new(); ids.len()];
+        let mut unknown = Vec::new();
+        self.replay(&wanted, |fac, time, value| {
+            // Blacked out vectors are unknown, as after `$dumpoff` in a
+            // VCD file; reals and strings keep their value.
+            let value = match (value, self.facs[fac].kind) {
+                (Some(value), _) => value,
+                (None, Kind::Bits) => {
+                    unknown.resize(self.facs[fac].width as usize, b'x');
+                    &unknown[..self.facs[fac].width as usize]
+                }
+                (None, _) => return,
+            };
+            for &i in &users[fac] {
+                let signal = &mut out[i];
+                if signal.is_empty() || signal.value(signal.len() - 1) != value {
+                    signal.push(time, value);
+                }
+            }
+        })?;
+        Ok(out)
+    }
+}
+
+impl Waveform for Lxt2File {
+    fn hierarchy(&self) -> &Hierarchy {
+        &self.hierarchy
+    }
+
+    fn timescale(&self) -> Option<Timescale> {
+        Some(self.timescale)
+    }
+
+    /// The ranges in which facilities are blacked out, which takes
+    /// decoding every block.
+    fn blackouts(&self) -> io::Result<Blackouts> {
+        let mut off = vec![false; self.facs.len()];
+        let mut edges = Vec::new();
+        self.replay(&vec![true; self.facs.len()], |fac, time, value| {
+            if off[fac] != value.is_none() {
+                off[fac] = value.is_none();
+                edges.push((time, value.is_some()));
+            }
+        })?;
+        edges.sort_by_key(|&(time, _)| time);
+        Ok(Blackouts::from_edges(edges))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::inflate::crc32;
+    use crate::TimeUnit;
+
+    /// `data` as a gzip stream of stored blocks.
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+        let mut chunks = data.chunks(0xffff).peekable();
+        if chunks.peek().is_none() {
+            out.extend([1, 0, 0, 0xff, 0xff]);
+        }
+        while let Some(chunk) = chunks.next() {
+            let len = chunk.len() as u16;
+            out.push(chunks.peek().is_none() as u8);
+            out.extend(len.to_le_bytes());
+            out.extend((!len).to_le_bytes());
+            out.extend(chunk);
+        }
+        out.extend(crc32(data).to_le_bytes());
+        out.extend((data.len() as u32).to_le_bytes());
+        out
+    }
+
+    /// The header of a file with facilities of these names and geometry,
+    /// in 32-step granules and nanoseconds.
+    fn header(facs: &[(&str, [u32; 4])]) -> Vec<u8> {
+        let mut names = Vec::new();
+        let mut prev = "";
+        for (name, _) in facs {
+            let shared = name.bytes().zip(prev.bytes()).take_while(|(a, b)| a == b);
+            let shared = shared.count();
+            names.extend((shared as u16).to_be_bytes());
+            names.extend(&name.as_bytes()[shared..]);
+            names.push(0);
+            prev = name;
+        }
+        let geometry: Vec<u8> = (facs.iter().flat_map(|(_, g)| g))
+            .flat_map(|v| v.to_be_bytes())
+            .collect();
+        let (znames, zgeometry) = (gzip(&names), gzip(&geometry));
+        let mut out = vec![0x13, 0x80, 0, 1, 32];
+        let longest = facs.iter().map(|(n, _)| n.len()).max().unwrap_or(0);
+        for v in [
+            facs.len(),
+            names.len(),
+            longest,
+            znames.len(),
+            names.len(),
+            zgeometry.len(),
+        ] {
+            out.extend((v as u32).to_be_bytes());
+        }
+        out.push(-9i8 as u8);
+        out.extend(znames);
+        out.extend(zgeometry);
+        out
+    }
+
+    /// A granule at `times`, with a byte per mask index and value code.
+    fn granule(times: &[u64], maps: &[u8], codes: &[u8]) -> Vec<u8> {
+        let mut out = vec![SECT_TIME, times.len() as u8];
+        out.extend(times.iter().flat_map(|t| t.to_be_bytes()));
+        out.extend([1, 1]);
+        out.extend(maps);
+        out.extend(codes);
+        out
+    }
+
+    /// The contents of a block: granules, then its dictionaries.
+    fn contents(granules: &[Vec<u8>], dict: &[&str], masks: &[u32]) -> Vec<u8> {
+        let mut out = granules.concat();
+        out.push(SECT_DICT);
+        let strings = dict.iter().flat_map(|s| s.bytes().chain([0]));
+        let dict_size = dict.iter().map(|s| s.len() + 1).sum::<usize>();
+        out.extend(strings);
+        out.extend(masks.iter().flat_map(|m| m.to_be_bytes()));
+        for v in [dict.len(), dict_size, masks.len()] {
+            out.extend((v as u32).to_be_bytes());
+        }
+        out
+    }
+
+    /// A block header and the compressed contents.
+    fn block(start: u64, end: u64, contents: &[u8]) -> Vec<u8> {
+        let compressed = gzip(contents);
+        let mut out = Vec::new();
+        out.extend((contents.len() as u32).to_be_bytes());
+        out.extend((compressed.len() as u32).to_be_bytes());
+        out.extend(start.to_be_bytes());
+        out.extend(end.to_be_bytes());
+        out.extend(compressed);
+        out
+    }
+
+    const D: u8 = DICT_START as u8;
+    const X: u8 = ENC_X as u8;
+    const OFF: u8 = ENC_BLACKOUT as u8;
+
+    /// The contents of the two blocks of the sample.
+    fn sample_blocks() -> [Vec<u8>; 2] {
+        // Facilities: bits[2], clk, count, mode, n, sub.cnt (an alias of
+        // count) and v.
+        let first = contents(
+            &[
+                granule(
+                    &[0, 10],
+                    &[1, 2, 2, 1, 2, 0, 3],
+                    &[1, 0, 1, X, D, D + 1, D + 2, ENC_INV as u8, D + 3],
+                ),
+                granule(&[20], &[0, 1, 1, 1, 0, 0, 0], &[0, ENC_ADD1 as u8, D + 4]),
+            ],
+            &["1", "idle", "101", "0.5", "run"],
+            &[0, 0b1, 0b11, 0b10],
+        );
+        // $dumpoff at 40 and $dumpon at 50.
+        let second = contents(
+            &[granule(
+                &[30, 40, 50],
+                &[2, 1, 1, 2, 2, 0, 2],
+                &[
+                    OFF,
+                    1,
+                    1,
+                    OFF,
+                    0,
+                    D,
+                    OFF,
+                    ENC_LSH1 as u8,
+                    OFF,
+                    D + 1,
+                    OFF,
+                    ENC_SUB1 as u8 + 1,
+                    OFF,
+                    D + 2,
+                ],
+            )],
+            &["10", "run", "0.5"],
+            &[0, 0b111, 0b110],
+        );
+        [first, second]
+    }
+
+    fn build(blocks: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = header(&[
+            ("top.bits[2]", [0, 2, 2, 0]),
+            ("top.clk", [0, 0, 0, 0]),
+            ("top.count", [0, 3, 0, 0]),
+            ("top.mode", [0, 0, 0, F_STRING]),
+            ("top.n", [0, 31, 0, F_INTEGER]),
+            ("top.sub.cnt", [2, 3, 0, F_ALIAS]),
+            ("top.v", [0, 0, 0, F_DOUBLE]),
+        ]);
+        out.extend(block(0, 20, &blocks[0]));
+        out.extend(block(30, 50, &blocks[1]));
+        out
+    }
+
+    /// A design with scalar, vector, integer, string and real signals, an
+    /// alias and a `$dumpoff`.
+    pub(crate) fn sample() -> Vec<u8> {
+        build(&sample_blocks())
+    }
+
+    fn load(lxt: &mut Lxt2File, path: &str) -> Signal {
+        let signal = lxt.hierarchy().lookup(path).unwrap().signal;
+        lxt.load_signals(&[signal]).unwrap().remove(0)
+    }
+
+    fn values(signal: &Signal) -> Vec<String> {
+        (0..signal.len())
+            .map(|i| String::from_utf8_lossy(signal.value(i)).into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn reads_a_design() {
+        let mut lxt = Lxt2File::from_bytes(sample()).unwrap();
+        assert_eq!(lxt.timescale(), Some(Timescale::new(1, TimeUnit::NS)));
+        let hierarchy = lxt.hierarchy();
+        let var = |path| hierarchy.lookup(path).unwrap();
+        assert_eq!(
+            (var("top.clk").kind, var("top.clk").width),
+            (VarKind::Wire, 1)
+        );
+        assert_eq!(var("top.clk").index, None);
+        assert_eq!(var("top.count").index, Some(ReferenceIndex::Range(3, 0)));
+        assert_eq!(var("top.bits").index, Some(ReferenceIndex::BitSelect(2)));
+        assert_eq!(var("top.n").kind, VarKind::Integer);
+        assert_eq!(var("top.mode").kind, VarKind::String);
+        assert_eq!(var("top.v").kind, VarKind::Real);
+        assert_eq!(var("top.sub.cnt").signal, var("top.count").signal);
+        assert_eq!(hierarchy.var_count(), 7);
+
+        let clk = load(&mut lxt, "top.clk");
+        assert_eq!(clk.times(), [0, 10, 20, 30, 40, 50]);
+        assert_eq!(values(&clk), ["0", "1", "0", "1", "x", "0"]);
+        let count = load(&mut lxt, "top.sub.cnt");
+        assert_eq!(count.times(), [0, 10, 20, 40, 50]);
+        assert_eq!(values(&count), ["xxxx", "0001", "0010", "xxxx", "0101"]);
+        let n = load(&mut lxt, "top.n");
+        assert_eq!(n.times(), [0, 10, 40, 50]);
+        assert_eq!(n.value(0), format!("{:032b}", 5).as_bytes());
+        assert_eq!(n.value(1), format!("{:032b}", !5u32).as_bytes());
+        assert_eq!(n.value(3), format!("{:032b}", !5u32 - 2).as_bytes());
+        let mode = load(&mut lxt, "top.mode");
+        assert_eq!(mode.times(), [0, 20]);
+        assert_eq!(values(&mode), ["idle", "run"]);
+        let v = load(&mut lxt, "top.v");
+        assert_eq!((v.times(), v.value(0)), (&[10][..], &b"0.5"[..]));
+        assert_eq!(values(&load(&mut lxt, "top.bits")), ["1", "x", "1"]);
+        let blackouts = lxt.blackouts().unwrap();
+        assert_eq!(blackouts.ranges().len(), 1);
+        assert_eq!(blackouts.at(45), Some(40..50));
+    }
+
+    #[test]
+    fn rejects_partial_granules() {
+        let mut blocks = sample_blocks();
+        blocks[1][0] = SECT_TIME_PARTIAL;
+        let mut lxt = Lxt2File::from_bytes(build(&blocks)).unwrap();
+        let clk = lxt.hierarchy().lookup("top.clk").unwrap().signal;
+        let err = lxt.load_signals(&[clk]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn rejects_truncated_and_corrupt_files() {
+        let sample = sample();
+        let load_all = |bytes: Vec<u8>| -> io::Result<Vec<Signal>> {
+            let mut lxt = Lxt2File::from_bytes(bytes)?;
+            let signals = lxt.hierarchy().signal_ids();
+            lxt.blackouts()?;
+            lxt.load_signals(&signals)
+        };
+
+        // A file cut in its header is rejected; one cut in a block has
+        // the blocks before it.
+        let blocks = sample_blocks();
+        let second = sample.len() - block(30, 50, &blocks[1]).len();
+        let body = second - block(0, 20, &blocks[0]).len();
+        for len in 0..sample.len() {
+            let cut = sample[..len].to_vec();
+            if len < body {
+                let err = Lxt2File::from_bytes(cut).err().expect("not rejected");
+                assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+                continue;
+            }
+            let blocks = [len >= second, len == sample.len()];
+            let clk = &load_all(cut).unwrap()[1];
+            let times: &[u64] = match blocks {
+                [false, _] => &[],
+                [true, false] => &[0, 10, 20],
+                [true, true] => &[0, 10, 20, 30, 40, 50],
+            };
+            assert_eq!(clk.times(), times);
+        }
+
+        // No corrupt byte of the file or of the decoded blocks makes the
+        // reader panic.
+        for i in 0..sample.len() {
+            for byte in [0, 0x7f, 0x80, 0xff] {
+                let mut bad = sample.clone();
+                bad[i] = byte;
+                let _ = load_all(bad);
+            }
+        }
+        for b in 0..2 {
+            for i in 0..sample_blocks()[b].len() {
+                for byte in [0, 1, 2, 17, 18, 0x7f, 0xff] {
+                    let mut blocks = sample_blocks();
+                    blocks[b][i] = byte;
+                    let _ = load_all(build(&blocks));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! A decoder for LZ4 blocks, the raw format without frame headers.
+//!
+//! FST writers that favor speed, Verilator among them, compress value
+//! chains and the hierarchy with LZ4 instead of zlib.
+
+use crate::inflate::capacity;
+use crate::InvalidData;
+
+const CORRUPT: InvalidData = InvalidData("corrupt LZ4 block");
+
+/// A length of 15 or more continues in the following bytes.
+fn length(data: &[u8], pos: &mut usize, mut len: usize) -> Result<usize, InvalidData> {
+    if len == 15 {
+        loop {
+            let b = *data.get(*pos).ok_or(CORRUPT)?;
+            *pos += 1;
+            len += b as usize;
+            if b != 255 {
+                break;
+            }
+        }
+    }
+    Ok(len)
+}
+
+/// Decode a complete LZ4 block.
+pub(crate) fn lz4_decode(data: &[u8], size_hint: usize) -> Result<Vec<u8>, InvalidData> {
+    let mut out = Vec::with_capacity(capacity(size_hint, data.len()));
+    let mut pos = 0;
+    while pos < data.len() {
+        let token = data[pos];
+        pos += 1;
+        let literals = length(data, &mut pos, (token >> 4) as usize)?;
+        let literal = data.get(pos..pos + literals).ok_or(CORRUPT)?;
+        out.extend_from_slice(literal);
+        pos += literals;
+        if pos == data.len() {
+            break;
+        }
+        let offset = data.get(pos..pos + 2).ok_or(CORRUPT)?;
+        let offset = u16::from_le_bytes([offset[0], offset[1]]) as usize;
+        pos += 2;
+        let len = length(data, &mut pos, (token & 15) as usize)? + 4;
+        if offset == 0 || offset > out.len() {
+            return Err(CORRUPT);
+        }
+        let start = out.len() - offset;
+        for k in 0..len {
+            out.push(out[start + k]);
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sequences() {
+        // "abc", a copy of 6 bytes from 3 back, then "xyz".
+        let block = [0x32, b'a', b'b', b'c', 3, 0, 0x30, b'x', b'y', b'z'];
+        assert_eq!(lz4_decode(&block, 0).unwrap(), b"abcabcabcxyz");
+        // A run of 4 + 15 + 2 bytes from a single literal.
+        let run = [0x1f, b'-', 1, 0, 2, 0x00];
+        assert_eq!(lz4_decode(&run, 0).unwrap(), [b'-'; 22]);
+        // A corrupt size is only a hint.
+        assert_eq!(lz4_decode(&run, usize::MAX).unwrap(), [b'-'; 22]);
+        assert!(lz4_decode(&[0x10, b'a', 5, 0], 0).is_err());
+        assert!(lz4_decode(&[0x20, b'a'], 0).is_err());
+    }
+}
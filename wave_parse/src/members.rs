@@ -0,0 +1,238 @@
+//! Members of SystemVerilog structs, unions and interfaces.
+//!
+//! Waveform formats declare an aggregate as a scope holding a variable
+//! per member, so every member has its own signal but nothing says where
+//! it lies in the packed value of the aggregate. A [`Member`] tree puts
+//! the members back in declaration order with the bits each occupies:
+//! packed structs are laid out with their first member most significant,
+//! and the members of a packed union all start at bit 0. Interfaces and
+//! unpacked structs have no packed value, so their members carry no bits,
+//! while packed aggregates inside them are laid out on their own.
+//!
+//! The layout needs the order in which fields and nested aggregates are
+//! declared, which [`Hierarchy`] does not keep since a [`Scope`] holds its
+//! scopes and variables apart, so trees come from the reader:
+//! [`FstFile::members`](crate::FstFile::members) and
+//! [`FstFile::member`](crate::FstFile::member). A writer that dumps a
+//! packed struct as a single vector stores no member names at all, so
+//! such a struct stays an ordinary variable.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::{Hierarchy, Scope, ScopeKind, SignalId};
+
+/// What a [`Member`] is.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MemberKind {
+    /// A variable, by its signal.
+    Field(SignalId),
+    Struct,
+    Union,
+    Interface,
+}
+
+/// A member of an aggregate, or the aggregate itself at the root of a
+/// tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    pub name: String,
+    pub kind: MemberKind,
+    /// The bits of the member in the outermost packed aggregate around
+    /// it, counted from the least significant; `None` outside of packed
+    /// aggregates.
+    pub bits: Option<Range<u32>>,
+    /// The members of an aggregate, in declaration order.
+    pub members: Vec<Member>,
+}
+
+impl Member {
+    /// The member at a dot-separated path below this one.
+    pub fn get(&self, path: &str) -> Option<&Member> {
+        path.split('.').try_fold(self, |member, name| {
+            member.members.iter().find(|m| m.name == name)
+        })
+    }
+
+    /// The variables of the tree in declaration order, which for a
+    /// packed struct is most significant first.
+    pub fn fields(&self) -> Vec<&Member> {
+        let mut fields = Vec::new();
+        let mut stack = vec![self];
+        while let Some(member) = stack.pop() {
+            match member.kind {
+                MemberKind::Field(_) => fields.push(member),
+                _ => stack.extend(member.members.iter().rev()),
+            }
+        }
+        fields
+    }
+
+    fn shift(&mut self, by: u32) {
+        if let Some(bits) = &mut self.bits {
+            *bits = bits.start + by..bits.end + by;
+        }
+        for member in &mut self.members {
+            member.shift(by);
+        }
+    }
+}
+
+/// A declaration inside an aggregate scope: the next of its variables,
+/// or the next of its scopes.
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum Child {
+    Var,
+    Scope,
+}
+
+/// The tree of aggregate `scope` of `h` at dotted `path`, `None` if it
+/// is another kind of scope. `children` holds the declarations of each
+/// aggregate in order by path, `packed` whether a struct or union is
+/// packed.
+pub(crate) fn build(
+    h: &Hierarchy,
+    scope: &Scope,
+    path: &str,
+    children: &HashMap<String, Vec<Child>>,
+    packed: &dyn Fn(&str) -> bool,
+) -> Option<Member> {
+    build_in(h, scope, path, children, packed).map(|(member, _)| member)
+}
+
+/// The tree of `scope` with its width; members of a packed aggregate
+/// are laid out from bit 0 and moved into place by the parent.
+fn build_in(
+    h: &Hierarchy,
+    scope: &Scope,
+    path: &str,
+    children: &HashMap<String, Vec<Child>>,
+    packed: &dyn Fn(&str) -> bool,
+) -> Option<(Member, u32)> {
+    let (kind, is_packed) = match scope.kind {
+        ScopeKind::Struct => (MemberKind::Struct, packed(path)),
+        ScopeKind::Union => (MemberKind::Union, packed(path)),
+        ScopeKind::Interface => (MemberKind::Interface, false),
+        _ => return None,
+    };
+    let mut vars = scope.vars.iter();
+    let mut scopes = scope.scopes.iter();
+    let mut members = Vec::new();
+    for child in children.get(path).map_or(&[][..], Vec::as_slice) {
+        match *child {
+            Child::Var => {
+                let Some(var) = vars.next() else { continue };
+                members.push((
+                    Member {
+                        name: h.name(var.name).to_string(),
+                        kind: MemberKind::Field(var.signal),
+                        bits: is_packed.then_some(0..var.width),
+                        members: Vec::new(),
+                    },
+                    var.width,
+                ));
+            }
+            Child::Scope => {
+                let Some(inner) = scopes.next() else { continue };
+                let path = format!("{}.{}", path, h.name(inner.name));
+                members.extend(build_in(h, inner, &path, children, packed));
+            }
+        }
+    }
+    let width = match kind {
+        _ if !is_packed => 0,
+        MemberKind::Union => members.iter().map(|(_, w)| *w).max().unwrap_or(0),
+        _ => {
+            let total = members.iter().map(|(_, w)| *w).sum();
+            let mut at = total;
+            for (member, width) in &mut members {
+                at -= *width;
+                member.shift(at);
+            }
+            total
+        }
+    };
+    let member = Member {
+        name: h.name(scope.name).to_string(),
+        kind,
+        bits: is_packed.then_some(0..width),
+        members: members.into_iter().map(|(member, _)| member).collect(),
+    };
+    Some((member, width))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fst::Attribute;
+    use crate::{FstFile, FstWriter, VarKind, Waveform};
+    use std::io::Cursor;
+
+    #[test]
+    fn lays_out_packed_aggregates() {
+        let mut w = FstWriter::new(Cursor::new(Vec::new()));
+        let mut signal = 0;
+        let mut var = |w: &mut FstWriter<_>, name, width| {
+            signal += 1;
+            w.var_def(VarKind::Logic, width, SignalId(signal), name, None)
+                .unwrap();
+        };
+        w.scope_def(ScopeKind::Module, "top").unwrap();
+        w.scope_def(ScopeKind::Interface, "bus").unwrap();
+        var(&mut w, "clk", 1);
+        w.scope_def(ScopeKind::Struct, "req").unwrap();
+        var(&mut w, "valid", 1);
+        w.scope_def(ScopeKind::Struct, "addr").unwrap();
+        var(&mut w, "hi", 4);
+        var(&mut w, "lo", 4);
+        w.upscope().unwrap();
+        var(&mut w, "data", 8);
+        w.scope_def(ScopeKind::Union, "u").unwrap();
+        var(&mut w, "a", 6);
+        var(&mut w, "b", 2);
+        w.upscope().unwrap();
+        w.upscope().unwrap();
+        w.attribute_def(&Attribute::Other {
+            kind: 3,
+            subtype: 2,
+            name: String::new(),
+            arg: 0,
+        })
+        .unwrap();
+        w.scope_def(ScopeKind::Struct, "cfg").unwrap();
+        var(&mut w, "mode", 3);
+        w.upscope().unwrap();
+        w.upscope().unwrap();
+        w.upscope().unwrap();
+        let fst = FstFile::from_bytes(w.finish().unwrap().into_inner()).unwrap();
+
+        let bits = |path| fst.member(path).unwrap().bits;
+        let req = fst.member("top.bus.req").unwrap();
+        assert_eq!(req.kind, MemberKind::Struct);
+        assert_eq!(req.bits, Some(0..23));
+        let names: Vec<&str> = req.members.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["valid", "addr", "data", "u"]);
+        assert_eq!(bits("top.bus.req.valid"), Some(22..23));
+        assert_eq!(bits("top.bus.req.addr"), Some(14..22));
+        assert_eq!(bits("top.bus.req.addr.hi"), Some(18..22));
+        assert_eq!(bits("top.bus.req.addr.lo"), Some(14..18));
+        assert_eq!(bits("top.bus.req.data"), Some(6..14));
+        assert_eq!(bits("top.bus.req.u.a"), Some(0..6));
+        assert_eq!(bits("top.bus.req.u.b"), Some(0..2));
+        let fields: Vec<&str> = req.fields().iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(fields, ["valid", "hi", "lo", "data", "a", "b"]);
+        let lo = fst.hierarchy().lookup("top.bus.req.addr.lo").unwrap();
+        assert_eq!(
+            req.get("addr.lo").unwrap().kind,
+            MemberKind::Field(lo.signal)
+        );
+
+        // Interfaces and unpacked structs have no packed value.
+        assert_eq!(bits("top.bus"), None);
+        assert_eq!(bits("top.bus.clk"), None);
+        assert_eq!(bits("top.bus.cfg.mode"), None);
+        assert!(fst.member("top").is_none());
+        assert!(fst.member("top.bus.req.nope").is_none());
+        assert!(fst.members("top").is_none());
+    }
+}
@@ -0,0 +1,77 @@
+//! Waveforms held entirely in memory.
+//!
+//! Importers for formats without random access (captures, CSV files)
+//! decode everything up front into a [`MemoryWaveform`], which then
+//! serves signals like any other [`Waveform`].
+
+use std::collections::HashMap;
+use std::io;
+
+use crate::{Hierarchy, Signal, SignalId, SignalLoader, Timescale, Waveform};
+
+/// A waveform whose signals are all decoded already.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryWaveform {
+    hierarchy: Hierarchy,
+    timescale: Option<Timescale>,
+    date: Option<String>,
+    signals: HashMap<SignalId, Signal>,
+}
+
+impl MemoryWaveform {
+    pub fn new(hierarchy: Hierarchy, timescale: Option<Timescale>) -> MemoryWaveform {
+        MemoryWaveform {
+            hierarchy,
+            timescale,
+            date: None,
+            signals: HashMap::new(),
+        }
+    }
+
+    /// Mutable access to the hierarchy, e.g. to declare more variables.
+    pub fn hierarchy_mut(&mut self) -> &mut Hierarchy {
+        &mut self.hierarchy
+    }
+
+    pub fn set_timescale(&mut self, timescale: Option<Timescale>) {
+        self.timescale = timescale;
+    }
+
+    pub fn set_date(&mut self, date: Option<String>) {
+        self.date = date;
+    }
+
+    /// Set the data of a signal, replacing any previous data.
+    pub fn insert_signal(&mut self, id: SignalId, signal: Signal) {
+        self.signals.insert(id, signal);
+    }
+
+    /// The data of a signal, if any was inserted.
+    pub fn signal(&self, id: SignalId) -> Option<&Signal> {
+        self.signals.get(&id)
+    }
+}
+
+impl SignalLoader for MemoryWaveform {
+    /// Signals without data load as empty signals.
+    fn load_signals(&mut self, ids: &[SignalId]) -> io::Result<Vec<Signal>> {
+        Ok(ids
+            .iter()
+            .map(|id| self.signals.get(id).cloned().unwrap_or_default())
+            .collect())
+    }
+}
+
+impl Waveform for MemoryWaveform {
+    fn hierarchy(&self) -> &Hierarchy {
+        &self.hierarchy
+    }
+
+    fn timescale(&self) -> Option<Timescale> {
+        self.timescale
+    }
+
+    fn date(&self) -> Option<&str> {
+        self.date.as_deref()
+    }
+}
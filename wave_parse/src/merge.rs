@@ -0,0 +1,223 @@
+//! Several waveforms combined onto one timeline.
+//!
+//! A [`MergedWaveform`] places the hierarchy of each source under a
+//! top-level scope named by its prefix, e.g. `core0.` and `core1.` for
+//! per-core dumps, and serves their signals with new, distinct ids.
+//! Times are converted to the [common](Timescale::common) timescale of
+//! all sources, so no change moves. Signals load lazily, from the source
+//! they belong to.
+
+use std::collections::HashMap;
+use std::io;
+
+use crate::{
+    Blackouts, Hierarchy, InvalidData, Scope, ScopeKind, Signal, SignalId, SignalLoader, Timescale,
+    Var, Waveform,
+};
+
+struct Source {
+    wave: Box<dyn Waveform + Send>,
+    /// Merged ids of the source's signals, back to the source's ids.
+    ids: HashMap<SignalId, SignalId>,
+}
+
+/// Waveforms merged under per-source scopes.
+#[derive(Default)]
+pub struct MergedWaveform {
+    sources: Vec<Source>,
+    hierarchy: Hierarchy,
+    timescale: Option<Timescale>,
+    /// The source of each merged signal.
+    owners: HashMap<SignalId, usize>,
+}
+
+/// Merge `sources`, pairs of a prefix and a waveform; see
+/// [`MergedWaveform::add`].
+pub fn merge<I>(sources: I) -> io::Result<MergedWaveform>
+where
+    I: IntoIterator<Item = (String, Box<dyn Waveform + Send>)>,
+{
+    let mut merged = MergedWaveform::default();
+    for (prefix, wave) in sources {
+        merged.add(&prefix, wave)?;
+    }
+    Ok(merged)
+}
+
+fn renumber(vars: &mut [Var], ids: &mut HashMap<SignalId, SignalId>, next: &mut u64) {
+    for var in vars {
+        var.signal = *ids.entry(var.signal).or_insert_with(|| {
+            *next += 1;
+            SignalId(*next - 1)
+        });
+    }
+}
+
+fn renumber_scope(scope: &mut Scope, ids: &mut HashMap<SignalId, SignalId>, next: &mut u64) {
+    renumber(&mut scope.vars, ids, next);
+    for child in &mut scope.scopes {
+        renumber_scope(child, ids, next);
+    }
+}
+
+impl MergedWaveform {
+    /// Add a waveform with its hierarchy under a new top-level module
+    /// `prefix`, which must not be taken. Waveforms without a timescale
+    /// can only be merged with each other.
+    pub fn add(&mut self, prefix: &str, wave: Box<dyn Waveform + Send>) -> io::Result<()> {
+        if prefix.is_empty() || prefix.contains('.') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid merge prefix {:?}", prefix),
+            ));
+        }
+        if self.hierarchy.find_scope(&[prefix]).is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("merge prefix {} is used twice", prefix),
+            ));
+        }
+        self.timescale = match (self.sources.is_empty(), self.timescale, wave.timescale()) {
+            (true, _, timescale) => timescale,
+            (false, Some(a), Some(b)) => Some(
+                Timescale::common(a, b).ok_or(InvalidData("no common timescale for merging"))?,
+            ),
+            (false, None, None) => None,
+            _ => {
+                return Err(InvalidData("cannot merge waveforms with and without timescale").into())
+            }
+        };
+
+        let mut scope = Scope::new(ScopeKind::Module, self.hierarchy.intern(prefix));
+        let h = wave.hierarchy();
+        scope.vars = h
+            .vars
+            .iter()
+            .map(|v| self.hierarchy.import_var(h, v))
+            .collect();
+        scope.scopes = h
+            .scopes
+            .iter()
+            .map(|s| self.hierarchy.import_scope(h, s))
+            .collect();
+        let mut next = self.owners.len() as u64;
+        let mut ids = HashMap::new();
+        renumber_scope(&mut scope, &mut ids, &mut next);
+        for &merged in ids.values() {
+            self.owners.insert(merged, self.sources.len());
+        }
+        self.hierarchy.scopes.push(scope);
+        self.sources.push(Source {
+            wave,
+            ids: ids.into_iter().map(|(from, to)| (to, from)).collect(),
+        });
+        Ok(())
+    }
+
+    /// Number of merged waveforms.
+    pub fn source_count(&self) -> usize {
+        self.sources.len()
+    }
+}
+
+impl SignalLoader for MergedWaveform {
+    /// Loads from each source once, with all its requested signals.
+    fn load_signals(&mut self, ids: &[SignalId]) -> io::Result<Vec<Signal>> {
+        let mut out = vec![Signal::new(); ids.len()];
+        for (i, source) in self.sources.iter_mut().enumerate() {
+            let (slots, inner): (Vec<usize>, Vec<SignalId>) = ids
+                .iter()
+                .enumerate()
+                .filter(|(_, id)| self.owners.get(id) == Some(&i))
+                .map(|(slot, id)| (slot, source.ids[id]))
+                .unzip();
+            if inner.is_empty() {
+                continue;
+            }
+            let from = source.wave.timescale();
+            let signals = source.wave.load_signals(&inner)?;
+            for (slot, signal) in slots.into_iter().zip(signals) {
+                out[slot] = match (from, self.timescale) {
+                    (Some(from), Some(to)) if from != to => {
+                        let mut converted = Signal::new();
+                        for (t, v) in signal.iter() {
+                            let t = from
+                                .convert(t, to)
+                                .ok_or(InvalidData("time does not fit the timescale"))?;
+                            converted.push(t, v);
+                        }
+                        converted
+                    }
+                    _ => signal,
+                };
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl Waveform for MergedWaveform {
+    fn hierarchy(&self) -> &Hierarchy {
+        &self.hierarchy
+    }
+
+    fn timescale(&self) -> Option<Timescale> {
+        self.timescale
+    }
+
+    /// The blackouts of all sources: a time is covered if any source
+    /// recorded nothing then.
+    fn blackouts(&self) -> io::Result<Blackouts> {
+        let mut ranges = Vec::new();
+        for source in &self.sources {
+            let mut blackouts = source.wave.blackouts()?;
+            if let (Some(from), Some(to)) = (source.wave.timescale(), self.timescale) {
+                blackouts = blackouts.map_times(|t| from.convert(t, to).unwrap_or(u64::MAX));
+            }
+            ranges.extend_from_slice(blackouts.ranges());
+        }
+        Ok(Blackouts::new(ranges))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{TimeUnit, VcdFile};
+
+    fn vcd(text: &str) -> Box<dyn Waveform + Send> {
+        Box::new(VcdFile::from_bytes(text.as_bytes().to_vec()).unwrap())
+    }
+
+    #[test]
+    fn merges_under_prefixes() {
+        let a = vcd(
+            "$timescale 10ns $end $scope module top $end $var wire 1 ! clk $end
+$upscope $end $enddefinitions $end #0 0! #1 1!",
+        );
+        let b = vcd(
+            "$timescale 1ns $end $scope module top $end $var wire 1 ! clk $end
+$var wire 2 \" d $end $upscope $end $enddefinitions $end #0 0! b10 \" #5 1!",
+        );
+        let mut merged = merge([("core0".to_string(), a), ("core1".to_string(), b)]).unwrap();
+        assert_eq!(merged.timescale(), Some(Timescale::new(1, TimeUnit::NS)));
+        let h = merged.hierarchy();
+        assert_eq!(h.var_count(), 3);
+        let ids: Vec<SignalId> = ["core0.top.clk", "core1.top.clk", "core1.top.d"]
+            .iter()
+            .map(|p| h.lookup(p).unwrap().signal)
+            .collect();
+        assert_eq!(h.signal_ids().len(), 3);
+        let signals = merged.load_signals(&ids).unwrap();
+        assert_eq!(signals[0].times(), [0, 10]);
+        assert_eq!(signals[1].times(), [0, 5]);
+        assert_eq!(signals[2].value(0), b"10");
+
+        let err = merged
+            .add("core0", vcd("$enddefinitions $end"))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert!(merged.add("bare", vcd("$enddefinitions $end")).is_err());
+        assert_eq!(merged.source_count(), 2);
+    }
+}
@@ -0,0 +1,159 @@
+//! Read-only memory maps of whole files.
+//!
+//! On 64-bit unix targets the file is mapped with `mmap(2)`; elsewhere the
+//! file is read into memory, which keeps the same interface everywhere.
+
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+
+/// A read-only view of a complete file.
+///
+/// The mapping is private: modifying the file on disk while it is mapped
+/// is not supported and may be observed through the slice. Reading past
+/// the end of a file truncated since raises `SIGBUS`.
+pub struct Mmap {
+    inner: imp::Map,
+}
+
+impl Mmap {
+    /// Map the complete file.
+    pub fn open(file: &File) -> io::Result<Mmap> {
+        Ok(Mmap {
+            inner: imp::Map::new(file)?,
+        })
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        self.inner.as_slice()
+    }
+}
+
+impl AsRef<[u8]> for Mmap {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+/// The bytes of a file, mapped or read into memory.
+pub(crate) enum Data {
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl Data {
+    pub(crate) fn as_slice(&self) -> &[u8] {
+        match self {
+            Data::Mapped(m) => m,
+            Data::Owned(v) => v,
+        }
+    }
+}
+
+#[cfg(all(unix, target_pointer_width = "64"))]
+mod imp {
+    use std::ffi::c_void;
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+    use std::ptr;
+    use std::slice;
+
+    const PROT_READ: i32 = 1;
+    const MAP_PRIVATE: i32 = 2;
+
+    extern "C" {
+        fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: i32,
+            flags: i32,
+            fd: i32,
+            offset: i64,
+        ) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> i32;
+    }
+
+    pub struct Map {
+        ptr: *mut c_void,
+        len: usize,
+    }
+
+    // The mapping is read-only and owned by this value.
+    unsafe impl Send for Map {}
+    unsafe impl Sync for Map {}
+
+    impl Map {
+        pub fn new(file: &File) -> io::Result<Map> {
+            let len = file.metadata()?.len();
+            let len = usize::try_from(len).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, "file too large to map")
+            })?;
+            if len == 0 {
+                // mmap rejects empty mappings.
+                return Ok(Map {
+                    ptr: ptr::null_mut(),
+                    len: 0,
+                });
+            }
+            let ptr = unsafe {
+                mmap(
+                    ptr::null_mut(),
+                    len,
+                    PROT_READ,
+                    MAP_PRIVATE,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            if ptr as isize == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Map { ptr, len })
+        }
+
+        #[inline]
+        pub fn as_slice(&self) -> &[u8] {
+            if self.len == 0 {
+                return &[];
+            }
+            unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
+        }
+    }
+
+    impl Drop for Map {
+        fn drop(&mut self) {
+            if self.len != 0 {
+                unsafe {
+                    munmap(self.ptr, self.len);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(all(unix, target_pointer_width = "64")))]
+mod imp {
+    use std::fs::File;
+    use std::io::{self, Read};
+
+    pub struct Map(Vec<u8>);
+
+    impl Map {
+        pub fn new(mut file: &File) -> io::Result<Map> {
+            let mut buf = Vec::new();
+            file.read_to_end(&mut buf)?;
+            Ok(Map(buf))
+        }
+
+        #[inline]
+        pub fn as_slice(&self) -> &[u8] {
+            &self.0
+        }
+    }
+}
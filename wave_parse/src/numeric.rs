@@ -0,0 +1,166 @@
+//! Numeric interpretation of vector values.
+//!
+//! A raw vector value (VCD left extension applied) is read as an integer
+//! of its declared width and then interpreted according to an
+//! [`Encoding`]. Values with `x`, `z` or other non-binary bits have no
+//! numeric value and come out as `None`.
+
+use crate::{Logic, Signal};
+
+/// How the bits of a vector encode a number.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Encoding {
+    Unsigned,
+    /// Two's complement.
+    Signed,
+    /// Qm.n fixed point with `frac` fractional bits.
+    Fixed {
+        signed: bool,
+        frac: u32,
+    },
+    /// IEEE 754 binary16, binary32 or binary64, chosen by width.
+    Float,
+}
+
+/// The bits of a raw value of a `width`-bit vector as an integer, if they
+/// are all `0`/`1` and the value fits in 64 bits. The left extension of a
+/// value with such bits only adds zeros.
+pub(crate) fn raw_bits(value: &[u8], width: u32) -> Option<u64> {
+    let mut n = 0u64;
+    for i in 0..value.len() {
+        match Logic::vcd_bit(value, i)? {
+            Logic::Zero => {}
+            Logic::One if i < 64 && i < width as usize => n |= 1 << i,
+            _ => return None,
+        }
+    }
+    Some(n)
+}
+
+/// The two's complement value of the low `width` bits, if it fits in 64
+/// bits. A wider value, from [`raw_bits`], has a clear sign bit.
+fn sign_extend(bits: u64, width: u32) -> Option<i64> {
+    match width {
+        0 | 64 => Some(bits as i64),
+        65.. => i64::try_from(bits).ok(),
+        _ => {
+            let shift = 64 - width;
+            Some(((bits << shift) as i64) >> shift)
+        }
+    }
+}
+
+fn half_to_f64(h: u16) -> f64 {
+    let sign = if h >> 15 == 1 { -1.0 } else { 1.0 };
+    let exp = ((h >> 10) & 0x1f) as i32;
+    let mant = (h & 0x3ff) as f64;
+    sign * match exp {
+        0 => mant * 2f64.powi(-24),
+        0x1f if mant == 0.0 => f64::INFINITY,
+        0x1f => f64::NAN,
+        _ => (1.0 + mant / 1024.0) * 2f64.powi(exp - 15),
+    }
+}
+
+impl Encoding {
+    /// Interpret a raw value of a `width`-bit vector.
+    pub fn decode(self, value: &[u8], width: u32) -> Option<f64> {
+        let bits = raw_bits(value, width)?;
+        Some(match self {
+            Encoding::Unsigned => bits as f64,
+            Encoding::Signed => sign_extend(bits, width)? as f64,
+            Encoding::Fixed { signed, frac } => {
+                let n = if signed {
+                    sign_extend(bits, width)? as f64
+                } else {
+                    bits as f64
+                };
+                n / 2f64.powi(frac as i32)
+            }
+            Encoding::Float => match width {
+                16 => half_to_f64(bits as u16),
+                32 => f32::from_bits(bits as u32) as f64,
+                64 => f64::from_bits(bits),
+                _ => return None,
+            },
+        })
+    }
+}
+
+impl Signal {
+    /// The changes of a `width`-bit vector as unsigned integers.
+    pub fn unsigned(&self, width: u32) -> impl Iterator<Item = (u64, Option<u64>)> + '_ {
+        self.iter().map(move |(t, v)| (t, raw_bits(v, width)))
+    }
+
+    /// The changes of a `width`-bit vector as two's complement integers.
+    pub fn signed(&self, width: u32) -> impl Iterator<Item = (u64, Option<i64>)> + '_ {
+        self.iter()
+            .map(move |(t, v)| (t, raw_bits(v, width).and_then(|b| sign_extend(b, width))))
+    }
+
+    /// The changes of a `width`-bit vector interpreted with `encoding`.
+    pub fn numeric(
+        &self,
+        width: u32,
+        encoding: Encoding,
+    ) -> impl Iterator<Item = (u64, Option<f64>)> + '_ {
+        self.iter()
+            .map(move |(t, v)| (t, encoding.decode(v, width)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn integers() {
+        assert_eq!(raw_bits(b"101", 8), Some(5));
+        assert_eq!(raw_bits(b"x1", 8), None);
+        assert_eq!(raw_bits(b"1x", 8), None);
+        assert_eq!(raw_bits(b"0001", 2), Some(1));
+        assert_eq!(raw_bits(b"1001", 2), None);
+        assert_eq!(raw_bits(&[b'1'; 64], 64), Some(u64::MAX));
+        assert_eq!(raw_bits(&[b'1'; 65], 65), None);
+
+        let mut s = Signal::new();
+        s.push(0, b"11111111");
+        s.push(5, b"z");
+        s.push(9, b"1111111");
+        let signed: Vec<_> = s.signed(8).collect();
+        assert_eq!(signed, [(0, Some(-1)), (5, None), (9, Some(127))]);
+        assert_eq!(s.unsigned(8).next(), Some((0, Some(255))));
+
+        // Bit 63 of a 100-bit value is not its sign bit.
+        let mut s = Signal::new();
+        s.push(0, format!("1{}", "0".repeat(63)).as_bytes());
+        s.push(5, &[b'1'; 63]);
+        let signed: Vec<_> = s.signed(100).collect();
+        assert_eq!(signed, [(0, None), (5, Some(i64::MAX))]);
+        assert_eq!(s.signed(64).next(), Some((0, Some(i64::MIN))));
+        let bits = format!("1{}", "0".repeat(63));
+        assert_eq!(Encoding::Signed.decode(bits.as_bytes(), 100), None);
+        assert_eq!(
+            Encoding::Unsigned.decode(bits.as_bytes(), 100),
+            Some(2f64.powi(63))
+        );
+    }
+
+    #[test]
+    fn encodings() {
+        let q = Encoding::Fixed {
+            signed: true,
+            frac: 4,
+        };
+        assert_eq!(q.decode(b"11111000", 8), Some(-0.5));
+        assert_eq!(q.decode(b"00011000", 8), Some(1.5));
+        let bits = format!("{:b}", 1.25f32.to_bits());
+        assert_eq!(Encoding::Float.decode(bits.as_bytes(), 32), Some(1.25));
+        let bits = format!("{:b}", (-2.5f64).to_bits());
+        assert_eq!(Encoding::Float.decode(bits.as_bytes(), 64), Some(-2.5));
+        // 0x3e00 is 1.5 in binary16.
+        assert_eq!(Encoding::Float.decode(b"11111000000000", 16), Some(1.5));
+        assert_eq!(Encoding::Float.decode(b"1", 12), None);
+    }
+}
@@ -0,0 +1,237 @@
+//! Compressed in-memory signals.
+//!
+//! A [`Signal`] spends 16 bytes per change on its time and value offset
+//! before the value itself, which puts signals with billions of changes
+//! out of reach. A [`PackedSignal`] holds the same changes with times as
+//! LEB128 deltas and each distinct value once in a dictionary, referenced
+//! by a LEB128 index: a clock or a bus cycling through few values takes
+//! about 2 bytes per change. Changes are grouped in blocks of 64 that
+//! record where they start, so a lookup decodes at most one block.
+
+use std::collections::HashMap;
+use std::mem;
+
+use crate::{varint, Signal};
+
+/// Changes per block of a [`PackedSignal`].
+const BLOCK: usize = 64;
+
+/// The changes of a signal, compressed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackedSignal {
+    len: usize,
+    /// Time of the first change of each block and where it starts in
+    /// `stream`.
+    blocks: Vec<(u64, usize)>,
+    /// Per change the time since the previous change of the block, 0 for
+    /// the first, and the dictionary index of the value.
+    stream: Vec<u8>,
+    /// The distinct values back to back, in order of first appearance.
+    values: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl PackedSignal {
+    /// Compress the changes of `signal`.
+    pub fn new(signal: &Signal) -> PackedSignal {
+        let mut packed = PackedSignal {
+            len: signal.len(),
+            blocks: Vec::with_capacity(signal.len().div_ceil(BLOCK)),
+            ..PackedSignal::default()
+        };
+        let mut dict: HashMap<&[u8], u64> = HashMap::new();
+        let mut last = 0;
+        for (i, (time, value)) in signal.iter().enumerate() {
+            if i.is_multiple_of(BLOCK) {
+                packed.blocks.push((time, packed.stream.len()));
+                last = time;
+            }
+            varint::write(&mut packed.stream, time - last);
+            last = time;
+            let next = dict.len() as u64;
+            let index = *dict.entry(value).or_insert_with(|| {
+                packed.offsets.push(packed.values.len());
+                packed.values.extend_from_slice(value);
+                next
+            });
+            varint::write(&mut packed.stream, index);
+        }
+        packed.shrink_to_fit();
+        packed
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.blocks.shrink_to_fit();
+        self.stream.shrink_to_fit();
+        self.values.shrink_to_fit();
+        self.offsets.shrink_to_fit();
+    }
+
+    /// Number of changes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of distinct values.
+    pub fn distinct_values(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Heap memory held, in bytes, comparable to
+    /// [`Signal::size_bytes`].
+    pub fn size_bytes(&self) -> usize {
+        self.blocks.capacity() * mem::size_of::<(u64, usize)>()
+            + self.stream.capacity()
+            + self.values.capacity()
+            + self.offsets.capacity() * mem::size_of::<usize>()
+    }
+
+    fn dict_value(&self, index: usize) -> &[u8] {
+        let end = self
+            .offsets
+            .get(index + 1)
+            .copied()
+            .unwrap_or(self.values.len());
+        &self.values[self.offsets[index]..end]
+    }
+
+    /// The changes of block `b`.
+    fn block(&self, b: usize) -> PackedIter<'_> {
+        let (time, pos) = self.blocks[b];
+        PackedIter {
+            signal: self,
+            i: b * BLOCK,
+            pos,
+            time,
+        }
+    }
+
+    /// The `i`-th change.
+    pub fn get(&self, i: usize) -> (u64, &[u8]) {
+        assert!(i < self.len, "change index out of range");
+        self.block(i / BLOCK)
+            .nth(i % BLOCK)
+            .expect("change is in its block")
+    }
+
+    /// Index of the last change at or before `time`, like
+    /// [`Signal::index_at`].
+    pub fn index_at(&self, time: u64) -> Option<usize> {
+        let b = self
+            .blocks
+            .partition_point(|&(t, _)| t <= time)
+            .checked_sub(1)?;
+        let in_block = self.block(b).take(BLOCK).take_while(|&(t, _)| t <= time);
+        Some(b * BLOCK + in_block.count() - 1)
+    }
+
+    /// Value at `time`, if assigned by then, like [`Signal::value_at`].
+    pub fn value_at(&self, time: u64) -> Option<&[u8]> {
+        self.index_at(time).map(|i| self.get(i).1)
+    }
+
+    /// Iterate over `(time, value)` pairs.
+    pub fn iter(&self) -> PackedIter<'_> {
+        PackedIter {
+            signal: self,
+            i: 0,
+            pos: 0,
+            time: 0,
+        }
+    }
+
+    /// Decompress into a [`Signal`].
+    pub fn to_signal(&self) -> Signal {
+        let mut signal = Signal::new();
+        for (time, value) in self {
+            signal.push(time, value);
+        }
+        signal
+    }
+}
+
+impl From<&Signal> for PackedSignal {
+    fn from(signal: &Signal) -> PackedSignal {
+        PackedSignal::new(signal)
+    }
+}
+
+/// An iterator over the `(time, value)` changes of a [`PackedSignal`].
+pub struct PackedIter<'s> {
+    signal: &'s PackedSignal,
+    i: usize,
+    pos: usize,
+    time: u64,
+}
+
+impl<'s> Iterator for PackedIter<'s> {
+    type Item = (u64, &'s [u8]);
+
+    fn next(&mut self) -> Option<(u64, &'s [u8])> {
+        if self.i >= self.signal.len {
+            return None;
+        }
+        let s = self.signal;
+        if self.i.is_multiple_of(BLOCK) {
+            (self.time, self.pos) = s.blocks[self.i / BLOCK];
+        }
+        self.time += varint::read(&s.stream, &mut self.pos).expect("packed stream is well formed");
+        let index =
+            varint::read(&s.stream, &mut self.pos).expect("packed stream is well formed") as usize;
+        self.i += 1;
+        Some((self.time, s.dict_value(index)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.signal.len - self.i;
+        (n, Some(n))
+    }
+}
+
+impl ExactSizeIterator for PackedIter<'_> {}
+
+impl<'s> IntoIterator for &'s PackedSignal {
+    type Item = (u64, &'s [u8]);
+    type IntoIter = PackedIter<'s>;
+
+    fn into_iter(self) -> PackedIter<'s> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn packs_and_queries() {
+        let mut s = Signal::new();
+        for i in 0..200u64 {
+            s.push(i * 10, if i % 2 == 0 { b"0" } else { b"1" });
+        }
+        s.push(2000, b"1010");
+        s.push(2000, b"");
+        s.push(u64::MAX, b"1010");
+        let p = PackedSignal::new(&s);
+        assert_eq!(p.len(), s.len());
+        assert_eq!(p.distinct_values(), 4);
+        assert_eq!(p.to_signal(), s);
+        assert!(p.size_bytes() * 4 < s.size_bytes());
+
+        for t in [0, 5, 10, 639, 640, 1999, 2000, u64::MAX - 1, u64::MAX] {
+            assert_eq!(p.index_at(t), s.index_at(t), "at {}", t);
+            assert_eq!(p.value_at(t), s.value_at(t), "at {}", t);
+        }
+        assert_eq!(p.get(64), (640, &b"0"[..]));
+        assert_eq!(p.get(202), (u64::MAX, &b"1010"[..]));
+
+        let mut late = Signal::new();
+        late.push(5, b"1");
+        assert_eq!(PackedSignal::new(&late).value_at(4), None);
+        assert!(PackedSignal::new(&Signal::new()).iter().next().is_none());
+    }
+}
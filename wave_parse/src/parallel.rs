@@ -0,0 +1,263 @@
+//! Multi-threaded parsing of VCD bodies.
+//!
+//! A body is split into chunks that each start at a timestamp, so every
+//! chunk can be tokenized on its own. Chunks are handed to a pool of
+//! scoped threads and the per-chunk results are returned in file order,
+//! ready to be stitched together.
+//!
+//! Split points are found by looking for a line holding only a timestamp,
+//! outside any `$comment`. Like vcd-ng's `FastFlow`, this assumes the
+//! conventional one-command-per-line layout written by simulators.
+//!
+//! `wasm32-unknown-unknown` cannot spawn threads, so there everything runs
+//! on the calling thread whatever thread count is asked for.
+
+use std::io;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::scan;
+use crate::vcd::Tokens;
+
+/// Smallest chunk worth handing to another thread.
+pub const MIN_CHUNK_SIZE: usize = 1 << 20;
+
+/// Whether the target can spawn threads.
+pub(crate) const THREADS: bool = !cfg!(all(target_arch = "wasm32", target_os = "unknown"));
+
+/// The number of threads to use by default: all available cores.
+pub fn default_threads() -> usize {
+    if !THREADS {
+        return 1;
+    }
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Split `range` of `data` into at most `chunks` pieces, each starting at
+/// a line holding only a timestamp (except possibly the first). `range`
+/// must not start inside a `$comment`.
+pub fn split_at_timestamps(data: &[u8], range: Range<usize>, chunks: usize) -> Vec<Range<usize>> {
+    let len = range.end - range.start;
+    let chunks = chunks.max(1).min(len / MIN_CHUNK_SIZE).max(1);
+    let step = len / chunks;
+    let data = &data[..range.end];
+    let mut out = Vec::with_capacity(chunks);
+    let mut start = range.start;
+    let mut comments = Comments {
+        pos: range.start,
+        open: false,
+    };
+    for i in 1..chunks {
+        let mut target = (range.start + i * step).max(start);
+        let split = loop {
+            match find_timestamp_line(data, target) {
+                Some(split) if comments.open_at(data, split) => target = split,
+                split => break split,
+            }
+        };
+        match split {
+            Some(split) if split > start => {
+                out.push(start..split);
+                start = split;
+            }
+            Some(_) => {}
+            None => break,
+        }
+    }
+    out.push(start..range.end);
+    out
+}
+
+/// Offset of the first line at or after `from` holding only a timestamp.
+fn find_timestamp_line(data: &[u8], from: usize) -> Option<usize> {
+    let mut i = from.max(1);
+    while i < data.len() {
+        let nl = scan::find_byte(data, i, b'\n')?;
+        let line = nl + 1;
+        let digits = data[(line + 1).min(data.len())..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        let rest = &data[(line + 1 + digits).min(data.len())..];
+        if data.get(line) == Some(&b'#')
+            && digits > 0
+            && (rest.is_empty() || rest.starts_with(b"\n") || rest.starts_with(b"\r\n"))
+        {
+            return Some(line);
+        }
+        i = line;
+    }
+    None
+}
+
+/// Whether positions of a body are inside a `$comment`, asked in
+/// increasing order so the body is scanned once.
+struct Comments {
+    /// Scanned up to here.
+    pos: usize,
+    open: bool,
+}
+
+impl Comments {
+    /// Whether `to`, not before any position asked about earlier, is
+    /// inside a comment.
+    fn open_at(&mut self, data: &[u8], to: usize) -> bool {
+        let data = &data[..to];
+        while let Some(d) = scan::find_byte(data, self.pos.min(to), b'$') {
+            let end = scan::find_whitespace(data, d);
+            if d == 0 || scan::is_whitespace(data[d - 1]) {
+                match &data[d..end] {
+                    b"$comment" => self.open = true,
+                    b"$end" => self.open = false,
+                    _ => {}
+                }
+            }
+            self.pos = end;
+        }
+        self.pos = to;
+        self.open
+    }
+}
+
+/// Run `f` over the tokens of every chunk on up to `threads` threads,
+/// returning the results in chunk order.
+///
+/// The first error returned by any chunk is returned.
+pub fn map_chunks<T, F>(
+    data: &[u8],
+    chunks: &[Range<usize>],
+    threads: usize,
+    f: F,
+) -> io::Result<Vec<T>>
+where
+    T: Send,
+    F: Fn(Tokens<'_>) -> io::Result<T> + Sync,
+{
+    map_indexed(chunks.len(), threads, |i| {
+        let range = &chunks[i];
+        f(Tokens::new(&data[..range.end], range.start))
+    })
+}
+
+/// Run `f` for every index below `count` on up to `threads` threads,
+/// returning the results in index order.
+///
+/// The first error returned for any index is returned.
+pub fn map_indexed<T, F>(count: usize, threads: usize, f: F) -> io::Result<Vec<T>>
+where
+    T: Send,
+    F: Fn(usize) -> io::Result<T> + Sync,
+{
+    let threads = threads.max(1).min(count);
+    if threads <= 1 || !THREADS {
+        return (0..count).map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<io::Result<T>>>> = Mutex::new((0..count).map(|_| None).collect());
+    thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= count {
+                    break;
+                }
+                let result = f(i);
+                results.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|r| r.expect("every index was processed"))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn body(stamps: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        for t in 0..stamps {
+            out.extend_from_slice(format!("#{}\nb{:b} !\n1\"\n", t * 10, t).as_bytes());
+        }
+        out
+    }
+
+    #[test]
+    fn chunks_start_at_timestamps() {
+        let data = body(300_000);
+        let chunks = split_at_timestamps(&data, 0..data.len(), 8);
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks[0].start, 0);
+        assert_eq!(chunks.last().unwrap().end, data.len());
+        for w in chunks.windows(2) {
+            assert_eq!(w[0].end, w[1].start);
+            assert_eq!(data[w[1].start], b'#');
+        }
+    }
+
+    #[test]
+    fn comments_are_not_split() {
+        // A comment holding timestamp-like lines, then timestamps sharing
+        // their lines with changes, which are not split at.
+        let mut data = body(100_000);
+        let start = data.len();
+        data.extend_from_slice(b"$comment\n");
+        data.extend(b"#1\n".repeat(1_000_000));
+        data.extend_from_slice(b"$end\n");
+        let comment = start..data.len();
+        for t in 100_000..400_000u64 {
+            data.extend_from_slice(format!("#{}\n1!\n", t * 10).as_bytes());
+        }
+        let same_line = data.len();
+        for t in 400_000..800_000u64 {
+            data.extend_from_slice(format!("#{} 1!\n", t * 10).as_bytes());
+        }
+        let chunks = split_at_timestamps(&data, 0..data.len(), 8);
+        assert!(chunks.len() > 2);
+        for c in &chunks[1..] {
+            assert!(!comment.contains(&c.start), "{c:?} starts in the comment");
+            assert!(c.start < same_line);
+        }
+        let tokens = |chunks: &[Range<usize>]| {
+            let counts = map_chunks(&data, chunks, 4, |tokens| {
+                tokens.map(|t| t.map(|_| 1)).sum::<io::Result<usize>>()
+            });
+            counts.unwrap().into_iter().sum::<usize>()
+        };
+        let whole = split_at_timestamps(&data, 0..data.len(), 1);
+        assert_eq!(tokens(&chunks), tokens(&whole));
+    }
+
+    #[test]
+    fn small_input_is_one_chunk() {
+        let data = body(10);
+        assert_eq!(
+            split_at_timestamps(&data, 3..data.len(), 16),
+            vec![3..data.len()]
+        );
+    }
+
+    #[test]
+    fn results_are_in_order() {
+        let data = body(300_000);
+        let chunks = split_at_timestamps(&data, 0..data.len(), 8);
+        let counts = map_chunks(&data, &chunks, 4, |tokens| {
+            let mut n = 0;
+            for t in tokens {
+                t?;
+                n += 1;
+            }
+            Ok(n)
+        })
+        .unwrap();
+        assert_eq!(counts.len(), chunks.len());
+        assert_eq!(counts.iter().sum::<usize>(), 300_000 * 3);
+    }
+}
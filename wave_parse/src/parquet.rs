@@ -0,0 +1,637 @@
+//! Parquet export of sampled signals.
+//!
+//! [`write_parquet`] stores a [`RecordBatch`] from
+//! [`to_arrow`](crate::arrow::to_arrow) as an uncompressed Parquet file
+//! with one column per batch column. Rows are split into row groups of
+//! [`ParquetOptions::row_group_rows`] rows. Integer and text columns,
+//! which mostly hold a few distinct states, are dictionary encoded in
+//! each row group where that pays off, so change times stay plain; the
+//! indices and definition levels are run-length
+//! encoded, which suits held values repeating over many rows.
+//!
+//! The file metadata is written with the Thrift compact protocol by hand,
+//! so no Parquet library is needed.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io::{self, Write};
+
+use crate::arrow::{ColumnData, RecordBatch};
+use crate::varint;
+
+/// Rows per row group by default.
+pub const ROW_GROUP_ROWS: usize = 1 << 20;
+
+/// Most distinct values of a column chunk kept in a dictionary.
+pub const DICTIONARY_LIMIT: usize = 1 << 16;
+
+/// Options for [`write_parquet`].
+#[derive(Debug, Clone)]
+pub struct ParquetOptions {
+    pub row_group_rows: usize,
+}
+
+impl Default for ParquetOptions {
+    fn default() -> ParquetOptions {
+        ParquetOptions {
+            row_group_rows: ROW_GROUP_ROWS,
+        }
+    }
+}
+
+// Physical types, converted types and encodings of parquet.thrift.
+const BOOLEAN: i32 = 0;
+const INT64: i32 = 2;
+const DOUBLE: i32 = 5;
+const BYTE_ARRAY: i32 = 6;
+const UTF8: i32 = 0;
+const UINT_64: i32 = 14;
+const PLAIN: i32 = 0;
+const RLE: i32 = 3;
+const RLE_DICTIONARY: i32 = 8;
+const REQUIRED: i32 = 0;
+const OPTIONAL: i32 = 1;
+const DATA_PAGE: i32 = 0;
+const DICTIONARY_PAGE: i32 = 2;
+
+/// Writer for the Thrift compact protocol.
+#[derive(Default)]
+struct Compact {
+    out: Vec<u8>,
+    last: i16,
+    stack: Vec<i16>,
+}
+
+// Compact protocol type codes.
+const T_I32: u8 = 5;
+const T_I64: u8 = 6;
+const T_BINARY: u8 = 8;
+const T_LIST: u8 = 9;
+const T_STRUCT: u8 = 12;
+
+impl Compact {
+    fn header(&mut self, id: i16, ty: u8) {
+        let delta = id - self.last;
+        if (1..=15).contains(&delta) {
+            self.out.push((delta as u8) << 4 | ty);
+        } else {
+            self.out.push(ty);
+            varint::write(&mut self.out, ((id << 1) ^ (id >> 15)) as u16 as u64);
+        }
+        self.last = id;
+    }
+
+    fn i32(&mut self, id: i16, v: i32) {
+        self.header(id, T_I32);
+        self.i32_element(v);
+    }
+
+    fn i64(&mut self, id: i16, v: i64) {
+        self.header(id, T_I64);
+        varint::write(&mut self.out, ((v << 1) ^ (v >> 63)) as u64);
+    }
+
+    fn binary(&mut self, id: i16, b: &[u8]) {
+        self.header(id, T_BINARY);
+        self.binary_element(b);
+    }
+
+    fn list(&mut self, id: i16, ty: u8, len: usize) {
+        self.header(id, T_LIST);
+        if len < 15 {
+            self.out.push((len as u8) << 4 | ty);
+        } else {
+            self.out.push(0xf0 | ty);
+            varint::write(&mut self.out, len as u64);
+        }
+    }
+
+    fn i32_element(&mut self, v: i32) {
+        varint::write(&mut self.out, ((v << 1) ^ (v >> 31)) as u32 as u64);
+    }
+
+    fn binary_element(&mut self, b: &[u8]) {
+        varint::write(&mut self.out, b.len() as u64);
+        self.out.extend_from_slice(b);
+    }
+
+    /// Start a struct field; `begin_element` starts a struct in a list.
+    fn begin(&mut self, id: i16) {
+        self.header(id, T_STRUCT);
+        self.begin_element();
+    }
+
+    fn begin_element(&mut self) {
+        self.stack.push(self.last);
+        self.last = 0;
+    }
+
+    fn end(&mut self) {
+        self.out.push(0);
+        self.last = self.stack.pop().unwrap_or(0);
+    }
+}
+
+/// Run-length encode `values` of `bit_width` bits in the RLE/bit-packing
+/// hybrid, using runs only.
+fn rle(out: &mut Vec<u8>, values: impl Iterator<Item = u32>, bit_width: u32) {
+    let bytes = bit_width.div_ceil(8) as usize;
+    let mut values = values.peekable();
+    while let Some(v) = values.next() {
+        let mut run = 1u64;
+        while values.next_if_eq(&v).is_some() {
+            run += 1;
+        }
+        varint::write(out, run << 1);
+        out.extend_from_slice(&v.to_le_bytes()[..bytes]);
+    }
+}
+
+/// The distinct values in order of appearance and the index of each
+/// value, if there are few enough for a dictionary to pay: at most
+/// [`DICTIONARY_LIMIT`] and at most half the values.
+fn dictionary<T: Copy + Eq + Hash>(values: &[T]) -> Option<(Vec<T>, Vec<u32>)> {
+    let mut index = HashMap::new();
+    let mut distinct = Vec::new();
+    let mut keys = Vec::with_capacity(values.len());
+    for &v in values {
+        let k = *index.entry(v).or_insert_with(|| {
+            distinct.push(v);
+            distinct.len() - 1
+        });
+        if distinct.len() > DICTIONARY_LIMIT {
+            return None;
+        }
+        keys.push(k as u32);
+    }
+    (distinct.len() * 2 <= values.len()).then_some((distinct, keys))
+}
+
+/// The non-null values of a column chunk.
+enum Values<'a> {
+    Boolean(Vec<bool>),
+    Int64(Vec<u64>),
+    Double(Vec<f64>),
+    Bytes(Vec<&'a [u8]>),
+}
+
+fn plain_bytes(out: &mut Vec<u8>, values: &[&[u8]]) {
+    for b in values {
+        out.extend_from_slice(&(b.len() as u32).to_le_bytes());
+        out.extend_from_slice(b);
+    }
+}
+
+fn plain_words(out: &mut Vec<u8>, values: &[u64]) {
+    for v in values {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+}
+
+impl Values<'_> {
+    /// The dictionary page contents, number of entries and keys, for
+    /// columns worth a dictionary.
+    fn dictionary(&self) -> Option<(Vec<u8>, usize, Vec<u32>)> {
+        let mut page = Vec::new();
+        match self {
+            Values::Int64(v) => {
+                let (dict, keys) = dictionary(v)?;
+                plain_words(&mut page, &dict);
+                Some((page, dict.len(), keys))
+            }
+            Values::Bytes(v) => {
+                let (dict, keys) = dictionary(v)?;
+                plain_bytes(&mut page, &dict);
+                Some((page, dict.len(), keys))
+            }
+            _ => None,
+        }
+    }
+
+    fn plain(&self, out: &mut Vec<u8>) {
+        match self {
+            Values::Boolean(v) => {
+                let start = out.len();
+                out.resize(start + v.len().div_ceil(8), 0);
+                for (i, &b) in v.iter().enumerate() {
+                    out[start + i / 8] |= (b as u8) << (i % 8);
+                }
+            }
+            Values::Int64(v) => plain_words(out, v),
+            Values::Double(v) => {
+                for f in v {
+                    out.extend_from_slice(&f.to_le_bytes());
+                }
+            }
+            Values::Bytes(v) => plain_bytes(out, v),
+        }
+    }
+}
+
+/// The non-null values in `rows` of a column and whether each row holds
+/// one.
+fn chunk_values(data: &ColumnData, rows: std::ops::Range<usize>) -> (Values<'_>, Vec<bool>) {
+    fn split<T: Copy>(v: &[Option<T>]) -> (Vec<T>, Vec<bool>) {
+        (
+            v.iter().flatten().copied().collect(),
+            v.iter().map(Option::is_some).collect(),
+        )
+    }
+    match data {
+        ColumnData::Boolean(v) => {
+            let (values, defined) = split(&v[rows]);
+            (Values::Boolean(values), defined)
+        }
+        ColumnData::UInt64(v) => {
+            let (values, defined) = split(&v[rows]);
+            (Values::Int64(values), defined)
+        }
+        ColumnData::Float64(v) => {
+            let (values, defined) = split(&v[rows]);
+            (Values::Double(values), defined)
+        }
+        ColumnData::Utf8(v) => {
+            let v = &v[rows];
+            let values = v.iter().flatten().map(|s| s.as_bytes()).collect();
+            (
+                Values::Bytes(values),
+                v.iter().map(Option::is_some).collect(),
+            )
+        }
+    }
+}
+
+struct Schema {
+    name: String,
+    physical: i32,
+    converted: Option<i32>,
+    required: bool,
+}
+
+/// A written column chunk, as described in the row group metadata.
+struct Chunk {
+    physical: i32,
+    path: String,
+    encodings: Vec<i32>,
+    rows: usize,
+    start: u64,
+    dictionary_page: Option<u64>,
+    data_page: u64,
+    size: u64,
+}
+
+/// A `Write` keeping track of the file offset.
+struct Counting<'a, W> {
+    inner: &'a mut W,
+    pos: u64,
+}
+
+impl<W: Write> Counting<'_, W> {
+    fn write_all(&mut self, b: &[u8]) -> io::Result<()> {
+        self.inner.write_all(b)?;
+        self.pos += b.len() as u64;
+        Ok(())
+    }
+
+    fn page(&mut self, ty: i32, header: impl FnOnce(&mut Compact), body: &[u8]) -> io::Result<()> {
+        let size = i32::try_from(body.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "page too large"))?;
+        let mut h = Compact::default();
+        h.i32(1, ty);
+        h.i32(2, size);
+        h.i32(3, size);
+        header(&mut h);
+        h.end();
+        self.write_all(&h.out)?;
+        self.write_all(body)
+    }
+}
+
+/// Write one column chunk: a dictionary page if it pays, and a data page.
+fn write_chunk<W: Write>(
+    out: &mut Counting<'_, W>,
+    schema: &Schema,
+    values: &Values<'_>,
+    defined: &[bool],
+) -> io::Result<Chunk> {
+    let start = out.pos;
+    let mut body = Vec::new();
+    if !schema.required {
+        let mut levels = Vec::new();
+        rle(&mut levels, defined.iter().map(|&d| d as u32), 1);
+        body.extend_from_slice(&(levels.len() as u32).to_le_bytes());
+        body.extend_from_slice(&levels);
+    }
+    let rows = defined.len();
+    let mut dictionary_page = None;
+    let encoding = match values.dictionary() {
+        Some((page, entries, keys)) => {
+            dictionary_page = Some(start);
+            out.page(
+                DICTIONARY_PAGE,
+                |h| {
+                    h.begin(7);
+                    h.i32(1, entries as i32);
+                    h.i32(2, PLAIN);
+                    h.end();
+                },
+                &page,
+            )?;
+            let bit_width = (32 - (entries as u32).saturating_sub(1).leading_zeros()).max(1);
+            body.push(bit_width as u8);
+            rle(&mut body, keys.into_iter(), bit_width);
+            RLE_DICTIONARY
+        }
+        None => {
+            values.plain(&mut body);
+            PLAIN
+        }
+    };
+    let data_page = out.pos;
+    out.page(
+        DATA_PAGE,
+        |h| {
+            h.begin(5);
+            h.i32(1, rows as i32);
+            h.i32(2, encoding);
+            h.i32(3, RLE);
+            h.i32(4, RLE);
+            h.end();
+        },
+        &body,
+    )?;
+    let mut encodings = vec![PLAIN, RLE];
+    if encoding == RLE_DICTIONARY {
+        encodings.push(RLE_DICTIONARY);
+    }
+    Ok(Chunk {
+        physical: schema.physical,
+        path: schema.name.clone(),
+        encodings,
+        rows,
+        start,
+        dictionary_page,
+        data_page,
+        size: out.pos - start,
+    })
+}
+
+fn schema_element(h: &mut Compact, s: &Schema) {
+    h.begin_element();
+    h.i32(1, s.physical);
+    h.i32(3, if s.required { REQUIRED } else { OPTIONAL });
+    h.binary(4, s.name.as_bytes());
+    if let Some(c) = s.converted {
+        h.i32(6, c);
+    }
+    h.end();
+}
+
+fn column_chunk(h: &mut Compact, c: &Chunk) {
+    h.begin_element();
+    h.i64(2, c.start as i64);
+    h.begin(3);
+    h.i32(1, c.physical);
+    h.list(2, T_I32, c.encodings.len());
+    for &e in &c.encodings {
+        h.i32_element(e);
+    }
+    h.list(3, T_BINARY, 1);
+    h.binary_element(c.path.as_bytes());
+    h.i32(4, 0);
+    h.i64(5, c.rows as i64);
+    h.i64(6, c.size as i64);
+    h.i64(7, c.size as i64);
+    h.i64(9, c.data_page as i64);
+    if let Some(d) = c.dictionary_page {
+        h.i64(11, d as i64);
+    }
+    h.end();
+    h.end();
+}
+
+/// Write `batch` as a Parquet file.
+pub fn write_parquet<W: Write>(
+    out: &mut W,
+    batch: &RecordBatch,
+    options: &ParquetOptions,
+) -> io::Result<()> {
+    let mut schema = vec![Schema {
+        name: "time".to_string(),
+        physical: INT64,
+        converted: Some(UINT_64),
+        required: true,
+    }];
+    for column in &batch.columns {
+        let (physical, converted) = match column.data {
+            ColumnData::Boolean(_) => (BOOLEAN, None),
+            ColumnData::UInt64(_) => (INT64, Some(UINT_64)),
+            ColumnData::Float64(_) => (DOUBLE, None),
+            ColumnData::Utf8(_) => (BYTE_ARRAY, Some(UTF8)),
+        };
+        schema.push(Schema {
+            name: column.name.clone(),
+            physical,
+            converted,
+            required: false,
+        });
+    }
+
+    let mut out = Counting { inner: out, pos: 0 };
+    out.write_all(b"PAR1")?;
+    let rows = batch.num_rows();
+    let group = options.row_group_rows.max(1);
+    let mut groups = Vec::new();
+    for first in (0..rows).step_by(group) {
+        let range = first..rows.min(first + group);
+        let time = Values::Int64(batch.time[range.clone()].to_vec());
+        let mut chunks = vec![write_chunk(
+            &mut out,
+            &schema[0],
+            &time,
+            &vec![true; range.len()],
+        )?];
+        for (column, s) in batch.columns.iter().zip(&schema[1..]) {
+            let (values, defined) = chunk_values(&column.data, range.clone());
+            chunks.push(write_chunk(&mut out, s, &values, &defined)?);
+        }
+        groups.push((range.len(), chunks));
+    }
+
+    let mut h = Compact::default();
+    h.i32(1, 1);
+    h.list(2, T_STRUCT, schema.len() + 1);
+    h.begin_element();
+    h.binary(4, b"schema");
+    h.i32(5, schema.len() as i32);
+    h.end();
+    for s in &schema {
+        schema_element(&mut h, s);
+    }
+    h.i64(3, rows as i64);
+    h.list(4, T_STRUCT, groups.len());
+    for (rows, chunks) in &groups {
+        h.begin_element();
+        h.list(1, T_STRUCT, chunks.len());
+        for c in chunks {
+            column_chunk(&mut h, c);
+        }
+        h.i64(2, chunks.iter().map(|c| c.size as i64).sum());
+        h.i64(3, *rows as i64);
+        h.end();
+    }
+    h.binary(6, b"wave_parse");
+    h.end();
+    out.write_all(&h.out)?;
+    out.write_all(&(h.out.len() as u32).to_le_bytes())?;
+    out.write_all(b"PAR1")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::arrow::Column;
+
+    /// A decoded Thrift compact value, enough to check the metadata.
+    #[derive(Debug, PartialEq)]
+    enum Thrift {
+        Bool(bool),
+        Int(i64),
+        Binary(Vec<u8>),
+        List(Vec<Thrift>),
+        Struct(Vec<(i16, Thrift)>),
+    }
+
+    impl Thrift {
+        fn field(&self, id: i16) -> &Thrift {
+            match self {
+                Thrift::Struct(f) => &f.iter().find(|(i, _)| *i == id).unwrap().1,
+                _ => panic!("not a struct"),
+            }
+        }
+
+        fn int(&self) -> i64 {
+            match self {
+                Thrift::Int(i) => *i,
+                _ => panic!("not an integer"),
+            }
+        }
+
+        fn list(&self) -> &[Thrift] {
+            match self {
+                Thrift::List(l) => l,
+                _ => panic!("not a list"),
+            }
+        }
+    }
+
+    fn read_varint(b: &mut &[u8]) -> u64 {
+        let mut pos = 0;
+        let v = varint::read(b, &mut pos).expect("varint");
+        *b = &b[pos..];
+        v
+    }
+
+    fn read_value(b: &mut &[u8], ty: u8) -> Thrift {
+        match ty {
+            1 => Thrift::Bool(true),
+            2 => Thrift::Bool(false),
+            T_I32 | T_I64 => {
+                let v = read_varint(b);
+                Thrift::Int((v >> 1) as i64 ^ -((v & 1) as i64))
+            }
+            T_BINARY => {
+                let len = read_varint(b) as usize;
+                let (v, rest) = b.split_at(len);
+                *b = rest;
+                Thrift::Binary(v.to_vec())
+            }
+            T_LIST => {
+                let h = b[0];
+                *b = &b[1..];
+                let len = match h >> 4 {
+                    15 => read_varint(b) as usize,
+                    n => n as usize,
+                };
+                Thrift::List((0..len).map(|_| read_value(b, h & 15)).collect())
+            }
+            T_STRUCT => {
+                let mut fields = Vec::new();
+                let mut last = 0;
+                loop {
+                    let h = b[0];
+                    *b = &b[1..];
+                    if h == 0 {
+                        break;
+                    }
+                    last = match h >> 4 {
+                        0 => {
+                            let v = read_varint(b);
+                            ((v >> 1) as i64 ^ -((v & 1) as i64)) as i16
+                        }
+                        d => last + d as i16,
+                    };
+                    fields.push((last, read_value(b, h & 15)));
+                }
+                Thrift::Struct(fields)
+            }
+            _ => panic!("unexpected type {}", ty),
+        }
+    }
+
+    #[test]
+    fn file_layout() {
+        let rows = 5;
+        let batch = RecordBatch {
+            time: (0..rows as u64).map(|t| t * 10).collect(),
+            columns: vec![
+                Column {
+                    name: "top.state".to_string(),
+                    data: ColumnData::UInt64(vec![None, Some(3), Some(3), Some(7), Some(3)]),
+                },
+                Column {
+                    name: "top.clk".to_string(),
+                    data: ColumnData::Boolean(vec![Some(false), Some(true), None, None, None]),
+                },
+            ],
+        };
+        let mut file = Vec::new();
+        let options = ParquetOptions { row_group_rows: 3 };
+        write_parquet(&mut file, &batch, &options).unwrap();
+        assert_eq!(&file[..4], b"PAR1");
+        assert_eq!(&file[file.len() - 4..], b"PAR1");
+
+        let len = u32::from_le_bytes(file[file.len() - 8..file.len() - 4].try_into().unwrap());
+        let meta_start = file.len() - 8 - len as usize;
+        let mut meta = &file[meta_start..file.len() - 8];
+        let meta = read_value(&mut meta, T_STRUCT);
+        assert_eq!(meta.field(3).int(), rows as i64);
+        let schema = meta.field(2).list();
+        assert_eq!(schema.len(), 4);
+        assert_eq!(*schema[2].field(4), Thrift::Binary(b"top.state".to_vec()));
+        let groups = meta.field(4).list();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[1].field(3).int(), 2);
+
+        // The state chunk of the first group: dictionary [3], then keys.
+        let chunk = groups[0].field(1).list()[1].field(3);
+        assert_eq!(chunk.field(5).int(), 3);
+        let dict = chunk.field(11).int() as usize;
+        let mut page = &file[dict..];
+        let header = read_value(&mut page, T_STRUCT);
+        assert_eq!(header.field(1).int(), DICTIONARY_PAGE as i64);
+        assert_eq!(header.field(7).field(1).int(), 1);
+        assert_eq!(&page[..8], &3u64.to_le_bytes());
+
+        let mut page = &file[chunk.field(9).int() as usize..];
+        let header = read_value(&mut page, T_STRUCT);
+        assert_eq!(header.field(5).field(2).int(), RLE_DICTIONARY as i64);
+        let size = header.field(2).int() as usize;
+        // Levels [0, 1, 1] as two runs, bit width 1, one run of key 0.
+        assert_eq!(&page[..size], &[4, 0, 0, 0, 2, 0, 4, 1, 1, 4, 0]);
+
+        // The last chunk, without a dictionary, ends at the metadata.
+        let last = groups[1].field(1).list()[2].field(3);
+        assert_eq!(last.field(9).int() + last.field(7).int(), meta_start as i64);
+    }
+}
@@ -0,0 +1,137 @@
+//! Progress reporting and cancellation of long parses and loads.
+
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// The part of a long operation being reported by a [`ProgressSink`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Parsing the header. The total is the file length, as the end of the
+    /// header is not known in advance; the phase ends early.
+    Header,
+    /// Scanning the body to build an index.
+    Index,
+    /// Parsing the parts of the body holding the requested signals.
+    Signals,
+}
+
+/// Receives the progress of parsing and signal loading, in bytes.
+///
+/// Calls may come from several threads at once, roughly every megabyte
+/// of input, and should return quickly. Any `Fn(Phase, u64, u64)`
+/// closure is a sink.
+pub trait ProgressSink: Send + Sync {
+    /// `done` of `total` bytes of `phase` have been processed.
+    fn progress(&self, phase: Phase, done: u64, total: u64);
+}
+
+impl<F: Fn(Phase, u64, u64) + Send + Sync> ProgressSink for F {
+    fn progress(&self, phase: Phase, done: u64, total: u64) {
+        self(phase, done, total)
+    }
+}
+
+/// A flag shared between a running operation and whoever may abort it.
+///
+/// Clones share the flag. Operations given a token check it at the same
+/// points they report progress and fail with [`Cancelled`] once it is set,
+/// dropping everything loaded so far.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    /// Ask operations using the token to stop.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Error returned by an operation stopped through its [`CancelToken`].
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl Cancelled {
+    /// Whether `e` reports a cancelled operation.
+    pub fn is(e: &io::Error) -> bool {
+        e.get_ref().is_some_and(|e| e.is::<Cancelled>())
+    }
+}
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "operation cancelled".fmt(f)
+    }
+}
+impl Error for Cancelled {}
+impl From<Cancelled> for io::Error {
+    fn from(e: Cancelled) -> io::Error {
+        io::Error::other(e)
+    }
+}
+
+/// Bytes processed between two reports from a loop.
+pub(crate) const REPORT_STEP: usize = 1 << 20;
+
+/// The progress of one phase, shared by the threads working on it.
+pub(crate) struct Progress<'a> {
+    sink: Option<&'a dyn ProgressSink>,
+    cancel: Option<&'a CancelToken>,
+    phase: Phase,
+    total: u64,
+    done: AtomicU64,
+}
+
+impl<'a> Progress<'a> {
+    /// Start `phase`, reporting that nothing is done yet.
+    pub(crate) fn new(
+        sink: Option<&'a dyn ProgressSink>,
+        cancel: Option<&'a CancelToken>,
+        phase: Phase,
+        total: u64,
+    ) -> Progress<'a> {
+        if let Some(sink) = sink {
+            sink.progress(phase, 0, total);
+        }
+        Progress {
+            sink,
+            cancel,
+            phase,
+            total,
+            done: AtomicU64::new(0),
+        }
+    }
+
+    /// A phase nobody listens to or cancels.
+    pub(crate) fn none() -> Progress<'static> {
+        Progress::new(None, None, Phase::Signals, 0)
+    }
+
+    /// Fail if the phase was cancelled.
+    pub(crate) fn check(&self) -> io::Result<()> {
+        match self.cancel {
+            Some(cancel) if cancel.is_cancelled() => Err(Cancelled.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Record `bytes` more bytes as done, failing if the phase was
+    /// cancelled.
+    pub(crate) fn add(&self, bytes: usize) -> io::Result<()> {
+        if let Some(sink) = self.sink {
+            let done = self.done.fetch_add(bytes as u64, Ordering::Relaxed) + bytes as u64;
+            sink.progress(self.phase, done.min(self.total), self.total);
+        }
+        self.check()
+    }
+}
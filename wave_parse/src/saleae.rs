@@ -0,0 +1,244 @@
+//! Import of Saleae logic analyzer captures.
+//!
+//! Two export formats are understood:
+//!
+//! * the Logic 2 binary export of digital channels, one `digital_N.bin`
+//!   file per channel, and
+//! * CSV exports of digital channels, with a time column in seconds
+//!   followed by one `0`/`1` column per channel.
+//!
+//! Capture times are in seconds relative to the trigger; they are shifted
+//! so that the earliest sample is at time 0 and rounded to the requested
+//! [`Timescale`]. Every channel becomes a one-bit wire at the top level of
+//! the hierarchy.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+use crate::{InvalidData, MemoryWaveform, Signal, SignalId, Timescale, Var, VarKind};
+
+const MAGIC: &[u8; 8] = b"<SALEAE>";
+const TYPE_DIGITAL: i32 = 0;
+
+/// One digital channel of a binary export.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DigitalCapture {
+    /// Level of the channel at `begin_time`.
+    pub initial_state: bool,
+    pub begin_time: f64,
+    pub end_time: f64,
+    /// Times at which the level toggles, in increasing order.
+    pub transitions: Vec<f64>,
+}
+
+fn read_array<const N: usize, R: Read>(r: &mut R) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Read a digital channel in the Logic 2 binary export format.
+pub fn read_digital<R: Read>(mut r: R) -> io::Result<DigitalCapture> {
+    if &read_array::<8, _>(&mut r)? != MAGIC {
+        return Err(InvalidData("not a Saleae binary export").into());
+    }
+    let version = i32::from_le_bytes(read_array(&mut r)?);
+    if version != 0 {
+        return Err(InvalidData("unsupported Saleae binary version").into());
+    }
+    if i32::from_le_bytes(read_array(&mut r)?) != TYPE_DIGITAL {
+        return Err(InvalidData("Saleae binary export is not a digital channel").into());
+    }
+    let initial_state = u32::from_le_bytes(read_array(&mut r)?) != 0;
+    let begin_time = f64::from_le_bytes(read_array(&mut r)?);
+    let end_time = f64::from_le_bytes(read_array(&mut r)?);
+    let count = u64::from_le_bytes(read_array(&mut r)?);
+    let mut transitions = Vec::new();
+    for _ in 0..count {
+        transitions.push(f64::from_le_bytes(read_array(&mut r)?));
+    }
+    Ok(DigitalCapture {
+        initial_state,
+        begin_time,
+        end_time,
+        transitions,
+    })
+}
+
+fn to_ticks(time: f64, origin: f64, tick: f64) -> u64 {
+    ((time - origin) / tick).round().max(0.0) as u64
+}
+
+fn level(high: bool) -> &'static [u8] {
+    if high {
+        b"1"
+    } else {
+        b"0"
+    }
+}
+
+fn channel_waveform<'a, I>(names: I, timescale: Timescale) -> MemoryWaveform
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut wave = MemoryWaveform::new(Default::default(), Some(timescale));
+    for (i, name) in names.into_iter().enumerate() {
+        let hierarchy = wave.hierarchy_mut();
+        let name = hierarchy.intern(name);
+        hierarchy.vars.push(Var {
+            kind: VarKind::Wire,
+            width: 1,
+            signal: SignalId(i as u64),
+            name,
+            index: None,
+        });
+    }
+    wave
+}
+
+/// Combine named digital channels into one waveform.
+pub fn import_digital<S: AsRef<str>>(
+    channels: &[(S, DigitalCapture)],
+    timescale: Timescale,
+) -> MemoryWaveform {
+    let tick = timescale.seconds();
+    let origin = channels
+        .iter()
+        .map(|(_, c)| c.begin_time)
+        .fold(f64::INFINITY, f64::min);
+    let mut wave = channel_waveform(channels.iter().map(|(name, _)| name.as_ref()), timescale);
+    for (i, (_, capture)) in channels.iter().enumerate() {
+        let mut signal = Signal::new();
+        let mut high = capture.initial_state;
+        signal.push(to_ticks(capture.begin_time, origin, tick), level(high));
+        for &t in &capture.transitions {
+            high = !high;
+            signal.push(to_ticks(t, origin, tick), level(high));
+        }
+        wave.insert_signal(SignalId(i as u64), signal);
+    }
+    wave
+}
+
+/// Import binary channel files, naming each channel after its file stem.
+pub fn import_binary_files<P: AsRef<Path>>(
+    paths: &[P],
+    timescale: Timescale,
+) -> io::Result<MemoryWaveform> {
+    let mut channels = Vec::with_capacity(paths.len());
+    for path in paths {
+        let path = path.as_ref();
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let capture = read_digital(BufReader::new(File::open(path)?))?;
+        channels.push((name, capture));
+    }
+    Ok(import_digital(&channels, timescale))
+}
+
+/// Import a CSV export of digital channels.
+///
+/// The first line names the columns; the first column is the time in
+/// seconds, the others hold one channel each. Only changes are kept.
+pub fn import_csv<R: BufRead>(r: R, timescale: Timescale) -> io::Result<MemoryWaveform> {
+    let mut lines = r.lines();
+    let header = lines
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "empty CSV file"))??;
+    let names: Vec<&str> = header.split(',').skip(1).map(str::trim).collect();
+    if names.is_empty() {
+        return Err(InvalidData("CSV capture without channel columns").into());
+    }
+
+    let tick = timescale.seconds();
+    let mut origin = None;
+    let mut signals: Vec<Signal> = names.iter().map(|_| Signal::new()).collect();
+    let mut last: Vec<Option<bool>> = vec![None; names.len()];
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut fields = line.split(',').map(str::trim);
+        let time: f64 = fields
+            .next()
+            .and_then(|t| t.parse().ok())
+            .ok_or(InvalidData("invalid time in CSV capture"))?;
+        let origin = *origin.get_or_insert(time);
+        let time = to_ticks(time, origin, tick);
+        for (i, field) in fields.enumerate().take(names.len()) {
+            let high = match field {
+                "0" => false,
+                "1" => true,
+                _ => return Err(InvalidData("invalid level in CSV capture").into()),
+            };
+            if last[i] != Some(high) {
+                signals[i].push(time, level(high));
+                last[i] = Some(high);
+            }
+        }
+    }
+
+    let mut wave = channel_waveform(names.iter().copied(), timescale);
+    for (i, signal) in signals.into_iter().enumerate() {
+        wave.insert_signal(SignalId(i as u64), signal);
+    }
+    Ok(wave)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{SignalLoader, TimeUnit, Waveform};
+
+    fn binary(initial: u32, begin: f64, transitions: &[f64]) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&0i32.to_le_bytes());
+        out.extend_from_slice(&TYPE_DIGITAL.to_le_bytes());
+        out.extend_from_slice(&initial.to_le_bytes());
+        out.extend_from_slice(&begin.to_le_bytes());
+        out.extend_from_slice(&1.0f64.to_le_bytes());
+        out.extend_from_slice(&(transitions.len() as u64).to_le_bytes());
+        for t in transitions {
+            out.extend_from_slice(&t.to_le_bytes());
+        }
+        out
+    }
+
+    #[test]
+    fn binary_channels() {
+        let a = read_digital(&binary(0, -0.5, &[-0.25, 0.0, 0.5])[..]).unwrap();
+        assert_eq!(a.transitions.len(), 3);
+        let b = read_digital(&binary(1, 0.0, &[0.125])[..]).unwrap();
+        let mut wave = import_digital(&[("a", a), ("b", b)], Timescale::new(1, TimeUnit::MS));
+        assert_eq!(wave.hierarchy().var_count(), 2);
+        let sigs = wave.load_signals(&[SignalId(0), SignalId(1)]).unwrap();
+        assert_eq!(sigs[0].times(), &[0, 250, 500, 1000]);
+        assert_eq!(sigs[0].value(0), b"0");
+        assert_eq!(sigs[0].value(3), b"1");
+        assert_eq!(sigs[1].times(), &[500, 625]);
+        assert_eq!(sigs[1].value(1), b"0");
+
+        assert!(read_digital(&b"<SALEAE>"[..]).is_err());
+        assert!(read_digital(&[0u8; 64][..]).is_err());
+    }
+
+    #[test]
+    fn csv_capture() {
+        let csv = "Time [s],Channel 0,Channel 1\n\
+                   0.000001,0,1\n\
+                   0.000002,1,1\n\
+                   0.000003,1,0\n";
+        let mut wave = import_csv(csv.as_bytes(), Timescale::new(1, TimeUnit::NS)).unwrap();
+        assert!(wave.hierarchy().lookup("Channel 1").is_some());
+        let sigs = wave.load_signals(&[SignalId(0), SignalId(1)]).unwrap();
+        assert_eq!(sigs[0].times(), &[0, 1000]);
+        assert_eq!(sigs[1].times(), &[0, 2000]);
+        assert_eq!(sigs[1].value(1), b"0");
+
+        assert!(import_csv("t,a\n0,2\n".as_bytes(), Timescale::new(1, TimeUnit::NS)).is_err());
+    }
+}
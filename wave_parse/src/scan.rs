@@ -0,0 +1,209 @@
+//! Byte scanning for the tokenizer.
+//!
+//! Words in a VCD body are usually only a few bytes long, so every search
+//! starts with a short scalar loop. Longer runs (wide vector values,
+//! indentation, comment text, whole lines) continue sixteen or thirty-two
+//! bytes at a time with SSE2 or AVX2 on x86_64, picked at runtime. Other
+//! targets use the scalar loop throughout.
+
+/// Bytes checked one at a time before switching to vector instructions.
+const SCALAR_PREFIX: usize = 16;
+
+#[inline]
+pub(crate) fn is_whitespace(b: u8) -> bool {
+    matches!(b, b' ' | b'\n' | b'\r' | b'\t')
+}
+
+/// Offset of the first non-whitespace byte at or after `from`, or
+/// `data.len()`.
+#[inline]
+pub(crate) fn skip_whitespace(data: &[u8], from: usize) -> usize {
+    scan(data, from, false)
+}
+
+/// Offset of the first whitespace byte at or after `from`, or
+/// `data.len()`.
+#[inline]
+pub(crate) fn find_whitespace(data: &[u8], from: usize) -> usize {
+    scan(data, from, true)
+}
+
+/// Offset of the first `needle` at or after `from`.
+#[inline]
+pub(crate) fn find_byte(data: &[u8], from: usize, needle: u8) -> Option<usize> {
+    let end = data.len().min(from + SCALAR_PREFIX);
+    if let Some(p) = data[from..end].iter().position(|&b| b == needle) {
+        return Some(from + p);
+    }
+    if end == data.len() {
+        return None;
+    }
+    imp::find_byte(data, end, needle)
+}
+
+#[inline]
+fn scan(data: &[u8], from: usize, ws: bool) -> usize {
+    let end = data.len().min(from + SCALAR_PREFIX);
+    let mut i = from;
+    while i < end {
+        if is_whitespace(data[i]) == ws {
+            return i;
+        }
+        i += 1;
+    }
+    if i == data.len() {
+        return i;
+    }
+    imp::scan(data, i, ws)
+}
+
+fn scan_scalar(data: &[u8], from: usize, ws: bool) -> usize {
+    data[from..]
+        .iter()
+        .position(|&b| is_whitespace(b) == ws)
+        .map_or(data.len(), |p| from + p)
+}
+
+fn find_byte_scalar(data: &[u8], from: usize, needle: u8) -> Option<usize> {
+    data[from..]
+        .iter()
+        .position(|&b| b == needle)
+        .map(|p| from + p)
+}
+
+#[cfg(target_arch = "x86_64")]
+mod imp {
+    use std::arch::x86_64::*;
+
+    pub fn scan(data: &[u8], from: usize, ws: bool) -> usize {
+        if is_x86_feature_detected!("avx2") {
+            unsafe { scan_avx2(data, from, ws) }
+        } else {
+            unsafe { scan_sse2(data, from, ws) }
+        }
+    }
+
+    pub fn find_byte(data: &[u8], from: usize, needle: u8) -> Option<usize> {
+        if is_x86_feature_detected!("avx2") {
+            unsafe { find_byte_avx2(data, from, needle) }
+        } else {
+            unsafe { find_byte_sse2(data, from, needle) }
+        }
+    }
+
+    #[target_feature(enable = "sse2")]
+    unsafe fn scan_sse2(data: &[u8], mut i: usize, ws: bool) -> usize {
+        let space = _mm_set1_epi8(b' ' as i8);
+        let nl = _mm_set1_epi8(b'\n' as i8);
+        let cr = _mm_set1_epi8(b'\r' as i8);
+        let tab = _mm_set1_epi8(b'\t' as i8);
+        while i + 16 <= data.len() {
+            let v = _mm_loadu_si128(data.as_ptr().add(i) as *const __m128i);
+            let m = _mm_or_si128(
+                _mm_or_si128(_mm_cmpeq_epi8(v, space), _mm_cmpeq_epi8(v, nl)),
+                _mm_or_si128(_mm_cmpeq_epi8(v, cr), _mm_cmpeq_epi8(v, tab)),
+            );
+            let mut mask = _mm_movemask_epi8(m) as u32;
+            if !ws {
+                mask = !mask & 0xffff;
+            }
+            if mask != 0 {
+                return i + mask.trailing_zeros() as usize;
+            }
+            i += 16;
+        }
+        super::scan_scalar(data, i, ws)
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn scan_avx2(data: &[u8], mut i: usize, ws: bool) -> usize {
+        let space = _mm256_set1_epi8(b' ' as i8);
+        let nl = _mm256_set1_epi8(b'\n' as i8);
+        let cr = _mm256_set1_epi8(b'\r' as i8);
+        let tab = _mm256_set1_epi8(b'\t' as i8);
+        while i + 32 <= data.len() {
+            let v = _mm256_loadu_si256(data.as_ptr().add(i) as *const __m256i);
+            let m = _mm256_or_si256(
+                _mm256_or_si256(_mm256_cmpeq_epi8(v, space), _mm256_cmpeq_epi8(v, nl)),
+                _mm256_or_si256(_mm256_cmpeq_epi8(v, cr), _mm256_cmpeq_epi8(v, tab)),
+            );
+            let mut mask = _mm256_movemask_epi8(m) as u32;
+            if !ws {
+                mask = !mask;
+            }
+            if mask != 0 {
+                return i + mask.trailing_zeros() as usize;
+            }
+            i += 32;
+        }
+        scan_sse2(data, i, ws)
+    }
+
+    #[target_feature(enable = "sse2")]
+    unsafe fn find_byte_sse2(data: &[u8], mut i: usize, needle: u8) -> Option<usize> {
+        let n = _mm_set1_epi8(needle as i8);
+        while i + 16 <= data.len() {
+            let v = _mm_loadu_si128(data.as_ptr().add(i) as *const __m128i);
+            let mask = _mm_movemask_epi8(_mm_cmpeq_epi8(v, n)) as u32;
+            if mask != 0 {
+                return Some(i + mask.trailing_zeros() as usize);
+            }
+            i += 16;
+        }
+        super::find_byte_scalar(data, i, needle)
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn find_byte_avx2(data: &[u8], mut i: usize, needle: u8) -> Option<usize> {
+        let n = _mm256_set1_epi8(needle as i8);
+        while i + 32 <= data.len() {
+            let v = _mm256_loadu_si256(data.as_ptr().add(i) as *const __m256i);
+            let mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(v, n)) as u32;
+            if mask != 0 {
+                return Some(i + mask.trailing_zeros() as usize);
+            }
+            i += 32;
+        }
+        find_byte_sse2(data, i, needle)
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+mod imp {
+    pub fn scan(data: &[u8], from: usize, ws: bool) -> usize {
+        super::scan_scalar(data, from, ws)
+    }
+
+    pub fn find_byte(data: &[u8], from: usize, needle: u8) -> Option<usize> {
+        super::find_byte_scalar(data, from, needle)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn matches_scalar() {
+        let mut data = Vec::new();
+        for i in 0..300usize {
+            data.extend(std::iter::repeat_n(b'1', i % 70));
+            data.push([b' ', b'\n', b'\r', b'\t'][i % 4]);
+            data.extend(std::iter::repeat_n(b' ', i % 37));
+        }
+        for from in 0..data.len() {
+            assert_eq!(find_whitespace(&data, from), scan_scalar(&data, from, true));
+            assert_eq!(
+                skip_whitespace(&data, from),
+                scan_scalar(&data, from, false)
+            );
+            assert_eq!(
+                find_byte(&data, from, b'\n'),
+                find_byte_scalar(&data, from, b'\n')
+            );
+        }
+        assert_eq!(find_whitespace(b"abc", 3), 3);
+        assert_eq!(skip_whitespace(&[b' '; 100], 0), 100);
+        assert_eq!(find_byte(&[b'a'; 100], 0, b'\n'), None);
+    }
+}
@@ -0,0 +1,99 @@
+//! Searching for the times at which a condition holds.
+//!
+//! A condition is an [`Expression`](crate::expr::Expression) over signal
+//! paths. Its value can only change when one of the signals it refers to
+//! changes, so a search visits the change times of those signals only,
+//! merged in time order, and skips everything in between no matter how
+//! busy the rest of the waveform is.
+
+use std::io;
+
+use crate::expr::Evaluator;
+use crate::Waveform;
+
+/// A condition bound to the signals of a waveform.
+#[derive(Debug, Clone)]
+pub struct Search {
+    eval: Evaluator,
+}
+
+impl Search {
+    /// Parse `condition` and load the signals it refers to.
+    pub fn new<F>(wave: &mut F, condition: &str) -> io::Result<Search>
+    where
+        F: Waveform + ?Sized,
+    {
+        Ok(Search {
+            eval: Evaluator::load(wave, condition)?,
+        })
+    }
+
+    /// The first time at or after `from` at which the condition is true.
+    pub fn find_first(&self, from: u64) -> Option<u64> {
+        self.eval
+            .sweep(from)
+            .find(|(_, v)| v.is_some_and(|w| w.is_true()))
+            .map(|(t, _)| t)
+    }
+
+    /// The first time after `time` at which the condition becomes true,
+    /// having been false or unknown before.
+    pub fn find_next(&self, time: u64) -> Option<u64> {
+        let mut holds = self.eval.eval_at(time).is_some_and(|w| w.is_true());
+        for (t, v) in self.eval.sweep(time).skip(1) {
+            let now = v.is_some_and(|w| w.is_true());
+            if now && !holds {
+                return Some(t);
+            }
+            holds = now;
+        }
+        None
+    }
+}
+
+/// The first time at or after `from` at which `condition` holds in `wave`.
+pub fn find_first<F>(wave: &mut F, condition: &str, from: u64) -> io::Result<Option<u64>>
+where
+    F: Waveform + ?Sized,
+{
+    Ok(Search::new(wave, condition)?.find_first(from))
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::VcdFile;
+
+    #[test]
+    fn first_occurrence() {
+        let mut vcd = VcdFile::from_bytes(
+            b"$scope module top $end $var wire 1 ! valid $end $var wire 1 \" ready $end
+$var wire 8 # addr $end $upscope $end $enddefinitions $end
+#0 0! 0\" bx #
+#10 1! b1000000 #
+#20 1\"
+#30 0\" b10000000 #
+#40 1\"
+#50 0!
+#60 1!"
+                .to_vec(),
+        )
+        .unwrap();
+        let cond = "top.valid && top.ready && top.addr == 0x80";
+        assert_eq!(find_first(&mut vcd, cond, 0).unwrap(), Some(40));
+        assert_eq!(find_first(&mut vcd, cond, 45).unwrap(), Some(45));
+        assert_eq!(
+            find_first(&mut vcd, "top.addr == 0x80", 0).unwrap(),
+            Some(30)
+        );
+        assert_eq!(find_first(&mut vcd, "top.addr == 3", 0).unwrap(), None);
+        assert!(find_first(&mut vcd, "top.nope", 0).is_err());
+
+        let s = Search::new(&mut vcd, cond).unwrap();
+        assert_eq!(s.find_next(40), Some(60));
+        assert_eq!(s.find_next(60), None);
+
+        let s = Search::new(&mut vcd, "top.valid && !delay(top.valid, 5)").unwrap();
+        assert_eq!(s.find_first(0), Some(10));
+        assert_eq!(s.find_next(10), Some(60));
+    }
+}
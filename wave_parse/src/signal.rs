@@ -0,0 +1,265 @@
+use std::mem;
+use std::ops::Range;
+
+#[cfg(feature = "serde")]
+use crate::InvalidData;
+
+/// Identifier of the data behind one or more variables of a waveform.
+///
+/// For VCD inputs this is the numeric value of the identifier code, so
+/// variables sharing an id code share the same `SignalId`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignalId(pub u64);
+
+/// The complete change history of a single signal.
+///
+/// Values are stored as their raw textual encoding (`0`, `1`, `x`, `z` per
+/// bit for logic values) back to back in one buffer, which keeps the number
+/// of allocations per signal constant regardless of its change count.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "SignalParts")
+)]
+pub struct Signal {
+    times: Vec<u64>,
+    offsets: Vec<usize>,
+    data: Vec<u8>,
+}
+
+/// The fields of a deserialized [`Signal`], checked before use.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SignalParts {
+    times: Vec<u64>,
+    offsets: Vec<usize>,
+    data: Vec<u8>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<SignalParts> for Signal {
+    type Error = InvalidData;
+
+    fn try_from(p: SignalParts) -> Result<Signal, InvalidData> {
+        if p.times.len() != p.offsets.len() {
+            return Err(InvalidData("signal times and values differ in number"));
+        }
+        if !p.times.is_sorted() {
+            return Err(InvalidData("signal changes out of order"));
+        }
+        if !p.offsets.is_sorted() || p.offsets.last().is_some_and(|&o| o > p.data.len()) {
+            return Err(InvalidData("signal value out of bounds"));
+        }
+        Ok(Signal {
+            times: p.times,
+            offsets: p.offsets,
+            data: p.data,
+        })
+    }
+}
+
+impl Signal {
+    /// Create a new signal without any changes.
+    pub fn new() -> Signal {
+        Signal::default()
+    }
+
+    /// Append a change. Times are expected to be non-decreasing.
+    #[inline]
+    pub fn push(&mut self, time: u64, value: &[u8]) {
+        debug_assert!(
+            self.times.last().is_none_or(|&last| last <= time),
+            "signal changes must be pushed in time order"
+        );
+        self.times.push(time);
+        self.offsets.push(self.data.len());
+        self.data.extend_from_slice(value);
+    }
+
+    /// Append all changes of `other`, which must not start before the
+    /// last change of `self`.
+    pub fn append(&mut self, other: &Signal) {
+        let base = self.data.len();
+        self.times.extend_from_slice(&other.times);
+        self.offsets.extend(other.offsets.iter().map(|o| o + base));
+        self.data.extend_from_slice(&other.data);
+    }
+
+    /// Number of changes in this signal.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.times.len()
+    }
+
+    /// Whether the signal has no changes at all.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.times.is_empty()
+    }
+
+    /// Time of the `i`-th change.
+    #[inline]
+    pub fn time(&self, i: usize) -> u64 {
+        self.times[i]
+    }
+
+    /// Value of the `i`-th change.
+    #[inline]
+    pub fn value(&self, i: usize) -> &[u8] {
+        let end = self.offsets.get(i + 1).copied().unwrap_or(self.data.len());
+        &self.data[self.offsets[i]..end]
+    }
+
+    /// All change times in order.
+    #[inline]
+    pub fn times(&self) -> &[u64] {
+        &self.times
+    }
+
+    /// Index of the last change at or before `time`.
+    pub fn index_at(&self, time: u64) -> Option<usize> {
+        self.times.partition_point(|&t| t <= time).checked_sub(1)
+    }
+
+    /// Value of the signal at `time`, if it has been assigned by then.
+    /// Changes at `time` itself have taken effect.
+    pub fn value_at(&self, time: u64) -> Option<&[u8]> {
+        self.index_at(time).map(|i| self.value(i))
+    }
+
+    /// Index of the last change strictly before `time`, the last of
+    /// several at the same time.
+    pub fn prev_change(&self, time: u64) -> Option<usize> {
+        self.times.partition_point(|&t| t < time).checked_sub(1)
+    }
+
+    /// Index of the first change strictly after `time`, the first of
+    /// several at the same time.
+    pub fn next_change(&self, time: u64) -> Option<usize> {
+        let i = self.times.partition_point(|&t| t <= time);
+        (i < self.len()).then_some(i)
+    }
+
+    /// Iterate over `(time, value)` pairs.
+    pub fn iter(&self) -> SignalIter<'_> {
+        SignalIter {
+            signal: self,
+            i: 0,
+            end: self.len(),
+        }
+    }
+
+    /// Iterate over the changes at times in `range`.
+    pub fn changes_in(&self, range: Range<u64>) -> SignalIter<'_> {
+        let i = self.times.partition_point(|&t| t < range.start);
+        let end = self.times.partition_point(|&t| t < range.end).max(i);
+        SignalIter {
+            signal: self,
+            i,
+            end,
+        }
+    }
+
+    /// Heap memory held by this signal, in bytes.
+    ///
+    /// This is what a [`SignalStore`](crate::SignalStore) charges against
+    /// its budget.
+    pub fn size_bytes(&self) -> usize {
+        self.times.capacity() * mem::size_of::<u64>()
+            + self.offsets.capacity() * mem::size_of::<usize>()
+            + self.data.capacity()
+    }
+
+    /// Release excess capacity left over from loading.
+    pub fn shrink_to_fit(&mut self) {
+        self.times.shrink_to_fit();
+        self.offsets.shrink_to_fit();
+        self.data.shrink_to_fit();
+    }
+}
+
+/// An iterator over the `(time, value)` changes of a [`Signal`].
+pub struct SignalIter<'s> {
+    signal: &'s Signal,
+    i: usize,
+    end: usize,
+}
+
+impl<'s> Iterator for SignalIter<'s> {
+    type Item = (u64, &'s [u8]);
+
+    #[inline]
+    fn next(&mut self) -> Option<(u64, &'s [u8])> {
+        if self.i >= self.end {
+            return None;
+        }
+        let i = self.i;
+        self.i += 1;
+        Some((self.signal.time(i), self.signal.value(i)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.end - self.i;
+        (n, Some(n))
+    }
+}
+
+impl ExactSizeIterator for SignalIter<'_> {}
+
+impl<'s> IntoIterator for &'s Signal {
+    type Item = (u64, &'s [u8]);
+    type IntoIter = SignalIter<'s>;
+
+    fn into_iter(self) -> SignalIter<'s> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Signal;
+
+    #[test]
+    fn push_and_read_back() {
+        let mut s = Signal::new();
+        s.push(0, b"xxxx");
+        s.push(10, b"0101");
+        s.push(10, b"1");
+        assert_eq!(s.len(), 3);
+        assert_eq!(s.value(0), b"xxxx");
+        assert_eq!(s.value(2), b"1");
+        assert_eq!(s.times(), &[0, 10, 10]);
+        let collected: Vec<_> = s.iter().collect();
+        assert_eq!(collected[1], (10, &b"0101"[..]));
+        assert!(s.size_bytes() >= 3 * 8 + 9);
+
+        let mut t = Signal::new();
+        t.push(0, b"z");
+        t.append(&s);
+        assert_eq!(t.len(), 4);
+        assert_eq!(t.value(0), b"z");
+        assert_eq!(t.value(2), b"0101");
+        assert_eq!(t.value(3), b"1");
+
+        let window: Vec<u64> = t.changes_in(0..10).map(|(time, _)| time).collect();
+        assert_eq!(window, [0, 0]);
+        assert_eq!(t.changes_in(10..11).len(), 2);
+        assert_eq!(t.changes_in(11..20).count(), 0);
+
+        assert_eq!(t.index_at(0), Some(1));
+        assert_eq!(s.value_at(9), Some(&b"xxxx"[..]));
+        assert_eq!(s.value_at(10), Some(&b"1"[..]));
+        assert_eq!(s.value_at(u64::MAX), Some(&b"1"[..]));
+        let mut late = Signal::new();
+        late.push(5, b"0");
+        assert_eq!(late.value_at(4), None);
+
+        assert_eq!(s.prev_change(10), Some(0));
+        assert_eq!(s.prev_change(11), Some(2));
+        assert_eq!(s.prev_change(0), None);
+        assert_eq!(s.next_change(0), Some(1));
+        assert_eq!(s.next_change(10), None);
+    }
+}
@@ -0,0 +1,226 @@
+//! Import of sigrok `.sr` session files.
+//!
+//! A session is a zip archive holding a `version` file, a `metadata` file
+//! in INI syntax and the raw logic samples of each device, split over
+//! `logic-1-1`, `logic-1-2`, ... (or a single `logic-1` in older files).
+//! Each sample is `unitsize` bytes, with bit `n` holding probe `n + 1`.
+//!
+//! Every logic probe becomes a one-bit wire at the top level of the
+//! hierarchy. Time is counted in samples; the timescale is the sample
+//! period when the metadata declares a sample rate that maps onto one
+//! exactly, and picoseconds otherwise. Analog channels are ignored.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::zip::ZipArchive;
+use crate::{InvalidData, MemoryWaveform, Signal, SignalId, TimeUnit, Timescale, Var, VarKind};
+
+/// Parse a sample rate such as `1 MHz` or `200 kHz` into hertz.
+fn parse_samplerate(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let n: u64 = s[..split].parse().ok()?;
+    let mult = match s[split..].trim() {
+        "" | "Hz" => 1,
+        "kHz" => 1_000,
+        "MHz" => 1_000_000,
+        "GHz" => 1_000_000_000,
+        _ => return None,
+    };
+    n.checked_mul(mult)
+}
+
+/// The timescale for a sample rate, and the number of ticks per sample
+/// when the sample period is not an exact timescale.
+fn sample_timescale(rate: u64) -> (Timescale, Option<f64>) {
+    use TimeUnit::*;
+    for unit in [S, MS, US, NS, PS, FS] {
+        let div = unit.divisor();
+        if div % rate == 0 {
+            if let Ok(factor) = u32::try_from(div / rate) {
+                return (Timescale::new(factor, unit), None);
+            }
+        }
+    }
+    let ps = PS.divisor() as f64 / rate as f64;
+    (Timescale::new(1, PS), Some(ps))
+}
+
+/// Sections of an INI file, keyed by section then key.
+fn parse_ini(text: &str) -> HashMap<&str, HashMap<&str, &str>> {
+    let mut out: HashMap<&str, HashMap<&str, &str>> = HashMap::new();
+    let mut section = "";
+    for line in text.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name;
+        } else if let Some((k, v)) = line.split_once('=') {
+            out.entry(section).or_default().insert(k.trim(), v.trim());
+        }
+    }
+    out
+}
+
+/// The logic data of a device, concatenated from all chunk files.
+fn logic_data(zip: &ZipArchive<'_>, capturefile: &str) -> io::Result<Vec<u8>> {
+    if let Some(data) = zip.read(capturefile)? {
+        return Ok(data);
+    }
+    let prefix = format!("{}-", capturefile);
+    let mut chunks: Vec<(u64, &str)> = zip
+        .names()
+        .filter_map(|n| Some((n.strip_prefix(&prefix)?.parse().ok()?, n)))
+        .collect();
+    chunks.sort_unstable();
+    let mut out = Vec::new();
+    for (_, name) in chunks {
+        out.extend_from_slice(&zip.read(name)?.unwrap_or_default());
+    }
+    Ok(out)
+}
+
+/// Import a session from the bytes of a `.sr` file.
+pub fn import(data: &[u8]) -> io::Result<MemoryWaveform> {
+    let zip = ZipArchive::new(data)?;
+    let version = zip
+        .read("version")?
+        .ok_or(InvalidData("sigrok session without version"))?;
+    if version.trim_ascii() != b"2" {
+        return Err(InvalidData("unsupported sigrok session version").into());
+    }
+    let metadata = zip
+        .read("metadata")?
+        .ok_or(InvalidData("sigrok session without metadata"))?;
+    let metadata = String::from_utf8_lossy(&metadata);
+    let ini = parse_ini(&metadata);
+    let device = ini
+        .get("device 1")
+        .ok_or(InvalidData("sigrok session without device"))?;
+
+    let capturefile = device.get("capturefile").copied().unwrap_or("logic-1");
+    let probes: usize = device
+        .get("total probes")
+        .and_then(|v| v.parse().ok())
+        .ok_or(InvalidData("sigrok session without probe count"))?;
+    let unitsize: usize = device
+        .get("unitsize")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1);
+    if !(1..=8).contains(&unitsize) || probes > unitsize * 8 {
+        return Err(InvalidData("unsupported sigrok unit size").into());
+    }
+    let (timescale, scale) = match device.get("samplerate").and_then(|r| parse_samplerate(r)) {
+        Some(rate) if rate > 0 => {
+            let (ts, scale) = sample_timescale(rate);
+            (Some(ts), scale)
+        }
+        _ => (None, None),
+    };
+    let tick = |sample: usize| match scale {
+        Some(s) => (sample as f64 * s).round() as u64,
+        None => sample as u64,
+    };
+
+    let mut wave = MemoryWaveform::new(Default::default(), timescale);
+    for i in 0..probes {
+        let key = format!("probe{}", i + 1);
+        let name = device.get(key.as_str()).copied().unwrap_or(&key);
+        let hierarchy = wave.hierarchy_mut();
+        let name = hierarchy.intern(name);
+        hierarchy.vars.push(Var {
+            kind: VarKind::Wire,
+            width: 1,
+            signal: SignalId(i as u64),
+            name,
+            index: None,
+        });
+    }
+
+    let samples = logic_data(&zip, capturefile)?;
+    let mut signals: Vec<Signal> = (0..probes).map(|_| Signal::new()).collect();
+    let mut prev = None;
+    for (n, sample) in samples.chunks_exact(unitsize).enumerate() {
+        let mut bytes = [0u8; 8];
+        bytes[..unitsize].copy_from_slice(sample);
+        let value = u64::from_le_bytes(bytes);
+        let changed = match prev {
+            Some(p) => p ^ value,
+            None => !0,
+        };
+        if changed != 0 {
+            for (bit, signal) in signals.iter_mut().enumerate() {
+                if changed >> bit & 1 == 1 {
+                    let v: &[u8] = if value >> bit & 1 == 1 { b"1" } else { b"0" };
+                    signal.push(tick(n), v);
+                }
+            }
+        }
+        prev = Some(value);
+    }
+    for (i, signal) in signals.into_iter().enumerate() {
+        wave.insert_signal(SignalId(i as u64), signal);
+    }
+    Ok(wave)
+}
+
+/// Import a session from a `.sr` file.
+pub fn import_file<P: AsRef<Path>>(path: P) -> io::Result<MemoryWaveform> {
+    import(&fs::read(path)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::zip::stored_archive;
+    use crate::{SignalLoader, Waveform};
+
+    const METADATA: &[u8] = b"[global]
+sigrok version=0.5.2
+
+[device 1]
+capturefile=logic-1
+total probes=3
+samplerate=1 MHz
+total analog=0
+probe1=CLK
+probe2=DATA
+probe3=CS
+unitsize=1
+";
+
+    #[test]
+    fn chunked_session() {
+        let data = stored_archive(&[
+            ("version", b"2"),
+            ("metadata", METADATA),
+            ("logic-1-2", &[0b101, 0b101, 0b000]),
+            ("logic-1-1", &[0b100, 0b101, 0b111]),
+        ]);
+        let mut wave = import(&data).unwrap();
+        assert_eq!(wave.timescale(), Some(Timescale::new(1, TimeUnit::US)));
+        let clk = wave.hierarchy().lookup("CLK").unwrap().signal;
+        let cs = wave.hierarchy().lookup("CS").unwrap().signal;
+        let sigs = wave.load_signals(&[clk, cs]).unwrap();
+        assert_eq!(sigs[0].times(), &[0, 1, 5]);
+        assert_eq!(sigs[0].value(1), b"1");
+        assert_eq!(sigs[1].times(), &[0, 5]);
+        assert_eq!(sigs[1].value(0), b"1");
+        assert_eq!(sigs[1].value(1), b"0");
+    }
+
+    #[test]
+    fn sample_rates() {
+        assert_eq!(parse_samplerate("200 kHz"), Some(200_000));
+        assert_eq!(parse_samplerate("1 GHz"), Some(1_000_000_000));
+        assert_eq!(parse_samplerate("fast"), None);
+        assert_eq!(
+            sample_timescale(200_000),
+            (Timescale::new(5, TimeUnit::US), None)
+        );
+        let (ts, scale) = sample_timescale(24_000_000);
+        assert_eq!(ts, Timescale::new(1, TimeUnit::PS));
+        assert!((scale.unwrap() - 41_666.67).abs() < 0.01);
+    }
+}
@@ -0,0 +1,113 @@
+//! Bit slices of vector signals.
+//!
+//! Slices are cut straight out of the raw values, applying VCD's left
+//! extension for values shorter than the vector, so no full value is
+//! decoded per change. Changes that leave the slice unchanged are
+//! dropped.
+
+use std::ops::RangeInclusive;
+
+use crate::{Logic, ReferenceIndex, Signal, Var};
+
+/// The character of bit `i`, counted from the least significant bit, of
+/// a raw vector value.
+#[inline]
+fn bit_char(value: &[u8], i: usize) -> u8 {
+    match value.len().checked_sub(i + 1) {
+        Some(pos) => value[pos],
+        None => Logic::vcd_fill(value).to_char(),
+    }
+}
+
+impl Signal {
+    /// The bits in `bits` of a vector signal, counted from the least
+    /// significant bit, as a signal of its own.
+    ///
+    /// The result only has a change where the slice changes value.
+    pub fn bit_slice(&self, bits: RangeInclusive<u32>) -> Signal {
+        let (lsb, msb) = (*bits.start() as usize, *bits.end() as usize);
+        let mut out = Signal::new();
+        let mut current: Vec<u8> = Vec::with_capacity(msb + 1 - lsb);
+        let mut next = Vec::with_capacity(msb + 1 - lsb);
+        for (time, value) in self {
+            next.clear();
+            next.extend((lsb..=msb).rev().map(|i| bit_char(value, i)));
+            if out.is_empty() || next != current {
+                out.push(time, &next);
+                std::mem::swap(&mut current, &mut next);
+            }
+        }
+        out
+    }
+
+    /// A single bit of a vector signal, see [`bit_slice`](Signal::bit_slice).
+    pub fn bit(&self, bit: u32) -> Signal {
+        self.bit_slice(bit..=bit)
+    }
+}
+
+impl Var {
+    /// The position, counted from the least significant bit, of the bit
+    /// with the declared index `index`, e.g. 3 for bit 11 of `[15:8]`.
+    pub fn bit_position(&self, index: i32) -> Option<u32> {
+        let (msb, lsb) = match self.index {
+            Some(ReferenceIndex::Range(msb, lsb)) => (msb, lsb),
+            Some(ReferenceIndex::BitSelect(i)) => (i, i),
+            None => (self.width as i32 - 1, 0),
+        };
+        let (lo, hi) = (msb.min(lsb), msb.max(lsb));
+        if !(lo..=hi).contains(&index) {
+            return None;
+        }
+        Some(index.abs_diff(lsb))
+    }
+
+    /// Positions of the declared bit range `msb..=lsb` (in either order),
+    /// for use with [`Signal::bit_slice`].
+    pub fn slice_positions(&self, msb: i32, lsb: i32) -> Option<RangeInclusive<u32>> {
+        let (a, b) = (self.bit_position(msb)?, self.bit_position(lsb)?);
+        Some(a.min(b)..=a.max(b))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{NameId, ReferenceIndex, Signal, SignalId, Var, VarKind};
+
+    #[test]
+    fn slices() {
+        let mut s = Signal::new();
+        s.push(0, b"x");
+        s.push(1, b"1");
+        s.push(2, b"100000001");
+        s.push(3, b"100001101");
+        s.push(4, b"z0101");
+        let hi = s.bit_slice(4..=7);
+        assert_eq!(hi.times(), &[0, 1, 4]);
+        assert_eq!(hi.value(0), b"xxxx");
+        assert_eq!(hi.value(1), b"0000");
+        assert_eq!(hi.value(2), b"zzzz");
+        let lo = s.bit_slice(0..=3);
+        assert_eq!(lo.times(), &[0, 1, 3, 4]);
+        assert_eq!(lo.value(2), b"1101");
+        assert_eq!(s.bit(8).times(), &[0, 1, 2, 4]);
+    }
+
+    #[test]
+    fn declared_positions() {
+        let mut var = Var {
+            kind: VarKind::Wire,
+            width: 8,
+            signal: SignalId(0),
+            name: NameId(0),
+            index: Some(ReferenceIndex::Range(15, 8)),
+        };
+        assert_eq!(var.bit_position(11), Some(3));
+        assert_eq!(var.bit_position(7), None);
+        assert_eq!(var.slice_positions(15, 12), Some(4..=7));
+        var.index = Some(ReferenceIndex::Range(0, 7));
+        assert_eq!(var.bit_position(0), Some(7));
+        var.index = None;
+        assert_eq!(var.slice_positions(3, 0), Some(0..=3));
+    }
+}
@@ -0,0 +1,296 @@
+//! Snapshots of parsed waveforms.
+//!
+//! A snapshot stores a hierarchy, the timescale and any subset of loaded
+//! signals in a compact binary form, so the result of parsing a large dump
+//! can be cached on disk or sent to another process and read back as a
+//! [`MemoryWaveform`] without touching the original file.
+//!
+//! The format is little-endian: the magic `WPSNAP` and a version byte,
+//! the timescale, the scope tree, then the signals with their changes.
+//! Strings and values are prefixed with their length; kinds are stored
+//! with their VCD keywords.
+//!
+//! This format stays readable by any version of the crate, as the version
+//! byte of the magic changes with the layout. A caller that wants the
+//! waveform in another format, e.g. to send it over RPC, enables the
+//! `serde` feature: [`Hierarchy`], [`Signal`] and the types inside them
+//! then implement serde's traits, and deserializing checks what the
+//! reader here checks.
+
+use std::io::{self, Read, Write};
+
+use crate::{
+    Hierarchy, InvalidData, MemoryWaveform, Scope, Signal, SignalId, Timescale, Var, Waveform,
+};
+
+const MAGIC: &[u8; 7] = b"WPSNAP\x01";
+
+/// Write the hierarchy and timescale of `wave` and the signals `ids`.
+/// Signals not listed load as empty from the snapshot.
+pub fn write_snapshot<W, F>(out: &mut W, wave: &mut F, ids: &[SignalId]) -> io::Result<()>
+where
+    W: Write,
+    F: Waveform + ?Sized,
+{
+    let signals = wave.load_signals(ids)?;
+    out.write_all(MAGIC)?;
+    match wave.timescale() {
+        Some(ts) => {
+            out.write_all(&[1])?;
+            out.write_all(&ts.factor.to_le_bytes())?;
+            write_str(out, ts.unit.as_str())?;
+        }
+        None => out.write_all(&[0])?,
+    }
+    write_hierarchy(out, wave.hierarchy())?;
+    write_u64(out, ids.len() as u64)?;
+    for (id, signal) in ids.iter().zip(&signals) {
+        write_u64(out, id.0)?;
+        write_u64(out, signal.len() as u64)?;
+        for (t, v) in signal.iter() {
+            write_u64(out, t)?;
+            write_bytes(out, v)?;
+        }
+    }
+    Ok(())
+}
+
+/// Read a snapshot written by [`write_snapshot`].
+pub fn read_snapshot<R: Read>(input: &mut R) -> io::Result<MemoryWaveform> {
+    let mut magic = [0; 7];
+    input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(InvalidData("not a snapshot").into());
+    }
+    let timescale = match read_u8(input)? {
+        0 => None,
+        1 => {
+            let mut factor = [0; 4];
+            input.read_exact(&mut factor)?;
+            let unit = read_string(input)?.parse()?;
+            Some(Timescale::new(u32::from_le_bytes(factor), unit))
+        }
+        _ => return Err(InvalidData("invalid timescale in snapshot").into()),
+    };
+    let mut wave = MemoryWaveform::new(read_hierarchy(input)?, timescale);
+    for _ in 0..read_u64(input)? {
+        let id = SignalId(read_u64(input)?);
+        let mut signal = Signal::new();
+        let mut last = 0;
+        for _ in 0..read_u64(input)? {
+            let t = read_u64(input)?;
+            if t < last {
+                return Err(InvalidData("signal changes out of order").into());
+            }
+            last = t;
+            signal.push(t, &read_bytes(input)?);
+        }
+        wave.insert_signal(id, signal);
+    }
+    Ok(wave)
+}
+
+/// Write the scopes and variables of `h`.
+pub(crate) fn write_hierarchy<W: Write>(out: &mut W, h: &Hierarchy) -> io::Result<()> {
+    write_contents(out, h, &h.scopes, &h.vars)
+}
+
+fn write_contents<W: Write>(
+    out: &mut W,
+    h: &Hierarchy,
+    scopes: &[Scope],
+    vars: &[Var],
+) -> io::Result<()> {
+    write_u64(out, vars.len() as u64)?;
+    for var in vars {
+        write_str(out, &var.kind.to_string())?;
+        out.write_all(&var.width.to_le_bytes())?;
+        write_u64(out, var.signal.0)?;
+        write_str(out, h.name(var.name))?;
+        write_str(out, &var.index.map(|i| i.to_string()).unwrap_or_default())?;
+    }
+    write_u64(out, scopes.len() as u64)?;
+    for scope in scopes {
+        write_str(out, &scope.kind.to_string())?;
+        write_str(out, h.name(scope.name))?;
+        write_contents(out, h, &scope.scopes, &scope.vars)?;
+    }
+    Ok(())
+}
+
+/// Deepest scope nesting accepted, which bounds the recursion on corrupt
+/// input.
+const MAX_DEPTH: usize = 1024;
+
+/// Read a hierarchy written by [`write_hierarchy`].
+pub(crate) fn read_hierarchy<R: Read>(input: &mut R) -> io::Result<Hierarchy> {
+    let mut hierarchy = Hierarchy::default();
+    let (mut scopes, mut vars) = (Vec::new(), Vec::new());
+    read_contents(input, &mut hierarchy, &mut scopes, &mut vars, 0)?;
+    hierarchy.scopes = scopes;
+    hierarchy.vars = vars;
+    Ok(hierarchy)
+}
+
+fn read_contents<R: Read>(
+    input: &mut R,
+    h: &mut Hierarchy,
+    scopes: &mut Vec<Scope>,
+    vars: &mut Vec<Var>,
+    depth: usize,
+) -> io::Result<()> {
+    if depth > MAX_DEPTH {
+        return Err(InvalidData("scopes nested too deeply").into());
+    }
+    for _ in 0..read_u64(input)? {
+        let kind = read_string(input)?.parse()?;
+        let mut width = [0; 4];
+        input.read_exact(&mut width)?;
+        let signal = SignalId(read_u64(input)?);
+        let name = h.intern(&read_string(input)?);
+        let index = match read_string(input)?.as_str() {
+            "" => None,
+            i => Some(i.parse()?),
+        };
+        vars.push(Var {
+            kind,
+            width: u32::from_le_bytes(width),
+            signal,
+            name,
+            index,
+        });
+    }
+    for _ in 0..read_u64(input)? {
+        let kind = read_string(input)?.parse()?;
+        let mut scope = Scope::new(kind, h.intern(&read_string(input)?));
+        read_contents(input, h, &mut scope.scopes, &mut scope.vars, depth + 1)?;
+        scopes.push(scope);
+    }
+    Ok(())
+}
+
+pub(crate) fn write_u64<W: Write>(out: &mut W, v: u64) -> io::Result<()> {
+    out.write_all(&v.to_le_bytes())
+}
+
+pub(crate) fn write_bytes<W: Write>(out: &mut W, b: &[u8]) -> io::Result<()> {
+    write_u64(out, b.len() as u64)?;
+    out.write_all(b)
+}
+
+pub(crate) fn write_str<W: Write>(out: &mut W, s: &str) -> io::Result<()> {
+    write_bytes(out, s.as_bytes())
+}
+
+pub(crate) fn read_u8<R: Read>(input: &mut R) -> io::Result<u8> {
+    let mut b = [0];
+    input.read_exact(&mut b)?;
+    Ok(b[0])
+}
+
+pub(crate) fn read_u64<R: Read>(input: &mut R) -> io::Result<u64> {
+    let mut b = [0; 8];
+    input.read_exact(&mut b)?;
+    Ok(u64::from_le_bytes(b))
+}
+
+pub(crate) fn read_bytes<R: Read>(input: &mut R) -> io::Result<Vec<u8>> {
+    let len = read_u64(input)?;
+    let mut b = Vec::new();
+    // A corrupt length fails on the short read instead of allocating.
+    input.take(len).read_to_end(&mut b)?;
+    if b.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(b)
+}
+
+pub(crate) fn read_string<R: Read>(input: &mut R) -> io::Result<String> {
+    String::from_utf8(read_bytes(input)?).map_err(|_| InvalidData("string is not UTF-8").into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{SignalLoader, VcdFile};
+
+    #[test]
+    fn round_trip() {
+        let mut vcd = VcdFile::from_bytes(
+            b"$timescale 10ps $end $scope module top $end $var wire 1 ! clk $end
+$scope begin blk $end $var reg 4 \" q [3:0] $end $upscope $end
+$var real 1 # r $end $upscope $end $enddefinitions $end
+#0 0! bx \" r0.5 #
+#5 1! b1010 \"
+#10 0!"
+                .to_vec(),
+        )
+        .unwrap();
+        let all = vcd.hierarchy().signal_ids();
+        let ids = &all[..2];
+        let mut bytes = Vec::new();
+        write_snapshot(&mut bytes, &mut vcd, ids).unwrap();
+
+        let mut snap = read_snapshot(&mut &bytes[..]).unwrap();
+        assert_eq!(snap.hierarchy(), vcd.hierarchy());
+        assert_eq!(snap.timescale(), vcd.timescale());
+        assert_eq!(
+            snap.load_signals(ids).unwrap(),
+            vcd.load_signals(ids).unwrap()
+        );
+        assert!(snap.signal(all[2]).is_none());
+
+        assert!(read_snapshot(&mut &bytes[..bytes.len() - 1]).is_err());
+        assert!(read_snapshot(&mut &b"WPSNAP\x02"[..]).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        use crate::{FlatHierarchy, LogicVec};
+
+        let mut vcd = VcdFile::from_bytes(
+            b"$scope module top $end $var wire 1 ! clk $end
+$scope begin blk $end $var reg 4 \" q [3:0] $end $upscope $end
+$upscope $end $enddefinitions $end
+#0 0! bx \" #5 1! b1010 \""
+                .to_vec(),
+        )
+        .unwrap();
+        let h = vcd.hierarchy().clone();
+        let json = serde_json::to_string(&h).unwrap();
+        assert_eq!(serde_json::from_str::<Hierarchy>(&json).unwrap(), h);
+        let flat = FlatHierarchy::new(&h);
+        let back: FlatHierarchy =
+            serde_json::from_str(&serde_json::to_string(&flat).unwrap()).unwrap();
+        assert_eq!(back.var_paths(), flat.var_paths());
+
+        let signals = vcd.load_signals(&h.signal_ids()).unwrap();
+        let json = serde_json::to_string(&signals).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<Signal>>(&json).unwrap(), signals);
+        let v = LogicVec::from_bytes(b"1x0z").unwrap();
+        let json = serde_json::to_string(&v).unwrap();
+        assert_eq!(serde_json::from_str::<LogicVec>(&json).unwrap(), v);
+
+        for bad in [
+            r#"{"times":[0,1],"offsets":[0],"data":[48]}"#,
+            r#"{"times":[1,0],"offsets":[0,1],"data":[48,49]}"#,
+            r#"{"times":[0],"offsets":[2],"data":[48]}"#,
+        ] {
+            assert!(serde_json::from_str::<Signal>(bad).is_err(), "{}", bad);
+        }
+        assert!(
+            serde_json::from_str::<LogicVec>(r#"{"states":"Two","len":65,"words":[0]}"#).is_err()
+        );
+        assert!(
+            serde_json::from_str::<LogicVec>(r#"{"states":"Nine","len":1,"words":[15]}"#).is_err()
+        );
+        let unnamed = r#"{"names":["top"],"scopes":[],"vars":[
+            {"kind":"Wire","width":1,"signal":0,"name":1,"index":null}]}"#;
+        assert!(serde_json::from_str::<Hierarchy>(unnamed).is_err());
+        assert!(
+            serde_json::from_str::<Hierarchy>(r#"{"names":["a","a"],"scopes":[],"vars":[]}"#)
+                .is_err()
+        );
+    }
+}
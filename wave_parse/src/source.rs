@@ -0,0 +1,933 @@
+//! Random-access byte sources and VCD reading over them.
+//!
+//! A [`ByteSource`] serves byte ranges of a file that may not be local:
+//! [`HttpSource`] fetches them from a web server with range requests.
+//! [`SourceVcd`] reads a VCD file through a source, fetching only the
+//! header to browse the hierarchy and, given the file's [sidecar
+//! index](crate::index), only the blocks containing the signals that are
+//! loaded. Without an index, loading reads the whole body. Blocks are
+//! fetched on several threads, and a [`CachedSource`] keeps recently read
+//! blocks, so remote files are not fetched again when browsing.
+//!
+//! With the `https` feature, [`HttpSource`] also reads `https://` URLs, over
+//! rustls, checking the server's certificate against the Mozilla roots of
+//! webpki-roots or against the configuration given to
+//! `HttpSource::open_tls`. Without it, serve files over plain HTTP or put
+//! a TLS-terminating proxy in front.
+//!
+//! With the `object_store` feature, `ObjectSource` reads objects in S3, GCS
+//! and Azure, or in any other store of the object_store crate, with ranged
+//! gets. Wrapped in a [`CachedSource`], it is fetched from concurrently by
+//! [`SourceVcd`] like any other source.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+
+use crate::index::VcdIndex;
+use crate::parallel;
+use crate::progress::Progress;
+use crate::vcd::{collect_changes, dump_edges, may_dump_off, parse_header, Header, Tokens};
+use crate::{
+    Blackouts, CancelToken, Hierarchy, InvalidData, Phase, ProgressSink, Signal, SignalId,
+    SignalLoader, Timescale, Waveform,
+};
+
+/// A file that can be read at arbitrary offsets.
+pub trait ByteSource: Send + Sync {
+    /// Length of the file in bytes.
+    fn len(&self) -> u64;
+
+    /// Fill `buf` with the bytes starting at `offset`.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()>;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn out_of_range() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "read past the end of the source",
+    )
+}
+
+impl ByteSource for [u8] {
+    fn len(&self) -> u64 {
+        <[u8]>::len(self) as u64
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        let start = usize::try_from(offset).map_err(|_| out_of_range())?;
+        let src = start
+            .checked_add(buf.len())
+            .and_then(|end| self.get(start..end))
+            .ok_or_else(out_of_range)?;
+        buf.copy_from_slice(src);
+        Ok(())
+    }
+}
+
+impl ByteSource for Vec<u8> {
+    fn len(&self) -> u64 {
+        self.as_slice().len() as u64
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        self.as_slice().read_at(offset, buf)
+    }
+}
+
+/// A local file read with positioned reads.
+#[derive(Debug)]
+pub struct FileSource {
+    #[cfg(unix)]
+    file: File,
+    #[cfg(not(unix))]
+    file: std::sync::Mutex<File>,
+    len: u64,
+}
+
+impl FileSource {
+    pub fn new(file: File) -> io::Result<FileSource> {
+        let len = file.metadata()?.len();
+        #[cfg(not(unix))]
+        let file = std::sync::Mutex::new(file);
+        Ok(FileSource { file, len })
+    }
+}
+
+impl ByteSource for FileSource {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    #[cfg(unix)]
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        std::os::unix::fs::FileExt::read_exact_at(&self.file, buf, offset)
+    }
+
+    #[cfg(not(unix))]
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        use std::io::{Seek, SeekFrom};
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(buf)
+    }
+}
+
+/// Sequential reading from a source, e.g. to read an index with
+/// [`VcdIndex::read`].
+pub struct SourceReader<'a, S: ?Sized> {
+    source: &'a S,
+    pos: u64,
+}
+
+impl<'a, S: ByteSource + ?Sized> SourceReader<'a, S> {
+    pub fn new(source: &'a S) -> SourceReader<'a, S> {
+        SourceReader { source, pos: 0 }
+    }
+}
+
+impl<S: ByteSource + ?Sized> Read for SourceReader<'_, S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = (self.source.len().saturating_sub(self.pos)).min(buf.len() as u64) as usize;
+        self.source.read_at(self.pos, &mut buf[..n])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+/// A file on a web server, read with HTTP/1.1 range requests.
+///
+/// Every read opens a new connection.
+#[derive(Debug, Clone)]
+pub struct HttpSource {
+    host: String,
+    port: u16,
+    path: String,
+    len: u64,
+    /// The TLS configuration of an `https://` URL.
+    #[cfg(feature = "https")]
+    tls: Option<Arc<rustls::ClientConfig>>,
+}
+
+fn http_error(msg: String) -> io::Error {
+    io::Error::other(msg)
+}
+
+/// The client configuration of `https://` URLs opened with
+/// [`HttpSource::open`].
+#[cfg(feature = "https")]
+fn web_roots() -> io::Result<Arc<rustls::ClientConfig>> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let roots = rustls::RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(io::Error::other)?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+impl HttpSource {
+    /// Connect to the file at an `http://host[:port]/path` URL and find its
+    /// length. With the `https` feature, `https://` URLs are read over TLS.
+    pub fn open(url: &str) -> io::Result<HttpSource> {
+        let rest = match url.strip_prefix("http://") {
+            Some(rest) => rest,
+            #[cfg(feature = "https")]
+            None if url.starts_with("https://") => return HttpSource::open_tls(url, web_roots()?),
+            #[cfg(not(feature = "https"))]
+            None if url.starts_with("https://") => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "https needs the https feature",
+                ))
+            }
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("not an http URL: {}", url),
+                ))
+            }
+        };
+        let mut source = HttpSource::parse(url, rest, 80)?;
+        source.len = source.request(0, 0, &mut [0])?;
+        Ok(source)
+    }
+
+    /// Connect to the file at an `https://host[:port]/path` URL, checking
+    /// the server's certificate with `config`, and find its length.
+    #[cfg(feature = "https")]
+    pub fn open_tls(url: &str, config: Arc<rustls::ClientConfig>) -> io::Result<HttpSource> {
+        let rest = url.strip_prefix("https://").ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("not an https URL: {}", url),
+            )
+        })?;
+        let mut source = HttpSource::parse(url, rest, 443)?;
+        source.tls = Some(config);
+        source.len = source.request(0, 0, &mut [0])?;
+        Ok(source)
+    }
+
+    /// The source for `url`, whose part after the scheme is `rest`, before
+    /// finding its length.
+    fn parse(url: &str, rest: &str, default_port: u16) -> io::Result<HttpSource> {
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port.parse().map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("invalid port in {}", url),
+                    )
+                })?;
+                (host, port)
+            }
+            None => (authority, default_port),
+        };
+        Ok(HttpSource {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+            len: 0,
+            #[cfg(feature = "https")]
+            tls: None,
+        })
+    }
+
+    /// Fetch the inclusive range `first..=last` into `buf`, returning the
+    /// file length reported by the server.
+    fn request(&self, first: u64, last: u64, buf: &mut [u8]) -> io::Result<u64> {
+        let stream = TcpStream::connect((self.host.as_str(), self.port))?;
+        #[cfg(feature = "https")]
+        if let Some(config) = &self.tls {
+            let name = rustls::pki_types::ServerName::try_from(self.host.clone())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            let conn =
+                rustls::ClientConnection::new(config.clone(), name).map_err(io::Error::other)?;
+            return self.exchange(rustls::StreamOwned::new(conn, stream), first, last, buf);
+        }
+        self.exchange(stream, first, last, buf)
+    }
+
+    /// Send the request for `first..=last` over `stream` and read the
+    /// response into `buf`.
+    fn exchange<S: Read + Write>(
+        &self,
+        mut stream: S,
+        first: u64,
+        last: u64,
+        buf: &mut [u8],
+    ) -> io::Result<u64> {
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nHost: {}\r\nRange: bytes={}-{}\r\nUser-Agent: wave_parse\r\n\
+             Connection: close\r\n\r\n",
+            self.path, self.host, first, last
+        )?;
+        stream.flush()?;
+        let mut r = BufReader::new(stream);
+        let mut line = String::new();
+        r.read_line(&mut line)?;
+        let status = line.split_whitespace().nth(1).unwrap_or("");
+        if status != "206" {
+            return Err(http_error(match status {
+                "200" => format!("{} does not support range requests", self.host),
+                _ => format!("HTTP request for {} failed: {}", self.path, line.trim()),
+            }));
+        }
+        let mut length = None;
+        let mut total = None;
+        loop {
+            line.clear();
+            if r.read_line(&mut line)? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let Some((name, value)) = line.trim_end().split_once(':') else {
+                break;
+            };
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.parse::<u64>().ok();
+            } else if name.eq_ignore_ascii_case("content-range") {
+                total = value.rsplit('/').next().and_then(|t| t.parse::<u64>().ok());
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                return Err(http_error(format!(
+                    "unsupported transfer encoding {}",
+                    value
+                )));
+            }
+        }
+        let (Some(length), Some(total)) = (length, total) else {
+            return Err(http_error("response without content length".to_string()));
+        };
+        if length != buf.len() as u64 {
+            return Err(http_error(format!(
+                "asked for {} bytes, got {}",
+                buf.len(),
+                length
+            )));
+        }
+        r.read_exact(buf)?;
+        Ok(total)
+    }
+}
+
+impl ByteSource for HttpSource {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        if buf.is_empty() {
+            return Ok(());
+        }
+        if offset + buf.len() as u64 > self.len {
+            return Err(out_of_range());
+        }
+        self.request(offset, offset + buf.len() as u64 - 1, buf)
+            .map(|_| ())
+    }
+}
+
+/// An object in an object store, read with ranged gets.
+///
+/// The stores' clients are async: reads block on a runtime owned by the
+/// source, so they must not be made from inside an async task.
+#[cfg(feature = "object_store")]
+#[derive(Debug, Clone)]
+pub struct ObjectSource {
+    store: Arc<dyn object_store::ObjectStore>,
+    path: object_store::path::Path,
+    len: u64,
+    runtime: Arc<tokio::runtime::Runtime>,
+}
+
+#[cfg(feature = "object_store")]
+impl ObjectSource {
+    /// Open the object at an `s3://`, `gs://` or `az://` URL, configuring
+    /// the store from the environment, e.g. from `AWS_REGION` and
+    /// `AWS_ACCESS_KEY_ID` for S3.
+    pub fn open_url(url: &str) -> io::Result<ObjectSource> {
+        let url =
+            url::Url::parse(url).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let (store, path) = object_store::parse_url_opts(&url, std::env::vars())?;
+        ObjectSource::open(store.into(), path)
+    }
+
+    /// Open the object at `path` in `store` and find its length.
+    pub fn open(
+        store: Arc<dyn object_store::ObjectStore>,
+        path: object_store::path::Path,
+    ) -> io::Result<ObjectSource> {
+        use object_store::ObjectStoreExt;
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?;
+        let len = runtime.block_on(store.head(&path))?.size;
+        Ok(ObjectSource {
+            store,
+            path,
+            len,
+            runtime: Arc::new(runtime),
+        })
+    }
+}
+
+#[cfg(feature = "object_store")]
+impl ByteSource for ObjectSource {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        use object_store::ObjectStoreExt;
+
+        if buf.is_empty() {
+            return Ok(());
+        }
+        let end = offset
+            .checked_add(buf.len() as u64)
+            .filter(|&end| end <= self.len)
+            .ok_or_else(out_of_range)?;
+        let bytes = (self.runtime).block_on(self.store.get_range(&self.path, offset..end))?;
+        if bytes.len() != buf.len() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.copy_from_slice(&bytes);
+        Ok(())
+    }
+}
+
+/// Size of the blocks kept by a [`CachedSource`].
+pub const CACHE_BLOCK_SIZE: usize = 1 << 20;
+
+/// A source keeping the most recently read blocks of another source in
+/// memory, up to a budget in bytes. Reads at least as large as the
+/// budget bypass the cache.
+pub struct CachedSource<S> {
+    inner: S,
+    budget: usize,
+    cache: Mutex<BlockCache>,
+}
+
+#[derive(Default)]
+struct BlockCache {
+    blocks: HashMap<u64, Arc<Vec<u8>>>,
+    /// Least recently used first.
+    order: VecDeque<u64>,
+}
+
+impl<S: ByteSource> CachedSource<S> {
+    pub fn new(inner: S, budget: usize) -> CachedSource<S> {
+        CachedSource {
+            inner,
+            budget,
+            cache: Mutex::default(),
+        }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Number of blocks currently cached.
+    pub fn cached_blocks(&self) -> usize {
+        self.cache.lock().unwrap().blocks.len()
+    }
+
+    fn block(&self, n: u64) -> io::Result<Arc<Vec<u8>>> {
+        {
+            let mut cache = self.cache.lock().unwrap();
+            if let Some(block) = cache.blocks.get(&n).cloned() {
+                let pos = cache.order.iter().position(|&b| b == n).expect("cached");
+                cache.order.remove(pos);
+                cache.order.push_back(n);
+                return Ok(block);
+            }
+        }
+        // Fetch without holding the lock, so other blocks can be read
+        // meanwhile.
+        let start = n * CACHE_BLOCK_SIZE as u64;
+        let mut buf = vec![0; (self.inner.len() - start).min(CACHE_BLOCK_SIZE as u64) as usize];
+        self.inner.read_at(start, &mut buf)?;
+        let block = Arc::new(buf);
+        let mut cache = self.cache.lock().unwrap();
+        if cache.blocks.insert(n, block.clone()).is_none() {
+            cache.order.push_back(n);
+        }
+        while cache.blocks.len() > 1 && cache.blocks.len() * CACHE_BLOCK_SIZE > self.budget {
+            let old = cache.order.pop_front().expect("blocks are ordered");
+            cache.blocks.remove(&old);
+        }
+        Ok(block)
+    }
+}
+
+impl<S: ByteSource> ByteSource for CachedSource<S> {
+    fn len(&self) -> u64 {
+        self.inner.len()
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        if buf.len() >= self.budget {
+            return self.inner.read_at(offset, buf);
+        }
+        if offset + buf.len() as u64 > self.len() {
+            return Err(out_of_range());
+        }
+        let bs = CACHE_BLOCK_SIZE as u64;
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done as u64;
+            let block = self.block(pos / bs)?;
+            let from = (pos % bs) as usize;
+            let n = (block.len() - from).min(buf.len() - done);
+            buf[done..done + n].copy_from_slice(&block[from..from + n]);
+            done += n;
+        }
+        Ok(())
+    }
+}
+
+/// Bytes fetched first when looking for the end of the header; doubled
+/// until the header is complete.
+const HEADER_FETCH: usize = 64 << 10;
+
+/// A VCD file read through a [`ByteSource`].
+pub struct SourceVcd<S> {
+    source: S,
+    header: Header,
+    body_start: usize,
+    index: Option<VcdIndex>,
+    threads: usize,
+    progress: Option<Arc<dyn ProgressSink>>,
+    cancel: Option<CancelToken>,
+}
+
+fn offset(v: u64) -> io::Result<usize> {
+    usize::try_from(v).map_err(|_| InvalidData("file too large").into())
+}
+
+impl<S: ByteSource> SourceVcd<S> {
+    /// Read and parse the header of the file.
+    pub fn open(source: S) -> io::Result<SourceVcd<S>> {
+        let len = offset(source.len())?;
+        let mut fetch = HEADER_FETCH;
+        loop {
+            let mut buf = vec![0; fetch.min(len)];
+            source.read_at(0, &mut buf)?;
+            // Cut at whitespace so the parser never sees a partial word.
+            let complete = if buf.len() == len {
+                buf.len()
+            } else {
+                buf.iter().rposition(u8::is_ascii_whitespace).unwrap_or(0)
+            };
+            match parse_header(&buf[..complete]) {
+                Ok((header, body_start)) => {
+                    return Ok(SourceVcd {
+                        source,
+                        header,
+                        body_start,
+                        index: None,
+                        threads: parallel::default_threads(),
+                        progress: None,
+                        cancel: None,
+                    })
+                }
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof && buf.len() < len => {
+                    fetch *= 2;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Use the header and index of a sidecar file read from `index`,
+    /// without fetching the header of the VCD file itself.
+    pub fn open_indexed<I>(source: S, index: &I) -> io::Result<SourceVcd<S>>
+    where
+        I: ByteSource + ?Sized,
+    {
+        let (header, index) = VcdIndex::read(&mut BufReader::new(SourceReader::new(index)))?;
+        if index.stamp().len != source.len() || index.file_len() as u64 != source.len() {
+            return Err(InvalidData("index does not match file").into());
+        }
+        Ok(SourceVcd {
+            source,
+            body_start: index.body_start(),
+            header,
+            index: Some(index),
+            threads: parallel::default_threads(),
+            progress: None,
+            cancel: None,
+        })
+    }
+
+    /// Number of blocks fetched and parsed at the same time.
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Report the progress of loading signals to `sink`.
+    pub fn set_progress(&mut self, sink: Option<Arc<dyn ProgressSink>>) {
+        self.progress = sink;
+    }
+
+    /// Stop loading signals, between fetches, once `token` is cancelled.
+    pub fn set_cancel_token(&mut self, token: Option<CancelToken>) {
+        self.cancel = token;
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    pub fn index(&self) -> Option<&VcdIndex> {
+        self.index.as_ref()
+    }
+
+    fn fetch(&self, start: usize, end: usize) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0; end - start];
+        self.source.read_at(start as u64, &mut buf)?;
+        Ok(buf)
+    }
+}
+
+impl<S: ByteSource> SignalLoader for SourceVcd<S> {
+    /// Fetches and parses the blocks the index lists for `ids` on
+    /// [`threads`](SourceVcd::threads) threads, or the whole body without
+    /// an index.
+    fn load_signals(&mut self, ids: &[SignalId]) -> io::Result<Vec<Signal>> {
+        let mut slots = HashMap::with_capacity(ids.len());
+        for &id in ids {
+            let n = slots.len();
+            slots.entry(id).or_insert(n);
+        }
+        let ranges = match &self.index {
+            Some(index) => index
+                .blocks_of(ids)
+                .into_iter()
+                .map(|i| index.block(i))
+                .collect(),
+            None => {
+                let body = self.body_start..offset(self.source.len())?;
+                vec![body]
+            }
+        };
+        let total = ranges.iter().map(|r| r.len() as u64).sum();
+        let progress = Progress::new(
+            self.progress.as_deref(),
+            self.cancel.as_ref(),
+            Phase::Signals,
+            total,
+        );
+        let parts = parallel::map_indexed(ranges.len(), self.threads, |i| {
+            progress.check()?;
+            let data = self.fetch(ranges[i].start, ranges[i].end)?;
+            collect_changes(Tokens::new(&data, 0), 0, &slots, slots.len(), &progress)
+        })?;
+        let mut merged: Vec<Signal> = (0..slots.len()).map(|_| Signal::new()).collect();
+        for part in parts {
+            for (signal, piece) in merged.iter_mut().zip(&part) {
+                signal.append(piece);
+            }
+        }
+        Ok(ids.iter().map(|id| merged[slots[id]].clone()).collect())
+    }
+}
+
+impl<S: ByteSource> Waveform for SourceVcd<S> {
+    fn hierarchy(&self) -> &Hierarchy {
+        self.header.hierarchy()
+    }
+
+    fn timescale(&self) -> Option<Timescale> {
+        self.header.timescale
+    }
+
+    fn date(&self) -> Option<&str> {
+        self.header.date.as_deref()
+    }
+
+    /// Taken from the index if there is one, otherwise found by fetching
+    /// the whole body.
+    fn blackouts(&self) -> io::Result<Blackouts> {
+        if let Some(index) = &self.index {
+            return Ok(index.blackouts().clone());
+        }
+        let body = self.fetch(self.body_start, offset(self.source.len())?)?;
+        if !may_dump_off(&body) {
+            return Ok(Blackouts::default());
+        }
+        Ok(Blackouts::from_edges(dump_edges(Tokens::new(&body, 0))?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::index::Stamp;
+    use crate::VcdFile;
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// A source counting the bytes read from it.
+    struct Counting(Vec<u8>, AtomicU64);
+
+    impl ByteSource for Counting {
+        fn len(&self) -> u64 {
+            self.0.len() as u64
+        }
+
+        fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+            self.1.fetch_add(buf.len() as u64, Ordering::Relaxed);
+            self.0.read_at(offset, buf)
+        }
+    }
+
+    fn dump() -> Vec<u8> {
+        let mut text = b"$scope module t $end $var wire 4 ! a $end $var wire 1 \" b $end \
+                         $var wire 1 # late $end $upscope $end $enddefinitions $end\n0#\n"
+            .to_vec();
+        for t in 0..300_000u64 {
+            text.extend_from_slice(format!("#{}\nb{:b} !\n{}\"\n", t, t % 16, t % 2).as_bytes());
+        }
+        text.extend_from_slice(b"1#\n");
+        text
+    }
+
+    #[test]
+    fn partial_reads() {
+        let data = dump();
+        let vcd = VcdFile::from_bytes(data.clone()).unwrap();
+        let stamp = Stamp {
+            len: data.len() as u64,
+            modified: 0,
+            hash: 0,
+        };
+        let index = VcdIndex::build(&vcd, stamp).unwrap();
+        let mut sidecar = Vec::new();
+        index.write(vcd.header(), &mut sidecar).unwrap();
+
+        let late = [SignalId(2)];
+        let mut plain = SourceVcd::open(data.clone()).unwrap();
+        assert_eq!(plain.hierarchy(), vcd.hierarchy());
+        let expected = plain.load_signals(&late).unwrap();
+        assert_eq!(expected[0].times(), &[0, 299_999]);
+
+        let source = Counting(data, AtomicU64::new(0));
+        let mut indexed = SourceVcd::open_indexed(source, &sidecar).unwrap();
+        indexed.set_threads(4);
+        assert_eq!(indexed.load_signals(&late).unwrap(), expected);
+        let read = indexed.source().1.load(Ordering::Relaxed);
+        assert!(read < indexed.source().len() / 2);
+        let all = [SignalId(0), SignalId(1), SignalId(2)];
+        assert_eq!(
+            indexed.load_signals(&all).unwrap(),
+            plain.load_signals(&all).unwrap()
+        );
+        assert!(SourceVcd::open_indexed(b"short".to_vec(), &sidecar).is_err());
+    }
+
+    /// Answer one range request for `data` on `stream`.
+    fn respond<S: Read + Write>(mut stream: S, data: &[u8]) {
+        let mut range = None;
+        let mut r = BufReader::new(&mut stream);
+        let mut line = String::new();
+        while r.read_line(&mut line).unwrap() > 2 {
+            if let Some(v) = line.trim().strip_prefix("Range: bytes=") {
+                let (a, b) = v.split_once('-').unwrap();
+                range = Some((a.parse::<usize>().unwrap(), b.parse::<usize>().unwrap()));
+            }
+            line.clear();
+        }
+        let (a, b) = range.unwrap();
+        let body = &data[a..=b];
+        write!(
+            stream,
+            "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\n\
+             Content-Range: bytes {}-{}/{}\r\n\r\n",
+            body.len(),
+            a,
+            b,
+            data.len()
+        )
+        .unwrap();
+        stream.write_all(body).unwrap();
+        stream.flush().unwrap();
+    }
+
+    /// Serve `data` with range requests until the listener is dropped.
+    fn serve(data: Arc<Vec<u8>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/dump.vcd", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                respond(stream.unwrap(), &data);
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn block_cache() {
+        let data: Vec<u8> = (0..3 * CACHE_BLOCK_SIZE).map(|i| i as u8).collect();
+        let cached = CachedSource::new(Counting(data, AtomicU64::new(0)), 2 * CACHE_BLOCK_SIZE);
+        let fetched = || cached.inner().1.load(Ordering::Relaxed) as usize;
+        let mut buf = [0; 16];
+        cached
+            .read_at(CACHE_BLOCK_SIZE as u64 - 8, &mut buf)
+            .unwrap();
+        assert_eq!(buf[8], 0);
+        assert_eq!(
+            (fetched(), cached.cached_blocks()),
+            (2 * CACHE_BLOCK_SIZE, 2)
+        );
+        cached.read_at(10, &mut buf).unwrap();
+        assert_eq!(buf[0], 10);
+        assert_eq!(fetched(), 2 * CACHE_BLOCK_SIZE);
+        // Reading the third block evicts the second, used least recently.
+        cached
+            .read_at(2 * CACHE_BLOCK_SIZE as u64, &mut buf)
+            .unwrap();
+        assert_eq!(cached.cached_blocks(), 2);
+        cached.read_at(5, &mut buf).unwrap();
+        assert_eq!(fetched(), 3 * CACHE_BLOCK_SIZE);
+        assert!(cached
+            .read_at(3 * CACHE_BLOCK_SIZE as u64 - 1, &mut buf)
+            .is_err());
+    }
+
+    #[test]
+    fn http_ranges() {
+        let data = Arc::new(
+            b"$timescale 1ns $end $scope module top $end $var wire 1 ! clk $end $upscope $end
+$enddefinitions $end
+#0 0!
+#5 1!"
+                .to_vec(),
+        );
+        let url = serve(data.clone());
+        let source = HttpSource::open(&url).unwrap();
+        assert_eq!(source.len(), data.len() as u64);
+        let mut buf = [0; 4];
+        source.read_at(1, &mut buf).unwrap();
+        assert_eq!(&buf, b"time");
+        assert!(source.read_at(data.len() as u64 - 1, &mut buf).is_err());
+
+        let mut vcd = SourceVcd::open(source).unwrap();
+        let clk = vcd.hierarchy().lookup("top.clk").unwrap().signal;
+        assert_eq!(vcd.load_signals(&[clk]).unwrap()[0].len(), 2);
+
+        #[cfg(not(feature = "https"))]
+        {
+            let err = HttpSource::open("https://example.com/x.fst").unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        }
+    }
+
+    #[cfg(feature = "https")]
+    #[test]
+    fn https_ranges() {
+        use rustls::pki_types::pem::PemObject;
+        use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+        let provider = || Arc::new(rustls::crypto::ring::default_provider());
+        let certs = CertificateDer::pem_slice_iter(include_bytes!("../testdata/tls/server.pem"))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let key =
+            PrivateKeyDer::from_pem_slice(include_bytes!("../testdata/tls/server.key")).unwrap();
+        let server = Arc::new(
+            rustls::ServerConfig::builder_with_provider(provider())
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_no_client_auth()
+                .with_single_cert(certs, key)
+                .unwrap(),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("https://{}/dump.vcd", listener.local_addr().unwrap());
+        let data = dump();
+        let served = data.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let conn = rustls::ServerConnection::new(server.clone()).unwrap();
+                respond(rustls::StreamOwned::new(conn, stream.unwrap()), &served);
+            }
+        });
+
+        let mut roots = rustls::RootCertStore::empty();
+        let ca = CertificateDer::from_pem_slice(include_bytes!("../testdata/tls/ca.pem")).unwrap();
+        roots.add(ca).unwrap();
+        let client = Arc::new(
+            rustls::ClientConfig::builder_with_provider(provider())
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_root_certificates(roots)
+                .with_no_client_auth(),
+        );
+        let source = HttpSource::open_tls(&url, client).unwrap();
+        assert_eq!(source.len(), data.len() as u64);
+        let mut buf = [0; 4];
+        source.read_at(1, &mut buf).unwrap();
+        assert_eq!(buf, data[1..5]);
+
+        // The server's certificate is not signed by any of the web's roots.
+        let err = HttpSource::open(&url).unwrap_err();
+        assert!(err.to_string().contains("certificate"), "{}", err);
+    }
+
+    #[cfg(feature = "object_store")]
+    #[test]
+    fn object_ranges() {
+        use object_store::memory::InMemory;
+        use object_store::path::Path;
+        use object_store::ObjectStoreExt;
+
+        let data = dump();
+        let store = Arc::new(InMemory::new());
+        let path = Path::from("ci/dump.vcd");
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime
+            .block_on(store.put(&path, data.clone().into()))
+            .unwrap();
+
+        let source = ObjectSource::open(store.clone(), path).unwrap();
+        assert_eq!(source.len(), data.len() as u64);
+        let mut buf = [0; 4];
+        source.read_at(1, &mut buf).unwrap();
+        assert_eq!(buf, data[1..5]);
+        assert!(source.read_at(data.len() as u64 - 1, &mut buf).is_err());
+
+        let mut vcd = SourceVcd::open(CachedSource::new(source, 4 * CACHE_BLOCK_SIZE)).unwrap();
+        let late = vcd.hierarchy().lookup("t.late").unwrap().signal;
+        assert_eq!(vcd.load_signals(&[late]).unwrap()[0].times(), &[0, 299_999]);
+        assert!(ObjectSource::open(store, Path::from("missing.vcd")).is_err());
+    }
+}
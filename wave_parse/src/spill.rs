@@ -0,0 +1,178 @@
+//! Temporary on-disk storage for signals evicted from a
+//! [`SignalStore`](crate::SignalStore).
+//!
+//! Signals are appended to one file in a compact encoding: times as
+//! LEB128 deltas, and values made only of `0`, `1`, `x` and `z` packed
+//! four to a byte. A 1-bit signal takes about 2 bytes per change on disk
+//! instead of 17 in memory. Entries are never rewritten, as a signal does
+//! not change once loaded; the file is deleted when dropped.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{varint, InvalidData, Signal, SignalId};
+
+const CORRUPT: InvalidData = InvalidData("corrupt spill file");
+
+/// Logic characters in the order of their 2-bit codes.
+const LOGIC: [u8; 4] = *b"01xz";
+
+pub(crate) struct SpillFile {
+    file: File,
+    path: PathBuf,
+    len: u64,
+    max_len: u64,
+    entries: HashMap<SignalId, (u64, usize)>,
+}
+
+impl SpillFile {
+    /// Create a new spill file in `dir` holding at most `max_len` bytes.
+    pub(crate) fn create(dir: &Path, max_len: u64) -> io::Result<SpillFile> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let n = COUNT.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("wave_parse_spill_{}_{}", std::process::id(), n));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(SpillFile {
+            file,
+            path,
+            len: 0,
+            max_len,
+            entries: HashMap::new(),
+        })
+    }
+
+    pub(crate) fn contains(&self, id: SignalId) -> bool {
+        self.entries.contains_key(&id)
+    }
+
+    pub(crate) fn dir(&self) -> &Path {
+        self.path.parent().expect("spill files are in a directory")
+    }
+
+    pub(crate) fn max_len(&self) -> u64 {
+        self.max_len
+    }
+
+    /// Bytes written so far.
+    pub(crate) fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Store `signal` unless it is already stored. Returns whether it is
+    /// stored, which it is not if the file would grow past its limit.
+    pub(crate) fn write(&mut self, id: SignalId, signal: &Signal) -> io::Result<bool> {
+        if self.contains(id) {
+            return Ok(true);
+        }
+        let mut buf = Vec::new();
+        encode(signal, &mut buf);
+        if self.len + buf.len() as u64 > self.max_len {
+            return Ok(false);
+        }
+        self.file.seek(SeekFrom::Start(self.len))?;
+        self.file.write_all(&buf)?;
+        self.entries.insert(id, (self.len, buf.len()));
+        self.len += buf.len() as u64;
+        Ok(true)
+    }
+
+    /// Read back a stored signal.
+    pub(crate) fn read(&mut self, id: SignalId) -> io::Result<Option<Signal>> {
+        let Some(&(offset, len)) = self.entries.get(&id) else {
+            return Ok(None);
+        };
+        let mut buf = vec![0; len];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut buf)?;
+        decode(&buf).map(Some)
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn encode(signal: &Signal, out: &mut Vec<u8>) {
+    varint::write(out, signal.len() as u64);
+    let mut last = 0;
+    for (t, v) in signal.iter() {
+        varint::write(out, t - last);
+        last = t;
+        let packed = v.iter().all(|b| LOGIC.contains(b));
+        varint::write(out, (v.len() as u64) << 1 | packed as u64);
+        if packed {
+            for group in v.chunks(4) {
+                let mut byte = 0;
+                for (i, b) in group.iter().enumerate() {
+                    let code = LOGIC.iter().position(|l| l == b).expect("logic value");
+                    byte |= (code as u8) << (2 * i);
+                }
+                out.push(byte);
+            }
+        } else {
+            out.extend_from_slice(v);
+        }
+    }
+}
+
+fn decode(data: &[u8]) -> io::Result<Signal> {
+    let mut pos = 0;
+    let mut signal = Signal::new();
+    let mut time = 0u64;
+    let mut value = Vec::new();
+    for _ in 0..varint::read(data, &mut pos).ok_or(CORRUPT)? {
+        time = time
+            .checked_add(varint::read(data, &mut pos).ok_or(CORRUPT)?)
+            .ok_or(CORRUPT)?;
+        let head = varint::read(data, &mut pos).ok_or(CORRUPT)?;
+        let len = usize::try_from(head >> 1).map_err(|_| CORRUPT)?;
+        value.clear();
+        if head & 1 == 1 {
+            let bytes = data.get(pos..pos + len.div_ceil(4)).ok_or(CORRUPT)?;
+            pos += bytes.len();
+            value.extend((0..len).map(|i| LOGIC[(bytes[i / 4] >> (2 * (i % 4)) & 3) as usize]));
+        } else {
+            value.extend_from_slice(data.get(pos..pos + len).ok_or(CORRUPT)?);
+            pos += len;
+        }
+        signal.push(time, &value);
+    }
+    Ok(signal)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut signal = Signal::new();
+        signal.push(0, b"x");
+        signal.push(5, b"10z1x");
+        signal.push(5, b"0.25");
+        signal.push(1 << 40, b"");
+        signal.push(u64::MAX, b"X");
+        let mut buf = Vec::new();
+        encode(&signal, &mut buf);
+        assert_eq!(decode(&buf).unwrap(), signal);
+        assert!(decode(&buf[..buf.len() - 1]).is_err());
+
+        let mut file = SpillFile::create(&std::env::temp_dir(), buf.len() as u64).unwrap();
+        let path = file.path.clone();
+        assert!(file.write(SignalId(1), &signal).unwrap());
+        assert!(!file.write(SignalId(2), &signal).unwrap());
+        assert_eq!(file.read(SignalId(1)).unwrap(), Some(signal));
+        assert_eq!(file.read(SignalId(2)).unwrap(), None);
+        drop(file);
+        assert!(!path.exists());
+    }
+}
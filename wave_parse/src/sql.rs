@@ -0,0 +1,199 @@
+//! SQL over waveforms through DataFusion, with the `datafusion` feature.
+//!
+//! [`register_waveform`] adds the table function `sig` to a DataFusion
+//! session. `sig('top.cpu.pc')` is a table with a `time` column holding
+//! the change times of the variable and a `value` column holding its value
+//! from then on, typed as in [`to_arrow`]: booleans for single bits,
+//! unsigned integers for vectors up to 64 bits, doubles for reals and text
+//! otherwise, with unknown values as nulls.
+//!
+//! Several paths, `sig('top.a', 'top.b')`, sample the variables at every
+//! change time of any of them into one column per variable, named by its
+//! path. Tables of single variables join on `time` at the times both
+//! change.
+//!
+//! ```no_run
+//! use std::sync::{Arc, Mutex};
+//! use datafusion::prelude::SessionContext;
+//! use wave_parse::{sql::register_waveform, VcdFile};
+//!
+//! # async fn run() -> datafusion::error::Result<()> {
+//! let ctx = SessionContext::new();
+//! let vcd = VcdFile::open("dump.vcd")?;
+//! register_waveform(&ctx, Arc::new(Mutex::new(vcd)));
+//! let df = ctx
+//!     .sql("SELECT time, value FROM sig('top.cpu.pc') WHERE time BETWEEN 100 AND 200")
+//!     .await?;
+//! df.show().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A table loads its variables when a query using it is planned, through
+//! the waveform's signal loading; filters apply to the loaded rows.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use datafusion::arrow::array::{ArrayRef, BooleanArray, Float64Array, StringArray, UInt64Array};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::catalog::{TableFunctionArgs, TableFunctionImpl, TableProvider};
+use datafusion::common::{plan_err, DataFusionError, Result, ScalarValue};
+use datafusion::datasource::MemTable;
+use datafusion::logical_expr::Expr;
+use datafusion::prelude::SessionContext;
+
+use crate::arrow::{to_arrow, ColumnData};
+use crate::Waveform;
+
+/// The `sig` table function over a shared waveform.
+pub struct SigFunction<W: ?Sized> {
+    wave: Arc<Mutex<W>>,
+}
+
+impl<W: ?Sized> SigFunction<W> {
+    pub fn new(wave: Arc<Mutex<W>>) -> SigFunction<W> {
+        SigFunction { wave }
+    }
+}
+
+impl<W: ?Sized> fmt::Debug for SigFunction<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigFunction").finish_non_exhaustive()
+    }
+}
+
+fn array(data: ColumnData) -> (DataType, ArrayRef) {
+    match data {
+        ColumnData::Boolean(v) => (DataType::Boolean, Arc::new(BooleanArray::from(v))),
+        ColumnData::UInt64(v) => (DataType::UInt64, Arc::new(UInt64Array::from(v))),
+        ColumnData::Float64(v) => (DataType::Float64, Arc::new(Float64Array::from(v))),
+        ColumnData::Utf8(v) => (DataType::Utf8, Arc::new(StringArray::from(v))),
+    }
+}
+
+impl<W> TableFunctionImpl for SigFunction<W>
+where
+    W: Waveform + Send + ?Sized + 'static,
+{
+    fn call_with_args(&self, args: TableFunctionArgs) -> Result<Arc<dyn TableProvider>> {
+        let mut paths = Vec::with_capacity(args.exprs().len());
+        for arg in args.exprs() {
+            match arg {
+                Expr::Literal(ScalarValue::Utf8(Some(path)), _) => paths.push(path.as_str()),
+                _ => return plan_err!("sig takes the paths of variables as strings"),
+            }
+        }
+        if paths.is_empty() {
+            return plan_err!("sig takes the path of at least one variable");
+        }
+        let batch = {
+            let mut wave = self
+                .wave
+                .lock()
+                .map_err(|_| DataFusionError::Execution("waveform lock poisoned".into()))?;
+            to_arrow(&mut *wave, &paths, 0..u64::MAX)?
+        };
+        let mut fields = vec![Field::new("time", DataType::UInt64, false)];
+        let mut columns: Vec<ArrayRef> = vec![Arc::new(UInt64Array::from(batch.time))];
+        let single = batch.columns.len() == 1;
+        for column in batch.columns {
+            let (ty, values) = array(column.data);
+            let name = if single { "value" } else { &column.name };
+            fields.push(Field::new(name, ty, true));
+            columns.push(values);
+        }
+        let schema = Arc::new(Schema::new(fields));
+        let batch = RecordBatch::try_new(schema.clone(), columns)?;
+        Ok(Arc::new(MemTable::try_new(schema, vec![vec![batch]])?))
+    }
+}
+
+/// Register the `sig` table function over `wave` in `ctx`.
+pub fn register_waveform<W>(ctx: &SessionContext, wave: Arc<Mutex<W>>)
+where
+    W: Waveform + Send + ?Sized + 'static,
+{
+    ctx.register_udtf("sig", Arc::new(SigFunction::new(wave)));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::VcdFile;
+    use datafusion::arrow::util::pretty::pretty_format_batches;
+
+    const INPUT: &[u8] = b"$timescale 1ns $end
+$scope module top $end
+$var wire 1 ! clk $end
+$var wire 8 \" pc $end
+$var real 64 # temp $end
+$upscope $end
+$enddefinitions $end
+#0 0! b0 \" r20.5 #
+#10 1! b1 \"
+#20 0! b10 \"
+#30 1! bx \" r21 #
+#40 0! b100 \"
+";
+
+    fn query(sql: &str) -> Result<String> {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let ctx = SessionContext::new();
+            let vcd = VcdFile::from_bytes(INPUT.to_vec()).unwrap();
+            register_waveform(&ctx, Arc::new(Mutex::new(vcd)));
+            let batches = ctx.sql(sql).await?.collect().await?;
+            Ok(pretty_format_batches(&batches)?.to_string())
+        })
+    }
+
+    #[test]
+    fn selects_and_joins_signals() {
+        let pc =
+            query("SELECT time, value FROM sig('top.pc') WHERE time BETWEEN 10 AND 30").unwrap();
+        assert_eq!(
+            pc,
+            "+------+-------+
+| time | value |
++------+-------+
+| 10   | 1     |
+| 20   | 2     |
+| 30   |       |
++------+-------+"
+        );
+
+        let joined = query(
+            "SELECT c.time, c.value AS clk, p.value AS pc \
+             FROM sig('top.clk') c JOIN sig('top.pc') p ON c.time = p.time \
+             WHERE c.value ORDER BY c.time",
+        )
+        .unwrap();
+        assert_eq!(
+            joined,
+            "+------+------+----+
+| time | clk  | pc |
++------+------+----+
+| 10   | true | 1  |
+| 30   | true |    |
++------+------+----+"
+        );
+
+        let sampled = query("SELECT * FROM sig('top.pc', 'top.temp') WHERE time >= 20").unwrap();
+        assert_eq!(
+            sampled,
+            "+------+--------+----------+
+| time | top.pc | top.temp |
++------+--------+----------+
+| 20   | 2      | 20.5     |
+| 30   |        | 21.0     |
+| 40   | 4      | 21.0     |
++------+--------+----------+"
+        );
+
+        assert!(query("SELECT * FROM sig('top.nope')").is_err());
+        assert!(query("SELECT * FROM sig(1)").is_err());
+        assert!(query("SELECT * FROM sig()").is_err());
+    }
+}
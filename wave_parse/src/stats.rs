@@ -0,0 +1,206 @@
+//! Per-signal statistics over a time range.
+//!
+//! [`signal_stats`] computes in one pass what power and duty-cycle
+//! scripts would otherwise derive from every change: the change count,
+//! the toggles of each bit, the time spent at each value and, for real
+//! variables, time-weighted aggregates.
+//!
+//! Durations are measured up to the end of the range, so a range reaching
+//! past the end of the simulation weights the last values by the excess.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::ops::Range;
+
+use crate::coverage::count_toggles;
+use crate::{LogicVec, Signal, Var, VarKind, Waveform};
+
+/// Time-weighted aggregates of a real variable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RealStats {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    /// Root mean square.
+    pub rms: f64,
+}
+
+/// Statistics of one signal over a time range.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SignalStats {
+    /// Changes recorded in the range.
+    pub changes: u64,
+    /// Number of `0` to `1` transitions per bit in the range, least
+    /// significant first. Empty for real and string variables.
+    pub rises: Vec<u64>,
+    /// Number of `1` to `0` transitions per bit, like `rises`.
+    pub falls: Vec<u64>,
+    /// Ticks spent at each value, for logic and string variables. Vector
+    /// values are extended to the full width.
+    pub time_in_state: BTreeMap<Vec<u8>, u64>,
+    /// Ticks before the first value.
+    pub unassigned: u64,
+    /// Aggregates of the values of a real variable, if any held for a
+    /// while.
+    pub real: Option<RealStats>,
+}
+
+impl SignalStats {
+    /// Transitions of bit `i` either way.
+    pub fn toggles(&self, i: usize) -> u64 {
+        self.rises[i] + self.falls[i]
+    }
+
+    /// The share of the assigned time a 1-bit signal spent at `1`.
+    pub fn duty_cycle(&self) -> Option<f64> {
+        let high = self.time_in_state.get(&b"1"[..]).copied().unwrap_or(0);
+        let total: u64 = self.time_in_state.values().sum();
+        (self.rises.len() == 1 && total > 0).then(|| high as f64 / total as f64)
+    }
+}
+
+/// Running sums for [`RealStats`].
+#[derive(Default)]
+struct RealSums {
+    min: f64,
+    max: f64,
+    sum: f64,
+    squares: f64,
+    ticks: f64,
+}
+
+/// Statistics of `signal`, the data of `var`, over `range`.
+pub fn signal_stats(signal: &Signal, var: &Var, range: Range<u64>) -> SignalStats {
+    let times = signal.times();
+    let from = times.partition_point(|&t| t < range.start);
+    let to = times.partition_point(|&t| t < range.end).max(from);
+    let mut stats = SignalStats {
+        changes: (to - from) as u64,
+        ..SignalStats::default()
+    };
+    let real = var.kind.is_real();
+    let logic = !real && var.kind != VarKind::String;
+
+    if logic {
+        // The value in effect before the range is not counted as a toggle.
+        let mut clipped = Signal::new();
+        for i in from.saturating_sub(1)..to {
+            clipped.push(signal.time(i), signal.value(i));
+        }
+        (stats.rises, stats.falls) = count_toggles(&clipped, var.width);
+    }
+
+    let mut sums: Option<RealSums> = None;
+    let mut hold = |value: Option<&[u8]>, ticks: u64| {
+        let Some(value) = value else {
+            stats.unassigned += ticks;
+            return;
+        };
+        if ticks == 0 {
+            return;
+        }
+        if !real {
+            let key = if logic {
+                LogicVec::from_vcd(value, var.width as usize)
+                    .map_or_else(|_| value.to_vec(), |v| v.to_bytes())
+            } else {
+                value.to_vec()
+            };
+            *stats.time_in_state.entry(key).or_insert(0) += ticks;
+            return;
+        }
+        let Some(v) = std::str::from_utf8(value)
+            .ok()
+            .and_then(|s| s.parse::<f64>().ok())
+        else {
+            return;
+        };
+        let s = sums.get_or_insert(RealSums {
+            min: v,
+            max: v,
+            ..RealSums::default()
+        });
+        let w = ticks as f64;
+        s.min = s.min.min(v);
+        s.max = s.max.max(v);
+        s.sum += v * w;
+        s.squares += v * v * w;
+        s.ticks += w;
+    };
+    let mut time = range.start;
+    let mut current = from.checked_sub(1).map(|i| signal.value(i));
+    for i in from..to {
+        hold(current, signal.time(i) - time);
+        time = signal.time(i);
+        current = Some(signal.value(i));
+    }
+    hold(current, range.end.saturating_sub(time));
+
+    stats.real = sums.map(|s| RealStats {
+        min: s.min,
+        max: s.max,
+        mean: s.sum / s.ticks,
+        rms: (s.squares / s.ticks).sqrt(),
+    });
+    stats
+}
+
+/// [`signal_stats`] for the variable at `path` in `wave`.
+pub fn var_stats<F>(wave: &mut F, path: &str, range: Range<u64>) -> io::Result<SignalStats>
+where
+    F: Waveform + ?Sized,
+{
+    let var = wave
+        .hierarchy()
+        .lookup(path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no signal {}", path)))?
+        .clone();
+    let signal = wave.load_signals(&[var.signal])?.remove(0);
+    Ok(signal_stats(&signal, &var, range))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::VcdFile;
+
+    #[test]
+    fn stats() {
+        let mut wave = VcdFile::from_bytes(
+            b"$var wire 1 ! clk $end $var wire 2 \" st $end $var real 64 # v $end
+$enddefinitions $end #10 0! b0 \" r1.5 # #20 1! b1 \" #25 0! #30 1! b10 \" r-0.5 # #40 0!"
+                .to_vec(),
+        )
+        .unwrap();
+        let clk = var_stats(&mut wave, "clk", 0..50).unwrap();
+        assert_eq!(clk.changes, 5);
+        assert_eq!((clk.toggles(0), clk.rises[0]), (4, 2));
+        assert_eq!(clk.unassigned, 10);
+        assert_eq!(clk.time_in_state[&b"1"[..]], 15);
+        assert_eq!(clk.duty_cycle(), Some(15.0 / 40.0));
+
+        // The change at 20 is before the range and not a toggle in it.
+        let late = var_stats(&mut wave, "clk", 21..35).unwrap();
+        assert_eq!((late.changes, late.toggles(0)), (2, 2));
+        assert_eq!(late.unassigned, 0);
+
+        let st = var_stats(&mut wave, "st", 0..40).unwrap();
+        assert_eq!(st.rises, [1, 1]);
+        assert_eq!(st.falls, [1, 0]);
+        let states: Vec<(&[u8], u64)> = st
+            .time_in_state
+            .iter()
+            .map(|(k, &v)| (k.as_slice(), v))
+            .collect();
+        assert_eq!(states, [(&b"00"[..], 10), (b"01", 10), (b"10", 10)]);
+        assert_eq!(st.duty_cycle(), None);
+
+        let v = var_stats(&mut wave, "v", 0..50).unwrap();
+        assert!(v.rises.is_empty() && v.time_in_state.is_empty());
+        let real = v.real.unwrap();
+        assert_eq!((real.min, real.max), (-0.5, 1.5));
+        assert_eq!(real.mean, (1.5 * 20.0 - 0.5 * 20.0) / 40.0);
+        assert_eq!(real.rms, 1.25f64.sqrt());
+        assert!(var_stats(&mut wave, "none", 0..1).is_err());
+    }
+}
@@ -0,0 +1,545 @@
+//! On-demand signal loading with least-recently-used eviction.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Weak};
+
+use crate::spill::SpillFile;
+use crate::{PackedSignal, Signal, SignalId};
+
+/// A source that can produce the full change history of signals.
+///
+/// Loading several signals at once should be preferred by implementors
+/// where it saves work, e.g. a single pass over a VCD body.
+pub trait SignalLoader {
+    /// Load the requested signals, returned in the same order as `ids`.
+    fn load_signals(&mut self, ids: &[SignalId]) -> io::Result<Vec<Signal>>;
+}
+
+/// A cached signal, as loaded or packed. A packed signal remembers its
+/// last decompressed copy, which serves requests while a handle to it is
+/// alive.
+enum Cached {
+    Plain(Arc<Signal>),
+    Packed(PackedSignal, Weak<Signal>),
+}
+
+impl Cached {
+    fn new(mut signal: Signal, pack: bool) -> Cached {
+        if pack {
+            return Cached::Packed(PackedSignal::new(&signal), Weak::new());
+        }
+        signal.shrink_to_fit();
+        Cached::Plain(Arc::new(signal))
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Cached::Plain(s) => s.size_bytes(),
+            Cached::Packed(p, _) => p.size_bytes(),
+        }
+    }
+
+    fn signal(&mut self) -> Arc<Signal> {
+        match self {
+            Cached::Plain(s) => s.clone(),
+            Cached::Packed(p, decoded) => decoded.upgrade().unwrap_or_else(|| {
+                let signal = Arc::new(p.to_signal());
+                *decoded = Arc::downgrade(&signal);
+                signal
+            }),
+        }
+    }
+}
+
+struct Entry {
+    signal: Cached,
+    size: usize,
+    last_used: u64,
+}
+
+/// A cache of loaded signals under a memory budget.
+///
+/// Signals are loaded through the wrapped [`SignalLoader`] the first time
+/// they are requested. Whenever the total size of the cached signals
+/// exceeds the budget, the least recently used ones are dropped from the
+/// cache. Handles already given out stay valid, as they are reference
+/// counted; they simply no longer count towards the budget.
+///
+/// The most recently requested signal is never evicted, so a single signal
+/// larger than the budget can still be served.
+///
+/// With [spilling](SignalStore::spill_to) enabled, evicted signals are
+/// written to a temporary file and read back from it when requested
+/// again, instead of being loaded from the source a second time. With
+/// [packing](SignalStore::set_packing), cached signals are kept as
+/// [`PackedSignal`]s and decompressed when requested, which fits more of
+/// them in the budget. Decompressing costs a pass over the changes, so the
+/// last signal requested stays decompressed as well, outside the budget,
+/// and so does any signal a handle is still held to: requesting one signal
+/// over and over decompresses it once, while alternating between packed
+/// signals decompresses each on every request.
+pub struct SignalStore<L: SignalLoader> {
+    loader: L,
+    budget: usize,
+    used: usize,
+    tick: u64,
+    entries: HashMap<SignalId, Entry>,
+    lru: BTreeMap<u64, SignalId>,
+    spill: Option<SpillFile>,
+    pack: bool,
+    /// The last packed signal requested, decompressed.
+    hot: Option<(SignalId, Arc<Signal>)>,
+}
+
+impl<L: SignalLoader> SignalStore<L> {
+    /// Create a store over `loader` holding at most `budget` bytes of signals.
+    pub fn new(loader: L, budget: usize) -> SignalStore<L> {
+        SignalStore {
+            loader,
+            budget,
+            used: 0,
+            tick: 0,
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            spill: None,
+            pack: false,
+            hot: None,
+        }
+    }
+
+    /// Get a signal, loading it if it isn't cached.
+    pub fn get(&mut self, id: SignalId) -> io::Result<Arc<Signal>> {
+        if !self.entries.contains_key(&id) {
+            self.load_missing(&[id])?;
+        }
+        let signal = self.touch(id);
+        self.enforce_budget(id);
+        Ok(signal)
+    }
+
+    /// Make sure all signals in `ids` are loaded, fetching the missing ones
+    /// with a single loader call.
+    ///
+    /// If the signals do not fit in the budget together, the ones listed
+    /// first are evicted first.
+    pub fn prefetch(&mut self, ids: &[SignalId]) -> io::Result<()> {
+        self.load_missing(ids)?;
+        for &id in ids {
+            if self.entries.contains_key(&id) {
+                self.touch(id);
+            }
+        }
+        if let Some(&last) = ids.last() {
+            self.enforce_budget(last);
+        }
+        Ok(())
+    }
+
+    /// Whether a signal is currently cached.
+    pub fn contains(&self, id: SignalId) -> bool {
+        self.entries.contains_key(&id)
+    }
+
+    /// Number of cached signals.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Bytes currently charged against the budget.
+    pub fn used_bytes(&self) -> usize {
+        self.used
+    }
+
+    /// The memory budget in bytes.
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Change the memory budget, evicting signals if needed.
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        while self.used > self.budget && self.evict_oldest(None) {}
+    }
+
+    /// Spill signals evicted to stay under the budget to a new temporary
+    /// file in `dir`, holding at most `max_bytes`. Signals that no longer
+    /// fit are dropped as without spilling.
+    pub fn spill_to(&mut self, dir: &Path, max_bytes: u64) -> io::Result<()> {
+        self.spill = Some(SpillFile::create(dir, max_bytes)?);
+        Ok(())
+    }
+
+    /// Keep cached signals packed, or stop doing so. Signals already
+    /// cached are converted, evicting signals if they no longer fit.
+    pub fn set_packing(&mut self, pack: bool) {
+        self.pack = pack;
+        self.hot = None;
+        for entry in self.entries.values_mut() {
+            let signal = match &entry.signal {
+                Cached::Plain(s) if pack => (**s).clone(),
+                Cached::Packed(p, _) if !pack => p.to_signal(),
+                _ => continue,
+            };
+            entry.signal = Cached::new(signal, pack);
+            self.used = self.used - entry.size + entry.signal.size();
+            entry.size = entry.signal.size();
+        }
+        while self.used > self.budget && self.evict_oldest(None) {}
+    }
+
+    /// Whether cached signals are kept packed.
+    pub fn is_packing(&self) -> bool {
+        self.pack
+    }
+
+    /// Stop spilling and delete the spill file.
+    pub fn disable_spill(&mut self) {
+        self.spill = None;
+    }
+
+    /// Whether a signal can be read back from the spill file.
+    pub fn is_spilled(&self, id: SignalId) -> bool {
+        self.spill.as_ref().is_some_and(|s| s.contains(id))
+    }
+
+    /// Bytes written to the spill file.
+    pub fn spilled_bytes(&self) -> u64 {
+        self.spill.as_ref().map_or(0, |s| s.len())
+    }
+
+    /// Drop a signal from the cache. Returns whether it was cached.
+    pub fn evict(&mut self, id: SignalId) -> bool {
+        if self.hot.as_ref().is_some_and(|(hot, _)| *hot == id) {
+            self.hot = None;
+        }
+        match self.entries.remove(&id) {
+            Some(entry) => {
+                self.lru.remove(&entry.last_used);
+                self.used -= entry.size;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drop all cached signals, including spilled ones.
+    pub fn clear(&mut self) {
+        self.hot = None;
+        self.entries.clear();
+        self.lru.clear();
+        self.used = 0;
+        self.clear_spill();
+    }
+
+    /// Extend every cached signal with the changes `load` returns for
+    /// them from the loader, in the order of the ids it is given, e.g.
+    /// those [appended](crate::VcdFile::load_appended) to a file since. Handles
+    /// already given out keep the signals as they were. Spilled signals
+    /// are dropped, as extending them would mean reading them back.
+    pub fn append<F>(&mut self, load: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut L, &[SignalId]) -> io::Result<Vec<Signal>>,
+    {
+        self.clear_spill();
+        self.hot = None;
+        let ids: Vec<SignalId> = self.entries.keys().copied().collect();
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let tails = load(&mut self.loader, &ids)?;
+        if tails.len() != ids.len() {
+            return Err(io::Error::other(
+                "signal loader returned a wrong number of signals",
+            ));
+        }
+        for (id, tail) in ids.iter().zip(tails) {
+            let entry = self.entries.get_mut(id).expect("signal is cached");
+            match &mut entry.signal {
+                Cached::Plain(s) => {
+                    let signal = Arc::make_mut(s);
+                    signal.append(&tail);
+                    signal.shrink_to_fit();
+                }
+                Cached::Packed(p, decoded) => {
+                    let mut signal = p.to_signal();
+                    signal.append(&tail);
+                    *p = PackedSignal::new(&signal);
+                    *decoded = Weak::new();
+                }
+            }
+            self.used = self.used - entry.size + entry.signal.size();
+            entry.size = entry.signal.size();
+        }
+        while self.used > self.budget && self.evict_oldest(None) {}
+        Ok(())
+    }
+
+    /// Get a reference to the underlying loader.
+    pub fn loader(&self) -> &L {
+        &self.loader
+    }
+
+    /// Get a mutable reference to the underlying loader.
+    pub fn loader_mut(&mut self) -> &mut L {
+        &mut self.loader
+    }
+
+    /// Unwraps this store, returning the underlying loader.
+    pub fn into_inner(self) -> L {
+        self.loader
+    }
+
+    fn clear_spill(&mut self) {
+        if let Some(spill) = self.spill.take() {
+            // Without a new file spilling stops, as if it failed.
+            self.spill = SpillFile::create(spill.dir(), spill.max_len()).ok();
+        }
+    }
+
+    fn load_missing(&mut self, ids: &[SignalId]) -> io::Result<()> {
+        let mut missing: Vec<SignalId> = Vec::new();
+        for &id in ids {
+            if !self.entries.contains_key(&id) && !missing.contains(&id) {
+                missing.push(id);
+            }
+        }
+        if let Some(spill) = &mut self.spill {
+            let mut spilled = Vec::new();
+            let mut unspilled = Vec::with_capacity(missing.len());
+            for id in missing {
+                match spill.read(id)? {
+                    Some(signal) => spilled.push((id, signal)),
+                    None => unspilled.push(id),
+                }
+            }
+            for (id, signal) in spilled {
+                self.insert(id, signal);
+            }
+            missing = unspilled;
+        }
+        if missing.is_empty() {
+            return Ok(());
+        }
+        let signals = self.loader.load_signals(&missing)?;
+        if signals.len() != missing.len() {
+            return Err(io::Error::other(
+                "signal loader returned a wrong number of signals",
+            ));
+        }
+        for (id, signal) in missing.into_iter().zip(signals) {
+            self.insert(id, signal);
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, id: SignalId, signal: Signal) {
+        let signal = Cached::new(signal, self.pack);
+        let size = signal.size();
+        self.tick += 1;
+        self.lru.insert(self.tick, id);
+        self.used += size;
+        self.entries.insert(
+            id,
+            Entry {
+                signal,
+                size,
+                last_used: self.tick,
+            },
+        );
+    }
+
+    fn touch(&mut self, id: SignalId) -> Arc<Signal> {
+        self.tick += 1;
+        let entry = self.entries.get_mut(&id).expect("signal is cached");
+        self.lru.remove(&entry.last_used);
+        entry.last_used = self.tick;
+        self.lru.insert(self.tick, id);
+        let signal = entry.signal.signal();
+        if let Cached::Packed(..) = entry.signal {
+            self.hot = Some((id, signal.clone()));
+        }
+        signal
+    }
+
+    fn enforce_budget(&mut self, keep: SignalId) {
+        while self.used > self.budget && self.evict_oldest(Some(keep)) {}
+    }
+
+    /// Evict the least recently used signal other than `keep`, spilling
+    /// it if enabled.
+    fn evict_oldest(&mut self, keep: Option<SignalId>) -> bool {
+        let victim = self.lru.values().copied().find(|&id| Some(id) != keep);
+        let Some(id) = victim else {
+            return false;
+        };
+        if let Some(spill) = &mut self.spill {
+            // A full disk only costs a reload later.
+            let entry = self.entries.get_mut(&id).expect("signal is cached");
+            if spill.write(id, &entry.signal.signal()).is_err() {
+                self.spill = None;
+            }
+        }
+        self.evict(id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SignalLoader, SignalStore};
+    use crate::{Signal, SignalId};
+    use std::io;
+    use std::sync::Arc;
+
+    /// Produces `id + 1` changes of an 8-bit value per signal and records
+    /// every load request.
+    #[derive(Default)]
+    struct Counting {
+        requests: Vec<Vec<SignalId>>,
+    }
+
+    impl SignalLoader for Counting {
+        fn load_signals(&mut self, ids: &[SignalId]) -> io::Result<Vec<Signal>> {
+            self.requests.push(ids.to_vec());
+            Ok(ids
+                .iter()
+                .map(|id| {
+                    let mut s = Signal::new();
+                    for t in 0..=id.0 {
+                        s.push(t, b"01010101");
+                    }
+                    s
+                })
+                .collect())
+        }
+    }
+
+    #[test]
+    fn loads_once_and_caches() {
+        let mut store = SignalStore::new(Counting::default(), usize::MAX);
+        let a = store.get(SignalId(3)).unwrap();
+        let b = store.get(SignalId(3)).unwrap();
+        assert_eq!(a.len(), 4);
+        assert_eq!(a, b);
+        assert_eq!(store.loader().requests.len(), 1);
+    }
+
+    #[test]
+    fn shrinking_budget_evicts() {
+        let mut store = SignalStore::new(Counting::default(), usize::MAX);
+        store
+            .prefetch(&[SignalId(1), SignalId(2), SignalId(3)])
+            .unwrap();
+        assert_eq!(store.len(), 3);
+        let newest = store.get(SignalId(3)).unwrap().size_bytes();
+        store.set_budget(newest);
+        assert_eq!(store.len(), 1);
+        assert!(store.contains(SignalId(3)));
+        assert_eq!(store.used_bytes(), newest);
+    }
+
+    #[test]
+    fn lru_order_respects_access() {
+        struct Fixed;
+        impl SignalLoader for Fixed {
+            fn load_signals(&mut self, ids: &[SignalId]) -> io::Result<Vec<Signal>> {
+                Ok(ids
+                    .iter()
+                    .map(|_| {
+                        let mut s = Signal::new();
+                        s.push(0, b"1");
+                        s
+                    })
+                    .collect())
+            }
+        }
+        let mut store = SignalStore::new(Fixed, usize::MAX);
+        let size = store.get(SignalId(100)).unwrap().size_bytes();
+        store.clear();
+        store.set_budget(size * 2);
+
+        store.get(SignalId(1)).unwrap();
+        store.get(SignalId(2)).unwrap();
+        store.get(SignalId(1)).unwrap();
+        store.get(SignalId(3)).unwrap();
+        assert!(store.contains(SignalId(1)));
+        assert!(!store.contains(SignalId(2)));
+        assert!(store.contains(SignalId(3)));
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn oversized_signal_is_kept() {
+        let mut store = SignalStore::new(Counting::default(), 1);
+        let s = store.get(SignalId(10)).unwrap();
+        assert_eq!(s.len(), 11);
+        assert!(store.contains(SignalId(10)));
+        store.get(SignalId(11)).unwrap();
+        assert!(!store.contains(SignalId(10)));
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn spills_evicted_signals() {
+        let mut store = SignalStore::new(Counting::default(), 1);
+        store.spill_to(&std::env::temp_dir(), u64::MAX).unwrap();
+        let first = store.get(SignalId(10)).unwrap();
+        store.get(SignalId(20)).unwrap();
+        assert!(!store.contains(SignalId(10)));
+        assert!(store.is_spilled(SignalId(10)));
+        assert!(store.spilled_bytes() < first.size_bytes() as u64);
+
+        assert_eq!(store.get(SignalId(10)).unwrap(), first);
+        assert_eq!(store.loader().requests.len(), 2);
+        store.prefetch(&[SignalId(20), SignalId(30)]).unwrap();
+        assert_eq!(
+            store.loader().requests,
+            vec![vec![SignalId(10)], vec![SignalId(20)], vec![SignalId(30)]]
+        );
+
+        store.clear();
+        assert!(!store.is_spilled(SignalId(10)));
+        store.disable_spill();
+        assert_eq!(store.spilled_bytes(), 0);
+    }
+
+    #[test]
+    fn prefetch_batches_missing_signals() {
+        let mut store = SignalStore::new(Counting::default(), usize::MAX);
+        store.get(SignalId(1)).unwrap();
+        store
+            .prefetch(&[SignalId(1), SignalId(2), SignalId(3), SignalId(2)])
+            .unwrap();
+        assert_eq!(
+            store.loader().requests,
+            vec![vec![SignalId(1)], vec![SignalId(2), SignalId(3)]]
+        );
+    }
+
+    #[test]
+    fn packing_fits_more_signals() {
+        let mut store = SignalStore::new(Counting::default(), usize::MAX);
+        let plain = store.get(SignalId(99)).unwrap();
+        let used = store.used_bytes();
+        store.set_packing(true);
+        assert!(store.is_packing());
+        assert!(store.used_bytes() * 4 < used);
+        assert_eq!(store.get(SignalId(99)).unwrap(), plain);
+        // The last signal requested is not decompressed again.
+        let first = Arc::as_ptr(&store.get(SignalId(99)).unwrap());
+        assert_eq!(Arc::as_ptr(&store.get(SignalId(99)).unwrap()), first);
+
+        store.set_budget(used);
+        store.prefetch(&[SignalId(98), SignalId(97)]).unwrap();
+        assert_eq!(store.len(), 3);
+        store.set_packing(false);
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.loader().requests.len(), 2);
+    }
+}
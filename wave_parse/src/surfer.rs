@@ -0,0 +1,469 @@
+//! Surfer saved states.
+//!
+//! Surfer saves a session as a RON document holding the loaded file and
+//! the displayed items. [`parse_state`] reads the variables and dividers
+//! shown, in display order, with their names, colors and formats, and
+//! [`SurferState::resolve`] matches them against a hierarchy the same way
+//! as GTKWave [save files](crate::gtkw).
+//!
+//! Surfer only loads states holding every field of its own version, so
+//! selections go back to it as a command file instead, run with
+//! `surfer --command-file`; see [`SurferState::write_commands`].
+
+use std::io::{self, Write};
+
+use crate::gtkw::{self, Resolution};
+use crate::{Hierarchy, InvalidData};
+
+/// Nesting of RON values accepted, well above what Surfer writes.
+const MAX_DEPTH: usize = 128;
+
+/// A RON value.
+#[derive(Debug, Clone, PartialEq)]
+enum Ron {
+    Str(String),
+    /// Numbers, booleans and unit variants such as `None`.
+    Atom(String),
+    /// `Name(a, b)` or `(a, b)`.
+    Tuple(Option<String>, Vec<Ron>),
+    /// `Name(field: a)` or `(field: a)`.
+    Struct(Option<String>, Vec<(String, Ron)>),
+    List(Vec<Ron>),
+    Map(Vec<(Ron, Ron)>),
+}
+
+impl Ron {
+    fn field(&self, name: &str) -> Option<&Ron> {
+        match self.inner() {
+            Ron::Struct(_, fields) => fields.iter().find(|(k, _)| k == name).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// The value inside `Some(..)`, newtypes such as `(1)` and other
+    /// single-element tuples.
+    fn inner(&self) -> &Ron {
+        match self {
+            Ron::Tuple(_, items) if items.len() == 1 => items[0].inner(),
+            _ => self,
+        }
+    }
+
+    fn str(&self) -> Option<&str> {
+        match self.inner() {
+            Ron::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+fn is_word(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'_' | b'.' | b'+' | b'-')
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn skip_ws(&mut self) {
+        loop {
+            let rest = &self.data[self.pos..];
+            if rest.first().is_some_and(u8::is_ascii_whitespace) {
+                self.pos += 1;
+            } else if rest.starts_with(b"//") {
+                self.pos += rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+            } else if rest.starts_with(b"/*") {
+                self.pos += rest
+                    .windows(2)
+                    .position(|w| w == b"*/")
+                    .map_or(rest.len(), |end| end + 2);
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn expect(&mut self, b: u8, err: &'static str) -> Result<(), InvalidData> {
+        self.skip_ws();
+        if self.peek() != Some(b) {
+            return Err(InvalidData(err));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn word(&mut self) -> &str {
+        let start = self.pos;
+        while self.peek().is_some_and(is_word) {
+            self.pos += 1;
+        }
+        // Word characters are ASCII.
+        std::str::from_utf8(&self.data[start..self.pos]).expect("ascii word")
+    }
+
+    fn string(&mut self) -> Result<String, InvalidData> {
+        const BAD: InvalidData = InvalidData("invalid string in Surfer state");
+        let mut out = Vec::new();
+        self.pos += 1;
+        loop {
+            let b = self.peek().ok_or(BAD)?;
+            self.pos += 1;
+            match b {
+                b'"' => break,
+                b'\\' => {
+                    let e = self.peek().ok_or(BAD)?;
+                    self.pos += 1;
+                    match e {
+                        b'n' => out.push(b'\n'),
+                        b't' => out.push(b'\t'),
+                        b'r' => out.push(b'\r'),
+                        b'0' => out.push(0),
+                        b'u' => {
+                            let braced = self.peek() == Some(b'{');
+                            let start = self.pos + braced as usize;
+                            let len = if braced {
+                                let rest = &self.data[start..];
+                                rest.iter().position(|&b| b == b'}').ok_or(BAD)?
+                            } else {
+                                4
+                            };
+                            let end = start + len;
+                            let hex = self.data.get(start..end).ok_or(BAD)?;
+                            let c = std::str::from_utf8(hex)
+                                .ok()
+                                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                                .and_then(char::from_u32)
+                                .ok_or(BAD)?;
+                            self.pos = end + braced as usize;
+                            out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                        }
+                        _ => out.push(e),
+                    }
+                }
+                _ => out.push(b),
+            }
+        }
+        String::from_utf8(out).map_err(|_| BAD)
+    }
+
+    /// The values up to `close`, separated by commas.
+    fn items<T>(
+        &mut self,
+        close: u8,
+        depth: usize,
+        mut item: impl FnMut(&mut Self, usize) -> Result<T, InvalidData>,
+    ) -> Result<Vec<T>, InvalidData> {
+        let mut out = Vec::new();
+        self.pos += 1;
+        loop {
+            self.skip_ws();
+            if self.peek() == Some(close) {
+                self.pos += 1;
+                return Ok(out);
+            }
+            out.push(item(self, depth + 1)?);
+            self.skip_ws();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b) if b == close => {}
+                _ => return Err(InvalidData("expected , in Surfer state")),
+            }
+        }
+    }
+
+    /// A tuple or struct, at its opening parenthesis.
+    fn group(&mut self, name: Option<String>, depth: usize) -> Result<Ron, InvalidData> {
+        let start = self.pos;
+        self.pos += 1;
+        self.skip_ws();
+        let is_struct = self
+            .peek()
+            .is_some_and(|b| b.is_ascii_alphabetic() || b == b'_')
+            && {
+                self.word();
+                self.skip_ws();
+                self.peek() == Some(b':')
+            };
+        self.pos = start;
+        if is_struct {
+            let fields = self.items(b')', depth, |p, depth| {
+                p.skip_ws();
+                let key = p.word().to_string();
+                p.expect(b':', "expected : in Surfer state")?;
+                Ok((key, p.value(depth)?))
+            })?;
+            Ok(Ron::Struct(name, fields))
+        } else {
+            Ok(Ron::Tuple(name, self.items(b')', depth, Parser::value)?))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Ron, InvalidData> {
+        if depth > MAX_DEPTH {
+            return Err(InvalidData("Surfer state nested too deeply"));
+        }
+        self.skip_ws();
+        match self.peek() {
+            Some(b'"') => Ok(Ron::Str(self.string()?)),
+            Some(b'(') => self.group(None, depth),
+            Some(b'[') => Ok(Ron::List(self.items(b']', depth, Parser::value)?)),
+            Some(b'{') => Ok(Ron::Map(self.items(b'}', depth, |p, depth| {
+                let key = p.value(depth)?;
+                p.expect(b':', "expected : in Surfer state")?;
+                Ok((key, p.value(depth)?))
+            })?)),
+            Some(b) if is_word(b) => {
+                let word = self.word().to_string();
+                self.skip_ws();
+                if self.peek() == Some(b'(') && word.as_bytes()[0].is_ascii_alphabetic() {
+                    self.group(Some(word), depth)
+                } else {
+                    Ok(Ron::Atom(word))
+                }
+            }
+            _ => Err(InvalidData("invalid value in Surfer state")),
+        }
+    }
+}
+
+fn parse_ron(text: &str) -> Result<Ron, InvalidData> {
+    let mut parser = Parser {
+        data: text.as_bytes(),
+        pos: 0,
+    };
+    let value = parser.value(0)?;
+    parser.skip_ws();
+    if parser.pos != parser.data.len() {
+        return Err(InvalidData("trailing data in Surfer state"));
+    }
+    Ok(value)
+}
+
+/// What a displayed item shows.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ItemKind {
+    /// A variable, by its dot-separated path.
+    Variable(String),
+    Divider,
+    /// Markers, timelines and other items, by Surfer's name for their kind.
+    Other(String),
+}
+
+/// One displayed item.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub kind: ItemKind,
+    /// The name given in Surfer, or the divider text.
+    pub name: Option<String>,
+    /// Surfer's color name, e.g. `Blue`.
+    pub color: Option<String>,
+    /// Surfer's translator name, e.g. `Hexadecimal`.
+    pub format: Option<String>,
+}
+
+/// The parts of a Surfer state about the displayed signals.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SurferState {
+    /// The waveform file or URL the state was saved with.
+    pub dumpfile: Option<String>,
+    pub items: Vec<Item>,
+}
+
+fn item(value: &Ron) -> Option<Item> {
+    let Ron::Tuple(Some(kind), fields) = value else {
+        return None;
+    };
+    let fields = fields.first()?;
+    let text = |key: &str| fields.field(key).and_then(Ron::str).map(str::to_string);
+    let kind = match kind.as_str() {
+        "Variable" => {
+            let var = fields.field("variable_ref")?;
+            let mut path: Vec<&str> = match var.field("path")?.field("strs")? {
+                Ron::List(strs) => strs.iter().map(Ron::str).collect::<Option<_>>()?,
+                _ => return None,
+            };
+            path.push(var.field("name")?.str()?);
+            ItemKind::Variable(path.join("."))
+        }
+        "Divider" => ItemKind::Divider,
+        other => ItemKind::Other(other.to_string()),
+    };
+    let name = match kind {
+        ItemKind::Variable(_) => text("manual_name"),
+        _ => text("name"),
+    };
+    Some(Item {
+        kind,
+        name,
+        color: text("color"),
+        format: text("format"),
+    })
+}
+
+/// Parse the text of a Surfer state file.
+///
+/// Items are in the order Surfer shows them. Items Surfer wrote in a
+/// layout this parser does not know are skipped.
+pub fn parse_state(text: &str) -> Result<SurferState, InvalidData> {
+    let root = parse_ron(text)?;
+    let mut state = SurferState::default();
+    let Some(waves) = root.field("waves") else {
+        return Ok(state);
+    };
+    state.dumpfile = waves.field("source").and_then(Ron::str).map(str::to_string);
+    let items: Vec<&Ron> = match waves.field("displayed_items") {
+        Some(Ron::List(items)) => items.iter().collect(),
+        Some(Ron::Map(items)) => {
+            let order: Vec<&Ron> = match (
+                waves.field("displayed_items_order"),
+                waves.field("items_tree").and_then(|t| t.field("items")),
+            ) {
+                (Some(Ron::List(keys)), _) => keys.iter().collect(),
+                (_, Some(Ron::List(nodes))) => {
+                    nodes.iter().filter_map(|n| n.field("item_ref")).collect()
+                }
+                _ => items.iter().map(|(k, _)| k).collect(),
+            };
+            order
+                .into_iter()
+                .filter_map(|key| {
+                    let found = items.iter().find(|(k, _)| k.inner() == key.inner());
+                    found.map(|(_, v)| v)
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    };
+    state.items = items.into_iter().filter_map(item).collect();
+    Ok(state)
+}
+
+impl SurferState {
+    /// Match the variables against `h`; indices are into
+    /// [`SurferState::items`]. Other items are neither resolved nor
+    /// unresolved.
+    pub fn resolve(&self, h: &Hierarchy) -> Resolution {
+        gtkw::resolve(
+            h,
+            self.items.iter().map(|item| match &item.kind {
+                ItemKind::Variable(path) => std::slice::from_ref(path),
+                _ => &[],
+            }),
+        )
+    }
+
+    /// Write a Surfer command file loading the dump file and adding the
+    /// variables and dividers, in order. Names, colors and formats are not
+    /// written, and other items are skipped.
+    pub fn write_commands<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        if let Some(file) = &self.dumpfile {
+            writeln!(out, "load_file {}", file)?;
+        }
+        for item in &self.items {
+            match (&item.kind, &item.name) {
+                (ItemKind::Variable(path), _) => writeln!(out, "variable_add {}", path)?,
+                (ItemKind::Divider, Some(name)) => writeln!(out, "divider_add {}", name)?,
+                (ItemKind::Divider, None) => writeln!(out, "divider_add")?,
+                (ItemKind::Other(_), _) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{VcdFile, Waveform};
+
+    const STATE: &str = r#"(
+    file_format_version: Some("0.3.0"),
+    show_hierarchy: None,
+    waves: Some((
+        source: File("/work/sim/dump.vcd"),
+        format: Vcd,
+        // Keys are not in display order.
+        displayed_items: {
+            (3): Variable((
+                variable_ref: (path: (strs: ["top", "cpu"], id: None), name: "pc", id: None),
+                color: Some("Blue"),
+                display_name: "pc",
+                manual_name: Some("program \"counter\""),
+                format: Some("Hexadecimal"),
+                field_formats: [],
+            )),
+            (1): Variable((
+                variable_ref: (path: (strs: ["top"], id: None), name: "clk", id: None),
+                color: None,
+                manual_name: None,
+                format: None,
+            )),
+            (2): Divider((color: None, background_color: None, name: Some("Bus"))),
+            (4): Variable((
+                variable_ref: (path: (strs: ["top"]), name: "gone"),
+            )),
+            (5): TimeLine((color: None, name: None)),
+        },
+        displayed_items_order: [(1), (2), (3), (4), (5)],
+        viewports: [(curr_left: (0.0), curr_right: (1.0))],
+        /* block comment */
+        cursor: Some((Positive, [100])),
+    )),
+)"#;
+
+    #[test]
+    fn parse_resolve_and_write() {
+        let state = parse_state(STATE).unwrap();
+        assert_eq!(state.dumpfile.as_deref(), Some("/work/sim/dump.vcd"));
+        let kinds: Vec<&ItemKind> = state.items.iter().map(|i| &i.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                &ItemKind::Variable("top.clk".to_string()),
+                &ItemKind::Divider,
+                &ItemKind::Variable("top.cpu.pc".to_string()),
+                &ItemKind::Variable("top.gone".to_string()),
+                &ItemKind::Other("TimeLine".to_string()),
+            ]
+        );
+        let pc = &state.items[2];
+        assert_eq!(pc.name.as_deref(), Some("program \"counter\""));
+        assert_eq!(pc.color.as_deref(), Some("Blue"));
+        assert_eq!(pc.format.as_deref(), Some("Hexadecimal"));
+        assert_eq!(state.items[1].name.as_deref(), Some("Bus"));
+
+        let vcd = VcdFile::from_bytes(
+            b"$scope module top $end $var wire 1 ! clk $end
+$scope module cpu $end $var wire 32 \" pc [31:0] $end
+$upscope $end $upscope $end $enddefinitions $end"
+                .to_vec(),
+        )
+        .unwrap();
+        let r = state.resolve(vcd.hierarchy());
+        let found: Vec<usize> = r.resolved.iter().map(|t| t.trace).collect();
+        assert_eq!(found, [0, 2]);
+        assert_eq!(r.resolved[1].vars[0].0, "top.cpu.pc");
+        assert_eq!(r.unresolved, [3]);
+
+        let mut out = Vec::new();
+        state.write_commands(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "load_file /work/sim/dump.vcd\nvariable_add top.clk\ndivider_add Bus\n\
+             variable_add top.cpu.pc\nvariable_add top.gone\n"
+        );
+
+        assert!(parse_state("(waves: Some((displayed_items: {").is_err());
+        assert!(parse_state("(a: 1) extra").is_err());
+        assert_eq!(
+            parse_state("(waves: None)").unwrap(),
+            SurferState::default()
+        );
+    }
+}
@@ -0,0 +1,279 @@
+//! Non-blocking waveform access for async code.
+//!
+//! Opening a dump and loading signals are long, blocking operations. The
+//! functions here run them on a background thread and return a [`Task`],
+//! a [`Future`] completed by that thread, so an async runtime (tokio,
+//! async-std, a browser event loop) keeps serving other requests
+//! meanwhile. Tasks do not depend on any particular runtime; under tokio
+//! a task is awaited like any future.
+//!
+//! The threads come from a pool of the crate's own rather than from a
+//! runtime's blocking pool, which only the `object_store` feature brings
+//! in. The pool starts a thread whenever a task would otherwise wait, up
+//! to [`default_threads`] of them, and keeps them for later tasks; further
+//! tasks queue. A task should therefore not block on another task.
+//!
+//! [`AsyncWaveform`] wraps an open waveform behind a lock shared by its
+//! tasks. The hierarchy and timescale are copied when it is created, so
+//! they can be read without waiting.
+//!
+//! On `wasm32-unknown-unknown`, which cannot spawn threads, the work runs
+//! when the task is created and the task is ready on its first poll.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use crate::parallel::{default_threads, THREADS};
+use crate::{convert, Hierarchy, Signal, SignalId, Timescale, Waveform};
+
+/// A blocking operation running on a background thread, resolving to its
+/// result. A panic in the operation resolves to an error.
+pub struct Task<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+struct Shared<T> {
+    result: Option<io::Result<T>>,
+    waker: Option<Waker>,
+}
+
+/// Run `f` on the pool and return a task resolving to its result.
+pub fn spawn<T, F>(f: F) -> Task<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+{
+    let run = move || {
+        panic::catch_unwind(AssertUnwindSafe(f))
+            .unwrap_or_else(|_| Err(io::Error::other("background task panicked")))
+    };
+    if !THREADS {
+        return Task {
+            shared: Arc::new(Mutex::new(Shared {
+                result: Some(run()),
+                waker: None,
+            })),
+        };
+    }
+    let shared = Arc::new(Mutex::new(Shared {
+        result: None,
+        waker: None,
+    }));
+    let done = shared.clone();
+    pool().run(Box::new(move || {
+        let result = run();
+        let mut shared = done.lock().unwrap();
+        shared.result = Some(result);
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }));
+    Task { shared }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The threads running tasks.
+struct Pool {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+}
+
+struct Queue {
+    jobs: VecDeque<Job>,
+    threads: usize,
+    /// The threads waiting for a job.
+    idle: usize,
+}
+
+fn pool() -> &'static Pool {
+    static POOL: OnceLock<Pool> = OnceLock::new();
+    POOL.get_or_init(|| Pool {
+        queue: Mutex::new(Queue {
+            jobs: VecDeque::new(),
+            threads: 0,
+            idle: 0,
+        }),
+        ready: Condvar::new(),
+    })
+}
+
+impl Pool {
+    fn run(&'static self, job: Job) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.jobs.push_back(job);
+        if queue.jobs.len() > queue.idle && queue.threads < default_threads() {
+            queue.threads += 1;
+            thread::spawn(move || self.work());
+        }
+        self.ready.notify_one();
+    }
+
+    fn work(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        loop {
+            match queue.jobs.pop_front() {
+                Some(job) => {
+                    drop(queue);
+                    job();
+                    queue = self.queue.lock().unwrap();
+                }
+                None => {
+                    queue.idle += 1;
+                    queue = self.ready.wait(queue).unwrap();
+                    queue.idle -= 1;
+                }
+            }
+        }
+    }
+}
+
+impl<T> Future for Task<T> {
+    type Output = io::Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<T>> {
+        let mut shared = self.shared.lock().unwrap();
+        match shared.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// A waveform whose signals load on background threads.
+#[derive(Clone)]
+pub struct AsyncWaveform {
+    wave: Arc<Mutex<Box<dyn Waveform + Send>>>,
+    hierarchy: Arc<Hierarchy>,
+    timescale: Option<Timescale>,
+}
+
+/// Open a waveform file like [`convert::open`], without blocking.
+pub fn open(path: impl Into<PathBuf>) -> Task<AsyncWaveform> {
+    let path = path.into();
+    spawn(move || Ok(AsyncWaveform::new(convert::open(&path)?)))
+}
+
+impl AsyncWaveform {
+    /// Wrap an open waveform, copying its hierarchy and timescale.
+    pub fn new(wave: Box<dyn Waveform + Send>) -> AsyncWaveform {
+        AsyncWaveform {
+            hierarchy: Arc::new(wave.hierarchy().clone()),
+            timescale: wave.timescale(),
+            wave: Arc::new(Mutex::new(wave)),
+        }
+    }
+
+    /// The scopes and variables of the waveform, read without waiting.
+    pub fn hierarchy(&self) -> &Hierarchy {
+        &self.hierarchy
+    }
+
+    /// The duration of one time tick, if the waveform declares it.
+    pub fn timescale(&self) -> Option<Timescale> {
+        self.timescale
+    }
+
+    /// Load the signals `ids`, in order.
+    pub fn load_signals(&self, ids: Vec<SignalId>) -> Task<Vec<Signal>> {
+        self.with(move |wave| wave.load_signals(&ids))
+    }
+
+    /// Run a query needing the waveform itself, such as a search or an
+    /// export. Tasks on the same waveform run one after the other.
+    pub fn with<T, F>(&self, f: F) -> Task<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut dyn Waveform) -> io::Result<T> + Send + 'static,
+    {
+        let wave = self.wave.clone();
+        spawn(move || {
+            let mut wave = wave
+                .lock()
+                .map_err(|_| io::Error::other("waveform poisoned by a panicked task"))?;
+            f(&mut **wave)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::VcdFile;
+    use std::collections::HashSet;
+    use std::task::Wake;
+
+    struct Unpark(thread::Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// A minimal executor: poll, park until woken, repeat.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+
+    #[test]
+    fn load_in_background() {
+        let path = std::env::temp_dir().join(format!("wave_parse_task_{}.vcd", std::process::id()));
+        std::fs::write(
+            &path,
+            b"$timescale 1ns $end $scope module top $end $var wire 1 ! clk $end
+$upscope $end $enddefinitions $end
+#0 0!
+#5 1!",
+        )
+        .unwrap();
+        let wave = block_on(open(&path)).unwrap();
+        let mut vcd = VcdFile::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(wave.hierarchy(), vcd.hierarchy());
+        assert_eq!(wave.timescale(), vcd.timescale());
+        let ids = wave.hierarchy().signal_ids();
+        let expected = crate::SignalLoader::load_signals(&mut vcd, &ids).unwrap();
+        assert_eq!(block_on(wave.load_signals(ids)).unwrap(), expected);
+        let count = block_on(wave.with(|w| Ok(w.hierarchy().signal_ids().len()))).unwrap();
+        assert_eq!(count, 1);
+
+        assert!(block_on(open("/nonexistent.vcd")).is_err());
+        let failed = block_on(wave.with(|_| -> io::Result<()> { panic!("query failed") }));
+        assert!(failed.is_err());
+    }
+
+    #[test]
+    fn tasks_share_a_bounded_pool() {
+        let tasks: Vec<_> = (0..4 * default_threads() + 1)
+            .map(|_| {
+                spawn(|| {
+                    thread::sleep(std::time::Duration::from_millis(1));
+                    Ok(thread::current().id())
+                })
+            })
+            .collect();
+        let ids: HashSet<_> = tasks.into_iter().map(|t| block_on(t).unwrap()).collect();
+        assert!(ids.len() <= default_threads());
+        assert!(ids.iter().all(|&id| id != thread::current().id()));
+    }
+}
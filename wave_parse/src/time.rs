@@ -0,0 +1,360 @@
+//! Timescales and times carrying their timescale.
+//!
+//! Changes are stored as raw `u64` ticks of the waveform's timescale. A
+//! [`Time`] pairs ticks with their timescale, so times of different files
+//! compare and add correctly, and converts to seconds or clock cycles.
+//! [`Rescaled`] normalizes a whole waveform to another timescale, e.g. the
+//! [common](Timescale::common) timescale of several files.
+
+use std::cmp::Ordering;
+use std::fmt::{self, Display};
+use std::io;
+use std::str::FromStr;
+
+use crate::{Blackouts, Hierarchy, InvalidData, Signal, SignalId, SignalLoader, Waveform};
+
+/// A unit of time for the `$timescale` command.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TimeUnit {
+    S,
+    MS,
+    US,
+    NS,
+    PS,
+    FS,
+}
+
+impl TimeUnit {
+    /// Number of this unit in one second.
+    pub fn divisor(&self) -> u64 {
+        use TimeUnit::*;
+        match *self {
+            S => 1,
+            MS => 1_000,
+            US => 1_000_000,
+            NS => 1_000_000_000,
+            PS => 1_000_000_000_000,
+            FS => 1_000_000_000_000_000,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        use TimeUnit::*;
+        match *self {
+            S => "s",
+            MS => "ms",
+            US => "us",
+            NS => "ns",
+            PS => "ps",
+            FS => "fs",
+        }
+    }
+}
+
+impl FromStr for TimeUnit {
+    type Err = InvalidData;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use TimeUnit::*;
+        match s {
+            "s" => Ok(S),
+            "ms" => Ok(MS),
+            "us" => Ok(US),
+            "ns" => Ok(NS),
+            "ps" => Ok(PS),
+            "fs" => Ok(FS),
+            _ => Err(InvalidData("invalid timescale unit")),
+        }
+    }
+}
+
+impl Display for TimeUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The duration of one time tick, e.g. `10 ns`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Timescale {
+    pub factor: u32,
+    pub unit: TimeUnit,
+}
+
+impl Timescale {
+    pub fn new(factor: u32, unit: TimeUnit) -> Timescale {
+        Timescale { factor, unit }
+    }
+
+    /// The duration of one tick in seconds.
+    pub fn seconds(&self) -> f64 {
+        self.factor as f64 / self.unit.divisor() as f64
+    }
+
+    /// The duration of one tick in femtoseconds, exactly.
+    pub fn femtoseconds(&self) -> u128 {
+        self.factor as u128 * (TimeUnit::FS.divisor() / self.unit.divisor()) as u128
+    }
+
+    /// The coarsest timescale in which ticks of both `a` and `b` are whole
+    /// numbers, e.g. `1 ns` for `10 ns` and `1 us`, or none if its factor
+    /// does not fit.
+    pub fn common(a: Timescale, b: Timescale) -> Option<Timescale> {
+        let (mut x, mut y) = (a.femtoseconds(), b.femtoseconds());
+        while y != 0 {
+            (x, y) = (y, x % y);
+        }
+        use TimeUnit::*;
+        [S, MS, US, NS, PS, FS].into_iter().find_map(|unit| {
+            let unit_fs = (FS.divisor() / unit.divisor()) as u128;
+            if x % unit_fs != 0 {
+                return None;
+            }
+            let factor = u32::try_from(x / unit_fs).ok()?;
+            Some(Timescale::new(factor, unit))
+        })
+    }
+
+    /// Convert `ticks` of this timescale to ticks of `to`, rounding to the
+    /// nearest tick. Returns none if the result does not fit.
+    pub fn convert(&self, ticks: u64, to: Timescale) -> Option<u64> {
+        if *self == to {
+            return Some(ticks);
+        }
+        let (from, to) = (self.femtoseconds(), to.femtoseconds());
+        let fs = (ticks as u128).checked_mul(from)?;
+        u64::try_from((fs + to / 2) / to).ok()
+    }
+}
+
+impl FromStr for Timescale {
+    type Err = InvalidData;
+    /// Parses both `1ps` and `1 ps`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let idx = s
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or(InvalidData("timescale without unit"))?;
+        let factor = s[..idx]
+            .parse()
+            .map_err(|_| InvalidData("invalid timescale factor"))?;
+        let unit = s[idx..].trim().parse()?;
+        Ok(Timescale { factor, unit })
+    }
+}
+
+impl Display for Timescale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.factor, self.unit)
+    }
+}
+
+/// A point in time or a duration: ticks of a timescale.
+///
+/// Times compare by the duration they stand for, whatever their
+/// timescales: `1000 ps` equals `1 ns`.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Time {
+    pub ticks: u64,
+    pub timescale: Timescale,
+}
+
+impl Time {
+    pub fn new(ticks: u64, timescale: Timescale) -> Time {
+        Time { ticks, timescale }
+    }
+
+    /// The time in seconds.
+    pub fn seconds(&self) -> f64 {
+        self.ticks as f64 * self.timescale.seconds()
+    }
+
+    /// The time in `unit`, e.g. nanoseconds with [`TimeUnit::NS`].
+    pub fn in_unit(&self, unit: TimeUnit) -> f64 {
+        self.seconds() * unit.divisor() as f64
+    }
+
+    /// The number of clock cycles of `period` this time spans.
+    pub fn cycles(&self, period: Time) -> f64 {
+        self.seconds() / period.seconds()
+    }
+
+    /// The same time in ticks of `timescale`, rounded to the nearest tick.
+    pub fn to_timescale(&self, timescale: Timescale) -> Option<Time> {
+        let ticks = self.timescale.convert(self.ticks, timescale)?;
+        Some(Time { ticks, timescale })
+    }
+
+    /// The sum of two times, in their [common](Timescale::common)
+    /// timescale so nothing is rounded.
+    pub fn checked_add(self, other: Time) -> Option<Time> {
+        let (a, b, timescale) = self.common(other)?;
+        Some(Time::new(a.checked_add(b)?, timescale))
+    }
+
+    /// The difference of two times, none if `other` is later.
+    pub fn checked_sub(self, other: Time) -> Option<Time> {
+        let (a, b, timescale) = self.common(other)?;
+        Some(Time::new(a.checked_sub(b)?, timescale))
+    }
+
+    /// Both times in exact ticks of their common timescale.
+    fn common(self, other: Time) -> Option<(u64, u64, Timescale)> {
+        let timescale = Timescale::common(self.timescale, other.timescale)?;
+        let a = self.timescale.convert(self.ticks, timescale)?;
+        let b = other.timescale.convert(other.ticks, timescale)?;
+        Some((a, b, timescale))
+    }
+}
+
+impl PartialEq for Time {
+    fn eq(&self, other: &Time) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Time {
+    fn partial_cmp(&self, other: &Time) -> Option<Ordering> {
+        let a = (self.ticks as u128).checked_mul(self.timescale.femtoseconds());
+        let b = (other.ticks as u128).checked_mul(other.timescale.femtoseconds());
+        match (a, b) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            _ => self.seconds().partial_cmp(&other.seconds()),
+        }
+    }
+}
+
+impl Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let n = self.ticks as u128 * self.timescale.factor as u128;
+        write!(f, "{} {}", n, self.timescale.unit)
+    }
+}
+
+/// A waveform with its change times converted to another timescale.
+///
+/// Times are rounded to the nearest tick, so converting to a coarser
+/// timescale may put changes at the same time. A waveform without a
+/// timescale is passed through unchanged.
+#[derive(Debug)]
+pub struct Rescaled<W> {
+    inner: W,
+    timescale: Timescale,
+}
+
+impl<W: Waveform> Rescaled<W> {
+    pub fn new(inner: W, timescale: Timescale) -> Rescaled<W> {
+        Rescaled { inner, timescale }
+    }
+
+    pub fn inner(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Waveform> SignalLoader for Rescaled<W> {
+    fn load_signals(&mut self, ids: &[SignalId]) -> io::Result<Vec<Signal>> {
+        let signals = self.inner.load_signals(ids)?;
+        let from = match self.inner.timescale() {
+            Some(from) if from != self.timescale => from,
+            _ => return Ok(signals),
+        };
+        signals
+            .iter()
+            .map(|signal| {
+                let mut out = Signal::new();
+                for (t, v) in signal.iter() {
+                    let t = from
+                        .convert(t, self.timescale)
+                        .ok_or(InvalidData("time does not fit the timescale"))?;
+                    out.push(t, v);
+                }
+                Ok(out)
+            })
+            .collect()
+    }
+}
+
+impl<W: Waveform> Waveform for Rescaled<W> {
+    fn hierarchy(&self) -> &Hierarchy {
+        self.inner.hierarchy()
+    }
+
+    fn timescale(&self) -> Option<Timescale> {
+        self.inner.timescale().map(|_| self.timescale)
+    }
+
+    fn date(&self) -> Option<&str> {
+        self.inner.date()
+    }
+
+    fn blackouts(&self) -> io::Result<Blackouts> {
+        let blackouts = self.inner.blackouts()?;
+        Ok(match self.inner.timescale() {
+            Some(from) if from != self.timescale => {
+                blackouts.map_times(|t| from.convert(t, self.timescale).unwrap_or(u64::MAX))
+            }
+            _ => blackouts,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::VcdFile;
+    use TimeUnit::*;
+
+    #[test]
+    fn conversions() {
+        let ns10 = Timescale::new(10, NS);
+        let us = Timescale::new(1, US);
+        assert_eq!(Timescale::common(ns10, us), Some(Timescale::new(10, NS)));
+        let ps = Timescale::new(1, PS);
+        assert_eq!(
+            Timescale::common(Timescale::new(3, NS), Timescale::new(2, NS)),
+            Some(Timescale::new(1, NS))
+        );
+        assert_eq!(Timescale::common(ns10, ps), Some(ps));
+        assert_eq!(ns10.convert(15, us), Some(0));
+        assert_eq!(ns10.convert(50, us), Some(1));
+        assert_eq!(us.convert(2, ns10), Some(200));
+        assert_eq!(Timescale::new(1, S).convert(u64::MAX, ps), None);
+
+        let t = Time::new(25, ns10);
+        assert_eq!(t, Time::new(250_000, ps));
+        assert!(t < Time::new(1, us));
+        assert_eq!(t.in_unit(NS), 250.0);
+        assert_eq!(t.cycles(Time::new(5, ns10)), 5.0);
+        assert_eq!(t.to_timescale(us), Some(Time::new(0, us)));
+        let sum = t.checked_add(Time::new(1, us)).unwrap();
+        assert_eq!((sum.ticks, sum.timescale), (125, ns10));
+        assert_eq!(
+            Time::new(1, us).checked_sub(t).unwrap(),
+            Time::new(750, Timescale::new(1, NS))
+        );
+        assert_eq!(t.checked_sub(Time::new(1, us)), None);
+        assert_eq!(t.to_string(), "250 ns");
+    }
+
+    #[test]
+    fn rescaled_waveform() {
+        let vcd = VcdFile::from_bytes(
+            b"$timescale 10ns $end $var wire 1 ! a $end $enddefinitions $end
+#0 0! #3 1! #7 0!"
+                .to_vec(),
+        )
+        .unwrap();
+        let id = vcd.hierarchy().signal_ids()[0];
+        let mut wave = Rescaled::new(vcd, Timescale::new(1, NS));
+        assert_eq!(wave.timescale(), Some(Timescale::new(1, NS)));
+        let signal = &wave.load_signals(&[id]).unwrap()[0];
+        assert_eq!(signal.times(), [0, 30, 70]);
+    }
+}
@@ -0,0 +1,411 @@
+//! Typed logic values.
+//!
+//! [`Signal`](crate::Signal)s keep values in their textual encoding, which
+//! is what every input format can produce cheaply. This module decodes them
+//! into [`LogicVec`]s, bit-packed with the fewest bits per bit of value
+//! that represent it losslessly:
+//!
+//! * 2-state (`0 1`): one bit,
+//! * 4-state (`0 1 x z`): two bits,
+//! * 9-state (VHDL `std_logic`, adding `u w l h -`): four bits.
+//!
+//! Code reading single bits or integers straight from raw values, such as
+//! edge detection and numeric decoding, goes through [`Logic`] with the
+//! same left extension as [`LogicVec::from_vcd`].
+
+use std::fmt::{self, Display};
+
+use crate::{InvalidData, VarKind};
+
+/// One bit of a logic value. The first four states are Verilog's, all nine
+/// are VHDL's `std_logic`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum Logic {
+    Zero = 0,
+    One = 1,
+    X = 2,
+    Z = 3,
+    /// Uninitialized
+    U = 4,
+    /// Weak unknown
+    W = 5,
+    /// Weak zero
+    L = 6,
+    /// Weak one
+    H = 7,
+    /// Don't care
+    DontCare = 8,
+}
+
+const LOGIC: [Logic; 9] = [
+    Logic::Zero,
+    Logic::One,
+    Logic::X,
+    Logic::Z,
+    Logic::U,
+    Logic::W,
+    Logic::L,
+    Logic::H,
+    Logic::DontCare,
+];
+
+impl Logic {
+    /// Parse a value character, ignoring case.
+    pub fn from_char(c: u8) -> Option<Logic> {
+        Some(match c {
+            b'0' => Logic::Zero,
+            b'1' => Logic::One,
+            b'x' | b'X' => Logic::X,
+            b'z' | b'Z' => Logic::Z,
+            b'u' | b'U' => Logic::U,
+            b'w' | b'W' => Logic::W,
+            b'l' | b'L' => Logic::L,
+            b'h' | b'H' => Logic::H,
+            b'-' => Logic::DontCare,
+            _ => return None,
+        })
+    }
+
+    /// The lower-case value character.
+    pub fn to_char(self) -> u8 {
+        b"01xzuwlh-"[self as usize]
+    }
+
+    /// The smallest state set containing this value.
+    pub fn states(self) -> States {
+        match self {
+            Logic::Zero | Logic::One => States::Two,
+            Logic::X | Logic::Z => States::Four,
+            _ => States::Nine,
+        }
+    }
+
+    /// The bit a VCD vector value is extended with on the left: its
+    /// leftmost bit if that is `x` or `z`, `0` otherwise.
+    pub(crate) fn vcd_fill(value: &[u8]) -> Logic {
+        match value.first().and_then(|&c| Logic::from_char(c)) {
+            Some(b @ (Logic::X | Logic::Z)) => b,
+            _ => Logic::Zero,
+        }
+    }
+
+    /// Bit `i`, counted from the least significant bit, of a VCD vector
+    /// value extended like [`LogicVec::from_vcd`] does, if it is a logic
+    /// value.
+    #[inline]
+    pub(crate) fn vcd_bit(value: &[u8], i: usize) -> Option<Logic> {
+        match value.len().checked_sub(i + 1) {
+            Some(pos) => Logic::from_char(value[pos]),
+            None => Some(Logic::vcd_fill(value)),
+        }
+    }
+
+    /// Map to the closest 4-state value, like VHDL's `To_X01Z`.
+    pub fn to_four_state(self) -> Logic {
+        match self {
+            Logic::L => Logic::Zero,
+            Logic::H => Logic::One,
+            Logic::U | Logic::W | Logic::DontCare => Logic::X,
+            v => v,
+        }
+    }
+}
+
+impl Display for Logic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (self.to_char() as char).fmt(f)
+    }
+}
+
+/// A set of logic states, which determines the storage of a [`LogicVec`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum States {
+    Two,
+    Four,
+    Nine,
+}
+
+impl States {
+    /// Storage bits per bit of value.
+    pub fn bits(self) -> u32 {
+        match self {
+            States::Two => 1,
+            States::Four => 2,
+            States::Nine => 4,
+        }
+    }
+}
+
+/// A bit-packed vector of logic values.
+///
+/// Bit 0 is the least significant bit, i.e. the last character of the
+/// textual encoding.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "LogicVecParts")
+)]
+pub struct LogicVec {
+    states: States,
+    len: usize,
+    words: Vec<u64>,
+}
+
+/// The fields of a deserialized [`LogicVec`], checked before use.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct LogicVecParts {
+    states: States,
+    len: usize,
+    words: Vec<u64>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<LogicVecParts> for LogicVec {
+    type Error = InvalidData;
+
+    fn try_from(p: LogicVecParts) -> Result<LogicVec, InvalidData> {
+        let bits = p.states.bits() as usize;
+        let per_word = 64 / bits;
+        if p.words.len() != p.len.div_ceil(per_word) {
+            return Err(InvalidData("logic vector length does not match its words"));
+        }
+        let mask = (1u64 << bits) - 1;
+        for i in 0..p.words.len() * per_word {
+            let v = (p.words[i / per_word] >> (i % per_word * bits)) & mask;
+            if v as usize >= LOGIC.len() || (i >= p.len && v != 0) {
+                return Err(InvalidData("invalid logic value"));
+            }
+        }
+        Ok(LogicVec {
+            states: p.states,
+            len: p.len,
+            words: p.words,
+        })
+    }
+}
+
+impl LogicVec {
+    /// A vector of `len` copies of `v`.
+    pub fn repeat(v: Logic, len: usize) -> LogicVec {
+        let mut out = LogicVec::with_states(v.states(), len);
+        for i in 0..len {
+            out.set_raw(i, v as u64);
+        }
+        out
+    }
+
+    fn with_states(states: States, len: usize) -> LogicVec {
+        let per_word = 64 / states.bits() as usize;
+        LogicVec {
+            states,
+            len,
+            words: vec![0; len.div_ceil(per_word)],
+        }
+    }
+
+    #[inline]
+    fn set_raw(&mut self, i: usize, v: u64) {
+        let bits = self.states.bits() as usize;
+        let (word, shift) = (i * bits / 64, i * bits % 64);
+        self.words[word] |= v << shift;
+    }
+
+    /// Parse a textual value, most significant bit first.
+    pub fn from_bytes(value: &[u8]) -> Result<LogicVec, InvalidData> {
+        let mut states = States::Two;
+        for &c in value {
+            let v = Logic::from_char(c).ok_or(InvalidData("invalid logic value"))?;
+            states = states.max(v.states());
+        }
+        let mut out = LogicVec::with_states(states, value.len());
+        for (i, &c) in value.iter().rev().enumerate() {
+            out.set_raw(i, Logic::from_char(c).expect("checked above") as u64);
+        }
+        Ok(out)
+    }
+
+    /// Parse a VCD vector value, extending it to `width` bits: with `x` or
+    /// `z` if that is the leftmost bit, with `0` otherwise.
+    pub fn from_vcd(value: &[u8], width: usize) -> Result<LogicVec, InvalidData> {
+        let v = LogicVec::from_bytes(value)?;
+        if v.len() >= width {
+            return Ok(v);
+        }
+        let fill = Logic::vcd_fill(value);
+        let mut out = LogicVec::with_states(v.states, width);
+        for i in 0..width {
+            let b = if i < v.len() { v.get(i) } else { fill };
+            out.set_raw(i, b as u64);
+        }
+        Ok(out)
+    }
+
+    /// Number of bits.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The state set used for storage.
+    pub fn states(&self) -> States {
+        self.states
+    }
+
+    /// Bit `i`, counted from the least significant bit.
+    #[inline]
+    pub fn get(&self, i: usize) -> Logic {
+        assert!(i < self.len, "bit index out of range");
+        let bits = self.states.bits() as usize;
+        let (word, shift) = (i * bits / 64, i * bits % 64);
+        let mask = (1u64 << bits) - 1;
+        LOGIC[((self.words[word] >> shift) & mask) as usize]
+    }
+
+    /// Iterate from the least significant bit.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = Logic> + '_ {
+        (0..self.len).map(|i| self.get(i))
+    }
+
+    /// Re-pack with a different state set. Fails if a bit does not fit.
+    pub fn to_states(&self, states: States) -> Option<LogicVec> {
+        if self.iter().any(|b| b.states() > states) {
+            return None;
+        }
+        let mut out = LogicVec::with_states(states, self.len);
+        for (i, b) in self.iter().enumerate() {
+            out.set_raw(i, b as u64);
+        }
+        Some(out)
+    }
+
+    /// Map every bit to 4-state, see [`Logic::to_four_state`].
+    pub fn to_four_state(&self) -> LogicVec {
+        let mut states = States::Two;
+        for b in self.iter() {
+            states = states.max(b.to_four_state().states());
+        }
+        let mut out = LogicVec::with_states(states, self.len);
+        for (i, b) in self.iter().enumerate() {
+            out.set_raw(i, b.to_four_state() as u64);
+        }
+        out
+    }
+
+    /// The unsigned integer value, if every bit is `0` or `1` and it fits.
+    pub fn to_u64(&self) -> Option<u64> {
+        let mut n = 0u64;
+        for (i, b) in self.iter().enumerate() {
+            match b {
+                Logic::Zero => {}
+                Logic::One if i < 64 => n |= 1 << i,
+                _ => return None,
+            }
+        }
+        Some(n)
+    }
+
+    /// The textual encoding, most significant bit first.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.iter().rev().map(Logic::to_char).collect()
+    }
+}
+
+impl Display for LogicVec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in self.iter().rev() {
+            b.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// A decoded value of any variable.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Value {
+    Logic(LogicVec),
+    Real(f64),
+    String(String),
+}
+
+impl Value {
+    /// Decode a raw value of a variable of the given kind and width.
+    pub fn decode(kind: VarKind, width: u32, raw: &[u8]) -> Result<Value, InvalidData> {
+        if kind.is_real() {
+            let text = std::str::from_utf8(raw).map_err(|_| InvalidData("invalid real value"))?;
+            return text
+                .parse()
+                .map(Value::Real)
+                .map_err(|_| InvalidData("invalid real value"));
+        }
+        if kind == VarKind::String {
+            return Ok(Value::String(String::from_utf8_lossy(raw).into_owned()));
+        }
+        LogicVec::from_vcd(raw, width as usize).map(Value::Logic)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn packing() {
+        let two = LogicVec::from_bytes(b"1011").unwrap();
+        assert_eq!(two.states(), States::Two);
+        assert_eq!(two.get(0), Logic::One);
+        assert_eq!(two.get(2), Logic::Zero);
+        assert_eq!(two.to_u64(), Some(0b1011));
+
+        let four = LogicVec::from_bytes(b"x1Z0").unwrap();
+        assert_eq!(four.states(), States::Four);
+        assert_eq!(four.to_string(), "x1z0");
+        assert_eq!(four.to_u64(), None);
+
+        let nine = LogicVec::from_bytes(b"UXZWLH-01").unwrap();
+        assert_eq!(nine.states(), States::Nine);
+        assert_eq!(nine.to_bytes(), b"uxzwlh-01");
+        assert_eq!(nine.to_four_state().to_string(), "xxzx01x01");
+        assert!(nine.to_states(States::Four).is_none());
+
+        let wide = LogicVec::from_bytes(&[b'z'; 100]).unwrap();
+        assert_eq!(wide.words.len(), 4);
+        assert_eq!(wide.to_states(States::Nine).unwrap().words.len(), 7);
+        assert_eq!(wide.to_states(States::Nine).unwrap().get(99), Logic::Z);
+
+        assert!(LogicVec::from_bytes(b"12").is_err());
+    }
+
+    #[test]
+    fn vcd_extension_and_decode() {
+        assert_eq!(LogicVec::from_vcd(b"1", 4).unwrap().to_string(), "0001");
+        assert_eq!(LogicVec::from_vcd(b"x10", 5).unwrap().to_string(), "xxx10");
+        assert_eq!(LogicVec::from_vcd(b"z", 3).unwrap().to_string(), "zzz");
+        assert_eq!(LogicVec::from_vcd(b"0110", 2).unwrap().len(), 4);
+        assert_eq!(Logic::vcd_bit(b"Z1", 5), Some(Logic::Z));
+        assert_eq!(Logic::vcd_bit(b"h1", 5), Some(Logic::Zero));
+        assert_eq!(Logic::vcd_bit(b"h1", 1), Some(Logic::H));
+        assert_eq!(Logic::vcd_bit(b"a1", 1), None);
+
+        assert_eq!(
+            Value::decode(VarKind::Real, 64, b"1.5").unwrap(),
+            Value::Real(1.5)
+        );
+        assert_eq!(
+            Value::decode(VarKind::String, 1, b"idle").unwrap(),
+            Value::String("idle".to_string())
+        );
+        assert_eq!(
+            Value::decode(VarKind::Wire, 2, b"1").unwrap(),
+            Value::Logic(LogicVec::from_bytes(b"01").unwrap())
+        );
+    }
+}
@@ -0,0 +1,47 @@
+//! LEB128 varints, as used by FST, packed signals, spill files and
+//! Parquet metadata: 7 bits per byte, least significant first, with the
+//! top bit set on every byte but the last.
+
+/// Append `v` to `out`.
+pub(crate) fn write(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+/// The varint at `pos`, moving past it, or `None` if `data` ends inside
+/// it or it is longer than a `u64`.
+#[inline]
+pub(crate) fn read(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let b = *data.get(*pos)?;
+        *pos += 1;
+        v |= ((b & 0x7f) as u64) << shift;
+        if b < 0x80 {
+            return Some(v);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let mut out = Vec::new();
+        for v in [0, 1, 0x7f, 0x80, 300, u64::MAX] {
+            write(&mut out, v);
+        }
+        assert_eq!(out[..5], [0, 1, 0x7f, 0x80, 1]);
+        let mut pos = 0;
+        let values: Vec<u64> = std::iter::from_fn(|| read(&out, &mut pos)).collect();
+        assert_eq!(values, [0, 1, 0x7f, 0x80, 300, u64::MAX]);
+        assert_eq!(read(&[0x80], &mut 0), None);
+        assert_eq!(read(&[0xff; 10], &mut 0), None);
+    }
+}
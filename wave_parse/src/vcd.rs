@@ -0,0 +1,1360 @@
+//! Zero-copy VCD reading.
+//!
+//! [`VcdFile`] maps the whole file into memory and parses the body into
+//! [`Token`]s whose identifiers and values are slices borrowed from the
+//! mapping, so scanning a file allocates nothing per token.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::str::from_utf8;
+use std::sync::{Arc, OnceLock};
+
+use crate::index::{self, Stamp, VcdIndex};
+use crate::mmap::Mmap;
+use crate::parallel;
+use crate::progress::{Progress, REPORT_STEP};
+use crate::scan;
+use crate::{
+    Blackouts, CancelToken, FlatBuilder, FlatHierarchy, Hierarchy, InvalidData, Phase,
+    ProgressSink, ReferenceIndex, Signal, SignalId, SignalLoader, Timescale, Waveform,
+};
+
+/// Bytes before the end of what was read that [`VcdFile::refresh`]
+/// compares to the file on disk to detect rewrites.
+const REFRESH_CHECK: usize = 4096;
+
+/// Structure containing the data from the header of a VCD file.
+///
+/// The parser declares the scopes and variables into a [`FlatHierarchy`],
+/// which lookups and counts go through; the [`Hierarchy`] tree is built
+/// from it the first time it is asked for.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct Header {
+    pub date: Option<String>,
+    pub version: Option<String>,
+    pub comment: Option<String>,
+    pub timescale: Option<Timescale>,
+    flat: FlatHierarchy,
+    tree: OnceLock<Hierarchy>,
+}
+
+impl Header {
+    /// The scopes and variables, as parsed.
+    pub fn flat(&self) -> &FlatHierarchy {
+        &self.flat
+    }
+
+    /// The scopes and variables as a tree, built on first use.
+    pub fn hierarchy(&self) -> &Hierarchy {
+        self.tree.get_or_init(|| self.flat.to_hierarchy())
+    }
+
+    /// Replace the scopes and variables.
+    pub fn set_hierarchy(&mut self, hierarchy: Hierarchy) {
+        self.flat = FlatHierarchy::new(&hierarchy);
+        self.tree = OnceLock::from(hierarchy);
+    }
+}
+
+impl PartialEq for Header {
+    fn eq(&self, other: &Header) -> bool {
+        (&self.date, &self.version, &self.comment, self.timescale)
+            == (&other.date, &other.version, &other.comment, other.timescale)
+            && self.hierarchy() == other.hierarchy()
+    }
+}
+
+/// A simulation command type, used in [`Token::Begin`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SimulationCommand {
+    Dumpall,
+    Dumpoff,
+    Dumpon,
+    Dumpvars,
+}
+
+impl Display for SimulationCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use SimulationCommand::*;
+        f.write_str(match *self {
+            Dumpall => "dumpall",
+            Dumpoff => "dumpoff",
+            Dumpon => "dumpon",
+            Dumpvars => "dumpvars",
+        })
+    }
+}
+
+/// The encoding of a value change.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ChangeKind {
+    /// A `1a` change of a single bit
+    Scalar,
+    /// A `b0101 a` change of a bit vector
+    Vector,
+    /// A `r1.5 a` change of a real number
+    Real,
+    /// A `shello a` change of a string
+    String,
+}
+
+/// A value change borrowing its identifier and value from the input.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Change<'a> {
+    pub kind: ChangeKind,
+    /// The raw identifier code
+    pub id: &'a [u8],
+    /// The value without its `b`/`r`/`s` prefix
+    pub value: &'a [u8],
+}
+
+impl Change<'_> {
+    /// The signal this change applies to.
+    #[inline]
+    pub fn signal(&self) -> Result<SignalId, InvalidData> {
+        SignalId::from_code(self.id)
+    }
+}
+
+/// An element of a VCD body.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Token<'a> {
+    /// A `#xxx` timestamp
+    Timestamp(u64),
+    /// A value change
+    Change(Change<'a>),
+    /// The start of a `$dumpvars`-like block
+    Begin(SimulationCommand),
+    /// The `$end` of a simulation command
+    End,
+    /// The text of a `$comment` command
+    Comment(&'a [u8]),
+}
+
+fn unexpected_eof(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, msg)
+}
+
+fn text(v: &[u8]) -> String {
+    String::from_utf8_lossy(v.trim_ascii()).into_owned()
+}
+
+fn word_str(w: &[u8]) -> Result<&str, InvalidData> {
+    from_utf8(w).map_err(|_| InvalidData("string is not UTF-8"))
+}
+
+/// Parse a decimal `u64` without going through `str`.
+#[inline]
+pub(crate) fn parse_u64(v: &[u8]) -> Result<u64, InvalidData> {
+    if v.is_empty() {
+        return Err(InvalidData("empty number"));
+    }
+    let mut n = 0u64;
+    for &b in v {
+        let d = b.wrapping_sub(b'0');
+        if d > 9 {
+            return Err(InvalidData("invalid digit in number"));
+        }
+        n = n
+            .checked_mul(10)
+            .and_then(|n| n.checked_add(d as u64))
+            .ok_or(InvalidData("number too large"))?;
+    }
+    Ok(n)
+}
+
+/// Whitespace separated word scanner over a byte slice.
+#[derive(Clone)]
+struct Scanner<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    #[inline]
+    fn word(&mut self) -> Option<&'a [u8]> {
+        let data = self.data;
+        let start = scan::skip_whitespace(data, self.pos);
+        if start == data.len() {
+            self.pos = start;
+            return None;
+        }
+        let end = scan::find_whitespace(data, start);
+        self.pos = end;
+        Some(&data[start..end])
+    }
+
+    fn expect_word(&mut self) -> io::Result<&'a [u8]> {
+        self.word()
+            .ok_or_else(|| unexpected_eof("unexpected end of VCD file"))
+    }
+
+    fn expect_end(&mut self) -> io::Result<()> {
+        if self.expect_word()? == b"$end" {
+            Ok(())
+        } else {
+            Err(InvalidData("expected $end").into())
+        }
+    }
+
+    /// Skip to the next `$end` word, returning everything before it.
+    fn until_end(&mut self) -> io::Result<&'a [u8]> {
+        let start = self.pos;
+        loop {
+            let w = self.expect_word()?;
+            if w == b"$end" {
+                return Ok(&self.data[start..self.pos - w.len()]);
+            }
+        }
+    }
+}
+
+/// Parse the header, returning it and the offset of the first body byte.
+pub fn parse_header(data: &[u8]) -> io::Result<(Header, usize)> {
+    parse_header_reporting(data, &Progress::none())
+}
+
+fn parse_header_reporting(data: &[u8], progress: &Progress<'_>) -> io::Result<(Header, usize)> {
+    let mut sc = Scanner { data, pos: 0 };
+    let mut header = Header::default();
+    let mut flat = FlatBuilder::new();
+    let mut reported = 0;
+
+    loop {
+        if sc.pos - reported >= REPORT_STEP {
+            progress.add(sc.pos - reported)?;
+            reported = sc.pos;
+        }
+        let w = sc
+            .word()
+            .ok_or_else(|| unexpected_eof("unexpected end of VCD file before $enddefinitions"))?;
+        match w {
+            b"$date" => header.date = Some(text(sc.until_end()?)),
+            b"$version" => header.version = Some(text(sc.until_end()?)),
+            b"$comment" => header.comment = Some(text(sc.until_end()?)),
+            b"$timescale" => {
+                let ts = text(sc.until_end()?);
+                header.timescale = Some(ts.parse()?);
+            }
+            b"$scope" => {
+                let kind = word_str(sc.expect_word()?)?.parse()?;
+                let name = word_str(sc.expect_word()?)?;
+                sc.expect_end()?;
+                flat.open_scope(kind, name);
+            }
+            b"$upscope" => {
+                sc.expect_end()?;
+                flat.close_scope()
+                    .ok_or(InvalidData("unmatched $upscope"))?;
+            }
+            b"$var" => parse_var(&mut sc, &mut flat)?,
+            b"$enddefinitions" => {
+                sc.expect_end()?;
+                break;
+            }
+            // Unknown header commands (e.g. `$attrbegin`) are skipped.
+            _ if w.starts_with(b"$") => {
+                sc.until_end()?;
+            }
+            _ => return Err(InvalidData("unexpected token in header").into()),
+        }
+    }
+
+    if flat.depth() > 0 {
+        return Err(InvalidData("$enddefinitions with open $scope").into());
+    }
+    header.flat = flat.finish();
+    progress.add(sc.pos - reported)?;
+    Ok((header, sc.pos))
+}
+
+fn parse_var(sc: &mut Scanner<'_>, flat: &mut FlatBuilder) -> io::Result<()> {
+    let kind = word_str(sc.expect_word()?)?.parse()?;
+    let width = parse_u64(sc.expect_word()?)?;
+    let width = u32::try_from(width).map_err(|_| InvalidData("variable too wide"))?;
+    let signal = SignalId::from_code(sc.expect_word()?)?;
+    let name = word_str(sc.expect_word()?)?;
+    let mut index = None;
+    loop {
+        let w = sc.expect_word()?;
+        if w == b"$end" {
+            break;
+        }
+        if w.starts_with(b"[") {
+            index = Some(word_str(w)?.parse::<ReferenceIndex>()?);
+        }
+    }
+    flat.add_var(kind, width, signal, name, index);
+    Ok(())
+}
+
+/// Iterator over the [`Token`]s of a VCD body.
+///
+/// A timestamp earlier than the one before it is an error of kind
+/// [`InvalidData`](io::ErrorKind::InvalidData).
+#[derive(Clone)]
+pub struct Tokens<'a> {
+    sc: Scanner<'a>,
+    /// The last timestamp, which later ones may not precede.
+    time: Option<u64>,
+}
+
+impl<'a> Tokens<'a> {
+    /// Tokenize `data` starting at byte offset `pos`, which must be the
+    /// start of a token or whitespace.
+    pub fn new(data: &'a [u8], pos: usize) -> Tokens<'a> {
+        Tokens {
+            sc: Scanner { data, pos },
+            time: None,
+        }
+    }
+
+    /// Continue after tokens whose last timestamp was `time`.
+    pub(crate) fn after(mut self, time: Option<u64>) -> Tokens<'a> {
+        self.time = time;
+        self
+    }
+
+    /// The last timestamp read.
+    pub(crate) fn time(&self) -> Option<u64> {
+        self.time
+    }
+
+    /// Byte offset of the next unread byte.
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.sc.pos
+    }
+
+    #[inline]
+    fn value_then_id(&mut self, kind: ChangeKind, value: &'a [u8]) -> io::Result<Token<'a>> {
+        let id = self.sc.expect_word()?;
+        Ok(Token::Change(Change { kind, id, value }))
+    }
+
+    fn parse_command(&mut self, w: &'a [u8]) -> io::Result<Token<'a>> {
+        use SimulationCommand::*;
+        match w {
+            b"$dumpvars" => Ok(Token::Begin(Dumpvars)),
+            b"$dumpall" => Ok(Token::Begin(Dumpall)),
+            b"$dumpon" => Ok(Token::Begin(Dumpon)),
+            b"$dumpoff" => Ok(Token::Begin(Dumpoff)),
+            b"$end" => Ok(Token::End),
+            b"$comment" => Ok(Token::Comment(self.sc.until_end()?.trim_ascii())),
+            _ => Err(InvalidData("invalid keyword").into()),
+        }
+    }
+
+    #[inline]
+    fn parse_word(&mut self, w: &'a [u8]) -> io::Result<Token<'a>> {
+        match w[0] {
+            b'#' => {
+                let t = parse_u64(&w[1..])?;
+                if self.time.is_some_and(|last| t < last) {
+                    return Err(InvalidData("timestamp goes backwards").into());
+                }
+                self.time = Some(t);
+                Ok(Token::Timestamp(t))
+            }
+            b'0' | b'1' | b'x' | b'X' | b'z' | b'Z' | b'u' | b'U' | b'w' | b'W' | b'l' | b'L'
+            | b'h' | b'H' | b'-' => {
+                if w.len() > 1 {
+                    Ok(Token::Change(Change {
+                        kind: ChangeKind::Scalar,
+                        id: &w[1..],
+                        value: &w[..1],
+                    }))
+                } else {
+                    // Tolerate `1 !` with a space before the id.
+                    self.value_then_id(ChangeKind::Scalar, w)
+                }
+            }
+            b'b' | b'B' => self.value_then_id(ChangeKind::Vector, &w[1..]),
+            b'r' | b'R' => self.value_then_id(ChangeKind::Real, &w[1..]),
+            b's' | b'S' => self.value_then_id(ChangeKind::String, &w[1..]),
+            b'$' => self.parse_command(w),
+            _ => Err(InvalidData("unexpected character at start of command").into()),
+        }
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = io::Result<Token<'a>>;
+
+    #[inline]
+    fn next(&mut self) -> Option<io::Result<Token<'a>>> {
+        let w = self.sc.word()?;
+        Some(self.parse_word(w))
+    }
+}
+
+enum Data {
+    Mapped(Mmap),
+    /// The first bytes of a mapping, up to the last complete line.
+    Prefix(Mmap, usize),
+    Owned(Vec<u8>),
+    Source(Box<dyn AsRef<[u8]> + Send + Sync>),
+}
+
+impl Data {
+    #[inline]
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Data::Mapped(m) => m,
+            Data::Prefix(m, len) => &m[..*len],
+            Data::Owned(v) => v,
+            Data::Source(s) => s.as_ref().as_ref(),
+        }
+    }
+}
+
+/// A complete VCD file held in memory, usually through a memory map.
+///
+/// Signal loading splits the body into chunks parsed on several threads;
+/// see [`set_threads`](VcdFile::set_threads). With an
+/// [index] only the parts of the body containing the
+/// requested signals are parsed.
+pub struct VcdFile {
+    data: Data,
+    header: Header,
+    body_start: usize,
+    threads: usize,
+    index: Option<VcdIndex>,
+    progress: Option<Arc<dyn ProgressSink>>,
+    cancel: Option<CancelToken>,
+}
+
+impl VcdFile {
+    /// Map a file and parse its header.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<VcdFile> {
+        let file = File::open(path)?;
+        VcdFile::new(Data::Mapped(Mmap::open(&file)?))
+    }
+
+    /// Map a file and use its [sidecar index](index::sidecar_path), which
+    /// is built and saved first if it is missing or out of date. Failing
+    /// to save it, e.g. in a read-only directory, is not an error.
+    pub fn open_indexed<P: AsRef<Path>>(path: P) -> io::Result<VcdFile> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let data = Data::Mapped(Mmap::open(&file)?);
+        let stamp = Stamp::new(&file.metadata()?, data.as_slice());
+        let sidecar = index::sidecar_path(path);
+        if let Some((header, index)) = index::load_sidecar(&sidecar, stamp) {
+            return Ok(VcdFile {
+                data,
+                header,
+                body_start: index.body_start(),
+                threads: parallel::default_threads(),
+                index: Some(index),
+                progress: None,
+                cancel: None,
+            });
+        }
+        let mut vcd = VcdFile::new(data)?;
+        let index = VcdIndex::build(&vcd, stamp)?;
+        let _ = index::save_sidecar(&sidecar, &vcd.header, &index);
+        vcd.set_index(index)?;
+        Ok(vcd)
+    }
+
+    /// Map a file and parse its header, reporting to `sink`, which is then
+    /// kept for [loading signals](VcdFile::set_progress).
+    pub fn open_with_progress<P: AsRef<Path>>(
+        path: P,
+        sink: Arc<dyn ProgressSink>,
+    ) -> io::Result<VcdFile> {
+        let file = File::open(path)?;
+        let data = Data::Mapped(Mmap::open(&file)?);
+        let progress = Progress::new(
+            Some(&*sink),
+            None,
+            Phase::Header,
+            data.as_slice().len() as u64,
+        );
+        let (header, body_start) = parse_header_reporting(data.as_slice(), &progress)?;
+        let mut vcd = VcdFile::with_header(data, header, body_start);
+        vcd.progress = Some(sink);
+        Ok(vcd)
+    }
+
+    /// Parse a VCD file already in memory.
+    ///
+    /// ```
+    /// let vcd = wave_parse::VcdFile::from_bytes(b"
+    /// $scope module top $end
+    /// $var wire 1 ! clk $end
+    /// $upscope $end
+    /// $enddefinitions $end
+    /// #0 0!
+    /// #5 1!
+    /// ".to_vec()).unwrap();
+    /// assert_eq!(vcd.tokens().count(), 4);
+    /// ```
+    pub fn from_bytes(data: Vec<u8>) -> io::Result<VcdFile> {
+        VcdFile::new(Data::Owned(data))
+    }
+
+    /// Parse a VCD file from any owner of its bytes, such as an
+    /// `Arc<[u8]>` shared with other readers or a buffer handed over by a
+    /// browser, without copying them.
+    pub fn from_source<S>(source: S) -> io::Result<VcdFile>
+    where
+        S: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        VcdFile::new(Data::Source(Box::new(source)))
+    }
+
+    fn new(data: Data) -> io::Result<VcdFile> {
+        let (header, body_start) = parse_header(data.as_slice())?;
+        Ok(VcdFile::with_header(data, header, body_start))
+    }
+
+    fn with_header(data: Data, header: Header, body_start: usize) -> VcdFile {
+        VcdFile {
+            data,
+            header,
+            body_start,
+            threads: parallel::default_threads(),
+            index: None,
+            progress: None,
+            cancel: None,
+        }
+    }
+
+    /// Use `index` for loading signals. Fails if it was built for a file
+    /// of another layout.
+    pub fn set_index(&mut self, index: VcdIndex) -> io::Result<()> {
+        if !index.fits(self) {
+            return Err(InvalidData("index does not match file").into());
+        }
+        self.index = Some(index);
+        Ok(())
+    }
+
+    pub fn index(&self) -> Option<&VcdIndex> {
+        self.index.as_ref()
+    }
+
+    /// Number of threads used to load signals.
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Set the number of threads used to load signals. `1` disables
+    /// parallel parsing.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Report the progress of loading signals and of
+    /// [building an index](VcdIndex::build) to `sink`.
+    pub fn set_progress(&mut self, sink: Option<Arc<dyn ProgressSink>>) {
+        self.progress = sink;
+    }
+
+    pub(crate) fn progress_sink(&self) -> Option<&dyn ProgressSink> {
+        self.progress.as_deref()
+    }
+
+    /// Stop loading signals and [building an index](VcdIndex::build) with
+    /// a [`Cancelled`](crate::Cancelled) error once `token` is cancelled.
+    pub fn set_cancel_token(&mut self, token: Option<CancelToken>) {
+        self.cancel = token;
+    }
+
+    pub(crate) fn cancel_token(&self) -> Option<&CancelToken> {
+        self.cancel.as_ref()
+    }
+
+    /// The parsed header.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// The complete file contents.
+    pub fn bytes(&self) -> &[u8] {
+        self.data.as_slice()
+    }
+
+    /// Byte offset of the body, just past `$enddefinitions $end`.
+    pub fn body_start(&self) -> usize {
+        self.body_start
+    }
+
+    /// The body of the file, following the header.
+    pub fn body(&self) -> &[u8] {
+        &self.bytes()[self.body_start..]
+    }
+
+    /// Iterate over the tokens of the body.
+    pub fn tokens(&self) -> Tokens<'_> {
+        Tokens::new(self.bytes(), self.body_start)
+    }
+
+    /// Map the file at `path` again after it grew and take the complete
+    /// lines appended since it was read, returning their byte range, empty
+    /// if there are none. It must be the file this was read from, only
+    /// appended to since, and what was read must have ended with a
+    /// complete line, as it does after an earlier refresh. An
+    /// [index](VcdFile::set_index) is extended rather than rebuilt; load
+    /// the appended changes with [`load_appended`](VcdFile::load_appended).
+    /// Other formats have no counterpart, see the
+    /// [crate documentation](crate).
+    ///
+    /// A file found shorter than what was read is an error, but truncating
+    /// a mapped file is not supported: if it happens while the mapping is
+    /// being read, the process gets `SIGBUS`.
+    pub fn refresh<P: AsRef<Path>>(&mut self, path: P) -> io::Result<Range<usize>> {
+        let file = File::open(path)?;
+        // The old mapping faults past the end of a truncated file, so the
+        // length is checked before reading any of it.
+        let old = self.bytes().len();
+        let truncated = || io::Error::other("refreshed file was truncated");
+        if file.metadata()?.len() < old as u64 {
+            return Err(truncated());
+        }
+        let map = Mmap::open(&file)?;
+        if map.len() < old {
+            return Err(truncated());
+        }
+        let checked = old.saturating_sub(REFRESH_CHECK)..old;
+        if map[checked.clone()] != self.bytes()[checked] {
+            return Err(io::Error::other("refreshed file was rewritten"));
+        }
+        let len = match map[old..].iter().rposition(|&b| b == b'\n') {
+            Some(p) => old + p + 1,
+            None => return Ok(old..old),
+        };
+        self.data = Data::Prefix(map, len);
+        if let Some(mut index) = self.index.take() {
+            index.extend(self, Stamp::new(&file.metadata()?, self.bytes()))?;
+            self.index = Some(index);
+        }
+        Ok(old..len)
+    }
+
+    /// Load the changes of `ids` in the byte range `bytes` of the body,
+    /// which starts at the beginning of a line, such as a range returned
+    /// by [`refresh`](VcdFile::refresh). Changes before its first
+    /// timestamp are at the time in effect where it starts.
+    pub fn load_appended(&self, ids: &[SignalId], bytes: Range<usize>) -> io::Result<Vec<Signal>> {
+        let time = self.time_at(bytes.start)?;
+        let chunks = parallel::split_at_timestamps(self.bytes(), bytes, self.threads * 4);
+        self.load_chunks(ids, &chunks, time)
+    }
+
+    /// The time in effect at byte `pos` of the body, found by parsing
+    /// from the last line before it starting with a timestamp.
+    fn time_at(&self, pos: usize) -> io::Result<u64> {
+        let data = &self.bytes()[..pos];
+        let start = data[self.body_start..]
+            .windows(2)
+            .rposition(|w| w == b"\n#")
+            .map_or(self.body_start, |p| self.body_start + p + 1);
+        let mut time = 0;
+        for token in Tokens::new(data, start) {
+            if let Token::Timestamp(t) = token? {
+                time = t;
+            }
+        }
+        Ok(time)
+    }
+
+    /// Check that each of `chunks` after the first, which start at a
+    /// timestamp, does not start before the time in effect where it
+    /// starts. Tokenizing checks the order within a chunk.
+    pub(crate) fn check_order(&self, chunks: &[Range<usize>]) -> io::Result<()> {
+        for chunk in chunks.iter().skip(1) {
+            let before = self.time_at(chunk.start)?;
+            let mut tokens =
+                Tokens::new(&self.bytes()[..chunk.end], chunk.start).after(Some(before));
+            if let Some(Err(e)) = tokens.next() {
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Load `ids` from `chunks` of the body, the first starting at `time`.
+    fn load_chunks(
+        &self,
+        ids: &[SignalId],
+        chunks: &[Range<usize>],
+        time: u64,
+    ) -> io::Result<Vec<Signal>> {
+        let mut slots: HashMap<SignalId, usize> = HashMap::with_capacity(ids.len());
+        let mut unique = 0;
+        for &id in ids {
+            slots.entry(id).or_insert_with(|| {
+                unique += 1;
+                unique - 1
+            });
+        }
+
+        self.check_order(chunks)?;
+        let total = chunks.iter().map(|c| c.len() as u64).sum();
+        let progress = Progress::new(
+            self.progress_sink(),
+            self.cancel.as_ref(),
+            Phase::Signals,
+            total,
+        );
+        // Chunks after the first start at a timestamp, so `time` is only
+        // used by the first.
+        let parts = parallel::map_chunks(self.bytes(), chunks, self.threads, |tokens| {
+            collect_changes(tokens, time, &slots, unique, &progress)
+        })?;
+
+        let mut parts = parts.into_iter();
+        let mut merged = parts
+            .next()
+            .unwrap_or_else(|| (0..unique).map(|_| Signal::new()).collect());
+        for part in parts {
+            for (signal, piece) in merged.iter_mut().zip(&part) {
+                signal.append(piece);
+            }
+        }
+        // Duplicate requests share the data of their first occurrence.
+        Ok(ids.iter().map(|id| merged[slots[id]].clone()).collect())
+    }
+
+    /// The changes of signal `id` at times in `range`, parsed as the
+    /// iterator advances. With an [index](VcdFile::set_index) parsing
+    /// starts at the block holding `range.start` and skips the blocks the
+    /// signal does not change in; without one it starts at the beginning
+    /// of the body. Parsing stops at the first timestamp past the range.
+    pub fn changes_in(&self, id: SignalId, range: Range<u64>) -> Changes<'_> {
+        let data = self.bytes();
+        let mut runs: Vec<Range<usize>> = match &self.index {
+            Some(index) => {
+                let first = index.block_at(range.start);
+                index
+                    .blocks_of(&[id])
+                    .into_iter()
+                    .filter(|&i| i >= first && index.block_time(i) < range.end)
+                    .map(|i| index.block(i))
+                    .collect()
+            }
+            None => {
+                let body = self.body_start..data.len();
+                vec![body]
+            }
+        };
+        runs.reverse();
+        Changes {
+            data,
+            runs,
+            tokens: None,
+            id,
+            range,
+            time: 0,
+        }
+    }
+}
+
+/// A lazy iterator over the changes of one signal in a time range; see
+/// [`VcdFile::changes_in`].
+pub struct Changes<'a> {
+    data: &'a [u8],
+    /// Byte ranges still to parse, in reverse order.
+    runs: Vec<Range<usize>>,
+    tokens: Option<Tokens<'a>>,
+    id: SignalId,
+    range: Range<u64>,
+    time: u64,
+}
+
+impl<'a> Iterator for Changes<'a> {
+    type Item = io::Result<(u64, &'a [u8])>;
+
+    fn next(&mut self) -> Option<io::Result<(u64, &'a [u8])>> {
+        loop {
+            let Some(tokens) = &mut self.tokens else {
+                let run = self.runs.pop()?;
+                self.tokens = Some(Tokens::new(&self.data[..run.end], run.start));
+                continue;
+            };
+            let token = match tokens.next() {
+                Some(Ok(token)) => token,
+                Some(Err(e)) => {
+                    self.runs.clear();
+                    self.tokens = None;
+                    return Some(Err(e));
+                }
+                None => {
+                    self.tokens = None;
+                    continue;
+                }
+            };
+            match token {
+                Token::Timestamp(t) if t >= self.range.end => {
+                    self.runs.clear();
+                    self.tokens = None;
+                    return None;
+                }
+                Token::Timestamp(t) => self.time = t,
+                Token::Change(c) if self.time >= self.range.start => match c.signal() {
+                    Ok(id) if id == self.id => return Some(Ok((self.time, c.value))),
+                    Ok(_) => {}
+                    Err(e) => return Some(Err(e.into())),
+                },
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Collect the changes of the signals in `slots` from one run of tokens.
+///
+/// Changes before the first timestamp are recorded at `time`.
+pub(crate) fn collect_changes(
+    mut tokens: Tokens<'_>,
+    mut time: u64,
+    slots: &HashMap<SignalId, usize>,
+    count: usize,
+    progress: &Progress<'_>,
+) -> io::Result<Vec<Signal>> {
+    let mut signals: Vec<Signal> = (0..count).map(|_| Signal::new()).collect();
+    let mut reported = tokens.position();
+    while let Some(token) = tokens.next() {
+        if tokens.position() - reported >= REPORT_STEP {
+            progress.add(tokens.position() - reported)?;
+            reported = tokens.position();
+        }
+        match token? {
+            Token::Timestamp(t) => time = t,
+            Token::Change(c) => {
+                if let Some(&slot) = slots.get(&c.signal()?) {
+                    signals[slot].push(time, c.value);
+                }
+            }
+            _ => {}
+        }
+    }
+    progress.add(tokens.position() - reported)?;
+    Ok(signals)
+}
+
+/// The `$dumpoff` (`false`) and `$dumpon` (`true`) edges of one run of
+/// tokens, with their times.
+pub(crate) fn dump_edges(tokens: Tokens<'_>) -> io::Result<Vec<(u64, bool)>> {
+    let mut edges = Vec::new();
+    let mut time = 0;
+    for token in tokens {
+        match token? {
+            Token::Timestamp(t) => time = t,
+            Token::Begin(SimulationCommand::Dumpoff) => edges.push((time, false)),
+            Token::Begin(SimulationCommand::Dumpon) => edges.push((time, true)),
+            _ => {}
+        }
+    }
+    Ok(edges)
+}
+
+/// Whether `body` may contain a `$dumpoff`, checked without tokenizing.
+pub(crate) fn may_dump_off(body: &[u8]) -> bool {
+    let mut pos = 0;
+    while let Some(at) = scan::find_byte(body, pos, b'$') {
+        if body[at..].starts_with(b"$dumpoff") {
+            return true;
+        }
+        pos = at + 1;
+    }
+    false
+}
+
+impl SignalLoader for VcdFile {
+    /// Loads signals in a single pass over the body, split across
+    /// [`threads`](VcdFile::threads) threads for large files, or over the
+    /// blocks the index lists for them.
+    fn load_signals(&mut self, ids: &[SignalId]) -> io::Result<Vec<Signal>> {
+        let data = self.bytes();
+        let chunks: Vec<Range<usize>> = match &self.index {
+            Some(index) => index
+                .blocks_of(ids)
+                .into_iter()
+                .map(|i| index.block(i))
+                .collect(),
+            None => {
+                parallel::split_at_timestamps(data, self.body_start..data.len(), self.threads * 4)
+            }
+        };
+        self.load_chunks(ids, &chunks, 0)
+    }
+}
+
+impl Waveform for VcdFile {
+    fn hierarchy(&self) -> &Hierarchy {
+        self.header.hierarchy()
+    }
+
+    fn timescale(&self) -> Option<Timescale> {
+        self.header.timescale
+    }
+
+    fn date(&self) -> Option<&str> {
+        self.header.date.as_deref()
+    }
+
+    /// Taken from the index if there is one, otherwise found with a pass
+    /// over the body unless it has no `$dumpoff` at all.
+    fn blackouts(&self) -> io::Result<Blackouts> {
+        if let Some(index) = &self.index {
+            return Ok(index.blackouts().clone());
+        }
+        if !may_dump_off(self.body()) {
+            return Ok(Blackouts::default());
+        }
+        let data = self.bytes();
+        let chunks = parallel::split_at_timestamps(data, self.body_start..data.len(), self.threads);
+        let edges = parallel::map_chunks(data, &chunks, self.threads, dump_edges)?;
+        Ok(Blackouts::from_edges(edges.into_iter().flatten()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ScopeKind, TimeUnit, VarKind};
+
+    const WIKIPEDIA: &[u8] = b"
+$date
+   Date text.
+$end
+$version
+   VCD generator text.
+$end
+$comment
+   Any comment text.
+$end
+$timescale 100 ns $end
+$scope module logic $end
+$var wire 8 # data $end
+$var wire 1 $ data_valid $end
+$var wire 1 % en $end
+$var wire 1 & rx_en $end
+$var wire 1 ' tx_en $end
+$var wire 1 ( empty $end
+$var wire 1 ) underrun $end
+$upscope $end
+$enddefinitions $end
+$dumpvars
+bxxxxxxxx #
+x$
+0%
+x&
+x'
+1(
+0)
+$end
+#0
+b10000001 #
+0$
+1%
+#2211
+0'
+#2296
+b0 #
+1$
+#2302
+0$
+#2303
+";
+
+    fn id(code: &[u8]) -> SignalId {
+        SignalId::from_code(code).unwrap()
+    }
+
+    #[test]
+    fn wikipedia_header() {
+        let (header, _) = parse_header(WIKIPEDIA).unwrap();
+        assert_eq!(header.date.as_deref(), Some("Date text."));
+        assert_eq!(header.version.as_deref(), Some("VCD generator text."));
+        assert_eq!(header.comment.as_deref(), Some("Any comment text."));
+        assert_eq!(header.timescale, Some(Timescale::new(100, TimeUnit::NS)));
+
+        let scope = &header.hierarchy().scopes[0];
+        assert_eq!(header.hierarchy().name(scope.name), "logic");
+        assert_eq!(scope.kind, ScopeKind::Module);
+        assert_eq!(scope.vars.len(), 7);
+        assert_eq!(scope.vars[0].kind, VarKind::Wire);
+        assert_eq!(scope.vars[0].width, 8);
+        assert_eq!(scope.vars[0].signal, id(b"#"));
+        let flat = header.flat();
+        let underrun = flat.lookup("logic.underrun").unwrap();
+        assert_eq!(flat.var(underrun).signal, id(b")"));
+    }
+
+    #[test]
+    fn wikipedia_tokens() {
+        let vcd = VcdFile::from_bytes(WIKIPEDIA.to_vec()).unwrap();
+        let tokens: Vec<Token> = vcd.tokens().map(|t| t.unwrap()).collect();
+        let change = |kind, id: &'static [u8], value: &'static [u8]| {
+            Token::Change(Change { kind, id, value })
+        };
+        use ChangeKind::*;
+        assert_eq!(tokens[0], Token::Begin(SimulationCommand::Dumpvars));
+        assert_eq!(tokens[1], change(Vector, b"#", b"xxxxxxxx"));
+        assert_eq!(tokens[2], change(Scalar, b"$", b"x"));
+        assert_eq!(tokens[8], Token::End);
+        assert_eq!(tokens[9], Token::Timestamp(0));
+        assert_eq!(tokens[10], change(Vector, b"#", b"10000001"));
+        assert_eq!(tokens.last(), Some(&Token::Timestamp(2303)));
+        assert_eq!(tokens.len(), 21);
+    }
+
+    #[test]
+    fn load_selected_signals() {
+        let mut vcd = VcdFile::from_bytes(WIKIPEDIA.to_vec()).unwrap();
+        let signals = vcd.load_signals(&[id(b"$"), id(b"#"), id(b"$")]).unwrap();
+        let valid: Vec<_> = signals[0].iter().collect();
+        assert_eq!(
+            valid,
+            vec![
+                (0, &b"x"[..]),
+                (0, &b"0"[..]),
+                (2296, &b"1"[..]),
+                (2302, &b"0"[..])
+            ]
+        );
+        assert_eq!(signals[1].len(), 3);
+        assert_eq!(signals[1].value(2), b"0");
+        assert_eq!(signals[2], signals[0]);
+    }
+
+    #[test]
+    fn blackouts() {
+        let text = b"$var wire 1 ! a $end $enddefinitions $end
+#0 1! #10 $dumpoff x! $end #25 $dumpon 1! $end #30 0! #40 $dumpoff $end";
+        let mut vcd = VcdFile::from_bytes(text.to_vec()).unwrap();
+        let blackouts = vcd.blackouts().unwrap();
+        assert_eq!(blackouts.ranges(), [10..25, 40..u64::MAX]);
+        let a = vcd.load_signals(&[id(b"!")]).unwrap().remove(0);
+        assert_eq!(blackouts.value_at(&a, 5), crate::Sample::Value(b"1"));
+        assert_eq!(blackouts.value_at(&a, 12), crate::Sample::NoData);
+        assert_eq!(blackouts.value_at(&a, 45), crate::Sample::NoData);
+
+        let stamp = Stamp {
+            len: text.len() as u64,
+            modified: 0,
+            hash: 0,
+        };
+        let index = VcdIndex::build(&vcd, stamp).unwrap();
+        let mut sidecar = Vec::new();
+        index.write(vcd.header(), &mut sidecar).unwrap();
+        vcd.set_index(index).unwrap();
+        assert_eq!(vcd.blackouts().unwrap(), blackouts);
+        let source = crate::source::SourceVcd::open(text.to_vec()).unwrap();
+        assert_eq!(source.blackouts().unwrap(), blackouts);
+        let indexed = crate::source::SourceVcd::open_indexed(text.to_vec(), &sidecar).unwrap();
+        assert_eq!(indexed.blackouts().unwrap(), blackouts);
+
+        let shifted = crate::align::Shifted::new(vcd, -15);
+        assert_eq!(shifted.blackouts().unwrap().ranges(), [0..10, 25..u64::MAX]);
+        let plain = VcdFile::from_bytes(WIKIPEDIA.to_vec()).unwrap();
+        assert!(plain.blackouts().unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_time_going_back() {
+        let mut vcd =
+            VcdFile::from_bytes(b"$var wire 1 ! a $end $enddefinitions $end #10 1! #5 0!".to_vec())
+                .unwrap();
+        let err = vcd.load_signals(&[id(b"!")]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(vcd.tokens().any(|t| t.is_err()));
+
+        // Wherever the chunks split, the step back is found.
+        let mut text = b"$var wire 1 ! a $end $enddefinitions $end\n".to_vec();
+        for t in (0..100_000u64).chain(50_000..100_000) {
+            text.extend_from_slice(format!("#{}\n{}!\n", t, t % 2).as_bytes());
+        }
+        let mut vcd = VcdFile::from_bytes(text).unwrap();
+        vcd.set_threads(4);
+        let err = vcd.load_signals(&[id(b"!")]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let stamp = Stamp {
+            len: 0,
+            modified: 0,
+            hash: 0,
+        };
+        assert!(VcdIndex::build(&vcd, stamp).is_err());
+    }
+
+    #[test]
+    fn aliases_share_data() {
+        let vcd = VcdFile::from_bytes(
+            b"$scope module top $end $var wire 1 ! clk $end $var wire 8 \" d $end
+$scope module cpu $end $var wire 1 ! clk_in $end $upscope $end
+$scope module mem $end $var wire 1 ! clk $end $upscope $end $upscope $end
+$enddefinitions $end #0 0! #5 1!"
+                .to_vec(),
+        )
+        .unwrap();
+        let aliases = vcd.hierarchy().aliases();
+        let clk = id(b"!");
+        assert_eq!(
+            aliases.paths(clk),
+            ["top.clk", "top.cpu.clk_in", "top.mem.clk"]
+        );
+        assert_eq!(aliases.canonical(clk), Some("top.clk"));
+        assert!(!aliases.is_aliased(id(b"\"")));
+        assert_eq!(aliases.groups().count(), 1);
+        assert_eq!(aliases.alias_count(), 2);
+        assert!(aliases.paths(id(b"%")).is_empty());
+
+        let mut store = crate::SignalStore::new(vcd, usize::MAX);
+        let a = store
+            .loader()
+            .hierarchy()
+            .lookup("top.cpu.clk_in")
+            .unwrap()
+            .signal;
+        let b = store
+            .loader()
+            .hierarchy()
+            .lookup("top.mem.clk")
+            .unwrap()
+            .signal;
+        let (a, b) = (store.get(a).unwrap(), store.get(b).unwrap());
+        assert!(Arc::ptr_eq(&a, &b));
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn tolerates_loose_formatting() {
+        let vcd = VcdFile::from_bytes(
+            b"$timescale 1ps $end $var real 64 a r $end $enddefinitions $end
+              #1 r1.5 a 1 b $comment hi there $end #2"
+                .to_vec(),
+        )
+        .unwrap();
+        assert_eq!(
+            vcd.header().timescale,
+            Some(Timescale::new(1, TimeUnit::PS))
+        );
+        let h = vcd.header().hierarchy();
+        assert_eq!(h.name(h.vars[0].name), "r");
+        let tokens: Vec<Token> = vcd.tokens().map(|t| t.unwrap()).collect();
+        assert_eq!(
+            tokens[1],
+            Token::Change(Change {
+                kind: ChangeKind::Real,
+                id: b"a",
+                value: b"1.5"
+            })
+        );
+        assert_eq!(
+            tokens[2],
+            Token::Change(Change {
+                kind: ChangeKind::Scalar,
+                id: b"b",
+                value: b"1"
+            })
+        );
+        assert_eq!(tokens[3], Token::Comment(b"hi there"));
+        assert_eq!(tokens[4], Token::Timestamp(2));
+    }
+
+    #[test]
+    fn open_maps_file() {
+        let path = std::env::temp_dir().join(format!("wave_parse_open_{}.vcd", std::process::id()));
+        std::fs::write(&path, WIKIPEDIA).unwrap();
+        let mut vcd = VcdFile::open(&path).unwrap();
+        assert_eq!(vcd.bytes(), WIKIPEDIA);
+        assert_eq!(vcd.hierarchy().var_count(), 7);
+        assert_eq!(vcd.load_signals(&[id(b"'")]).unwrap()[0].len(), 2);
+        drop(vcd);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn shared_source() {
+        let bytes: std::sync::Arc<[u8]> = WIKIPEDIA.into();
+        let mut vcd = VcdFile::from_source(bytes.clone()).unwrap();
+        assert_eq!(vcd.bytes().as_ptr(), bytes.as_ptr());
+        assert_eq!(vcd.load_signals(&[id(b"'")]).unwrap()[0].len(), 2);
+    }
+
+    #[test]
+    fn parallel_load_matches_sequential() {
+        let mut text = b"$scope module t $end $var wire 4 ! a $end $var wire 1 \" b $end \
+                         $upscope $end $enddefinitions $end\n"
+            .to_vec();
+        for t in 0..200_000u64 {
+            text.extend_from_slice(format!("#{}\nb{:b} !\n{}\"\n", t, t % 16, t % 2).as_bytes());
+        }
+        let mut vcd = VcdFile::from_bytes(text).unwrap();
+        let ids = [id(b"!"), id(b"\"")];
+        vcd.set_threads(1);
+        let sequential = vcd.load_signals(&ids).unwrap();
+        vcd.set_threads(4);
+        let parallel = vcd.load_signals(&ids).unwrap();
+        assert_eq!(sequential[0].len(), 200_000);
+        assert_eq!(sequential, parallel);
+    }
+
+    #[test]
+    fn reports_progress() {
+        let mut text = b"$scope module t $end $var wire 1 ! a $end $upscope $end \
+                         $enddefinitions $end\n"
+            .to_vec();
+        for t in 0..500_000u64 {
+            text.extend_from_slice(format!("#{}\n{}!\n", t, t % 2).as_bytes());
+        }
+        let path =
+            std::env::temp_dir().join(format!("wave_parse_progress_{}.vcd", std::process::id()));
+        std::fs::write(&path, &text).unwrap();
+        let reports = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = reports.clone();
+        let mut vcd = VcdFile::open_with_progress(
+            &path,
+            Arc::new(move |phase, done, total| sink.lock().unwrap().push((phase, done, total))),
+        )
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let stamp = Stamp {
+            len: text.len() as u64,
+            modified: 0,
+            hash: 0,
+        };
+        vcd.set_index(VcdIndex::build(&vcd, stamp).unwrap())
+            .unwrap();
+        vcd.load_signals(&[id(b"!")]).unwrap();
+
+        let reports = reports.lock().unwrap();
+        let body = (text.len() - vcd.body_start()) as u64;
+        let last = |phase| *reports.iter().rev().find(|r| r.0 == phase).unwrap();
+        assert_eq!(reports[0], (Phase::Header, 0, text.len() as u64));
+        assert_eq!(last(Phase::Header).1, vcd.body_start() as u64);
+        assert_eq!(last(Phase::Index), (Phase::Index, body, body));
+        assert_eq!(last(Phase::Signals).1, last(Phase::Signals).2);
+        let signals = reports.iter().filter(|r| r.0 == Phase::Signals).count();
+        assert!(signals > 2);
+    }
+
+    #[test]
+    fn cancels_loading() {
+        let mut text = b"$scope module t $end $var wire 1 ! a $end $upscope $end \
+                         $enddefinitions $end\n"
+            .to_vec();
+        for t in 0..500_000u64 {
+            text.extend_from_slice(format!("#{}\n{}!\n", t, t % 2).as_bytes());
+        }
+        let mut vcd = VcdFile::from_bytes(text).unwrap();
+        vcd.set_threads(2);
+        let token = CancelToken::new();
+        let cancel = token.clone();
+        // Cancel at the first progress report, long before the body is
+        // through.
+        vcd.set_progress(Some(Arc::new(move |_, done, _| {
+            if done > 0 {
+                cancel.cancel()
+            }
+        })));
+        vcd.set_cancel_token(Some(token.clone()));
+        let err = vcd.load_signals(&[id(b"!")]).unwrap_err();
+        assert!(crate::Cancelled::is(&err));
+        let stamp = Stamp {
+            len: vcd.bytes().len() as u64,
+            modified: 0,
+            hash: 0,
+        };
+        assert!(crate::Cancelled::is(
+            &VcdIndex::build(&vcd, stamp).unwrap_err()
+        ));
+
+        vcd.set_cancel_token(None);
+        assert_eq!(vcd.load_signals(&[id(b"!")]).unwrap()[0].len(), 500_000);
+        assert!(!crate::Cancelled::is(&InvalidData("x").into()));
+    }
+
+    #[test]
+    fn header_errors() {
+        assert!(parse_header(b"$scope module a $end $enddefinitions $end").is_err());
+        assert!(parse_header(b"$upscope $end").is_err());
+        assert!(parse_header(b"$var wire 1 ! a $end").is_err());
+    }
+
+    #[test]
+    fn refresh_appended() {
+        use crate::SignalStore;
+        use std::io::Write;
+
+        let path =
+            std::env::temp_dir().join(format!("wave_parse_refresh_{}.vcd", std::process::id()));
+        std::fs::write(
+            &path,
+            b"$var wire 1 ! a $end $var wire 2 \" b $end $enddefinitions $end\n#0\n0!\nb0 \"\n#5\n1!\n",
+        )
+        .unwrap();
+        let vcd = VcdFile::open_indexed(&path).unwrap();
+        let (a, b) = (
+            SignalId::from_code(b"!").unwrap(),
+            SignalId::from_code(b"\"").unwrap(),
+        );
+        let mut store = SignalStore::new(vcd, 1 << 20);
+        store.prefetch(&[a, b]).unwrap();
+        let before = store.get(a).unwrap();
+
+        let mut out = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        out.write_all(b"b1 \"\n#8\n$dumpoff\n#9\n$dumpon 0! $end\n#12\n0")
+            .unwrap();
+        let appended = store.loader_mut().refresh(&path).unwrap();
+        // The last line is not complete yet.
+        assert_eq!(&store.loader().bytes()[appended.clone()][..6], b"b1 \"\n#");
+        assert!(store.loader().bytes().ends_with(b"#12\n"));
+        store
+            .append(|vcd, ids| vcd.load_appended(ids, appended.clone()))
+            .unwrap();
+        assert_eq!(before.times(), &[0, 5]);
+        assert_eq!(store.get(a).unwrap().times(), &[0, 5, 9]);
+        let bus = store.get(b).unwrap();
+        let bus: Vec<(u64, &[u8])> = bus.iter().collect();
+        assert_eq!(bus, [(0, &b"0"[..]), (5, b"1")]);
+
+        out.write_all(b"!\n").unwrap();
+        let appended = store.loader_mut().refresh(&path).unwrap();
+        assert_eq!(appended.len(), 3);
+        assert_eq!(store.loader_mut().refresh(&path).unwrap().len(), 0);
+        store
+            .append(|vcd, ids| vcd.load_appended(ids, appended.clone()))
+            .unwrap();
+        assert_eq!(store.get(a).unwrap().times(), &[0, 5, 9, 12]);
+
+        // The extended index loads what a full pass does.
+        let mut vcd = store.into_inner();
+        assert_eq!(vcd.blackouts().unwrap().at(8), Some(8..9));
+        let mut fresh = VcdFile::from_bytes(vcd.bytes().to_vec()).unwrap();
+        assert_eq!(
+            vcd.load_signals(&[a, b]).unwrap(),
+            fresh.load_signals(&[a, b]).unwrap()
+        );
+
+        std::fs::write(&path, b"$enddefinitions $end\n").unwrap();
+        assert!(vcd.refresh(&path).is_err());
+
+        // Truncated by several pages, which the old mapping no longer
+        // covers.
+        let mut body = b"$enddefinitions $end\n".to_vec();
+        for t in 0..10_000 {
+            body.extend_from_slice(format!("#{t}\n1!\n").as_bytes());
+        }
+        std::fs::write(&path, &body).unwrap();
+        let mut vcd = VcdFile::open(&path).unwrap();
+        std::fs::write(&path, &body[..100]).unwrap();
+        let err = vcd.refresh(&path).unwrap_err();
+        assert_eq!(err.to_string(), "refreshed file was truncated");
+        std::fs::remove_file(&path).unwrap();
+        let _ = std::fs::remove_file(index::sidecar_path(&path));
+    }
+}
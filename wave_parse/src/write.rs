@@ -0,0 +1,453 @@
+//! VCD writing.
+//!
+//! [`VcdWriter`] writes commands one at a time and keeps track of the
+//! identifier code and encoding of every declared variable, so changes
+//! are written by [`SignalId`]. [`write_waveform`] serializes a complete
+//! [`Waveform`] through it.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::io::{self, Write};
+
+use crate::vcd::{ChangeKind, Header, SimulationCommand};
+use crate::{Hierarchy, Scope, ScopeKind, SignalId, Timescale, Var, VarKind, Waveform};
+
+/// Struct wrapping an `io::Write` with methods for writing VCD commands and data.
+pub struct VcdWriter<W: Write> {
+    writer: W,
+    compact_ids: bool,
+    codes: HashMap<SignalId, (String, ChangeKind)>,
+    next_id: u64,
+    scope_depth: usize,
+}
+
+/// Names must not contain whitespace, which would end the word.
+fn write_name<W: Write>(w: &mut W, name: &str) -> io::Result<()> {
+    for (i, part) in name.split(char::is_whitespace).enumerate() {
+        if i > 0 {
+            w.write_all(b"_")?;
+        }
+        w.write_all(part.as_bytes())?;
+    }
+    Ok(())
+}
+
+impl<W: Write> VcdWriter<W> {
+    /// Creates a writer, wrapping an `io::Write`.
+    pub fn new(writer: W) -> VcdWriter<W> {
+        VcdWriter {
+            writer,
+            compact_ids: false,
+            codes: HashMap::new(),
+            next_id: 0,
+            scope_depth: 0,
+        }
+    }
+
+    /// Assign dense identifier codes in declaration order instead of
+    /// reusing the codes of the declared [`SignalId`]s. Must be set before
+    /// the first variable is declared.
+    pub fn set_compact_ids(&mut self, compact: bool) {
+        debug_assert!(self.codes.is_empty(), "variables were already declared");
+        self.compact_ids = compact;
+    }
+
+    /// Writes a complete header, including `$enddefinitions`.
+    pub fn header(&mut self, h: &Header) -> io::Result<()> {
+        if let Some(ref s) = h.date {
+            self.date(s)?;
+        }
+        if let Some(ref s) = h.version {
+            self.version(s)?;
+        }
+        if let Some(ref s) = h.comment {
+            self.comment(s)?;
+        }
+        if let Some(ts) = h.timescale {
+            self.timescale(ts)?;
+        }
+        self.hierarchy(h.hierarchy())?;
+        self.enddefinitions()
+    }
+
+    /// Writes a `$comment` command.
+    pub fn comment(&mut self, v: &str) -> io::Result<()> {
+        writeln!(self.writer, "$comment\n    {}\n$end", v)
+    }
+
+    /// Writes a `$date` command.
+    pub fn date(&mut self, v: &str) -> io::Result<()> {
+        writeln!(self.writer, "$date\n    {}\n$end", v)
+    }
+
+    /// Writes a `$version` command.
+    pub fn version(&mut self, v: &str) -> io::Result<()> {
+        writeln!(self.writer, "$version\n    {}\n$end", v)
+    }
+
+    /// Writes a `$timescale` command.
+    pub fn timescale(&mut self, ts: Timescale) -> io::Result<()> {
+        writeln!(self.writer, "$timescale {} $end", ts)
+    }
+
+    /// Writes a `$scope` command.
+    pub fn scope_def(&mut self, kind: ScopeKind, name: &str) -> io::Result<()> {
+        self.scope_depth += 1;
+        write!(self.writer, "$scope {} ", kind)?;
+        write_name(&mut self.writer, name)?;
+        writeln!(self.writer, " $end")
+    }
+
+    /// Writes an `$upscope` command.
+    pub fn upscope(&mut self) -> io::Result<()> {
+        debug_assert!(
+            self.scope_depth > 0,
+            "Generating invalid VCD: upscope without a matching scope"
+        );
+        self.scope_depth -= 1;
+        writeln!(self.writer, "$upscope $end")
+    }
+
+    /// Writes a scope of `h` with everything declared inside it.
+    pub fn scope(&mut self, h: &Hierarchy, s: &Scope) -> io::Result<()> {
+        self.scope_def(s.kind, h.name(s.name))?;
+        for v in &s.vars {
+            self.var(h, v)?;
+        }
+        for child in &s.scopes {
+            self.scope(h, child)?;
+        }
+        self.upscope()
+    }
+
+    /// Writes all scopes and variables of a hierarchy.
+    pub fn hierarchy(&mut self, h: &Hierarchy) -> io::Result<()> {
+        for v in &h.vars {
+            self.var(h, v)?;
+        }
+        for s in &h.scopes {
+            self.scope(h, s)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a `$var` command for a variable of `h`. Variables sharing a
+    /// signal share its code.
+    pub fn var(&mut self, h: &Hierarchy, v: &Var) -> io::Result<()> {
+        let kind = if v.kind.is_real() {
+            ChangeKind::Real
+        } else if v.kind == VarKind::String {
+            ChangeKind::String
+        } else if v.width == 1 {
+            ChangeKind::Scalar
+        } else {
+            ChangeKind::Vector
+        };
+        let compact = self.compact_ids;
+        let next_id = &mut self.next_id;
+        let (code, _) = self.codes.entry(v.signal).or_insert_with(|| {
+            let code = if compact {
+                *next_id += 1;
+                SignalId(*next_id - 1).to_code()
+            } else {
+                v.signal.to_code()
+            };
+            (code, kind)
+        });
+        write!(self.writer, "$var {} {} {} ", v.kind, v.width, code)?;
+        write_name(&mut self.writer, h.name(v.name))?;
+        match v.index {
+            Some(idx) => writeln!(self.writer, " {} $end", idx),
+            None => writeln!(self.writer, " $end"),
+        }
+    }
+
+    /// Writes a `$enddefinitions` command to end the header.
+    pub fn enddefinitions(&mut self) -> io::Result<()> {
+        debug_assert!(
+            self.scope_depth == 0,
+            "Generating invalid VCD: {} scopes must be closed with $upscope before $enddefinitions",
+            self.scope_depth
+        );
+        writeln!(self.writer, "$enddefinitions $end")
+    }
+
+    /// Writes a `#xxx` timestamp.
+    pub fn timestamp(&mut self, ts: u64) -> io::Result<()> {
+        writeln!(self.writer, "#{}", ts)
+    }
+
+    /// Writes a change of a declared signal, in the raw value encoding
+    /// used by [`Signal`](crate::Signal).
+    pub fn change(&mut self, signal: SignalId, value: &[u8]) -> io::Result<()> {
+        let (code, kind) = self.codes.get(&signal).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "change of undeclared signal")
+        })?;
+        let w = &mut self.writer;
+        match kind {
+            ChangeKind::Scalar if value.len() == 1 => {
+                w.write_all(value)?;
+                writeln!(w, "{}", code)
+            }
+            ChangeKind::Scalar | ChangeKind::Vector => {
+                w.write_all(b"b")?;
+                w.write_all(value)?;
+                writeln!(w, " {}", code)
+            }
+            ChangeKind::Real => {
+                w.write_all(b"r")?;
+                w.write_all(value)?;
+                writeln!(w, " {}", code)
+            }
+            ChangeKind::String => {
+                w.write_all(b"s")?;
+                w.write_all(value)?;
+                writeln!(w, " {}", code)
+            }
+        }
+    }
+
+    /// Writes the beginning of a simulation command.
+    pub fn begin(&mut self, c: SimulationCommand) -> io::Result<()> {
+        writeln!(self.writer, "${}", c)
+    }
+
+    /// Writes an `$end` to end a simulation command.
+    pub fn end(&mut self) -> io::Result<()> {
+        writeln!(self.writer, "$end")
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Options for [`write_waveform`].
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// Timescale of the output. Times are converted from the timescale of
+    /// the waveform, rounding to the nearest tick.
+    pub timescale: Option<Timescale>,
+    /// See [`VcdWriter::set_compact_ids`].
+    pub compact_ids: bool,
+    /// The `$date` of the output; by default that of the waveform.
+    pub date: Option<String>,
+    pub version: Option<String>,
+}
+
+/// Converts times from the timescale of a waveform to the output
+/// timescale, rounding to the nearest tick.
+pub(crate) fn rescaler(
+    source: Option<Timescale>,
+    target: Option<Timescale>,
+) -> impl Fn(u64) -> u64 {
+    let rescale = match (source, target) {
+        (Some(from), Some(to)) if from != to => Some((from.femtoseconds(), to.femtoseconds())),
+        _ => None,
+    };
+    move |time: u64| match rescale {
+        Some((from, to)) => ((time as u128 * from + to / 2) / to) as u64,
+        None => time,
+    }
+}
+
+/// One step of [`replay`].
+pub(crate) enum Step<'a> {
+    /// The following steps happen at this time, which never decreases.
+    Time(u64),
+    Change(SignalId, &'a [u8]),
+    /// A `$dumpoff` or `$dumpon` at a blackout bound.
+    Dump(SimulationCommand),
+}
+
+/// Load all signals of `wave` and pass their changes, merged in time
+/// order, to `step`, together with the bounds of its blackouts. Times are
+/// converted with `rescale`.
+pub(crate) fn replay<F, R, S>(wave: &mut F, rescale: R, mut step: S) -> io::Result<()>
+where
+    F: Waveform + ?Sized,
+    R: Fn(u64) -> u64,
+    S: FnMut(Step<'_>) -> io::Result<()>,
+{
+    let ids = wave.hierarchy().signal_ids();
+    let signals = wave.load_signals(&ids)?;
+    let blackouts = wave.blackouts()?.map_times(&rescale);
+    let mut edges = blackouts
+        .ranges()
+        .iter()
+        .flat_map(|r| {
+            let on = (r.end != u64::MAX).then_some((r.end, SimulationCommand::Dumpon));
+            [Some((r.start, SimulationCommand::Dumpoff)), on]
+        })
+        .flatten()
+        .peekable();
+
+    let mut heap: BinaryHeap<Reverse<(u64, usize, usize)>> = signals
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| !s.is_empty())
+        .map(|(i, s)| Reverse((s.time(0), i, 0)))
+        .collect();
+    let mut current = None;
+    let mut time = |step: &mut S, t: u64| {
+        if current != Some(t) {
+            current = Some(t);
+            return step(Step::Time(t));
+        }
+        Ok(())
+    };
+    while let Some(Reverse((t, i, pos))) = heap.pop() {
+        let out_time = rescale(t);
+        while let Some((edge, command)) = edges.next_if(|&(edge, _)| edge <= out_time) {
+            time(&mut step, edge)?;
+            step(Step::Dump(command))?;
+        }
+        time(&mut step, out_time)?;
+        step(Step::Change(ids[i], signals[i].value(pos)))?;
+        if pos + 1 < signals[i].len() {
+            heap.push(Reverse((signals[i].time(pos + 1), i, pos + 1)));
+        }
+    }
+    for (edge, command) in edges {
+        time(&mut step, edge)?;
+        step(Step::Dump(command))?;
+    }
+    Ok(())
+}
+
+/// Serialize a complete waveform as VCD.
+///
+/// All signals are loaded at once and their changes are merged in time
+/// order. The [blackouts](Waveform::blackouts) of the waveform are written
+/// as `$dumpoff` and `$dumpon` at their bounds.
+pub fn write_waveform<W, F>(out: W, wave: &mut F, options: &WriteOptions) -> io::Result<()>
+where
+    W: Write,
+    F: Waveform + ?Sized,
+{
+    let source = wave.timescale();
+    let timescale = options.timescale.or(source);
+    let mut w = VcdWriter::new(io::BufWriter::new(out));
+    w.set_compact_ids(options.compact_ids);
+    if let Some(date) = options.date.as_deref().or(wave.date()) {
+        w.date(date)?;
+    }
+    if let Some(version) = &options.version {
+        w.version(version)?;
+    }
+    if let Some(ts) = timescale {
+        w.timescale(ts)?;
+    }
+    w.hierarchy(wave.hierarchy())?;
+    w.enddefinitions()?;
+
+    replay(
+        wave,
+        rescaler(source, options.timescale),
+        |step| match step {
+            Step::Time(t) => w.timestamp(t),
+            Step::Change(id, value) => w.change(id, value),
+            Step::Dump(command) => {
+                w.begin(command)?;
+                w.end()
+            }
+        },
+    )?;
+    w.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{SignalLoader, TimeUnit, VcdFile};
+
+    const INPUT: &[u8] = b"$timescale 1 ns $end
+$scope module top $end
+$var wire 4 xy data $end
+$var wire 1 # clk $end
+$var real 64 %% volts [3:0] $end
+$scope module sub $end
+$var wire 1 # clk_alias $end
+$upscope $end
+$upscope $end
+$enddefinitions $end
+#0
+b0000 xy
+0#
+r0.5 %%
+#5
+1#
+#10
+b1x01 xy
+0#
+r1.25 %%
+";
+
+    #[test]
+    fn round_trip() {
+        let mut vcd = VcdFile::from_bytes(INPUT.to_vec()).unwrap();
+        let mut out = Vec::new();
+        write_waveform(&mut out, &mut vcd, &WriteOptions::default()).unwrap();
+        let mut copy = VcdFile::from_bytes(out).unwrap();
+        assert_eq!(copy.hierarchy(), vcd.hierarchy());
+        assert_eq!(copy.timescale(), vcd.timescale());
+        let ids = vcd.hierarchy().signal_ids();
+        assert_eq!(
+            copy.load_signals(&ids).unwrap(),
+            vcd.load_signals(&ids).unwrap()
+        );
+    }
+
+    #[test]
+    fn compact_ids_and_rescale() {
+        let mut vcd = VcdFile::from_bytes(INPUT.to_vec()).unwrap();
+        let options = WriteOptions {
+            timescale: Some(Timescale::new(10, TimeUnit::NS)),
+            compact_ids: true,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        write_waveform(&mut out, &mut vcd, &options).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("$timescale 10 ns $end"));
+        assert!(text.contains("$var wire 4 ! data $end"));
+        assert!(text.contains("$var wire 1 \" clk_alias $end"));
+        assert!(text.contains("$var real 64 # volts [3:0] $end"));
+        // #5 rounds up into the same tick as #10.
+        assert!(text.contains("#1\n1\"\nb1x01 !\n0\"\nr1.25 #\n"));
+        assert!(!text.contains("xy"));
+    }
+
+    #[test]
+    fn blackouts() {
+        let input = b"$var wire 1 ! a $end $enddefinitions $end
+#0 1! #10 $dumpoff x! $end #25 $dumpon 1! $end #30 0! #40 $dumpoff $end #50 $dumpon $end
+#60 $dumpoff $end";
+        let mut vcd = VcdFile::from_bytes(input.to_vec()).unwrap();
+        let mut out = Vec::new();
+        write_waveform(&mut out, &mut vcd, &WriteOptions::default()).unwrap();
+        let text = String::from_utf8(out.clone()).unwrap();
+        assert!(text.contains("#10\n$dumpoff\n$end\nx!\n#25\n$dumpon\n$end\n1!\n"));
+        assert!(text.ends_with("#40\n$dumpoff\n$end\n#50\n$dumpon\n$end\n#60\n$dumpoff\n$end\n"));
+        let copy = VcdFile::from_bytes(out).unwrap();
+        assert_eq!(copy.blackouts().unwrap(), vcd.blackouts().unwrap());
+    }
+
+    #[test]
+    fn names_and_undeclared() {
+        let mut w = VcdWriter::new(Vec::new());
+        w.scope_def(ScopeKind::Module, "my top").unwrap();
+        w.upscope().unwrap();
+        assert!(w.change(SignalId(0), b"1").is_err());
+        assert_eq!(
+            String::from_utf8(w.into_inner()).unwrap(),
+            "$scope module my_top $end\n$upscope $end\n"
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! Read-only access to zip archives held in memory.
+//!
+//! Supports the stored and deflate methods, which is all that capture
+//! tools write. Zip64 archives and encryption are not supported.
+
+use std::io;
+
+use crate::inflate::{crc32, inflate};
+use crate::InvalidData;
+
+const EOCD_SIG: u32 = 0x0605_4b50;
+const CENTRAL_SIG: u32 = 0x0201_4b50;
+const LOCAL_SIG: u32 = 0x0403_4b50;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+const CORRUPT: InvalidData = InvalidData("corrupt zip archive");
+
+struct Entry {
+    name: String,
+    method: u16,
+    crc: u32,
+    compressed: usize,
+    size: usize,
+    header: usize,
+}
+
+/// The central directory of a zip archive.
+pub(crate) struct ZipArchive<'a> {
+    data: &'a [u8],
+    entries: Vec<Entry>,
+}
+
+fn u16_at(data: &[u8], pos: usize) -> Result<u16, InvalidData> {
+    let b = data.get(pos..pos + 2).ok_or(CORRUPT)?;
+    Ok(u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Result<u32, InvalidData> {
+    let b = data.get(pos..pos + 4).ok_or(CORRUPT)?;
+    Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+impl<'a> ZipArchive<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> io::Result<ZipArchive<'a>> {
+        // The end of central directory record is followed by a comment of
+        // at most 64 KiB.
+        let lowest = data.len().saturating_sub(22 + 0xffff);
+        let eocd = (lowest..=data.len().saturating_sub(22))
+            .rev()
+            .find(|&p| u32_at(data, p).ok() == Some(EOCD_SIG))
+            .ok_or(InvalidData("not a zip archive"))?;
+        let count = u16_at(data, eocd + 10)? as usize;
+        let mut pos = u32_at(data, eocd + 16)? as usize;
+
+        let mut entries = Vec::with_capacity(count);
+        for _ in 0..count {
+            if u32_at(data, pos)? != CENTRAL_SIG {
+                return Err(CORRUPT.into());
+            }
+            let name_len = u16_at(data, pos + 28)? as usize;
+            let extra_len = u16_at(data, pos + 30)? as usize;
+            let comment_len = u16_at(data, pos + 32)? as usize;
+            let name = data.get(pos + 46..pos + 46 + name_len).ok_or(CORRUPT)?;
+            entries.push(Entry {
+                name: String::from_utf8_lossy(name).into_owned(),
+                method: u16_at(data, pos + 10)?,
+                crc: u32_at(data, pos + 16)?,
+                compressed: u32_at(data, pos + 20)? as usize,
+                size: u32_at(data, pos + 24)? as usize,
+                header: u32_at(data, pos + 42)? as usize,
+            });
+            pos += 46 + name_len + extra_len + comment_len;
+        }
+        Ok(ZipArchive { data, entries })
+    }
+
+    /// Names of all entries in archive order.
+    pub(crate) fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|e| e.name.as_str())
+    }
+
+    /// The decompressed contents of an entry, if it exists.
+    pub(crate) fn read(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        let Some(entry) = self.entries.iter().find(|e| e.name == name) else {
+            return Ok(None);
+        };
+        let data = self.data;
+        if u32_at(data, entry.header)? != LOCAL_SIG {
+            return Err(CORRUPT.into());
+        }
+        let start = entry.header
+            + 30
+            + u16_at(data, entry.header + 26)? as usize
+            + u16_at(data, entry.header + 28)? as usize;
+        let raw = data.get(start..start + entry.compressed).ok_or(CORRUPT)?;
+        let out = match entry.method {
+            STORED => raw.to_vec(),
+            DEFLATED => inflate(raw, entry.size)?,
+            _ => return Err(InvalidData("unsupported zip compression method").into()),
+        };
+        if out.len() != entry.size || crc32(&out) != entry.crc {
+            return Err(InvalidData("zip entry checksum mismatch").into());
+        }
+        Ok(Some(out))
+    }
+}
+
+/// Build a zip archive of stored entries, for tests.
+#[cfg(test)]
+pub(crate) fn stored_archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    for (name, data) in files {
+        let header = out.len() as u32;
+        let crc = crc32(data);
+        let fields = |v: &mut Vec<u8>| {
+            v.extend_from_slice(&[0; 4]); // flags, method
+            v.extend_from_slice(&[0; 4]); // time, date
+            v.extend_from_slice(&crc.to_le_bytes());
+            v.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            v.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            v.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            v.extend_from_slice(&[0; 2]); // extra
+        };
+        out.extend_from_slice(&LOCAL_SIG.to_le_bytes());
+        out.extend_from_slice(&20u16.to_le_bytes());
+        fields(&mut out);
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(data);
+
+        central.extend_from_slice(&CENTRAL_SIG.to_le_bytes());
+        central.extend_from_slice(&[20, 0, 20, 0]);
+        fields(&mut central);
+        central.extend_from_slice(&[0; 10]); // comment, disk, attributes
+        central.extend_from_slice(&header.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+    }
+    let offset = out.len() as u32;
+    out.extend_from_slice(&central);
+    out.extend_from_slice(&EOCD_SIG.to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    out.extend_from_slice(&offset.to_le_bytes());
+    out.extend_from_slice(&[0; 2]);
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn read_stored_entries() {
+        let data = stored_archive(&[("version", b"2"), ("metadata", b"[global]\n")]);
+        let zip = ZipArchive::new(&data).unwrap();
+        assert_eq!(zip.names().collect::<Vec<_>>(), ["version", "metadata"]);
+        assert_eq!(zip.read("version").unwrap().unwrap(), b"2");
+        assert_eq!(zip.read("metadata").unwrap().unwrap(), b"[global]\n");
+        assert!(zip.read("missing").unwrap().is_none());
+        assert!(ZipArchive::new(b"not a zip").is_err());
+
+        // An entry whose contents are not of its declared size.
+        let mut bad = data.clone();
+        let central = bad.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+        bad[central + 24..central + 28].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(ZipArchive::new(&bad).unwrap().read("version").is_err());
+    }
+}
@@ -0,0 +1,52 @@
+/* Writes fstapi.fst and fastlz.fst with GTKWave's fstapi, for the reader
+ * tests:
+ *
+ *     cc -Icsrc fstapi.c csrc/fstapi.c csrc/lz4.c csrc/fastlz.c -lz -o gen
+ *
+ * A clock, an 8-bit counter with an alias in a nested scope, and a real,
+ * in nanoseconds, with dumping off from 40 to 60. fastlz.fst compresses
+ * its value chains with FastLZ instead of zlib.
+ */
+#include <string.h>
+#include "fstapi.h"
+
+static void gen(const char *path, enum fstWriterPackType pack) {
+    void *ctx = fstWriterCreate(path, 1);
+    fstWriterSetPackType(ctx, pack);
+    fstWriterSetTimescale(ctx, -9);
+    fstWriterSetDate(ctx, "Thu Oct 15 12:00:00 2026");
+    fstWriterSetVersion(ctx, "fstapi");
+    fstWriterSetScope(ctx, FST_ST_VCD_MODULE, "top", NULL);
+    fstHandle clk = fstWriterCreateVar(ctx, FST_VT_VCD_WIRE, FST_VD_IMPLICIT, 1, "clk", 0);
+    fstHandle cnt = fstWriterCreateVar(ctx, FST_VT_VCD_REG, FST_VD_IMPLICIT, 8, "cnt [7:0]", 0);
+    fstHandle v = fstWriterCreateVar(ctx, FST_VT_VCD_REAL, FST_VD_IMPLICIT, 8, "v", 0);
+    fstWriterSetScope(ctx, FST_ST_VCD_MODULE, "sub", NULL);
+    fstWriterCreateVar(ctx, FST_VT_VCD_REG, FST_VD_IMPLICIT, 8, "count [7:0]", cnt);
+    fstWriterSetUpscope(ctx);
+    fstWriterSetUpscope(ctx);
+    for (int t = 0; t <= 100; t += 5) {
+        fstWriterEmitTimeChange(ctx, t);
+        if (t == 40)
+            fstWriterEmitDumpActive(ctx, 0);
+        if (t == 60)
+            fstWriterEmitDumpActive(ctx, 1);
+        fstWriterEmitValueChange(ctx, clk, (t / 5) % 2 ? "1" : "0", 1);
+        if (t % 10 == 0) {
+            char bits[8];
+            for (int i = 0; i < 8; i++)
+                bits[i] = ((t / 10) >> (7 - i) & 1) ? '1' : '0';
+            if (t == 30)
+                memcpy(bits, "0000xxzz", 8);
+            fstWriterEmitValueChange(ctx, cnt, bits, 8);
+            double d = t / 4.0;
+            fstWriterEmitValueChange(ctx, v, &d, 8);
+        }
+    }
+    fstWriterClose(ctx);
+}
+
+int main(void) {
+    gen("fstapi.fst", FST_WR_PT_ZLIB);
+    gen("fastlz.fst", FST_WR_PT_FASTLZ);
+    return 0;
+}